@@ -0,0 +1,349 @@
+//! Derive macro generating papermake schemas from Rust structs
+//!
+//! Use it through the `derive` feature of the `papermake` crate:
+//!
+//! ```rust,ignore
+//! use papermake::PapermakeData;
+//! use serde::Serialize;
+//!
+//! #[derive(Serialize, PapermakeData)]
+//! struct Invoice {
+//!     /// Name of the billed customer
+//!     customer: String,
+//!     #[papermake(label = "Total amount")]
+//!     total: f64,
+//!     #[papermake(date)]
+//!     due_date: String,
+//!     #[papermake(barcode = "qr")]
+//!     payment_link: String,
+//!     #[papermake(section)]
+//!     show_terms: bool,
+//!     #[papermake(widget = "textarea", placeholder = "Anything else?", group = "Extras")]
+//!     notes: Option<String>,
+//!     #[papermake(options = ["EUR", "USD"], order = 1)]
+//!     currency: String,
+//!     items: Vec<LineItem>,
+//! }
+//! ```
+//!
+//! Field keys follow serde's `rename`, `rename_all` and `skip` attributes so
+//! the generated schema always matches the serialized data. Doc comments
+//! become field descriptions.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Attribute, Data, DeriveInput, Expr, Fields, Lit, LitStr, Meta};
+
+/// Derive `papermake::data::PapermakeData` and `papermake::data::SchemaType`
+#[proc_macro_derive(PapermakeData, attributes(papermake))]
+pub fn derive_papermake_data(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    name,
+                    "PapermakeData can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                name,
+                "PapermakeData can only be derived for structs",
+            ))
+        }
+    };
+
+    let container = ContainerAttrs::parse(&input.attrs)?;
+
+    let mut schema_fields = Vec::new();
+    for field in fields {
+        let attrs = FieldAttrs::parse(&field.attrs)?;
+        if attrs.skip {
+            continue;
+        }
+
+        let ident = field.ident.as_ref().expect("named field");
+        let key = match attrs.rename {
+            Some(rename) => rename,
+            None => container.rename_field(&ident.to_string()),
+        };
+
+        let ty = &field.ty;
+        let field_type = if let Some(kind) = &attrs.barcode {
+            quote! { ::papermake::FieldType::Barcode(::papermake::BarcodeKind::#kind) }
+        } else if attrs.date {
+            quote! { ::papermake::FieldType::Date }
+        } else if attrs.section {
+            quote! { ::papermake::FieldType::Section }
+        } else {
+            quote! { <#ty as ::papermake::data::SchemaType>::field_type() }
+        };
+        let required = if attrs.default {
+            quote! { false }
+        } else {
+            quote! { <#ty as ::papermake::data::SchemaType>::REQUIRED }
+        };
+        let label = optional_string(attrs.label);
+        let description = optional_string(attrs.description.or(attrs.doc));
+        let placeholder = optional_string(attrs.placeholder);
+        let group = optional_string(attrs.group);
+        let order = match attrs.order {
+            Some(order) => quote! { Some(#order) },
+            None => quote! { None },
+        };
+        let widget = match (attrs.widget, attrs.options) {
+            (Some(widget), None) if widget != "select" => {
+                let variant = syn::Ident::new(&widget_variant(&widget), proc_macro2::Span::call_site());
+                quote! { Some(::papermake::Widget::#variant) }
+            }
+            (Some(_), None) => {
+                return Err(syn::Error::new_spanned(ident, "widget = \"select\" needs options = [...]"));
+            }
+            (Some(widget), Some(_)) if widget != "select" => {
+                return Err(syn::Error::new_spanned(ident, "options = [...] only applies to select widgets"));
+            }
+            (_, Some(options)) => quote! {
+                Some(::papermake::Widget::Select { options: vec![#(#options.to_string()),*] })
+            },
+            (None, None) => quote! { None },
+        };
+
+        schema_fields.push(quote! {
+            ::papermake::SchemaField {
+                key: #key.to_string(),
+                label: #label,
+                field_type: #field_type,
+                required: #required,
+                description: #description,
+                default: None,
+                placeholder: #placeholder,
+                widget: #widget,
+                order: #order,
+                group: #group,
+            }
+        });
+    }
+
+    Ok(quote! {
+        impl #impl_generics ::papermake::data::PapermakeData for #name #ty_generics #where_clause {
+            fn schema() -> ::papermake::Schema {
+                ::papermake::Schema {
+                    fields: vec![#(#schema_fields),*],
+                }
+            }
+        }
+
+        impl #impl_generics ::papermake::data::SchemaType for #name #ty_generics #where_clause {
+            fn field_type() -> ::papermake::FieldType {
+                ::papermake::FieldType::Object(Box::new(
+                    <Self as ::papermake::data::PapermakeData>::schema(),
+                ))
+            }
+        }
+    })
+}
+
+/// `Widget` variant of a `widget = "..."` value, already checked by `FieldAttrs::parse`
+fn widget_variant(widget: &str) -> String {
+    widget.split('_').map(capitalize).collect()
+}
+
+fn optional_string(value: Option<String>) -> TokenStream2 {
+    match value {
+        Some(value) => quote! { Some(#value.to_string()) },
+        None => quote! { None },
+    }
+}
+
+/// Struct-level attributes affecting field keys
+#[derive(Default)]
+struct ContainerAttrs {
+    rename_all: Option<String>,
+}
+
+impl ContainerAttrs {
+    fn parse(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut result = Self::default();
+        for attr in attrs.iter().filter(|a| a.path().is_ident("serde")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename_all") {
+                    let value: LitStr = meta.value()?.parse()?;
+                    result.rename_all = Some(value.value());
+                    Ok(())
+                } else {
+                    skip_meta(&meta)
+                }
+            })?;
+        }
+        Ok(result)
+    }
+
+    fn rename_field(&self, field: &str) -> String {
+        let field = field.strip_prefix("r#").unwrap_or(field);
+        let words: Vec<&str> = field.split('_').filter(|w| !w.is_empty()).collect();
+
+        match self.rename_all.as_deref() {
+            Some("lowercase") => field.to_lowercase(),
+            Some("UPPERCASE") => field.to_uppercase(),
+            Some("camelCase") => words
+                .iter()
+                .enumerate()
+                .map(|(i, w)| if i == 0 { w.to_string() } else { capitalize(w) })
+                .collect(),
+            Some("PascalCase") => words.iter().map(|w| capitalize(w)).collect(),
+            Some("SCREAMING_SNAKE_CASE") => field.to_uppercase(),
+            Some("kebab-case") => words.join("-"),
+            Some("SCREAMING-KEBAB-CASE") => words.join("-").to_uppercase(),
+            _ => field.to_string(),
+        }
+    }
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Field-level attributes from `#[papermake(...)]`, `#[serde(...)]` and doc comments
+#[derive(Default)]
+struct FieldAttrs {
+    rename: Option<String>,
+    label: Option<String>,
+    description: Option<String>,
+    doc: Option<String>,
+    skip: bool,
+    default: bool,
+    date: bool,
+    /// Boolean toggling a document section
+    section: bool,
+    /// `BarcodeKind` variant of a barcode field
+    barcode: Option<syn::Ident>,
+    placeholder: Option<String>,
+    /// Snake-case `Widget` variant, e.g. `date_picker`
+    widget: Option<String>,
+    /// Options of a select widget
+    options: Option<Vec<String>>,
+    order: Option<i32>,
+    group: Option<String>,
+}
+
+impl FieldAttrs {
+    fn parse(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut result = Self::default();
+        let mut doc_lines = Vec::new();
+
+        for attr in attrs {
+            if attr.path().is_ident("papermake") {
+                attr.parse_nested_meta(|meta| {
+                    if meta.path.is_ident("rename") {
+                        result.rename = Some(meta.value()?.parse::<LitStr>()?.value());
+                    } else if meta.path.is_ident("label") {
+                        result.label = Some(meta.value()?.parse::<LitStr>()?.value());
+                    } else if meta.path.is_ident("description") {
+                        result.description = Some(meta.value()?.parse::<LitStr>()?.value());
+                    } else if meta.path.is_ident("skip") {
+                        result.skip = true;
+                    } else if meta.path.is_ident("date") {
+                        result.date = true;
+                    } else if meta.path.is_ident("section") {
+                        result.section = true;
+                    } else if meta.path.is_ident("barcode") {
+                        let kind = meta.value()?.parse::<LitStr>()?;
+                        let variant = match kind.value().as_str() {
+                            "qr" => "Qr",
+                            "code128" => "Code128",
+                            "ean13" => "Ean13",
+                            _ => return Err(syn::Error::new_spanned(kind, "expected \"qr\", \"code128\" or \"ean13\"")),
+                        };
+                        result.barcode = Some(syn::Ident::new(variant, kind.span()));
+                    } else if meta.path.is_ident("placeholder") {
+                        result.placeholder = Some(meta.value()?.parse::<LitStr>()?.value());
+                    } else if meta.path.is_ident("widget") {
+                        let widget = meta.value()?.parse::<LitStr>()?;
+                        const WIDGETS: [&str; 7] =
+                            ["text", "textarea", "select", "date_picker", "checkbox", "number", "hidden"];
+                        if !WIDGETS.contains(&widget.value().as_str()) {
+                            return Err(syn::Error::new_spanned(widget, format!("expected one of {}", WIDGETS.join(", "))));
+                        }
+                        result.widget = Some(widget.value());
+                    } else if meta.path.is_ident("options") {
+                        let array = meta.value()?.parse::<syn::ExprArray>()?;
+                        let options = array
+                            .elems
+                            .iter()
+                            .map(|elem| match elem {
+                                Expr::Lit(syn::ExprLit { lit: Lit::Str(s), .. }) => Ok(s.value()),
+                                other => Err(syn::Error::new_spanned(other, "expected a string")),
+                            })
+                            .collect::<syn::Result<_>>()?;
+                        result.options = Some(options);
+                    } else if meta.path.is_ident("order") {
+                        result.order = Some(meta.value()?.parse::<syn::LitInt>()?.base10_parse()?);
+                    } else if meta.path.is_ident("group") {
+                        result.group = Some(meta.value()?.parse::<LitStr>()?.value());
+                    } else {
+                        return Err(meta.error("unsupported papermake attribute"));
+                    }
+                    Ok(())
+                })?;
+            } else if attr.path().is_ident("serde") {
+                attr.parse_nested_meta(|meta| {
+                    if meta.path.is_ident("rename") && meta.input.peek(syn::Token![=]) {
+                        result.rename = Some(meta.value()?.parse::<LitStr>()?.value());
+                        Ok(())
+                    } else if meta.path.is_ident("skip") || meta.path.is_ident("skip_serializing") {
+                        result.skip = true;
+                        Ok(())
+                    } else if meta.path.is_ident("default") {
+                        result.default = true;
+                        skip_meta(&meta)
+                    } else {
+                        skip_meta(&meta)
+                    }
+                })?;
+            } else if attr.path().is_ident("doc") {
+                if let Meta::NameValue(nv) = &attr.meta {
+                    if let Expr::Lit(expr) = &nv.value {
+                        if let Lit::Str(s) = &expr.lit {
+                            let line = s.value().trim().to_string();
+                            if !line.is_empty() {
+                                doc_lines.push(line);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        if !doc_lines.is_empty() {
+            result.doc = Some(doc_lines.join(" "));
+        }
+        Ok(result)
+    }
+}
+
+/// Consume the value of an attribute we don't interpret
+fn skip_meta(meta: &syn::meta::ParseNestedMeta) -> syn::Result<()> {
+    if meta.input.peek(syn::Token![=]) {
+        meta.value()?.parse::<Expr>()?;
+    } else if meta.input.peek(syn::token::Paren) {
+        meta.parse_nested_meta(|nested| skip_meta(&nested))?;
+    }
+    Ok(())
+}
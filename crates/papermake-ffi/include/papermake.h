@@ -0,0 +1,65 @@
+#ifndef PAPERMAKE_H
+#define PAPERMAKE_H
+
+/* Generated by cbindgen from crates/papermake-ffi; do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Result of a render: a PDF on success, an error message otherwise
+ *
+ * Exactly one of `data` and `error` is non-null.
+ */
+typedef struct PapermakeBuffer {
+  /**
+   * PDF bytes, `len` long
+   */
+  uint8_t *data;
+  uintptr_t len;
+  /**
+   * NUL-terminated error message
+   */
+  char *error;
+} PapermakeBuffer;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Render a template with data to a PDF
+ *
+ * `template_json` is an object with `content` and optionally `id`, `name`
+ * and `schema`. `options_json` may be null for default options.
+ *
+ * # Safety
+ *
+ * Non-null pointers must point to NUL-terminated strings valid for the
+ * duration of the call.
+ */
+struct PapermakeBuffer papermake_render(const char *template_json,
+                                        const char *data_json,
+                                        const char *options_json);
+
+/**
+ * Release a buffer returned by `papermake_render`
+ *
+ * # Safety
+ *
+ * `buffer` must come from `papermake_render` and not have been freed.
+ */
+void papermake_buffer_free(struct PapermakeBuffer buffer);
+
+/**
+ * Library version as a static NUL-terminated string
+ */
+const char *papermake_version(void);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* PAPERMAKE_H */
@@ -0,0 +1,162 @@
+//! C ABI for embedding the papermake renderer
+//!
+//! All inputs are NUL-terminated UTF-8 JSON strings. Every
+//! [`PapermakeBuffer`] returned must be released with
+//! [`papermake_buffer_free`]. The header is generated into
+//! `include/papermake.h` on build.
+
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+
+use papermake::{render_pdf, RenderOptions, Schema, Template};
+use serde::Deserialize;
+
+/// Result of a render: a PDF on success, an error message otherwise
+///
+/// Exactly one of `data` and `error` is non-null.
+#[repr(C)]
+pub struct PapermakeBuffer {
+    /// PDF bytes, `len` long
+    pub data: *mut u8,
+    pub len: usize,
+    /// NUL-terminated error message
+    pub error: *mut c_char,
+}
+
+/// Template accepted by `papermake_render`; stored templates (as returned
+/// by the HTTP API) also deserialize into this
+#[derive(Deserialize)]
+struct TemplateSpec {
+    #[serde(default = "default_id")]
+    id: String,
+    #[serde(default)]
+    name: String,
+    content: String,
+    #[serde(default)]
+    schema: Schema,
+}
+
+fn default_id() -> String {
+    "ffi".to_string()
+}
+
+/// Subset of `RenderOptions` settable through the C ABI
+#[derive(Deserialize)]
+struct OptionsSpec {
+    paper_size: Option<String>,
+    compress: Option<bool>,
+    coerce_data: Option<bool>,
+}
+
+impl From<OptionsSpec> for RenderOptions {
+    fn from(spec: OptionsSpec) -> Self {
+        let defaults = RenderOptions::default();
+        RenderOptions {
+            paper_size: spec.paper_size.unwrap_or(defaults.paper_size),
+            compress: spec.compress.unwrap_or(defaults.compress),
+            coerce_data: spec.coerce_data.unwrap_or(defaults.coerce_data),
+            ..defaults
+        }
+    }
+}
+
+/// Render a template with data to a PDF
+///
+/// `template_json` is an object with `content` and optionally `id`, `name`
+/// and `schema`. `options_json` may be null for default options.
+///
+/// # Safety
+///
+/// Non-null pointers must point to NUL-terminated strings valid for the
+/// duration of the call.
+#[no_mangle]
+pub unsafe extern "C" fn papermake_render(
+    template_json: *const c_char,
+    data_json: *const c_char,
+    options_json: *const c_char,
+) -> PapermakeBuffer {
+    // Panics must not unwind across the C boundary
+    let result = catch_unwind(AssertUnwindSafe(|| unsafe {
+        render(template_json, data_json, options_json)
+    }))
+    .unwrap_or_else(|_| Err("Renderer panicked".to_string()));
+
+    match result {
+        Ok(pdf) => {
+            let pdf = pdf.into_boxed_slice();
+            let len = pdf.len();
+            PapermakeBuffer {
+                data: Box::into_raw(pdf) as *mut u8,
+                len,
+                error: ptr::null_mut(),
+            }
+        }
+        Err(message) => PapermakeBuffer {
+            data: ptr::null_mut(),
+            len: 0,
+            error: CString::new(message.replace('\0', ""))
+                .unwrap_or_default()
+                .into_raw(),
+        },
+    }
+}
+
+/// Release a buffer returned by `papermake_render`
+///
+/// # Safety
+///
+/// `buffer` must come from `papermake_render` and not have been freed.
+#[no_mangle]
+pub unsafe extern "C" fn papermake_buffer_free(buffer: PapermakeBuffer) {
+    if !buffer.data.is_null() {
+        drop(unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(buffer.data, buffer.len)) });
+    }
+    if !buffer.error.is_null() {
+        drop(unsafe { CString::from_raw(buffer.error) });
+    }
+}
+
+/// Library version as a static NUL-terminated string
+#[no_mangle]
+pub extern "C" fn papermake_version() -> *const c_char {
+    concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr() as *const c_char
+}
+
+unsafe fn render(
+    template_json: *const c_char,
+    data_json: *const c_char,
+    options_json: *const c_char,
+) -> Result<Vec<u8>, String> {
+    let spec: TemplateSpec = parse("template", unsafe { read_str(template_json) }?)?;
+    let data: serde_json::Value = parse("data", unsafe { read_str(data_json) }?)?;
+    let options: RenderOptions = if options_json.is_null() {
+        RenderOptions::default()
+    } else {
+        parse::<OptionsSpec>("options", unsafe { read_str(options_json) }?)?.into()
+    };
+
+    let template = Template::new(spec.id, spec.name, spec.content, spec.schema);
+    let result = render_pdf(&template, &data, Some(options)).map_err(|e| e.to_string())?;
+
+    match result.pdf {
+        Some(pdf) => Ok(pdf),
+        None => {
+            let messages: Vec<String> = result.errors.into_iter().map(|e| e.message).collect();
+            Err(format!("Compilation failed: {}", messages.join("; ")))
+        }
+    }
+}
+
+unsafe fn read_str<'a>(ptr: *const c_char) -> Result<&'a str, String> {
+    if ptr.is_null() {
+        return Err("Unexpected null argument".to_string());
+    }
+    unsafe { CStr::from_ptr(ptr) }
+        .to_str()
+        .map_err(|_| "Argument is not valid UTF-8".to_string())
+}
+
+fn parse<'a, T: Deserialize<'a>>(what: &str, json: &'a str) -> Result<T, String> {
+    serde_json::from_str(json).map_err(|e| format!("Invalid {} JSON: {}", what, e))
+}
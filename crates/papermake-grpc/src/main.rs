@@ -0,0 +1,45 @@
+//! gRPC server exposing template creation and (batch) rendering
+
+mod service;
+
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use papermake::storage::FileStorage;
+use papermake::WorldPool;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+
+use crate::service::PapermakeService;
+
+pub mod pb {
+    tonic::include_proto!("papermake.v1");
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    tracing_subscriber::registry()
+        .with(EnvFilter::new(
+            std::env::var("RUST_LOG").unwrap_or_else(|_| "papermake_grpc=info".into()),
+        ))
+        .with(tracing_subscriber::fmt::layer())
+        .try_init()?;
+
+    // Shares the storage layout with the HTTP server
+    let storage_path = std::env::var("PAPERMAKE_STORAGE_PATH").unwrap_or_else(|_| "./data".to_string());
+    let storage = Arc::new(FileStorage::new(PathBuf::from(storage_path)));
+    let service = PapermakeService::new(storage, Arc::new(WorldPool::new()));
+
+    let port = std::env::var("GRPC_PORT")
+        .ok()
+        .and_then(|s| s.parse::<u16>().ok())
+        .unwrap_or(50051);
+    let addr = SocketAddr::from(([0, 0, 0, 0], port));
+    tracing::info!("gRPC server listening on {}", addr);
+
+    tonic::transport::Server::builder()
+        .add_service(pb::papermake_server::PapermakeServer::new(service))
+        .serve(addr)
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,196 @@
+//! gRPC service implementation backed by papermake storage and a world pool
+
+use std::sync::Arc;
+
+use papermake::lifecycle::{publish_template, template_for_render};
+use papermake::render::{prepare_data, RenderOptions};
+use papermake::storage::Storage;
+use papermake::{resolve_shared, ErrorCode, PapermakeError, Template, TemplateId, TemplateVersion, WorldPool};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status};
+
+use crate::pb;
+
+/// Number of finished documents buffered for slow batch clients
+const BATCH_BUFFER: usize = 4;
+
+pub struct PapermakeService {
+    storage: Arc<dyn Storage>,
+    world_pool: Arc<WorldPool>,
+}
+
+impl PapermakeService {
+    pub fn new(storage: Arc<dyn Storage>, world_pool: Arc<WorldPool>) -> Self {
+        Self { storage, world_pool }
+    }
+
+    /// Load the requested revision and build render options for it
+    async fn prepare(
+        &self,
+        template_id: String,
+        draft: bool,
+        options: Option<pb::RenderOptions>,
+    ) -> Result<(Template, RenderOptions), Status> {
+        let version = if draft { TemplateVersion::Draft } else { TemplateVersion::Published };
+        let template = template_for_render(self.storage.as_ref(), &TemplateId(template_id), version)
+            .await
+            .map_err(|err| match err {
+                PapermakeError::InvalidInput(msg) => Status::failed_precondition(msg),
+                err => internal(err),
+            })?;
+
+        let mut options = options.map(render_options).unwrap_or_default();
+        options.shared_sources = resolve_shared(self.storage.as_ref(), &template)
+            .await
+            .map_err(|err| Status::invalid_argument(format!("Failed to resolve imports: {}", err)))?;
+        Ok((template, options))
+    }
+}
+
+#[tonic::async_trait]
+impl pb::papermake_server::Papermake for PapermakeService {
+    async fn create_template(
+        &self,
+        request: Request<pb::CreateTemplateRequest>,
+    ) -> Result<Response<pb::Template>, Status> {
+        let request = request.into_inner();
+        let schema = serde_json::from_str(&request.schema_json)
+            .map_err(|e| Status::invalid_argument(format!("Invalid schema: {}", e)))?;
+
+        let mut template = Template::new(request.id, request.name, request.content, schema);
+        template.description = request.description;
+        self.storage.save_template(&template).await.map_err(internal)?;
+
+        if request.publish {
+            template = publish_template(self.storage.as_ref(), &template.id)
+                .await
+                .map_err(internal)?;
+        }
+        self.world_pool.prune(&template).map_err(internal)?;
+        Ok(Response::new(template_message(template)))
+    }
+
+    async fn render_template(
+        &self,
+        request: Request<pb::RenderTemplateRequest>,
+    ) -> Result<Response<pb::RenderResponse>, Status> {
+        let request = request.into_inner();
+        let (template, mut options) = self.prepare(request.template_id, request.draft, request.options).await?;
+        let data = parse_data(&request.data_json)?;
+        let data = prepare_data(&template, &data, &options)
+            .map_err(|err| Status::invalid_argument(format!("Invalid data: {}", err)))?;
+        options.data_prepared = true;
+
+        let result = self
+            .world_pool
+            .render_async(&template, &data, Some(options))
+            .await
+            .map_err(internal)?;
+
+        Ok(Response::new(pb::RenderResponse {
+            pdf: result.pdf.unwrap_or_default(),
+            errors: result.errors.into_iter().map(error_message).collect(),
+        }))
+    }
+
+    type BatchRenderStream = ReceiverStream<Result<pb::BatchRenderResponse, Status>>;
+
+    async fn batch_render(
+        &self,
+        request: Request<pb::BatchRenderRequest>,
+    ) -> Result<Response<Self::BatchRenderStream>, Status> {
+        let request = request.into_inner();
+        let (template, options) = self.prepare(request.template_id, request.draft, request.options).await?;
+
+        // Reject the whole batch up front if any record is malformed
+        let records = request
+            .records_json
+            .iter()
+            .enumerate()
+            .map(|(index, json)| {
+                let data = parse_data(json)?;
+                prepare_data(&template, &data, &options).map_err(|err| {
+                    Status::invalid_argument(format!("Invalid data in record {}: {}", index, err))
+                })
+            })
+            .collect::<Result<Vec<_>, Status>>()?;
+        let options = RenderOptions { data_prepared: true, ..options };
+
+        let (tx, rx) = mpsc::channel(BATCH_BUFFER);
+        let world_pool = self.world_pool.clone();
+        tokio::spawn(async move {
+            for (index, data) in records.into_iter().enumerate() {
+                let response = world_pool
+                    .render_async(&template, &data, Some(options.clone()))
+                    .await
+                    .map(|result| pb::BatchRenderResponse {
+                        index: index as u32,
+                        pdf: result.pdf.unwrap_or_default(),
+                        errors: result.errors.into_iter().map(error_message).collect(),
+                    })
+                    .map_err(internal);
+
+                // Stop rendering once the client has gone away
+                if tx.send(response).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+}
+
+fn render_options(options: pb::RenderOptions) -> RenderOptions {
+    let defaults = RenderOptions::default();
+    RenderOptions {
+        paper_size: options.paper_size.unwrap_or(defaults.paper_size),
+        compress: options.compress.unwrap_or(defaults.compress),
+        coerce_data: options.coerce_data.unwrap_or(defaults.coerce_data),
+        ..defaults
+    }
+}
+
+fn parse_data(json: &str) -> Result<serde_json::Value, Status> {
+    serde_json::from_str(json).map_err(|e| Status::invalid_argument(format!("Invalid data JSON: {}", e)))
+}
+
+fn template_message(template: Template) -> pb::Template {
+    pb::Template {
+        id: template.id.0,
+        name: template.name,
+        content: template.content,
+        schema_json: serde_json::to_string(&template.schema).unwrap_or_default(),
+        description: template.description,
+        status: serde_json::to_value(template.status)
+            .ok()
+            .and_then(|v| v.as_str().map(str::to_string))
+            .unwrap_or_default(),
+        created_at: template.created_at.to_string(),
+        updated_at: template.updated_at.to_string(),
+    }
+}
+
+fn error_message(error: papermake::render::RenderError) -> pb::RenderError {
+    pb::RenderError {
+        message: error.message,
+        start: error.start as u64,
+        end: error.end as u64,
+    }
+}
+
+/// Status for an error, by its code
+fn internal(err: PapermakeError) -> Status {
+    let message = err.to_string();
+    match err.code() {
+        ErrorCode::TemplateNotFound | ErrorCode::NotFound => Status::not_found(message),
+        ErrorCode::SchemaValidation | ErrorCode::InvalidInput | ErrorCode::TemplateInvalid | ErrorCode::CompileError => {
+            Status::invalid_argument(message)
+        }
+        ErrorCode::Timeout => Status::deadline_exceeded(message),
+        ErrorCode::Conflict => Status::aborted(message),
+        ErrorCode::TooLarge => Status::resource_exhausted(message),
+        ErrorCode::RenderError | ErrorCode::StorageIo => Status::internal(message),
+    }
+}
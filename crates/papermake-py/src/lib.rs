@@ -0,0 +1,179 @@
+//! Python bindings for the papermake render pipeline
+//!
+//! Build with `maturin develop` from this directory. Data and schemas are
+//! passed as plain Python dicts and lists (anything JSON-like). Compilation
+//! runs with the GIL released, so renders can be spread across threads.
+
+use papermake::{PapermakeError, RenderOptions};
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use pythonize::{depythonize, pythonize};
+
+create_exception!(papermake, RenderError, PyException, "Template failed to compile");
+create_exception!(papermake, ValidationError, PyValueError, "Data does not match the schema");
+
+fn to_py_err(err: PapermakeError) -> PyErr {
+    match err {
+        PapermakeError::SchemaValidation(msg) => ValidationError::new_err(msg),
+        PapermakeError::InvalidInput(msg) => PyValueError::new_err(msg),
+        other => RenderError::new_err(other.to_string()),
+    }
+}
+
+fn to_json(obj: &Bound<'_, PyAny>, what: &str) -> PyResult<serde_json::Value> {
+    depythonize(obj).map_err(|e| PyValueError::new_err(format!("Invalid {}: {}", what, e)))
+}
+
+/// Data schema of a template
+#[pyclass(module = "papermake")]
+#[derive(Clone)]
+struct Schema {
+    inner: papermake::Schema,
+}
+
+#[pymethods]
+impl Schema {
+    /// Create a schema from its dict form, `{"fields": [...]}`
+    #[new]
+    #[pyo3(signature = (schema = None))]
+    fn new(schema: Option<&Bound<'_, PyAny>>) -> PyResult<Self> {
+        let inner = match schema {
+            Some(schema) => serde_json::from_value(to_json(schema, "schema")?)
+                .map_err(|e| PyValueError::new_err(format!("Invalid schema: {}", e)))?,
+            None => papermake::Schema::default(),
+        };
+        Ok(Self { inner })
+    }
+
+    /// Raise `ValidationError` if `data` does not match the schema
+    fn validate(&self, data: &Bound<'_, PyAny>) -> PyResult<()> {
+        self.inner.validate(&to_json(data, "data")?).map_err(to_py_err)
+    }
+
+    /// The schema as a dict
+    fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        Ok(pythonize(py, &self.inner)?)
+    }
+
+    fn __repr__(&self) -> String {
+        let keys: Vec<&str> = self.inner.fields.iter().map(|f| f.key.as_str()).collect();
+        format!("Schema(fields={:?})", keys)
+    }
+}
+
+/// A Typst template with its schema
+#[pyclass(module = "papermake")]
+#[derive(Clone)]
+struct Template {
+    inner: papermake::Template,
+}
+
+#[pymethods]
+impl Template {
+    #[new]
+    #[pyo3(signature = (id, content, schema = None, name = None))]
+    fn new(id: String, content: String, schema: Option<Schema>, name: Option<String>) -> Self {
+        let name = name.unwrap_or_else(|| id.clone());
+        let schema = schema.map(|s| s.inner).unwrap_or_default();
+        Self {
+            inner: papermake::Template::new(id, name, content, schema),
+        }
+    }
+
+    /// Load a template from a `.typ` file with schema frontmatter
+    #[staticmethod]
+    fn from_file(path: std::path::PathBuf) -> PyResult<Self> {
+        let inner = papermake::Template::from_file(path).map_err(to_py_err)?;
+        Ok(Self { inner })
+    }
+
+    #[getter]
+    fn id(&self) -> String {
+        self.inner.id.0.clone()
+    }
+
+    #[getter]
+    fn name(&self) -> String {
+        self.inner.name.clone()
+    }
+
+    #[getter]
+    fn content(&self) -> String {
+        self.inner.content.clone()
+    }
+
+    #[getter]
+    fn schema(&self) -> Schema {
+        Schema {
+            inner: self.inner.schema.clone(),
+        }
+    }
+
+    /// Render the template with `data`; see the module-level `render_pdf`
+    #[pyo3(signature = (data, paper_size = None, compress = None, coerce_data = None))]
+    fn render<'py>(
+        &self,
+        py: Python<'py>,
+        data: &Bound<'py, PyAny>,
+        paper_size: Option<String>,
+        compress: Option<bool>,
+        coerce_data: Option<bool>,
+    ) -> PyResult<Bound<'py, PyBytes>> {
+        render_pdf(py, self, data, paper_size, compress, coerce_data)
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Template(id={:?}, name={:?})", self.inner.id.0, self.inner.name)
+    }
+}
+
+/// Render a template with data to PDF bytes
+///
+/// Raises `ValidationError` if the data does not match the template's
+/// schema and `RenderError` if the template fails to compile.
+#[pyfunction]
+#[pyo3(signature = (template, data, paper_size = None, compress = None, coerce_data = None))]
+fn render_pdf<'py>(
+    py: Python<'py>,
+    template: &Template,
+    data: &Bound<'py, PyAny>,
+    paper_size: Option<String>,
+    compress: Option<bool>,
+    coerce_data: Option<bool>,
+) -> PyResult<Bound<'py, PyBytes>> {
+    let data = to_json(data, "data")?;
+    let defaults = RenderOptions::default();
+    let options = RenderOptions {
+        paper_size: paper_size.unwrap_or(defaults.paper_size),
+        compress: compress.unwrap_or(defaults.compress),
+        coerce_data: coerce_data.unwrap_or(defaults.coerce_data),
+        ..defaults
+    };
+
+    let template = &template.inner;
+    let result = py
+        .allow_threads(|| papermake::render_pdf(template, &data, Some(options)))
+        .map_err(to_py_err)?;
+
+    match result.pdf {
+        Some(pdf) => Ok(PyBytes::new_bound(py, &pdf)),
+        None => {
+            let messages: Vec<String> = result.errors.into_iter().map(|e| e.message).collect();
+            Err(RenderError::new_err(messages.join("\n")))
+        }
+    }
+}
+
+#[pymodule]
+#[pyo3(name = "papermake")]
+fn papermake_py(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Schema>()?;
+    m.add_class::<Template>()?;
+    m.add_function(wrap_pyfunction!(render_pdf, m)?)?;
+    m.add("RenderError", m.py().get_type_bound::<RenderError>())?;
+    m.add("ValidationError", m.py().get_type_bound::<ValidationError>())?;
+    m.add("__version__", env!("CARGO_PKG_VERSION"))?;
+    Ok(())
+}
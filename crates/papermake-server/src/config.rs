@@ -0,0 +1,583 @@
+//! Server configuration from `papermake.toml` and the environment
+//!
+//! Settings are read from the file given with `--config <path>` (or
+//! `PAPERMAKE_CONFIG`), falling back to `papermake.toml` in the working
+//! directory if it exists. Environment variables override the file, so
+//! deployments can keep a shared file and adjust single settings per
+//! instance. `--print-config` prints the resolved configuration.
+
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use axum::http::HeaderValue;
+use papermake::{RemoteResources, SizeLimits};
+use serde::{Deserialize, Serialize};
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
+
+/// Configuration file read when no path is given
+pub const DEFAULT_CONFIG_FILE: &str = "papermake.toml";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// Address to listen on
+    pub bind: SocketAddr,
+    /// Deployment environment (e.g. `staging`) selecting template settings
+    /// for renders that don't name one
+    pub environment: Option<String>,
+    /// Serve HTTPS with this certificate and key
+    pub tls: Option<TlsConfig>,
+    pub cors: CorsConfig,
+    pub storage: StorageConfig,
+    pub timeouts: TimeoutConfig,
+    pub limits: LimitsConfig,
+    pub data_sources: DataSourceConfig,
+    pub remote_resources: RemoteResourceConfig,
+    pub webhooks: WebhookConfig,
+    pub retention: RetentionConfig,
+    pub downloads: DownloadConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TlsConfig {
+    /// PEM certificate chain
+    pub cert: PathBuf,
+    /// PEM private key
+    pub key: PathBuf,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CorsConfig {
+    /// Origins allowed to call the API, e.g. `https://editor.example.com`;
+    /// `*` allows any origin
+    pub allowed_origins: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RenderCacheKind {
+    Memory,
+    Disk,
+    Off,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageConfig {
+    /// Directory holding templates, render history and quotas
+    pub path: PathBuf,
+    /// Where job output is written: a directory or `s3://bucket/prefix`;
+    /// `{path}/outputs` if unset
+    pub output: Option<String>,
+    pub render_cache: RenderCacheKind,
+    /// Entries of the in-memory render cache
+    pub render_cache_size: usize,
+    /// How long responses to requests with an `Idempotency-Key` are replayed
+    pub idempotency_ttl_secs: u64,
+    /// Store each distinct template file once in `{path}/blobs`, shared by
+    /// all templates and tenants
+    pub dedup: bool,
+    /// Age below which unreferenced blobs survive `--gc`, covering uploads
+    /// still in progress
+    pub blob_gc_grace_secs: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TimeoutConfig {
+    /// Longest time a request may take before it is answered with 408
+    pub request_secs: u64,
+    /// How long in-flight requests and jobs may take to finish on shutdown
+    pub drain_secs: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LimitsConfig {
+    /// Maximum size of a request body other than uploads
+    pub max_body_bytes: usize,
+    /// Maximum size of render data, as JSON
+    pub max_data_bytes: usize,
+    /// Maximum size of a template's source
+    pub max_template_bytes: usize,
+    /// Maximum size of a single uploaded file
+    pub max_upload_file_bytes: usize,
+    /// Maximum size of a whole upload request
+    pub max_upload_request_bytes: usize,
+    /// Requests per minute per API key; unlimited if unset
+    pub rate_limit_per_key: Option<u32>,
+    /// Requests per minute per client IP; unlimited if unset
+    pub rate_limit_per_ip: Option<u32>,
+    /// Largest burst of requests; a minute's worth if unset
+    pub rate_limit_burst: Option<u32>,
+    /// Renders per API key per month; unlimited if unset
+    pub monthly_render_quota: Option<u64>,
+    /// Renders of one template running at once; unlimited if unset
+    pub max_renders_per_template: Option<usize>,
+    /// Renders of one API key running at once; unlimited if unset
+    pub max_renders_per_key: Option<usize>,
+    /// Renders waiting for a slot before requests are rejected with 503
+    pub max_queued_renders: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DataSourceConfig {
+    /// Longest time fetching the data of a render may take
+    pub timeout_secs: u64,
+    /// Hosts `data_url` may point to; any host if empty
+    pub allowed_hosts: Vec<String>,
+    /// Bucket `data_s3_key` is read from; S3 data is disabled if unset
+    pub s3_bucket: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RemoteResourceConfig {
+    /// Hosts templates may load images and other files from by URL;
+    /// remote files are disabled if empty
+    pub allowed_hosts: Vec<String>,
+    /// Longest time fetching a single file may take
+    pub timeout_secs: u64,
+    /// Maximum size of a fetched file
+    pub max_bytes: usize,
+    /// How long fetched files are reused
+    pub cache_ttl_secs: u64,
+}
+
+/// Where job webhooks may be sent; the signing secret is only read from
+/// `PAPERMAKE_WEBHOOK_SECRET`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WebhookConfig {
+    /// Hosts webhook URLs may point to, matched exactly; jobs with a
+    /// webhook are rejected if empty
+    pub allowed_hosts: Vec<String>,
+    /// Schemes webhook URLs may use, `http` and/or `https`
+    pub allowed_schemes: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetentionConfig {
+    /// Days render records and their inputs are kept; forever if unset
+    pub render_history_days: Option<u64>,
+    /// Days finished jobs and their documents are kept; forever if unset
+    pub job_results_days: Option<u64>,
+    /// Days published revisions of archived templates are kept; forever if unset
+    pub archived_versions_days: Option<u64>,
+    /// How often expired artifacts are deleted
+    pub sweep_interval_secs: u64,
+}
+
+/// Signed download links; the secret is only read from
+/// `PAPERMAKE_DOWNLOAD_SECRET`, so it doesn't show up in printed configs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DownloadConfig {
+    /// How long links are valid unless the request asks for less
+    pub ttl_secs: u64,
+    /// Longest validity a request may ask for
+    pub max_ttl_secs: u64,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            bind: SocketAddr::from(([0, 0, 0, 0], 3000)),
+            environment: None,
+            tls: None,
+            cors: CorsConfig::default(),
+            storage: StorageConfig::default(),
+            timeouts: TimeoutConfig::default(),
+            limits: LimitsConfig::default(),
+            data_sources: DataSourceConfig::default(),
+            remote_resources: RemoteResourceConfig::default(),
+            webhooks: WebhookConfig::default(),
+            retention: RetentionConfig::default(),
+            downloads: DownloadConfig::default(),
+        }
+    }
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self { allowed_origins: vec!["*".to_string()] }
+    }
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from("./data"),
+            output: None,
+            render_cache: RenderCacheKind::Memory,
+            render_cache_size: 256,
+            idempotency_ttl_secs: 24 * 60 * 60,
+            dedup: false,
+            blob_gc_grace_secs: 60 * 60,
+        }
+    }
+}
+
+impl Default for TimeoutConfig {
+    fn default() -> Self {
+        Self { request_secs: 120, drain_secs: 30 }
+    }
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self {
+            max_body_bytes: 16 * 1024 * 1024,
+            max_data_bytes: 10 * 1024 * 1024,
+            max_template_bytes: 1024 * 1024,
+            max_upload_file_bytes: 10 * 1024 * 1024,
+            max_upload_request_bytes: 50 * 1024 * 1024,
+            rate_limit_per_key: None,
+            rate_limit_per_ip: None,
+            rate_limit_burst: None,
+            monthly_render_quota: None,
+            max_renders_per_template: None,
+            max_renders_per_key: None,
+            max_queued_renders: 100,
+        }
+    }
+}
+
+impl Default for DataSourceConfig {
+    fn default() -> Self {
+        Self {
+            timeout_secs: 30,
+            allowed_hosts: Vec::new(),
+            s3_bucket: None,
+        }
+    }
+}
+
+impl Default for RemoteResourceConfig {
+    fn default() -> Self {
+        Self {
+            allowed_hosts: Vec::new(),
+            timeout_secs: 10,
+            max_bytes: 5 * 1024 * 1024,
+            cache_ttl_secs: 300,
+        }
+    }
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        Self {
+            allowed_hosts: Vec::new(),
+            allowed_schemes: vec!["https".to_string()],
+        }
+    }
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        Self {
+            render_history_days: None,
+            job_results_days: None,
+            archived_versions_days: None,
+            sweep_interval_secs: 60 * 60,
+        }
+    }
+}
+
+impl Default for DownloadConfig {
+    fn default() -> Self {
+        Self { ttl_secs: 60 * 60, max_ttl_secs: 7 * 24 * 60 * 60 }
+    }
+}
+
+impl ServerConfig {
+    /// Load the configuration file, apply environment overrides and validate
+    /// the result
+    pub fn load(path: Option<&Path>) -> Result<Self, String> {
+        let path = path
+            .map(Path::to_path_buf)
+            .or_else(|| std::env::var("PAPERMAKE_CONFIG").ok().map(PathBuf::from));
+        let mut config = match path {
+            Some(path) => Self::from_file(&path)?,
+            None if Path::new(DEFAULT_CONFIG_FILE).exists() => Self::from_file(Path::new(DEFAULT_CONFIG_FILE))?,
+            None => Self::default(),
+        };
+        config.apply_env()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn from_file(path: &Path) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        toml::from_str(&content).map_err(|e| format!("Invalid {}: {}", path.display(), e))
+    }
+
+    /// Override settings with the `PAPERMAKE_*` variables (and `PORT`)
+    fn apply_env(&mut self) -> Result<(), String> {
+        if let Some(bind) = env("PAPERMAKE_BIND")? {
+            self.bind = bind;
+        }
+        if let Some(port) = env("PORT")? {
+            self.bind.set_port(port);
+        }
+        if let Some(environment) = env("PAPERMAKE_ENVIRONMENT")? {
+            self.environment = Some(environment);
+        }
+        match (env::<PathBuf>("PAPERMAKE_TLS_CERT")?, env::<PathBuf>("PAPERMAKE_TLS_KEY")?) {
+            (Some(cert), Some(key)) => self.tls = Some(TlsConfig { cert, key }),
+            (None, None) => {}
+            _ => return Err("PAPERMAKE_TLS_CERT and PAPERMAKE_TLS_KEY must be set together".to_string()),
+        }
+        if let Some(origins) = env::<String>("PAPERMAKE_CORS_ORIGINS")? {
+            self.cors.allowed_origins = origins
+                .split(',')
+                .map(|origin| origin.trim().to_string())
+                .filter(|origin| !origin.is_empty())
+                .collect();
+        }
+
+        if let Some(path) = env("PAPERMAKE_STORAGE_PATH")? {
+            self.storage.path = path;
+        }
+        if let Some(output) = env("PAPERMAKE_OUTPUT")? {
+            self.storage.output = Some(output);
+        }
+        if let Some(cache) = env::<String>("PAPERMAKE_RENDER_CACHE")? {
+            self.storage.render_cache = match cache.as_str() {
+                "memory" => RenderCacheKind::Memory,
+                "disk" => RenderCacheKind::Disk,
+                "off" => RenderCacheKind::Off,
+                other => return Err(format!("Invalid PAPERMAKE_RENDER_CACHE '{}', expected memory, disk or off", other)),
+            };
+        }
+        if let Some(size) = env("PAPERMAKE_RENDER_CACHE_SIZE")? {
+            self.storage.render_cache_size = size;
+        }
+        if let Some(secs) = env("PAPERMAKE_IDEMPOTENCY_TTL")? {
+            self.storage.idempotency_ttl_secs = secs;
+        }
+        if let Some(dedup) = env("PAPERMAKE_STORAGE_DEDUP")? {
+            self.storage.dedup = dedup;
+        }
+        if let Some(secs) = env("PAPERMAKE_BLOB_GC_GRACE")? {
+            self.storage.blob_gc_grace_secs = secs;
+        }
+
+        if let Some(secs) = env("PAPERMAKE_REQUEST_TIMEOUT")? {
+            self.timeouts.request_secs = secs;
+        }
+        if let Some(secs) = env("PAPERMAKE_DRAIN_TIMEOUT")? {
+            self.timeouts.drain_secs = secs;
+        }
+
+        let limits = &mut self.limits;
+        if let Some(bytes) = env("PAPERMAKE_MAX_BODY_BYTES")? {
+            limits.max_body_bytes = bytes;
+        }
+        if let Some(bytes) = env("PAPERMAKE_MAX_DATA_BYTES")? {
+            limits.max_data_bytes = bytes;
+        }
+        if let Some(bytes) = env("PAPERMAKE_MAX_TEMPLATE_BYTES")? {
+            limits.max_template_bytes = bytes;
+        }
+        if let Some(bytes) = env("PAPERMAKE_MAX_UPLOAD_FILE_BYTES")? {
+            limits.max_upload_file_bytes = bytes;
+        }
+        if let Some(bytes) = env("PAPERMAKE_MAX_UPLOAD_REQUEST_BYTES")? {
+            limits.max_upload_request_bytes = bytes;
+        }
+        limits.rate_limit_per_key = env("PAPERMAKE_RATE_LIMIT_PER_KEY")?.or(limits.rate_limit_per_key);
+        limits.rate_limit_per_ip = env("PAPERMAKE_RATE_LIMIT_PER_IP")?.or(limits.rate_limit_per_ip);
+        limits.rate_limit_burst = env("PAPERMAKE_RATE_LIMIT_BURST")?.or(limits.rate_limit_burst);
+        limits.monthly_render_quota = env("PAPERMAKE_MONTHLY_RENDER_QUOTA")?.or(limits.monthly_render_quota);
+        limits.max_renders_per_template = env("PAPERMAKE_MAX_RENDERS_PER_TEMPLATE")?.or(limits.max_renders_per_template);
+        limits.max_renders_per_key = env("PAPERMAKE_MAX_RENDERS_PER_KEY")?.or(limits.max_renders_per_key);
+        if let Some(queued) = env("PAPERMAKE_MAX_QUEUED_RENDERS")? {
+            limits.max_queued_renders = queued;
+        }
+
+        if let Some(secs) = env("PAPERMAKE_DATA_FETCH_TIMEOUT")? {
+            self.data_sources.timeout_secs = secs;
+        }
+        if let Some(hosts) = env::<String>("PAPERMAKE_DATA_ALLOWED_HOSTS")? {
+            self.data_sources.allowed_hosts = hosts
+                .split(',')
+                .map(|host| host.trim().to_string())
+                .filter(|host| !host.is_empty())
+                .collect();
+        }
+        if let Some(bucket) = env("PAPERMAKE_DATA_S3_BUCKET")? {
+            self.data_sources.s3_bucket = Some(bucket);
+        }
+
+        if let Some(hosts) = env::<String>("PAPERMAKE_RESOURCE_ALLOWED_HOSTS")? {
+            self.remote_resources.allowed_hosts = hosts
+                .split(',')
+                .map(|host| host.trim().to_string())
+                .filter(|host| !host.is_empty())
+                .collect();
+        }
+        if let Some(secs) = env("PAPERMAKE_RESOURCE_TIMEOUT")? {
+            self.remote_resources.timeout_secs = secs;
+        }
+        if let Some(bytes) = env("PAPERMAKE_RESOURCE_MAX_BYTES")? {
+            self.remote_resources.max_bytes = bytes;
+        }
+
+        if let Some(hosts) = env::<String>("PAPERMAKE_WEBHOOK_ALLOWED_HOSTS")? {
+            self.webhooks.allowed_hosts = hosts
+                .split(',')
+                .map(|host| host.trim().to_string())
+                .filter(|host| !host.is_empty())
+                .collect();
+        }
+        if let Some(schemes) = env::<String>("PAPERMAKE_WEBHOOK_ALLOWED_SCHEMES")? {
+            self.webhooks.allowed_schemes = schemes
+                .split(',')
+                .map(|scheme| scheme.trim().to_string())
+                .filter(|scheme| !scheme.is_empty())
+                .collect();
+        }
+
+        let retention = &mut self.retention;
+        retention.render_history_days = env("PAPERMAKE_RETENTION_HISTORY_DAYS")?.or(retention.render_history_days);
+        retention.job_results_days = env("PAPERMAKE_RETENTION_JOB_DAYS")?.or(retention.job_results_days);
+        retention.archived_versions_days =
+            env("PAPERMAKE_RETENTION_ARCHIVED_DAYS")?.or(retention.archived_versions_days);
+        if let Some(secs) = env("PAPERMAKE_RETENTION_SWEEP_INTERVAL")? {
+            retention.sweep_interval_secs = secs;
+        }
+        if let Some(secs) = env("PAPERMAKE_DOWNLOAD_TTL")? {
+            self.downloads.ttl_secs = secs;
+        }
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if let Some(tls) = &self.tls {
+            for path in [&tls.cert, &tls.key] {
+                if !path.is_file() {
+                    return Err(format!("TLS file {} does not exist", path.display()));
+                }
+            }
+        }
+        if self.cors.allowed_origins.len() > 1 && self.cors.allowed_origins.iter().any(|o| o == "*") {
+            return Err("CORS origin '*' can't be combined with other origins".to_string());
+        }
+        for origin in &self.cors.allowed_origins {
+            if origin != "*" && !(origin.starts_with("http://") || origin.starts_with("https://")) {
+                return Err(format!("Invalid CORS origin '{}', expected e.g. https://example.com", origin));
+            }
+            HeaderValue::from_str(origin).map_err(|_| format!("Invalid CORS origin '{}'", origin))?;
+        }
+        if self.storage.idempotency_ttl_secs == 0 {
+            return Err("storage.idempotency_ttl_secs must be positive".to_string());
+        }
+        if self.timeouts.request_secs == 0 {
+            return Err("timeouts.request_secs must be positive".to_string());
+        }
+        if self.data_sources.timeout_secs == 0 {
+            return Err("data_sources.timeout_secs must be positive".to_string());
+        }
+        if self.remote_resources.timeout_secs == 0 || self.remote_resources.max_bytes == 0 {
+            return Err("remote_resources.timeout_secs and max_bytes must be positive".to_string());
+        }
+        let schemes = &self.webhooks.allowed_schemes;
+        if schemes.is_empty() || schemes.iter().any(|scheme| scheme != "http" && scheme != "https") {
+            return Err("webhooks.allowed_schemes must list http and/or https".to_string());
+        }
+        let retention = &self.retention;
+        let days = [retention.render_history_days, retention.job_results_days, retention.archived_versions_days];
+        if days.contains(&Some(0)) || retention.sweep_interval_secs == 0 {
+            return Err("Retention periods and the sweep interval must be positive; leave periods unset to keep forever".to_string());
+        }
+        if self.downloads.ttl_secs == 0 || self.downloads.max_ttl_secs < self.downloads.ttl_secs {
+            return Err("downloads.ttl_secs must be positive and at most downloads.max_ttl_secs".to_string());
+        }
+        let limits = &self.limits;
+        if [limits.max_body_bytes, limits.max_data_bytes, limits.max_template_bytes].contains(&0) {
+            return Err("Body, data and template size limits must be positive".to_string());
+        }
+        if limits.max_upload_file_bytes == 0 || limits.max_upload_request_bytes < limits.max_upload_file_bytes {
+            return Err("Upload limits must be positive, with the request limit at least the file limit".to_string());
+        }
+        let rates = [limits.rate_limit_per_key, limits.rate_limit_per_ip, limits.rate_limit_burst];
+        if rates.contains(&Some(0)) || limits.monthly_render_quota == Some(0) {
+            return Err("Rate limits and quotas must be positive; leave them unset for no limit".to_string());
+        }
+        if [limits.max_renders_per_template, limits.max_renders_per_key].contains(&Some(0)) {
+            return Err("Render concurrency limits must be positive; leave them unset for no limit".to_string());
+        }
+        Ok(())
+    }
+
+    /// The configuration as TOML, as printed by `--print-config`
+    pub fn to_toml(&self) -> String {
+        toml::to_string_pretty(self).unwrap_or_default()
+    }
+
+    pub fn request_timeout(&self) -> Duration {
+        Duration::from_secs(self.timeouts.request_secs)
+    }
+
+    pub fn drain_timeout(&self) -> Duration {
+        Duration::from_secs(self.timeouts.drain_secs)
+    }
+
+    /// CORS layer allowing the configured origins
+    pub fn cors_layer(&self) -> CorsLayer {
+        if self.cors.allowed_origins.iter().any(|origin| origin == "*") {
+            return CorsLayer::permissive();
+        }
+        let origins = self.cors.allowed_origins
+            .iter()
+            .filter_map(|origin| HeaderValue::from_str(origin).ok());
+        CorsLayer::new()
+            .allow_origin(AllowOrigin::list(origins))
+            .allow_methods(Any)
+            .allow_headers(Any)
+            .expose_headers(Any)
+    }
+}
+
+impl RemoteResourceConfig {
+    /// Fetching settings for renders, `None` if no host is allowed
+    pub fn remote_resources(&self) -> Option<RemoteResources> {
+        if self.allowed_hosts.is_empty() {
+            return None;
+        }
+        let resources = RemoteResources::new(&self.allowed_hosts)
+            .timeout(Duration::from_secs(self.timeout_secs))
+            .max_bytes(self.max_bytes)
+            .cache_ttl(Duration::from_secs(self.cache_ttl_secs));
+        Some(resources)
+    }
+}
+
+impl LimitsConfig {
+    /// Limits enforced by the library on every render and template
+    pub fn size_limits(&self) -> SizeLimits {
+        SizeLimits::default()
+            .max_data_bytes(self.max_data_bytes)
+            .max_template_bytes(self.max_template_bytes)
+            .max_asset_bytes(self.max_upload_file_bytes)
+    }
+}
+
+/// Parse an environment variable, if set
+fn env<T: std::str::FromStr>(name: &str) -> Result<Option<T>, String> {
+    match std::env::var(name) {
+        Ok(value) => value
+            .trim()
+            .parse()
+            .map(Some)
+            .map_err(|_| format!("Invalid value for {}: '{}'", name, value)),
+        Err(_) => Ok(None),
+    }
+}
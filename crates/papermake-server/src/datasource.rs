@@ -0,0 +1,191 @@
+//! Render data fetched from a URL or S3 instead of the request body
+//!
+//! Render requests either inline their data or reference it:
+//!
+//! ```json
+//! { "data_url": "https://exports.example.com/invoice/42.json",
+//!   "headers": { "Authorization": "Bearer ..." } }
+//! { "data_s3_key": "exports/invoice-42.json" }
+//! ```
+//!
+//! The server fetches referenced data before validating it against the
+//! template schema, bounded by `data_sources.timeout_secs` and
+//! `limits.max_data_bytes`. URLs are restricted to
+//! `data_sources.allowed_hosts` when set, and S3 keys are read from
+//! `data_sources.s3_bucket`.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use papermake::error::PapermakeError;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use serde::Deserialize;
+
+use crate::config::DataSourceConfig;
+use crate::AppError;
+
+/// Where the data of a render request comes from; exactly one source is set
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct DataInput {
+    /// Inline render data
+    pub data: Option<serde_json::Value>,
+    /// `http(s)` URL answering with the render data as JSON
+    pub data_url: Option<String>,
+    /// Headers sent with the `data_url` request, e.g. for authorization
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// Key of a JSON object in the configured data bucket
+    pub data_s3_key: Option<String>,
+}
+
+/// Fetches referenced render data with size and time limits
+#[derive(Debug, Clone)]
+pub struct DataFetcher {
+    client: reqwest::Client,
+    allowed_hosts: Vec<String>,
+    s3: Option<(aws_sdk_s3::Client, String)>,
+    timeout: Duration,
+    max_bytes: usize,
+}
+
+impl DataFetcher {
+    /// Create a fetcher; S3 credentials and region come from the environment
+    pub async fn from_config(config: &DataSourceConfig, max_bytes: usize) -> Self {
+        let timeout = Duration::from_secs(config.timeout_secs);
+        let s3 = match &config.s3_bucket {
+            Some(bucket) => {
+                let aws = aws_config::load_defaults(aws_config::BehaviorVersion::latest()).await;
+                Some((aws_sdk_s3::Client::new(&aws), bucket.clone()))
+            }
+            None => None,
+        };
+        Self {
+            client: reqwest::Client::builder()
+                .timeout(timeout)
+                .build()
+                .expect("failed to build data source HTTP client"),
+            allowed_hosts: config.allowed_hosts.iter().map(|host| host.to_ascii_lowercase()).collect(),
+            s3,
+            timeout,
+            max_bytes,
+        }
+    }
+
+    /// The render data of a request, fetching it if referenced
+    pub async fn resolve(&self, input: DataInput) -> Result<serde_json::Value, AppError> {
+        match (input.data, input.data_url, input.data_s3_key) {
+            (Some(data), None, None) => {
+                if !input.headers.is_empty() {
+                    return Err(AppError::BadRequest("headers are only sent with data_url".to_string()));
+                }
+                Ok(data)
+            }
+            (None, Some(url), None) => self.fetch_url(&url, &input.headers).await,
+            (None, None, Some(key)) => {
+                if !input.headers.is_empty() {
+                    return Err(AppError::BadRequest("headers are only sent with data_url".to_string()));
+                }
+                self.fetch_s3(&key).await
+            }
+            (None, None, None) => Err(AppError::BadRequest(
+                "Missing render data: set data, data_url or data_s3_key".to_string(),
+            )),
+            _ => Err(AppError::BadRequest(
+                "Only one of data, data_url and data_s3_key may be set".to_string(),
+            )),
+        }
+    }
+
+    async fn fetch_url(&self, url: &str, headers: &BTreeMap<String, String>) -> Result<serde_json::Value, AppError> {
+        let parsed = reqwest::Url::parse(url).map_err(|e| AppError::BadRequest(format!("Invalid data_url: {}", e)))?;
+        if !matches!(parsed.scheme(), "http" | "https") {
+            return Err(AppError::BadRequest("data_url must use http or https".to_string()));
+        }
+        let host = parsed.host_str().unwrap_or_default().to_ascii_lowercase();
+        if !self.allowed_hosts.is_empty() && !self.allowed_hosts.contains(&host) {
+            return Err(AppError::BadRequest(format!("Fetching data from '{}' is not allowed", host)));
+        }
+
+        let mut header_map = HeaderMap::new();
+        for (name, value) in headers {
+            let name = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|_| AppError::BadRequest(format!("Invalid header name '{}'", name)))?;
+            let value = HeaderValue::from_str(value)
+                .map_err(|_| AppError::BadRequest(format!("Invalid value for header '{}'", name)))?;
+            header_map.insert(name, value);
+        }
+
+        let failed = |e: reqwest::Error| {
+            if e.is_timeout() {
+                return self.timed_out();
+            }
+            AppError::BadRequest(format!("Failed to fetch data from {}: {}", url, e))
+        };
+        let mut response = self
+            .client
+            .get(parsed.clone())
+            .headers(header_map)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(failed)?;
+        self.check_length(response.content_length())?;
+
+        let mut body = Vec::new();
+        while let Some(chunk) = response.chunk().await.map_err(failed)? {
+            body.extend_from_slice(&chunk);
+            self.check_length(Some(body.len() as u64))?;
+        }
+        parse(&body, url)
+    }
+
+    async fn fetch_s3(&self, key: &str) -> Result<serde_json::Value, AppError> {
+        let Some((client, bucket)) = &self.s3 else {
+            return Err(AppError::BadRequest("No data bucket is configured for data_s3_key".to_string()));
+        };
+        let location = format!("s3://{}/{}", bucket, key);
+        let failed = |e: &dyn std::fmt::Display| AppError::BadRequest(format!("Failed to fetch data from {}: {}", location, e));
+
+        let download = async {
+            let mut object = client
+                .get_object()
+                .bucket(bucket)
+                .key(key)
+                .send()
+                .await
+                .map_err(|e| failed(&e))?;
+            self.check_length(object.content_length().and_then(|length| u64::try_from(length).ok()))?;
+
+            let mut body = Vec::new();
+            while let Some(chunk) = object.body.next().await {
+                body.extend_from_slice(&chunk.map_err(|e| failed(&e))?);
+                self.check_length(Some(body.len() as u64))?;
+            }
+            Ok(body)
+        };
+        let body = tokio::time::timeout(self.timeout, download)
+            .await
+            .map_err(|_| self.timed_out())??;
+        parse(&body, &location)
+    }
+
+    fn timed_out(&self) -> AppError {
+        AppError::Papermake(PapermakeError::Timeout {
+            operation: "Fetching render data".to_string(),
+            after: self.timeout,
+        })
+    }
+
+    fn check_length(&self, length: Option<u64>) -> Result<(), AppError> {
+        match length {
+            Some(length) if length > self.max_bytes as u64 => Err(AppError::Papermake(PapermakeError::TooLarge(
+                format!("Render data exceeds the maximum size of {} bytes", self.max_bytes),
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn parse(body: &[u8], source: &str) -> Result<serde_json::Value, AppError> {
+    serde_json::from_slice(body).map_err(|e| AppError::BadRequest(format!("Data from {} is not valid JSON: {}", source, e)))
+}
@@ -0,0 +1,238 @@
+//! Development mode: live previews of templates in a local directory
+//!
+//! Started with `papermake-server --dev <dir>`. Every `<id>.typ` in the
+//! directory is a template, with an optional `<id>.schema.json` schema and
+//! `<id>.data.json` preview data (sample data generated from the schema
+//! otherwise). The directory is polled for changes; changed templates are
+//! re-rendered to PNG and pushed to editors connected to
+//! `GET /dev/templates/{id}/ws`.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+
+use axum::{
+    extract::{ws::{Message, WebSocket, WebSocketUpgrade}, Path as UrlPath, State},
+    response::IntoResponse,
+    routing::get,
+    Json, Router,
+};
+use base64::{prelude::BASE64_STANDARD, Engine};
+use papermake::{render, render::RenderError, OutputFormat, Schema, Template};
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+use crate::AppError;
+
+/// How often the directory is checked for changes
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Latest preview of a template, as pushed to editors
+#[derive(Debug, Clone, Serialize)]
+pub struct Preview {
+    pub template_id: String,
+    /// Base64-encoded PNG of each page
+    pub pages: Vec<String>,
+    pub errors: Vec<RenderError>,
+    pub warnings: Vec<RenderError>,
+}
+
+/// Templates of the watched directory and their latest previews
+pub struct DevWorkspace {
+    dir: PathBuf,
+    previews: RwLock<BTreeMap<String, Preview>>,
+    updates: broadcast::Sender<Preview>,
+}
+
+impl DevWorkspace {
+    pub fn new(dir: impl Into<PathBuf>) -> Arc<Self> {
+        let (updates, _) = broadcast::channel(64);
+        Arc::new(Self {
+            dir: dir.into(),
+            previews: RwLock::new(BTreeMap::new()),
+            updates,
+        })
+    }
+
+    /// Poll the directory forever, re-rendering templates whose files changed
+    pub async fn watch(self: Arc<Self>) {
+        let mut seen: BTreeMap<String, SystemTime> = BTreeMap::new();
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+            let current = match self.scan().await {
+                Ok(current) => current,
+                Err(err) => {
+                    tracing::warn!("failed to scan {}: {}", self.dir.display(), err);
+                    continue;
+                }
+            };
+
+            for (id, modified) in &current {
+                if seen.get(id) != Some(modified) {
+                    tracing::info!("reloading template '{}'", id);
+                    self.reload(id).await;
+                }
+            }
+            if let Ok(mut previews) = self.previews.write() {
+                previews.retain(|id, _| current.contains_key(id));
+            }
+            seen = current;
+        }
+    }
+
+    /// Template ids with the latest modification time of their files
+    async fn scan(&self) -> std::io::Result<BTreeMap<String, SystemTime>> {
+        let mut templates: BTreeMap<String, SystemTime> = BTreeMap::new();
+        let mut entries = tokio::fs::read_dir(&self.dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let Some(id) = [".typ", ".schema.json", ".data.json"]
+                .iter()
+                .find_map(|suffix| name.strip_suffix(suffix))
+            else {
+                continue;
+            };
+            let modified = entry.metadata().await?.modified()?;
+            let latest = templates.entry(id.to_string()).or_insert(modified);
+            *latest = (*latest).max(modified);
+        }
+        // Schemas and data without a template aren't previewed
+        templates.retain(|id, _| self.dir.join(format!("{}.typ", id)).exists());
+        Ok(templates)
+    }
+
+    /// Re-render a template and push the preview to its editors
+    async fn reload(&self, id: &str) {
+        let dir = self.dir.clone();
+        let template_id = id.to_string();
+        let preview = tokio::task::spawn_blocking(move || render_preview(&dir, &template_id))
+            .await
+            .unwrap_or_else(|e| Preview {
+                template_id: id.to_string(),
+                pages: Vec::new(),
+                errors: vec![error(format!("Preview task failed: {}", e))],
+                warnings: Vec::new(),
+            });
+
+        if let Ok(mut previews) = self.previews.write() {
+            previews.insert(id.to_string(), preview.clone());
+        }
+        // No receivers just means no editor is connected
+        let _ = self.updates.send(preview);
+    }
+
+    fn preview(&self, id: &str) -> Option<Preview> {
+        self.previews.read().ok()?.get(id).cloned()
+    }
+}
+
+/// Load a template from the directory and render every page to PNG
+fn render_preview(dir: &Path, id: &str) -> Preview {
+    let failed = |message: String| Preview {
+        template_id: id.to_string(),
+        pages: Vec::new(),
+        errors: vec![error(message)],
+        warnings: Vec::new(),
+    };
+
+    let content = match std::fs::read_to_string(dir.join(format!("{}.typ", id))) {
+        Ok(content) => content,
+        Err(e) => return failed(format!("Failed to read template: {}", e)),
+    };
+    let schema = match read_json::<Schema>(&dir.join(format!("{}.schema.json", id))) {
+        Ok(schema) => schema.unwrap_or_default(),
+        Err(message) => return failed(message),
+    };
+    let data = match read_json::<serde_json::Value>(&dir.join(format!("{}.data.json", id))) {
+        Ok(data) => data.unwrap_or_else(|| schema.generate_sample_data(0)),
+        Err(message) => return failed(message),
+    };
+
+    let template = Template::new(id, id, content, schema);
+    match render(&template, &data, OutputFormat::Png, None) {
+        Ok(output) => Preview {
+            template_id: id.to_string(),
+            pages: output.files.iter().map(|png| BASE64_STANDARD.encode(png)).collect(),
+            errors: output.errors,
+            warnings: output.warnings,
+        },
+        Err(e) => failed(e.to_string()),
+    }
+}
+
+/// Parse an optional JSON file
+fn read_json<T: serde::de::DeserializeOwned>(path: &Path) -> Result<Option<T>, String> {
+    match std::fs::read_to_string(path) {
+        Ok(json) => serde_json::from_str(&json)
+            .map(Some)
+            .map_err(|e| format!("Invalid {}: {}", path.display(), e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(format!("Failed to read {}: {}", path.display(), e)),
+    }
+}
+
+fn error(message: String) -> RenderError {
+    RenderError { message, start: 0, end: 0 }
+}
+
+/// Routes of the development mode
+pub fn dev_routes(workspace: Arc<DevWorkspace>) -> Router {
+    Router::new()
+        .route("/dev/templates", get(list_previews))
+        .route("/dev/templates/{id}", get(get_preview))
+        .route("/dev/templates/{id}/ws", get(preview_socket))
+        .with_state(workspace)
+}
+
+async fn list_previews(State(workspace): State<Arc<DevWorkspace>>) -> Json<Vec<String>> {
+    let ids = workspace.previews.read()
+        .map(|previews| previews.keys().cloned().collect())
+        .unwrap_or_default();
+    Json(ids)
+}
+
+async fn get_preview(
+    State(workspace): State<Arc<DevWorkspace>>,
+    UrlPath(id): UrlPath<String>,
+) -> Result<Json<Preview>, AppError> {
+    workspace.preview(&id).map(Json).ok_or(AppError::NotFound)
+}
+
+// Push the current preview, then every new one, as JSON text messages
+async fn preview_socket(
+    State(workspace): State<Arc<DevWorkspace>>,
+    UrlPath(id): UrlPath<String>,
+    ws: WebSocketUpgrade,
+) -> impl IntoResponse {
+    ws.on_upgrade(move |socket| push_previews(socket, workspace, id))
+}
+
+async fn push_previews(mut socket: WebSocket, workspace: Arc<DevWorkspace>, id: String) {
+    let mut updates = workspace.updates.subscribe();
+    if let Some(preview) = workspace.preview(&id) {
+        if send(&mut socket, &preview).await.is_err() {
+            return;
+        }
+    }
+
+    loop {
+        match updates.recv().await {
+            Ok(preview) if preview.template_id == id => {
+                if send(&mut socket, &preview).await.is_err() {
+                    return;
+                }
+            }
+            Ok(_) => {}
+            // Slow clients skip to the most recent previews
+            Err(broadcast::error::RecvError::Lagged(_)) => {}
+            Err(broadcast::error::RecvError::Closed) => return,
+        }
+    }
+}
+
+async fn send(socket: &mut WebSocket, preview: &Preview) -> Result<(), axum::Error> {
+    let json = serde_json::to_string(preview).unwrap_or_default();
+    socket.send(Message::Text(json.into())).await
+}
@@ -0,0 +1,102 @@
+//! Signed, expiring download links for job output
+//!
+//! `POST /jobs/{id}/outputs/{index}/link` (under `/tenants/{tenant}` for a
+//! tenant's jobs) hands out a URL like `/downloads/{token}` that serves one
+//! stored document without an API key until it expires, so links can go to
+//! end users or into emails. The token carries the job's namespace, the sink
+//! key and expiry, signed with HMAC-SHA256 using
+//! `PAPERMAKE_DOWNLOAD_SECRET`; replicas sharing the secret accept each
+//! other's links. Links are disabled without a secret.
+
+use std::time::Duration;
+
+use base64::{prelude::BASE64_URL_SAFE_NO_PAD, Engine};
+use hmac::{Hmac, Mac};
+use papermake::storage::Namespace;
+use sha2::Sha256;
+
+use crate::config::DownloadConfig;
+
+/// Signs and verifies download tokens
+#[derive(Clone)]
+pub struct DownloadSigner {
+    secret: Vec<u8>,
+    public_url: String,
+    default_ttl: Duration,
+    max_ttl: Duration,
+}
+
+impl std::fmt::Debug for DownloadSigner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DownloadSigner")
+            .field("public_url", &self.public_url)
+            .field("default_ttl", &self.default_ttl)
+            .field("max_ttl", &self.max_ttl)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A signed link and when it stops working
+#[derive(Debug, serde::Serialize)]
+pub struct DownloadLink {
+    pub url: String,
+    pub token: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub expires_at: time::OffsetDateTime,
+}
+
+impl DownloadSigner {
+    /// A signer from `PAPERMAKE_DOWNLOAD_SECRET` and `PAPERMAKE_PUBLIC_URL`,
+    /// `None` if no secret is set
+    pub fn from_env(config: &DownloadConfig) -> Option<Self> {
+        let secret = std::env::var("PAPERMAKE_DOWNLOAD_SECRET").ok().filter(|secret| !secret.is_empty())?;
+        Some(Self {
+            secret: secret.into_bytes(),
+            public_url: std::env::var("PAPERMAKE_PUBLIC_URL")
+                .unwrap_or_default()
+                .trim_end_matches('/')
+                .to_string(),
+            default_ttl: Duration::from_secs(config.ttl_secs),
+            max_ttl: Duration::from_secs(config.max_ttl_secs),
+        })
+    }
+
+    /// A link to the sink object `key` of a job in `namespace`, valid for
+    /// `ttl` (the configured default if `None`, capped at the maximum)
+    pub fn sign(&self, namespace: Option<&Namespace>, key: &str, ttl: Option<Duration>) -> DownloadLink {
+        let ttl = ttl.unwrap_or(self.default_ttl).min(self.max_ttl);
+        let expires_at = time::OffsetDateTime::now_utc() + ttl;
+        let namespace = namespace.map(Namespace::as_str).unwrap_or_default();
+        let payload = BASE64_URL_SAFE_NO_PAD.encode(format!("{}:{}:{}", expires_at.unix_timestamp(), namespace, key));
+        let token = format!("{}.{}", payload, hex::encode(self.mac(&payload).finalize().into_bytes()));
+        DownloadLink {
+            url: format!("{}/downloads/{}", self.public_url, token),
+            token,
+            expires_at,
+        }
+    }
+
+    /// The namespace and sink key of a token, if its signature is valid and
+    /// it hasn't expired
+    pub fn verify(&self, token: &str) -> Option<(Option<Namespace>, String)> {
+        let (payload, signature) = token.rsplit_once('.')?;
+        let signature = hex::decode(signature).ok()?;
+        self.mac(payload).verify_slice(&signature).ok()?;
+
+        let payload = String::from_utf8(BASE64_URL_SAFE_NO_PAD.decode(payload).ok()?).ok()?;
+        let mut parts = payload.splitn(3, ':');
+        let (expires, namespace, key) = (parts.next()?, parts.next()?, parts.next()?);
+        let expires = time::OffsetDateTime::from_unix_timestamp(expires.parse().ok()?).ok()?;
+        let namespace = match namespace {
+            "" => None,
+            namespace => Some(Namespace::new(namespace).ok()?),
+        };
+        (expires > time::OffsetDateTime::now_utc()).then(|| (namespace, key.to_string()))
+    }
+
+    fn mac(&self, payload: &str) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.secret).expect("HMAC accepts keys of any length");
+        mac.update(payload.as_bytes());
+        mac
+    }
+}
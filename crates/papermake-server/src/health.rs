@@ -0,0 +1,86 @@
+//! Liveness and readiness probes
+//!
+//! `/health/live` only tells that the process serves requests, so a probe
+//! failing it should restart the instance. `/health/ready` also checks the
+//! storage backend and the job queue and answers 503 when one of them is
+//! unreachable, or once shutdown has started, so load balancers stop routing
+//! to the instance without restarting it:
+//!
+//! ```json
+//! {"status": "unavailable", "components": {
+//!   "storage": {"status": "down", "latency_ms": 3, "error": "Permission denied"},
+//!   "queue": {"status": "up", "latency_ms": 1}}}
+//! ```
+
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use axum::{extract::State, http::StatusCode, Json};
+use serde::Serialize;
+
+use crate::AppState;
+
+/// How long each dependency gets to answer a readiness check
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Serialize)]
+pub struct HealthResponse {
+    /// `ok` or `unavailable`
+    status: &'static str,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    components: BTreeMap<&'static str, ComponentHealth>,
+}
+
+#[derive(Serialize)]
+struct ComponentHealth {
+    /// `up` or `down`
+    status: &'static str,
+    latency_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl ComponentHealth {
+    fn is_up(&self) -> bool {
+        self.status == "up"
+    }
+}
+
+/// Whether the process is running
+pub async fn live() -> Json<HealthResponse> {
+    Json(HealthResponse { status: "ok", components: BTreeMap::new() })
+}
+
+/// Whether the instance can serve requests: storage and queue respond and
+/// the server isn't draining
+pub async fn ready(State(state): State<Arc<AppState>>) -> (StatusCode, Json<HealthResponse>) {
+    let (storage, queue) = tokio::join!(check(state.storage.check_health()), check(state.queue.ping()));
+    let components = BTreeMap::from([("storage", storage), ("queue", queue)]);
+
+    let ready = components.values().all(ComponentHealth::is_up) && !state.shutdown.is_triggered();
+    let (status, label) = if ready {
+        (StatusCode::OK, "ok")
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, "unavailable")
+    };
+    (status, Json(HealthResponse { status: label, components }))
+}
+
+async fn check(probe: impl Future<Output = papermake::error::Result<()>>) -> ComponentHealth {
+    let started = Instant::now();
+    let error = match tokio::time::timeout(CHECK_TIMEOUT, probe).await {
+        Ok(Ok(())) => None,
+        Ok(Err(err)) => Some(err.to_string()),
+        Err(_) => Some(format!("No response within {:?}", CHECK_TIMEOUT)),
+    };
+    if let Some(error) = &error {
+        tracing::warn!("readiness check failed: {}", error);
+    }
+    ComponentHealth {
+        status: if error.is_none() { "up" } else { "down" },
+        latency_ms: started.elapsed().as_millis() as u64,
+        error,
+    }
+}
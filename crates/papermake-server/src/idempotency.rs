@@ -0,0 +1,244 @@
+//! Replay of render responses for retried requests
+//!
+//! Render and job submission requests may carry an `Idempotency-Key` header.
+//! The first request with a key runs as usual and its response is stored
+//! under the storage path; retries with the same key get the stored response
+//! back, marked with `Idempotent-Replayed: true`, instead of rendering again
+//! or submitting a second job (which would fire its webhook twice).
+//!
+//! Keys are scoped to the API key and the request path, which names the
+//! template, and expire after `storage.idempotency_ttl_secs`. A key reused
+//! with a different request body is rejected, as is a retry arriving while
+//! the first request is still running. Server errors and responses asking
+//! the client to retry later aren't stored, so those requests run again.
+
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use axum::body::{to_bytes, Body};
+use axum::extract::{Request, State};
+use axum::http::{header, HeaderName, HeaderValue, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use base64::prelude::{Engine, BASE64_STANDARD};
+use papermake::error::PapermakeError;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::tenants::api_key_id;
+use crate::{AppError, AppState};
+
+const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+
+/// Set on responses replayed from an earlier request
+const REPLAYED_HEADER: &str = "idempotent-replayed";
+
+/// Longest accepted `Idempotency-Key`
+const MAX_KEY_LENGTH: usize = 255;
+
+/// Expired responses are swept at most this often
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// A response stored for replay
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredResponse {
+    /// Digest of the request the response answered
+    fingerprint: String,
+    status: u16,
+    content_type: Option<String>,
+    /// Base64 encoded body
+    body: String,
+    /// Seconds since the Unix epoch
+    created_at: u64,
+}
+
+/// Responses of idempotent requests, one JSON file per key
+pub struct IdempotencyStore {
+    dir: PathBuf,
+    ttl: Duration,
+    /// Largest request body buffered to fingerprint it
+    max_body_bytes: usize,
+    /// Keys of requests still running on this server
+    in_flight: Mutex<HashSet<String>>,
+    last_sweep: Mutex<SystemTime>,
+}
+
+impl IdempotencyStore {
+    pub fn new(dir: impl Into<PathBuf>, ttl: Duration, max_body_bytes: usize) -> Self {
+        Self {
+            dir: dir.into(),
+            ttl,
+            max_body_bytes,
+            in_flight: Mutex::new(HashSet::new()),
+            last_sweep: Mutex::new(UNIX_EPOCH),
+        }
+    }
+
+    fn path(&self, scope: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", scope))
+    }
+
+    /// The stored response for `scope`, unless it expired
+    async fn load(&self, scope: &str) -> Option<StoredResponse> {
+        let json = tokio::fs::read(self.path(scope)).await.ok()?;
+        let stored: StoredResponse = serde_json::from_slice(&json).ok()?;
+        (unix_secs(SystemTime::now()) < stored.created_at + self.ttl.as_secs()).then_some(stored)
+    }
+
+    async fn save(&self, scope: &str, stored: &StoredResponse) -> std::io::Result<()> {
+        let json = serde_json::to_vec(stored)?;
+        tokio::fs::create_dir_all(&self.dir).await?;
+        let path = self.path(scope);
+        let tmp = path.with_extension("json.tmp");
+        tokio::fs::write(&tmp, json).await?;
+        tokio::fs::rename(&tmp, &path).await
+    }
+
+    /// Mark `scope` as running, or return `None` if it already is
+    fn start(self: &Arc<Self>, scope: &str) -> Option<InFlight> {
+        self.in_flight
+            .lock()
+            .unwrap()
+            .insert(scope.to_string())
+            .then(|| InFlight { store: self.clone(), scope: scope.to_string() })
+    }
+
+    /// Delete expired responses, at most once per sweep interval
+    async fn sweep(&self) {
+        {
+            let mut last_sweep = self.last_sweep.lock().unwrap();
+            let now = SystemTime::now();
+            if now.duration_since(*last_sweep).unwrap_or_default() < SWEEP_INTERVAL {
+                return;
+            }
+            *last_sweep = now;
+        }
+        let Ok(mut entries) = tokio::fs::read_dir(&self.dir).await else {
+            return;
+        };
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let expired = entry
+                .metadata()
+                .await
+                .and_then(|metadata| metadata.modified())
+                .is_ok_and(|modified| modified.elapsed().unwrap_or_default() > self.ttl);
+            if expired {
+                let _ = tokio::fs::remove_file(entry.path()).await;
+            }
+        }
+    }
+}
+
+/// Removes a running request's key when the request finishes or goes away
+struct InFlight {
+    store: Arc<IdempotencyStore>,
+    scope: String,
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.store.in_flight.lock().unwrap().remove(&self.scope);
+    }
+}
+
+/// Middleware replaying stored responses for requests with an
+/// `Idempotency-Key` header
+pub async fn idempotent(State(state): State<Arc<AppState>>, request: Request, next: Next) -> Response {
+    let Some(key) = request.headers().get(IDEMPOTENCY_KEY_HEADER) else {
+        return next.run(request).await;
+    };
+    let key = match key.to_str() {
+        Ok(key) if !key.is_empty() && key.len() <= MAX_KEY_LENGTH => key.to_string(),
+        _ => {
+            return AppError::BadRequest(format!(
+                "Idempotency-Key must be 1 to {} visible ASCII characters",
+                MAX_KEY_LENGTH
+            ))
+            .into_response();
+        }
+    };
+    let store = state.idempotency.clone();
+    let scope = hex::encode(Sha256::digest(format!(
+        "{}\n{}\n{}",
+        api_key_id(request.headers()).unwrap_or_default(),
+        request.uri().path(),
+        key
+    )));
+
+    // Buffer the body to tell retries from different requests reusing the key
+    let (parts, body) = request.into_parts();
+    let body = match to_bytes(body, store.max_body_bytes).await {
+        Ok(body) => body,
+        Err(_) => {
+            return AppError::Papermake(PapermakeError::TooLarge("Request body is too large".to_string()))
+                .into_response();
+        }
+    };
+    let mut hasher = Sha256::new();
+    hasher.update(parts.method.as_str());
+    hasher.update(parts.uri.query().unwrap_or_default());
+    hasher.update(&body);
+    let fingerprint = hex::encode(hasher.finalize());
+
+    let Some(_in_flight) = store.start(&scope) else {
+        return AppError::Conflict("A request with this Idempotency-Key is still in progress".to_string())
+            .into_response();
+    };
+    if let Some(stored) = store.load(&scope).await {
+        if stored.fingerprint != fingerprint {
+            return AppError::BadRequest("Idempotency-Key was already used for a different request".to_string())
+                .into_response();
+        }
+        return replay(stored);
+    }
+
+    let response = next.run(Request::from_parts(parts, Body::from(body))).await;
+    let status = response.status();
+    if status.is_server_error()
+        || matches!(status, StatusCode::CONFLICT | StatusCode::REQUEST_TIMEOUT | StatusCode::TOO_MANY_REQUESTS)
+    {
+        return response;
+    }
+
+    let (parts, body) = response.into_parts();
+    let Ok(body) = to_bytes(body, usize::MAX).await else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+    let stored = StoredResponse {
+        fingerprint,
+        status: status.as_u16(),
+        content_type: parts
+            .headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string),
+        body: BASE64_STANDARD.encode(&body),
+        created_at: unix_secs(SystemTime::now()),
+    };
+    if let Err(err) = store.save(&scope, &stored).await {
+        tracing::warn!("failed to store idempotent response: {}", err);
+    }
+    store.sweep().await;
+    Response::from_parts(parts, Body::from(body))
+}
+
+fn replay(stored: StoredResponse) -> Response {
+    let Ok(body) = BASE64_STANDARD.decode(&stored.body) else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+    let mut response = Response::new(Body::from(body));
+    *response.status_mut() = StatusCode::from_u16(stored.status).unwrap_or(StatusCode::OK);
+    if let Some(content_type) = stored.content_type.and_then(|value| HeaderValue::from_str(&value).ok()) {
+        response.headers_mut().insert(header::CONTENT_TYPE, content_type);
+    }
+    response
+        .headers_mut()
+        .insert(HeaderName::from_static(REPLAYED_HEADER), HeaderValue::from_static("true"));
+    response
+}
+
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
@@ -0,0 +1,160 @@
+//! Asynchronous render jobs and their in-memory store
+
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::time::Duration;
+
+use papermake::render::RenderError;
+use papermake::storage::Namespace;
+use papermake::BatchItem;
+use serde::{Deserialize, Serialize};
+
+use crate::webhook::WebhookTarget;
+
+/// Lifecycle state of a render job
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Completed,
+    Failed,
+}
+
+/// An asynchronous render job and, once finished, where its output was written
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Job {
+    pub id: String,
+    pub template_id: String,
+    /// Tenant namespace the job was submitted to; only that tenant can
+    /// read it
+    #[serde(default)]
+    pub namespace: Option<String>,
+    pub status: JobStatus,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: time::OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub finished_at: Option<time::OffsetDateTime>,
+    pub duration_ms: Option<u64>,
+    /// One entry per rendered document, written to the output sink
+    pub outputs: Vec<BatchItem>,
+    pub errors: Vec<RenderError>,
+    pub webhook: Option<WebhookTarget>,
+}
+
+impl Job {
+    pub fn new(template_id: impl Into<String>, namespace: Option<&Namespace>, webhook: Option<WebhookTarget>) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            template_id: template_id.into(),
+            namespace: namespace.map(|ns| ns.as_str().to_string()),
+            status: JobStatus::Queued,
+            created_at: time::OffsetDateTime::now_utc(),
+            finished_at: None,
+            duration_ms: None,
+            outputs: Vec::new(),
+            errors: Vec::new(),
+            webhook,
+        }
+    }
+
+    /// Total size of all written documents
+    pub fn size_bytes(&self) -> Option<usize> {
+        self.outputs.iter().filter_map(|item| item.size_bytes).reduce(|a, b| a + b)
+    }
+
+    /// Whether the job was submitted to `namespace`
+    pub fn belongs_to(&self, namespace: Option<&Namespace>) -> bool {
+        self.namespace.as_deref() == namespace.map(Namespace::as_str)
+    }
+}
+
+/// Serializable view of a job
+#[derive(Debug, Serialize)]
+pub struct JobResponse {
+    pub id: String,
+    pub template_id: String,
+    pub status: JobStatus,
+    pub created_at: String,
+    pub finished_at: Option<String>,
+    pub duration_ms: Option<u64>,
+    pub size_bytes: Option<usize>,
+    pub outputs: Vec<BatchItem>,
+    pub errors: Vec<RenderError>,
+}
+
+impl From<&Job> for JobResponse {
+    fn from(job: &Job) -> Self {
+        Self {
+            id: job.id.clone(),
+            template_id: job.template_id.clone(),
+            status: job.status,
+            created_at: job.created_at.to_string(),
+            finished_at: job.finished_at.map(|t| t.to_string()),
+            duration_ms: job.duration_ms,
+            size_bytes: job.size_bytes(),
+            outputs: job.outputs.clone(),
+            errors: job.errors.clone(),
+        }
+    }
+}
+
+/// Thread-safe job registry of a single server
+///
+/// Finished jobs expire after `ttl`, and once more than `max_jobs` are
+/// stored the oldest finished ones are dropped. Queued and running jobs are
+/// always kept.
+#[derive(Debug)]
+pub struct JobStore {
+    jobs: RwLock<HashMap<String, Job>>,
+    max_jobs: usize,
+    ttl: Duration,
+}
+
+impl JobStore {
+    pub fn new(max_jobs: usize, ttl: Duration) -> Self {
+        Self {
+            jobs: RwLock::new(HashMap::new()),
+            max_jobs,
+            ttl,
+        }
+    }
+
+    pub fn insert(&self, job: Job) {
+        let mut jobs = self.jobs.write().unwrap();
+        let added = jobs.insert(job.id.clone(), job).is_none();
+        if added {
+            Self::evict(&mut jobs, self.max_jobs, time::OffsetDateTime::now_utc() - self.ttl);
+        }
+    }
+
+    /// Drop jobs that finished before `expired`, then the oldest finished
+    /// jobs until at most `max_jobs` are left
+    fn evict(jobs: &mut HashMap<String, Job>, max_jobs: usize, expired: time::OffsetDateTime) {
+        jobs.retain(|_, job| job.finished_at.is_none_or(|finished| finished >= expired));
+        if jobs.len() <= max_jobs {
+            return;
+        }
+        let mut finished: Vec<_> = jobs
+            .values()
+            .filter_map(|job| Some((job.finished_at?, job.id.clone())))
+            .collect();
+        finished.sort();
+        let excess = jobs.len() - max_jobs;
+        for (_, id) in finished.into_iter().take(excess) {
+            jobs.remove(&id);
+        }
+    }
+
+    pub fn get(&self, id: &str) -> Option<Job> {
+        self.jobs.read().unwrap().get(id).cloned()
+    }
+
+    /// Drop jobs that finished before `before`, returning how many
+    pub fn purge_finished(&self, before: time::OffsetDateTime) -> usize {
+        let mut jobs = self.jobs.write().unwrap();
+        let count = jobs.len();
+        Self::evict(&mut jobs, usize::MAX, before);
+        count - jobs.len()
+    }
+}
@@ -0,0 +1,201 @@
+//! Request rate limits and monthly render quotas
+//!
+//! Every request takes a token from the bucket of its client IP and, when
+//! made with an API key, from the bucket of that key. Renders additionally
+//! count against the key's monthly quota, which is persisted under the
+//! storage path so it survives restarts. Exceeding either is answered with
+//! `429 Too Many Requests` and a `Retry-After` header.
+
+use std::collections::{BTreeMap, HashMap};
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::extract::{ConnectInfo, Request, State};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use papermake::error::{PapermakeError, Result};
+
+use crate::config::LimitsConfig;
+use crate::tenants::api_key_id;
+use crate::{AppError, AppState};
+
+/// Buckets are pruned once this many clients are tracked
+const MAX_BUCKETS: usize = 10_000;
+
+/// A token bucket refilled continuously at `per_minute` tokens per minute
+#[derive(Debug, Clone, Copy)]
+pub struct Rate {
+    pub per_minute: u32,
+    /// Tokens available after a pause, i.e. the largest burst
+    pub burst: u32,
+}
+
+impl Rate {
+    fn per_second(&self) -> f64 {
+        self.per_minute as f64 / 60.0
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Token buckets per API key and per client IP
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    per_key: Option<Rate>,
+    per_ip: Option<Rate>,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(per_key: Option<Rate>, per_ip: Option<Rate>) -> Self {
+        Self { per_key, per_ip, buckets: Mutex::new(HashMap::new()) }
+    }
+
+    /// Limits in requests per minute, with bursts of up to `rate_limit_burst`
+    /// requests (default: a minute's worth)
+    pub fn from_config(limits: &LimitsConfig) -> Self {
+        let rate = |per_minute: Option<u32>| {
+            per_minute.map(|per_minute| Rate { per_minute, burst: limits.rate_limit_burst.unwrap_or(per_minute) })
+        };
+        Self::new(rate(limits.rate_limit_per_key), rate(limits.rate_limit_per_ip))
+    }
+
+    /// Take a token for a request, or return how long until one is available
+    pub fn check(&self, api_key_id: Option<&str>, ip: Option<IpAddr>) -> std::result::Result<(), Duration> {
+        let mut buckets = self.buckets.lock().unwrap();
+        let now = Instant::now();
+        if buckets.len() > MAX_BUCKETS {
+            // Clients idle long enough to have refilled their bucket
+            buckets.retain(|_, bucket| now.duration_since(bucket.updated) < Duration::from_secs(60));
+        }
+
+        let mut checks = Vec::new();
+        if let (Some(rate), Some(ip)) = (self.per_ip, ip) {
+            checks.push((format!("ip:{}", ip), rate));
+        }
+        if let (Some(rate), Some(key)) = (self.per_key, api_key_id) {
+            checks.push((format!("key:{}", key), rate));
+        }
+
+        // Refill all buckets first so a denied request doesn't use up tokens
+        let mut wait = Duration::ZERO;
+        for (name, rate) in &checks {
+            let bucket = buckets.entry(name.clone()).or_insert(Bucket { tokens: rate.burst as f64, updated: now });
+            let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+            bucket.tokens = (bucket.tokens + elapsed * rate.per_second()).min(rate.burst as f64);
+            bucket.updated = now;
+            if bucket.tokens < 1.0 {
+                wait = wait.max(Duration::from_secs_f64((1.0 - bucket.tokens) / rate.per_second()));
+            }
+        }
+        if !wait.is_zero() {
+            return Err(wait);
+        }
+        for (name, _) in &checks {
+            if let Some(bucket) = buckets.get_mut(name) {
+                bucket.tokens -= 1.0;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Middleware applying the server's rate limits
+pub async fn rate_limit(State(state): State<Arc<AppState>>, request: Request, next: Next) -> Response {
+    let ip = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip());
+    let api_key_id = api_key_id(request.headers());
+    match state.rate_limiter.check(api_key_id.as_deref(), ip) {
+        Ok(()) => next.run(request).await,
+        Err(retry_after) => AppError::TooManyRequests {
+            message: "Rate limit exceeded".to_string(),
+            retry_after,
+        }
+        .into_response(),
+    }
+}
+
+/// Monthly render counts per API key, persisted as one JSON file per month
+pub struct QuotaStore {
+    dir: PathBuf,
+    /// Renders per key per month; unlimited if unset
+    monthly_renders: Option<u64>,
+    /// Counts of the current month, loaded lazily
+    counts: tokio::sync::Mutex<Option<(String, BTreeMap<String, u64>)>>,
+}
+
+impl QuotaStore {
+    pub fn new(dir: impl Into<PathBuf>, monthly_renders: Option<u64>) -> Self {
+        Self { dir: dir.into(), monthly_renders, counts: tokio::sync::Mutex::new(None) }
+    }
+
+    /// Count `renders` against the key's quota, rejecting them as a whole if
+    /// they would exceed it. Requests without an API key aren't metered.
+    pub async fn consume(&self, api_key_id: Option<&str>, renders: u64) -> std::result::Result<(), AppError> {
+        let (Some(limit), Some(key)) = (self.monthly_renders, api_key_id) else {
+            return Ok(());
+        };
+
+        let now = time::OffsetDateTime::now_utc();
+        let month = format!("{:04}-{:02}", now.year(), now.month() as u8);
+        let mut counts = self.counts.lock().await;
+        if counts.as_ref().is_none_or(|(loaded, _)| *loaded != month) {
+            *counts = Some((month.clone(), self.load(&month).await?));
+        }
+        let Some((_, counts)) = counts.as_mut() else {
+            return Ok(());
+        };
+
+        let used = counts.get(key).copied().unwrap_or(0);
+        if used + renders > limit {
+            return Err(AppError::TooManyRequests {
+                message: format!("Monthly render quota of {} exceeded", limit),
+                retry_after: until_next_month(now),
+            });
+        }
+        counts.insert(key.to_string(), used + renders);
+        self.save(&month, counts).await?;
+        Ok(())
+    }
+
+    async fn load(&self, month: &str) -> Result<BTreeMap<String, u64>> {
+        match tokio::fs::read(self.dir.join(format!("{}.json", month))).await {
+            Ok(json) => serde_json::from_slice(&json)
+                .map_err(|e| PapermakeError::Storage(format!("Invalid quota file: {}", e))),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn save(&self, month: &str, counts: &BTreeMap<String, u64>) -> Result<()> {
+        let json = serde_json::to_vec_pretty(counts)
+            .map_err(|e| PapermakeError::Storage(format!("Failed to serialize quotas: {}", e)))?;
+        tokio::fs::create_dir_all(&self.dir).await?;
+        let path = self.dir.join(format!("{}.json", month));
+        let tmp = path.with_extension("json.tmp");
+        tokio::fs::write(&tmp, json).await?;
+        tokio::fs::rename(&tmp, &path).await?;
+        Ok(())
+    }
+}
+
+/// Time until the first day of the next month, UTC
+fn until_next_month(now: time::OffsetDateTime) -> Duration {
+    let (year, month) = match now.month() {
+        time::Month::December => (now.year() + 1, time::Month::January),
+        month => (now.year(), month.next()),
+    };
+    time::Date::from_calendar_date(year, month, 1)
+        .map(|date| date.midnight().assume_utc() - now)
+        .ok()
+        .and_then(|remaining| remaining.try_into().ok())
+        .unwrap_or(Duration::from_secs(24 * 60 * 60))
+}
@@ -1,26 +1,107 @@
+mod config;
+mod datasource;
+mod dev;
+mod downloads;
+mod health;
+mod idempotency;
+mod jobs;
+mod limits;
+mod metrics;
+mod queue;
+mod retention;
+mod scheduler;
+mod shutdown;
+mod stream;
+mod tenants;
+mod uploads;
+mod webhook;
+
+use std::collections::BTreeMap;
 use std::net::SocketAddr;
-use std::path::PathBuf;
 use std::sync::Arc;
 
 use axum::{
-    extract::{Path, State},
+    extract::{DefaultBodyLimit, Multipart, Path, Query, State},
+    middleware,
     http::StatusCode,
+    http::{header, HeaderMap},
     response::IntoResponse,
     routing::{get, post},
-    Json, Router,
+    Form, Json, Router,
 };
 use base64::{prelude::BASE64_STANDARD, Engine};
 use papermake::{
-    error::PapermakeError, render::{render_pdf, RenderError, RenderOptions}, storage::{FileStorage, Storage}, template::{Template, TemplateId}
+    error::{ErrorCode, PapermakeError}, render::{prepare_data, RenderError, RenderOptions}, storage::{FileStorage, ListOptions, Namespace, Storage, TemplateSort}, line_diff::{merge, MergeConflict}, template::{Template, TemplateId}, testing::{run_examples, ExampleReport}, lint::LintReport, analyze_template, Analysis, CheckReport, render_merged, resolve_shared, form_data, Dependent, PdfEncryption, WorldPool,
+    sink::{FileSink, RenderSink, S3Sink}, render_batch, BatchItem,
+    CachePolicy, DiskRenderCache, MemoryRenderCache, RenderCache,
+    diff::{compile_document, diff_documents, DiffOptions, DiffReport},
+    FileRenderHistory, RenderHistory, RenderRecord, FileRenderStats, RenderStats, TemplateStats, SandboxPolicy, SizeLimits, PageSelection, AttachmentRelationship, PdfAttachment, ChartSpec,
+    lifecycle::{archive_template, asset_manifest, publish_template, save_draft, template_for_render}, TemplateStatus, TemplateVersion, SampleOptions, ScaffoldStyle, TransformSpec, OptimizationReport, OptimizeLevel, DocumentMetadata, EnvironmentConfig, Watermark, WatermarkPages, RemoteResources, EncryptedRenderCache, EncryptedStorage, EncryptionKey,
+    render_all, OutputFormat, RenderBundle, PageText, TemplateChanges, render_preview, PreviewFormat, PreviewOptions,
+    BlobStore, DedupStorage, FileBlobStore
 };
 use serde::{Deserialize, Serialize};
+use axum_server::tls_rustls::RustlsConfig;
+use tower_http::timeout::TimeoutLayer;
 use tower_http::trace::TraceLayer;
-use tower_http::cors::CorsLayer;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
 
+use crate::config::{RenderCacheKind, ServerConfig};
+use crate::datasource::{DataFetcher, DataInput};
+use crate::dev::{dev_routes, DevWorkspace};
+use crate::downloads::{DownloadLink, DownloadSigner};
+use crate::idempotency::{idempotent, IdempotencyStore};
+use crate::jobs::{Job, JobResponse, JobStatus};
+use crate::limits::{rate_limit, QuotaStore, RateLimiter};
+use crate::metrics::{InstrumentedStorage, Metrics};
+use crate::queue::{queue_from_env, JobQueue, JobTask, JobWork};
+use crate::scheduler::{RenderPermit, RenderScheduler};
+use crate::shutdown::Shutdown;
+use crate::stream::render_stream;
+use crate::tenants::{Tenant, TenantHistory, TenantKeys, TenantPool, TenantStorage};
+use crate::uploads::{validate_content_type, UploadLimits};
+use crate::webhook::{WebhookNotifier, WebhookTarget};
+
+/// How long job consumers wait when the queue is empty
+const QUEUE_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(250);
+
 // Application state with shared storage
 struct AppState {
     storage: Arc<dyn Storage>,
+    world_pool: Arc<WorldPool>,
+    sink: Arc<dyn RenderSink>,
+    /// Asynchronous render jobs, shared with other replicas (`PAPERMAKE_QUEUE`)
+    queue: Arc<dyn JobQueue>,
+    webhooks: WebhookNotifier,
+    metrics: Arc<Metrics>,
+    tenants: TenantKeys,
+    render_cache: Option<Arc<dyn RenderCache>>,
+    upload_limits: UploadLimits,
+    /// Sizes of render data, template sources and assets
+    size_limits: SizeLimits,
+    /// Fetches render data referenced by URL or S3 key
+    data_fetcher: DataFetcher,
+    /// Responses replayed for retried requests with an `Idempotency-Key`
+    idempotency: Arc<IdempotencyStore>,
+    rate_limiter: RateLimiter,
+    /// Concurrent renders per template and per API key
+    scheduler: Arc<RenderScheduler>,
+    quotas: QuotaStore,
+    history: Arc<dyn RenderHistory>,
+    /// Per-template render counts, durations and failure rates
+    stats: Arc<dyn RenderStats>,
+    /// Sandbox applied to every render (`PAPERMAKE_SANDBOX=restrictive`)
+    sandbox: Option<SandboxPolicy>,
+    /// Whether render records keep the input data (`PAPERMAKE_ARCHIVE_INPUTS`)
+    archive_inputs: bool,
+    /// Environment of renders that don't name one (`PAPERMAKE_ENVIRONMENT`)
+    environment: Option<String>,
+    /// Hosts templates may load files from by URL, sharing one cache
+    remote_resources: Option<RemoteResources>,
+    /// Signs download links to job output (`PAPERMAKE_DOWNLOAD_SECRET`)
+    downloads: Option<DownloadSigner>,
+    /// Readiness fails once shutdown has started
+    shutdown: Shutdown,
 }
 
 // Request and response types
@@ -31,6 +112,21 @@ struct CreateTemplateRequest {
     content: String,
     schema: papermake::schema::Schema,
     description: Option<String>,
+    #[serde(default)]
+    examples: BTreeMap<String, serde_json::Value>,
+    #[serde(default)]
+    shared: bool,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    metadata: BTreeMap<String, String>,
+    #[serde(default)]
+    variants: BTreeMap<String, String>,
+    sandbox: Option<SandboxPolicy>,
+    #[serde(default)]
+    environments: BTreeMap<String, EnvironmentConfig>,
+    /// Rhai script computing fields before rendering
+    script: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -39,24 +135,497 @@ struct UpdateTemplateRequest {
     content: Option<String>,
     schema: Option<papermake::schema::Schema>,
     description: Option<String>,
+    examples: Option<BTreeMap<String, serde_json::Value>>,
+    shared: Option<bool>,
+    tags: Option<Vec<String>>,
+    metadata: Option<BTreeMap<String, String>>,
+    variants: Option<BTreeMap<String, String>>,
+    sandbox: Option<SandboxPolicy>,
+    environments: Option<BTreeMap<String, EnvironmentConfig>>,
+    script: Option<String>,
+}
+
+/// Content changes made by an editor since `base_revision`, merged with
+/// changes others saved in the meantime
+#[derive(Deserialize)]
+struct PatchTemplateRequest {
+    /// Revision the editor started from
+    base_revision: u64,
+    /// Content at `base_revision`
+    base: String,
+    /// The editor's content
+    content: String,
+}
+
+#[derive(Serialize)]
+struct MergeConflictResponse {
+    error: String,
+    code: &'static str,
+    /// Current revision, to base the resolved content on
+    revision: u64,
+    /// Merged content with conflict markers
+    content: String,
+    conflicts: Vec<MergeConflict>,
+}
+
+#[derive(Deserialize)]
+struct ListTemplatesQuery {
+    shared: Option<bool>,
+    /// Case-insensitive substring of the template name
+    name: Option<String>,
+    limit: Option<usize>,
+    cursor: Option<String>,
+    /// `id` (default), `updated_at` or `-updated_at`
+    sort: Option<String>,
+    /// Comma-separated tags, all of which must be present
+    tag: Option<String>,
+    /// Comma-separated `key:value` metadata entries, all of which must match
+    meta: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SearchTemplatesQuery {
+    /// Whitespace-separated terms, all of which must match
+    q: String,
+    limit: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct SearchHitResponse {
+    #[serde(flatten)]
+    template: TemplateResponse,
+    score: u32,
 }
 
 #[derive(Deserialize)]
 struct RenderTemplateRequest {
-    data: serde_json::Value,
+    #[serde(flatten)]
+    data: DataInput,
     options: Option<RenderOptionsRequest>,
+    /// Locale (e.g. `de-DE`) selecting the template variant
+    locale: Option<String>,
 }
 
 #[derive(Deserialize)]
+struct PreviewRequest {
+    #[serde(flatten)]
+    data: DataInput,
+    options: Option<RenderOptionsRequest>,
+    locale: Option<String>,
+    /// Resolution, pages, maximum width, image format and quality
+    #[serde(default)]
+    preview: PreviewOptions,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct RenderOptionsRequest {
     paper_size: Option<String>,
     compress: Option<bool>,
+    /// Size optimization of the PDF: `none`, `lossless`, `balanced` or `aggressive`
+    optimize: Option<OptimizeLevel>,
+    coerce_data: Option<bool>,
+    bookmark_field: Option<String>,
+    encryption: Option<EncryptionRequest>,
+    #[serde(default)]
+    transforms: Vec<TransformSpec>,
+    /// Byte-identical output for identical requests
+    deterministic: Option<bool>,
+    /// Pages to export, e.g. `1-3,5`
+    pages: Option<PageSelection>,
+    /// Environment selecting the template's settings; the server's by default
+    environment: Option<String>,
+    /// RFC 3339 time the template sees as the current time
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    now: Option<time::OffsetDateTime>,
+    /// UTC offset `datetime.today()` gives the date in, e.g. `+02:00`
+    timezone: Option<String>,
+    /// Stamp drawn over the pages, e.g. `CONFIDENTIAL`
+    watermark: Option<WatermarkRequest>,
+    /// Files embedded in the PDF
+    #[serde(default)]
+    attachments: Vec<AttachmentRequest>,
+    /// Charts the template loads as `chart:<name>.svg`
+    #[serde(default)]
+    charts: BTreeMap<String, ChartSpec>,
+    /// Return the positioned text of each page with the result
+    extract_text: Option<bool>,
+    /// Named inputs besides the data, e.g. `requesting_user`, which
+    /// templates read from `sys.inputs.extra`
+    #[serde(default)]
+    extra_inputs: BTreeMap<String, serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct AttachmentRequest {
+    filename: String,
+    /// Text content, e.g. an XML invoice; the input data as JSON if absent
+    content: Option<String>,
+    mime_type: Option<String>,
+    description: Option<String>,
+    #[serde(default)]
+    relationship: AttachmentRelationship,
+}
+
+impl From<AttachmentRequest> for PdfAttachment {
+    fn from(req: AttachmentRequest) -> Self {
+        let attachment = match req.content {
+            Some(content) => {
+                let mime_type = req.mime_type.unwrap_or_else(|| "text/plain".to_string());
+                PdfAttachment::bytes(req.filename, mime_type, content.into_bytes())
+            }
+            None => PdfAttachment::input_data(req.filename),
+        };
+        PdfAttachment {
+            description: req.description,
+            relationship: req.relationship,
+            ..attachment
+        }
+    }
+}
+
+// A UTC offset like `+02:00`, `-0530` or `Z`
+fn parse_timezone(timezone: &str) -> papermake::Result<time::UtcOffset> {
+    let invalid = || PapermakeError::InvalidInput(format!("Invalid timezone '{}', expected an offset like +02:00", timezone));
+    if timezone == "Z" || timezone == "UTC" {
+        return Ok(time::UtcOffset::UTC);
+    }
+    let (sign, digits) = if let Some(digits) = timezone.strip_prefix('+') {
+        (1, digits)
+    } else if let Some(digits) = timezone.strip_prefix('-') {
+        (-1, digits)
+    } else {
+        return Err(invalid());
+    };
+    let digits = digits.replace(':', "");
+    if !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(invalid());
+    }
+    let (hours, minutes) = match digits.len() {
+        2 => (digits.parse::<i8>().map_err(|_| invalid())?, 0),
+        4 => (
+            digits[..2].parse::<i8>().map_err(|_| invalid())?,
+            digits[2..].parse::<i8>().map_err(|_| invalid())?,
+        ),
+        _ => return Err(invalid()),
+    };
+    time::UtcOffset::from_hms(sign * hours, sign * minutes, 0).map_err(|_| invalid())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct WatermarkRequest {
+    text: Option<String>,
+    /// PNG or JPEG image shown instead of text
+    image_base64: Option<String>,
+    /// From 0 to 1; 0.25 by default
+    opacity: Option<f32>,
+    /// Counter-clockwise degrees; 45 for text and 0 for images by default
+    rotation: Option<f32>,
+    #[serde(default)]
+    pages: WatermarkPages,
+}
+
+impl WatermarkRequest {
+    fn into_watermark(self) -> papermake::Result<Watermark> {
+        let mut watermark = match (self.text, self.image_base64) {
+            (Some(text), None) => Watermark::text(text),
+            (None, Some(image)) => Watermark::image(
+                BASE64_STANDARD
+                    .decode(image)
+                    .map_err(|e| PapermakeError::InvalidInput(format!("Invalid watermark image: {}", e)))?,
+            ),
+            _ => {
+                return Err(PapermakeError::InvalidInput(
+                    "A watermark needs either text or image_base64".to_string(),
+                ))
+            }
+        };
+        watermark.opacity = self.opacity.unwrap_or(watermark.opacity);
+        watermark.rotation = self.rotation.unwrap_or(watermark.rotation);
+        watermark.pages = self.pages;
+        Ok(watermark)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct EncryptionRequest {
+    owner_password: String,
+    user_password: Option<String>,
+    #[serde(default)]
+    no_print: bool,
+    #[serde(default)]
+    no_copy: bool,
+}
+
+impl From<EncryptionRequest> for PdfEncryption {
+    fn from(req: EncryptionRequest) -> Self {
+        PdfEncryption {
+            owner_password: req.owner_password,
+            user_password: req.user_password.unwrap_or_default(),
+            allow_print: !req.no_print,
+            allow_copy: !req.no_copy,
+        }
+    }
+}
+
+impl From<RenderOptionsRequest> for RenderOptions {
+    fn from(opts: RenderOptionsRequest) -> Self {
+        RenderOptions {
+            paper_size: opts.paper_size.unwrap_or_else(|| "a4".to_string()),
+            compress: opts.compress.unwrap_or(true),
+            optimize: opts.optimize.unwrap_or_default(),
+            coerce_data: opts.coerce_data.unwrap_or(false),
+            bookmark_field: opts.bookmark_field,
+            encryption: opts.encryption.map(PdfEncryption::from),
+            transforms: opts.transforms.into_iter().collect(),
+            deterministic: opts.deterministic.unwrap_or(false),
+            pages: opts.pages,
+            environment: opts.environment,
+            now: opts.now,
+            attachments: opts.attachments.into_iter().map(PdfAttachment::from).collect(),
+            charts: opts.charts,
+            extract_text: opts.extract_text.unwrap_or(false),
+            extra_inputs: opts.extra_inputs,
+            ..RenderOptions::default()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct RenderJobRequest {
+    #[serde(flatten)]
+    data: DataInput,
+    options: Option<RenderOptionsRequest>,
+    /// Formats exported from a single compilation, e.g. `["pdf", "png"]`
+    /// for a PDF with page thumbnails; just the PDF if left out
+    #[serde(default)]
+    formats: Vec<OutputFormat>,
+    webhook: Option<WebhookTarget>,
+}
+
+#[derive(Deserialize)]
+struct DownloadLinkQuery {
+    /// Seconds the link stays valid; the configured default if unset
+    ttl_secs: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct RenderBatchRequest {
+    records: Vec<serde_json::Value>,
+    options: Option<RenderOptionsRequest>,
+    webhook: Option<WebhookTarget>,
+}
+
+#[derive(Deserialize)]
+struct RenderMergedRequest {
+    records: Vec<serde_json::Value>,
+    options: Option<RenderOptionsRequest>,
+}
+
+#[derive(Deserialize)]
+struct CloneTemplateRequest {
+    /// Id of the copy
+    id: String,
+    /// Name of the copy; the source's name if absent
+    name: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ScaffoldRequest {
+    schema: papermake::schema::Schema,
+    #[serde(default)]
+    style: ScaffoldStyle,
+}
+
+#[derive(Serialize)]
+struct ScaffoldResponse {
+    content: String,
+}
+
+#[derive(Deserialize)]
+struct UpdateTemplateQuery {
+    /// Apply schema changes that break existing callers
+    #[serde(default)]
+    allow_breaking: bool,
+}
+
+#[derive(Deserialize)]
+struct RenderVersionQuery {
+    version: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RenderFormQuery {
+    version: Option<String>,
+    locale: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct CheckQuery {
+    locale: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TemplatePath {
+    #[serde(deserialize_with = "template_id")]
+    id: String,
+}
+
+// Reject ids that can't name a template directory, so axum answers 400
+// before the request reaches storage
+fn template_id<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    let id = String::deserialize(deserializer)?;
+    papermake::storage::validate_template_id(&TemplateId(id.clone())).map_err(serde::de::Error::custom)?;
+    Ok(id)
+}
+
+#[derive(Deserialize)]
+struct VersionDiffPath {
+    #[serde(deserialize_with = "template_id")]
+    id: String,
+    /// `published` or `draft`
+    a: String,
+    b: String,
+}
+
+#[derive(Deserialize)]
+struct JobPath {
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct JobOutputPath {
+    id: String,
+    index: usize,
+}
+
+#[derive(Deserialize)]
+struct TemplateFilePath {
+    #[serde(deserialize_with = "template_id")]
+    id: String,
+    path: String,
+}
+
+#[derive(Deserialize)]
+struct UploadFilesQuery {
+    /// Directory the uploaded files are stored in
+    dir: Option<String>,
+}
+
+#[derive(Serialize)]
+struct UploadedFile {
+    path: String,
+    size_bytes: usize,
+    content_type: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RenameFileRequest {
+    to: String,
+}
+
+#[derive(Deserialize)]
+struct DiffQuery {
+    /// Version the draft is compared against; `published` by default
+    against: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct DiffRequest {
+    /// Data rendered with both versions; generated from the schema if absent
+    data: Option<serde_json::Value>,
+    options: Option<RenderOptionsRequest>,
+    /// Largest per-channel pixel difference still treated as equal
+    #[serde(default)]
+    tolerance: u8,
+}
+
+#[derive(Deserialize)]
+struct AnalyzeRequest {
+    /// Unsaved source from the editor; the stored working copy if absent
+    content: Option<String>,
+    /// Unsaved schema from the editor
+    schema: Option<papermake::schema::Schema>,
+    /// Byte offset of the cursor, for completions and hover docs
+    cursor: Option<usize>,
+    /// Data compiled with; an example or sample data if absent
+    data: Option<serde_json::Value>,
+    options: Option<RenderOptionsRequest>,
+}
+
+#[derive(Deserialize)]
+struct SampleDataQuery {
+    #[serde(default)]
+    seed: u64,
+    array_len: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct ImportTemplateQuery {
+    #[serde(default)]
+    overwrite: bool,
 }
 
 #[derive(Serialize)]
 struct RenderResultResponse {
     pdf_base64: Option<String>,
     errors: Vec<RenderError>,
+    /// Non-fatal Typst diagnostics, e.g. unknown fonts
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    warnings: Vec<RenderError>,
+    cached: bool,
+    /// Sizes before and after optimizing the PDF
+    #[serde(skip_serializing_if = "Option::is_none")]
+    optimization: Option<OptimizationReport>,
+    /// Page count, outline and named destinations of the PDF
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metadata: Option<DocumentMetadata>,
+    /// Positioned text of each page, if `extract_text` was requested
+    #[serde(skip_serializing_if = "Option::is_none")]
+    text: Option<Vec<PageText>>,
+    /// Id of the render's audit record
+    render_id: String,
+}
+
+#[derive(Serialize)]
+struct PreviewResponse {
+    format: PreviewFormat,
+    pages: Vec<PreviewPageResponse>,
+    errors: Vec<RenderError>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    warnings: Vec<RenderError>,
+}
+
+#[derive(Serialize)]
+struct PreviewPageResponse {
+    page: usize,
+    width: u32,
+    height: u32,
+    image_base64: String,
+}
+
+#[derive(Deserialize)]
+struct ListRendersQuery {
+    limit: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct RenderPath {
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct GetRenderQuery {
+    /// Include the archived input data
+    #[serde(default)]
+    inputs: bool,
+}
+
+#[derive(Serialize)]
+struct RenderRecordResponse {
+    #[serde(flatten)]
+    record: RenderRecord,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    inputs: Option<serde_json::Value>,
 }
 
 #[derive(Serialize)]
@@ -66,6 +635,20 @@ struct TemplateResponse {
     schema: papermake::schema::Schema,
     content: String,
     description: Option<String>,
+    examples: BTreeMap<String, serde_json::Value>,
+    shared: bool,
+    tags: Vec<String>,
+    metadata: BTreeMap<String, String>,
+    variants: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sandbox: Option<SandboxPolicy>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    environments: BTreeMap<String, EnvironmentConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    script: Option<String>,
+    revision: u64,
+    status: TemplateStatus,
+    published_at: Option<String>,
     created_at: String,
     updated_at: String,
 }
@@ -78,6 +661,17 @@ impl From<Template> for TemplateResponse {
             schema: template.schema,
             content: template.content,
             description: template.description,
+            examples: template.examples,
+            shared: template.shared,
+            tags: template.tags,
+            metadata: template.metadata,
+            variants: template.variants,
+            sandbox: template.sandbox,
+            environments: template.environments,
+            script: template.script,
+            revision: template.revision,
+            status: template.status,
+            published_at: template.published_at.map(|t| t.to_string()),
             created_at: template.created_at.to_string(),
             updated_at: template.updated_at.to_string(),
         }
@@ -88,7 +682,11 @@ impl From<Template> for TemplateResponse {
 enum AppError {
     Papermake(PapermakeError),
     NotFound,
+    Unauthorized,
     BadRequest(String),
+    Conflict(String),
+    TooManyRequests { message: String, retry_after: std::time::Duration },
+    ServiceUnavailable { message: String, retry_after: std::time::Duration },
 }
 
 impl From<PapermakeError> for AppError {
@@ -99,49 +697,204 @@ impl From<PapermakeError> for AppError {
 
 impl IntoResponse for AppError {
     fn into_response(self) -> axum::response::Response {
-        let (status, error_message) = match self {
-            Self::Papermake(err) => (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()),
-            Self::NotFound => (StatusCode::NOT_FOUND, "Resource not found".to_string()),
-            Self::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg),
+        let (status, code, error_message) = match self {
+            Self::Papermake(err) => {
+                let code = err.code();
+                let status = match code {
+                    ErrorCode::TemplateNotFound | ErrorCode::NotFound => StatusCode::NOT_FOUND,
+                    ErrorCode::SchemaValidation | ErrorCode::InvalidInput | ErrorCode::TemplateInvalid => {
+                        StatusCode::BAD_REQUEST
+                    }
+                    ErrorCode::CompileError => StatusCode::UNPROCESSABLE_ENTITY,
+                    ErrorCode::Timeout => StatusCode::GATEWAY_TIMEOUT,
+                    ErrorCode::Conflict => StatusCode::CONFLICT,
+                    ErrorCode::TooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+                    ErrorCode::RenderError | ErrorCode::StorageIo => StatusCode::INTERNAL_SERVER_ERROR,
+                };
+                (status, code.as_str(), err.to_string())
+            }
+            Self::NotFound => (StatusCode::NOT_FOUND, "NOT_FOUND", "Resource not found".to_string()),
+            Self::Unauthorized => (StatusCode::UNAUTHORIZED, "UNAUTHORIZED", "Invalid or missing API key".to_string()),
+            Self::BadRequest(msg) => (StatusCode::BAD_REQUEST, "BAD_REQUEST", msg),
+            Self::Conflict(msg) => (StatusCode::CONFLICT, "CONFLICT", msg),
+            Self::TooManyRequests { message, retry_after } => {
+                return retry_later(StatusCode::TOO_MANY_REQUESTS, "RATE_LIMITED", message, retry_after);
+            }
+            Self::ServiceUnavailable { message, retry_after } => {
+                return retry_later(StatusCode::SERVICE_UNAVAILABLE, "UNAVAILABLE", message, retry_after);
+            }
         };
 
-        (status, Json(serde_json::json!({ "error": error_message }))).into_response()
+        (status, Json(serde_json::json!({ "error": error_message, "code": code }))).into_response()
     }
 }
 
+fn retry_later(
+    status: StatusCode,
+    code: &str,
+    message: String,
+    retry_after: std::time::Duration,
+) -> axum::response::Response {
+    // Whole seconds, rounded up so clients don't retry too early
+    let seconds = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+    let body = Json(serde_json::json!({ "error": message, "code": code }));
+    (status, [(header::RETRY_AFTER, seconds.to_string())], body).into_response()
+}
+
 #[tokio::main]
 async fn main() {
     // Initialize tracing with more detailed configuration
     tracing_subscriber::registry()
         .with(EnvFilter::new(
             std::env::var("RUST_LOG")
-                .unwrap_or_else(|_| "papermake_server=debug,tower_http=debug".into())
+                .unwrap_or_else(|_| "papermake_server=debug,papermake=debug,tower_http=debug".into())
         ))
         .with(tracing_subscriber::fmt::layer())
         .try_init()
         .unwrap();
+    // `--config <path>` (or `PAPERMAKE_CONFIG`, or `./papermake.toml`),
+    // overridden by environment variables
+    let config_path = std::env::args().skip_while(|arg| arg != "--config").nth(1);
+    let config = match ServerConfig::load(config_path.as_deref().map(std::path::Path::new)) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Invalid configuration: {}", e);
+            std::process::exit(1);
+        }
+    };
+    if std::env::args().any(|arg| arg == "--print-config") {
+        print!("{}", config.to_toml());
+        return;
+    }
+
     // Initialize storage
-    let storage_path = std::env::var("PAPERMAKE_STORAGE_PATH")
-        .unwrap_or_else(|_| "./data".to_string());
-    let storage = Arc::new(FileStorage::new(PathBuf::from(storage_path)));
+    let storage_path = config.storage.path.clone();
+    // `PAPERMAKE_ENCRYPTION_KEY` (32 base64-encoded bytes) encrypts templates,
+    // their files and cached renders at rest
+    let encryption_key = std::env::var("PAPERMAKE_ENCRYPTION_KEY")
+        .ok()
+        .map(|key| EncryptionKey::from_base64(&key).expect("invalid PAPERMAKE_ENCRYPTION_KEY"));
+    let mut storage: Arc<dyn Storage> = Arc::new(FileStorage::new(storage_path.clone()));
+    // `storage.dedup` keeps each distinct template file once, unencrypted,
+    // in `{path}/blobs`
+    let blobs = config.storage.dedup
+        .then(|| Arc::new(FileBlobStore::new(storage_path.join("blobs"))) as Arc<dyn BlobStore>);
+    if let Some(blobs) = &blobs {
+        if encryption_key.is_some() {
+            eprintln!("Invalid configuration: storage.dedup can't be combined with PAPERMAKE_ENCRYPTION_KEY");
+            std::process::exit(1);
+        }
+        storage = Arc::new(DedupStorage::new(storage, blobs.clone()));
+    }
+    if let Some(key) = &encryption_key {
+        storage = Arc::new(EncryptedStorage::new(storage, key.clone()));
+    }
+    // `--gc` deletes blobs no template file references anymore, then exits
+    if std::env::args().any(|arg| arg == "--gc") {
+        let Some(blobs) = &blobs else {
+            eprintln!("--gc needs storage.dedup");
+            std::process::exit(1);
+        };
+        let before = time::OffsetDateTime::now_utc() - time::Duration::seconds(config.storage.blob_gc_grace_secs as i64);
+        match blobs.collect_garbage(before).await {
+            Ok(report) => println!(
+                "Deleted {} unreferenced blobs ({} bytes), kept {}",
+                report.deleted, report.freed_bytes, report.kept
+            ),
+            Err(e) => {
+                eprintln!("Garbage collection failed: {}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    // Rendered job output goes to `storage.output` (a directory or `s3://bucket/prefix`)
+    let sink: Arc<dyn RenderSink> = match &config.storage.output {
+        Some(output) if output.starts_with("s3://") => {
+            let location = output.trim_start_matches("s3://");
+            let (bucket, prefix) = location.split_once('/').unwrap_or((location, ""));
+            Arc::new(S3Sink::from_env(bucket, prefix).await)
+        }
+        Some(output) => Arc::new(FileSink::new(output)),
+        None => Arc::new(FileSink::new(storage_path.join("outputs"))),
+    };
+    let render_cache: Option<Arc<dyn RenderCache>> = match config.storage.render_cache {
+        RenderCacheKind::Off => None,
+        RenderCacheKind::Disk => Some(Arc::new(DiskRenderCache::new(storage_path.join("render_cache")))),
+        RenderCacheKind::Memory => Some(Arc::new(MemoryRenderCache::new(config.storage.render_cache_size))),
+    };
+    let render_cache = match &encryption_key {
+        Some(key) => render_cache.map(|cache| Arc::new(EncryptedRenderCache::new(cache, key.clone())) as Arc<dyn RenderCache>),
+        None => render_cache,
+    };
+    let metrics = Arc::new(Metrics::new());
+    let storage = Arc::new(InstrumentedStorage::new(storage, metrics.clone()));
+    let queue = queue_from_env(&storage_path).await.expect("failed to set up job queue");
+    let scheduler = Arc::new(RenderScheduler::from_config(&config.limits, metrics.render_queue_depth.clone()));
+    let shutdown = Shutdown::listen();
 
     // Create app state
-    let state = Arc::new(AppState { storage });
+    let state = Arc::new(AppState {
+        storage,
+        metrics,
+        world_pool: Arc::new(WorldPool::new()),
+        sink,
+        queue,
+        webhooks: WebhookNotifier::from_env(&config.webhooks),
+        tenants: TenantKeys::from_env(),
+        render_cache,
+        upload_limits: UploadLimits::from_config(&config.limits),
+        size_limits: config.limits.size_limits(),
+        data_fetcher: DataFetcher::from_config(&config.data_sources, config.limits.max_data_bytes).await,
+        idempotency: Arc::new(IdempotencyStore::new(
+            storage_path.join("idempotency"),
+            std::time::Duration::from_secs(config.storage.idempotency_ttl_secs),
+            config.limits.max_body_bytes,
+        )),
+        rate_limiter: RateLimiter::from_config(&config.limits),
+        scheduler,
+        quotas: QuotaStore::new(storage_path.join("quotas"), config.limits.monthly_render_quota),
+        history: Arc::new(FileRenderHistory::new(storage_path.clone())),
+        stats: Arc::new(FileRenderStats::new(storage_path.clone())),
+        archive_inputs: std::env::var("PAPERMAKE_ARCHIVE_INPUTS").is_ok_and(|v| v == "true" || v == "1"),
+        sandbox: match std::env::var("PAPERMAKE_SANDBOX").as_deref() {
+            Ok("restrictive") => Some(SandboxPolicy::restrictive()),
+            _ => None,
+        },
+        environment: config.environment.clone(),
+        remote_resources: config.remote_resources.remote_resources(),
+        downloads: DownloadSigner::from_env(&config.downloads),
+        shutdown: shutdown.clone(),
+    });
 
-    // Build router
+    // Job consumers: `PAPERMAKE_QUEUE_CONSUMERS` jobs run concurrently per replica
+    let consumers = std::env::var("PAPERMAKE_QUEUE_CONSUMERS")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(4);
+    let mut consumer_tasks = tokio::task::JoinSet::new();
+    for _ in 0..consumers {
+        consumer_tasks.spawn(consume_jobs(state.clone(), shutdown.clone()));
+    }
+
+    tokio::spawn(retention::sweep_expired(state.clone(), config.retention.clone(), shutdown.clone()));
+
+    // Build router; template and job routes are served for the default
+    // namespace and, with a tenant API key, for each tenant
     let app = Router::new()
-        .route("/templates", get(list_templates).post(create_template))
-        .route("/templates/{id}", 
-            get(get_template)
-            .put(update_template)
-            .delete(delete_template))
-        .route("/templates/{id}/render", post(render_template))
-        .route("/templates/{id}/files", get(list_template_files))
-        .route("/templates/{id}/files/{*path}", 
-            get(get_template_file)
-            .put(save_template_file)
-            .delete(delete_template_file))
-        .route("/health", get(health_check))
+        .merge(template_routes(&state))
+        .nest("/tenants/{tenant}", template_routes(&state))
+        .merge(job_routes())
+        .nest("/tenants/{tenant}", job_routes())
+        .route("/downloads/{token}", get(download))
+        .route("/metrics", get(metrics_handler))
+        .layer(middleware::from_fn_with_state(state.clone(), rate_limit))
+        // Probes aren't rate limited
+        .route("/health", get(health::live))
+        .route("/health/live", get(health::live))
+        .route("/health/ready", get(health::ready))
+        .layer(TimeoutLayer::new(config.request_timeout()))
+        .layer(DefaultBodyLimit::max(config.limits.max_body_bytes))
         .layer(
             TraceLayer::new_for_http()
                 .make_span_with(|request: &axum::http::Request<_>| {
@@ -160,38 +913,203 @@ async fn main() {
                     tracing::debug!("status: {}", response.status());
                 })
         )
-        .layer(CorsLayer::permissive())
-        .with_state(state);
-
-    // Run server
-    let port = std::env::var("PORT")
-        .ok()
-        .and_then(|s| s.parse::<u16>().ok())
-        .unwrap_or(3000);
-    let addr = SocketAddr::from(([0, 0, 0, 0], port));
-    
-    tracing::info!("Server listening on {}", addr);
+        .layer(config.cors_layer())
+        .with_state(state.clone());
     
-    axum::serve(
-        tokio::net::TcpListener::bind(addr).await.unwrap(),
-        app.into_make_service()
-    ).await.unwrap();
+    // `--dev <dir>` watches a local template directory and serves live previews
+    let dev_dir = std::env::args().skip_while(|arg| arg != "--dev").nth(1);
+    let app = match dev_dir {
+        Some(dir) => {
+            let workspace = DevWorkspace::new(&dir);
+            tokio::spawn(workspace.clone().watch());
+            tracing::info!("Development mode: watching {}", dir);
+            app.merge(dev_routes(workspace))
+        }
+        None => app,
+    };
+
+    // Run server; client addresses are needed for per-IP rate limits
+    let addr = config.bind;
+    let app = app.into_make_service_with_connect_info::<SocketAddr>();
+    let server = match &config.tls {
+        Some(tls) => {
+            let rustls = RustlsConfig::from_pem_file(&tls.cert, &tls.key)
+                .await
+                .expect("failed to load TLS certificate");
+            let handle = axum_server::Handle::new();
+            tokio::spawn({
+                let (handle, shutdown) = (handle.clone(), shutdown.clone());
+                async move {
+                    shutdown.triggered().await;
+                    handle.graceful_shutdown(None);
+                }
+            });
+            tracing::info!("Server listening on https://{}", addr);
+            tokio::spawn(async move {
+                axum_server::bind_rustls(addr, rustls).handle(handle).serve(app).await.unwrap();
+            })
+        }
+        None => {
+            let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
+            tracing::info!("Server listening on http://{}", addr);
+            let shutdown = shutdown.clone();
+            tokio::spawn(async move {
+                axum::serve(listener, app).with_graceful_shutdown(shutdown.triggered()).await.unwrap();
+            })
+        }
+    };
+
+    // Once signalled, the listener closes and consumers stop taking jobs;
+    // in-flight requests and jobs get the drain timeout to finish
+    shutdown.triggered().await;
+    let drained = tokio::time::timeout(config.drain_timeout(), async {
+        let _ = server.await;
+        while consumer_tasks.join_next().await.is_some() {}
+    })
+    .await;
+    if drained.is_err() {
+        tracing::warn!("Drain timeout of {:?} exceeded, interrupting remaining work", config.drain_timeout());
+        consumer_tasks.abort_all();
+    }
+    if let Err(err) = state.queue.persist().await {
+        tracing::error!("failed to persist job queue: {}", err);
+    }
+    tracing::info!("Server stopped");
+}
+
+// Routes operating on the templates of one storage namespace
+fn template_routes(state: &Arc<AppState>) -> Router<Arc<AppState>> {
+    // Retries with the same `Idempotency-Key` get the first response back
+    let idempotency = || middleware::from_fn_with_state(state.clone(), idempotent);
+    Router::new()
+        .route("/templates", get(list_templates).post(create_template))
+        .route("/templates/import", post(import_template))
+        .route("/templates/search", get(search_templates))
+        .route("/templates/scaffold", post(scaffold_template))
+        .route("/templates/{id}", 
+            get(get_template)
+            .put(update_template)
+            .patch(patch_template)
+            .delete(delete_template))
+        .route("/templates/{id}/publish", post(publish_template_handler))
+        .route("/templates/{id}/archive", post(archive_template_handler))
+        .route("/templates/{id}/render", post(render_template).layer(idempotency()))
+        .route("/templates/{id}/render_merged", post(render_merged_template).layer(idempotency()))
+        .route("/templates/{id}/render_form", post(render_form))
+        .route("/templates/{id}/preview", post(preview_template))
+        .route("/templates/{id}/render_async", post(submit_render_job).layer(idempotency()))
+        .route("/templates/{id}/render_batch", post(submit_batch_job).layer(idempotency()))
+        .route("/templates/{id}/render_stream", post(render_stream))
+        .route("/templates/{id}/test", post(test_template))
+        .route("/templates/{id}/lint", post(lint_template))
+        .route("/templates/{id}/check", post(check_template))
+        .route("/templates/{id}/analyze", post(analyze_template_handler))
+        .route("/templates/{id}/dependents", get(list_dependents))
+        .route("/templates/{id}/clone", post(clone_template))
+        .route("/templates/{id}/diff", post(diff_template))
+        .route("/templates/{id}/versions/{a}/diff/{b}", get(diff_versions))
+        .route("/templates/{id}/sample_data", get(sample_data))
+        .route("/templates/{id}/export", get(export_template))
+        .route("/templates/{id}/renders", get(list_renders))
+        .route("/renders/{id}", get(get_render))
+        .route("/templates/{id}/stats", get(template_stats))
+        .route("/stats/slowest", get(slowest_templates))
+        .route("/templates/{id}/files", 
+            get(list_template_files)
+            .post(upload_template_files)
+            .layer(DefaultBodyLimit::max(state.upload_limits.max_request_bytes)))
+        .route("/templates/{id}/files/{*path}", 
+            get(get_template_file)
+            .put(save_template_file)
+            .patch(rename_template_file)
+            .delete(delete_template_file))
+}
+
+// Jobs are only visible in the namespace they were submitted to
+fn job_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/jobs/{id}", get(get_job))
+        .route("/jobs/{id}/pdf", get(get_job_pdf))
+        .route("/jobs/{id}/outputs/{index}", get(get_job_output))
+        .route("/jobs/{id}/outputs/{index}/link", post(create_download_link))
 }
 
 // Route handlers
 
 // Template operations
 async fn list_templates(
-    State(state): State<Arc<AppState>>,
-) -> Result<Json<Vec<TemplateResponse>>, AppError> {
-    let templates = state.storage.list_templates().await?;
-    Ok(Json(templates.into_iter().map(TemplateResponse::from).collect()))
+    TenantStorage(storage): TenantStorage,
+    Query(query): Query<ListTemplatesQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let options = ListOptions {
+        limit: query.limit,
+        cursor: query.cursor,
+        name_contains: query.name,
+        shared: query.shared,
+        tags: query.tag
+            .map(|tags| tags.split(',').map(|t| t.trim().to_string()).filter(|t| !t.is_empty()).collect())
+            .unwrap_or_default(),
+        metadata: match query.meta {
+            Some(meta) => parse_metadata_filter(&meta)?,
+            None => BTreeMap::new(),
+        },
+        sort: match query.sort {
+            Some(sort) => sort.parse().map_err(|err: PapermakeError| AppError::BadRequest(err.to_string()))?,
+            None => TemplateSort::default(),
+        },
+    };
+    let page = storage.list_templates(&options).await
+        .map_err(|err| match err {
+            PapermakeError::InvalidInput(msg) => AppError::BadRequest(msg),
+            err => AppError::Papermake(err),
+        })?;
+    
+    // The next page's cursor goes in a header so the body stays a plain list
+    let mut headers = HeaderMap::new();
+    if let Some(cursor) = page.next_cursor.and_then(|c| c.parse().ok()) {
+        headers.insert("x-next-cursor", cursor);
+    }
+    let templates: Vec<TemplateResponse> = page.templates.into_iter().map(TemplateResponse::from).collect();
+    Ok((headers, Json(templates)))
+}
+
+async fn search_templates(
+    TenantStorage(storage): TenantStorage,
+    Query(query): Query<SearchTemplatesQuery>,
+) -> Result<Json<Vec<SearchHitResponse>>, AppError> {
+    if query.q.trim().is_empty() {
+        return Err(AppError::BadRequest("Search query must not be empty".to_string()));
+    }
+    
+    let hits = storage.search_templates(&query.q).await?;
+    let hits = hits.into_iter()
+        .take(query.limit.unwrap_or(usize::MAX))
+        .map(|hit| SearchHitResponse {
+            template: TemplateResponse::from(hit.template),
+            score: hit.score,
+        })
+        .collect();
+    Ok(Json(hits))
+}
+
+// Parse `key:value,key:value` metadata filters
+fn parse_metadata_filter(meta: &str) -> Result<BTreeMap<String, String>, AppError> {
+    meta.split(',')
+        .filter(|entry| !entry.trim().is_empty())
+        .map(|entry| {
+            entry.split_once(':')
+                .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+                .ok_or_else(|| AppError::BadRequest(format!("Invalid metadata filter '{}', expected key:value", entry)))
+        })
+        .collect()
 }
 
 async fn create_template(
     State(state): State<Arc<AppState>>,
+    TenantStorage(storage): TenantStorage,
     Json(payload): Json<CreateTemplateRequest>,
 ) -> Result<Json<TemplateResponse>, AppError> {
+    papermake::storage::validate_template_id(&TemplateId(payload.id.clone()))?;
     let template = Template::new(
         payload.id,
         payload.name,
@@ -199,32 +1117,62 @@ async fn create_template(
         payload.schema,
     );
     
-    let template = if let Some(description) = payload.description {
+    let mut template = if let Some(description) = payload.description {
         template.with_description(description)
     } else {
         template
     };
+    template.examples = payload.examples;
+    template.shared = payload.shared;
+    template.tags = payload.tags;
+    template.metadata = payload.metadata;
+    template.variants = payload.variants;
+    template.sandbox = payload.sandbox;
+    template.environments = payload.environments;
+    template.script = payload.script;
+    state.size_limits.check_template(&template)?;
 
-    state.storage.save_template(&template).await?;
+    // A stored template with the same id makes this a revision conflict
+    storage.save_template(&template).await?;
+    template.revision += 1;
+    state.metrics.template_operation("create");
     Ok(Json(TemplateResponse::from(template)))
 }
 
 async fn get_template(
-    State(state): State<Arc<AppState>>,
-    Path(id): Path<String>,
-) -> Result<Json<TemplateResponse>, AppError> {
-    let template = state.storage.get_template(&TemplateId(id)).await
-        .map_err(|_| AppError::NotFound)?;
-    Ok(Json(TemplateResponse::from(template)))
+    TenantStorage(storage): TenantStorage,
+    Path(TemplatePath { id }): Path<TemplatePath>,
+) -> Result<impl IntoResponse, AppError> {
+    let template = storage.get_template(&TemplateId(id)).await?;
+    Ok(([(header::ETAG, etag(&template))], Json(TemplateResponse::from(template))))
+}
+
+// Entity tag of a template's stored revision
+fn etag(template: &Template) -> String {
+    format!("\"{}\"", template.revision)
 }
 
 async fn update_template(
     State(state): State<Arc<AppState>>,
-    Path(id): Path<String>,
+    TenantStorage(storage): TenantStorage,
+    TenantPool(pool): TenantPool,
+    Path(TemplatePath { id }): Path<TemplatePath>,
+    Query(query): Query<UpdateTemplateQuery>,
+    headers: HeaderMap,
     Json(payload): Json<UpdateTemplateRequest>,
-) -> Result<Json<TemplateResponse>, AppError> {
-    let mut template = state.storage.get_template(&TemplateId(id)).await
-        .map_err(|_| AppError::NotFound)?;
+) -> Result<impl IntoResponse, AppError> {
+    let mut template = storage.get_template(&TemplateId(id)).await?;
+    
+    // `If-Match` makes the update conditional on the revision the client last saw
+    if let Some(if_match) = headers.get(header::IF_MATCH).and_then(|v| v.to_str().ok()) {
+        let current = etag(&template);
+        if if_match.trim() != "*" && !if_match.split(',').any(|tag| tag.trim() == current) {
+            return Err(AppError::Conflict(format!(
+                "Template '{}' has changed; current revision is {}",
+                template.id.as_ref(), template.revision
+            )));
+        }
+    }
     
     if let Some(name) = payload.name {
         template.name = name;
@@ -234,7 +1182,21 @@ async fn update_template(
         template.content = content;
     }
     
+    // Schema changes breaking existing callers need `?allow_breaking=true`
+    let mut warning = None;
     if let Some(schema) = payload.schema {
+        let report = papermake::schema::Schema::compatibility(&template.schema, &schema);
+        let breaking: Vec<&str> = report.breaking_changes().map(|change| change.message.as_str()).collect();
+        if !breaking.is_empty() {
+            if !query.allow_breaking {
+                return Err(AppError::Conflict(format!(
+                    "Schema change breaks existing callers: {}; pass allow_breaking=true to apply it",
+                    breaking.join("; ")
+                )));
+            }
+            tracing::warn!("applying breaking schema change to '{}': {}", template.id.as_ref(), breaking.join("; "));
+            warning = Some(format!("299 - \"Breaking schema change: {}\"", breaking.join("; ").replace('"', "'")));
+        }
         template.schema = schema;
     }
     
@@ -242,46 +1204,250 @@ async fn update_template(
         template.description = Some(description);
     }
     
-    template.updated_at = time::OffsetDateTime::now_utc();
+    if let Some(examples) = payload.examples {
+        template.examples = examples;
+    }
     
-    state.storage.save_template(&template).await?;
-    Ok(Json(TemplateResponse::from(template)))
-}
-
-async fn delete_template(
-    State(state): State<Arc<AppState>>,
-    Path(id): Path<String>,
-) -> Result<StatusCode, AppError> {
-    state.storage.delete_template(&TemplateId(id)).await
-        .map_err(|_| AppError::NotFound)?;
-    Ok(StatusCode::NO_CONTENT)
-}
-
-// Rendering
-async fn render_template(
-    State(state): State<Arc<AppState>>,
-    Path(id): Path<String>,
-    Json(payload): Json<RenderTemplateRequest>,
-) -> Result<Json<RenderResultResponse>, AppError> {
-    let template = state.storage.get_template(&TemplateId(id)).await
-        .map_err(|_| AppError::NotFound)?;
+    if let Some(shared) = payload.shared {
+        template.shared = shared;
+    }
     
-    // Convert options if provided
-    let options = payload.options.map(|opts| RenderOptions {
-        paper_size: opts.paper_size.unwrap_or_else(|| "a4".to_string()),
-        compress: opts.compress.unwrap_or(true),
-    });
+    if let Some(tags) = payload.tags {
+        template.tags = tags;
+    }
     
-    // Validate data against schema
-    if let Err(err) = template.validate_data(&payload.data) {
-        return Err(AppError::BadRequest(format!("Invalid data: {}", err)));
+    if let Some(metadata) = payload.metadata {
+        template.metadata = metadata;
     }
     
-    // Render PDF and handle errors
-    let render_result = match render_pdf(&template, &payload.data, options) {
-        Ok(result) => result,
-        Err(e) => return Err(AppError::Papermake(e)),
-    };
+    if let Some(variants) = payload.variants {
+        template.variants = variants;
+    }
+    
+    if let Some(sandbox) = payload.sandbox {
+        template.sandbox = Some(sandbox);
+    }
+    
+    if let Some(environments) = payload.environments {
+        template.environments = environments;
+    }
+    
+    if let Some(script) = payload.script {
+        template.script = Some(script);
+    }
+    
+    state.size_limits.check_template(&template)?;
+    save_draft(storage.as_ref(), &mut template).await?;
+    pool.prune(&template)?;
+    state.metrics.template_operation("update");
+    let mut response_headers = HeaderMap::new();
+    if let Ok(etag) = etag(&template).parse() {
+        response_headers.insert(header::ETAG, etag);
+    }
+    if let Some(warning) = warning.and_then(|w| w.parse().ok()) {
+        response_headers.insert(header::WARNING, warning);
+    }
+    Ok((response_headers, Json(TemplateResponse::from(template))))
+}
+
+// Merge an editor's content changes into the draft; `ours` in the merge is
+// the stored content, `theirs` the editor's
+async fn patch_template(
+    State(state): State<Arc<AppState>>,
+    TenantStorage(storage): TenantStorage,
+    TenantPool(pool): TenantPool,
+    Path(TemplatePath { id }): Path<TemplatePath>,
+    Json(payload): Json<PatchTemplateRequest>,
+) -> Result<axum::response::Response, AppError> {
+    let mut template = storage.get_template(&TemplateId(id)).await?;
+    if payload.base_revision > template.revision {
+        return Err(AppError::BadRequest(format!(
+            "Unknown base revision {}; current revision is {}",
+            payload.base_revision, template.revision
+        )));
+    }
+
+    if payload.base_revision == template.revision {
+        if payload.base != template.content {
+            return Err(AppError::BadRequest(format!(
+                "Base content doesn't match revision {}",
+                payload.base_revision
+            )));
+        }
+        template.content = payload.content;
+    } else {
+        let merged = merge(&payload.base, &template.content, &payload.content);
+        if !merged.is_clean() {
+            let response = MergeConflictResponse {
+                error: format!(
+                    "{} conflicting changes since revision {}",
+                    merged.conflicts.len(),
+                    payload.base_revision
+                ),
+                code: "MERGE_CONFLICT",
+                revision: template.revision,
+                content: merged.content,
+                conflicts: merged.conflicts,
+            };
+            return Ok((StatusCode::CONFLICT, Json(response)).into_response());
+        }
+        template.content = merged.content;
+    }
+
+    state.size_limits.check_template(&template)?;
+    save_draft(storage.as_ref(), &mut template).await?;
+    pool.prune(&template)?;
+    state.metrics.template_operation("patch");
+    let mut response_headers = HeaderMap::new();
+    if let Ok(etag) = etag(&template).parse() {
+        response_headers.insert(header::ETAG, etag);
+    }
+    Ok((response_headers, Json(TemplateResponse::from(template))).into_response())
+}
+
+async fn delete_template(
+    State(state): State<Arc<AppState>>,
+    TenantStorage(storage): TenantStorage,
+    TenantPool(pool): TenantPool,
+    Path(TemplatePath { id }): Path<TemplatePath>,
+) -> Result<StatusCode, AppError> {
+    let id = TemplateId(id);
+    storage.delete_template(&id).await?;
+    pool.evict(&id)?;
+    state.metrics.template_operation("delete");
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Template lifecycle
+async fn publish_template_handler(
+    State(state): State<Arc<AppState>>,
+    TenantStorage(storage): TenantStorage,
+    TenantPool(pool): TenantPool,
+    Path(TemplatePath { id }): Path<TemplatePath>,
+) -> Result<Json<TemplateResponse>, AppError> {
+    let id = TemplateId(id);
+    storage.get_template(&id).await?;
+    let template = publish_template(storage.as_ref(), &id).await
+        .map_err(|err| match err {
+            PapermakeError::InvalidInput(msg) => AppError::Conflict(msg),
+            err => AppError::Papermake(err),
+        })?;
+    pool.prune(&template)?;
+    state.metrics.template_operation("publish");
+    Ok(Json(TemplateResponse::from(template)))
+}
+
+async fn archive_template_handler(
+    State(state): State<Arc<AppState>>,
+    TenantStorage(storage): TenantStorage,
+    Path(TemplatePath { id }): Path<TemplatePath>,
+) -> Result<Json<TemplateResponse>, AppError> {
+    let id = TemplateId(id);
+    storage.get_template(&id).await?;
+    let template = archive_template(storage.as_ref(), &id).await?;
+    state.metrics.template_operation("archive");
+    Ok(Json(TemplateResponse::from(template)))
+}
+
+// Template packages
+async fn export_template(
+    TenantStorage(storage): TenantStorage,
+    Path(TemplatePath { id }): Path<TemplatePath>,
+) -> Result<impl IntoResponse, AppError> {
+    let id = TemplateId(id);
+    let template = storage.get_template(&id).await?;
+    
+    let mut files = BTreeMap::new();
+    for path in storage.list_template_files(&id).await? {
+        let content = storage.get_template_file(&id, &path).await?;
+        files.insert(path, content);
+    }
+    
+    let archive = template.export_package(&files)?;
+    let disposition = format!("attachment; filename=\"{}.tar.gz\"", id.as_ref());
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/gzip".to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        archive,
+    ))
+}
+
+async fn import_template(
+    State(state): State<Arc<AppState>>,
+    TenantStorage(storage): TenantStorage,
+    TenantPool(pool): TenantPool,
+    Query(query): Query<ImportTemplateQuery>,
+    body: axum::body::Bytes,
+) -> Result<Json<TemplateResponse>, AppError> {
+    let package = Template::import_package(&body)
+        .map_err(|e| AppError::BadRequest(e.to_string()))?;
+    let mut template = package.template;
+    
+    if let Ok(existing) = storage.get_template(&template.id).await {
+        if !query.overwrite {
+            return Err(AppError::Conflict(format!(
+                "Template '{}' already exists", template.id.as_ref()
+            )));
+        }
+        template.revision = existing.revision;
+    }
+    state.size_limits.check_template(&template)?;
+    for (path, content) in &package.files {
+        state.size_limits.check_asset(path, content.len())?;
+    }
+    
+    storage.save_template(&template).await?;
+    template.revision += 1;
+    for (path, content) in &package.files {
+        storage.save_template_file(&template.id, path, content).await?;
+    }
+    pool.evict(&template.id)?;
+    state.metrics.template_operation("import");
+    
+    Ok(Json(TemplateResponse::from(template)))
+}
+
+// Rendering
+async fn render_template(
+    State(state): State<Arc<AppState>>,
+    TenantStorage(storage): TenantStorage,
+    TenantPool(pool): TenantPool,
+    Path(TemplatePath { id }): Path<TemplatePath>,
+    Query(query): Query<RenderVersionQuery>,
+    requester: TenantHistory,
+    headers: HeaderMap,
+    Json(payload): Json<RenderTemplateRequest>,
+) -> Result<Json<RenderResultResponse>, AppError> {
+    let template = load_render_template(storage.as_ref(), id, query.version).await?;
+    
+    // Convert options if provided; `Cache-Control: no-cache`/`no-store` opt out of the render cache
+    let mut options = render_options(&state, storage.as_ref(), &template, payload.options).await?;
+    if let Some(cache_control) = headers.get(header::CACHE_CONTROL).and_then(|v| v.to_str().ok()) {
+        options.cache_policy = CachePolicy::from_cache_control(cache_control);
+    }
+    options.locale = payload.locale;
+    
+    // Apply schema defaults and validate data against schema
+    let input = state.data_fetcher.resolve(payload.data).await?;
+    let data = prepare_data(&template, &input, &options).map_err(invalid_data)?;
+    options.data_prepared = true;
+    
+    let _permit = acquire_render_slot(&state, &template, requester.api_key_id.as_deref()).await?;
+    state.quotas.consume(requester.api_key_id.as_deref(), 1).await?;
+    let record = RenderRecord::new(uuid::Uuid::new_v4().to_string(), &template, &input)
+        .with_locale(options.locale.clone())
+        .with_api_key_id(requester.api_key_id.clone());
+    
+    // Render PDF off the async executor with a pooled world and handle errors
+    let started = std::time::Instant::now();
+    let timer = state.metrics.start_render(template.id.as_ref());
+    let render_result = pool.render_async(&template, &data, Some(options)).await;
+    let record = record.finish(started.elapsed(), &render_result);
+    record_render(&state, &requester, &record, &input).await;
+    let render_result = render_result.map_err(AppError::Papermake)?;
+    timer.finish(render_result.pdf.is_some(), render_result.errors.len());
 
     // Convert PDF to base64 if present
     let pdf_base64 = render_result.pdf
@@ -291,48 +1457,995 @@ async fn render_template(
     Ok(Json(RenderResultResponse {
         pdf_base64,
         errors: render_result.errors,
+        warnings: render_result.warnings,
+        cached: render_result.cached,
+        optimization: render_result.optimization,
+        metadata: render_result.metadata,
+        text: render_result.text,
+        render_id: record.id,
+    }))
+    
+}
+
+// Rasterize pages as images, e.g. small JPEG thumbnails or 300 DPI proofs
+async fn preview_template(
+    State(state): State<Arc<AppState>>,
+    TenantStorage(storage): TenantStorage,
+    Path(TemplatePath { id }): Path<TemplatePath>,
+    Query(query): Query<RenderVersionQuery>,
+    requester: TenantHistory,
+    Json(payload): Json<PreviewRequest>,
+) -> Result<Json<PreviewResponse>, AppError> {
+    payload.preview.validate()?;
+    let template = load_render_template(storage.as_ref(), id, query.version).await?;
+    
+    let mut options = render_options(&state, storage.as_ref(), &template, payload.options).await?;
+    options.locale = payload.locale;
+    let input = state.data_fetcher.resolve(payload.data).await?;
+    let data = prepare_data(&template, &input, &options).map_err(invalid_data)?;
+    options.data_prepared = true;
+    
+    let _permit = acquire_render_slot(&state, &template, requester.api_key_id.as_deref()).await?;
+    state.quotas.consume(requester.api_key_id.as_deref(), 1).await?;
+    let preview_options = payload.preview;
+    let preview = tokio::task::spawn_blocking(move || render_preview(&template, &data, &preview_options, Some(options)))
+        .await
+        .map_err(|e| AppError::Papermake(PapermakeError::Rendering(e.to_string())))??;
+    
+    Ok(Json(PreviewResponse {
+        format: preview.format,
+        pages: preview.pages.into_iter().map(|page| PreviewPageResponse {
+            page: page.page,
+            width: page.width,
+            height: page.height,
+            image_base64: BASE64_STANDARD.encode(&page.image),
+        }).collect(),
+        errors: preview.errors,
+        warnings: preview.warnings,
     }))
+}
+
+// Render an HTML form submission (`application/x-www-form-urlencoded`),
+// answering with the PDF itself so a plain `<form>` can post here
+async fn render_form(
+    State(state): State<Arc<AppState>>,
+    TenantStorage(storage): TenantStorage,
+    TenantPool(pool): TenantPool,
+    Path(TemplatePath { id }): Path<TemplatePath>,
+    Query(query): Query<RenderFormQuery>,
+    requester: TenantHistory,
+    Form(fields): Form<Vec<(String, String)>>,
+) -> Result<impl IntoResponse, AppError> {
+    let template = load_render_template(storage.as_ref(), id, query.version).await?;
+    
+    let mut options = render_options(&state, storage.as_ref(), &template, None).await?;
+    options.locale = query.locale;
+    options.coerce_data = true;
+    let input = form_data(&template.schema, fields).map_err(invalid_data)?;
+    let data = prepare_data(&template, &input, &options).map_err(invalid_data)?;
+    options.data_prepared = true;
+    
+    let _permit = acquire_render_slot(&state, &template, requester.api_key_id.as_deref()).await?;
+    state.quotas.consume(requester.api_key_id.as_deref(), 1).await?;
+    let record = RenderRecord::new(uuid::Uuid::new_v4().to_string(), &template, &input)
+        .with_locale(options.locale.clone())
+        .with_api_key_id(requester.api_key_id.clone());
+    
+    let started = std::time::Instant::now();
+    let timer = state.metrics.start_render(template.id.as_ref());
+    let render_result = pool.render_async(&template, &data, Some(options)).await;
+    let record = record.finish(started.elapsed(), &render_result);
+    record_render(&state, &requester, &record, &input).await;
+    let render_result = render_result?;
+    timer.finish(render_result.pdf.is_some(), render_result.errors.len());
+    
+    let pdf = render_result.pdf.ok_or_else(|| PapermakeError::Compile {
+        template: template.id.0.clone(),
+        errors: render_result.errors,
+    })?;
+    let disposition = format!("inline; filename=\"{}.pdf\"", template.id.as_ref());
+    Ok((
+        [(header::CONTENT_TYPE, "application/pdf".to_string()), (header::CONTENT_DISPOSITION, disposition)],
+        [("x-render-id", record.id)],
+        pdf,
+    ))
+}
+
+// Error for data failing `prepare_data`: oversized data keeps its 413
+fn invalid_data(err: PapermakeError) -> AppError {
+    match err {
+        PapermakeError::TooLarge(_) => AppError::Papermake(err),
+        err => AppError::BadRequest(format!("Invalid data: {}", err)),
+    }
+}
+
+// Render many records into a single PDF
+async fn render_merged_template(
+    State(state): State<Arc<AppState>>,
+    TenantStorage(storage): TenantStorage,
+    Path(TemplatePath { id }): Path<TemplatePath>,
+    Query(query): Query<RenderVersionQuery>,
+    requester: TenantHistory,
+    Json(payload): Json<RenderMergedRequest>,
+) -> Result<Json<RenderResultResponse>, AppError> {
+    let template = load_render_template(storage.as_ref(), id, query.version).await?;
+    
+    if payload.records.is_empty() {
+        return Err(AppError::BadRequest("No records to render".to_string()));
+    }
+    
+    let options = render_options(&state, storage.as_ref(), &template, payload.options).await?;
+    let _permit = acquire_render_slot(&state, &template, requester.api_key_id.as_deref()).await?;
+    state.quotas.consume(requester.api_key_id.as_deref(), payload.records.len() as u64).await?;
+    let inputs = serde_json::Value::Array(payload.records);
+    let record = RenderRecord::new(uuid::Uuid::new_v4().to_string(), &template, &inputs)
+        .with_api_key_id(requester.api_key_id.clone());
     
+    let template_id = template.id.clone();
+    let started = std::time::Instant::now();
+    let timer = state.metrics.start_render(template_id.as_ref());
+    let (render_result, inputs) = tokio::task::spawn_blocking(move || {
+        let records = inputs.as_array().map(Vec::as_slice).unwrap_or_default();
+        (render_merged(&template, records, options), inputs)
+    })
+        .await
+        .map_err(|e| AppError::Papermake(PapermakeError::Rendering(e.to_string())))?;
+    let record = record.finish(started.elapsed(), &render_result);
+    record_render(&state, &requester, &record, &inputs).await;
+    let render_result = render_result?;
+    timer.finish(render_result.pdf.is_some(), render_result.errors.len());
+    
+    let pdf_base64 = render_result.pdf
+        .as_ref()
+        .map(|pdf| BASE64_STANDARD.encode(pdf));
+    
+    Ok(Json(RenderResultResponse {
+        pdf_base64,
+        errors: render_result.errors,
+        warnings: render_result.warnings,
+        cached: false,
+        optimization: render_result.optimization,
+        metadata: render_result.metadata,
+        text: render_result.text,
+        render_id: record.id,
+    }))
+}
+
+// Wait for a concurrency slot for a synchronous render
+async fn acquire_render_slot(
+    state: &AppState,
+    template: &Template,
+    api_key_id: Option<&str>,
+) -> Result<RenderPermit, AppError> {
+    state.scheduler.acquire(template.id.as_ref(), api_key_id).await.map_err(|retry_after| {
+        AppError::ServiceUnavailable {
+            message: "Too many renders queued, try again later".to_string(),
+            retry_after,
+        }
+    })
+}
+
+// Store a render in the audit log and the template's statistics; failures
+// are logged rather than failing the render
+async fn record_render(
+    state: &AppState,
+    requester: &TenantHistory,
+    record: &RenderRecord,
+    data: &serde_json::Value,
+) {
+    let inputs = state.archive_inputs.then_some(data);
+    if let Err(err) = requester.history.record(record, inputs).await {
+        tracing::warn!("failed to record render {}: {}", record.id, err);
+    }
+    if let Err(err) = requester.stats.record(record).await {
+        tracing::warn!("failed to update stats of template {}: {}", record.template_id.as_ref(), err);
+    }
+}
+
+// Audit records of a template's renders, most recent first
+async fn list_renders(
+    requester: TenantHistory,
+    Path(TemplatePath { id }): Path<TemplatePath>,
+    Query(query): Query<ListRendersQuery>,
+) -> Result<Json<Vec<RenderRecord>>, AppError> {
+    let limit = query.limit.unwrap_or(50).min(1000);
+    let records = requester.history.list_records(&TemplateId(id), limit).await?;
+    Ok(Json(records))
+}
+
+async fn get_render(
+    requester: TenantHistory,
+    Path(RenderPath { id }): Path<RenderPath>,
+    Query(query): Query<GetRenderQuery>,
+) -> Result<Json<RenderRecordResponse>, AppError> {
+    let record = requester.history.get_record(&id).await?;
+    let inputs = if query.inputs && record.has_inputs {
+        Some(requester.history.get_inputs(&id).await?)
+    } else {
+        None
+    };
+    Ok(Json(RenderRecordResponse { record, inputs }))
+}
+
+// Render count, duration percentiles, failure rate and output size of a template
+async fn template_stats(
+    requester: TenantHistory,
+    Path(TemplatePath { id }): Path<TemplatePath>,
+) -> Result<Json<TemplateStats>, AppError> {
+    Ok(Json(requester.stats.get_stats(&TemplateId(id)).await?))
+}
+
+// Templates with the slowest renders by p95 duration
+async fn slowest_templates(
+    requester: TenantHistory,
+    Query(query): Query<ListRendersQuery>,
+) -> Result<Json<Vec<TemplateStats>>, AppError> {
+    let limit = query.limit.unwrap_or(10).min(1000);
+    Ok(Json(requester.stats.slowest(limit).await?))
+}
+
+// Load the revision of a template a render request asks for (`?version=draft`
+// or the published revision by default)
+async fn load_render_template(
+    storage: &dyn Storage,
+    id: String,
+    version: Option<String>,
+) -> Result<Template, AppError> {
+    let version = match version {
+        Some(version) => version.parse::<TemplateVersion>()
+            .map_err(|err| AppError::BadRequest(err.to_string()))?,
+        None => TemplateVersion::default(),
+    };
+    template_for_render(storage, &TemplateId(id), version).await
+        .map_err(|err| match err {
+            PapermakeError::InvalidInput(msg) => AppError::Conflict(msg),
+            err => AppError::Papermake(err),
+        })
+}
+
+// Build render options and resolve the shared templates the template imports
+async fn render_options(
+    state: &AppState,
+    storage: &dyn Storage,
+    template: &Template,
+    options: Option<RenderOptionsRequest>,
+) -> Result<RenderOptions, AppError> {
+    build_render_options(state, storage, template, options).await.map_err(|err| match err {
+        // Invalid options keep their own message
+        PapermakeError::InvalidInput(_) => AppError::Papermake(err),
+        err => AppError::BadRequest(format!("Failed to resolve imports: {}", err)),
+    })
+}
+
+async fn build_render_options(
+    state: &AppState,
+    storage: &dyn Storage,
+    template: &Template,
+    options: Option<RenderOptionsRequest>,
+) -> papermake::Result<RenderOptions> {
+    let watermark = options
+        .as_ref()
+        .and_then(|options| options.watermark.clone())
+        .map(WatermarkRequest::into_watermark)
+        .transpose()?;
+    let timezone = options
+        .as_ref()
+        .and_then(|options| options.timezone.as_deref())
+        .map(parse_timezone)
+        .transpose()?;
+    let mut options = options.map(RenderOptions::from).unwrap_or_default();
+    options.watermark = watermark;
+    options.timezone = timezone;
+    options.shared_sources = resolve_shared(storage, template).await?;
+    options.render_cache = state.render_cache.clone();
+    options.sandbox = state.sandbox.clone();
+    options.size_limits = state.size_limits;
+    options.environment = options.environment.or_else(|| state.environment.clone());
+    options.remote_resources = state.remote_resources.clone();
+    Ok(options)
+}
+
+// Asynchronous render jobs
+async fn submit_render_job(
+    State(state): State<Arc<AppState>>,
+    TenantStorage(storage): TenantStorage,
+    Tenant(namespace): Tenant,
+    Path(TemplatePath { id }): Path<TemplatePath>,
+    Query(query): Query<RenderVersionQuery>,
+    requester: TenantHistory,
+    Json(payload): Json<RenderJobRequest>,
+) -> Result<(StatusCode, Json<JobResponse>), AppError> {
+    let template = load_render_template(storage.as_ref(), id, query.version).await?;
+    if payload.formats.contains(&OutputFormat::Html) {
+        return Err(AppError::BadRequest("HTML can't be rendered in a job".to_string()));
+    }
+    if let Some(webhook) = &payload.webhook {
+        state.webhooks.check(webhook)?;
+    }
+    
+    // Fetch referenced data now and reject invalid data up front; the job
+    // renders it again wherever it runs
+    let options = render_options(&state, storage.as_ref(), &template, payload.options.clone()).await?;
+    let data = state.data_fetcher.resolve(payload.data).await?;
+    prepare_data(&template, &data, &options).map_err(invalid_data)?;
+    state.quotas.consume(requester.api_key_id.as_deref(), 1).await?;
+    
+    let job = Job::new(template.id.as_ref(), namespace.as_ref(), payload.webhook);
+    let task = JobTask::new(
+        job.id.clone(),
+        namespace.map(|ns| ns.as_str().to_string()),
+        template,
+        payload.options,
+        requester.api_key_id,
+        JobWork::Render { data, formats: payload.formats },
+    );
+    enqueue_job(&state, &job, task).await?;
+    
+    Ok((StatusCode::ACCEPTED, Json(JobResponse::from(&job))))
+}
+
+// Render many records in the background, streaming each PDF to the output sink
+async fn submit_batch_job(
+    State(state): State<Arc<AppState>>,
+    TenantStorage(storage): TenantStorage,
+    Tenant(namespace): Tenant,
+    Path(TemplatePath { id }): Path<TemplatePath>,
+    Query(query): Query<RenderVersionQuery>,
+    requester: TenantHistory,
+    Json(payload): Json<RenderBatchRequest>,
+) -> Result<(StatusCode, Json<JobResponse>), AppError> {
+    let template = load_render_template(storage.as_ref(), id, query.version).await?;
+    
+    if payload.records.is_empty() {
+        return Err(AppError::BadRequest("No records to render".to_string()));
+    }
+    if let Some(webhook) = &payload.webhook {
+        state.webhooks.check(webhook)?;
+    }
+    
+    let options = render_options(&state, storage.as_ref(), &template, payload.options.clone()).await?;
+    for (i, record) in payload.records.iter().enumerate() {
+        prepare_data(&template, record, &options)
+            .map_err(|err| AppError::BadRequest(format!("Invalid data in record {}: {}", i, err)))?;
+    }
+    state.quotas.consume(requester.api_key_id.as_deref(), payload.records.len() as u64).await?;
+    
+    let job = Job::new(template.id.as_ref(), namespace.as_ref(), payload.webhook);
+    let task = JobTask::new(
+        job.id.clone(),
+        namespace.map(|ns| ns.as_str().to_string()),
+        template,
+        payload.options,
+        requester.api_key_id,
+        JobWork::Batch { records: payload.records },
+    );
+    enqueue_job(&state, &job, task).await?;
+    
+    Ok((StatusCode::ACCEPTED, Json(JobResponse::from(&job))))
+}
+
+async fn enqueue_job(state: &AppState, job: &Job, task: JobTask) -> Result<(), AppError> {
+    state.queue.save_job(job).await?;
+    state.queue.enqueue(task).await?;
+    Ok(())
+}
+
+// Take jobs off the queue and run them until shutdown
+async fn consume_jobs(state: Arc<AppState>, shutdown: Shutdown) {
+    // Sleep between polls, waking early on shutdown
+    let idle = || async {
+        tokio::select! {
+            _ = tokio::time::sleep(QUEUE_POLL_INTERVAL) => {}
+            _ = shutdown.clone().triggered() => {}
+        }
+    };
+    while !shutdown.is_triggered() {
+        let delivery = match state.queue.dequeue().await {
+            Ok(Some(delivery)) => delivery,
+            Ok(None) => {
+                idle().await;
+                continue;
+            }
+            Err(err) => {
+                tracing::warn!("failed to take job from queue: {}", err);
+                idle().await;
+                continue;
+            }
+        };
+        
+        let job_id = delivery.task.job_id.clone();
+        let outcome = if delivery.is_exhausted() {
+            Err(PapermakeError::Rendering("Job did not finish within its attempts".to_string()))
+        } else {
+            run_job(&state, &delivery.task).await
+        };
+        
+        let settled = match outcome {
+            Ok(()) => state.queue.ack(&job_id).await,
+            Err(err) if delivery.can_retry() => {
+                tracing::warn!("job {} failed on attempt {}, retrying: {}", job_id, delivery.attempt, err);
+                state.queue.retry(&job_id).await
+            }
+            Err(err) => {
+                tracing::error!("job {} failed after {} attempts: {}", job_id, delivery.attempt, err);
+                let failed = RenderError { message: err.to_string(), start: 0, end: 0 };
+                if let Err(err) = finish_job(&state, &job_id, None, Err(vec![failed])).await {
+                    tracing::warn!("failed to update job {}: {}", job_id, err);
+                }
+                state.queue.dead_letter(&job_id).await
+            }
+        };
+        if let Err(err) = settled {
+            tracing::warn!("failed to settle job {} in queue: {}", job_id, err);
+        }
+    }
+}
+
+// Render a job and write its documents to the sink
+//
+// Render failures fail the job; storage and sink errors are returned so the
+// job is retried.
+async fn run_job(state: &Arc<AppState>, task: &JobTask) -> papermake::Result<()> {
+    let namespace = task.namespace.clone().map(Namespace::new).transpose()?;
+    let (storage, pool) = match &namespace {
+        Some(namespace) => (state.storage.for_namespace(namespace), state.world_pool.for_namespace(namespace)),
+        None => (state.storage.clone(), state.world_pool.clone()),
+    };
+    let requester = TenantHistory::new(state, namespace.as_ref(), task.api_key_id.clone());
+    update_job(state, &task.job_id, |job| job.status = JobStatus::Running).await?;
+    
+    let template = &task.template;
+    let started = std::time::Instant::now();
+    let options = match task.render_options() {
+        Ok(options) => options,
+        Err(err) => {
+            let failed = RenderError { message: err.to_string(), start: 0, end: 0 };
+            return finish_job(state, &task.job_id, Some(started.elapsed()), Err(vec![failed])).await;
+        }
+    };
+    let mut options = build_render_options(state, storage.as_ref(), template, options).await?;
+    
+    let outputs = match &task.work {
+        JobWork::Render { data, formats } if !formats.is_empty() => {
+            let prepared = match prepare_data(template, data, &options) {
+                Ok(prepared) => prepared,
+                Err(err) => {
+                    let failed = RenderError { message: format!("Invalid data: {}", err), start: 0, end: 0 };
+                    return finish_job(state, &task.job_id, Some(started.elapsed()), Err(vec![failed])).await;
+                }
+            };
+            options.data_prepared = true;
+            
+            let record = RenderRecord::new(task.job_id.clone(), template, data)
+                .with_api_key_id(task.api_key_id.clone());
+            let _permit = state.scheduler.wait(template.id.as_ref(), task.api_key_id.as_deref()).await;
+            let timer = state.metrics.start_render(template.id.as_ref());
+            let result = {
+                let (template, formats) = (template.clone(), formats.clone());
+                tokio::task::spawn_blocking(move || render_all(&template, &prepared, &formats, Some(options)))
+                    .await
+                    .map_err(|e| PapermakeError::Rendering(format!("Render task failed: {}", e)))?
+            };
+            if let Ok(bundle) = &result {
+                timer.finish(bundle.is_success(), bundle.errors.len());
+            }
+            let record = record.finish_bundle(started.elapsed(), &result);
+            record_render(state, &requester, &record, data).await;
+            
+            match result {
+                Ok(bundle) if bundle.is_success() => write_bundle(state, &task.job_id, bundle).await?,
+                Ok(bundle) => return finish_job(state, &task.job_id, Some(started.elapsed()), Err(bundle.errors)).await,
+                Err(e) => {
+                    let failed = RenderError { message: e.to_string(), start: 0, end: 0 };
+                    return finish_job(state, &task.job_id, Some(started.elapsed()), Err(vec![failed])).await;
+                }
+            }
+        }
+        JobWork::Render { data, .. } => {
+            let prepared = match prepare_data(template, data, &options) {
+                Ok(prepared) => prepared,
+                Err(err) => {
+                    let failed = RenderError { message: format!("Invalid data: {}", err), start: 0, end: 0 };
+                    return finish_job(state, &task.job_id, Some(started.elapsed()), Err(vec![failed])).await;
+                }
+            };
+            options.data_prepared = true;
+            
+            // The audit record shares the job's id
+            let record = RenderRecord::new(task.job_id.clone(), template, data)
+                .with_api_key_id(task.api_key_id.clone());
+            let _permit = state.scheduler.wait(template.id.as_ref(), task.api_key_id.as_deref()).await;
+            let timer = state.metrics.start_render(template.id.as_ref());
+            let result = pool.render_async(template, &prepared, Some(options)).await;
+            if let Ok(result) = &result {
+                timer.finish(result.pdf.is_some(), result.errors.len());
+            }
+            let record = record.finish(started.elapsed(), &result);
+            record_render(state, &requester, &record, data).await;
+            
+            // Write the document to the sink before marking the job finished
+            match result {
+                Ok(result) => match result.pdf {
+                    Some(pdf) => {
+                        let key = format!("jobs/{}/{:06}.pdf", task.job_id, 0);
+                        let size_bytes = pdf.len();
+                        let url = state.sink.write(&key, pdf).await?;
+                        vec![BatchItem { index: 0, key: Some(key), url: Some(url), size_bytes: Some(size_bytes), errors: Vec::new() }]
+                    }
+                    None => return finish_job(state, &task.job_id, Some(started.elapsed()), Err(result.errors)).await,
+                },
+                Err(e) => {
+                    let failed = RenderError { message: e.to_string(), start: 0, end: 0 };
+                    return finish_job(state, &task.job_id, Some(started.elapsed()), Err(vec![failed])).await;
+                }
+            }
+        }
+        JobWork::Batch { records } => {
+            let records = match records.iter()
+                .enumerate()
+                .map(|(i, record)| prepare_data(template, record, &options)
+                    .map_err(|err| format!("Invalid data in record {}: {}", i, err)))
+                .collect::<Result<Vec<_>, _>>()
+            {
+                Ok(records) => records,
+                Err(message) => {
+                    let failed = RenderError { message, start: 0, end: 0 };
+                    return finish_job(state, &task.job_id, Some(started.elapsed()), Err(vec![failed])).await;
+                }
+            };
+            options.data_prepared = true;
+            let key_prefix = format!("jobs/{}", task.job_id);
+            let _permit = state.scheduler.wait(template.id.as_ref(), task.api_key_id.as_deref()).await;
+            render_batch(template, &records, options, state.sink.as_ref(), &key_prefix).await?
+        }
+    };
+    
+    finish_job(state, &task.job_id, Some(started.elapsed()), Ok(outputs)).await
+}
+
+// Write every file of a bundle to the sink under the job's prefix, the PDF
+// first, then page images and the extracted text
+async fn write_bundle(state: &AppState, job_id: &str, bundle: RenderBundle) -> papermake::Result<Vec<BatchItem>> {
+    let text = (!bundle.text.is_empty()).then(|| bundle.full_text().into_bytes());
+    let files = bundle.pdf.into_iter().map(|pdf| ("pdf", pdf))
+        .chain(bundle.png.into_iter().map(|png| ("png", png)))
+        .chain(bundle.svg.into_iter().map(|svg| ("svg", svg)))
+        .chain(text.map(|text| ("txt", text)));
+    
+    let mut items = Vec::new();
+    for (index, (extension, file)) in files.enumerate() {
+        let key = format!("jobs/{}/{:06}.{}", job_id, index, extension);
+        let size_bytes = file.len();
+        let url = state.sink.write(&key, file).await?;
+        items.push(BatchItem { index, key: Some(key), url: Some(url), size_bytes: Some(size_bytes), errors: Vec::new() });
+    }
+    Ok(items)
+}
+
+// Record the outcome of a job and notify its webhook
+//
+// Per-record errors are reported on the outputs; the job only fails if
+// nothing could be rendered.
+async fn finish_job(
+    state: &AppState,
+    job_id: &str,
+    duration: Option<std::time::Duration>,
+    outcome: Result<Vec<BatchItem>, Vec<RenderError>>,
+) -> papermake::Result<()> {
+    let finished = update_job(state, job_id, |job| {
+        job.finished_at = Some(time::OffsetDateTime::now_utc());
+        job.duration_ms = duration.map(|d| d.as_millis() as u64);
+        match outcome {
+            Ok(items) => {
+                job.status = if items.iter().any(BatchItem::is_success) {
+                    JobStatus::Completed
+                } else {
+                    JobStatus::Failed
+                };
+                job.outputs = items;
+            }
+            Err(errors) => {
+                job.status = JobStatus::Failed;
+                job.errors = errors;
+            }
+        }
+    }).await?;
+    
+    if let Some(job) = finished {
+        state.webhooks.notify(&job).await;
+    }
+    Ok(())
+}
+
+// Apply a change to a stored job, returning the updated job
+async fn update_job(state: &AppState, id: &str, f: impl FnOnce(&mut Job)) -> papermake::Result<Option<Job>> {
+    let Some(mut job) = state.queue.get_job(id).await? else {
+        return Ok(None);
+    };
+    f(&mut job);
+    state.queue.save_job(&job).await?;
+    Ok(Some(job))
+}
+
+async fn get_job(
+    State(state): State<Arc<AppState>>,
+    Tenant(namespace): Tenant,
+    Path(JobPath { id }): Path<JobPath>,
+) -> Result<Json<JobResponse>, AppError> {
+    let job = tenant_job(&state, namespace.as_ref(), &id).await?;
+    Ok(Json(JobResponse::from(&job)))
+}
+
+async fn get_job_pdf(
+    state: State<Arc<AppState>>,
+    tenant: Tenant,
+    Path(JobPath { id }): Path<JobPath>,
+) -> Result<impl IntoResponse, AppError> {
+    get_job_output(state, tenant, Path(JobOutputPath { id, index: 0 })).await
+}
+
+async fn get_job_output(
+    State(state): State<Arc<AppState>>,
+    Tenant(namespace): Tenant,
+    Path(JobOutputPath { id, index }): Path<JobOutputPath>,
+) -> Result<impl IntoResponse, AppError> {
+    let job = tenant_job(&state, namespace.as_ref(), &id).await?;
+    let key = job_output_key(job, index)?;
+    let file = state.sink.read(&key).await?;
+    Ok(([(header::CONTENT_TYPE, output_content_type(&key))], file))
+}
+
+// A job of the request's namespace; other tenants' jobs are not found
+async fn tenant_job(state: &AppState, namespace: Option<&Namespace>, id: &str) -> Result<Job, AppError> {
+    state.queue.get_job(id).await?
+        .filter(|job| job.belongs_to(namespace))
+        .ok_or(AppError::NotFound)
+}
+
+fn job_output_key(job: Job, index: usize) -> Result<String, AppError> {
+    job.outputs.into_iter()
+        .find(|item| item.index == index)
+        .and_then(|item| item.key)
+        .ok_or(AppError::NotFound)
+}
+
+// Jobs rendering several formats store them side by side
+fn output_content_type(key: &str) -> &'static str {
+    match key.rsplit_once('.').map(|(_, extension)| extension) {
+        Some("txt") => "text/plain; charset=utf-8",
+        extension => extension
+            .and_then(|extension| extension.parse::<OutputFormat>().ok())
+            .unwrap_or_default()
+            .content_type(),
+    }
+}
+
+// Hand out a signed link to a job's document that works without an API key
+async fn create_download_link(
+    State(state): State<Arc<AppState>>,
+    Tenant(namespace): Tenant,
+    Path(JobOutputPath { id, index }): Path<JobOutputPath>,
+    Query(query): Query<DownloadLinkQuery>,
+) -> Result<Json<DownloadLink>, AppError> {
+    let signer = state.downloads.as_ref().ok_or_else(|| {
+        AppError::BadRequest("Download links are disabled; set PAPERMAKE_DOWNLOAD_SECRET".to_string())
+    })?;
+    if query.ttl_secs == Some(0) {
+        return Err(AppError::BadRequest("ttl_secs must be positive".to_string()));
+    }
+    let job = tenant_job(&state, namespace.as_ref(), &id).await?;
+    let key = job_output_key(job, index)?;
+    Ok(Json(signer.sign(namespace.as_ref(), &key, query.ttl_secs.map(std::time::Duration::from_secs))))
+}
+
+// Serve the document behind a signed link; forged and expired links, and
+// links to documents no longer held by a job of the signed namespace, are
+// not found
+async fn download(
+    State(state): State<Arc<AppState>>,
+    Path(token): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let (namespace, key) = state.downloads.as_ref()
+        .and_then(|signer| signer.verify(&token))
+        .ok_or(AppError::NotFound)?;
+    let job_id = key.strip_prefix("jobs/").and_then(|rest| rest.split('/').next()).ok_or(AppError::NotFound)?;
+    let job = tenant_job(&state, namespace.as_ref(), job_id).await?;
+    if !job.outputs.iter().any(|item| item.key.as_deref() == Some(key.as_str())) {
+        return Err(AppError::NotFound);
+    }
+    let file = state.sink.read(&key).await?;
+    let filename = key.rsplit('/').next().unwrap_or(&key);
+    let disposition = format!("attachment; filename=\"{}\"", filename);
+    Ok((
+        [(header::CONTENT_TYPE, output_content_type(&key).to_string()), (header::CONTENT_DISPOSITION, disposition)],
+        file,
+    ))
+}
+
+// Check template source against its schema
+async fn lint_template(
+    TenantStorage(storage): TenantStorage,
+    Path(TemplatePath { id }): Path<TemplatePath>,
+) -> Result<Json<LintReport>, AppError> {
+    let template = storage.get_template(&TemplateId(id)).await?;
+    Ok(Json(template.lint()))
+}
+
+// Compile the working copy with placeholder data, returning diagnostics only
+async fn check_template(
+    State(state): State<Arc<AppState>>,
+    TenantStorage(storage): TenantStorage,
+    Path(TemplatePath { id }): Path<TemplatePath>,
+    Query(query): Query<CheckQuery>,
+) -> Result<Json<CheckReport>, AppError> {
+    let template = storage.get_template(&TemplateId(id)).await?;
+    let mut options = render_options(&state, storage.as_ref(), &template, None).await?;
+    options.locale = query.locale;
+    let report = tokio::task::spawn_blocking(move || template.check_with(&options))
+        .await
+        .map_err(|e| AppError::Papermake(PapermakeError::Rendering(e.to_string())))??;
+    Ok(Json(report))
+}
+
+// Diagnostics, completions and hover docs for the template editor
+async fn analyze_template_handler(
+    State(state): State<Arc<AppState>>,
+    TenantStorage(storage): TenantStorage,
+    Path(TemplatePath { id }): Path<TemplatePath>,
+    Json(payload): Json<AnalyzeRequest>,
+) -> Result<Json<Analysis>, AppError> {
+    let mut template = storage.get_template(&TemplateId(id)).await?;
+    if let Some(content) = payload.content {
+        template.content = content;
+    }
+    if let Some(schema) = payload.schema {
+        template.schema = schema;
+    }
+    
+    let options = render_options(&state, storage.as_ref(), &template, payload.options).await?;
+    let analysis = tokio::task::spawn_blocking(move || {
+        analyze_template(&template, payload.cursor, payload.data.as_ref(), &options)
+    })
+        .await
+        .map_err(|e| AppError::Papermake(PapermakeError::Rendering(e.to_string())))?;
+    Ok(Json(analysis))
+}
+
+// Copy a template and its files to a new id
+async fn clone_template(
+    State(state): State<Arc<AppState>>,
+    TenantStorage(storage): TenantStorage,
+    Path(TemplatePath { id }): Path<TemplatePath>,
+    Json(payload): Json<CloneTemplateRequest>,
+) -> Result<Json<TemplateResponse>, AppError> {
+    let (id, new_id) = (TemplateId(id), TemplateId(payload.id));
+    papermake::storage::validate_template_id(&new_id)?;
+    storage.get_template(&id).await?;
+    
+    let mut template = storage.copy_template(&id, &new_id).await?;
+    if let Some(name) = payload.name {
+        template.name = name;
+        storage.save_template(&template).await?;
+        template.revision += 1;
+    }
+    state.metrics.template_operation("clone");
+    Ok(Json(TemplateResponse::from(template)))
+}
+
+// Templates importing a shared template, directly or transitively
+async fn list_dependents(
+    TenantStorage(storage): TenantStorage,
+    Path(TemplatePath { id }): Path<TemplatePath>,
+) -> Result<Json<Vec<Dependent>>, AppError> {
+    let id = TemplateId(id);
+    storage.get_template(&id).await?;
+    Ok(Json(storage.get_dependents(&id).await?))
+}
+
+// Run all examples attached to a template
+async fn test_template(
+    TenantStorage(storage): TenantStorage,
+    Path(TemplatePath { id }): Path<TemplatePath>,
+) -> Result<Json<Vec<ExampleReport>>, AppError> {
+    let template = storage.get_template(&TemplateId(id)).await?;
+    
+    let reports = tokio::task::spawn_blocking(move || run_examples(&template))
+        .await
+        .map_err(|e| AppError::Papermake(PapermakeError::Rendering(e.to_string())))?;
+    
+    Ok(Json(reports))
+}
+
+// Compare the draft of a template visually against another version
+async fn diff_template(
+    State(state): State<Arc<AppState>>,
+    TenantStorage(storage): TenantStorage,
+    Path(TemplatePath { id }): Path<TemplatePath>,
+    Query(query): Query<DiffQuery>,
+    Json(payload): Json<DiffRequest>,
+) -> Result<Json<DiffReport>, AppError> {
+    let baseline = load_render_template(storage.as_ref(), id.clone(), Some(query.against.unwrap_or_else(|| "published".to_string()))).await?;
+    let draft = load_render_template(storage.as_ref(), id, Some("draft".to_string())).await?;
+    
+    let draft_options = render_options(&state, storage.as_ref(), &draft, payload.options).await?;
+    let mut baseline_options = draft_options.clone();
+    baseline_options.shared_sources = resolve_shared(storage.as_ref(), &baseline).await
+        .map_err(|err| AppError::BadRequest(format!("Failed to resolve imports: {}", err)))?;
+    
+    let data = payload.data.unwrap_or_else(|| draft.schema.generate_sample_data(0));
+    let diff_options = DiffOptions { tolerance: payload.tolerance, ..DiffOptions::default() };
+    let report = tokio::task::spawn_blocking(move || {
+        // Each version validates the data against its own schema
+        let before = compile_document(&baseline, &data, &baseline_options)?;
+        let after = compile_document(&draft, &data, &draft_options)?;
+        Ok::<_, PapermakeError>(diff_documents(&before, &after, &diff_options))
+    })
+        .await
+        .map_err(|e| AppError::Papermake(PapermakeError::Rendering(e.to_string())))?
+        .map_err(|err| match err {
+            PapermakeError::SchemaValidation(msg) => AppError::BadRequest(format!("Invalid data: {}", msg)),
+            err => AppError::Papermake(err),
+        })?;
+    
+    Ok(Json(report))
+}
+
+// Content, schema and file changes from version `a` to version `b`, e.g.
+// from the published revision to the draft under review
+async fn diff_versions(
+    TenantStorage(storage): TenantStorage,
+    Path(VersionDiffPath { id, a, b }): Path<VersionDiffPath>,
+) -> Result<Json<TemplateChanges>, AppError> {
+    let id = TemplateId(id);
+    let old = load_version(storage.as_ref(), &id, &a).await?;
+    let new = load_version(storage.as_ref(), &id, &b).await?;
+    Ok(Json(old.changes(&new)))
+}
+
+// A version of a template with the manifest of its files; the draft's files
+// are the stored ones, revisions published before manifests were recorded
+// are taken to have them too
+async fn load_version(storage: &dyn Storage, id: &TemplateId, version: &str) -> Result<Template, AppError> {
+    let mut template = match version.parse::<TemplateVersion>()? {
+        TemplateVersion::Draft => storage.get_template(id).await?,
+        TemplateVersion::Published => template_for_render(storage, id, TemplateVersion::Published).await?,
+    };
+    if template.assets.is_empty() {
+        template.assets = asset_manifest(storage, id).await?;
+    }
+    Ok(template)
+}
+
+// Generate starter Typst content for a schema
+async fn scaffold_template(Json(payload): Json<ScaffoldRequest>) -> Json<ScaffoldResponse> {
+    Json(ScaffoldResponse {
+        content: Template::scaffold(&payload.schema, payload.style),
+    })
+}
+
+// Generate fake data matching a template's schema for previews
+async fn sample_data(
+    TenantStorage(storage): TenantStorage,
+    Path(TemplatePath { id }): Path<TemplatePath>,
+    Query(query): Query<SampleDataQuery>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let template = storage.get_template(&TemplateId(id)).await?;
+    
+    let mut options = SampleOptions::default();
+    if let Some(array_len) = query.array_len {
+        options.array_len = array_len.min(100);
+    }
+    Ok(Json(template.schema.generate_sample_data_with(query.seed, &options)))
 }
 
 // Template file operations
 async fn list_template_files(
-    State(state): State<Arc<AppState>>,
-    Path(id): Path<String>,
+    TenantStorage(storage): TenantStorage,
+    Path(TemplatePath { id }): Path<TemplatePath>,
 ) -> Result<Json<Vec<String>>, AppError> {
-    let files = state.storage.list_template_files(&TemplateId(id)).await
-        .map_err(|_| AppError::NotFound)?;
+    let files = storage.list_template_files(&TemplateId(id)).await?;
     Ok(Json(files))
 }
 
-async fn get_template_file(
+// Upload one or more files as multipart/form-data; each part's file name is
+// its path, optionally below `?dir=`
+async fn upload_template_files(
     State(state): State<Arc<AppState>>,
-    Path((id, path)): Path<(String, String)>,
+    TenantStorage(storage): TenantStorage,
+    Path(TemplatePath { id }): Path<TemplatePath>,
+    Query(query): Query<UploadFilesQuery>,
+    mut multipart: Multipart,
+) -> Result<Json<Vec<UploadedFile>>, AppError> {
+    let id = TemplateId(id);
+    storage.get_template(&id).await?;
+    
+    let multipart_error = |e: axum::extract::multipart::MultipartError| AppError::BadRequest(e.body_text());
+    
+    // Read and validate every part before storing anything, so a rejected
+    // request leaves no partial uploads behind
+    let mut files = Vec::new();
+    while let Some(mut field) = multipart.next_field().await.map_err(multipart_error)? {
+        let Some(file_name) = field.file_name().map(str::to_string) else {
+            continue;
+        };
+        let path = match query.dir.as_deref().map(|dir| dir.trim_matches('/')) {
+            Some(dir) if !dir.is_empty() => format!("{}/{}", dir, file_name),
+            _ => file_name,
+        };
+        papermake::storage::validate_file_path(&path)
+            .map_err(|e| AppError::BadRequest(e.to_string()))?;
+        let content_type = field.content_type().map(str::to_string);
+        validate_content_type(&path, content_type.as_deref())
+            .map_err(AppError::BadRequest)?;
+        
+        let mut content = Vec::new();
+        while let Some(chunk) = field.chunk().await.map_err(multipart_error)? {
+            state.size_limits.check_asset(&path, content.len() + chunk.len())?;
+            content.extend_from_slice(&chunk);
+        }
+        files.push((path, content_type, content));
+    }
+    
+    if files.is_empty() {
+        return Err(AppError::BadRequest("No files in request".to_string()));
+    }
+    
+    let mut uploaded = Vec::with_capacity(files.len());
+    for (path, content_type, content) in files {
+        storage.save_template_file(&id, &path, &content).await?;
+        uploaded.push(UploadedFile {
+            size_bytes: content.len(),
+            path,
+            content_type,
+        });
+    }
+    Ok(Json(uploaded))
+}
+
+async fn get_template_file(
+    TenantStorage(storage): TenantStorage,
+    Path(TemplateFilePath { id, path }): Path<TemplateFilePath>,
 ) -> Result<Vec<u8>, AppError> {
-    let content = state.storage.get_template_file(&TemplateId(id), &path).await
-        .map_err(|_| AppError::NotFound)?;
+    let content = storage.get_template_file(&TemplateId(id), &path).await?;
     Ok(content)
 }
 
 async fn save_template_file(
     State(state): State<Arc<AppState>>,
-    Path((id, path)): Path<(String, String)>,
+    TenantStorage(storage): TenantStorage,
+    Path(TemplateFilePath { id, path }): Path<TemplateFilePath>,
     body: axum::body::Bytes,
 ) -> Result<StatusCode, AppError> {
-    state.storage.save_template_file(&TemplateId(id), &path, &body).await?;
+    state.size_limits.check_asset(&path, body.len())?;
+    storage.save_template_file(&TemplateId(id), &path, &body).await?;
     Ok(StatusCode::NO_CONTENT)
 }
 
 async fn delete_template_file(
-    State(state): State<Arc<AppState>>,
-    Path((id, path)): Path<(String, String)>,
+    TenantStorage(storage): TenantStorage,
+    Path(TemplateFilePath { id, path }): Path<TemplateFilePath>,
+) -> Result<StatusCode, AppError> {
+    storage.delete_template_file(&TemplateId(id), &path).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn rename_template_file(
+    TenantStorage(storage): TenantStorage,
+    Path(TemplateFilePath { id, path }): Path<TemplateFilePath>,
+    Json(payload): Json<RenameFileRequest>,
 ) -> Result<StatusCode, AppError> {
-    // This method might need to be added to your Storage trait
-    // For now, we'll just acknowledge the request
+    storage.rename_template_file(&TemplateId(id), &path, &payload.to).await
+        .map_err(|e| match e {
+            PapermakeError::NotFound { .. } | PapermakeError::TemplateNotFound { .. } => AppError::NotFound,
+            e => AppError::BadRequest(e.to_string()),
+        })?;
     Ok(StatusCode::NO_CONTENT)
 }
 
-// Health check
-async fn health_check() -> StatusCode {
-    StatusCode::OK
-}
\ No newline at end of file
+// Prometheus metrics
+async fn metrics_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        state.metrics.encode(),
+    )
+}
@@ -0,0 +1,247 @@
+//! Prometheus metrics for renders, template operations and storage
+
+use std::sync::Arc;
+use std::time::Instant;
+
+use async_trait::async_trait;
+use papermake::{
+    error::Result,
+    shared::Dependent,
+    storage::{ArtifactKind, ListOptions, Namespace, PurgeReport, SearchHit, Storage, TemplatePage},
+    template::{Template, TemplateId},
+};
+use time::OffsetDateTime;
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, Opts, Registry, TextEncoder,
+};
+
+/// All metrics exported by the server
+pub struct Metrics {
+    registry: Registry,
+    pub renders_total: IntCounterVec,
+    pub render_duration: HistogramVec,
+    pub compile_errors_total: IntCounterVec,
+    pub template_operations_total: IntCounterVec,
+    pub storage_duration: HistogramVec,
+    pub renders_in_flight: IntGauge,
+    pub render_queue_depth: IntGauge,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let registry = Registry::new_custom(Some("papermake".to_string()), None)
+            .expect("valid metrics prefix");
+
+        let renders_total = IntCounterVec::new(
+            Opts::new("renders_total", "Total number of renders by template and outcome"),
+            &["template", "status"],
+        )
+        .unwrap();
+        let render_duration = HistogramVec::new(
+            HistogramOpts::new("render_duration_seconds", "Render duration by template")
+                .buckets(vec![0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0]),
+            &["template"],
+        )
+        .unwrap();
+        let compile_errors_total = IntCounterVec::new(
+            Opts::new("compile_errors_total", "Typst compile errors by template"),
+            &["template"],
+        )
+        .unwrap();
+        let template_operations_total = IntCounterVec::new(
+            Opts::new("template_operations_total", "Template CRUD operations"),
+            &["operation"],
+        )
+        .unwrap();
+        let storage_duration = HistogramVec::new(
+            HistogramOpts::new("storage_duration_seconds", "Storage call latency by operation")
+                .buckets(vec![0.0005, 0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0]),
+            &["operation", "status"],
+        )
+        .unwrap();
+        let renders_in_flight = IntGauge::new("renders_in_flight", "Renders currently in progress").unwrap();
+        let render_queue_depth =
+            IntGauge::new("render_queue_depth", "Renders waiting for a concurrency slot").unwrap();
+
+        registry.register(Box::new(renders_total.clone())).unwrap();
+        registry.register(Box::new(render_duration.clone())).unwrap();
+        registry.register(Box::new(compile_errors_total.clone())).unwrap();
+        registry.register(Box::new(template_operations_total.clone())).unwrap();
+        registry.register(Box::new(storage_duration.clone())).unwrap();
+        registry.register(Box::new(renders_in_flight.clone())).unwrap();
+        registry.register(Box::new(render_queue_depth.clone())).unwrap();
+
+        Self {
+            registry,
+            renders_total,
+            render_duration,
+            compile_errors_total,
+            template_operations_total,
+            storage_duration,
+            renders_in_flight,
+            render_queue_depth,
+        }
+    }
+
+    /// Start tracking a render; the returned guard records the outcome when finished
+    pub fn start_render<'a>(&'a self, template: &'a str) -> RenderTimer<'a> {
+        self.renders_in_flight.inc();
+        RenderTimer {
+            metrics: self,
+            template,
+            started: Instant::now(),
+            finished: false,
+        }
+    }
+
+    /// Count a template CRUD operation
+    pub fn template_operation(&self, operation: &str) {
+        self.template_operations_total.with_label_values(&[operation]).inc();
+    }
+
+    /// Encode all metrics in the Prometheus text exposition format
+    pub fn encode(&self) -> String {
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .expect("text encoding never fails");
+        String::from_utf8(buffer).expect("metrics are valid UTF-8")
+    }
+}
+
+/// Records duration and outcome of a single render
+pub struct RenderTimer<'a> {
+    metrics: &'a Metrics,
+    template: &'a str,
+    started: Instant,
+    finished: bool,
+}
+
+impl RenderTimer<'_> {
+    /// Record a finished render with the number of compile errors it produced
+    pub fn finish(mut self, success: bool, compile_errors: usize) {
+        self.record(if success { "success" } else { "failure" });
+        if compile_errors > 0 {
+            self.metrics
+                .compile_errors_total
+                .with_label_values(&[self.template])
+                .inc_by(compile_errors as u64);
+        }
+    }
+
+    fn record(&mut self, status: &str) {
+        self.finished = true;
+        self.metrics.renders_in_flight.dec();
+        self.metrics
+            .render_duration
+            .with_label_values(&[self.template])
+            .observe(self.started.elapsed().as_secs_f64());
+        self.metrics
+            .renders_total
+            .with_label_values(&[self.template, status])
+            .inc();
+    }
+}
+
+impl Drop for RenderTimer<'_> {
+    fn drop(&mut self) {
+        // Renders abandoned without calling `finish` (e.g. early returns) count as errors
+        if !self.finished {
+            self.record("error");
+        }
+    }
+}
+
+/// Storage decorator recording the latency of every call
+pub struct InstrumentedStorage {
+    inner: Arc<dyn Storage>,
+    metrics: Arc<Metrics>,
+}
+
+impl InstrumentedStorage {
+    pub fn new(inner: Arc<dyn Storage>, metrics: Arc<Metrics>) -> Self {
+        Self { inner, metrics }
+    }
+
+    async fn timed<T>(&self, operation: &str, call: impl std::future::Future<Output = Result<T>>) -> Result<T> {
+        let started = Instant::now();
+        let result = call.await;
+        let status = if result.is_ok() { "ok" } else { "error" };
+        self.metrics
+            .storage_duration
+            .with_label_values(&[operation, status])
+            .observe(started.elapsed().as_secs_f64());
+        result
+    }
+}
+
+#[async_trait]
+impl Storage for InstrumentedStorage {
+    async fn save_template(&self, template: &Template) -> Result<()> {
+        self.timed("save_template", self.inner.save_template(template)).await
+    }
+
+    async fn get_template(&self, id: &TemplateId) -> Result<Template> {
+        self.timed("get_template", self.inner.get_template(id)).await
+    }
+
+    async fn list_templates(&self, options: &ListOptions) -> Result<TemplatePage> {
+        self.timed("list_templates", self.inner.list_templates(options)).await
+    }
+
+    async fn search_templates(&self, query: &str) -> Result<Vec<SearchHit>> {
+        self.timed("search_templates", self.inner.search_templates(query)).await
+    }
+
+    async fn get_dependents(&self, id: &TemplateId) -> Result<Vec<Dependent>> {
+        self.timed("get_dependents", self.inner.get_dependents(id)).await
+    }
+
+    async fn copy_template(&self, id: &TemplateId, new_id: &TemplateId) -> Result<Template> {
+        self.timed("copy_template", self.inner.copy_template(id, new_id)).await
+    }
+
+    async fn delete_template(&self, id: &TemplateId) -> Result<()> {
+        self.timed("delete_template", self.inner.delete_template(id)).await
+    }
+
+    async fn save_template_file(&self, id: &TemplateId, path: &str, content: &[u8]) -> Result<()> {
+        self.timed("save_template_file", self.inner.save_template_file(id, path, content)).await
+    }
+
+    async fn get_template_file(&self, id: &TemplateId, path: &str) -> Result<Vec<u8>> {
+        self.timed("get_template_file", self.inner.get_template_file(id, path)).await
+    }
+
+    async fn list_template_files(&self, id: &TemplateId) -> Result<Vec<String>> {
+        self.timed("list_template_files", self.inner.list_template_files(id)).await
+    }
+
+    async fn delete_template_file(&self, id: &TemplateId, path: &str) -> Result<()> {
+        self.timed("delete_template_file", self.inner.delete_template_file(id, path)).await
+    }
+
+    async fn rename_template_file(&self, id: &TemplateId, from: &str, to: &str) -> Result<()> {
+        self.timed("rename_template_file", self.inner.rename_template_file(id, from, to)).await
+    }
+
+    async fn save_published_template(&self, template: &Template) -> Result<()> {
+        self.timed("save_published_template", self.inner.save_published_template(template)).await
+    }
+
+    async fn get_published_template(&self, id: &TemplateId) -> Result<Template> {
+        self.timed("get_published_template", self.inner.get_published_template(id)).await
+    }
+
+    async fn purge(&self, before: OffsetDateTime, kinds: &[ArtifactKind]) -> Result<PurgeReport> {
+        self.timed("purge", self.inner.purge(before, kinds)).await
+    }
+
+    async fn check_health(&self) -> Result<()> {
+        self.timed("check_health", self.inner.check_health()).await
+    }
+
+    fn for_namespace(&self, namespace: &Namespace) -> Arc<dyn Storage> {
+        Arc::new(InstrumentedStorage::new(self.inner.for_namespace(namespace), self.metrics.clone()))
+    }
+}
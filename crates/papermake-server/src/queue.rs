@@ -0,0 +1,605 @@
+//! Queue distributing asynchronous render jobs across server replicas
+//!
+//! Submitting a job stores its [`Job`] and enqueues a [`JobTask`] describing
+//! the work. Every replica runs consumers taking tasks off the queue, so with
+//! a shared backend (`PAPERMAKE_QUEUE=redis://...`) any replica may render a
+//! job and answer status requests for it. A task that isn't acknowledged
+//! within the visibility timeout, e.g. because its replica crashed, is
+//! delivered again; once it has used up its attempts it is moved to the
+//! dead-letter queue.
+//!
+//! The in-memory queue writes its unfinished tasks to a snapshot file on
+//! shutdown and reloads them on startup, so a restart doesn't drop work.
+//!
+//! PDF passwords are never written to a snapshot or to Redis: they stay in
+//! the memory of the process a job was submitted to. Encrypted jobs
+//! delivered to another replica, or resumed after a restart, fail.
+
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use papermake::error::{PapermakeError, Result};
+use papermake::{OutputFormat, Template};
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+
+use crate::jobs::{Job, JobStatus, JobStore};
+use crate::{EncryptionRequest, RenderOptionsRequest};
+
+/// What a job renders
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum JobWork {
+    /// A single document, exported to `formats` from one compilation; just
+    /// the PDF if empty
+    Render {
+        data: serde_json::Value,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        formats: Vec<OutputFormat>,
+    },
+    /// One document per record, streamed to the output sink
+    Batch { records: Vec<serde_json::Value> },
+}
+
+/// Everything a replica needs to run a job
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobTask {
+    pub job_id: String,
+    /// Tenant namespace the job was submitted to
+    pub namespace: Option<String>,
+    /// The template version resolved when the job was submitted
+    pub template: Template,
+    /// Render options without their PDF encryption, see [`JobTask::render_options`]
+    pub options: Option<RenderOptionsRequest>,
+    /// PDF encryption, passwords included; only kept in memory
+    #[serde(skip)]
+    pub encryption: Option<EncryptionRequest>,
+    /// Whether the job encrypts its PDF, so consumers without its
+    /// passwords fail it rather than render it unencrypted
+    #[serde(default)]
+    pub encrypted: bool,
+    pub api_key_id: Option<String>,
+    pub work: JobWork,
+}
+
+impl JobTask {
+    /// Task whose PDF encryption is moved out of the serialized options
+    pub fn new(
+        job_id: String,
+        namespace: Option<String>,
+        template: Template,
+        mut options: Option<RenderOptionsRequest>,
+        api_key_id: Option<String>,
+        work: JobWork,
+    ) -> Self {
+        let encryption = options.as_mut().and_then(|options| options.encryption.take());
+        Self {
+            job_id,
+            namespace,
+            template,
+            options,
+            encrypted: encryption.is_some(),
+            encryption,
+            api_key_id,
+            work,
+        }
+    }
+
+    /// The options the job was submitted with, PDF encryption included
+    pub fn render_options(&self) -> Result<Option<RenderOptionsRequest>> {
+        if self.encrypted && self.encryption.is_none() {
+            return Err(PapermakeError::Rendering(
+                "The job's PDF passwords are only kept by the server process it was submitted to".to_string(),
+            ));
+        }
+        let mut options = self.options.clone();
+        if let Some(options) = &mut options {
+            options.encryption = self.encryption.clone();
+        }
+        Ok(options)
+    }
+}
+
+/// A task taken off the queue
+#[derive(Debug)]
+pub struct Delivery {
+    pub task: JobTask,
+    /// How often the task has been delivered, including this delivery
+    pub attempt: u32,
+    pub max_attempts: u32,
+}
+
+impl Delivery {
+    /// Whether a failed attempt may be retried
+    pub fn can_retry(&self) -> bool {
+        self.attempt < self.max_attempts
+    }
+
+    /// Whether earlier deliveries already used up all attempts without
+    /// being acknowledged, e.g. because rendering crashed the replica
+    pub fn is_exhausted(&self) -> bool {
+        self.attempt > self.max_attempts
+    }
+}
+
+/// Delivery settings of a queue
+#[derive(Debug, Clone)]
+pub struct QueueConfig {
+    /// How long a delivered task stays invisible to other consumers
+    pub visibility_timeout: Duration,
+    /// Deliveries of a task before it is dead-lettered
+    pub max_attempts: u32,
+    /// How long finished jobs are kept by the in-memory queue
+    pub job_ttl: Duration,
+    /// Jobs kept by the in-memory queue; the oldest finished ones are
+    /// dropped first
+    pub max_jobs: usize,
+}
+
+impl Default for QueueConfig {
+    fn default() -> Self {
+        Self {
+            visibility_timeout: Duration::from_secs(300),
+            max_attempts: 3,
+            job_ttl: Duration::from_secs(7 * 24 * 60 * 60),
+            max_jobs: 10_000,
+        }
+    }
+}
+
+impl QueueConfig {
+    /// Read `PAPERMAKE_QUEUE_VISIBILITY_TIMEOUT` (seconds),
+    /// `PAPERMAKE_QUEUE_MAX_ATTEMPTS`, `PAPERMAKE_QUEUE_JOB_TTL` (seconds)
+    /// and `PAPERMAKE_QUEUE_MAX_JOBS`
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        Self {
+            visibility_timeout: std::env::var("PAPERMAKE_QUEUE_VISIBILITY_TIMEOUT")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.visibility_timeout),
+            max_attempts: std::env::var("PAPERMAKE_QUEUE_MAX_ATTEMPTS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .filter(|attempts| *attempts > 0)
+                .unwrap_or(defaults.max_attempts),
+            job_ttl: std::env::var("PAPERMAKE_QUEUE_JOB_TTL")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .filter(|secs| *secs > 0)
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.job_ttl),
+            max_jobs: std::env::var("PAPERMAKE_QUEUE_MAX_JOBS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .filter(|jobs| *jobs > 0)
+                .unwrap_or(defaults.max_jobs),
+        }
+    }
+}
+
+/// Job states and pending tasks, shared by the replicas using the queue
+#[async_trait]
+pub trait JobQueue: Send + Sync {
+    /// Store the current state of a job
+    async fn save_job(&self, job: &Job) -> Result<()>;
+
+    async fn get_job(&self, id: &str) -> Result<Option<Job>>;
+
+    async fn enqueue(&self, task: JobTask) -> Result<()>;
+
+    /// Take the next task, if any, hiding it for the visibility timeout
+    async fn dequeue(&self) -> Result<Option<Delivery>>;
+
+    /// Remove a finished task from the queue
+    async fn ack(&self, job_id: &str) -> Result<()>;
+
+    /// Put a failed task back at the end of the queue
+    async fn retry(&self, job_id: &str) -> Result<()>;
+
+    /// Move a task that can't be completed to the dead-letter queue
+    async fn dead_letter(&self, job_id: &str) -> Result<()>;
+
+    /// Save unfinished tasks before the server exits
+    ///
+    /// Shared queues are durable already: tasks interrupted by the shutdown
+    /// are delivered again after the visibility timeout.
+    async fn persist(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Check that the queue is reachable, for readiness probes
+    async fn ping(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Forget jobs that finished before `before`, returning how many
+    ///
+    /// The default implementation keeps every job, for backends that
+    /// expire job states themselves.
+    async fn purge_jobs(&self, before: time::OffsetDateTime) -> Result<usize> {
+        let _ = before;
+        Ok(0)
+    }
+}
+
+/// Create the queue selected by `PAPERMAKE_QUEUE`: `memory` (default) or a
+/// `redis://` URL. The memory queue keeps its snapshot under `storage_path`.
+pub async fn queue_from_env(storage_path: &Path) -> Result<Arc<dyn JobQueue>> {
+    let config = QueueConfig::from_env();
+    match std::env::var("PAPERMAKE_QUEUE") {
+        Ok(url) if url.starts_with("redis://") || url.starts_with("rediss://") => {
+            Ok(Arc::new(RedisQueue::connect(&url, config).await?))
+        }
+        Ok(queue) if queue != "memory" => {
+            Err(PapermakeError::InvalidInput(format!("Unsupported queue: {}", queue)))
+        }
+        _ => Ok(Arc::new(MemoryQueue::with_snapshot(config, storage_path.join("queue.json")).await?)),
+    }
+}
+
+/// Queue of a single server; dead-lettered tasks are dropped, leaving only
+/// their failed jobs
+pub struct MemoryQueue {
+    config: QueueConfig,
+    jobs: JobStore,
+    tasks: Mutex<MemoryTasks>,
+    /// Where unfinished tasks are saved on shutdown
+    snapshot: Option<PathBuf>,
+}
+
+/// Unfinished tasks saved by [`MemoryQueue::persist`], in delivery order
+#[derive(Serialize, Deserialize)]
+struct Snapshot {
+    tasks: Vec<SnapshotTask>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SnapshotTask {
+    job: Job,
+    task: JobTask,
+    /// Deliveries that failed; interrupted ones don't count
+    attempts: u32,
+}
+
+#[derive(Default)]
+struct MemoryTasks {
+    pending: VecDeque<String>,
+    /// Task and delivery count by job id
+    tasks: HashMap<String, (JobTask, u32)>,
+    /// When delivered tasks become visible again
+    in_flight: HashMap<String, Instant>,
+}
+
+impl MemoryQueue {
+    pub fn new(config: QueueConfig) -> Self {
+        Self {
+            jobs: JobStore::new(config.max_jobs, config.job_ttl),
+            config,
+            tasks: Mutex::new(MemoryTasks::default()),
+            snapshot: None,
+        }
+    }
+
+    /// Queue saving its tasks to `path` on shutdown, resuming the tasks
+    /// saved there by the previous run
+    pub async fn with_snapshot(config: QueueConfig, path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let mut queue = Self::new(config);
+        match tokio::fs::read(&path).await {
+            Ok(json) => {
+                let snapshot: Snapshot = serde_json::from_slice(&json).map_err(json_error)?;
+                let mut tasks = queue.lock()?;
+                for SnapshotTask { job, task, attempts } in snapshot.tasks {
+                    queue.jobs.insert(job);
+                    tasks.pending.push_back(task.job_id.clone());
+                    tasks.tasks.insert(task.job_id.clone(), (task, attempts));
+                }
+                tracing::info!("Resumed {} queued jobs from {}", tasks.pending.len(), path.display());
+                drop(tasks);
+                // Resumed tasks live in memory again until the next shutdown
+                tokio::fs::remove_file(&path).await?;
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+        queue.snapshot = Some(path);
+        Ok(queue)
+    }
+
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, MemoryTasks>> {
+        self.tasks
+            .lock()
+            .map_err(|_| PapermakeError::Storage("Failed to acquire queue lock".to_string()))
+    }
+}
+
+#[async_trait]
+impl JobQueue for MemoryQueue {
+    async fn save_job(&self, job: &Job) -> Result<()> {
+        self.jobs.insert(job.clone());
+        Ok(())
+    }
+
+    async fn get_job(&self, id: &str) -> Result<Option<Job>> {
+        Ok(self.jobs.get(id))
+    }
+
+    async fn purge_jobs(&self, before: time::OffsetDateTime) -> Result<usize> {
+        Ok(self.jobs.purge_finished(before))
+    }
+
+    async fn enqueue(&self, task: JobTask) -> Result<()> {
+        let mut tasks = self.lock()?;
+        tasks.pending.push_back(task.job_id.clone());
+        tasks.tasks.insert(task.job_id.clone(), (task, 0));
+        Ok(())
+    }
+
+    async fn dequeue(&self) -> Result<Option<Delivery>> {
+        let mut tasks = self.lock()?;
+        let now = Instant::now();
+
+        // Tasks whose consumer timed out are delivered next
+        let expired: Vec<String> = tasks.in_flight.iter()
+            .filter(|(_, visible_at)| **visible_at <= now)
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in expired {
+            tasks.in_flight.remove(&id);
+            tasks.pending.push_front(id);
+        }
+
+        while let Some(id) = tasks.pending.pop_front() {
+            let Some((task, attempts)) = tasks.tasks.get_mut(&id) else {
+                continue;
+            };
+            *attempts += 1;
+            let delivery = Delivery {
+                task: task.clone(),
+                attempt: *attempts,
+                max_attempts: self.config.max_attempts,
+            };
+            tasks.in_flight.insert(id, now + self.config.visibility_timeout);
+            return Ok(Some(delivery));
+        }
+        Ok(None)
+    }
+
+    async fn ack(&self, job_id: &str) -> Result<()> {
+        let mut tasks = self.lock()?;
+        tasks.in_flight.remove(job_id);
+        tasks.tasks.remove(job_id);
+        Ok(())
+    }
+
+    async fn retry(&self, job_id: &str) -> Result<()> {
+        let mut tasks = self.lock()?;
+        if tasks.in_flight.remove(job_id).is_some() {
+            tasks.pending.push_back(job_id.to_string());
+        }
+        Ok(())
+    }
+
+    async fn dead_letter(&self, job_id: &str) -> Result<()> {
+        let mut tasks = self.lock()?;
+        tasks.in_flight.remove(job_id);
+        tasks.pending.retain(|id| id != job_id);
+        tasks.tasks.remove(job_id);
+        Ok(())
+    }
+
+    async fn persist(&self) -> Result<()> {
+        let Some(path) = &self.snapshot else {
+            return Ok(());
+        };
+        let snapshot = {
+            let tasks = self.lock()?;
+            // Interrupted tasks first, as they were taken off the queue first
+            let interrupted = tasks.in_flight.keys().map(|id| (id, true));
+            let pending = tasks.pending.iter().map(|id| (id, false));
+            let tasks = interrupted
+                .chain(pending)
+                .filter_map(|(id, interrupted)| {
+                    let (task, attempts) = tasks.tasks.get(id)?;
+                    let mut job = self.jobs.get(id)?;
+                    job.status = JobStatus::Queued;
+                    let attempts = if interrupted { attempts.saturating_sub(1) } else { *attempts };
+                    Some(SnapshotTask { job, task: task.clone(), attempts })
+                })
+                .collect::<Vec<_>>();
+            Snapshot { tasks }
+        };
+        if snapshot.tasks.is_empty() {
+            return Ok(());
+        }
+
+        let json = serde_json::to_vec(&snapshot).map_err(json_error)?;
+        if let Some(dir) = path.parent() {
+            tokio::fs::create_dir_all(dir).await?;
+        }
+        let tmp = path.with_extension("json.tmp");
+        tokio::fs::write(&tmp, json).await?;
+        tokio::fs::rename(&tmp, path).await?;
+        tracing::info!("Saved {} queued jobs to {}", snapshot.tasks.len(), path.display());
+        Ok(())
+    }
+}
+
+/// How long job states are kept in Redis
+const REDIS_JOB_TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Moves timed-out tasks back to the queue, then delivers the next task
+///
+/// KEYS: pending list, in-flight sorted set (by visibility deadline), task
+/// hash, attempt hash. ARGV: current time and visibility timeout in ms.
+const DEQUEUE_SCRIPT: &str = r"
+local expired = redis.call('ZRANGEBYSCORE', KEYS[2], '-inf', ARGV[1])
+for _, id in ipairs(expired) do
+    redis.call('ZREM', KEYS[2], id)
+    redis.call('RPUSH', KEYS[1], id)
+end
+local id = redis.call('RPOP', KEYS[1])
+if not id then
+    return false
+end
+redis.call('ZADD', KEYS[2], tonumber(ARGV[1]) + tonumber(ARGV[2]), id)
+local attempt = redis.call('HINCRBY', KEYS[4], id, 1)
+return {redis.call('HGET', KEYS[3], id) or '', attempt}
+";
+
+/// Queue shared by all replicas through Redis
+///
+/// Tasks are kept in a hash by job id; the ids move between the pending
+/// list, a sorted set of in-flight tasks and the dead-letter list, all
+/// under `papermake:queue:`. Job states are stored as JSON under
+/// `papermake:jobs:{id}` and expire after a week.
+pub struct RedisQueue {
+    connection: redis::aio::ConnectionManager,
+    config: QueueConfig,
+    dequeue: redis::Script,
+    /// PDF encryption of the tasks enqueued by this process, by job id
+    encryption: Mutex<HashMap<String, EncryptionRequest>>,
+}
+
+impl RedisQueue {
+    const PENDING: &'static str = "papermake:queue:pending";
+    const IN_FLIGHT: &'static str = "papermake:queue:in_flight";
+    const TASKS: &'static str = "papermake:queue:tasks";
+    const ATTEMPTS: &'static str = "papermake:queue:attempts";
+    const DEAD: &'static str = "papermake:queue:dead";
+
+    pub async fn connect(url: &str, config: QueueConfig) -> Result<Self> {
+        let client = redis::Client::open(url).map_err(redis_error)?;
+        let connection = redis::aio::ConnectionManager::new(client).await.map_err(redis_error)?;
+        Ok(Self {
+            connection,
+            config,
+            dequeue: redis::Script::new(DEQUEUE_SCRIPT),
+            encryption: Mutex::new(HashMap::new()),
+        })
+    }
+
+    fn job_key(id: &str) -> String {
+        format!("papermake:jobs:{}", id)
+    }
+
+    fn encryption(&self) -> Result<std::sync::MutexGuard<'_, HashMap<String, EncryptionRequest>>> {
+        self.encryption
+            .lock()
+            .map_err(|_| PapermakeError::Storage("Failed to acquire queue lock".to_string()))
+    }
+}
+
+#[async_trait]
+impl JobQueue for RedisQueue {
+    async fn save_job(&self, job: &Job) -> Result<()> {
+        let json = serde_json::to_string(job).map_err(json_error)?;
+        let mut connection = self.connection.clone();
+        connection
+            .set_ex::<_, _, ()>(Self::job_key(&job.id), json, REDIS_JOB_TTL.as_secs() as usize)
+            .await
+            .map_err(redis_error)
+    }
+
+    async fn get_job(&self, id: &str) -> Result<Option<Job>> {
+        let mut connection = self.connection.clone();
+        let json: Option<String> = connection.get(Self::job_key(id)).await.map_err(redis_error)?;
+        json.map(|json| serde_json::from_str(&json).map_err(json_error)).transpose()
+    }
+
+    async fn enqueue(&self, task: JobTask) -> Result<()> {
+        let json = serde_json::to_string(&task).map_err(json_error)?;
+        if let Some(encryption) = &task.encryption {
+            self.encryption()?.insert(task.job_id.clone(), encryption.clone());
+        }
+        let mut connection = self.connection.clone();
+        redis::pipe()
+            .atomic()
+            .hset(Self::TASKS, &task.job_id, json)
+            .lpush(Self::PENDING, &task.job_id)
+            .query_async::<_, ()>(&mut connection)
+            .await
+            .map_err(redis_error)
+    }
+
+    async fn dequeue(&self) -> Result<Option<Delivery>> {
+        let now = time::OffsetDateTime::now_utc().unix_timestamp_nanos() / 1_000_000;
+        let mut connection = self.connection.clone();
+        let delivered: Option<(String, u32)> = self.dequeue
+            .key(Self::PENDING)
+            .key(Self::IN_FLIGHT)
+            .key(Self::TASKS)
+            .key(Self::ATTEMPTS)
+            .arg(now as i64)
+            .arg(self.config.visibility_timeout.as_millis() as i64)
+            .invoke_async(&mut connection)
+            .await
+            .map_err(redis_error)?;
+
+        let Some((json, attempt)) = delivered else {
+            return Ok(None);
+        };
+        let mut task: JobTask = serde_json::from_str(&json).map_err(json_error)?;
+        task.encryption = self.encryption()?.get(&task.job_id).cloned();
+        Ok(Some(Delivery { task, attempt, max_attempts: self.config.max_attempts }))
+    }
+
+    async fn ack(&self, job_id: &str) -> Result<()> {
+        self.encryption()?.remove(job_id);
+        let mut connection = self.connection.clone();
+        redis::pipe()
+            .atomic()
+            .zrem(Self::IN_FLIGHT, job_id)
+            .hdel(Self::TASKS, job_id)
+            .hdel(Self::ATTEMPTS, job_id)
+            .query_async::<_, ()>(&mut connection)
+            .await
+            .map_err(redis_error)
+    }
+
+    async fn retry(&self, job_id: &str) -> Result<()> {
+        // A task that already timed out is back in the queue
+        let mut connection = self.connection.clone();
+        let removed: usize = connection.zrem(Self::IN_FLIGHT, job_id).await.map_err(redis_error)?;
+        if removed > 0 {
+            connection.lpush::<_, _, ()>(Self::PENDING, job_id).await.map_err(redis_error)?;
+        }
+        Ok(())
+    }
+
+    async fn dead_letter(&self, job_id: &str) -> Result<()> {
+        // The task stays in the task hash for inspection
+        self.encryption()?.remove(job_id);
+        let mut connection = self.connection.clone();
+        redis::pipe()
+            .atomic()
+            .zrem(Self::IN_FLIGHT, job_id)
+            .lrem(Self::PENDING, 0, job_id)
+            .lpush(Self::DEAD, job_id)
+            .query_async::<_, ()>(&mut connection)
+            .await
+            .map_err(redis_error)
+    }
+
+    async fn ping(&self) -> Result<()> {
+        let mut connection = self.connection.clone();
+        redis::cmd("PING")
+            .query_async::<_, String>(&mut connection)
+            .await
+            .map(|_| ())
+            .map_err(redis_error)
+    }
+}
+
+fn redis_error(err: redis::RedisError) -> PapermakeError {
+    PapermakeError::Storage(format!("Queue error: {}", err))
+}
+
+fn json_error(err: serde_json::Error) -> PapermakeError {
+    PapermakeError::Storage(format!("Invalid job in queue: {}", err))
+}
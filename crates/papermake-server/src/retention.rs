@@ -0,0 +1,61 @@
+//! Background deletion of expired artifacts
+//!
+//! Long-running deployments otherwise accumulate render records, job
+//! results and revisions of archived templates without bound. Every
+//! `retention.sweep_interval_secs`, each kind with a configured retention
+//! period is purged from storage (including every tenant namespace), and
+//! finished jobs are dropped from the queue. Job documents written to a
+//! custom `storage.output` location, such as an S3 bucket, aren't touched;
+//! use the bucket's lifecycle rules for those.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use papermake::storage::ArtifactKind;
+use time::OffsetDateTime;
+
+use crate::config::RetentionConfig;
+use crate::shutdown::Shutdown;
+use crate::AppState;
+
+/// Delete expired artifacts periodically until shutdown
+pub async fn sweep_expired(state: Arc<AppState>, config: RetentionConfig, shutdown: Shutdown) {
+    let periods = [
+        (ArtifactKind::RenderHistory, config.render_history_days),
+        (ArtifactKind::JobOutputs, config.job_results_days),
+        (ArtifactKind::TemplateVersions, config.archived_versions_days),
+    ];
+    if periods.iter().all(|(_, days)| days.is_none()) {
+        return;
+    }
+
+    let interval = Duration::from_secs(config.sweep_interval_secs);
+    while !shutdown.is_triggered() {
+        let now = OffsetDateTime::now_utc();
+        for (kind, days) in periods {
+            let Some(days) = days else {
+                continue;
+            };
+            let before = now - time::Duration::days(days as i64);
+            match state.storage.purge(before, &[kind]).await {
+                Ok(report) if report.total() > 0 => {
+                    tracing::info!("Purged {} expired {:?} artifacts", report.total(), kind);
+                }
+                Ok(_) => {}
+                Err(err) => tracing::warn!("failed to purge expired {:?} artifacts: {}", kind, err),
+            }
+            if kind == ArtifactKind::JobOutputs {
+                match state.queue.purge_jobs(before).await {
+                    Ok(0) => {}
+                    Ok(count) => tracing::info!("Purged {} expired jobs", count),
+                    Err(err) => tracing::warn!("failed to purge expired jobs: {}", err),
+                }
+            }
+        }
+
+        tokio::select! {
+            _ = tokio::time::sleep(interval) => {}
+            _ = shutdown.clone().triggered() => {}
+        }
+    }
+}
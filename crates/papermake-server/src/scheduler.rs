@@ -0,0 +1,217 @@
+//! Concurrency limits for renders per template and per API key
+//!
+//! Every render takes a slot from the scheduler before compiling. At most
+//! `max_renders_per_template` renders of one template and
+//! `max_renders_per_key` renders of one API key run at the same time; further
+//! renders wait in a queue of up to `max_queued_renders` entries. A freed slot
+//! goes to the oldest waiting render that fits within both limits, so a
+//! tenant at its limit doesn't hold up renders of others queued behind it.
+//! Requests arriving at a full queue are answered with
+//! `503 Service Unavailable` and a `Retry-After` header.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use prometheus::IntGauge;
+use tokio::sync::oneshot;
+
+use crate::config::LimitsConfig;
+
+/// Suggested wait before retrying when the queue is full
+const QUEUE_FULL_RETRY_AFTER: Duration = Duration::from_secs(5);
+
+/// Schedules renders within per-template and per-key concurrency limits
+#[derive(Debug)]
+pub struct RenderScheduler {
+    per_template: Option<usize>,
+    per_key: Option<usize>,
+    max_queued: usize,
+    state: Mutex<SchedulerState>,
+    /// Renders currently waiting for a slot
+    queue_depth: IntGauge,
+}
+
+#[derive(Debug, Default)]
+struct SchedulerState {
+    running_templates: HashMap<String, usize>,
+    running_keys: HashMap<String, usize>,
+    waiting: VecDeque<Waiter>,
+    next_id: u64,
+}
+
+#[derive(Debug)]
+struct Waiter {
+    id: u64,
+    slot: Slot,
+    admit: oneshot::Sender<()>,
+}
+
+/// What a render counts against
+#[derive(Debug, Clone)]
+struct Slot {
+    template: String,
+    key: Option<String>,
+}
+
+impl SchedulerState {
+    fn fits(&self, slot: &Slot, per_template: Option<usize>, per_key: Option<usize>) -> bool {
+        let below = |limit: Option<usize>, running: Option<&usize>| {
+            limit.is_none_or(|limit| running.copied().unwrap_or(0) < limit)
+        };
+        below(per_template, self.running_templates.get(&slot.template))
+            && slot.key.as_ref().is_none_or(|key| below(per_key, self.running_keys.get(key)))
+    }
+
+    fn take(&mut self, slot: &Slot) {
+        *self.running_templates.entry(slot.template.clone()).or_default() += 1;
+        if let Some(key) = &slot.key {
+            *self.running_keys.entry(key.clone()).or_default() += 1;
+        }
+    }
+
+    fn release(&mut self, slot: &Slot) {
+        decrement(&mut self.running_templates, &slot.template);
+        if let Some(key) = &slot.key {
+            decrement(&mut self.running_keys, key);
+        }
+    }
+}
+
+fn decrement(counts: &mut HashMap<String, usize>, name: &str) {
+    if let Some(count) = counts.get_mut(name) {
+        *count -= 1;
+        if *count == 0 {
+            counts.remove(name);
+        }
+    }
+}
+
+impl RenderScheduler {
+    pub fn new(per_template: Option<usize>, per_key: Option<usize>, max_queued: usize, queue_depth: IntGauge) -> Self {
+        Self {
+            per_template,
+            per_key,
+            max_queued,
+            state: Mutex::new(SchedulerState::default()),
+            queue_depth,
+        }
+    }
+
+    pub fn from_config(limits: &LimitsConfig, queue_depth: IntGauge) -> Self {
+        Self::new(
+            limits.max_renders_per_template,
+            limits.max_renders_per_key,
+            limits.max_queued_renders,
+            queue_depth,
+        )
+    }
+
+    /// Wait for a render slot, or return how long to wait before retrying
+    /// if the queue is full
+    pub async fn acquire(self: &Arc<Self>, template: &str, api_key_id: Option<&str>) -> Result<RenderPermit, Duration> {
+        self.schedule(template, api_key_id, true).await.ok_or(QUEUE_FULL_RETRY_AFTER)
+    }
+
+    /// Wait for a render slot regardless of the queue length; for job
+    /// consumers, whose number already bounds how many renders they queue
+    pub async fn wait(self: &Arc<Self>, template: &str, api_key_id: Option<&str>) -> RenderPermit {
+        self.schedule(template, api_key_id, false)
+            .await
+            .expect("unbounded scheduling always admits")
+    }
+
+    async fn schedule(self: &Arc<Self>, template: &str, api_key_id: Option<&str>, bounded: bool) -> Option<RenderPermit> {
+        let slot = Slot { template: template.to_string(), key: api_key_id.map(str::to_string) };
+        let (admit, admitted) = oneshot::channel();
+        let id = {
+            let mut state = self.state.lock().unwrap();
+            // Renders queued earlier go first, even if this one would fit
+            if state.waiting.is_empty() && state.fits(&slot, self.per_template, self.per_key) {
+                state.take(&slot);
+                return Some(RenderPermit { scheduler: self.clone(), slot });
+            }
+            if bounded && state.waiting.len() >= self.max_queued {
+                return None;
+            }
+            state.next_id += 1;
+            let id = state.next_id;
+            state.waiting.push_back(Waiter { id, slot: slot.clone(), admit });
+            self.queue_depth.set(state.waiting.len() as i64);
+            id
+        };
+
+        // Leaves the queue, or gives the slot back, if the request goes away
+        let guard = WaitGuard { scheduler: self, id, slot: Some(slot) };
+        let _ = admitted.await;
+        Some(guard.admitted())
+    }
+
+    /// Hand freed slots to the oldest waiting renders that fit
+    fn admit_waiting(&self, state: &mut SchedulerState) {
+        let mut index = 0;
+        while index < state.waiting.len() {
+            if state.fits(&state.waiting[index].slot, self.per_template, self.per_key) {
+                let waiter = state.waiting.remove(index).expect("index is in bounds");
+                state.take(&waiter.slot);
+                // A dropped receiver gives the slot back through its guard
+                let _ = waiter.admit.send(());
+            } else {
+                index += 1;
+            }
+        }
+        self.queue_depth.set(state.waiting.len() as i64);
+    }
+
+    fn release(&self, slot: &Slot) {
+        let mut state = self.state.lock().unwrap();
+        state.release(slot);
+        self.admit_waiting(&mut state);
+    }
+}
+
+/// A running render's slot, freed when dropped
+#[derive(Debug)]
+pub struct RenderPermit {
+    scheduler: Arc<RenderScheduler>,
+    slot: Slot,
+}
+
+impl Drop for RenderPermit {
+    fn drop(&mut self) {
+        self.scheduler.release(&self.slot);
+    }
+}
+
+struct WaitGuard<'a> {
+    scheduler: &'a Arc<RenderScheduler>,
+    id: u64,
+    slot: Option<Slot>,
+}
+
+impl WaitGuard<'_> {
+    fn admitted(mut self) -> RenderPermit {
+        let slot = self.slot.take().expect("slot is taken once");
+        RenderPermit { scheduler: self.scheduler.clone(), slot }
+    }
+}
+
+impl Drop for WaitGuard<'_> {
+    fn drop(&mut self) {
+        let Some(slot) = self.slot.take() else {
+            return;
+        };
+        let mut state = self.scheduler.state.lock().unwrap();
+        match state.waiting.iter().position(|waiter| waiter.id == self.id) {
+            Some(index) => {
+                state.waiting.remove(index);
+                self.scheduler.queue_depth.set(state.waiting.len() as i64);
+            }
+            // Admitted after the request went away
+            None => {
+                state.release(&slot);
+                self.scheduler.admit_waiting(&mut state);
+            }
+        }
+    }
+}
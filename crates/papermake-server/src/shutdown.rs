@@ -0,0 +1,66 @@
+//! Graceful shutdown on SIGTERM and SIGINT
+//!
+//! On a signal the server stops accepting connections and job consumers stop
+//! taking tasks off the queue. In-flight requests and jobs get until the
+//! drain timeout to finish; afterwards the queue is persisted so the next
+//! instance picks up the remaining work.
+
+use tokio::sync::watch;
+
+/// Handle to the shutdown state, cloned into everything that must stop
+#[derive(Debug, Clone)]
+pub struct Shutdown {
+    triggered: watch::Receiver<bool>,
+}
+
+impl Shutdown {
+    /// Start listening for termination signals
+    pub fn listen() -> Self {
+        let (sender, triggered) = watch::channel(false);
+        tokio::spawn(async move {
+            signal().await;
+            tracing::info!("Shutdown signal received, draining");
+            let _ = sender.send(true);
+        });
+        Self { triggered }
+    }
+
+    pub fn is_triggered(&self) -> bool {
+        *self.triggered.borrow()
+    }
+
+    /// Wait until shutdown has started
+    pub async fn triggered(mut self) {
+        // An error means the signal task is gone, which only happens on exit
+        let _ = self.triggered.wait_for(|triggered| *triggered).await;
+    }
+}
+
+async fn signal() {
+    let ctrl_c = async {
+        if let Err(err) = tokio::signal::ctrl_c().await {
+            tracing::warn!("failed to listen for Ctrl-C: {}", err);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                sigterm.recv().await;
+            }
+            Err(err) => {
+                tracing::warn!("failed to listen for SIGTERM: {}", err);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+}
@@ -0,0 +1,226 @@
+//! Batch renders streamed as newline-delimited JSON
+//!
+//! `POST /templates/{id}/render_stream` takes one JSON record per line
+//! (`application/x-ndjson`) and answers with one result per line as soon as
+//! each record is rendered, so clients can pipeline batches of any size
+//! without either side buffering them. Records are rendered one after
+//! another, in the order they arrive, with the template's default render
+//! options. A record that is invalid or fails to render is reported on its
+//! line without ending the stream; every record counts against the API
+//! key's render quota.
+
+use std::convert::Infallible;
+use std::sync::Arc;
+
+use axum::body::{Body, Bytes};
+use axum::extract::{Path, Query, State};
+use axum::http::header;
+use axum::response::{IntoResponse, Response};
+use base64::prelude::{Engine, BASE64_STANDARD};
+use futures_util::StreamExt;
+use papermake::render::{prepare_data, RenderError, RenderOptions};
+use papermake::{RenderRecord, Template, WorldPool};
+use serde::Serialize;
+use tokio::sync::mpsc;
+
+use crate::tenants::{TenantHistory, TenantPool, TenantStorage};
+use crate::{
+    acquire_render_slot, load_render_template, record_render, render_options, AppError, AppState, RenderVersionQuery,
+    TemplatePath,
+};
+
+/// Results rendered ahead of a slow client
+const STREAM_BUFFER: usize = 4;
+
+/// Result of one record, written as a line of the response
+#[derive(Serialize)]
+struct StreamedResult {
+    /// Index of the record among the non-empty lines of the request
+    index: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pdf_base64: Option<String>,
+    errors: Vec<RenderError>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    warnings: Vec<RenderError>,
+    /// Audit record of the render, if the record was rendered
+    #[serde(skip_serializing_if = "Option::is_none")]
+    render_id: Option<String>,
+}
+
+impl StreamedResult {
+    fn failed(index: usize, message: String) -> Self {
+        Self {
+            index,
+            pdf_base64: None,
+            errors: vec![RenderError { message, start: 0, end: 0 }],
+            warnings: Vec::new(),
+            render_id: None,
+        }
+    }
+}
+
+// Render newline-delimited records, streaming a result line per record
+pub async fn render_stream(
+    State(state): State<Arc<AppState>>,
+    TenantStorage(storage): TenantStorage,
+    TenantPool(pool): TenantPool,
+    Path(TemplatePath { id }): Path<TemplatePath>,
+    Query(query): Query<RenderVersionQuery>,
+    requester: TenantHistory,
+    body: Body,
+) -> Result<Response, AppError> {
+    let template = load_render_template(storage.as_ref(), id, query.version).await?;
+    let options = render_options(&state, storage.as_ref(), &template, None).await?;
+    // Records render one at a time, so the stream holds a single slot
+    let permit = acquire_render_slot(&state, &template, requester.api_key_id.as_deref()).await?;
+
+    let (sender, receiver) = mpsc::channel(STREAM_BUFFER);
+    tokio::spawn(async move {
+        let _permit = permit;
+        let renderer = StreamRenderer { state, pool, template, options, requester, sender };
+        renderer.run(body).await;
+    });
+
+    let lines = futures_util::stream::unfold(receiver, |mut receiver| async move {
+        let line: Bytes = receiver.recv().await?;
+        Some((Ok::<_, Infallible>(line), receiver))
+    });
+    Ok(([(header::CONTENT_TYPE, "application/x-ndjson")], Body::from_stream(lines)).into_response())
+}
+
+struct StreamRenderer {
+    state: Arc<AppState>,
+    pool: Arc<WorldPool>,
+    template: Template,
+    options: RenderOptions,
+    requester: TenantHistory,
+    sender: mpsc::Sender<Bytes>,
+}
+
+impl StreamRenderer {
+    /// Render records as their lines arrive, until the request ends or the
+    /// client goes away
+    async fn run(self, body: Body) {
+        let max_line = self.options.size_limits.max_data_bytes.unwrap_or(usize::MAX);
+        let mut chunks = body.into_data_stream();
+        let mut buffer = Vec::new();
+        // Set while dropping the rest of an oversized line
+        let mut oversized = false;
+        let mut index = 0;
+
+        loop {
+            let chunk = match chunks.next().await {
+                Some(Ok(chunk)) => chunk,
+                Some(Err(err)) => {
+                    let result = StreamedResult::failed(index, format!("Failed to read request: {}", err));
+                    let _ = self.send(&result).await;
+                    return;
+                }
+                None => break,
+            };
+            let mut rest = &chunk[..];
+            while let Some(newline) = rest.iter().position(|&b| b == b'\n') {
+                let (line, next) = rest.split_at(newline);
+                rest = &next[1..];
+                if oversized {
+                    oversized = false;
+                    continue;
+                }
+                buffer.extend_from_slice(line);
+                let line = std::mem::take(&mut buffer);
+                if !self.record(&line, &mut index).await {
+                    return;
+                }
+            }
+            if oversized {
+                continue;
+            }
+            buffer.extend_from_slice(rest);
+            if buffer.len() > max_line {
+                oversized = true;
+                buffer.clear();
+                let result = StreamedResult::failed(
+                    index,
+                    format!("Record exceeds the maximum size of {} bytes", max_line),
+                );
+                index += 1;
+                if !self.send(&result).await {
+                    return;
+                }
+            }
+        }
+        if !oversized {
+            self.record(&buffer, &mut index).await;
+        }
+    }
+
+    /// Render the record on a line, returning whether to go on
+    async fn record(&self, line: &[u8], index: &mut usize) -> bool {
+        if line.trim_ascii().is_empty() {
+            return true;
+        }
+        let result = self.render(*index, line).await;
+        *index += 1;
+        match result {
+            Ok(result) => self.send(&result).await,
+            // Out of quota: report it and end the stream
+            Err(result) => {
+                self.send(&result).await;
+                false
+            }
+        }
+    }
+
+    async fn render(&self, index: usize, line: &[u8]) -> Result<StreamedResult, StreamedResult> {
+        let input: serde_json::Value = match serde_json::from_slice(line) {
+            Ok(input) => input,
+            Err(err) => return Ok(StreamedResult::failed(index, format!("Invalid JSON: {}", err))),
+        };
+        let data = match prepare_data(&self.template, &input, &self.options) {
+            Ok(data) => data,
+            Err(err) => return Ok(StreamedResult::failed(index, format!("Invalid data: {}", err))),
+        };
+        let api_key_id = self.requester.api_key_id.as_deref();
+        if let Err(err) = self.state.quotas.consume(api_key_id, 1).await {
+            let message = match err {
+                AppError::TooManyRequests { message, .. } => message,
+                _ => "Failed to check the render quota".to_string(),
+            };
+            return Err(StreamedResult::failed(index, message));
+        }
+
+        let record = RenderRecord::new(uuid::Uuid::new_v4().to_string(), &self.template, &input)
+            .with_api_key_id(self.requester.api_key_id.clone());
+        let started = std::time::Instant::now();
+        let timer = self.state.metrics.start_render(self.template.id.as_ref());
+        let result = self
+            .pool
+            .render_async(&self.template, &data, Some(RenderOptions { data_prepared: true, ..self.options.clone() }))
+            .await;
+        let record = record.finish(started.elapsed(), &result);
+        record_render(&self.state, &self.requester, &record, &input).await;
+
+        Ok(match result {
+            Ok(result) => {
+                timer.finish(result.pdf.is_some(), result.errors.len());
+                StreamedResult {
+                    index,
+                    pdf_base64: result.pdf.as_ref().map(|pdf| BASE64_STANDARD.encode(pdf)),
+                    errors: result.errors,
+                    warnings: result.warnings,
+                    render_id: Some(record.id),
+                }
+            }
+            Err(err) => StreamedResult::failed(index, err.to_string()),
+        })
+    }
+
+    /// Write a result line, returning false once the client went away
+    async fn send(&self, result: &StreamedResult) -> bool {
+        let Ok(mut line) = serde_json::to_vec(result) else {
+            return false;
+        };
+        line.push(b'\n');
+        self.sender.send(Bytes::from(line)).await.is_ok()
+    }
+}
@@ -0,0 +1,169 @@
+//! Tenant namespaces and per-tenant API keys
+//!
+//! Routes nested under `/tenants/{tenant}` operate on the tenant's own
+//! storage namespace and require the tenant's API key, sent either as
+//! `X-Api-Key` or as a bearer token.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use axum::extract::{FromRequestParts, RawPathParams};
+use axum::http::{header, request::Parts, HeaderMap};
+use papermake::storage::{Namespace, Storage};
+use papermake::{RenderHistory, RenderStats, WorldPool};
+use sha2::{Digest, Sha256};
+
+use crate::{AppError, AppState};
+
+/// Header carrying a tenant API key
+pub const API_KEY_HEADER: &str = "X-Api-Key";
+
+/// API keys of all configured tenants
+#[derive(Debug, Default)]
+pub struct TenantKeys {
+    keys: HashMap<String, String>,
+}
+
+impl TenantKeys {
+    /// Parse `PAPERMAKE_TENANTS`, a comma-separated list of `tenant=api_key` pairs
+    pub fn from_env() -> Self {
+        let keys = std::env::var("PAPERMAKE_TENANTS")
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|entry| entry.trim().split_once('='))
+            .filter(|(tenant, key)| {
+                let valid = Namespace::new(*tenant).is_ok() && !key.is_empty();
+                if !valid {
+                    tracing::warn!("ignoring invalid tenant entry '{}'", tenant);
+                }
+                valid
+            })
+            .map(|(tenant, key)| (tenant.to_string(), key.to_string()))
+            .collect();
+        Self { keys }
+    }
+
+    /// Whether `key` is the API key of `tenant`
+    fn verify(&self, tenant: &str, key: Option<&str>) -> bool {
+        match (self.keys.get(tenant), key) {
+            (Some(expected), Some(key)) => constant_time_eq(expected.as_bytes(), key.as_bytes()),
+            _ => false,
+        }
+    }
+}
+
+/// Storage the request operates on: the tenant's namespace for routes under
+/// `/tenants/{tenant}`, the default storage otherwise
+pub struct TenantStorage(pub Arc<dyn Storage>);
+
+impl FromRequestParts<Arc<AppState>> for TenantStorage {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &Arc<AppState>) -> Result<Self, Self::Rejection> {
+        match tenant_namespace(parts, state).await? {
+            Some(namespace) => Ok(Self(state.storage.for_namespace(&namespace))),
+            None => Ok(Self(state.storage.clone())),
+        }
+    }
+}
+
+/// World pool of the request's namespace, so tenants sharing template ids
+/// don't reuse or evict each other's worlds
+pub struct TenantPool(pub Arc<WorldPool>);
+
+impl FromRequestParts<Arc<AppState>> for TenantPool {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &Arc<AppState>) -> Result<Self, Self::Rejection> {
+        match tenant_namespace(parts, state).await? {
+            Some(namespace) => Ok(Self(state.world_pool.for_namespace(&namespace))),
+            None => Ok(Self(state.world_pool.clone())),
+        }
+    }
+}
+
+/// The request's tenant namespace, `None` for the default namespace
+pub struct Tenant(pub Option<Namespace>);
+
+impl FromRequestParts<Arc<AppState>> for Tenant {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &Arc<AppState>) -> Result<Self, Self::Rejection> {
+        tenant_namespace(parts, state).await.map(Self)
+    }
+}
+
+/// Render history and statistics of the request's namespace, and the
+/// fingerprint of the API key the request was made with
+pub struct TenantHistory {
+    pub history: Arc<dyn RenderHistory>,
+    pub stats: Arc<dyn RenderStats>,
+    pub api_key_id: Option<String>,
+}
+
+impl TenantHistory {
+    /// History and statistics of `namespace`, `None` for the default namespace
+    pub fn new(state: &AppState, namespace: Option<&Namespace>, api_key_id: Option<String>) -> Self {
+        match namespace {
+            Some(namespace) => Self {
+                history: state.history.for_namespace(namespace),
+                stats: state.stats.for_namespace(namespace),
+                api_key_id,
+            },
+            None => Self { history: state.history.clone(), stats: state.stats.clone(), api_key_id },
+        }
+    }
+}
+
+impl FromRequestParts<Arc<AppState>> for TenantHistory {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &Arc<AppState>) -> Result<Self, Self::Rejection> {
+        let namespace = tenant_namespace(parts, state).await?;
+        Ok(Self::new(state, namespace.as_ref(), api_key_id(&parts.headers)))
+    }
+}
+
+/// The verified tenant namespace of routes under `/tenants/{tenant}`
+async fn tenant_namespace(parts: &mut Parts, state: &Arc<AppState>) -> Result<Option<Namespace>, AppError> {
+    let params = RawPathParams::from_request_parts(parts, state)
+        .await
+        .map_err(|e| AppError::BadRequest(e.to_string()))?;
+    let Some(tenant) = params.iter().find(|(name, _)| *name == "tenant").map(|(_, value)| value) else {
+        return Ok(None);
+    };
+
+    // Unknown tenants and wrong keys are indistinguishable to the caller
+    if !state.tenants.verify(tenant, api_key(&parts.headers)) {
+        return Err(AppError::Unauthorized);
+    }
+
+    Namespace::new(tenant)
+        .map(Some)
+        .map_err(|e| AppError::BadRequest(e.to_string()))
+}
+
+/// Fingerprint of the request's API key, if any
+pub fn api_key_id(headers: &HeaderMap) -> Option<String> {
+    api_key(headers).map(api_key_fingerprint)
+}
+
+/// Identifies an API key in logs and audit records without revealing it
+fn api_key_fingerprint(key: &str) -> String {
+    hex::encode(&Sha256::digest(key.as_bytes())[..8])
+}
+
+/// The API key from `X-Api-Key` or an `Authorization: Bearer` header
+fn api_key(headers: &HeaderMap) -> Option<&str> {
+    if let Some(key) = headers.get(API_KEY_HEADER).and_then(|v| v.to_str().ok()) {
+        return Some(key);
+    }
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
@@ -0,0 +1,80 @@
+//! Validation of multipart template asset uploads
+
+use std::path::Path;
+
+use crate::config::LimitsConfig;
+
+/// Content types accepted for template assets
+const ALLOWED_CONTENT_TYPES: &[&str] = &[
+    "image/png",
+    "image/jpeg",
+    "image/gif",
+    "image/svg+xml",
+    "image/webp",
+    "font/ttf",
+    "font/otf",
+    "font/woff",
+    "font/woff2",
+    "application/font-sfnt",
+    "application/x-font-ttf",
+    "application/x-font-otf",
+    "text/plain",
+    "text/csv",
+    "text/x-typst",
+    "application/json",
+    "application/xml",
+    "text/xml",
+    "application/yaml",
+    "application/x-yaml",
+    "text/yaml",
+    "application/pdf",
+];
+
+/// File extensions accepted when the client sends a generic content type
+const ALLOWED_EXTENSIONS: &[&str] = &[
+    "png", "jpg", "jpeg", "gif", "svg", "webp", "ttf", "otf", "woff", "woff2", "typ", "txt",
+    "csv", "json", "xml", "yaml", "yml", "bib", "pdf",
+];
+
+/// Size limits for multipart uploads; single files are limited by
+/// `SizeLimits::max_asset_bytes`
+#[derive(Debug, Clone, Copy)]
+pub struct UploadLimits {
+    /// Maximum size of a whole request
+    pub max_request_bytes: usize,
+}
+
+impl UploadLimits {
+    pub fn from_config(limits: &LimitsConfig) -> Self {
+        Self {
+            max_request_bytes: limits.max_upload_request_bytes,
+        }
+    }
+}
+
+/// Check that an uploaded file has an accepted content type
+///
+/// Browsers send `application/octet-stream` (or nothing) for types they
+/// don't know, such as `.typ` or font files; those are accepted by extension.
+pub fn validate_content_type(path: &str, content_type: Option<&str>) -> Result<(), String> {
+    let content_type = content_type
+        .map(|ct| ct.split(';').next().unwrap_or(ct).trim().to_ascii_lowercase())
+        .unwrap_or_default();
+
+    if ALLOWED_CONTENT_TYPES.contains(&content_type.as_str()) {
+        return Ok(());
+    }
+
+    let generic = content_type.is_empty() || content_type == "application/octet-stream";
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_ascii_lowercase());
+    match extension {
+        Some(ext) if generic && ALLOWED_EXTENSIONS.contains(&ext.as_str()) => Ok(()),
+        _ => Err(format!(
+            "Unsupported content type '{}' for file '{}'",
+            content_type, path
+        )),
+    }
+}
@@ -0,0 +1,199 @@
+//! Signed webhook notifications for finished render jobs
+//!
+//! Webhook URLs come from API clients, so they are limited to the hosts and
+//! schemes in `webhooks`: jobs with any other target are rejected when
+//! submitted, and redirects aren't followed.
+
+use std::time::Duration;
+
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use crate::config::WebhookConfig;
+use crate::jobs::{Job, JobStatus};
+use crate::AppError;
+
+/// Header carrying the hex-encoded HMAC-SHA256 signature of the payload
+pub const SIGNATURE_HEADER: &str = "X-Papermake-Signature";
+
+/// Header carrying the unix timestamp that was signed together with the body
+pub const TIMESTAMP_HEADER: &str = "X-Papermake-Timestamp";
+
+/// Where to deliver the notification for a single job
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookTarget {
+    pub url: String,
+    /// Per-request signing secret, overriding the server-wide secret
+    pub secret: Option<String>,
+}
+
+/// JSON body POSTed to the webhook URL
+#[derive(Debug, Serialize)]
+pub struct WebhookPayload {
+    pub job_id: String,
+    pub template_id: String,
+    pub status: JobStatus,
+    pub output_url: Option<String>,
+    /// Sink locations of all written documents
+    pub outputs: Vec<String>,
+    pub size_bytes: Option<usize>,
+    pub duration_ms: Option<u64>,
+    pub error_count: usize,
+}
+
+/// Delivers webhook notifications with retries and exponential backoff
+#[derive(Debug, Clone)]
+pub struct WebhookNotifier {
+    client: reqwest::Client,
+    allowed_hosts: Vec<String>,
+    allowed_schemes: Vec<String>,
+    secret: Option<String>,
+    public_url: String,
+    max_attempts: u32,
+    base_delay: Duration,
+}
+
+impl WebhookNotifier {
+    /// Create a notifier for the configured targets from
+    /// `PAPERMAKE_WEBHOOK_SECRET`, `PAPERMAKE_PUBLIC_URL` and
+    /// `PAPERMAKE_WEBHOOK_MAX_ATTEMPTS`
+    pub fn from_env(config: &WebhookConfig) -> Self {
+        Self {
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .redirect(reqwest::redirect::Policy::none())
+                .build()
+                .expect("failed to build webhook HTTP client"),
+            allowed_hosts: config.allowed_hosts.iter().map(|host| host.to_ascii_lowercase()).collect(),
+            allowed_schemes: config.allowed_schemes.clone(),
+            secret: std::env::var("PAPERMAKE_WEBHOOK_SECRET").ok(),
+            public_url: std::env::var("PAPERMAKE_PUBLIC_URL")
+                .unwrap_or_default()
+                .trim_end_matches('/')
+                .to_string(),
+            max_attempts: std::env::var("PAPERMAKE_WEBHOOK_MAX_ATTEMPTS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(5),
+            base_delay: Duration::from_secs(1),
+        }
+    }
+
+    /// Whether notifications may be sent to `url`
+    pub fn allows(&self, url: &str) -> bool {
+        reqwest::Url::parse(url).is_ok_and(|url| {
+            self.allowed_schemes.iter().any(|scheme| scheme == url.scheme())
+                && url.host_str().is_some_and(|host| self.allowed_hosts.iter().any(|allowed| *allowed == host))
+        })
+    }
+
+    /// Reject a webhook target outside the configured hosts and schemes
+    pub fn check(&self, target: &WebhookTarget) -> Result<(), AppError> {
+        if self.allows(&target.url) {
+            return Ok(());
+        }
+        Err(AppError::BadRequest(format!("Sending webhooks to '{}' is not allowed", target.url)))
+    }
+
+    /// Build the payload describing a finished job
+    pub fn payload(&self, job: &Job) -> WebhookPayload {
+        let tenant = job.namespace.as_ref().map(|ns| format!("/tenants/{}", ns)).unwrap_or_default();
+        let output_url = (job.status == JobStatus::Completed)
+            .then(|| format!("{}{}/jobs/{}/pdf", self.public_url, tenant, job.id));
+
+        WebhookPayload {
+            job_id: job.id.clone(),
+            template_id: job.template_id.clone(),
+            status: job.status,
+            output_url,
+            outputs: job.outputs.iter().filter_map(|item| item.url.clone()).collect(),
+            size_bytes: job.size_bytes(),
+            duration_ms: job.duration_ms,
+            error_count: job.errors.len(),
+        }
+    }
+
+    /// Notify the job's webhook target, if any, retrying transient failures
+    pub async fn notify(&self, job: &Job) {
+        let Some(target) = &job.webhook else {
+            return;
+        };
+        // Jobs submitted before the allowlist changed
+        if !self.allows(&target.url) {
+            tracing::warn!("not sending webhook for job {}: target is no longer allowed", job.id);
+            return;
+        }
+
+        let body = match serde_json::to_vec(&self.payload(job)) {
+            Ok(body) => body,
+            Err(e) => {
+                tracing::error!("failed to serialize webhook payload for job {}: {}", job.id, e);
+                return;
+            }
+        };
+
+        let secret = target.secret.as_deref().or(self.secret.as_deref());
+        if secret.is_none() {
+            tracing::warn!("sending unsigned webhook for job {}: no secret configured", job.id);
+        }
+
+        let mut delay = self.base_delay;
+        for attempt in 1..=self.max_attempts {
+            let timestamp = time::OffsetDateTime::now_utc().unix_timestamp().to_string();
+            let mut request = self
+                .client
+                .post(&target.url)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .header(TIMESTAMP_HEADER, &timestamp);
+            if let Some(secret) = secret {
+                request = request.header(SIGNATURE_HEADER, sign(secret, &timestamp, &body));
+            }
+
+            match request.body(body.clone()).send().await {
+                Ok(response) if response.status().is_success() => {
+                    tracing::debug!("delivered webhook for job {} on attempt {}", job.id, attempt);
+                    return;
+                }
+                Ok(response) if !is_retryable(response.status()) => {
+                    tracing::warn!(
+                        "webhook for job {} rejected with status {}, not retrying",
+                        job.id,
+                        response.status()
+                    );
+                    return;
+                }
+                Ok(response) => {
+                    tracing::warn!("webhook for job {} failed with status {}", job.id, response.status());
+                }
+                Err(e) => {
+                    tracing::warn!("webhook for job {} failed: {}", job.id, e);
+                }
+            }
+
+            if attempt < self.max_attempts {
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+            }
+        }
+
+        tracing::error!("giving up on webhook for job {} after {} attempts", job.id, self.max_attempts);
+    }
+}
+
+/// Server errors, timeouts and rate limiting are worth retrying; other client errors are not
+fn is_retryable(status: reqwest::StatusCode) -> bool {
+    status.is_server_error()
+        || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+        || status == reqwest::StatusCode::REQUEST_TIMEOUT
+}
+
+/// Compute the signature header value over `"{timestamp}.{body}"`
+pub fn sign(secret: &str, timestamp: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(timestamp.as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
@@ -1,13 +1,136 @@
+//! Render worker consuming render requests from SQS or Kafka
+//!
+//! Each message is a JSON [`render::RenderRequest`] naming a template, its
+//! data and an `s3://bucket/key` output:
+//!
+//! ```json
+//! { "id": "invoice-42", "template_id": "invoice", "data": { ... },
+//!   "output": "s3://documents/invoices/42.pdf" }
+//! ```
+//!
+//! Templates are read from the storage directory shared with the server
+//! (`PAPERMAKE_STORAGE_PATH`) and rendered with a warm world pool;
+//! PDFs are written to S3. Requests that can't be rendered get an error
+//! report next to their output. Configuration:
+//!
+//! - `PAPERMAKE_SQS_QUEUE_URL`, or `PAPERMAKE_KAFKA_BROKERS`,
+//!   `PAPERMAKE_KAFKA_TOPIC` and `PAPERMAKE_KAFKA_GROUP` (`kafka` feature)
+//! - `PAPERMAKE_STORAGE_PATH`: template storage, `./data` by default
+//! - `PAPERMAKE_WORKER_CONCURRENCY`: requests rendered at once, 4 by default
+
+mod render;
+mod source;
+
+#[cfg(not(any(feature = "sqs", feature = "kafka")))]
+compile_error!("papermake-worker needs the `sqs` or `kafka` feature");
+
+use std::sync::Arc;
 use std::time::Duration;
 
+use papermake::storage::FileStorage;
+use tokio::task::JoinSet;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+
+use crate::render::{Outcome, Renderer};
+use crate::source::Source;
+
+/// Pause after a failed receive before trying again
+const RECEIVE_BACKOFF: Duration = Duration::from_secs(5);
+
 #[tokio::main]
 async fn main() {
-    println!("papermake-worker starting up");
-    println!("Using papermake version: {}", papermake::version());
-    
-    // Just keep the worker running to verify it works
+    tracing_subscriber::registry()
+        .with(EnvFilter::new(
+            std::env::var("RUST_LOG").unwrap_or_else(|_| "papermake_worker=info".into()),
+        ))
+        .with(tracing_subscriber::fmt::layer())
+        .init();
+    tracing::info!("papermake-worker {} starting up", papermake::version());
+
+    let source = match Source::from_env().await {
+        Ok(source) => Arc::new(source),
+        Err(err) => {
+            tracing::error!("{}", err);
+            std::process::exit(1);
+        }
+    };
+    let storage_path = std::env::var("PAPERMAKE_STORAGE_PATH").unwrap_or_else(|_| "./data".to_string());
+    let concurrency = std::env::var("PAPERMAKE_WORKER_CONCURRENCY")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .filter(|&n: &usize| n > 0)
+        .unwrap_or(4);
+    let aws = aws_config::load_defaults(aws_config::BehaviorVersion::latest()).await;
+    let renderer = Arc::new(Renderer::new(
+        Arc::new(FileStorage::new(storage_path)),
+        aws_sdk_s3::Client::new(&aws),
+    ));
+
+    // Finish the current batch on Ctrl-C or SIGTERM, then exit
+    let shutdown = shutdown_signal();
+    tokio::pin!(shutdown);
     loop {
-        println!("Worker running... (waiting for implementation)");
-        tokio::time::sleep(Duration::from_secs(5)).await;
+        let deliveries = tokio::select! {
+            received = source.receive(concurrency) => received,
+            _ = &mut shutdown => break,
+        };
+        let deliveries = match deliveries {
+            Ok(deliveries) => deliveries,
+            Err(err) => {
+                tracing::error!("{}", err);
+                tokio::time::sleep(RECEIVE_BACKOFF).await;
+                continue;
+            }
+        };
+
+        let mut batch = JoinSet::new();
+        for (index, delivery) in deliveries.into_iter().enumerate() {
+            let (source, renderer) = (source.clone(), renderer.clone());
+            batch.spawn(async move {
+                let ack = match renderer.handle(&delivery.body).await {
+                    Outcome::Rendered(url) => {
+                        tracing::info!("rendered {}", url);
+                        true
+                    }
+                    Outcome::Rejected(errors) => {
+                        let messages: Vec<_> = errors.iter().map(|e| e.message.as_str()).collect();
+                        tracing::warn!("rejected render request: {}", messages.join("; "));
+                        true
+                    }
+                    Outcome::Failed(err) => {
+                        tracing::error!("render request failed: {}", err);
+                        !source.redelivers()
+                    }
+                };
+                (index, delivery, ack)
+            });
+        }
+        // Acknowledge in delivery order once the whole batch is handled, so
+        // Kafka offsets never skip a message still rendering
+        let mut handled = batch.join_all().await;
+        handled.sort_by_key(|(index, _, _)| *index);
+        for (_, delivery, ack) in handled {
+            if ack {
+                if let Err(err) = source.ack(&delivery).await {
+                    tracing::error!("{}", err);
+                }
+            }
+        }
+    }
+    tracing::info!("papermake-worker stopped");
+}
+
+async fn shutdown_signal() {
+    let ctrl_c = tokio::signal::ctrl_c();
+    #[cfg(unix)]
+    {
+        let mut term = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to listen for SIGTERM");
+        tokio::select! {
+            _ = ctrl_c => {}
+            _ = term.recv() => {}
+        }
     }
-}
\ No newline at end of file
+    #[cfg(not(unix))]
+    let _ = ctrl_c.await;
+}
@@ -0,0 +1,139 @@
+//! Rendering render requests and writing the results to S3
+
+use std::sync::Arc;
+
+use aws_sdk_s3::primitives::ByteStream;
+use papermake::lifecycle::template_for_render;
+use papermake::render::{RenderError, RenderOptions};
+use papermake::sink::{RenderSink, S3Sink};
+use papermake::storage::{FileStorage, Namespace, Storage};
+use papermake::{resolve_shared, ErrorCode, PapermakeError, Result, TemplateId, TemplateVersion, WorldPool};
+use serde::{Deserialize, Serialize};
+
+/// A render request as sent to the queue or topic
+#[derive(Debug, Clone, Deserialize)]
+pub struct RenderRequest {
+    /// Caller's id of the request, included in logs and error reports
+    pub id: Option<String>,
+    pub template_id: String,
+    /// Tenant namespace holding the template
+    pub namespace: Option<String>,
+    /// `published` (default) or `draft`
+    pub version: Option<String>,
+    pub data: serde_json::Value,
+    /// Where the PDF is written, e.g. `s3://documents/invoices/42.pdf`
+    pub output: String,
+}
+
+/// Written next to the output, at `{output}.errors.json`, when a request
+/// can't be rendered
+#[derive(Debug, Serialize)]
+struct ErrorReport<'a> {
+    id: Option<&'a str>,
+    template_id: &'a str,
+    errors: &'a [RenderError],
+}
+
+/// How handling a request ended
+#[derive(Debug)]
+pub enum Outcome {
+    /// The PDF was written to this location
+    Rendered(String),
+    /// The request can't succeed, e.g. its template doesn't exist or fails
+    /// to compile; an error report was written if the output was valid
+    Rejected(Vec<RenderError>),
+    /// A temporary failure, e.g. S3 being unavailable; the request may be
+    /// delivered again
+    Failed(PapermakeError),
+}
+
+/// Renders requests with templates from shared storage and a warm world pool
+pub struct Renderer {
+    storage: Arc<FileStorage>,
+    pool: Arc<WorldPool>,
+    s3: aws_sdk_s3::Client,
+}
+
+impl Renderer {
+    pub fn new(storage: Arc<FileStorage>, s3: aws_sdk_s3::Client) -> Self {
+        Self { storage, pool: Arc::new(WorldPool::new()), s3 }
+    }
+
+    /// Handle a message body holding a [`RenderRequest`]
+    pub async fn handle(&self, body: &[u8]) -> Outcome {
+        let request: RenderRequest = match serde_json::from_slice(body) {
+            Ok(request) => request,
+            Err(err) => return Outcome::Rejected(vec![error(format!("Invalid render request: {}", err))]),
+        };
+        let Some((bucket, key)) = parse_output(&request.output) else {
+            return Outcome::Rejected(vec![error(format!(
+                "Invalid output '{}', expected s3://bucket/key",
+                request.output
+            ))]);
+        };
+
+        let errors = match self.render(&request).await {
+            Ok(Ok(pdf)) => {
+                let sink = S3Sink::new(self.s3.clone(), bucket, "");
+                return match sink.write(key, pdf).await {
+                    Ok(url) => Outcome::Rendered(url),
+                    Err(err) => Outcome::Failed(err),
+                };
+            }
+            Ok(Err(errors)) => errors,
+            Err(err) if is_temporary(&err) => return Outcome::Failed(err),
+            Err(err) => vec![error(err.to_string())],
+        };
+
+        let report = ErrorReport { id: request.id.as_deref(), template_id: &request.template_id, errors: &errors };
+        let written = self
+            .s3
+            .put_object()
+            .bucket(bucket)
+            .key(format!("{}.errors.json", key))
+            .content_type("application/json")
+            .body(ByteStream::from(serde_json::to_vec_pretty(&report).unwrap_or_default()))
+            .send()
+            .await;
+        if let Err(err) = written {
+            tracing::warn!("failed to write error report for {}: {}", request.output, err);
+        }
+        Outcome::Rejected(errors)
+    }
+
+    /// The PDF of a request, or its compile errors
+    async fn render(&self, request: &RenderRequest) -> Result<std::result::Result<Vec<u8>, Vec<RenderError>>> {
+        let storage: Arc<dyn Storage> = match &request.namespace {
+            Some(namespace) => self.storage.for_namespace(&Namespace::new(namespace.as_str())?),
+            None => self.storage.clone(),
+        };
+        let version = match &request.version {
+            Some(version) => version.parse()?,
+            None => TemplateVersion::default(),
+        };
+        let template_id = TemplateId(request.template_id.clone());
+        let template = template_for_render(storage.as_ref(), &template_id, version).await?;
+
+        let options = RenderOptions {
+            shared_sources: resolve_shared(storage.as_ref(), &template).await?,
+            ..RenderOptions::default()
+        };
+        let result = self.pool.render_async(&template, &request.data, Some(options)).await?;
+        Ok(result.pdf.ok_or(result.errors))
+    }
+}
+
+/// Split `s3://bucket/key` into its bucket and key
+fn parse_output(output: &str) -> Option<(&str, &str)> {
+    let (bucket, key) = output.strip_prefix("s3://")?.split_once('/')?;
+    (!bucket.is_empty() && !key.is_empty()).then_some((bucket, key))
+}
+
+/// Whether retrying could succeed
+fn is_temporary(err: &PapermakeError) -> bool {
+    matches!(err.code(), ErrorCode::StorageIo | ErrorCode::Timeout)
+}
+
+fn error(message: String) -> RenderError {
+    RenderError { message, start: 0, end: 0 }
+}
@@ -0,0 +1,168 @@
+//! Queues and topics render requests are consumed from
+//!
+//! Messages are received in batches and acknowledged once handled, so a
+//! worker that crashes mid-batch gets its messages delivered again. SQS
+//! redelivers messages that are left unacknowledged after a temporary
+//! failure (and moves them to a dead-letter queue per the queue's redrive
+//! policy); Kafka has no per-message redelivery, so its offsets always move
+//! on.
+
+use std::time::Duration;
+
+use papermake::{PapermakeError, Result};
+
+/// A message taken from a source
+#[derive(Debug)]
+pub struct Delivery {
+    pub body: Vec<u8>,
+    ack: Ack,
+}
+
+#[derive(Debug)]
+enum Ack {
+    #[cfg(feature = "sqs")]
+    Sqs { receipt_handle: String },
+    #[cfg(feature = "kafka")]
+    Kafka { topic: String, partition: i32, offset: i64 },
+}
+
+/// Where render requests come from
+pub enum Source {
+    #[cfg(feature = "sqs")]
+    Sqs { client: aws_sdk_sqs::Client, queue_url: String },
+    #[cfg(feature = "kafka")]
+    Kafka(rdkafka::consumer::StreamConsumer),
+}
+
+/// How long a receive waits for the first message of a batch
+const RECEIVE_WAIT: Duration = Duration::from_secs(20);
+
+impl Source {
+    /// The source configured by `PAPERMAKE_SQS_QUEUE_URL` or
+    /// `PAPERMAKE_KAFKA_BROKERS` and `PAPERMAKE_KAFKA_TOPIC`
+    pub async fn from_env() -> Result<Self> {
+        #[cfg(feature = "sqs")]
+        if let Ok(queue_url) = std::env::var("PAPERMAKE_SQS_QUEUE_URL") {
+            let config = aws_config::load_defaults(aws_config::BehaviorVersion::latest()).await;
+            return Ok(Source::Sqs { client: aws_sdk_sqs::Client::new(&config), queue_url });
+        }
+        #[cfg(feature = "kafka")]
+        if let Ok(brokers) = std::env::var("PAPERMAKE_KAFKA_BROKERS") {
+            use rdkafka::consumer::Consumer;
+
+            let topic = std::env::var("PAPERMAKE_KAFKA_TOPIC")
+                .map_err(|_| PapermakeError::InvalidInput("PAPERMAKE_KAFKA_TOPIC is not set".to_string()))?;
+            let group = std::env::var("PAPERMAKE_KAFKA_GROUP").unwrap_or_else(|_| "papermake-worker".to_string());
+            let consumer: rdkafka::consumer::StreamConsumer = rdkafka::ClientConfig::new()
+                .set("bootstrap.servers", &brokers)
+                .set("group.id", &group)
+                .set("enable.auto.commit", "true")
+                // Offsets are stored once a message is handled
+                .set("enable.auto.offset.store", "false")
+                .set("auto.offset.reset", "earliest")
+                .create()
+                .map_err(source_error)?;
+            consumer.subscribe(&[&topic]).map_err(source_error)?;
+            return Ok(Source::Kafka(consumer));
+        }
+        Err(PapermakeError::InvalidInput(
+            "No source configured: set PAPERMAKE_SQS_QUEUE_URL or PAPERMAKE_KAFKA_BROKERS".to_string(),
+        ))
+    }
+
+    /// Whether messages left unacknowledged are delivered again
+    pub fn redelivers(&self) -> bool {
+        match self {
+            #[cfg(feature = "sqs")]
+            Source::Sqs { .. } => true,
+            #[cfg(feature = "kafka")]
+            Source::Kafka(_) => false,
+        }
+    }
+
+    /// Wait for up to `max` messages; empty if none arrived in time
+    pub async fn receive(&self, max: usize) -> Result<Vec<Delivery>> {
+        match self {
+            #[cfg(feature = "sqs")]
+            Source::Sqs { client, queue_url } => {
+                let output = client
+                    .receive_message()
+                    .queue_url(queue_url)
+                    .max_number_of_messages(max.clamp(1, 10) as i32)
+                    .wait_time_seconds(RECEIVE_WAIT.as_secs() as i32)
+                    .send()
+                    .await
+                    .map_err(source_error)?;
+                Ok(output
+                    .messages
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter_map(|message| {
+                        Some(Delivery {
+                            body: message.body?.into_bytes(),
+                            ack: Ack::Sqs { receipt_handle: message.receipt_handle? },
+                        })
+                    })
+                    .collect())
+            }
+            #[cfg(feature = "kafka")]
+            Source::Kafka(consumer) => {
+                use rdkafka::Message;
+
+                let mut deliveries = Vec::new();
+                // Wait for the first message, then take what is already there
+                let mut wait = RECEIVE_WAIT;
+                while deliveries.len() < max {
+                    let message = match tokio::time::timeout(wait, consumer.recv()).await {
+                        Ok(message) => message.map_err(source_error)?,
+                        Err(_) => break,
+                    };
+                    deliveries.push(Delivery {
+                        body: message.payload().unwrap_or_default().to_vec(),
+                        ack: Ack::Kafka {
+                            topic: message.topic().to_string(),
+                            partition: message.partition(),
+                            offset: message.offset(),
+                        },
+                    });
+                    wait = Duration::from_millis(10);
+                }
+                Ok(deliveries)
+            }
+        }
+    }
+
+    /// Mark a message as handled
+    pub async fn ack(&self, delivery: &Delivery) -> Result<()> {
+        match (self, &delivery.ack) {
+            #[cfg(feature = "sqs")]
+            (Source::Sqs { client, queue_url }, Ack::Sqs { receipt_handle }) => {
+                client
+                    .delete_message()
+                    .queue_url(queue_url)
+                    .receipt_handle(receipt_handle)
+                    .send()
+                    .await
+                    .map_err(source_error)?;
+                Ok(())
+            }
+            #[cfg(feature = "kafka")]
+            (Source::Kafka(consumer), Ack::Kafka { topic, partition, offset }) => {
+                use rdkafka::consumer::Consumer;
+
+                // The committed offset is the next message to read
+                let mut offsets = rdkafka::TopicPartitionList::new();
+                offsets
+                    .add_partition_offset(topic, *partition, rdkafka::Offset::Offset(offset + 1))
+                    .map_err(source_error)?;
+                consumer.store_offsets(&offsets).map_err(source_error)
+            }
+            #[allow(unreachable_patterns)]
+            _ => Err(PapermakeError::InvalidInput("Message is from another source".to_string())),
+        }
+    }
+}
+
+fn source_error(err: impl std::fmt::Display) -> PapermakeError {
+    PapermakeError::Storage(format!("Queue error: {}", err))
+}
@@ -0,0 +1,84 @@
+//! Render benchmarks comparing cold renders with renders reusing a world
+//!
+//! Run with `cargo bench -p papermake`.
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use papermake::typst::TypstWorld;
+use papermake::{render_pdf, schema, CachedTemplate, Template, TemplateCache};
+use serde_json::json;
+
+const CONTENT: &str = r#"#let data = json.decode(sys.inputs.data)
+#set page(paper: "a4")
+= Invoice #data.number
+
+#table(
+  columns: 3,
+  [Item], [Quantity], [Price],
+  ..data.items.map(item => (item.name, str(item.quantity), str(item.price))).flatten()
+)
+
+#for i in range(40) [
+  #lorem(30)
+]
+"#;
+
+fn template() -> Template {
+    Template::builder("bench")
+        .name("Benchmark")
+        .content(CONTENT)
+        .schema(schema! {
+            number: Number
+        })
+        .build()
+        .unwrap()
+}
+
+fn data(number: usize) -> serde_json::Value {
+    json!({
+        "number": number,
+        "items": (0..10)
+            .map(|i| json!({ "name": format!("Item {}", i), "quantity": i + 1, "price": 9.5 }))
+            .collect::<Vec<_>>(),
+    })
+}
+
+fn bench_render(c: &mut Criterion) {
+    let template = template();
+    let mut group = c.benchmark_group("render");
+
+    group.bench_function("cold_world", |b| {
+        let mut n = 0;
+        b.iter(|| {
+            n += 1;
+            render_pdf(&template, &data(n), None).unwrap()
+        })
+    });
+
+    group.bench_function("reused_world", |b| {
+        let cached: CachedTemplate = template.clone().with_cache();
+        let mut n = 0;
+        b.iter(|| {
+            n += 1;
+            cached.render(&data(n)).unwrap()
+        })
+    });
+
+    group.bench_function("reused_world_same_data", |b| {
+        let cached: CachedTemplate = template.clone().with_cache();
+        let data = data(1);
+        b.iter(|| cached.render(&data).unwrap())
+    });
+
+    group.bench_function("fresh_world_same_template", |b| {
+        b.iter_batched(
+            || TypstWorld::new(template.content.clone(), "{}".to_string()),
+            |mut world| template.render_with_cache(&data(1), Some(&mut world)).unwrap(),
+            BatchSize::SmallInput,
+        )
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_render);
+criterion_main!(benches);
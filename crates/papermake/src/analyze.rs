@@ -0,0 +1,355 @@
+//! Editor support: diagnostics, completions and hover docs for a template
+//!
+//! [`analyze_template`] answers the questions a code editor asks about a
+//! template's source in one call, so a Monaco or CodeMirror based editor can
+//! offer the same checks as rendering:
+//!
+//! - diagnostics from compiling the template and from [`Template::lint`],
+//! - completions of the schema fields accessible where the cursor is, after
+//!   `data.customer.` or inside `data.at("`,
+//! - hover docs for the field access under the cursor.
+//!
+//! Ranges are byte offsets into the source, like [`RenderError`], together
+//! with zero-based lines and UTF-16 columns as used by the Language Server
+//! Protocol.
+
+use std::collections::BTreeSet;
+
+use serde::Serialize;
+use typst::syntax::{ast, LinkedNode, Side, SyntaxKind};
+
+use crate::compatibility::type_name;
+use crate::lint::{access_path, at_key, find_data_vars, LintKind};
+use crate::render::{compile_template, RenderError, RenderOptions};
+use crate::schema::{FieldType, Schema, SchemaField};
+use crate::template::Template;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+    /// Informational, e.g. a schema field the template doesn't use
+    Hint,
+}
+
+/// What produced a diagnostic
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiagnosticSource {
+    /// Typst compilation
+    Compile,
+    /// The schema linter
+    Lint,
+    /// Validating the data used to compile
+    Data,
+}
+
+/// A zero-based line and UTF-16 column
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Position {
+    pub line: usize,
+    pub character: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub source: DiagnosticSource,
+    pub message: String,
+    /// Byte range in the template source; empty at the start for
+    /// diagnostics without a location
+    pub start: usize,
+    pub end: usize,
+    pub start_position: Position,
+    pub end_position: Position,
+}
+
+/// A schema field that can be inserted at the cursor
+#[derive(Debug, Clone, Serialize)]
+pub struct Completion {
+    /// Field key, inserted in place of the range
+    pub label: String,
+    /// Field type, e.g. `string` or `array of object`
+    pub detail: String,
+    /// The field's label and description
+    pub documentation: Option<String>,
+    pub required: bool,
+    /// Byte range replaced by the completion: the part of the key typed so far
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Docs for the field access under the cursor
+#[derive(Debug, Clone, Serialize)]
+pub struct Hover {
+    /// Dotted field path, e.g. `customer.name`
+    pub path: String,
+    /// Markdown describing the field
+    pub contents: String,
+    /// Byte range of the access
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Result of [`analyze_template`]
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Analysis {
+    pub diagnostics: Vec<Diagnostic>,
+    /// Completions at the cursor; empty without a cursor
+    pub completions: Vec<Completion>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hover: Option<Hover>,
+}
+
+/// Analyze a template's source for an editor, with completions and hover
+/// docs at the byte offset `cursor`
+///
+/// The template is compiled with `data`, falling back to its first example
+/// and then to sample data generated from the schema. Invalid data is
+/// reported as a diagnostic rather than an error.
+pub fn analyze_template(
+    template: &Template,
+    cursor: Option<usize>,
+    data: Option<&serde_json::Value>,
+    options: &RenderOptions,
+) -> Analysis {
+    let source = &template.content;
+    let root = typst::syntax::parse(source);
+    let root = LinkedNode::new(&root);
+    let mut data_vars = BTreeSet::new();
+    find_data_vars(&root, &mut data_vars);
+    if data_vars.is_empty() {
+        data_vars.insert("data".to_string());
+    }
+
+    let mut analysis = Analysis::default();
+    let diagnostic = |severity, source_kind, message: String, start: usize, end: usize| Diagnostic {
+        severity,
+        source: source_kind,
+        message,
+        start,
+        end,
+        start_position: position(source, start),
+        end_position: position(source, end),
+    };
+
+    // Ranges refer to the main source, not a locale's variant
+    let mut options = options.clone();
+    options.locale = None;
+    let sample;
+    let data = match data.or_else(|| template.examples.values().next()) {
+        Some(data) => data,
+        None => {
+            sample = template.schema.generate_sample_data(0);
+            &sample
+        }
+    };
+    match compile_template(template, data, None, &options) {
+        Ok(compiled) => {
+            let found = [(Severity::Error, compiled.errors), (Severity::Warning, compiled.warnings)];
+            for (severity, errors) in found {
+                for RenderError { message, start, end } in errors {
+                    let (start, end) = (start.min(source.len()), end.min(source.len()));
+                    analysis
+                        .diagnostics
+                        .push(diagnostic(severity, DiagnosticSource::Compile, message, start, end));
+                }
+            }
+        }
+        Err(err) => analysis
+            .diagnostics
+            .push(diagnostic(Severity::Error, DiagnosticSource::Data, err.to_string(), 0, 0)),
+    }
+
+    for issue in template.lint().issues {
+        let severity = match issue.kind {
+            LintKind::UndeclaredField => Severity::Warning,
+            LintKind::UnusedField | LintKind::OpaqueDataUse => Severity::Hint,
+        };
+        let start = issue.start.unwrap_or(0);
+        let end = issue.end.unwrap_or(start);
+        analysis
+            .diagnostics
+            .push(diagnostic(severity, DiagnosticSource::Lint, issue.message, start, end));
+    }
+
+    if let Some(cursor) = cursor.filter(|cursor| source.is_char_boundary(*cursor)) {
+        analysis.completions = complete(&template.schema, &data_vars, source, cursor);
+        analysis.hover = hover(&template.schema, &data_vars, &root, cursor);
+    }
+    analysis
+}
+
+/// Position of a byte offset
+fn position(source: &str, offset: usize) -> Position {
+    let before = source.get(..offset).unwrap_or(source);
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    Position {
+        line: before.matches('\n').count(),
+        character: before[line_start..].encode_utf16().count(),
+    }
+}
+
+/// Fields completing the access chain that ends at the cursor, e.g.
+/// `data.customer.na` or `data.at("customer").at("na`
+fn complete(schema: &Schema, data_vars: &BTreeSet<String>, source: &str, cursor: usize) -> Vec<Completion> {
+    let before = &source[..cursor];
+    let ident_start = before.len() - before.chars().rev().take_while(|c| is_ident_char(*c)).map(char::len_utf8).sum::<usize>();
+    let (prefix_start, chain, quoted) = match before[..ident_start].strip_suffix('.') {
+        Some(chain) => (ident_start, chain, false),
+        None => {
+            let key_start = before.rfind(['"', '\n']).map_or(0, |i| i + 1);
+            match before[..key_start].strip_suffix(".at(\"") {
+                Some(chain) => (key_start, chain, true),
+                None => return Vec::new(),
+            }
+        }
+    };
+    let Some((root, path)) = chain_path(chain) else {
+        return Vec::new();
+    };
+    if !data_vars.contains(&root) {
+        return Vec::new();
+    }
+    let Some(schema) = object_at(schema, &path) else {
+        return Vec::new();
+    };
+
+    let prefix = &before[prefix_start..];
+    schema
+        .fields
+        .iter()
+        .filter(|field| field.key.starts_with(prefix))
+        // Keys that aren't identifiers can only be completed inside `.at("`
+        .filter(|field| quoted || field.key.chars().all(is_ident_char))
+        .map(|field| Completion {
+            label: field.key.clone(),
+            detail: describe(&field.field_type),
+            documentation: documentation(field),
+            required: field.required,
+            start: prefix_start,
+            end: cursor,
+        })
+        .collect()
+}
+
+/// Parse an access chain backwards into its root variable and field path;
+/// the source may be incomplete, so this doesn't rely on the syntax tree
+fn chain_path(chain: &str) -> Option<(String, Vec<String>)> {
+    let mut path = Vec::new();
+    let mut rest = chain;
+    loop {
+        if let Some(call) = rest.strip_suffix(')') {
+            // `.at("key")`, possibly with a default
+            let open = matching_paren(call)?;
+            let key = call[open + 1..].trim_start().strip_prefix('"')?;
+            path.push(key[..key.find('"')?].to_string());
+            rest = call[..open].strip_suffix(".at")?;
+            continue;
+        }
+        let start = rest.len() - rest.chars().rev().take_while(|c| is_ident_char(*c)).map(char::len_utf8).sum::<usize>();
+        let ident = &rest[start..];
+        if ident.is_empty() {
+            return None;
+        }
+        match rest[..start].strip_suffix('.') {
+            Some(target) => {
+                path.push(ident.to_string());
+                rest = target;
+            }
+            None => {
+                path.reverse();
+                return Some((ident.to_string(), path));
+            }
+        }
+    }
+}
+
+/// Offset of the `(` matching a call's closing parenthesis at the end of
+/// `call` (which excludes it)
+fn matching_paren(call: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in call.char_indices().rev() {
+        match c {
+            ')' => depth += 1,
+            '(' if depth == 0 => return Some(i),
+            '(' => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '-'
+}
+
+/// The schema of the object at `path`
+fn object_at<'a>(schema: &'a Schema, path: &[String]) -> Option<&'a Schema> {
+    path.iter().try_fold(schema, |schema, key| {
+        match &schema.fields.iter().find(|field| &field.key == key)?.field_type {
+            FieldType::Object(nested) => Some(nested.as_ref()),
+            _ => None,
+        }
+    })
+}
+
+/// Docs for the field accessed at the cursor
+fn hover(schema: &Schema, data_vars: &BTreeSet<String>, root: &LinkedNode, cursor: usize) -> Option<Hover> {
+    let leaf = root.leaf_at(cursor, Side::After)?;
+    let parent = leaf.parent()?;
+    // The field name of `data.customer.name`, or the key of `.at("name")`
+    let access = match leaf.kind() {
+        SyntaxKind::Ident if parent.kind() == SyntaxKind::FieldAccess => {
+            let field = parent.cast::<ast::FieldAccess>()?.field();
+            std::ptr::eq(field.to_untyped(), leaf.get()).then(|| parent.clone())?
+        }
+        SyntaxKind::Str if parent.kind() == SyntaxKind::Args => {
+            let call = parent.parent()?;
+            at_key(call.cast::<ast::FuncCall>()?)?;
+            call.clone()
+        }
+        _ => return None,
+    };
+    let (root_var, path) = access_path(access.cast::<ast::Expr>()?)?;
+    if !data_vars.contains(&root_var) {
+        return None;
+    }
+
+    let (key, parents) = path.split_last()?;
+    let field = object_at(schema, parents)?.fields.iter().find(|field| &field.key == key)?;
+    let path = path.join(".");
+    let mut contents = format!(
+        "`{}`: {}{}",
+        path,
+        describe(&field.field_type),
+        if field.required { "" } else { " (optional)" }
+    );
+    if let Some(documentation) = documentation(field) {
+        contents.push_str("\n\n");
+        contents.push_str(&documentation);
+    }
+    if let Some(default) = &field.default {
+        contents.push_str(&format!("\n\nDefault: `{}`", default));
+    }
+    Some(Hover { path, contents, start: access.offset(), end: access.range().end })
+}
+
+/// Type of a field, e.g. `array of object`
+fn describe(field_type: &FieldType) -> String {
+    match field_type {
+        FieldType::Array(item) => format!("array of {}", describe(item)),
+        field_type => type_name(field_type).to_string(),
+    }
+}
+
+/// A field's label in bold followed by its description
+fn documentation(field: &SchemaField) -> Option<String> {
+    match (&field.label, &field.description) {
+        (Some(label), Some(description)) => Some(format!("**{}**\n\n{}", label, description)),
+        (Some(label), None) => Some(format!("**{}**", label)),
+        (None, Some(description)) => Some(description.clone()),
+        (None, None) => None,
+    }
+}
@@ -0,0 +1,193 @@
+//! Files embedded in rendered PDFs
+//!
+//! E-invoicing formats like ZUGFeRD and Factur-X carry machine-readable
+//! data inside the PDF as an associated file. Attachments are added as a
+//! post-processing step, before encryption: each becomes an embedded file
+//! stream with a file specification listed both in the document's
+//! `EmbeddedFiles` name tree and in its associated files (`/AF`).
+
+use lopdf::{dictionary, Document, Object, ObjectId, Stream, StringFormat};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{PapermakeError, Result};
+
+/// How an attachment relates to the document (PDF/A-3 `AFRelationship`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AttachmentRelationship {
+    /// The original source material of the document
+    Source,
+    /// Data the document's content was derived from
+    #[default]
+    Data,
+    /// An alternative representation, e.g. the XML of a ZUGFeRD invoice
+    Alternative,
+    /// Supplementary information
+    Supplement,
+    Unspecified,
+}
+
+impl AttachmentRelationship {
+    fn as_name(&self) -> &'static str {
+        match self {
+            AttachmentRelationship::Source => "Source",
+            AttachmentRelationship::Data => "Data",
+            AttachmentRelationship::Alternative => "Alternative",
+            AttachmentRelationship::Supplement => "Supplement",
+            AttachmentRelationship::Unspecified => "Unspecified",
+        }
+    }
+}
+
+/// Content of an attachment
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AttachmentContent {
+    /// The render's input data as pretty-printed JSON
+    InputData,
+    /// Fixed bytes, e.g. an XML payload generated from the data
+    Bytes(Vec<u8>),
+}
+
+/// A file embedded in a rendered PDF
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PdfAttachment {
+    pub filename: String,
+    pub mime_type: String,
+    pub description: Option<String>,
+    pub relationship: AttachmentRelationship,
+    pub content: AttachmentContent,
+}
+
+impl PdfAttachment {
+    /// Attach the render's input data as `filename`
+    pub fn input_data(filename: impl Into<String>) -> Self {
+        Self {
+            filename: filename.into(),
+            mime_type: "application/json".to_string(),
+            description: None,
+            relationship: AttachmentRelationship::Data,
+            content: AttachmentContent::InputData,
+        }
+    }
+
+    /// Attach fixed bytes as `filename`
+    pub fn bytes(filename: impl Into<String>, mime_type: impl Into<String>, bytes: Vec<u8>) -> Self {
+        Self {
+            filename: filename.into(),
+            mime_type: mime_type.into(),
+            description: None,
+            relationship: AttachmentRelationship::Data,
+            content: AttachmentContent::Bytes(bytes),
+        }
+    }
+
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    pub fn relationship(mut self, relationship: AttachmentRelationship) -> Self {
+        self.relationship = relationship;
+        self
+    }
+
+    fn content_bytes(&self, data: &serde_json::Value) -> Result<Vec<u8>> {
+        match &self.content {
+            AttachmentContent::InputData => serde_json::to_vec_pretty(data)
+                .map_err(|e| PapermakeError::Rendering(format!("Failed to serialize attachment: {}", e))),
+            AttachmentContent::Bytes(bytes) => Ok(bytes.clone()),
+        }
+    }
+}
+
+/// Embed attachments into a PDF
+pub fn attach_files(pdf: &[u8], attachments: &[PdfAttachment], data: &serde_json::Value) -> Result<Vec<u8>> {
+    let pdf_error = |e: lopdf::Error| PapermakeError::Rendering(format!("Failed to attach files: {}", e));
+
+    let mut doc = Document::load_mem(pdf).map_err(pdf_error)?;
+    let mut specs = Vec::new();
+    for attachment in attachments {
+        let bytes = attachment.content_bytes(data)?;
+        let stream = Stream::new(
+            dictionary! {
+                "Type" => "EmbeddedFile",
+                "Subtype" => Object::Name(attachment.mime_type.as_bytes().to_vec()),
+                "Params" => dictionary! { "Size" => bytes.len() as i64 },
+            },
+            bytes,
+        );
+        let stream_id = doc.add_object(stream);
+
+        let mut spec = dictionary! {
+            "Type" => "Filespec",
+            "F" => Object::string_literal(attachment.filename.as_str()),
+            "UF" => text_string(&attachment.filename),
+            "AFRelationship" => Object::Name(attachment.relationship.as_name().as_bytes().to_vec()),
+            "EF" => dictionary! { "F" => stream_id, "UF" => stream_id },
+        };
+        if let Some(description) = &attachment.description {
+            spec.set("Desc", text_string(description));
+        }
+        specs.push((attachment.filename.clone(), doc.add_object(spec)));
+    }
+
+    let catalog_id = doc.trailer.get(b"Root").and_then(Object::as_reference).map_err(pdf_error)?;
+    let names_id = names_dictionary(&mut doc, catalog_id).map_err(pdf_error)?;
+
+    // Keep files embedded by the template itself; name tree keys must be sorted
+    let mut entries: Vec<(Vec<u8>, Object)> = Vec::new();
+    if let Ok(existing) = doc
+        .get_dictionary(names_id)
+        .and_then(|names| names.get(b"EmbeddedFiles"))
+        .and_then(|tree| doc.dereference(tree))
+        .and_then(|(_, tree)| tree.as_dict())
+        .and_then(|tree| tree.get(b"Names"))
+        .and_then(Object::as_array)
+    {
+        for pair in existing.chunks(2) {
+            if let [Object::String(name, _), spec] = pair {
+                entries.push((name.clone(), spec.clone()));
+            }
+        }
+    }
+    for (filename, spec_id) in &specs {
+        entries.push((filename.as_bytes().to_vec(), Object::Reference(*spec_id)));
+    }
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    let names: Vec<Object> = entries
+        .into_iter()
+        .flat_map(|(name, spec)| [Object::String(name, StringFormat::Literal), spec])
+        .collect();
+    doc.get_dictionary_mut(names_id)
+        .map_err(pdf_error)?
+        .set("EmbeddedFiles", dictionary! { "Names" => names });
+
+    let catalog = doc.get_dictionary_mut(catalog_id).map_err(pdf_error)?;
+    let mut associated = catalog.get(b"AF").and_then(Object::as_array).cloned().unwrap_or_default();
+    associated.extend(specs.iter().map(|(_, id)| Object::Reference(*id)));
+    catalog.set("AF", associated);
+
+    let mut output = Vec::new();
+    doc.save_to(&mut output).map_err(|e| PapermakeError::Rendering(format!("Failed to attach files: {}", e)))?;
+    Ok(output)
+}
+
+/// The catalog's `/Names` dictionary as an indirect object, creating it if needed
+fn names_dictionary(doc: &mut Document, catalog_id: ObjectId) -> lopdf::Result<ObjectId> {
+    let names = doc.get_dictionary(catalog_id)?.get(b"Names").ok().cloned();
+    let id = match names {
+        Some(Object::Reference(id)) => return Ok(id),
+        Some(Object::Dictionary(names)) => doc.add_object(names),
+        _ => doc.add_object(dictionary! {}),
+    };
+    doc.get_dictionary_mut(catalog_id)?.set("Names", id);
+    Ok(id)
+}
+
+/// PDF text string: UTF-16BE with a byte order mark
+fn text_string(text: &str) -> Object {
+    let bytes = [0xFE, 0xFF]
+        .into_iter()
+        .chain(text.encode_utf16().flat_map(u16::to_be_bytes))
+        .collect();
+    Object::String(bytes, StringFormat::Hexadecimal)
+}
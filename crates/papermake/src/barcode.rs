@@ -0,0 +1,97 @@
+//! QR codes and barcodes generated from schema fields
+//!
+//! Fields of type [`FieldType::Barcode`] hold the encoded text. Before each
+//! render, papermake draws every barcode field as an SVG and exposes it to
+//! the template at `papermake:barcodes/<field path>.svg`, where the path
+//! joins nested keys and array indices with dots:
+//!
+//! ```typst
+//! #image("papermake:barcodes/ticket.qr.svg", width: 3cm)
+//! #for (i, item) in sys.inputs.data.items.enumerate() [
+//!   #image("papermake:barcodes/items." + str(i) + ".ean.svg")
+//! ]
+//! ```
+
+use std::collections::HashMap;
+
+use barcoders::generators::svg::SVG;
+use barcoders::sym::code128::Code128;
+use barcoders::sym::ean13::EAN13;
+use qrcode::render::svg;
+use qrcode::QrCode;
+
+use crate::error::{PapermakeError, Result};
+use crate::schema::{BarcodeKind, FieldType, Schema, BARCODE_DIR};
+
+/// Height in pixels of linear barcodes; the SVG scales with the image
+const LINEAR_HEIGHT: u32 = 80;
+
+impl BarcodeKind {
+    /// Draw `text` as an SVG document
+    pub fn render_svg(&self, text: &str) -> Result<String> {
+        self.check(text)
+            .map_err(|message| PapermakeError::InvalidInput(format!("Barcode '{}' {}", text, message)))?;
+        let failed = |e: &dyn std::fmt::Display| PapermakeError::Rendering(format!("Failed to draw barcode: {}", e));
+
+        let encoded = match self {
+            BarcodeKind::Qr => {
+                let code = QrCode::new(text.as_bytes()).map_err(|e| failed(&e))?;
+                return Ok(code
+                    .render::<svg::Color>()
+                    .quiet_zone(true)
+                    .min_dimensions(200, 200)
+                    .build());
+            }
+            // Character set B covers printable ASCII
+            BarcodeKind::Code128 => Code128::new(format!("\u{0181}{}", text)).map_err(|e| failed(&e))?.encode(),
+            // The symbology adds the check digit itself
+            BarcodeKind::Ean13 => EAN13::new(&text[..12]).map_err(|e| failed(&e))?.encode(),
+        };
+        SVG::new(LINEAR_HEIGHT).generate(&encoded).map_err(|e| failed(&e))
+    }
+}
+
+/// Draw every barcode field present in `data`, keyed by the path the
+/// template imports it from (without the `papermake:` scheme)
+pub fn render_barcodes(schema: &Schema, data: &serde_json::Value) -> Result<HashMap<String, Vec<u8>>> {
+    let mut barcodes = HashMap::new();
+    collect_object(schema, data, "", &mut barcodes)?;
+    Ok(barcodes)
+}
+
+fn collect_object(
+    schema: &Schema,
+    data: &serde_json::Value,
+    prefix: &str,
+    barcodes: &mut HashMap<String, Vec<u8>>,
+) -> Result<()> {
+    for field in &schema.fields {
+        if let Some(value) = data.get(&field.key) {
+            let path = format!("{}{}", prefix, field.key);
+            collect_value(&field.field_type, value, &path, barcodes)?;
+        }
+    }
+    Ok(())
+}
+
+fn collect_value(
+    field_type: &FieldType,
+    value: &serde_json::Value,
+    path: &str,
+    barcodes: &mut HashMap<String, Vec<u8>>,
+) -> Result<()> {
+    match (field_type, value) {
+        (FieldType::Barcode(kind), serde_json::Value::String(text)) => {
+            let svg = kind.render_svg(text)?;
+            barcodes.insert(format!("{}{}.svg", BARCODE_DIR, path), svg.into_bytes());
+        }
+        (FieldType::Object(schema), value) => collect_object(schema, value, &format!("{}.", path), barcodes)?,
+        (FieldType::Array(item_type), serde_json::Value::Array(items)) => {
+            for (i, item) in items.iter().enumerate() {
+                collect_value(item_type, item, &format!("{}.{}", path, i), barcodes)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
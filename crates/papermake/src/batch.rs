@@ -0,0 +1,101 @@
+//! Batch rendering streamed into an output sink
+
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{PapermakeError, Result};
+use crate::render::{render_pdf_with_cache, RenderError, RenderOptions, RenderResult};
+use crate::sink::RenderSink;
+use crate::template::Template;
+use crate::typst::TypstWorld;
+
+/// Outcome of rendering a single record of a batch
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchItem {
+    /// Index of the record in the input
+    pub index: usize,
+    /// Sink key the document was written to
+    pub key: Option<String>,
+    /// Location returned by the sink
+    pub url: Option<String>,
+    pub size_bytes: Option<usize>,
+    pub errors: Vec<RenderError>,
+}
+
+impl BatchItem {
+    /// Whether the record rendered and was written to the sink
+    pub fn is_success(&self) -> bool {
+        self.url.is_some()
+    }
+}
+
+/// Render each record and write the PDF to the sink as soon as it is finished
+///
+/// Documents are written under `{key_prefix}/{index}.pdf`, so at most one
+/// rendered document is held in memory at a time. Records are compiled on
+/// tokio's blocking thread pool, reusing a single world. Failing records are
+/// reported in their `BatchItem` without aborting the batch; sink errors
+/// abort it.
+pub async fn render_batch(
+    template: &Template,
+    records: &[serde_json::Value],
+    options: RenderOptions,
+    sink: &dyn RenderSink,
+    key_prefix: &str,
+) -> Result<Vec<BatchItem>> {
+    let template = Arc::new(template.clone());
+    let key_prefix = key_prefix.trim_end_matches('/');
+    let mut world = TypstWorld::new(template.content.clone(), "{}".to_string());
+    let mut items = Vec::with_capacity(records.len());
+
+    for (index, record) in records.iter().enumerate() {
+        let task_template = template.clone();
+        let task_record = record.clone();
+        let task_options = options.clone();
+
+        let (returned_world, result) = tokio::task::spawn_blocking(move || {
+            let result = render_pdf_with_cache(&task_template, &task_record, Some(&mut world), Some(task_options));
+            (world, result)
+        })
+        .await
+        .map_err(|e| PapermakeError::Rendering(format!("Render task failed: {}", e)))?;
+        world = returned_world;
+
+        let item = match result {
+            Ok(RenderResult { pdf: Some(pdf), .. }) => {
+                let key = format!("{}/{:06}.pdf", key_prefix, index);
+                let size_bytes = pdf.len();
+                let url = sink.write(&key, pdf).await?;
+                BatchItem {
+                    index,
+                    key: Some(key),
+                    url: Some(url),
+                    size_bytes: Some(size_bytes),
+                    errors: Vec::new(),
+                }
+            }
+            Ok(result) => BatchItem {
+                index,
+                key: None,
+                url: None,
+                size_bytes: None,
+                errors: result.errors,
+            },
+            Err(e) => BatchItem {
+                index,
+                key: None,
+                url: None,
+                size_bytes: None,
+                errors: vec![RenderError {
+                    message: e.to_string(),
+                    start: 0,
+                    end: 0,
+                }],
+            },
+        };
+        items.push(item);
+    }
+
+    Ok(items)
+}
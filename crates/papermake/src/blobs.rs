@@ -0,0 +1,524 @@
+//! Content-addressed, deduplicated storage of template files
+//!
+//! Large deployments upload the same logos and fonts to many templates.
+//! [`DedupStorage`] wraps any [`Storage`] and keeps each distinct file
+//! content once in a [`BlobStore`], addressed by its SHA-256 hash; the
+//! inner storage only holds a small pointer per template file. Copying a
+//! template copies pointers, and importing content that is already stored
+//! writes nothing new.
+//!
+//! Blobs count their references, one per template file pointing at them
+//! (across tenant namespaces), and [`BlobStore::collect_garbage`] deletes
+//! those nothing references anymore. References are added before a pointer
+//! is written and removed after it is gone, so an interrupted operation
+//! leaves a blob behind rather than a pointer to nothing. Garbage
+//! collection spares blobs stored recently, whose pointer may still be on
+//! its way.
+//!
+//! A stored file is only taken as a pointer if its own path holds a
+//! reference to the blob, so files stored before deduplication was enabled
+//! are read as they are, even when their content looks like a pointer.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use time::OffsetDateTime;
+
+use crate::error::{PapermakeError, Result};
+use crate::shared::Dependent;
+use crate::storage::{ArtifactKind, ListOptions, Namespace, PurgeReport, SearchHit, Storage, TemplatePage};
+use crate::template::{Template, TemplateId};
+
+/// Leading bytes of a pointer to a blob, followed by the hex hash
+const POINTER_PREFIX: &[u8] = b"papermake-blob:sha256:";
+
+/// Hex SHA-256 hash addressing `content`
+pub fn blob_hash(content: &[u8]) -> String {
+    hex::encode(Sha256::digest(content))
+}
+
+/// Check that `hash` is a hex SHA-256 hash, as blob paths are built from it
+fn check_hash(hash: &str) -> Result<()> {
+    if hash.len() == 64 && hash.bytes().all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b)) {
+        Ok(())
+    } else {
+        Err(PapermakeError::InvalidInput(format!("Invalid blob hash: {}", hash)))
+    }
+}
+
+/// Result of [`BlobStore::collect_garbage`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct GcReport {
+    /// Blobs still referenced, or stored too recently to delete
+    pub kept: usize,
+    pub deleted: usize,
+    /// Size of the deleted blobs
+    pub freed_bytes: u64,
+}
+
+/// Reference-counted store of contents addressed by their hash
+#[async_trait]
+pub trait BlobStore: Send + Sync {
+    /// Store `content`, returning its hash; content already stored isn't
+    /// written again but counts as stored now
+    async fn put(&self, content: &[u8]) -> Result<String>;
+
+    /// Read the content with the given hash
+    async fn get(&self, hash: &str) -> Result<Vec<u8>>;
+
+    /// Record that `owner` references a blob; adding an owner again
+    /// doesn't count twice
+    async fn add_ref(&self, hash: &str, owner: &str) -> Result<()>;
+
+    /// Drop the reference of `owner` to a blob, if it has one
+    async fn remove_ref(&self, hash: &str, owner: &str) -> Result<()>;
+
+    /// Number of owners referencing a blob
+    async fn ref_count(&self, hash: &str) -> Result<usize>;
+
+    /// Whether `owner` references a blob
+    async fn has_ref(&self, hash: &str, owner: &str) -> Result<bool>;
+
+    /// Delete blobs nothing references that were last stored before `before`
+    async fn collect_garbage(&self, before: OffsetDateTime) -> Result<GcReport>;
+}
+
+#[derive(Debug)]
+struct MemoryBlob {
+    content: Vec<u8>,
+    owners: BTreeSet<String>,
+    stored_at: OffsetDateTime,
+}
+
+/// Blob store keeping contents in memory, mainly for tests
+#[derive(Debug, Default)]
+pub struct MemoryBlobStore {
+    blobs: Mutex<BTreeMap<String, MemoryBlob>>,
+}
+
+impl MemoryBlobStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, BTreeMap<String, MemoryBlob>>> {
+        self.blobs
+            .lock()
+            .map_err(|_| PapermakeError::Storage("Failed to acquire blob store lock".to_string()))
+    }
+}
+
+#[async_trait]
+impl BlobStore for MemoryBlobStore {
+    async fn put(&self, content: &[u8]) -> Result<String> {
+        let hash = blob_hash(content);
+        let now = OffsetDateTime::now_utc();
+        self.lock()?
+            .entry(hash.clone())
+            .and_modify(|blob| blob.stored_at = now)
+            .or_insert_with(|| MemoryBlob { content: content.to_vec(), owners: BTreeSet::new(), stored_at: now });
+        Ok(hash)
+    }
+
+    async fn get(&self, hash: &str) -> Result<Vec<u8>> {
+        self.lock()?
+            .get(hash)
+            .map(|blob| blob.content.clone())
+            .ok_or_else(|| PapermakeError::not_found("Blob", hash))
+    }
+
+    async fn add_ref(&self, hash: &str, owner: &str) -> Result<()> {
+        let mut blobs = self.lock()?;
+        let blob = blobs.get_mut(hash).ok_or_else(|| PapermakeError::not_found("Blob", hash))?;
+        blob.owners.insert(owner.to_string());
+        Ok(())
+    }
+
+    async fn remove_ref(&self, hash: &str, owner: &str) -> Result<()> {
+        if let Some(blob) = self.lock()?.get_mut(hash) {
+            blob.owners.remove(owner);
+        }
+        Ok(())
+    }
+
+    async fn ref_count(&self, hash: &str) -> Result<usize> {
+        Ok(self.lock()?.get(hash).map(|blob| blob.owners.len()).unwrap_or_default())
+    }
+
+    async fn has_ref(&self, hash: &str, owner: &str) -> Result<bool> {
+        Ok(self.lock()?.get(hash).is_some_and(|blob| blob.owners.contains(owner)))
+    }
+
+    async fn collect_garbage(&self, before: OffsetDateTime) -> Result<GcReport> {
+        let mut report = GcReport::default();
+        self.lock()?.retain(|_, blob| {
+            let keep = !blob.owners.is_empty() || blob.stored_at >= before;
+            if keep {
+                report.kept += 1;
+            } else {
+                report.deleted += 1;
+                report.freed_bytes += blob.content.len() as u64;
+            }
+            keep
+        });
+        Ok(report)
+    }
+}
+
+#[cfg(feature = "fs")]
+pub use file_blobs::FileBlobStore;
+
+#[cfg(feature = "fs")]
+mod file_blobs {
+    use std::path::{Path, PathBuf};
+    use std::time::SystemTime;
+
+    use async_trait::async_trait;
+    use time::OffsetDateTime;
+    use tokio::fs;
+
+    use super::{blob_hash, check_hash, BlobStore, GcReport};
+    use crate::error::{PapermakeError, Result};
+    use crate::storage::FileStorage;
+
+    /// Blob store below a local directory
+    ///
+    /// Directory structure:
+    /// ```text
+    /// base_path/
+    /// ├── objects/
+    /// │   └── 3f/
+    /// │       └── 9a1c...       (content, named by the rest of its hash)
+    /// └── refs/
+    ///     └── 3f/
+    ///         └── 9a1c.../
+    ///             └── <owner>   (one marker per reference)
+    /// ```
+    ///
+    /// Markers are named by the hash of their owner and created and removed
+    /// individually, so references need no lock.
+    #[derive(Debug, Clone)]
+    pub struct FileBlobStore {
+        base_path: PathBuf,
+    }
+
+    impl FileBlobStore {
+        pub fn new(base_path: impl Into<PathBuf>) -> Self {
+            Self {
+                base_path: base_path.into(),
+            }
+        }
+
+        fn object_path(&self, hash: &str) -> Result<PathBuf> {
+            check_hash(hash)?;
+            Ok(self.base_path.join("objects").join(&hash[..2]).join(&hash[2..]))
+        }
+
+        fn refs_dir(&self, hash: &str) -> Result<PathBuf> {
+            check_hash(hash)?;
+            Ok(self.base_path.join("refs").join(&hash[..2]).join(&hash[2..]))
+        }
+
+        /// Count existing content as stored now, so garbage collection
+        /// spares it until its new reference is added
+        async fn touch(path: &Path) -> Result<()> {
+            let file = fs::OpenOptions::new().append(true).open(path).await?.into_std().await;
+            tokio::task::spawn_blocking(move || file.set_modified(SystemTime::now()))
+                .await
+                .map_err(|e| PapermakeError::Storage(e.to_string()))??;
+            Ok(())
+        }
+    }
+
+    #[async_trait]
+    impl BlobStore for FileBlobStore {
+        async fn put(&self, content: &[u8]) -> Result<String> {
+            let hash = blob_hash(content);
+            let path = self.object_path(&hash)?;
+            if path.exists() {
+                Self::touch(&path).await?;
+            } else {
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent).await?;
+                }
+                FileStorage::write_atomic(&path, content).await?;
+            }
+            Ok(hash)
+        }
+
+        async fn get(&self, hash: &str) -> Result<Vec<u8>> {
+            let path = self.object_path(hash)?;
+            fs::read(&path).await.map_err(|e| match e.kind() {
+                std::io::ErrorKind::NotFound => PapermakeError::not_found("Blob", hash),
+                _ => PapermakeError::storage_io(&path, e),
+            })
+        }
+
+        async fn add_ref(&self, hash: &str, owner: &str) -> Result<()> {
+            if !self.object_path(hash)?.exists() {
+                return Err(PapermakeError::not_found("Blob", hash));
+            }
+            let dir = self.refs_dir(hash)?;
+            fs::create_dir_all(&dir).await?;
+            // The owner is kept as the content, for inspecting references
+            fs::write(dir.join(blob_hash(owner.as_bytes())), owner).await?;
+            Ok(())
+        }
+
+        async fn remove_ref(&self, hash: &str, owner: &str) -> Result<()> {
+            let dir = self.refs_dir(hash)?;
+            match fs::remove_file(dir.join(blob_hash(owner.as_bytes()))).await {
+                Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err),
+                _ => Ok(()),
+            }?;
+            // Fails while other references remain
+            let _ = fs::remove_dir(&dir).await;
+            Ok(())
+        }
+
+        async fn ref_count(&self, hash: &str) -> Result<usize> {
+            let dir = self.refs_dir(hash)?;
+            if !dir.exists() {
+                return Ok(0);
+            }
+            let mut count = 0;
+            let mut entries = fs::read_dir(&dir).await?;
+            while entries.next_entry().await?.is_some() {
+                count += 1;
+            }
+            Ok(count)
+        }
+
+        async fn has_ref(&self, hash: &str, owner: &str) -> Result<bool> {
+            Ok(self.refs_dir(hash)?.join(blob_hash(owner.as_bytes())).exists())
+        }
+
+        async fn collect_garbage(&self, before: OffsetDateTime) -> Result<GcReport> {
+            let before = SystemTime::from(before);
+            let mut report = GcReport::default();
+            let objects = self.base_path.join("objects");
+            if !objects.exists() {
+                return Ok(report);
+            }
+
+            let mut prefixes = fs::read_dir(&objects).await?;
+            while let Some(prefix) = prefixes.next_entry().await? {
+                if !prefix.file_type().await?.is_dir() {
+                    continue;
+                }
+                let mut entries = fs::read_dir(prefix.path()).await?;
+                while let Some(entry) = entries.next_entry().await? {
+                    let hash = format!("{}{}", prefix.file_name().to_string_lossy(), entry.file_name().to_string_lossy());
+                    // Leftovers of interrupted writes are named differently
+                    if check_hash(&hash).is_err() {
+                        continue;
+                    }
+                    let metadata = entry.metadata().await?;
+                    let stored_recently = !metadata.modified().is_ok_and(|modified| modified < before);
+                    if stored_recently || self.ref_count(&hash).await? > 0 {
+                        report.kept += 1;
+                        continue;
+                    }
+                    fs::remove_file(entry.path()).await?;
+                    report.deleted += 1;
+                    report.freed_bytes += metadata.len();
+                }
+                // Fails while the directory still has blobs
+                let _ = fs::remove_dir(prefix.path()).await;
+            }
+            Ok(report)
+        }
+    }
+}
+
+/// Storage keeping the contents of template files in a [`BlobStore`]
+pub struct DedupStorage<S: Storage + ?Sized> {
+    inner: Arc<S>,
+    blobs: Arc<dyn BlobStore>,
+    /// Prefix of the owners of this storage's references, distinguishing
+    /// namespaces sharing the blob store
+    owner_prefix: String,
+}
+
+impl<S: Storage + ?Sized> DedupStorage<S> {
+    pub fn new(inner: Arc<S>, blobs: Arc<dyn BlobStore>) -> Self {
+        Self { inner, blobs, owner_prefix: String::new() }
+    }
+
+    /// The blob store holding the file contents
+    pub fn blobs(&self) -> &Arc<dyn BlobStore> {
+        &self.blobs
+    }
+
+    fn owner(&self, id: &TemplateId, path: &str) -> String {
+        format!("{}{}/{}", self.owner_prefix, id.as_ref(), path)
+    }
+
+    /// Hash of the blob a stored file points to, if it exists and is a pointer
+    async fn pointed_hash(&self, id: &TemplateId, path: &str) -> Option<String> {
+        let stored = self.inner.get_template_file(id, path).await.ok()?;
+        let hash = parse_pointer(&stored)?;
+        self.is_pointer(id, path, hash).await.ok()?.then(|| hash.to_string())
+    }
+
+    /// Whether the file at `path` really points to `hash`: pointers are only
+    /// written once their file references the blob, while content that
+    /// merely looks like a pointer, e.g. uploaded before deduplication was
+    /// enabled, mustn't read blobs of other templates or tenants
+    async fn is_pointer(&self, id: &TemplateId, path: &str, hash: &str) -> Result<bool> {
+        self.blobs.has_ref(hash, &self.owner(id, path)).await
+    }
+
+    /// Paths of a template's files with the hashes they point to
+    async fn pointed_files(&self, id: &TemplateId) -> Result<Vec<(String, String)>> {
+        let mut files = Vec::new();
+        for path in self.inner.list_template_files(id).await? {
+            if let Some(hash) = self.pointed_hash(id, &path).await {
+                files.push((path, hash));
+            }
+        }
+        Ok(files)
+    }
+}
+
+fn parse_pointer(stored: &[u8]) -> Option<&str> {
+    let hash = std::str::from_utf8(stored.strip_prefix(POINTER_PREFIX)?).ok()?;
+    check_hash(hash).ok().map(|()| hash)
+}
+
+#[async_trait]
+impl<S: Storage + ?Sized + 'static> Storage for DedupStorage<S> {
+    async fn save_template(&self, template: &Template) -> Result<()> {
+        self.inner.save_template(template).await
+    }
+
+    async fn get_template(&self, id: &TemplateId) -> Result<Template> {
+        self.inner.get_template(id).await
+    }
+
+    async fn list_templates(&self, options: &ListOptions) -> Result<TemplatePage> {
+        self.inner.list_templates(options).await
+    }
+
+    async fn search_templates(&self, query: &str) -> Result<Vec<SearchHit>> {
+        self.inner.search_templates(query).await
+    }
+
+    async fn save_published_template(&self, template: &Template) -> Result<()> {
+        self.inner.save_published_template(template).await
+    }
+
+    async fn get_published_template(&self, id: &TemplateId) -> Result<Template> {
+        self.inner.get_published_template(id).await
+    }
+
+    async fn get_dependents(&self, id: &TemplateId) -> Result<Vec<Dependent>> {
+        self.inner.get_dependents(id).await
+    }
+
+    async fn list_shared_templates(&self) -> Result<Vec<Template>> {
+        self.inner.list_shared_templates().await
+    }
+
+    async fn delete_template(&self, id: &TemplateId) -> Result<()> {
+        let files = self.pointed_files(id).await?;
+        self.inner.delete_template(id).await?;
+        for (path, hash) in files {
+            self.blobs.remove_ref(&hash, &self.owner(id, &path)).await?;
+        }
+        Ok(())
+    }
+
+    /// Copies only the pointers; the copy references the same blobs
+    async fn copy_template(&self, id: &TemplateId, new_id: &TemplateId) -> Result<Template> {
+        if self.inner.get_template(new_id).await.is_ok() {
+            return Err(PapermakeError::Conflict(format!("Template '{}' already exists", new_id.as_ref())));
+        }
+        let files = self.pointed_files(id).await?;
+        for (path, hash) in &files {
+            self.blobs.add_ref(hash, &self.owner(new_id, path)).await?;
+        }
+        match self.inner.copy_template(id, new_id).await {
+            // A copy that appeared meanwhile may own the same references
+            Err(err @ PapermakeError::Conflict(_)) => Err(err),
+            Err(err) => {
+                for (path, hash) in &files {
+                    self.blobs.remove_ref(hash, &self.owner(new_id, path)).await?;
+                }
+                Err(err)
+            }
+            Ok(copy) => Ok(copy),
+        }
+    }
+
+    async fn save_template_file(&self, id: &TemplateId, path: &str, content: &[u8]) -> Result<()> {
+        let previous = self.pointed_hash(id, path).await;
+        let hash = self.blobs.put(content).await?;
+        let owner = self.owner(id, path);
+        self.blobs.add_ref(&hash, &owner).await?;
+        let pointer = [POINTER_PREFIX, hash.as_bytes()].concat();
+        self.inner.save_template_file(id, path, &pointer).await?;
+        if let Some(previous) = previous.filter(|previous| *previous != hash) {
+            self.blobs.remove_ref(&previous, &owner).await?;
+        }
+        Ok(())
+    }
+
+    async fn get_template_file(&self, id: &TemplateId, path: &str) -> Result<Vec<u8>> {
+        let stored = self.inner.get_template_file(id, path).await?;
+        match parse_pointer(&stored) {
+            Some(hash) if self.is_pointer(id, path, hash).await? => self.blobs.get(hash).await,
+            _ => Ok(stored),
+        }
+    }
+
+    async fn list_template_files(&self, id: &TemplateId) -> Result<Vec<String>> {
+        self.inner.list_template_files(id).await
+    }
+
+    async fn delete_template_file(&self, id: &TemplateId, path: &str) -> Result<()> {
+        let hash = self.pointed_hash(id, path).await;
+        self.inner.delete_template_file(id, path).await?;
+        if let Some(hash) = hash {
+            self.blobs.remove_ref(&hash, &self.owner(id, path)).await?;
+        }
+        Ok(())
+    }
+
+    async fn rename_template_file(&self, id: &TemplateId, from: &str, to: &str) -> Result<()> {
+        let Some(hash) = self.pointed_hash(id, from).await else {
+            return self.inner.rename_template_file(id, from, to).await;
+        };
+        let owner = self.owner(id, to);
+        self.blobs.add_ref(&hash, &owner).await?;
+        if let Err(err) = self.inner.rename_template_file(id, from, to).await {
+            // The target may be an existing file pointing to the same blob
+            if !matches!(err, PapermakeError::Conflict(_)) {
+                self.blobs.remove_ref(&hash, &owner).await?;
+            }
+            return Err(err);
+        }
+        self.blobs.remove_ref(&hash, &self.owner(id, from)).await
+    }
+
+    async fn purge(&self, before: OffsetDateTime, kinds: &[ArtifactKind]) -> Result<PurgeReport> {
+        self.inner.purge(before, kinds).await
+    }
+
+    async fn check_health(&self) -> Result<()> {
+        self.inner.check_health().await
+    }
+
+    /// Namespaces share the blob store, so identical files of different
+    /// tenants are stored once
+    fn for_namespace(&self, namespace: &Namespace) -> Arc<dyn Storage> {
+        Arc::new(DedupStorage {
+            inner: self.inner.for_namespace(namespace),
+            blobs: self.blobs.clone(),
+            owner_prefix: format!("{}tenants/{}/", self.owner_prefix, namespace.as_str()),
+        })
+    }
+}
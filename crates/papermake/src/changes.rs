@@ -0,0 +1,89 @@
+//! Reviewable changes between two revisions of a template
+//!
+//! [`Template::changes`] lists what an edit changes, the way a code review
+//! shows it: a unified diff of the Typst source, field-level schema changes
+//! (see [`crate::compatibility`]) and added, removed or modified files. File
+//! changes come from the [`Template::assets`] manifests of both revisions.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::Serialize;
+
+use crate::compatibility::CompatibilityReport;
+use crate::line_diff::unified_diff;
+use crate::schema::Schema;
+use crate::template::Template;
+
+/// Unchanged lines shown around each change of the content diff
+const DIFF_CONTEXT: usize = 3;
+
+/// Differences between an older and a newer revision of a template
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct TemplateChanges {
+    /// Unified diff of the content, empty if unchanged
+    pub content: String,
+    /// Unified diffs of locale variants that changed, by locale; added and
+    /// removed variants diff against empty content
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub variants: BTreeMap<String, String>,
+    pub schema: CompatibilityReport,
+    pub files: Vec<FileChange>,
+}
+
+impl TemplateChanges {
+    /// Whether nothing changed in content, variants, schema or files
+    pub fn is_empty(&self) -> bool {
+        self.content.is_empty() && self.variants.is_empty() && self.schema.changes.is_empty() && self.files.is_empty()
+    }
+}
+
+/// How a file differs between revisions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileChangeKind {
+    Added,
+    Removed,
+    Modified,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FileChange {
+    pub path: String,
+    pub kind: FileChangeKind,
+}
+
+impl Template {
+    /// Changes from this revision to `newer`
+    pub fn changes(&self, newer: &Template) -> TemplateChanges {
+        let locales = self.variants.keys().chain(newer.variants.keys());
+        let variants = locales
+            .filter_map(|locale| {
+                let old = self.variants.get(locale).map(String::as_str).unwrap_or_default();
+                let new = newer.variants.get(locale).map(String::as_str).unwrap_or_default();
+                let diff = unified_diff(old, new, DIFF_CONTEXT);
+                (!diff.is_empty()).then(|| (locale.clone(), diff))
+            })
+            .collect();
+
+        let paths = self.assets.keys().chain(newer.assets.keys()).collect::<BTreeSet<_>>();
+        let files = paths
+            .into_iter()
+            .filter_map(|path| {
+                let kind = match (self.assets.get(path), newer.assets.get(path)) {
+                    (None, Some(_)) => FileChangeKind::Added,
+                    (Some(_), None) => FileChangeKind::Removed,
+                    (Some(old), Some(new)) if old != new => FileChangeKind::Modified,
+                    _ => return None,
+                };
+                Some(FileChange { path: path.clone(), kind })
+            })
+            .collect();
+
+        TemplateChanges {
+            content: unified_diff(&self.content, &newer.content, DIFF_CONTEXT),
+            variants,
+            schema: Schema::compatibility(&self.schema, &newer.schema),
+            files,
+        }
+    }
+}
@@ -0,0 +1,333 @@
+//! Charts drawn before rendering and exposed to templates as SVG files
+//!
+//! Charts are declared by name in `RenderOptions::charts`. Before
+//! compiling, each one is drawn with plotters and made available to the
+//! template as `chart:<name>.svg`:
+//!
+//! ```typst
+//! #image("chart:revenue.svg", width: 100%)
+//! ```
+//!
+//! Labels and values are given inline or taken from the render data with a
+//! JSON pointer. A `*` segment maps over an array, so `/months/*/revenue`
+//! collects the `revenue` of every entry of `months`.
+
+use std::collections::{BTreeMap, HashMap};
+
+use plotters::coord::ranged1d::SegmentValue;
+use plotters::element::Pie;
+use plotters::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{PapermakeError, Result};
+
+/// Series colors, cycled when a chart has more series or slices
+const PALETTE: [RGBColor; 6] = [
+    RGBColor(31, 119, 180),
+    RGBColor(255, 127, 14),
+    RGBColor(44, 160, 44),
+    RGBColor(214, 39, 40),
+    RGBColor(148, 103, 189),
+    RGBColor(140, 86, 75),
+];
+
+/// Kind of chart
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChartKind {
+    Bar,
+    Line,
+    /// Pie of the first series
+    Pie,
+}
+
+/// Values given inline or read from the render data
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ChartData<T> {
+    Values(Vec<T>),
+    /// JSON pointer into the data, `*` mapping over arrays
+    Path(String),
+}
+
+/// A named series of values, one per label
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChartSeries {
+    pub name: String,
+    pub values: ChartData<f64>,
+}
+
+/// A chart drawn before rendering
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChartSpec {
+    pub kind: ChartKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Category labels along the x axis, or of the pie slices
+    pub labels: ChartData<String>,
+    pub series: Vec<ChartSeries>,
+    /// Size of the SVG in pixels; the image scales in the document
+    #[serde(default = "default_width")]
+    pub width: u32,
+    #[serde(default = "default_height")]
+    pub height: u32,
+}
+
+fn default_width() -> u32 {
+    640
+}
+
+fn default_height() -> u32 {
+    400
+}
+
+impl ChartSpec {
+    pub fn new(kind: ChartKind, labels: ChartData<String>) -> Self {
+        Self {
+            kind,
+            title: None,
+            labels,
+            series: Vec::new(),
+            width: default_width(),
+            height: default_height(),
+        }
+    }
+
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    pub fn series(mut self, name: impl Into<String>, values: ChartData<f64>) -> Self {
+        self.series.push(ChartSeries { name: name.into(), values });
+        self
+    }
+
+    pub fn size(mut self, width: u32, height: u32) -> Self {
+        self.width = width;
+        self.height = height;
+        self
+    }
+
+    /// Draw the chart as an SVG document
+    pub fn render_svg(&self, data: &serde_json::Value) -> Result<String> {
+        let labels: Vec<String> = resolve(&self.labels, data, |value| match value {
+            serde_json::Value::String(s) => Some(s.clone()),
+            serde_json::Value::Number(n) => Some(n.to_string()),
+            _ => None,
+        })?;
+        let series = self
+            .series
+            .iter()
+            .map(|series| Ok((series.name.as_str(), resolve(&series.values, data, serde_json::Value::as_f64)?)))
+            .collect::<Result<Vec<_>>>()?;
+        if series.is_empty() {
+            return Err(PapermakeError::InvalidInput("Chart has no series".to_string()));
+        }
+        if let Some((name, values)) = series.iter().find(|(_, values)| values.len() != labels.len()) {
+            return Err(PapermakeError::InvalidInput(format!(
+                "Chart series '{}' has {} values for {} labels",
+                name,
+                values.len(),
+                labels.len()
+            )));
+        }
+
+        let mut svg = String::new();
+        {
+            let root = SVGBackend::with_string(&mut svg, (self.width, self.height)).into_drawing_area();
+            root.fill(&WHITE).map_err(draw_error)?;
+            let root = match &self.title {
+                Some(title) => root.titled(title, ("sans-serif", 22)).map_err(draw_error)?,
+                None => root,
+            };
+            match self.kind {
+                ChartKind::Bar => draw_bars(&root, &labels, &series)?,
+                ChartKind::Line => draw_lines(&root, &labels, &series)?,
+                ChartKind::Pie => draw_pie(&root, &labels, &series[0].1)?,
+            }
+            root.present().map_err(draw_error)?;
+        }
+        Ok(svg)
+    }
+}
+
+type Area<'a> = DrawingArea<SVGBackend<'a>, plotters::coord::Shift>;
+
+/// Largest value, with headroom; at least 1 so empty charts get an axis
+fn y_max(series: &[(&str, Vec<f64>)]) -> f64 {
+    let max = series.iter().flat_map(|(_, values)| values).copied().fold(0.0, f64::max);
+    if max > 0.0 { max * 1.1 } else { 1.0 }
+}
+
+fn y_min(series: &[(&str, Vec<f64>)]) -> f64 {
+    let min = series.iter().flat_map(|(_, values)| values).copied().fold(0.0, f64::min);
+    min * 1.1
+}
+
+fn draw_bars(root: &Area, labels: &[String], series: &[(&str, Vec<f64>)]) -> Result<()> {
+    let mut chart = ChartBuilder::on(root)
+        .margin(12)
+        .x_label_area_size(32)
+        .y_label_area_size(48)
+        .build_cartesian_2d((0..labels.len()).into_segmented(), y_min(series)..y_max(series))
+        .map_err(draw_error)?;
+    chart
+        .configure_mesh()
+        .disable_x_mesh()
+        .x_labels(labels.len())
+        .x_label_formatter(&|x| match x {
+            SegmentValue::CenterOf(i) => labels.get(*i).cloned().unwrap_or_default(),
+            _ => String::new(),
+        })
+        .draw()
+        .map_err(draw_error)?;
+
+    // Series are drawn side by side in the middle 80% of each category
+    let count = series.len() as u32;
+    let segment = chart.plotting_area().dim_in_pixel().0 / labels.len().max(1) as u32;
+    let step = segment * 8 / 10 / count;
+    for (index, (name, values)) in series.iter().enumerate() {
+        let color = PALETTE[index % PALETTE.len()];
+        chart
+            .draw_series(values.iter().enumerate().map(|(i, value)| {
+                let mut bar = Rectangle::new(
+                    [(SegmentValue::Exact(i), 0.0), (SegmentValue::Exact(i + 1), *value)],
+                    color.filled(),
+                );
+                let left = segment / 10 + step * index as u32;
+                bar.set_margin(0, 0, left, segment.saturating_sub(left + step));
+                bar
+            }))
+            .map_err(draw_error)?
+            .label(*name)
+            .legend(move |(x, y)| Rectangle::new([(x, y - 5), (x + 10, y + 5)], color.filled()));
+    }
+    draw_legend(&mut chart, series.len())
+}
+
+fn draw_lines(root: &Area, labels: &[String], series: &[(&str, Vec<f64>)]) -> Result<()> {
+    let last = labels.len().saturating_sub(1).max(1) as f64;
+    let mut chart = ChartBuilder::on(root)
+        .margin(12)
+        .x_label_area_size(32)
+        .y_label_area_size(48)
+        .build_cartesian_2d(0.0..last, y_min(series)..y_max(series))
+        .map_err(draw_error)?;
+    chart
+        .configure_mesh()
+        .x_labels(labels.len())
+        .x_label_formatter(&|x| {
+            let index = x.round();
+            if (x - index).abs() > 1e-6 || index < 0.0 {
+                return String::new();
+            }
+            labels.get(index as usize).cloned().unwrap_or_default()
+        })
+        .draw()
+        .map_err(draw_error)?;
+
+    for (index, (name, values)) in series.iter().enumerate() {
+        let color = PALETTE[index % PALETTE.len()];
+        chart
+            .draw_series(LineSeries::new(
+                values.iter().enumerate().map(|(i, value)| (i as f64, *value)),
+                color.stroke_width(2),
+            ))
+            .map_err(draw_error)?
+            .label(*name)
+            .legend(move |(x, y)| PathElement::new([(x, y), (x + 16, y)], color.stroke_width(2)));
+    }
+    draw_legend(&mut chart, series.len())
+}
+
+fn draw_legend<'a, X, Y>(chart: &mut ChartContext<'a, SVGBackend<'a>, Cartesian2d<X, Y>>, series: usize) -> Result<()>
+where
+    X: Ranged,
+    Y: Ranged,
+{
+    // A single series is described by the title
+    if series < 2 {
+        return Ok(());
+    }
+    chart
+        .configure_series_labels()
+        .background_style(WHITE.mix(0.8))
+        .border_style(BLACK)
+        .draw()
+        .map_err(draw_error)
+}
+
+fn draw_pie(root: &Area, labels: &[String], values: &[f64]) -> Result<()> {
+    if values.iter().any(|value| *value < 0.0) {
+        return Err(PapermakeError::InvalidInput("Pie charts need non-negative values".to_string()));
+    }
+    let (width, height) = root.dim_in_pixel();
+    let center = (width as i32 / 2, height as i32 / 2);
+    let radius = f64::from(width.min(height)) * 0.35;
+    let colors: Vec<RGBColor> = (0..values.len()).map(|i| PALETTE[i % PALETTE.len()]).collect();
+    let mut pie = Pie::new(&center, &radius, values, &colors, labels);
+    pie.label_style(("sans-serif", 16).into_font());
+    pie.percentages(("sans-serif", 14).into_font().color(&WHITE));
+    root.draw(&pie).map_err(draw_error)
+}
+
+/// Resolve inline values or a data path, converting each element
+fn resolve<T: Clone>(
+    source: &ChartData<T>,
+    data: &serde_json::Value,
+    convert: impl Fn(&serde_json::Value) -> Option<T>,
+) -> Result<Vec<T>> {
+    let path = match source {
+        ChartData::Values(values) => return Ok(values.clone()),
+        ChartData::Path(path) => path,
+    };
+    let mut values = Vec::new();
+    collect(data, path, &mut values);
+    values
+        .into_iter()
+        .map(|value| {
+            convert(value).ok_or_else(|| {
+                PapermakeError::InvalidInput(format!("Chart data at '{}' has an unsupported value: {}", path, value))
+            })
+        })
+        .collect()
+}
+
+/// Values at a pointer whose `*` segments map over arrays
+fn collect<'a>(data: &'a serde_json::Value, path: &str, out: &mut Vec<&'a serde_json::Value>) {
+    match path.split_once("/*") {
+        Some((array, rest)) => {
+            if let Some(items) = data.pointer(array).and_then(serde_json::Value::as_array) {
+                for item in items {
+                    collect(item, rest, out);
+                }
+            }
+        }
+        None => match data.pointer(path) {
+            Some(serde_json::Value::Array(items)) => out.extend(items),
+            Some(value) => out.push(value),
+            None => {}
+        },
+    }
+}
+
+/// Draw every chart, keyed by the file name templates load it with
+pub fn render_charts(charts: &BTreeMap<String, ChartSpec>, data: &serde_json::Value) -> Result<HashMap<String, Vec<u8>>> {
+    charts
+        .iter()
+        .map(|(name, spec)| {
+            let svg = spec.render_svg(data).map_err(|e| match e {
+                PapermakeError::InvalidInput(message) => {
+                    PapermakeError::InvalidInput(format!("Chart '{}': {}", name, message))
+                }
+                e => e,
+            })?;
+            Ok((format!("{}.svg", name), svg.into_bytes()))
+        })
+        .collect()
+}
+
+fn draw_error<E: std::error::Error + Send + Sync>(err: DrawingAreaErrorKind<E>) -> PapermakeError {
+    PapermakeError::Rendering(format!("Failed to draw chart: {}", err))
+}
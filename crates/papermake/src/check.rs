@@ -0,0 +1,48 @@
+//! Dry-run compilation with placeholder data
+//!
+//! [`Template::check`] compiles a template with data generated from its
+//! schema and reports the diagnostics, without exporting a PDF. Editors can
+//! run it after every change to see whether the template still compiles,
+//! without a realistic payload at hand.
+
+use serde::Serialize;
+
+use crate::error::Result;
+use crate::render::{compile_template, RenderError, RenderOptions};
+use crate::sample::SampleOptions;
+use crate::template::Template;
+
+/// Diagnostics of a dry-run compilation
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckReport {
+    /// Whether the template compiled
+    pub ok: bool,
+    pub errors: Vec<RenderError>,
+    pub warnings: Vec<RenderError>,
+    /// Pages of the compiled document; 0 if compilation failed
+    pub pages: usize,
+}
+
+impl Template {
+    /// Compile with placeholder data and default options, reporting diagnostics only
+    pub fn check(&self) -> Result<CheckReport> {
+        self.check_with(&RenderOptions::default())
+    }
+
+    /// Compile with placeholder data, reporting diagnostics only
+    ///
+    /// Options supply what the template needs besides data, such as shared
+    /// sources and the locale. Optional fields are filled in, so code paths
+    /// using them are checked too.
+    pub fn check_with(&self, options: &RenderOptions) -> Result<CheckReport> {
+        let sample = SampleOptions { array_len: 1, include_optional: true };
+        let data = self.schema.generate_sample_data_with(0, &sample);
+        let compiled = compile_template(self, &data, None, options)?;
+        Ok(CheckReport {
+            ok: compiled.document.is_some(),
+            pages: compiled.document.map_or(0, |document| document.pages.len()),
+            errors: compiled.errors,
+            warnings: compiled.warnings,
+        })
+    }
+}
@@ -0,0 +1,150 @@
+//! Compatibility of schema changes for existing callers
+//!
+//! A schema change is backward-compatible if every payload valid under the
+//! old schema stays valid under the new one: optional fields may be added
+//! and required fields relaxed. Removing fields, requiring new data and
+//! changing types break callers.
+
+use serde::Serialize;
+
+use crate::schema::{FieldType, Schema, SchemaField};
+
+/// Kind of difference between two schemas
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SchemaChangeKind {
+    FieldAdded,
+    FieldRemoved,
+    TypeChanged,
+    BecameRequired,
+    BecameOptional,
+}
+
+/// A single field-level difference
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SchemaChange {
+    pub kind: SchemaChangeKind,
+    /// Dotted field path, e.g. `customer.name`; array items add `[]`
+    pub path: String,
+    /// Whether payloads valid under the old schema may fail under the new one
+    pub breaking: bool,
+    pub message: String,
+}
+
+/// All differences between an old and a new schema
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct CompatibilityReport {
+    pub changes: Vec<SchemaChange>,
+}
+
+impl CompatibilityReport {
+    /// Whether existing callers keep working
+    pub fn is_compatible(&self) -> bool {
+        !self.changes.iter().any(|change| change.breaking)
+    }
+
+    pub fn breaking_changes(&self) -> impl Iterator<Item = &SchemaChange> {
+        self.changes.iter().filter(|change| change.breaking)
+    }
+}
+
+impl Schema {
+    /// Classify the changes from `old` to `new`
+    pub fn compatibility(old: &Schema, new: &Schema) -> CompatibilityReport {
+        let mut report = CompatibilityReport::default();
+        compare_fields(&old.fields, &new.fields, "", &mut report.changes);
+        report
+    }
+}
+
+fn compare_fields(old: &[SchemaField], new: &[SchemaField], prefix: &str, changes: &mut Vec<SchemaChange>) {
+    for old_field in old {
+        let path = format!("{}{}", prefix, old_field.key);
+        match new.iter().find(|field| field.key == old_field.key) {
+            None => changes.push(SchemaChange {
+                kind: SchemaChangeKind::FieldRemoved,
+                message: format!("Field '{}' was removed", path),
+                path,
+                breaking: true,
+            }),
+            Some(new_field) => {
+                if !old_field.required && new_field.required && new_field.default.is_none() {
+                    changes.push(SchemaChange {
+                        kind: SchemaChangeKind::BecameRequired,
+                        message: format!("Field '{}' is now required", path),
+                        path: path.clone(),
+                        breaking: true,
+                    });
+                } else if old_field.required && !new_field.required {
+                    changes.push(SchemaChange {
+                        kind: SchemaChangeKind::BecameOptional,
+                        message: format!("Field '{}' is now optional", path),
+                        path: path.clone(),
+                        breaking: false,
+                    });
+                }
+                compare_types(&old_field.field_type, &new_field.field_type, &path, changes);
+            }
+        }
+    }
+
+    for new_field in new {
+        if old.iter().any(|field| field.key == new_field.key) {
+            continue;
+        }
+        let path = format!("{}{}", prefix, new_field.key);
+        // Defaults fill in required fields that callers don't send yet
+        let breaking = new_field.required && new_field.default.is_none();
+        changes.push(SchemaChange {
+            kind: SchemaChangeKind::FieldAdded,
+            message: if breaking {
+                format!("Required field '{}' was added", path)
+            } else {
+                format!("Field '{}' was added", path)
+            },
+            path,
+            breaking,
+        });
+    }
+}
+
+fn compare_types(old: &FieldType, new: &FieldType, path: &str, changes: &mut Vec<SchemaChange>) {
+    match (old, new) {
+        (FieldType::Object(old), FieldType::Object(new)) => {
+            compare_fields(&old.fields, &new.fields, &format!("{}.", path), changes);
+        }
+        (FieldType::Array(old), FieldType::Array(new)) => {
+            compare_types(old, new, &format!("{}[]", path), changes);
+        }
+        (FieldType::Barcode(old), FieldType::Barcode(new)) if old != new => {
+            changes.push(SchemaChange {
+                kind: SchemaChangeKind::TypeChanged,
+                path: path.to_string(),
+                breaking: true,
+                message: format!("Field '{}' changed barcode type from {:?} to {:?}", path, old, new),
+            });
+        }
+        (old, new) if std::mem::discriminant(old) != std::mem::discriminant(new) => {
+            changes.push(SchemaChange {
+                kind: SchemaChangeKind::TypeChanged,
+                path: path.to_string(),
+                breaking: true,
+                message: format!("Field '{}' changed type from {} to {}", path, type_name(old), type_name(new)),
+            });
+        }
+        _ => {}
+    }
+}
+
+pub(crate) fn type_name(field_type: &FieldType) -> &'static str {
+    match field_type {
+        FieldType::String => "string",
+        FieldType::Number => "number",
+        FieldType::Boolean => "boolean",
+        FieldType::Date => "date",
+        FieldType::Barcode(_) => "barcode",
+        FieldType::Section => "section",
+        FieldType::Object(_) => "object",
+        FieldType::Array(_) => "array",
+    }
+}
@@ -0,0 +1,103 @@
+//! Typed data binding: schemas derived from Rust types
+//!
+//! With the `derive` feature, `#[derive(PapermakeData)]` implements these
+//! traits for structs so that a template's schema and the data passed to it
+//! come from the same type definition.
+
+use std::collections::{BTreeSet, HashSet, VecDeque};
+
+use serde::Serialize;
+
+use crate::error::{PapermakeError, Result};
+use crate::render::{render_pdf, RenderOptions, RenderResult};
+use crate::schema::{FieldType, Schema};
+use crate::template::Template;
+
+/// A data type that can be rendered by a template
+pub trait PapermakeData: Serialize {
+    /// The schema describing the serialized form of this type
+    fn schema() -> Schema;
+}
+
+/// A type usable as a schema field
+pub trait SchemaType {
+    /// Whether the field must be present in the data
+    const REQUIRED: bool = true;
+
+    /// The schema field type of this Rust type
+    fn field_type() -> FieldType;
+}
+
+macro_rules! impl_schema_type {
+    ($field_type:ident: $($ty:ty),*) => {
+        $(
+            impl SchemaType for $ty {
+                fn field_type() -> FieldType {
+                    FieldType::$field_type
+                }
+            }
+        )*
+    };
+}
+
+impl_schema_type!(String: String, str, char);
+impl_schema_type!(Number: i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize, f32, f64);
+impl_schema_type!(Boolean: bool);
+
+impl<T: SchemaType + ?Sized> SchemaType for &T {
+    const REQUIRED: bool = T::REQUIRED;
+
+    fn field_type() -> FieldType {
+        T::field_type()
+    }
+}
+
+impl<T: SchemaType + ?Sized> SchemaType for Box<T> {
+    const REQUIRED: bool = T::REQUIRED;
+
+    fn field_type() -> FieldType {
+        T::field_type()
+    }
+}
+
+impl<T: SchemaType> SchemaType for Option<T> {
+    const REQUIRED: bool = false;
+
+    fn field_type() -> FieldType {
+        T::field_type()
+    }
+}
+
+macro_rules! impl_schema_type_array {
+    ($($ty:ty),*) => {
+        $(
+            impl<T: SchemaType> SchemaType for $ty {
+                fn field_type() -> FieldType {
+                    FieldType::Array(Box::new(T::field_type()))
+                }
+            }
+        )*
+    };
+}
+
+impl_schema_type_array!(Vec<T>, [T], VecDeque<T>, BTreeSet<T>, HashSet<T>);
+
+impl<T: SchemaType, const N: usize> SchemaType for [T; N] {
+    fn field_type() -> FieldType {
+        FieldType::Array(Box::new(T::field_type()))
+    }
+}
+
+/// Render a template with typed data
+///
+/// The data is serialized with serde and validated against the template's
+/// schema like any other payload.
+pub fn render_pdf_typed<T: PapermakeData>(
+    template: &Template,
+    data: &T,
+    options: Option<RenderOptions>,
+) -> Result<RenderResult> {
+    let value = serde_json::to_value(data)
+        .map_err(|e| PapermakeError::InvalidInput(format!("Failed to serialize data: {}", e)))?;
+    render_pdf(template, &value, options)
+}
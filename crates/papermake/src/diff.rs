@@ -0,0 +1,250 @@
+//! Visual comparison of rendered documents
+//!
+//! Two renders are compared page by page: pages are rasterized and their
+//! pixels compared, so a change in a template shows up as the region of
+//! the page that looks different. Already exported PDFs, e.g. a stored
+//! baseline, are compared structurally instead (page count and page
+//! content streams), as papermake only rasterizes documents it compiles.
+
+use serde::Serialize;
+use typst::layout::{Page, PagedDocument};
+
+use crate::error::{PapermakeError, Result};
+use crate::render::{compile_template, RenderOptions};
+use crate::template::Template;
+
+/// Settings for rasterizing and comparing pages
+#[derive(Debug, Clone)]
+pub struct DiffOptions {
+    /// Rasterization resolution in pixels per point
+    pub pixel_per_pt: f32,
+    /// Largest per-channel difference still treated as equal
+    pub tolerance: u8,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        Self {
+            pixel_per_pt: 1.0,
+            tolerance: 0,
+        }
+    }
+}
+
+/// How a page differs between two documents
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PageStatus {
+    Unchanged,
+    Changed,
+    /// Only in the second document
+    Added,
+    /// Only in the first document
+    Removed,
+}
+
+/// Pixel rectangle enclosing all differences on a page
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct DiffBounds {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Comparison of one page
+#[derive(Debug, Clone, Serialize)]
+pub struct PageDiff {
+    /// 1-based page number
+    pub page: usize,
+    pub status: PageStatus,
+    /// Whether the page dimensions differ
+    pub size_changed: bool,
+    /// Number of differing pixels; 0 for structural comparisons
+    pub changed_pixels: u64,
+    /// Number of compared pixels; 0 for structural comparisons
+    pub total_pixels: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bounds: Option<DiffBounds>,
+}
+
+impl PageDiff {
+    /// Share of pixels that differ, from 0.0 to 1.0
+    pub fn changed_ratio(&self) -> f64 {
+        if self.total_pixels == 0 {
+            return if self.status == PageStatus::Unchanged { 0.0 } else { 1.0 };
+        }
+        self.changed_pixels as f64 / self.total_pixels as f64
+    }
+
+    fn unpaired(page: usize, status: PageStatus) -> Self {
+        Self {
+            page,
+            status,
+            size_changed: true,
+            changed_pixels: 0,
+            total_pixels: 0,
+            bounds: None,
+        }
+    }
+}
+
+/// Page-by-page comparison of two documents
+#[derive(Debug, Clone, Serialize)]
+pub struct DiffReport {
+    pub pages: Vec<PageDiff>,
+}
+
+impl DiffReport {
+    /// Whether every page is unchanged
+    pub fn is_identical(&self) -> bool {
+        self.pages.iter().all(|page| page.status == PageStatus::Unchanged)
+    }
+
+    /// Pages that were changed, added or removed
+    pub fn changed_pages(&self) -> impl Iterator<Item = &PageDiff> {
+        self.pages.iter().filter(|page| page.status != PageStatus::Unchanged)
+    }
+}
+
+/// Render `before` and `after` with the same data and compare them visually
+pub fn diff_templates(
+    before: &Template,
+    after: &Template,
+    data: &serde_json::Value,
+    render_options: &RenderOptions,
+    options: &DiffOptions,
+) -> Result<DiffReport> {
+    let before = compile_document(before, data, render_options)?;
+    let after = compile_document(after, data, render_options)?;
+    Ok(diff_documents(&before, &after, options))
+}
+
+/// Compare two compiled documents by rasterizing their pages
+pub fn diff_documents(before: &PagedDocument, after: &PagedDocument, options: &DiffOptions) -> DiffReport {
+    let count = before.pages.len().max(after.pages.len());
+    let pages = (0..count)
+        .map(|index| match (before.pages.get(index), after.pages.get(index)) {
+            (Some(a), Some(b)) => diff_page(index + 1, a, b, options),
+            (Some(_), None) => PageDiff::unpaired(index + 1, PageStatus::Removed),
+            _ => PageDiff::unpaired(index + 1, PageStatus::Added),
+        })
+        .collect();
+    DiffReport { pages }
+}
+
+/// Compare two PDFs structurally: page count and each page's content stream
+pub fn diff_pdfs(before: &[u8], after: &[u8]) -> Result<DiffReport> {
+    let before = PdfPages::load(before)?;
+    let after = PdfPages::load(after)?;
+
+    let count = before.0.len().max(after.0.len());
+    let pages = (0..count)
+        .map(|index| match (before.0.get(index), after.0.get(index)) {
+            (Some(a), Some(b)) => {
+                let size_changed = a.media_box != b.media_box;
+                let changed = size_changed || a.content != b.content;
+                PageDiff {
+                    page: index + 1,
+                    status: if changed { PageStatus::Changed } else { PageStatus::Unchanged },
+                    size_changed,
+                    changed_pixels: 0,
+                    total_pixels: 0,
+                    bounds: None,
+                }
+            }
+            (Some(_), None) => PageDiff::unpaired(index + 1, PageStatus::Removed),
+            _ => PageDiff::unpaired(index + 1, PageStatus::Added),
+        })
+        .collect();
+    Ok(DiffReport { pages })
+}
+
+/// Compile a template for comparison with [`diff_documents`]; compile
+/// errors are returned as [`PapermakeError::Compile`]
+pub fn compile_document(template: &Template, data: &serde_json::Value, options: &RenderOptions) -> Result<PagedDocument> {
+    let compiled = compile_template(template, data, None, options)?;
+    compiled.document.ok_or_else(|| PapermakeError::Compile {
+        template: template.id.0.clone(),
+        errors: compiled.errors,
+    })
+}
+
+fn diff_page(number: usize, before: &Page, after: &Page, options: &DiffOptions) -> PageDiff {
+    let a = typst_render::render(before, options.pixel_per_pt);
+    let b = typst_render::render(after, options.pixel_per_pt);
+
+    // Pages of different size are compared over their common area
+    let size_changed = (a.width(), a.height()) != (b.width(), b.height());
+    let width = a.width().min(b.width());
+    let height = a.height().min(b.height());
+
+    let mut changed_pixels = 0;
+    let mut bounds: Option<(u32, u32, u32, u32)> = None;
+    for y in 0..height {
+        for x in 0..width {
+            let pa = pixel(a.data(), a.width(), x, y);
+            let pb = pixel(b.data(), b.width(), x, y);
+            let differs = pa.iter().zip(pb).any(|(ca, cb)| ca.abs_diff(*cb) > options.tolerance);
+            if differs {
+                changed_pixels += 1;
+                bounds = Some(match bounds {
+                    Some((x0, y0, x1, y1)) => (x0.min(x), y0.min(y), x1.max(x), y1.max(y)),
+                    None => (x, y, x, y),
+                });
+            }
+        }
+    }
+
+    let changed = size_changed || changed_pixels > 0;
+    PageDiff {
+        page: number,
+        status: if changed { PageStatus::Changed } else { PageStatus::Unchanged },
+        size_changed,
+        changed_pixels,
+        total_pixels: width as u64 * height as u64,
+        bounds: bounds.map(|(x0, y0, x1, y1)| DiffBounds {
+            x: x0,
+            y: y0,
+            width: x1 - x0 + 1,
+            height: y1 - y0 + 1,
+        }),
+    }
+}
+
+/// RGBA channels of a pixel
+fn pixel(data: &[u8], width: u32, x: u32, y: u32) -> &[u8] {
+    let offset = ((y * width + x) * 4) as usize;
+    &data[offset..offset + 4]
+}
+
+/// Media box and decoded content of each page of a PDF
+struct PdfPages(Vec<PdfPage>);
+
+struct PdfPage {
+    media_box: Option<Vec<f32>>,
+    content: Vec<u8>,
+}
+
+impl PdfPages {
+    fn load(pdf: &[u8]) -> Result<Self> {
+        let pdf_error = |e: lopdf::Error| PapermakeError::InvalidInput(format!("Failed to read PDF: {}", e));
+        let doc = lopdf::Document::load_mem(pdf).map_err(pdf_error)?;
+
+        let pages = doc
+            .get_pages()
+            .into_values()
+            .map(|page_id| {
+                let media_box = doc
+                    .get_dictionary(page_id)
+                    .and_then(|page| page.get(b"MediaBox"))
+                    .and_then(|object| object.as_array())
+                    .ok()
+                    .map(|values| values.iter().filter_map(|v| v.as_float().ok()).collect());
+                let content = doc.get_page_content(page_id).map_err(pdf_error)?;
+                Ok(PdfPage { media_box, content })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self(pages))
+    }
+}
@@ -0,0 +1,256 @@
+//! Encryption at rest for stored templates, assets and cached renders
+//!
+//! [`EncryptedStorage`] wraps any [`Storage`] and encrypts template sources,
+//! locale variants, scripts, examples and template files with AES-256-GCM
+//! before they reach the inner backend, so a shared disk or bucket never
+//! holds them in the clear. Names, tags, metadata and schemas stay readable
+//! so backends can still list and filter templates. [`EncryptedRenderCache`]
+//! does the same for cached PDFs.
+//!
+//! The 32-byte key comes from the deployment, e.g. an environment variable
+//! or a data key decrypted by a KMS at startup. Data stored before
+//! encryption was enabled is still read as plaintext and encrypted on its
+//! next write, so existing deployments can switch over in place.
+
+use std::fmt;
+use std::sync::Arc;
+
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use async_trait::async_trait;
+use base64::{prelude::BASE64_STANDARD, Engine};
+use time::OffsetDateTime;
+
+use crate::error::{PapermakeError, Result};
+use crate::render_cache::{RenderCache, RenderCacheKey};
+use crate::storage::{ArtifactKind, ListOptions, Namespace, PurgeReport, Storage, TemplatePage};
+use crate::template::{Template, TemplateId};
+
+/// Leading bytes of encrypted files
+const MAGIC: &[u8] = b"PMENC\x01";
+
+/// Prefix of encrypted strings inside template JSON
+const STRING_PREFIX: &str = "pmenc:v1:";
+
+const NONCE_LEN: usize = 12;
+
+/// AES-256-GCM key encrypting stored data
+#[derive(Clone)]
+pub struct EncryptionKey {
+    cipher: Aes256Gcm,
+}
+
+impl EncryptionKey {
+    pub fn from_bytes(key: [u8; 32]) -> Self {
+        Self {
+            cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key)),
+        }
+    }
+
+    /// Key from 32 base64-encoded bytes, e.g. generated with `openssl rand -base64 32`
+    pub fn from_base64(key: &str) -> Result<Self> {
+        let invalid = || PapermakeError::InvalidInput("Encryption key must be 32 base64-encoded bytes".to_string());
+        let bytes = BASE64_STANDARD.decode(key.trim()).map_err(|_| invalid())?;
+        let key: [u8; 32] = bytes.try_into().map_err(|_| invalid())?;
+        Ok(Self::from_bytes(key))
+    }
+
+    /// Encrypt bytes under a fresh random nonce
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, plaintext)
+            .map_err(|_| PapermakeError::Storage("Failed to encrypt data".to_string()))?;
+        let mut sealed = Vec::with_capacity(MAGIC.len() + NONCE_LEN + ciphertext.len());
+        sealed.extend_from_slice(MAGIC);
+        sealed.extend_from_slice(&nonce);
+        sealed.extend_from_slice(&ciphertext);
+        Ok(sealed)
+    }
+
+    /// Decrypt bytes written by [`encrypt`](Self::encrypt); data without the
+    /// encryption header is returned as is
+    pub fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>> {
+        let Some(sealed) = data.strip_prefix(MAGIC) else {
+            return Ok(data.to_vec());
+        };
+        if sealed.len() < NONCE_LEN {
+            return Err(decryption_failed());
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        self.cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| decryption_failed())
+    }
+
+    fn encrypt_str(&self, plaintext: &str) -> Result<String> {
+        let sealed = self.encrypt(plaintext.as_bytes())?;
+        Ok(format!("{}{}", STRING_PREFIX, BASE64_STANDARD.encode(&sealed[MAGIC.len()..])))
+    }
+
+    fn decrypt_str(&self, value: &str) -> Result<String> {
+        let Some(encoded) = value.strip_prefix(STRING_PREFIX) else {
+            return Ok(value.to_string());
+        };
+        let mut sealed = MAGIC.to_vec();
+        sealed.extend(BASE64_STANDARD.decode(encoded).map_err(|_| decryption_failed())?);
+        String::from_utf8(self.decrypt(&sealed)?).map_err(|_| decryption_failed())
+    }
+
+    /// Copy of a template with its confidential fields encrypted
+    fn encrypt_template(&self, template: &Template) -> Result<Template> {
+        let mut sealed = template.clone();
+        sealed.content = self.encrypt_str(&template.content)?;
+        for content in sealed.variants.values_mut() {
+            *content = self.encrypt_str(content)?;
+        }
+        if let Some(script) = &mut sealed.script {
+            *script = self.encrypt_str(script)?;
+        }
+        for example in sealed.examples.values_mut() {
+            *example = serde_json::Value::String(self.encrypt_str(&example.to_string())?);
+        }
+        Ok(sealed)
+    }
+
+    fn decrypt_template(&self, mut template: Template) -> Result<Template> {
+        template.content = self.decrypt_str(&template.content)?;
+        for content in template.variants.values_mut() {
+            *content = self.decrypt_str(content)?;
+        }
+        if let Some(script) = &mut template.script {
+            *script = self.decrypt_str(script)?;
+        }
+        for example in template.examples.values_mut() {
+            if let serde_json::Value::String(sealed) = example {
+                if sealed.starts_with(STRING_PREFIX) {
+                    *example = serde_json::from_str(&self.decrypt_str(sealed)?).map_err(|_| decryption_failed())?;
+                }
+            }
+        }
+        Ok(template)
+    }
+}
+
+impl fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("EncryptionKey(..)")
+    }
+}
+
+fn decryption_failed() -> PapermakeError {
+    PapermakeError::Storage("Failed to decrypt stored data; is the encryption key correct?".to_string())
+}
+
+/// Storage encrypting templates and their files before delegating to `S`
+pub struct EncryptedStorage<S: Storage + ?Sized> {
+    inner: Arc<S>,
+    key: EncryptionKey,
+}
+
+impl<S: Storage + ?Sized> EncryptedStorage<S> {
+    pub fn new(inner: Arc<S>, key: EncryptionKey) -> Self {
+        Self { inner, key }
+    }
+}
+
+#[async_trait]
+impl<S: Storage + ?Sized + 'static> Storage for EncryptedStorage<S> {
+    async fn save_template(&self, template: &Template) -> Result<()> {
+        self.inner.save_template(&self.key.encrypt_template(template)?).await
+    }
+
+    async fn get_template(&self, id: &TemplateId) -> Result<Template> {
+        self.key.decrypt_template(self.inner.get_template(id).await?)
+    }
+
+    async fn list_templates(&self, options: &ListOptions) -> Result<TemplatePage> {
+        let page = self.inner.list_templates(options).await?;
+        let templates = page
+            .templates
+            .into_iter()
+            .map(|template| self.key.decrypt_template(template))
+            .collect::<Result<_>>()?;
+        Ok(TemplatePage { templates, next_cursor: page.next_cursor })
+    }
+
+    // Searching and dependents use the default implementations, which see
+    // the decrypted templates; an index of the inner backend would only
+    // see ciphertext
+
+    async fn save_published_template(&self, template: &Template) -> Result<()> {
+        self.inner.save_published_template(&self.key.encrypt_template(template)?).await
+    }
+
+    async fn get_published_template(&self, id: &TemplateId) -> Result<Template> {
+        self.key.decrypt_template(self.inner.get_published_template(id).await?)
+    }
+
+    async fn delete_template(&self, id: &TemplateId) -> Result<()> {
+        self.inner.delete_template(id).await
+    }
+
+    async fn copy_template(&self, id: &TemplateId, new_id: &TemplateId) -> Result<Template> {
+        let copy = self.inner.copy_template(id, new_id).await?;
+        self.key.decrypt_template(copy)
+    }
+
+    async fn save_template_file(&self, id: &TemplateId, path: &str, content: &[u8]) -> Result<()> {
+        self.inner.save_template_file(id, path, &self.key.encrypt(content)?).await
+    }
+
+    async fn get_template_file(&self, id: &TemplateId, path: &str) -> Result<Vec<u8>> {
+        self.key.decrypt(&self.inner.get_template_file(id, path).await?)
+    }
+
+    async fn list_template_files(&self, id: &TemplateId) -> Result<Vec<String>> {
+        self.inner.list_template_files(id).await
+    }
+
+    async fn delete_template_file(&self, id: &TemplateId, path: &str) -> Result<()> {
+        self.inner.delete_template_file(id, path).await
+    }
+
+    async fn rename_template_file(&self, id: &TemplateId, from: &str, to: &str) -> Result<()> {
+        self.inner.rename_template_file(id, from, to).await
+    }
+
+    async fn purge(&self, before: OffsetDateTime, kinds: &[ArtifactKind]) -> Result<PurgeReport> {
+        self.inner.purge(before, kinds).await
+    }
+
+    async fn check_health(&self) -> Result<()> {
+        self.inner.check_health().await
+    }
+
+    fn for_namespace(&self, namespace: &Namespace) -> Arc<dyn Storage> {
+        Arc::new(EncryptedStorage::new(self.inner.for_namespace(namespace), self.key.clone()))
+    }
+}
+
+/// Render cache encrypting PDFs before delegating to `C`
+#[derive(Debug)]
+pub struct EncryptedRenderCache<C: RenderCache + ?Sized> {
+    inner: Arc<C>,
+    key: EncryptionKey,
+}
+
+impl<C: RenderCache + ?Sized> EncryptedRenderCache<C> {
+    pub fn new(inner: Arc<C>, key: EncryptionKey) -> Self {
+        Self { inner, key }
+    }
+}
+
+impl<C: RenderCache + ?Sized> RenderCache for EncryptedRenderCache<C> {
+    /// Entries that fail to decrypt are misses
+    fn get(&self, key: &RenderCacheKey) -> Option<Vec<u8>> {
+        self.key.decrypt(&self.inner.get(key)?).ok()
+    }
+
+    fn put(&self, key: &RenderCacheKey, pdf: &[u8]) {
+        if let Ok(sealed) = self.key.encrypt(pdf) {
+            self.inner.put(key, &sealed);
+        }
+    }
+}
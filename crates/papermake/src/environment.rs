@@ -0,0 +1,88 @@
+//! Per-environment template settings (e.g. dev, staging, prod)
+//!
+//! Templates carry settings per deployment environment in
+//! [`Template::environments`](crate::Template::environments). A render
+//! selects one with `RenderOptions::environment`; its settings are merged
+//! over the template's [`DEFAULT_ENVIRONMENT`] entry and exposed to the
+//! template as `sys.inputs.environment`, together with the environment's
+//! name:
+//!
+//! ```typst
+//! #let env = sys.inputs.environment
+//! #image(env.asset_base_url + "/logo.svg")
+//! #if env.watermark != none { place(center + horizon, text(48pt, env.watermark)) }
+//! ```
+//!
+//! Renders selecting an environment the template has no entry for use the
+//! default entry alone, so a deployment can select its environment for
+//! every template.
+
+use serde::{Deserialize, Serialize};
+use typst::foundations::{Array, Dict, IntoValue, Str, Value};
+
+/// Entry of `Template::environments` applying to every environment
+pub const DEFAULT_ENVIRONMENT: &str = "default";
+
+/// Settings of a template in one environment; unset settings fall back to
+/// the default entry
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EnvironmentConfig {
+    /// Base URL of images and other assets, e.g. a staging CDN
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub asset_base_url: Option<String>,
+    /// Text marking renders, e.g. `DRAFT` outside production; stamped on
+    /// every page unless `RenderOptions::watermark` is set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub watermark: Option<String>,
+    /// Any other settings, e.g. `"sign": false`
+    #[serde(flatten)]
+    pub settings: serde_json::Map<String, serde_json::Value>,
+}
+
+impl EnvironmentConfig {
+    /// These settings overridden by those set in `other`
+    pub fn merged(&self, other: &EnvironmentConfig) -> EnvironmentConfig {
+        let mut settings = self.settings.clone();
+        settings.extend(other.settings.iter().map(|(key, value)| (key.clone(), value.clone())));
+        EnvironmentConfig {
+            asset_base_url: other.asset_base_url.clone().or_else(|| self.asset_base_url.clone()),
+            watermark: other.watermark.clone().or_else(|| self.watermark.clone()),
+            settings,
+        }
+    }
+}
+
+/// Entry added to `sys.inputs` for the selected environment
+pub(crate) fn environment_inputs(name: Option<&str>, config: &EnvironmentConfig) -> Dict {
+    let mut environment = Dict::new();
+    for (key, value) in &config.settings {
+        environment.insert(key.as_str().into(), json_value(value));
+    }
+    environment.insert("name".into(), name.map(Str::from).into_value());
+    environment.insert("asset_base_url".into(), config.asset_base_url.as_deref().map(Str::from).into_value());
+    environment.insert("watermark".into(), config.watermark.as_deref().map(Str::from).into_value());
+
+    let mut inputs = Dict::new();
+    inputs.insert("environment".into(), environment.into_value());
+    inputs
+}
+
+/// A JSON value as a Typst value
+pub(crate) fn json_value(value: &serde_json::Value) -> Value {
+    match value {
+        serde_json::Value::Null => Value::None,
+        serde_json::Value::Bool(b) => b.into_value(),
+        serde_json::Value::Number(n) => match n.as_i64() {
+            Some(i) => i.into_value(),
+            None => n.as_f64().unwrap_or_default().into_value(),
+        },
+        serde_json::Value::String(s) => Str::from(s.as_str()).into_value(),
+        serde_json::Value::Array(items) => items.iter().map(json_value).collect::<Array>().into_value(),
+        serde_json::Value::Object(map) => map
+            .iter()
+            .map(|(key, value)| (key.as_str().into(), json_value(value)))
+            .collect::<Dict>()
+            .into_value(),
+    }
+}
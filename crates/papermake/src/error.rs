@@ -1,28 +1,144 @@
 //! Error types for the papermake library
+//!
+//! Every error has a stable, machine-readable [`ErrorCode`] for clients
+//! that need to react to specific failures, e.g. the server's JSON error
+//! responses and HTTP statuses.
 
+use std::fmt;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use serde::Serialize;
 use thiserror::Error;
 
+use crate::render::RenderError;
+
 /// Main error type for the papermake library
 #[derive(Error, Debug)]
 pub enum PapermakeError {
     #[error("Template error: {0}")]
     Template(String),
-    
+
+    #[error("Template not found: {id}")]
+    TemplateNotFound { id: String },
+
+    /// A resource other than a template, e.g. a template file or render record
+    #[error("{kind} not found: {id}")]
+    NotFound { kind: &'static str, id: String },
+
     #[error("Schema validation error: {0}")]
     SchemaValidation(String),
-    
+
+    /// Typst reported errors where a compiled document was required
+    #[error("Template '{template}' failed to compile: {}", compile_messages(.errors))]
+    Compile { template: String, errors: Vec<RenderError> },
+
     #[error("Rendering error: {0}")]
     Rendering(String),
-    
+
+    #[error("{operation} timed out after {}s", .after.as_secs_f64())]
+    Timeout { operation: String, after: Duration },
+
     #[error("Storage error: {0}")]
     Storage(String),
-    
+
+    /// An I/O error while accessing a stored file
+    #[error("I/O error on {}: {source}", .path.display())]
+    StorageIo {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
     #[error("I/O error: {0}")]
     Io(#[from] std::io::Error),
-    
+
     #[error("Invalid input: {0}")]
     InvalidInput(String),
+
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
+    #[error("Too large: {0}")]
+    TooLarge(String),
+}
+
+/// Stable machine-readable code of an error
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    TemplateInvalid,
+    TemplateNotFound,
+    NotFound,
+    SchemaValidation,
+    CompileError,
+    RenderError,
+    Timeout,
+    StorageIo,
+    InvalidInput,
+    Conflict,
+    TooLarge,
+}
+
+impl ErrorCode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCode::TemplateInvalid => "TEMPLATE_INVALID",
+            ErrorCode::TemplateNotFound => "TEMPLATE_NOT_FOUND",
+            ErrorCode::NotFound => "NOT_FOUND",
+            ErrorCode::SchemaValidation => "SCHEMA_VALIDATION",
+            ErrorCode::CompileError => "COMPILE_ERROR",
+            ErrorCode::RenderError => "RENDER_ERROR",
+            ErrorCode::Timeout => "TIMEOUT",
+            ErrorCode::StorageIo => "STORAGE_IO",
+            ErrorCode::InvalidInput => "INVALID_INPUT",
+            ErrorCode::Conflict => "CONFLICT",
+            ErrorCode::TooLarge => "TOO_LARGE",
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl PapermakeError {
+    /// The error's stable code
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            PapermakeError::Template(_) => ErrorCode::TemplateInvalid,
+            PapermakeError::TemplateNotFound { .. } => ErrorCode::TemplateNotFound,
+            PapermakeError::NotFound { .. } => ErrorCode::NotFound,
+            PapermakeError::SchemaValidation(_) => ErrorCode::SchemaValidation,
+            PapermakeError::Compile { .. } => ErrorCode::CompileError,
+            PapermakeError::Rendering(_) => ErrorCode::RenderError,
+            PapermakeError::Timeout { .. } => ErrorCode::Timeout,
+            PapermakeError::Storage(_) | PapermakeError::StorageIo { .. } | PapermakeError::Io(_) => ErrorCode::StorageIo,
+            PapermakeError::InvalidInput(_) => ErrorCode::InvalidInput,
+            PapermakeError::Conflict(_) => ErrorCode::Conflict,
+            PapermakeError::TooLarge(_) => ErrorCode::TooLarge,
+        }
+    }
+
+    pub fn template_not_found(id: impl AsRef<str>) -> Self {
+        PapermakeError::TemplateNotFound { id: id.as_ref().to_string() }
+    }
+
+    pub fn not_found(kind: &'static str, id: impl Into<String>) -> Self {
+        PapermakeError::NotFound { kind, id: id.into() }
+    }
+
+    /// An I/O error on `path`, keeping the original error as the source
+    pub fn storage_io(path: impl Into<PathBuf>, source: std::io::Error) -> Self {
+        PapermakeError::StorageIo { path: path.into(), source }
+    }
+}
+
+fn compile_messages(errors: &[RenderError]) -> String {
+    errors.iter().map(|e| e.message.as_str()).collect::<Vec<_>>().join("; ")
 }
 
 /// Shorthand result type for papermake operations
-pub type Result<T> = std::result::Result<T, PapermakeError>;
\ No newline at end of file
+pub type Result<T> = std::result::Result<T, PapermakeError>;
@@ -0,0 +1,305 @@
+//! Fault injection for testing code built on [`Storage`]
+//!
+//! [`FaultyStorage`] wraps any backend and adds latency, transient errors
+//! and partial failures to its operations, so retry and error handling can
+//! be tested without a flaky network:
+//!
+//! ```rust,no_run
+//! # async fn example(storage: std::sync::Arc<dyn papermake::storage::Storage>) {
+//! use papermake::faulty::{FaultyStorage, Operation};
+//!
+//! // The next two template saves fail, then the backend recovers
+//! let storage = FaultyStorage::new(storage).only(&[Operation::SaveTemplate]);
+//! storage.fail_next(2);
+//! # }
+//! ```
+//!
+//! Faults are deterministic: random failures come from a seeded generator,
+//! so a failing test fails the same way every run. Injected errors are
+//! [`PapermakeError::Storage`] errors mentioning "injected fault".
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use time::OffsetDateTime;
+
+use crate::error::{PapermakeError, Result};
+use crate::storage::{ArtifactKind, ListOptions, Namespace, PurgeReport, Storage, TemplatePage};
+use crate::template::{Template, TemplateId};
+
+/// A storage operation faults can be limited to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operation {
+    SaveTemplate,
+    GetTemplate,
+    ListTemplates,
+    SavePublishedTemplate,
+    GetPublishedTemplate,
+    DeleteTemplate,
+    CopyTemplate,
+    SaveTemplateFile,
+    GetTemplateFile,
+    ListTemplateFiles,
+    DeleteTemplateFile,
+    RenameTemplateFile,
+    Purge,
+    CheckHealth,
+}
+
+impl Operation {
+    /// Whether the operation changes stored data
+    pub fn is_write(&self) -> bool {
+        !matches!(
+            self,
+            Operation::GetTemplate
+                | Operation::ListTemplates
+                | Operation::GetPublishedTemplate
+                | Operation::GetTemplateFile
+                | Operation::ListTemplateFiles
+                | Operation::CheckHealth
+        )
+    }
+}
+
+/// Faults to inject, shared by a storage and its namespaces
+#[derive(Debug, Default)]
+struct Faults {
+    latency: Duration,
+    /// Operations faults apply to; all if `None`
+    operations: Option<Vec<Operation>>,
+    /// Targeted calls left to fail
+    fail_next: u32,
+    /// Fail every n-th targeted call
+    fail_every: Option<u64>,
+    /// Probability of a targeted call failing
+    failure_rate: f64,
+    rng: u64,
+    /// Apply failing writes to the inner storage before reporting the error
+    partial_writes: bool,
+    calls: u64,
+    targeted: u64,
+    injected: u64,
+}
+
+impl Faults {
+    /// Count a call and decide whether it fails
+    fn should_fail(&mut self, operation: Operation) -> bool {
+        self.calls += 1;
+        if self.operations.as_ref().is_some_and(|operations| !operations.contains(&operation)) {
+            return false;
+        }
+        self.targeted += 1;
+
+        let fail = if self.fail_next > 0 {
+            self.fail_next -= 1;
+            true
+        } else if self.fail_every.is_some_and(|n| self.targeted % n == 0) {
+            true
+        } else {
+            self.failure_rate > 0.0 && self.next_random() < self.failure_rate
+        };
+        if fail {
+            self.injected += 1;
+        }
+        fail
+    }
+
+    /// Uniform value in `[0, 1)` from a xorshift generator
+    fn next_random(&mut self) -> f64 {
+        let mut x = self.rng;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.rng = x;
+        (x >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Storage injecting latency and failures into the operations of `S`
+pub struct FaultyStorage<S: Storage + ?Sized> {
+    inner: Arc<S>,
+    faults: Arc<Mutex<Faults>>,
+}
+
+impl<S: Storage + ?Sized> FaultyStorage<S> {
+    /// A wrapper passing every operation through until faults are configured
+    pub fn new(inner: Arc<S>) -> Self {
+        Self { inner, faults: Arc::new(Mutex::new(Faults::default())) }
+    }
+
+    /// Delay every operation by `latency`, failing or not
+    pub fn latency(self, latency: Duration) -> Self {
+        self.update(|faults| faults.latency = latency);
+        self
+    }
+
+    /// Limit failures to the given operations
+    pub fn only(self, operations: &[Operation]) -> Self {
+        self.update(|faults| faults.operations = Some(operations.to_vec()));
+        self
+    }
+
+    /// Fail every `n`-th targeted call, e.g. every third save
+    pub fn fail_every(self, n: u64) -> Self {
+        self.update(|faults| faults.fail_every = (n > 0).then_some(n));
+        self
+    }
+
+    /// Fail targeted calls with probability `rate`, from a generator seeded
+    /// with `seed`
+    pub fn failure_rate(self, rate: f64, seed: u64) -> Self {
+        self.update(|faults| {
+            faults.failure_rate = rate.clamp(0.0, 1.0);
+            // Xorshift is stuck at zero
+            faults.rng = seed.max(1);
+        });
+        self
+    }
+
+    /// Carry out failing writes before reporting the error, like a backend
+    /// whose acknowledgement got lost
+    pub fn partial_writes(self) -> Self {
+        self.update(|faults| faults.partial_writes = true);
+        self
+    }
+
+    /// Fail the next `n` targeted calls, then recover
+    pub fn fail_next(&self, n: u32) {
+        self.update(|faults| faults.fail_next = n);
+    }
+
+    /// Stop injecting failures and latency
+    pub fn heal(&self) {
+        self.update(|faults| {
+            faults.latency = Duration::ZERO;
+            faults.fail_next = 0;
+            faults.fail_every = None;
+            faults.failure_rate = 0.0;
+        });
+    }
+
+    /// Operations called so far, including those of namespaces
+    pub fn calls(&self) -> u64 {
+        self.faults.lock().map(|faults| faults.calls).unwrap_or_default()
+    }
+
+    /// Failures injected so far
+    pub fn injected(&self) -> u64 {
+        self.faults.lock().map(|faults| faults.injected).unwrap_or_default()
+    }
+
+    fn update(&self, f: impl FnOnce(&mut Faults)) {
+        if let Ok(mut faults) = self.faults.lock() {
+            f(&mut faults);
+        }
+    }
+
+    /// Wait out the latency and decide whether `operation` fails
+    async fn enter(&self, operation: Operation) -> Fault {
+        let (latency, fail, partial) = match self.faults.lock() {
+            Ok(mut faults) => (faults.latency, faults.should_fail(operation), faults.partial_writes),
+            Err(_) => (Duration::ZERO, false, false),
+        };
+        if !latency.is_zero() {
+            tokio::time::sleep(latency).await;
+        }
+        match (fail, partial && operation.is_write()) {
+            (false, _) => Fault::None,
+            (true, false) => Fault::Fail(operation),
+            (true, true) => Fault::AfterWrite(operation),
+        }
+    }
+
+    /// Run `call` on the inner storage unless the operation fails first
+    async fn run<T, F>(&self, operation: Operation, call: F) -> Result<T>
+    where
+        F: std::future::Future<Output = Result<T>>,
+    {
+        match self.enter(operation).await {
+            Fault::None => call.await,
+            Fault::Fail(operation) => Err(injected(operation)),
+            Fault::AfterWrite(operation) => {
+                call.await?;
+                Err(injected(operation))
+            }
+        }
+    }
+}
+
+enum Fault {
+    None,
+    Fail(Operation),
+    AfterWrite(Operation),
+}
+
+fn injected(operation: Operation) -> PapermakeError {
+    PapermakeError::Storage(format!("Injected fault in {:?}", operation))
+}
+
+#[async_trait]
+impl<S: Storage + ?Sized + 'static> Storage for FaultyStorage<S> {
+    async fn save_template(&self, template: &Template) -> Result<()> {
+        self.run(Operation::SaveTemplate, self.inner.save_template(template)).await
+    }
+
+    async fn get_template(&self, id: &TemplateId) -> Result<Template> {
+        self.run(Operation::GetTemplate, self.inner.get_template(id)).await
+    }
+
+    async fn list_templates(&self, options: &ListOptions) -> Result<TemplatePage> {
+        self.run(Operation::ListTemplates, self.inner.list_templates(options)).await
+    }
+
+    // Searching and dependents use the default implementations, so they
+    // see faults of `ListTemplates`
+
+    async fn save_published_template(&self, template: &Template) -> Result<()> {
+        self.run(Operation::SavePublishedTemplate, self.inner.save_published_template(template)).await
+    }
+
+    async fn get_published_template(&self, id: &TemplateId) -> Result<Template> {
+        self.run(Operation::GetPublishedTemplate, self.inner.get_published_template(id)).await
+    }
+
+    async fn delete_template(&self, id: &TemplateId) -> Result<()> {
+        self.run(Operation::DeleteTemplate, self.inner.delete_template(id)).await
+    }
+
+    async fn copy_template(&self, id: &TemplateId, new_id: &TemplateId) -> Result<Template> {
+        self.run(Operation::CopyTemplate, self.inner.copy_template(id, new_id)).await
+    }
+
+    async fn save_template_file(&self, id: &TemplateId, path: &str, content: &[u8]) -> Result<()> {
+        self.run(Operation::SaveTemplateFile, self.inner.save_template_file(id, path, content)).await
+    }
+
+    async fn get_template_file(&self, id: &TemplateId, path: &str) -> Result<Vec<u8>> {
+        self.run(Operation::GetTemplateFile, self.inner.get_template_file(id, path)).await
+    }
+
+    async fn list_template_files(&self, id: &TemplateId) -> Result<Vec<String>> {
+        self.run(Operation::ListTemplateFiles, self.inner.list_template_files(id)).await
+    }
+
+    async fn delete_template_file(&self, id: &TemplateId, path: &str) -> Result<()> {
+        self.run(Operation::DeleteTemplateFile, self.inner.delete_template_file(id, path)).await
+    }
+
+    async fn rename_template_file(&self, id: &TemplateId, from: &str, to: &str) -> Result<()> {
+        self.run(Operation::RenameTemplateFile, self.inner.rename_template_file(id, from, to)).await
+    }
+
+    async fn purge(&self, before: OffsetDateTime, kinds: &[ArtifactKind]) -> Result<PurgeReport> {
+        self.run(Operation::Purge, self.inner.purge(before, kinds)).await
+    }
+
+    async fn check_health(&self) -> Result<()> {
+        self.run(Operation::CheckHealth, self.inner.check_health()).await
+    }
+
+    /// Namespaces share the faults and counters of this storage
+    fn for_namespace(&self, namespace: &Namespace) -> Arc<dyn Storage> {
+        Arc::new(FaultyStorage { inner: self.inner.for_namespace(namespace), faults: self.faults.clone() })
+    }
+}
@@ -0,0 +1,165 @@
+//! Render data from HTML form submissions
+//!
+//! [`form_data`] turns the fields of a submitted form into JSON matching a
+//! template's schema. Field names are paths into the data:
+//!
+//! ```text
+//! customer.name=Ada            {"customer": {"name": "Ada"},
+//! items[0].name=Pen             "items": [{"name": "Pen", "price": 1.5},
+//! items[0].price=1.5                      {"name": "Ink", "price": 4}],
+//! items[1].name=Ink             "tags": ["urgent", "paid"],
+//! items[1].price=4              "express": true}
+//! tags[]=urgent
+//! tags[]=paid
+//! express=on
+//! ```
+//!
+//! Values are coerced to the types the schema declares. Boolean fields
+//! follow checkbox semantics: any submitted value but `false`, `no`, `off`
+//! or `0` is true, and an unchecked (missing) box is false. Empty inputs of
+//! optional fields are left out, so defaults apply.
+
+use serde_json::{Map, Value};
+
+use crate::error::{PapermakeError, Result};
+use crate::schema::{FieldType, Schema};
+
+/// One step of a field path
+enum Segment {
+    Key(String),
+    /// `[n]`, or `None` for `[]` appending to the array
+    Index(Option<usize>),
+}
+
+/// Build render data from form fields, in submission order
+pub fn form_data<K, V>(schema: &Schema, fields: impl IntoIterator<Item = (K, V)>) -> Result<Value>
+where
+    K: AsRef<str>,
+    V: Into<String>,
+{
+    let mut data = Value::Object(Map::new());
+    for (name, value) in fields {
+        let path = parse_path(name.as_ref())?;
+        insert(&mut data, &path, value.into(), name.as_ref())?;
+    }
+    normalize_object(schema, &mut data);
+    schema.coerce(&mut data);
+    Ok(data)
+}
+
+fn parse_path(name: &str) -> Result<Vec<Segment>> {
+    let invalid = || PapermakeError::InvalidInput(format!("Invalid form field name: {}", name));
+    let mut segments = Vec::new();
+    for part in name.split('.') {
+        let (key, mut rest) = match part.find('[') {
+            Some(i) => part.split_at(i),
+            None => (part, ""),
+        };
+        if key.is_empty() {
+            return Err(invalid());
+        }
+        segments.push(Segment::Key(key.to_string()));
+        while let Some(inner) = rest.strip_prefix('[') {
+            let end = inner.find(']').ok_or_else(invalid)?;
+            let index = match &inner[..end] {
+                "" => None,
+                digits => Some(digits.parse().map_err(|_| invalid())?),
+            };
+            segments.push(Segment::Index(index));
+            rest = &inner[end + 1..];
+        }
+        if !rest.is_empty() {
+            return Err(invalid());
+        }
+    }
+    Ok(segments)
+}
+
+/// Largest array index a form may set, so `items[999999999]` can't
+/// allocate a huge array
+const MAX_INDEX: usize = 10_000;
+
+fn insert(target: &mut Value, path: &[Segment], value: String, name: &str) -> Result<()> {
+    let conflict = || PapermakeError::InvalidInput(format!("Conflicting form field: {}", name));
+    let Some((segment, rest)) = path.split_first() else {
+        // A repeated field collects its values into an array
+        match target {
+            Value::Null => *target = Value::String(value),
+            Value::String(first) => *target = Value::Array(vec![Value::String(std::mem::take(first)), Value::String(value)]),
+            Value::Array(items) => items.push(Value::String(value)),
+            Value::Object(_) | Value::Bool(_) | Value::Number(_) => return Err(conflict()),
+        }
+        return Ok(());
+    };
+
+    let child = match segment {
+        Segment::Key(key) => {
+            if target.is_null() {
+                *target = Value::Object(Map::new());
+            }
+            let object = target.as_object_mut().ok_or_else(conflict)?;
+            object.entry(key.clone()).or_insert(Value::Null)
+        }
+        Segment::Index(index) => {
+            if target.is_null() {
+                *target = Value::Array(Vec::new());
+            }
+            let items = target.as_array_mut().ok_or_else(conflict)?;
+            let index = index.unwrap_or(items.len());
+            if index > MAX_INDEX {
+                return Err(PapermakeError::InvalidInput(format!("Form field index too large: {}", name)));
+            }
+            if items.len() <= index {
+                items.resize(index + 1, Value::Null);
+            }
+            &mut items[index]
+        }
+    };
+    insert(child, rest, value, name)
+}
+
+/// Shape form values after the schema: checkboxes become booleans, single
+/// values of array fields become arrays, and empty optional inputs go away
+fn normalize_object(schema: &Schema, data: &mut Value) {
+    let Some(object) = data.as_object_mut() else {
+        return;
+    };
+    for field in &schema.fields {
+        let empty = matches!(object.get(&field.key), Some(Value::String(s)) if s.is_empty());
+        if empty && !field.required && !matches!(field.field_type, FieldType::String) {
+            object.remove(&field.key);
+        }
+        match object.get_mut(&field.key) {
+            Some(value) => normalize_value(&field.field_type, value),
+            None if matches!(field.field_type, FieldType::Boolean | FieldType::Section) => {
+                object.insert(field.key.clone(), Value::Bool(false));
+            }
+            None => {}
+        }
+    }
+}
+
+fn normalize_value(field_type: &FieldType, value: &mut Value) {
+    match field_type {
+        FieldType::Boolean | FieldType::Section => {
+            if let Value::String(s) = value {
+                let off = matches!(s.trim().to_lowercase().as_str(), "false" | "no" | "off" | "0");
+                *value = Value::Bool(!off);
+            }
+        }
+        FieldType::Object(schema) => normalize_object(schema, value),
+        FieldType::Array(item_type) => {
+            if value.is_string() || value.is_object() {
+                *value = Value::Array(vec![value.take()]);
+            }
+            if let Value::Array(items) = value {
+                // Gaps left by skipped indices
+                items.retain(|item| !item.is_null());
+                for item in items {
+                    normalize_value(item_type, item);
+                }
+            }
+        }
+        _ => {}
+    }
+}
@@ -0,0 +1,125 @@
+//! Locale-aware formatting of numbers, currency amounts and dates
+
+use time::Date;
+
+/// Number and date conventions of a locale
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocaleFormat {
+    /// BCP 47 tag the conventions were looked up for, e.g. `de-DE`
+    pub locale: String,
+    pub decimal_separator: char,
+    pub group_separator: char,
+    /// Whether currency symbols precede the amount (`$1.00` vs `1,00 €`)
+    pub currency_prefix: bool,
+    /// Order and separator of date components
+    pub date_order: DateOrder,
+    pub date_separator: char,
+}
+
+/// Order of day, month and year in formatted dates
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DateOrder {
+    DayMonthYear,
+    MonthDayYear,
+    YearMonthDay,
+}
+
+impl LocaleFormat {
+    /// Conventions for a locale tag like `de`, `de-DE` or `en_GB`
+    ///
+    /// The region is consulted first, then the language. Unknown locales
+    /// fall back to `en-US` conventions.
+    pub fn for_locale(locale: &str) -> Self {
+        let tag = locale.replace('_', "-").to_ascii_lowercase();
+        let language = tag.split('-').next().unwrap_or_default();
+
+        let (decimal, group, prefix, order, date_sep) = match tag.as_str() {
+            "en-gb" | "en-ie" | "en-au" | "en-nz" => ('.', ',', true, DateOrder::DayMonthYear, '/'),
+            "de-ch" | "fr-ch" | "it-ch" => ('.', '\'', false, DateOrder::DayMonthYear, '.'),
+            _ => match language {
+                "de" | "da" | "nb" | "no" | "tr" => (',', '.', false, DateOrder::DayMonthYear, '.'),
+                "fr" => (',', '\u{202f}', false, DateOrder::DayMonthYear, '/'),
+                "es" | "it" | "pt" => (',', '.', false, DateOrder::DayMonthYear, '/'),
+                "nl" => (',', '.', true, DateOrder::DayMonthYear, '-'),
+                "pl" | "cs" | "sk" | "ru" | "uk" | "sv" | "fi" => (',', '\u{a0}', false, DateOrder::DayMonthYear, '.'),
+                "ja" | "zh" | "ko" => ('.', ',', true, DateOrder::YearMonthDay, '/'),
+                _ => ('.', ',', true, DateOrder::MonthDayYear, '/'),
+            },
+        };
+
+        LocaleFormat {
+            locale: locale.to_string(),
+            decimal_separator: decimal,
+            group_separator: group,
+            currency_prefix: prefix,
+            date_order: order,
+            date_separator: date_sep,
+        }
+    }
+
+    /// Format a number with `decimals` fraction digits and grouped thousands
+    pub fn format_number(&self, value: f64, decimals: usize) -> String {
+        let formatted = format!("{:.*}", decimals, value.abs());
+        let (integer, fraction) = formatted.split_once('.').unwrap_or((&formatted, ""));
+
+        let mut grouped = String::new();
+        for (i, digit) in integer.chars().enumerate() {
+            if i > 0 && (integer.len() - i) % 3 == 0 {
+                grouped.push(self.group_separator);
+            }
+            grouped.push(digit);
+        }
+
+        let negative = value < 0.0 && formatted.chars().any(|c| c.is_ascii_digit() && c != '0');
+        let mut result = if negative { format!("-{}", grouped) } else { grouped };
+        if !fraction.is_empty() {
+            result.push(self.decimal_separator);
+            result.push_str(fraction);
+        }
+        result
+    }
+
+    /// Format a currency amount, e.g. `$1,234.50` or `1.234,50 €`
+    pub fn format_currency(&self, value: f64, currency: &str) -> String {
+        let symbol = currency_symbol(currency);
+        let amount = self.format_number(value, 2);
+        if self.currency_prefix {
+            match amount.strip_prefix('-') {
+                Some(amount) => format!("-{}{}", symbol, amount),
+                None => format!("{}{}", symbol, amount),
+            }
+        } else {
+            format!("{}\u{a0}{}", amount, symbol)
+        }
+    }
+
+    /// Format a date, e.g. `31.12.2024` or `12/31/2024`
+    pub fn format_date(&self, date: Date) -> String {
+        let (day, month, year) = (date.day(), date.month() as u8, date.year());
+        let sep = self.date_separator;
+        match self.date_order {
+            DateOrder::DayMonthYear => format!("{:02}{sep}{:02}{sep}{}", day, month, year),
+            DateOrder::MonthDayYear => format!("{:02}{sep}{:02}{sep}{}", month, day, year),
+            DateOrder::YearMonthDay => format!("{}{sep}{:02}{sep}{:02}", year, month, day),
+        }
+    }
+}
+
+/// Parse the date part of an ISO 8601 / RFC 3339 string like `2024-12-31`
+/// or `2024-12-31T10:00:00Z`
+pub fn parse_date(value: &str) -> Option<Date> {
+    let format = time::macros::format_description!("[year]-[month]-[day]");
+    Date::parse(value.get(..10)?, format).ok()
+}
+
+/// Symbol for an ISO 4217 currency code; unknown codes are used as is
+fn currency_symbol(currency: &str) -> &str {
+    match currency {
+        "EUR" => "€",
+        "USD" => "$",
+        "GBP" => "£",
+        "JPY" => "¥",
+        "INR" => "₹",
+        other => other,
+    }
+}
@@ -0,0 +1,337 @@
+//! Audit log of renders
+//!
+//! A [`RenderRecord`] captures which template revision rendered which data
+//! (by hash), how long it took, whether it succeeded and who asked for it.
+//! The input data can be archived alongside a record so a render can be
+//! reproduced later.
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use time::OffsetDateTime;
+
+use crate::error::{PapermakeError, Result};
+use crate::output::RenderBundle;
+use crate::render::RenderResult;
+use crate::storage::Namespace;
+use crate::template::{Template, TemplateId, TemplateStatus};
+
+/// One render, as stored in a [`RenderHistory`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RenderRecord {
+    pub id: String,
+    pub template_id: TemplateId,
+    /// Revision of the template that was rendered
+    pub template_revision: u64,
+    /// Status of the rendered revision (a published revision or a draft)
+    pub template_status: TemplateStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub locale: Option<String>,
+    /// SHA-256 of the input data, see [`data_hash`]
+    pub data_hash: String,
+    pub duration_ms: u64,
+    pub success: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
+    /// Size of the rendered document
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_bytes: Option<u64>,
+    /// Fingerprint of the API key that requested the render
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key_id: Option<String>,
+    /// Whether the input data is archived with the record
+    #[serde(default)]
+    pub has_inputs: bool,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+}
+
+impl RenderRecord {
+    /// Start a record of rendering `template` with `data`
+    pub fn new(id: impl Into<String>, template: &Template, data: &serde_json::Value) -> Self {
+        Self {
+            id: id.into(),
+            template_id: template.id.clone(),
+            template_revision: template.revision,
+            template_status: template.status,
+            locale: None,
+            data_hash: data_hash(data),
+            duration_ms: 0,
+            success: false,
+            errors: Vec::new(),
+            output_bytes: None,
+            api_key_id: None,
+            has_inputs: false,
+            created_at: OffsetDateTime::now_utc(),
+        }
+    }
+
+    pub fn with_locale(mut self, locale: Option<String>) -> Self {
+        self.locale = locale;
+        self
+    }
+
+    pub fn with_api_key_id(mut self, api_key_id: Option<String>) -> Self {
+        self.api_key_id = api_key_id;
+        self
+    }
+
+    /// Complete the record with the outcome of the render
+    pub fn finish(mut self, duration: Duration, result: &Result<RenderResult>) -> Self {
+        self.duration_ms = duration.as_millis() as u64;
+        match result {
+            Ok(result) => {
+                self.success = result.pdf.is_some();
+                self.errors = result.errors.iter().map(|e| e.message.clone()).collect();
+                self.output_bytes = result.pdf.as_ref().map(|pdf| pdf.len() as u64);
+            }
+            Err(err) => {
+                self.success = false;
+                self.errors = vec![err.to_string()];
+            }
+        }
+        self
+    }
+
+    /// Complete the record with the outcome of a [`render_all`](crate::render_all),
+    /// counting the size of all exported files
+    pub fn finish_bundle(mut self, duration: Duration, result: &Result<RenderBundle>) -> Self {
+        self.duration_ms = duration.as_millis() as u64;
+        match result {
+            Ok(bundle) => {
+                self.success = bundle.is_success();
+                self.errors = bundle.errors.iter().map(|e| e.message.clone()).collect();
+                let files = bundle.pdf.iter().chain(&bundle.png).chain(&bundle.svg);
+                self.output_bytes = self.success.then(|| files.map(|file| file.len() as u64).sum());
+            }
+            Err(err) => {
+                self.success = false;
+                self.errors = vec![err.to_string()];
+            }
+        }
+        self
+    }
+}
+
+/// Hex SHA-256 of a JSON value's serialization
+pub fn data_hash(data: &serde_json::Value) -> String {
+    hex::encode(Sha256::digest(data.to_string().as_bytes()))
+}
+
+/// Persistent log of renders
+#[async_trait]
+pub trait RenderHistory: Send + Sync {
+    /// Store a record, archiving `inputs` with it when given
+    async fn record(&self, record: &RenderRecord, inputs: Option<&serde_json::Value>) -> Result<()>;
+
+    /// Get a record by id
+    async fn get_record(&self, id: &str) -> Result<RenderRecord>;
+
+    /// Get the archived input data of a record
+    async fn get_inputs(&self, id: &str) -> Result<serde_json::Value>;
+
+    /// Records of renders of a template, most recent first
+    async fn list_records(&self, template_id: &TemplateId, limit: usize) -> Result<Vec<RenderRecord>>;
+
+    /// History of a tenant namespace, isolated from this one
+    fn for_namespace(&self, namespace: &Namespace) -> Arc<dyn RenderHistory>;
+}
+
+fn record_not_found(id: &str) -> PapermakeError {
+    PapermakeError::not_found("Render record", id)
+}
+
+/// Check that a record id is usable as a file name
+fn validate_record_id(id: &str) -> Result<()> {
+    let valid = !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(PapermakeError::InvalidInput(format!("Invalid render record id: {}", id)))
+    }
+}
+
+/// Keep the `limit` most recent of `records`
+fn most_recent(mut records: Vec<RenderRecord>, limit: usize) -> Vec<RenderRecord> {
+    records.sort_by(|a, b| b.created_at.cmp(&a.created_at).then_with(|| b.id.cmp(&a.id)));
+    records.truncate(limit);
+    records
+}
+
+type MemoryRecords = BTreeMap<(String, String), (RenderRecord, Option<serde_json::Value>)>;
+
+/// History kept in memory, mainly for tests
+#[derive(Debug, Clone, Default)]
+pub struct MemoryRenderHistory {
+    namespace: String,
+    records: Arc<Mutex<MemoryRecords>>,
+}
+
+impl MemoryRenderHistory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, MemoryRecords>> {
+        self.records
+            .lock()
+            .map_err(|_| PapermakeError::Storage("Failed to acquire history lock".to_string()))
+    }
+
+    fn key(&self, id: &str) -> (String, String) {
+        (self.namespace.clone(), id.to_string())
+    }
+}
+
+#[async_trait]
+impl RenderHistory for MemoryRenderHistory {
+    async fn record(&self, record: &RenderRecord, inputs: Option<&serde_json::Value>) -> Result<()> {
+        let mut record = record.clone();
+        record.has_inputs = inputs.is_some();
+        self.lock()?.insert(self.key(&record.id), (record, inputs.cloned()));
+        Ok(())
+    }
+
+    async fn get_record(&self, id: &str) -> Result<RenderRecord> {
+        self.lock()?
+            .get(&self.key(id))
+            .map(|(record, _)| record.clone())
+            .ok_or_else(|| record_not_found(id))
+    }
+
+    async fn get_inputs(&self, id: &str) -> Result<serde_json::Value> {
+        self.lock()?
+            .get(&self.key(id))
+            .and_then(|(_, inputs)| inputs.clone())
+            .ok_or_else(|| record_not_found(id))
+    }
+
+    async fn list_records(&self, template_id: &TemplateId, limit: usize) -> Result<Vec<RenderRecord>> {
+        let records = self.lock()?
+            .iter()
+            .filter(|((namespace, _), (record, _))| *namespace == self.namespace && record.template_id == *template_id)
+            .map(|(_, (record, _))| record.clone())
+            .collect();
+        Ok(most_recent(records, limit))
+    }
+
+    fn for_namespace(&self, namespace: &Namespace) -> Arc<dyn RenderHistory> {
+        Arc::new(Self {
+            namespace: namespace.to_string(),
+            records: self.records.clone(),
+        })
+    }
+}
+
+#[cfg(feature = "fs")]
+pub use file_history::FileRenderHistory;
+
+#[cfg(feature = "fs")]
+mod file_history {
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    use async_trait::async_trait;
+    use tokio::fs;
+
+    use super::{most_recent, record_not_found, validate_record_id, RenderHistory, RenderRecord};
+    use crate::error::{PapermakeError, Result};
+    use crate::storage::Namespace;
+    use crate::template::TemplateId;
+
+    /// History stored as JSON files
+    ///
+    /// Directory structure, mirroring `FileStorage`:
+    /// ```text
+    /// base_path/
+    /// ├── renders/
+    /// │   └── record_id/
+    /// │       ├── record.json
+    /// │       └── inputs.json
+    /// └── tenants/
+    ///     └── namespace/
+    ///         └── renders/
+    ///             └── ...
+    /// ```
+    #[derive(Debug, Clone)]
+    pub struct FileRenderHistory {
+        base_path: PathBuf,
+    }
+
+    impl FileRenderHistory {
+        pub fn new(base_path: impl Into<PathBuf>) -> Self {
+            Self {
+                base_path: base_path.into(),
+            }
+        }
+
+        fn record_dir(&self, id: &str) -> Result<PathBuf> {
+            validate_record_id(id)?;
+            Ok(self.base_path.join("renders").join(id))
+        }
+    }
+
+    #[async_trait]
+    impl RenderHistory for FileRenderHistory {
+        async fn record(&self, record: &RenderRecord, inputs: Option<&serde_json::Value>) -> Result<()> {
+            let dir = self.record_dir(&record.id)?;
+            fs::create_dir_all(&dir).await?;
+
+            if let Some(inputs) = inputs {
+                let json = serde_json::to_vec(inputs)
+                    .map_err(|e| PapermakeError::Storage(format!("Failed to serialize inputs: {}", e)))?;
+                fs::write(dir.join("inputs.json"), json).await?;
+            }
+
+            let mut record = record.clone();
+            record.has_inputs = inputs.is_some();
+            let json = serde_json::to_vec_pretty(&record)
+                .map_err(|e| PapermakeError::Storage(format!("Failed to serialize render record: {}", e)))?;
+            fs::write(dir.join("record.json"), json).await?;
+            Ok(())
+        }
+
+        async fn get_record(&self, id: &str) -> Result<RenderRecord> {
+            let json = fs::read(self.record_dir(id)?.join("record.json"))
+                .await
+                .map_err(|_| record_not_found(id))?;
+            serde_json::from_slice(&json)
+                .map_err(|e| PapermakeError::Storage(format!("Failed to parse render record {}: {}", id, e)))
+        }
+
+        async fn get_inputs(&self, id: &str) -> Result<serde_json::Value> {
+            let json = fs::read(self.record_dir(id)?.join("inputs.json"))
+                .await
+                .map_err(|_| record_not_found(id))?;
+            serde_json::from_slice(&json)
+                .map_err(|e| PapermakeError::Storage(format!("Failed to parse render inputs {}: {}", id, e)))
+        }
+
+        async fn list_records(&self, template_id: &TemplateId, limit: usize) -> Result<Vec<RenderRecord>> {
+            let renders_dir = self.base_path.join("renders");
+            if !renders_dir.exists() {
+                return Ok(Vec::new());
+            }
+
+            let mut records = Vec::new();
+            let mut entries = fs::read_dir(&renders_dir).await?;
+            while let Some(entry) = entries.next_entry().await? {
+                let id = entry.file_name().to_string_lossy().to_string();
+                if let Ok(record) = self.get_record(&id).await {
+                    if record.template_id == *template_id {
+                        records.push(record);
+                    }
+                }
+            }
+            Ok(most_recent(records, limit))
+        }
+
+        fn for_namespace(&self, namespace: &Namespace) -> Arc<dyn RenderHistory> {
+            Arc::new(Self::new(self.base_path.join("tenants").join(namespace.as_str())))
+        }
+    }
+}
@@ -3,19 +3,134 @@
 
 pub mod error;
 pub mod schema;
+pub mod sections;
+pub mod compatibility;
+pub mod changes;
+pub mod sample;
+pub mod scaffold;
 pub mod template;
 pub mod render;
+pub mod output;
+pub mod preview;
+pub mod render_cache;
+pub mod encryption;
+pub mod optimize;
+pub mod metadata;
+pub mod text;
+pub mod attachment;
+pub mod watermark;
 pub mod typst;
 pub mod macros;
 pub mod cache;
+pub mod pool;
+pub mod testing;
+mod trace;
+pub mod storage;
+pub mod blobs;
+pub mod merge;
+pub mod package;
+pub mod data;
+pub mod form;
+pub mod format;
+pub mod locale;
+pub mod environment;
+pub mod transform;
+pub mod lint;
+pub mod check;
+pub mod analyze;
+pub mod diff;
+pub mod line_diff;
+pub mod pdf_ops;
+pub mod shared;
+pub mod sandbox;
+pub mod limits;
+pub mod lifecycle;
+pub mod sink;
+pub mod history;
+pub mod stats;
+#[cfg(feature = "tokio")]
+pub mod batch;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "barcodes")]
+pub mod barcode;
+#[cfg(feature = "charts")]
+pub mod charts;
+#[cfg(feature = "scripting")]
+pub mod script;
+#[cfg(feature = "remote")]
+pub mod remote;
+#[cfg(feature = "encrypted-storage")]
+pub mod encrypted;
+#[cfg(feature = "test-util")]
+pub mod faulty;
 // Re-export core types
-pub use error::{PapermakeError, Result};
-pub use schema::{Schema, SchemaField, FieldType, SchemaBuilder};
-pub use template::{Template, TemplateId, TemplateBuilder};
-pub use render::{render_pdf, RenderOptions, RenderResult};
+pub use error::{ErrorCode, PapermakeError, Result};
+pub use schema::{BarcodeKind, Schema, SchemaField, FieldType, SchemaBuilder, Widget};
+pub use compatibility::{CompatibilityReport, SchemaChange, SchemaChangeKind};
+pub use changes::{FileChange, FileChangeKind, TemplateChanges};
+pub use sample::SampleOptions;
+pub use scaffold::ScaffoldStyle;
+pub use template::{Template, TemplateId, TemplateBuilder, TemplateStatus};
+pub use render::{render_pdf, prepare_data, RenderOptions, RenderResult};
+pub use output::{render, render_all, OutputFormat, RenderBundle, RenderOutput};
+pub use preview::{render_preview, Preview, PreviewFormat, PreviewOptions};
+#[cfg(feature = "html")]
+pub use output::render_html;
+pub use encryption::PdfEncryption;
+pub use optimize::{OptimizationReport, OptimizeLevel};
+pub use metadata::DocumentMetadata;
+pub use text::{PageText, TextSpan};
+pub use attachment::{AttachmentRelationship, PdfAttachment};
+pub use watermark::{Watermark, WatermarkContent, WatermarkPages};
+pub use render_cache::{CachePolicy, DiskRenderCache, MemoryRenderCache, RenderCache};
+#[cfg(feature = "tokio")]
+pub use render::render_pdf_async;
 pub use cache::{CachedTemplate, TemplateCache};
+pub use pool::WorldPool;
+pub use merge::render_merged;
+pub use diff::{DiffOptions, DiffReport};
+pub use analyze::{analyze_template, Analysis};
+pub use check::CheckReport;
+pub use pdf_ops::PageSelection;
+pub use package::TemplatePackage;
+pub use shared::{resolve_shared, Dependent, SharedSources};
+pub use sandbox::SandboxPolicy;
+pub use limits::SizeLimits;
+pub use lifecycle::TemplateVersion;
+pub use data::{render_pdf_typed, PapermakeData};
+pub use form::form_data;
+pub use format::LocaleFormat;
+pub use environment::EnvironmentConfig;
+pub use transform::{DataTransform, FormatDate, FormatNumber, TransformPipeline, TransformSpec};
+pub use sink::{MemorySink, RenderSink};
+pub use blobs::{BlobStore, DedupStorage, GcReport, MemoryBlobStore};
+pub use history::{MemoryRenderHistory, RenderHistory, RenderRecord};
+pub use stats::{MemoryRenderStats, RenderStats, TemplateStats};
+#[cfg(feature = "fs")]
+pub use history::FileRenderHistory;
+#[cfg(feature = "fs")]
+pub use stats::FileRenderStats;
+#[cfg(feature = "fs")]
+pub use sink::FileSink;
+#[cfg(feature = "fs")]
+pub use blobs::FileBlobStore;
+#[cfg(feature = "tokio")]
+pub use batch::{render_batch, BatchItem};
+#[cfg(feature = "charts")]
+pub use charts::{ChartData, ChartKind, ChartSeries, ChartSpec};
+#[cfg(feature = "scripting")]
+pub use script::ScriptLimits;
+#[cfg(feature = "remote")]
+pub use remote::RemoteResources;
+#[cfg(feature = "encrypted-storage")]
+pub use encrypted::{EncryptedRenderCache, EncryptedStorage, EncryptionKey};
+#[cfg(feature = "test-util")]
+pub use faulty::FaultyStorage;
+#[cfg(feature = "derive")]
+pub use papermake_derive::PapermakeData;
 
 /// Get the library version
 pub fn version() -> &'static str {
     env!("CARGO_PKG_VERSION")
-}
\ No newline at end of file
+}
@@ -0,0 +1,141 @@
+//! Draft/published lifecycle of templates
+//!
+//! Edits go to the template's working copy, which stays a draft until it is
+//! published. Publishing snapshots the working copy as the published
+//! revision, which renders use unless a draft is explicitly requested.
+
+use std::collections::BTreeMap;
+
+use sha2::{Digest, Sha256};
+
+use crate::error::{PapermakeError, Result};
+use crate::storage::Storage;
+use crate::template::{Template, TemplateId, TemplateStatus};
+
+/// Which revision of a template to render
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TemplateVersion {
+    /// The latest published revision
+    #[default]
+    Published,
+    /// The working copy, including unpublished changes
+    Draft,
+}
+
+impl std::str::FromStr for TemplateVersion {
+    type Err = PapermakeError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "published" => Ok(TemplateVersion::Published),
+            "draft" => Ok(TemplateVersion::Draft),
+            other => Err(PapermakeError::InvalidInput(format!(
+                "Unknown template version '{}', expected 'published' or 'draft'",
+                other
+            ))),
+        }
+    }
+}
+
+/// Save changes to a template's working copy, turning it into a draft
+///
+/// If the stored working copy is the live revision of a template that
+/// predates separate published revisions, it is kept as the published
+/// revision first, so the edit doesn't change what renders serve.
+pub async fn save_draft(storage: &dyn Storage, template: &mut Template) -> Result<()> {
+    if let Ok(current) = storage.get_template(&template.id).await {
+        if current.status == TemplateStatus::Published
+            && storage.get_published_template(&template.id).await.is_err()
+        {
+            storage.save_published_template(&current).await?;
+        }
+    }
+
+    template.status = TemplateStatus::Draft;
+    template.updated_at = time::OffsetDateTime::now_utc();
+    storage.save_template(template).await?;
+    template.revision += 1;
+    Ok(())
+}
+
+/// Publish a template's working copy
+pub async fn publish_template(storage: &dyn Storage, id: &TemplateId) -> Result<Template> {
+    let mut template = storage.get_template(id).await?;
+    if template.status == TemplateStatus::Archived {
+        return Err(PapermakeError::InvalidInput(format!(
+            "Template '{}' is archived",
+            id.as_ref()
+        )));
+    }
+
+    template.status = TemplateStatus::Published;
+    template.published_at = Some(time::OffsetDateTime::now_utc());
+    // Files aren't versioned; the published revision records what they
+    // were, so later changes to them show up in diffs
+    let published = Template { assets: asset_manifest(storage, id).await?, ..template.clone() };
+    storage.save_published_template(&published).await?;
+    storage.save_template(&template).await?;
+    template.revision += 1;
+    Ok(template)
+}
+
+/// SHA-256 of each of a template's files, by path
+pub async fn asset_manifest(storage: &dyn Storage, id: &TemplateId) -> Result<BTreeMap<String, String>> {
+    let mut manifest = BTreeMap::new();
+    for path in storage.list_template_files(id).await? {
+        let content = storage.get_template_file(id, &path).await?;
+        manifest.insert(path, hex::encode(Sha256::digest(&content)));
+    }
+    Ok(manifest)
+}
+
+/// Archive a template, making both its working copy and published revision unrenderable
+pub async fn archive_template(storage: &dyn Storage, id: &TemplateId) -> Result<Template> {
+    let mut template = storage.get_template(id).await?;
+    template.status = TemplateStatus::Archived;
+    storage.save_template(&template).await?;
+    template.revision += 1;
+
+    if let Ok(mut published) = storage.get_published_template(id).await {
+        published.status = TemplateStatus::Archived;
+        storage.save_published_template(&published).await?;
+    }
+    Ok(template)
+}
+
+/// Load the revision of a template to render
+///
+/// Fails with `InvalidInput` if the template has no published revision or is
+/// archived, and with `TemplateNotFound` if it doesn't exist.
+pub async fn template_for_render(
+    storage: &dyn Storage,
+    id: &TemplateId,
+    version: TemplateVersion,
+) -> Result<Template> {
+    let template = match version {
+        TemplateVersion::Draft => storage.get_template(id).await?,
+        TemplateVersion::Published => match storage.get_published_template(id).await {
+            Ok(template) => template,
+            Err(PapermakeError::NotFound { .. } | PapermakeError::TemplateNotFound { .. }) => {
+                // Templates that predate the lifecycle have no separate
+                // published revision; their working copy is live
+                let current = storage.get_template(id).await?;
+                if current.status != TemplateStatus::Published {
+                    return Err(PapermakeError::InvalidInput(format!(
+                        "Template '{}' has no published version",
+                        id.as_ref()
+                    )));
+                }
+                current
+            }
+        },
+    };
+
+    if template.status == TemplateStatus::Archived {
+        return Err(PapermakeError::InvalidInput(format!(
+            "Template '{}' is archived",
+            id.as_ref()
+        )));
+    }
+    Ok(template)
+}
@@ -0,0 +1,123 @@
+//! Size limits guarding against oversized inputs
+//!
+//! Renders hold the data, the template source and its assets in memory,
+//! several times over while compiling. [`SizeLimits`] rejects inputs above
+//! a configured size up front with [`PapermakeError::TooLarge`], instead of
+//! letting a huge payload exhaust the process's memory. Limits are set per
+//! render through `RenderOptions::size_limits`; all of them default to
+//! unlimited.
+
+use std::io;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{PapermakeError, Result};
+use crate::template::Template;
+
+/// Maximum sizes of render inputs, in bytes; `None` means unlimited
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SizeLimits {
+    /// Render data, measured as compact JSON
+    pub max_data_bytes: Option<usize>,
+    /// Template source, each of its locale variants, and its script
+    pub max_template_bytes: Option<usize>,
+    /// A single asset file of a template
+    pub max_asset_bytes: Option<usize>,
+}
+
+impl SizeLimits {
+    pub fn max_data_bytes(mut self, bytes: usize) -> Self {
+        self.max_data_bytes = Some(bytes);
+        self
+    }
+
+    pub fn max_template_bytes(mut self, bytes: usize) -> Self {
+        self.max_template_bytes = Some(bytes);
+        self
+    }
+
+    pub fn max_asset_bytes(mut self, bytes: usize) -> Self {
+        self.max_asset_bytes = Some(bytes);
+        self
+    }
+
+    /// Check the size of render data
+    pub fn check_data(&self, data: &serde_json::Value) -> Result<()> {
+        let Some(max) = self.max_data_bytes else {
+            return Ok(());
+        };
+        // Count while serializing and stop at the limit, without buffering
+        let mut counter = ByteCounter { count: 0, max };
+        if serde_json::to_writer(&mut counter, data).is_err() || counter.count > max {
+            return Err(PapermakeError::TooLarge(format!(
+                "Render data exceeds the maximum size of {} bytes",
+                max
+            )));
+        }
+        Ok(())
+    }
+
+    /// Check the size of a template's source, its variants and its script
+    pub fn check_template(&self, template: &Template) -> Result<()> {
+        let Some(max) = self.max_template_bytes else {
+            return Ok(());
+        };
+        let sources = std::iter::once((None, &template.content))
+            .chain(template.variants.iter().map(|(locale, content)| (Some(locale), content)));
+        for (locale, content) in sources {
+            if content.len() > max {
+                let source = match locale {
+                    Some(locale) => format!("Variant '{}' of template '{}'", locale, template.id.0),
+                    None => format!("Template '{}'", template.id.0),
+                };
+                return Err(PapermakeError::TooLarge(format!(
+                    "{} is {} bytes, exceeding the maximum of {} bytes",
+                    source,
+                    content.len(),
+                    max
+                )));
+            }
+        }
+        if let Some(script) = template.script.as_ref().filter(|script| script.len() > max) {
+            return Err(PapermakeError::TooLarge(format!(
+                "Script of template '{}' is {} bytes, exceeding the maximum of {} bytes",
+                template.id.0,
+                script.len(),
+                max
+            )));
+        }
+        Ok(())
+    }
+
+    /// Check the size of a template asset
+    pub fn check_asset(&self, path: &str, size: usize) -> Result<()> {
+        match self.max_asset_bytes {
+            Some(max) if size > max => Err(PapermakeError::TooLarge(format!(
+                "File '{}' is {} bytes, exceeding the maximum of {} bytes",
+                path, size, max
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Writer counting bytes, failing once more than `max` were written
+struct ByteCounter {
+    count: usize,
+    max: usize,
+}
+
+impl io::Write for ByteCounter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.count += buf.len();
+        if self.count > self.max {
+            return Err(io::Error::other("size limit exceeded"));
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
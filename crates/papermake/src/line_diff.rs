@@ -0,0 +1,267 @@
+//! Line-based diffs and three-way merges of template sources
+//!
+//! Both are built on a longest common subsequence of lines, computed after
+//! trimming the common prefix and suffix. Regions too large to compare line
+//! by line (see [`MAX_DIFF_CELLS`]) count as changed as a whole.
+
+use serde::Serialize;
+
+/// Outcome of a three-way [`merge`] of template content
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MergeResult {
+    /// Merged content; conflicting regions hold both sides between
+    /// `<<<<<<< ours`, `=======` and `>>>>>>> theirs` markers
+    pub content: String,
+    pub conflicts: Vec<MergeConflict>,
+}
+
+impl MergeResult {
+    /// Whether both sides merged without conflicts
+    pub fn is_clean(&self) -> bool {
+        self.conflicts.is_empty()
+    }
+}
+
+/// A region both sides changed differently
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MergeConflict {
+    /// Line of the merged content the conflict markers start at, from 1
+    pub line: usize,
+    pub base: String,
+    pub ours: String,
+    pub theirs: String,
+}
+
+/// Largest region, in compared line pairs, diffed line by line; beyond it
+/// the region counts as changed as a whole
+const MAX_DIFF_CELLS: usize = 4_000_000;
+
+/// Merge two edits of the same base content, line by line
+///
+/// Regions changed on one side only take that side's change, as do regions
+/// both sides changed identically. Regions changed differently on both
+/// sides are conflicts, kept with markers in the merged content.
+pub fn merge(base: &str, ours: &str, theirs: &str) -> MergeResult {
+    let base: Vec<&str> = base.split_inclusive('\n').collect();
+    let ours: Vec<&str> = ours.split_inclusive('\n').collect();
+    let theirs: Vec<&str> = theirs.split_inclusive('\n').collect();
+    let to_ours = line_matches(&base, &ours);
+    let to_theirs = line_matches(&base, &theirs);
+
+    let mut result = MergeResult { content: String::new(), conflicts: Vec::new() };
+    let (mut i, mut j, mut k) = (0, 0, 0);
+    loop {
+        // Lines unchanged on both sides
+        let mut stable = 0;
+        while i + stable < base.len()
+            && to_ours[i + stable] == Some(j + stable)
+            && to_theirs[i + stable] == Some(k + stable)
+        {
+            stable += 1;
+        }
+        if stable > 0 {
+            base[i..i + stable].iter().for_each(|line| result.content.push_str(line));
+            (i, j, k) = (i + stable, j + stable, k + stable);
+            continue;
+        }
+
+        // A changed region, up to the next base line both sides kept
+        let next = (i..base.len()).find(|&o| to_ours[o].is_some() && to_theirs[o].is_some());
+        let (o, oj, ok) = match next {
+            Some(o) => (o, to_ours[o].unwrap_or(ours.len()), to_theirs[o].unwrap_or(theirs.len())),
+            None => (base.len(), ours.len(), theirs.len()),
+        };
+        if next.is_none() && i == o && j == oj && k == ok {
+            break;
+        }
+        resolve(&base[i..o], &ours[j..oj], &theirs[k..ok], &mut result);
+        (i, j, k) = (o, oj, ok);
+    }
+    result
+}
+
+fn resolve(base: &[&str], ours: &[&str], theirs: &[&str], result: &mut MergeResult) {
+    let chosen = if ours == base || ours == theirs {
+        theirs
+    } else if theirs == base {
+        ours
+    } else {
+        let line = result.content.lines().count() + 1;
+        let (base, ours, theirs) = (base.concat(), ours.concat(), theirs.concat());
+        for (marker, lines) in [("<<<<<<< ours\n", &ours), ("=======\n", &theirs)] {
+            result.content.push_str(marker);
+            result.content.push_str(lines);
+            if !lines.is_empty() && !lines.ends_with('\n') {
+                result.content.push('\n');
+            }
+        }
+        result.content.push_str(">>>>>>> theirs\n");
+        result.conflicts.push(MergeConflict { line, base, ours, theirs });
+        return;
+    };
+    chosen.iter().for_each(|line| result.content.push_str(line));
+}
+
+/// Unified diff from `old` to `new`, with `context` unchanged lines around
+/// each change; empty if both are equal
+///
+/// Only hunks are produced, starting with `@@ -l,s +l,s @@` headers and
+/// without the `---`/`+++` file header.
+pub fn unified_diff(old: &str, new: &str, context: usize) -> String {
+    let old: Vec<&str> = old.split_inclusive('\n').collect();
+    let new: Vec<&str> = new.split_inclusive('\n').collect();
+    let matches = line_matches(&old, &new);
+
+    // Edit script as (old line, new line) pairs; `None` on the side a line
+    // is missing from
+    let mut ops = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && matches[i] == Some(j) {
+            ops.push((Some(i), Some(j)));
+            (i, j) = (i + 1, j + 1);
+        } else if i < old.len() && matches[i].is_none() {
+            ops.push((Some(i), None));
+            i += 1;
+        } else {
+            ops.push((None, Some(j)));
+            j += 1;
+        }
+    }
+
+    let changed: Vec<usize> = (0..ops.len()).filter(|&o| !matches!(ops[o], (Some(_), Some(_)))).collect();
+    let mut diff = String::new();
+    let mut group = 0;
+    while group < changed.len() {
+        // Changes closer than twice the context share a hunk
+        let mut last = group;
+        while last + 1 < changed.len() && changed[last + 1] - changed[last] <= 2 * context + 1 {
+            last += 1;
+        }
+        let start = changed[group].saturating_sub(context);
+        let end = (changed[last] + context + 1).min(ops.len());
+        let hunk = &ops[start..end];
+
+        let old_before = ops[..start].iter().filter(|(o, _)| o.is_some()).count();
+        let new_before = ops[..start].iter().filter(|(_, n)| n.is_some()).count();
+        let old_count = hunk.iter().filter(|(o, _)| o.is_some()).count();
+        let new_count = hunk.iter().filter(|(_, n)| n.is_some()).count();
+        let line_start = |before: usize, count: usize| if count == 0 { before } else { before + 1 };
+        diff.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            line_start(old_before, old_count),
+            old_count,
+            line_start(new_before, new_count),
+            new_count
+        ));
+        for op in hunk {
+            let (prefix, line) = match *op {
+                (Some(o), Some(_)) => (' ', old[o]),
+                (Some(o), None) => ('-', old[o]),
+                (None, Some(n)) => ('+', new[n]),
+                (None, None) => unreachable!(),
+            };
+            diff.push(prefix);
+            diff.push_str(line);
+            if !line.ends_with('\n') {
+                diff.push_str("\n\\ No newline at end of file\n");
+            }
+        }
+        group = last + 1;
+    }
+    diff
+}
+
+/// For each line of `a`, the line of `b` it is kept as in a longest common
+/// subsequence
+fn line_matches(a: &[&str], b: &[&str]) -> Vec<Option<usize>> {
+    let mut matches = vec![None; a.len()];
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..].iter().rev().zip(b[prefix..].iter().rev()).take_while(|(x, y)| x == y).count();
+    for (i, matched) in matches.iter_mut().enumerate().take(prefix) {
+        *matched = Some(i);
+    }
+    for n in 1..=suffix {
+        matches[a.len() - n] = Some(b.len() - n);
+    }
+
+    let (a_mid, b_mid) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+    let (n, m) = (a_mid.len(), b_mid.len());
+    if n == 0 || m == 0 || (n + 1) * (m + 1) > MAX_DIFF_CELLS {
+        return matches;
+    }
+    // lengths[i * (m + 1) + j]: LCS length of a_mid[i..] and b_mid[j..]
+    let mut lengths = vec![0u32; (n + 1) * (m + 1)];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lengths[i * (m + 1) + j] = if a_mid[i] == b_mid[j] {
+                lengths[(i + 1) * (m + 1) + j + 1] + 1
+            } else {
+                lengths[(i + 1) * (m + 1) + j].max(lengths[i * (m + 1) + j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if a_mid[i] == b_mid[j] {
+            matches[prefix + i] = Some(prefix + j);
+            i += 1;
+            j += 1;
+        } else if lengths[(i + 1) * (m + 1) + j] >= lengths[i * (m + 1) + j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    matches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_matches_follow_longest_common_subsequence() {
+        let a = ["a\n", "b\n", "c\n", "d\n"];
+        let b = ["a\n", "c\n", "x\n", "d\n"];
+        assert_eq!(line_matches(&a, &b), [Some(0), None, Some(1), Some(3)]);
+        assert_eq!(line_matches(&a, &[]), [None; 4]);
+        assert!(line_matches(&[], &b).is_empty());
+    }
+
+    #[test]
+    fn test_unified_diff_hunks() {
+        assert_eq!(unified_diff("a\nb\n", "a\nb\n", 3), "");
+        assert_eq!(unified_diff("a\nb\nc\n", "a\nB\nc\n", 1), "@@ -1,3 +1,3 @@\n a\n-b\n+B\n c\n");
+        assert_eq!(unified_diff("", "a\n", 3), "@@ -0,0 +1,1 @@\n+a\n");
+        assert_eq!(unified_diff("a", "b", 0), "@@ -1,1 +1,1 @@\n-a\n\\ No newline at end of file\n+b\n\\ No newline at end of file\n");
+
+        // Changes further apart than twice the context get their own hunks
+        let old = "1\n2\n3\n4\n5\n6\n7\n";
+        let new = "one\n2\n3\n4\n5\n6\nseven\n";
+        assert_eq!(unified_diff(old, new, 1), "@@ -1,2 +1,2 @@\n-1\n+one\n 2\n@@ -6,2 +6,2 @@\n 6\n-7\n+seven\n");
+        assert_eq!(unified_diff(old, new, 3).matches("@@ -").count(), 1);
+    }
+
+    #[test]
+    fn test_merge_takes_one_sided_changes() {
+        let base = "a\nb\nc\n";
+        let merged = merge(base, "a\nB\nc\n", "a\nb\nc\nd\n");
+        assert!(merged.is_clean());
+        assert_eq!(merged.content, "a\nB\nc\nd\n");
+
+        // Deletions are changes too
+        assert_eq!(merge(base, "a\nc\n", base).content, "a\nc\n");
+        assert_eq!(merge(base, base, "").content, "");
+    }
+
+    #[test]
+    fn test_merge_conflicts_without_trailing_newline() {
+        let merged = merge("a", "b", "c");
+        assert_eq!(merged.content, "<<<<<<< ours\nb\n=======\nc\n>>>>>>> theirs\n");
+        assert_eq!(
+            merged.conflicts,
+            [MergeConflict { line: 1, base: "a".to_string(), ours: "b".to_string(), theirs: "c".to_string() }]
+        );
+    }
+}
@@ -0,0 +1,319 @@
+//! Consistency checks between a template's Typst source and its schema
+//!
+//! The linter finds the variables bound to the injected data
+//! (`#let data = json.decode(sys.inputs.data)`), collects every field path
+//! accessed on them (`data.customer.name`, `data.at("customer")`) and
+//! compares those paths with the fields declared in the schema. Section
+//! fields count as used when the template names them in the section helpers.
+
+use std::collections::BTreeSet;
+
+use serde::Serialize;
+use typst::syntax::ast::{self, AstNode, Expr};
+use typst::syntax::{LinkedNode, SyntaxKind};
+
+use crate::schema::{FieldType, Schema};
+use crate::sections::{declared_sections, referenced_sections};
+use crate::template::Template;
+
+/// Kind of inconsistency found by the linter
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LintKind {
+    /// The template accesses a field the schema does not declare
+    UndeclaredField,
+    /// The schema declares a field the template never accesses
+    UnusedField,
+    /// The data is used as a whole (e.g. passed to a function), so unused
+    /// fields cannot be determined
+    OpaqueDataUse,
+}
+
+/// A single lint finding
+#[derive(Debug, Clone, Serialize)]
+pub struct LintIssue {
+    pub kind: LintKind,
+    /// Dotted field path, e.g. `customer.name`
+    pub path: String,
+    pub message: String,
+    /// Byte range in the template source, if the issue points at a usage
+    pub start: Option<usize>,
+    pub end: Option<usize>,
+}
+
+/// Result of linting a template
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LintReport {
+    pub issues: Vec<LintIssue>,
+}
+
+impl LintReport {
+    /// Whether the template and schema are fully consistent
+    pub fn is_clean(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    /// Issues of a given kind
+    pub fn of_kind(&self, kind: LintKind) -> impl Iterator<Item = &LintIssue> {
+        self.issues.iter().filter(move |issue| issue.kind == kind)
+    }
+}
+
+/// A data access found in the source
+#[derive(Debug)]
+struct DataAccess {
+    path: Vec<String>,
+    start: usize,
+    end: usize,
+}
+
+impl Template {
+    /// Cross-check data accesses in the Typst source against the schema
+    pub fn lint(&self) -> LintReport {
+        let root = typst::syntax::parse(&self.content);
+        let root = LinkedNode::new(&root);
+
+        let mut data_vars = BTreeSet::new();
+        find_data_vars(&root, &mut data_vars);
+        if data_vars.is_empty() {
+            data_vars.insert("data".to_string());
+        }
+
+        let mut accesses = Vec::new();
+        let mut opaque = Vec::new();
+        collect_accesses(&root, &data_vars, &mut accesses, &mut opaque);
+
+        let mut report = LintReport::default();
+        let mut used = BTreeSet::new();
+
+        for access in &accesses {
+            match resolve(&self.schema, &access.path) {
+                Ok(resolved) => mark_used(&self.schema, &access.path[..resolved], true, &mut used),
+                Err(missing) => {
+                    let path = access.path[..=missing].join(".");
+                    report.issues.push(LintIssue {
+                        kind: LintKind::UndeclaredField,
+                        message: format!("Field '{}' is used but not declared in the schema", path),
+                        path,
+                        start: Some(access.start),
+                        end: Some(access.end),
+                    });
+                    mark_used(&self.schema, &access.path[..missing], false, &mut used);
+                }
+            }
+        }
+
+        // Sections are used through the helpers rather than the data
+        let sections = declared_sections(&self.schema);
+        for (name, range) in referenced_sections(&self.content) {
+            if sections.contains(&name) {
+                let path: Vec<_> = name.split('.').map(str::to_string).collect();
+                mark_used(&self.schema, &path, false, &mut used);
+            } else {
+                report.issues.push(LintIssue {
+                    kind: LintKind::UndeclaredField,
+                    message: format!("Section '{}' is used but not declared in the schema", name),
+                    path: name,
+                    start: Some(range.start),
+                    end: Some(range.end),
+                });
+            }
+        }
+
+        if opaque.is_empty() {
+            let mut declared = Vec::new();
+            declared_paths(&self.schema, "", &mut declared);
+            for path in declared.into_iter().filter(|p| !used.contains(p)) {
+                report.issues.push(LintIssue {
+                    kind: LintKind::UnusedField,
+                    message: format!("Field '{}' is declared but never used", path),
+                    path,
+                    start: None,
+                    end: None,
+                });
+            }
+        } else {
+            for (start, end) in opaque {
+                report.issues.push(LintIssue {
+                    kind: LintKind::OpaqueDataUse,
+                    path: String::new(),
+                    message: "Data is used as a whole; unused fields cannot be determined".to_string(),
+                    start: Some(start),
+                    end: Some(end),
+                });
+            }
+        }
+
+        report
+    }
+}
+
+/// Find variables bound to `sys.inputs.data`, e.g. `#let data = json.decode(sys.inputs.data)`
+pub(crate) fn find_data_vars(node: &LinkedNode, vars: &mut BTreeSet<String>) {
+    if let Some(binding) = node.cast::<ast::LetBinding>() {
+        if let ast::LetBindingKind::Normal(ast::Pattern::Normal(Expr::Ident(ident))) = binding.kind() {
+            let reads_inputs = binding
+                .init()
+                .map(|init| init.to_untyped().clone().into_text().contains("sys.inputs.data"))
+                .unwrap_or(false);
+            if reads_inputs {
+                vars.insert(ident.get().to_string());
+            }
+        }
+    }
+
+    for child in node.children() {
+        find_data_vars(&child, vars);
+    }
+}
+
+/// Collect the outermost access chains rooted at a data variable
+fn collect_accesses(
+    node: &LinkedNode,
+    data_vars: &BTreeSet<String>,
+    accesses: &mut Vec<DataAccess>,
+    opaque: &mut Vec<(usize, usize)>,
+) {
+    let is_chain = node.kind() == SyntaxKind::FieldAccess
+        || node.cast::<ast::FuncCall>().is_some_and(|call| at_key(call).is_some());
+
+    if is_chain && !is_chain_link(node) {
+        if let Some(expr) = node.cast::<Expr>() {
+            if let Some((root, mut path)) = access_path(expr) {
+                if data_vars.contains(&root) {
+                    // `data.items.len()` calls a method on `data.items`
+                    if node.kind() == SyntaxKind::FieldAccess
+                        && node.parent_kind() == Some(SyntaxKind::FuncCall)
+                    {
+                        path.pop();
+                    }
+                    if path.is_empty() {
+                        opaque.push((node.offset(), node.range().end));
+                    } else {
+                        accesses.push(DataAccess {
+                            path,
+                            start: node.offset(),
+                            end: node.range().end,
+                        });
+                    }
+                }
+            }
+        }
+    } else if let Some(ident) = node.cast::<ast::Ident>() {
+        let standalone = !matches!(
+            node.parent_kind(),
+            Some(SyntaxKind::FieldAccess | SyntaxKind::LetBinding)
+        );
+        if standalone && data_vars.contains(ident.get().as_str()) {
+            opaque.push((node.offset(), node.range().end));
+        }
+    }
+
+    for child in node.children() {
+        collect_accesses(&child, data_vars, accesses, opaque);
+    }
+}
+
+/// Whether the node is an inner link of a longer access chain
+fn is_chain_link(node: &LinkedNode) -> bool {
+    match node.parent() {
+        Some(parent) if parent.kind() == SyntaxKind::FieldAccess => true,
+        Some(parent) => parent
+            .cast::<ast::FuncCall>()
+            .is_some_and(|call| at_key(call).is_some()),
+        None => false,
+    }
+}
+
+/// Resolve an access chain to its root variable and field path
+pub(crate) fn access_path(expr: Expr) -> Option<(String, Vec<String>)> {
+    match expr {
+        Expr::Ident(ident) => Some((ident.get().to_string(), Vec::new())),
+        Expr::FieldAccess(access) => {
+            let (root, mut path) = access_path(access.target())?;
+            path.push(access.field().get().to_string());
+            Some((root, path))
+        }
+        Expr::FuncCall(call) => {
+            let key = at_key(call)?;
+            let Expr::FieldAccess(callee) = call.callee() else {
+                return None;
+            };
+            let (root, mut path) = access_path(callee.target())?;
+            path.push(key);
+            Some((root, path))
+        }
+        _ => None,
+    }
+}
+
+/// The key of a `.at("key")` call with a string literal argument
+pub(crate) fn at_key(call: ast::FuncCall) -> Option<String> {
+    let Expr::FieldAccess(callee) = call.callee() else {
+        return None;
+    };
+    if callee.field().get().as_str() != "at" {
+        return None;
+    }
+    match call.args().items().next()? {
+        ast::Arg::Pos(Expr::Str(key)) => Some(key.get().to_string()),
+        _ => None,
+    }
+}
+
+/// Walk the schema along the path.
+///
+/// Returns how many segments were resolved to declared fields (the walk stops
+/// at non-object fields, whose further segments are methods or item fields),
+/// or the index of the first undeclared segment.
+fn resolve(schema: &Schema, path: &[String]) -> Result<usize, usize> {
+    let mut current = schema;
+    for (i, segment) in path.iter().enumerate() {
+        let field = current
+            .fields
+            .iter()
+            .find(|f| &f.key == segment)
+            .ok_or(i)?;
+        match &field.field_type {
+            FieldType::Object(sub_schema) => current = sub_schema,
+            _ => return Ok(i + 1),
+        }
+    }
+    Ok(path.len())
+}
+
+/// Mark a resolved path and its prefixes as used, plus all descendants when
+/// `whole` is set and the path ends at an object
+fn mark_used(schema: &Schema, path: &[String], whole: bool, used: &mut BTreeSet<String>) {
+    let mut current = Some(schema);
+    for i in 0..path.len() {
+        used.insert(path[..=i].join("."));
+        current = current
+            .and_then(|s| s.fields.iter().find(|f| f.key == path[i]))
+            .and_then(|f| match &f.field_type {
+                FieldType::Object(sub_schema) => Some(sub_schema.as_ref()),
+                _ => None,
+            });
+    }
+
+    if let (Some(sub_schema), true) = (current, whole && !path.is_empty()) {
+        let mut descendants = Vec::new();
+        declared_paths(sub_schema, &path.join("."), &mut descendants);
+        used.extend(descendants);
+    }
+}
+
+/// All declared field paths, descending into nested objects
+fn declared_paths(schema: &Schema, prefix: &str, paths: &mut Vec<String>) {
+    for field in &schema.fields {
+        let path = if prefix.is_empty() {
+            field.key.clone()
+        } else {
+            format!("{}.{}", prefix, field.key)
+        };
+        if let FieldType::Object(sub_schema) = &field.field_type {
+            declared_paths(sub_schema, &path, paths);
+        }
+        paths.push(path);
+    }
+}
@@ -0,0 +1,121 @@
+//! Locales: template variants and formatting inside templates
+//!
+//! A render's locale picks the template variant (see
+//! [`Template::variant`](crate::Template::variant)) and is exposed to the
+//! template as `sys.inputs.locale`. Templates format values for it by
+//! importing the built-in helpers:
+//!
+//! ```typst
+//! #import "papermake:locale.typ": format-number, format-currency, format-date
+//! #format-currency(1234.5, "EUR") // 1.234,50 € for de-DE
+//! ```
+
+use typst::foundations::{Dict, IntoValue};
+
+use crate::format::{DateOrder, LocaleFormat};
+
+/// Import path (without scheme) of the formatting helpers
+pub const LOCALE_MODULE_PATH: &str = "locale.typ";
+
+/// Typst source of the formatting helpers; reads the conventions injected
+/// by [`locale_inputs`] and falls back to `en-US`
+pub const LOCALE_MODULE: &str = r#"
+#let locale = sys.inputs.at("locale", default: "en-US")
+#let conventions = sys.inputs.at("locale-format", default: (
+  decimal: ".", group: ",", currency-prefix: true, date-order: "mdy", date-separator: "/",
+))
+
+#let currency-symbols = (EUR: "€", USD: "$", GBP: "£", JPY: "¥", INR: "₹")
+
+/// Format a number with grouped thousands and `decimals` fraction digits.
+#let format-number(value, decimals: 2) = {
+  let scaled = int(calc.round(calc.abs(value) * calc.pow(10, decimals)))
+  let digits = str(scaled)
+  while digits.len() <= decimals { digits = "0" + digits }
+  let integer = digits.slice(0, digits.len() - decimals)
+  let fraction = digits.slice(digits.len() - decimals)
+
+  let grouped = ""
+  for (i, digit) in integer.clusters().enumerate() {
+    if i > 0 and calc.rem(integer.len() - i, 3) == 0 { grouped += conventions.group }
+    grouped += digit
+  }
+
+  let result = if value < 0 and scaled != 0 { "-" + grouped } else { grouped }
+  if decimals > 0 { result += conventions.decimal + fraction }
+  result
+}
+
+/// Format an amount in an ISO 4217 currency.
+#let format-currency(value, currency) = {
+  let symbol = currency-symbols.at(currency, default: currency)
+  let amount = format-number(calc.abs(value), decimals: 2)
+  let sign = if value < 0 { "-" } else { "" }
+  if conventions.currency-prefix {
+    sign + symbol + amount
+  } else {
+    sign + amount + "\u{a0}" + symbol
+  }
+}
+
+/// Format an ISO 8601 date string (`2024-12-31`) or a datetime.
+#let format-date(value) = {
+  let (year, month, day) = if type(value) == datetime {
+    (str(value.year()), str(value.month()), str(value.day()))
+  } else {
+    value.slice(0, 10).split("-")
+  }
+  let pad(part) = if part.len() < 2 { "0" + part } else { part }
+  let sep = conventions.date-separator
+  if conventions.date-order == "dmy" {
+    pad(day) + sep + pad(month) + sep + year
+  } else if conventions.date-order == "ymd" {
+    year + sep + pad(month) + sep + pad(day)
+  } else {
+    pad(month) + sep + pad(day) + sep + year
+  }
+}
+"#;
+
+/// Entries added to `sys.inputs` for a locale: `locale` (the tag) and
+/// `locale-format` (the conventions used by the helper module)
+pub(crate) fn locale_inputs(locale: &str) -> Dict {
+    let format = LocaleFormat::for_locale(locale);
+    let date_order = match format.date_order {
+        DateOrder::DayMonthYear => "dmy",
+        DateOrder::MonthDayYear => "mdy",
+        DateOrder::YearMonthDay => "ymd",
+    };
+
+    let mut conventions = Dict::new();
+    conventions.insert("decimal".into(), format.decimal_separator.to_string().into_value());
+    conventions.insert("group".into(), format.group_separator.to_string().into_value());
+    conventions.insert("currency-prefix".into(), format.currency_prefix.into_value());
+    conventions.insert("date-order".into(), date_order.into_value());
+    conventions.insert("date-separator".into(), format.date_separator.to_string().into_value());
+
+    let mut inputs = Dict::new();
+    inputs.insert("locale".into(), locale.into_value());
+    inputs.insert("locale-format".into(), conventions.into_value());
+    inputs
+}
+
+/// Normalize a locale tag for comparison: `de_de` and `DE-de` become `de-de`
+fn normalize(locale: &str) -> String {
+    locale.trim().replace('_', "-").to_ascii_lowercase()
+}
+
+/// The best match for `requested` among `available` locales
+///
+/// Tries an exact match, then the bare language (`de` for `de-DE`), then
+/// any other region of the same language (`de-AT` for `de-DE`).
+pub fn resolve_locale<'a>(available: impl IntoIterator<Item = &'a str>, requested: &str) -> Option<&'a str> {
+    let requested = normalize(requested);
+    let language = requested.split('-').next().unwrap_or_default().to_string();
+    let available: Vec<&str> = available.into_iter().collect();
+
+    let find = |matches: &dyn Fn(&str) -> bool| available.iter().copied().find(|l| matches(&normalize(l)));
+    find(&|l| l == requested)
+        .or_else(|| find(&|l| l == language))
+        .or_else(|| find(&|l| l.split('-').next() == Some(language.as_str())))
+}
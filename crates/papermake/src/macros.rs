@@ -71,6 +71,7 @@ macro_rules! schema {
     (@type Number) => { $crate::FieldType::Number };
     (@type Boolean) => { $crate::FieldType::Boolean };
     (@type Date) => { $crate::FieldType::Date };
+    (@type Section) => { $crate::FieldType::Section };
 }
 
 #[cfg(test)]
@@ -0,0 +1,185 @@
+//! Rendering many records of a template into a single PDF
+
+use typst::introspection::Introspector;
+use typst::layout::PagedDocument;
+
+use crate::attachment::attach_files;
+use crate::encryption::encrypt_pdf;
+use crate::error::{PapermakeError, Result};
+use crate::metadata::DocumentMetadata;
+use crate::render::{
+    compile_prepared_template, optimize_output, pdf_options, prepared_data, RenderError, RenderOptions, RenderResult,
+};
+use crate::template::Template;
+use crate::text::extract_text;
+use crate::typst::TypstWorld;
+
+/// Render every record with the template and concatenate the pages into one PDF
+///
+/// All records share a single world, so the template source is parsed once.
+/// When `options.bookmark_field` is set, each record gets an entry in the PDF
+/// outline titled with that field's value. If any record fails to compile,
+/// no PDF is produced and the errors of all failing records are returned,
+/// prefixed with the record index.
+pub fn render_merged(
+    template: &Template,
+    records: &[serde_json::Value],
+    options: RenderOptions,
+) -> Result<RenderResult> {
+    if records.is_empty() {
+        return Err(PapermakeError::InvalidInput("No records to merge".to_string()));
+    }
+
+    let mut world = TypstWorld::new(template.content.clone(), "{}".to_string());
+    let mut pages = Vec::new();
+    let mut info = None;
+    let mut bookmarks = Vec::new();
+    let mut errors = Vec::new();
+    let mut warnings = Vec::new();
+    let mut prepared = Vec::with_capacity(records.len());
+
+    for (index, record) in records.iter().enumerate() {
+        let data = prepared_data(template, record, &options)?;
+        let compiled = compile_prepared_template(template, &data, Some(&mut world), &options)?;
+        prepared.push(data.into_owned());
+        warnings.extend(compiled.warnings.into_iter().map(|w| RenderError {
+            message: format!("Record {}: {}", index, w.message),
+            ..w
+        }));
+        match compiled.document {
+            Some(document) => {
+                if let Some(field) = &options.bookmark_field {
+                    bookmarks.push((bookmark_title(record, field, index), pages.len()));
+                }
+                info.get_or_insert(document.info);
+                pages.extend(document.pages);
+            }
+            None => errors.extend(compiled.errors.into_iter().map(|e| RenderError {
+                message: format!("Record {}: {}", index, e.message),
+                ..e
+            })),
+        }
+    }
+
+    if !errors.is_empty() {
+        return Ok(RenderResult { pdf: None, errors, warnings, cached: false, optimization: None, metadata: None, text: None });
+    }
+
+    for (index, page) in pages.iter_mut().enumerate() {
+        page.number = index + 1;
+    }
+
+    let document = PagedDocument {
+        introspector: Introspector::paged(&pages),
+        pages,
+        info: info.unwrap_or_default(),
+    };
+
+    let mut metadata = DocumentMetadata::from_document(&document);
+    if let Some(pages) = &options.pages {
+        pages.check(document.pages.len())?;
+        metadata = metadata.select(pages);
+    }
+    let mut pdf = typst_pdf::pdf(&document, &pdf_options(template, &options))
+        .map_err(|e| PapermakeError::Rendering(format!("PDF export failed: {:?}", e)))?;
+
+    // Bookmarks point at the exported pages; records starting on an
+    // unselected page lose theirs
+    if let Some(selection) = &options.pages {
+        bookmarks = bookmarks
+            .into_iter()
+            .filter(|(_, page)| selection.contains(page + 1))
+            .map(|(title, page)| (title, (1..=page).filter(|p| selection.contains(*p)).count()))
+            .collect();
+    }
+    if !bookmarks.is_empty() {
+        pdf = add_bookmarks(&pdf, &bookmarks)?;
+    }
+
+    // Attached input data holds all prepared records
+    if !options.attachments.is_empty() {
+        pdf = attach_files(&pdf, &options.attachments, &serde_json::Value::Array(prepared))?;
+    }
+
+    let (mut pdf, optimization) = optimize_output(pdf, options.optimize)?;
+    if let Some(encryption) = &options.encryption {
+        pdf = encrypt_pdf(&pdf, encryption)?;
+    }
+
+    Ok(RenderResult {
+        pdf: Some(pdf),
+        errors,
+        warnings,
+        cached: false,
+        optimization,
+        metadata: Some(metadata),
+        text: options.extract_text.then(|| extract_text(&document, options.pages.as_ref())),
+    })
+}
+
+/// Title for a record's bookmark: the field's value, or "Record N" if missing
+///
+/// Fields starting with `/` are treated as JSON pointers into the record.
+fn bookmark_title(record: &serde_json::Value, field: &str, index: usize) -> String {
+    let value = if field.starts_with('/') {
+        record.pointer(field)
+    } else {
+        record.get(field)
+    };
+
+    match value {
+        Some(serde_json::Value::String(s)) => s.clone(),
+        Some(serde_json::Value::Null) | None => format!("Record {}", index + 1),
+        Some(other) => other.to_string(),
+    }
+}
+
+/// Replace the PDF outline with one entry per `(title, first page index)`
+fn add_bookmarks(pdf: &[u8], bookmarks: &[(String, usize)]) -> Result<Vec<u8>> {
+    use lopdf::{dictionary, Document, Object, StringFormat};
+
+    let pdf_error = |e: lopdf::Error| PapermakeError::Rendering(format!("Failed to add bookmarks: {}", e));
+
+    let mut doc = Document::load_mem(pdf).map_err(pdf_error)?;
+    let page_ids: Vec<_> = doc.get_pages().into_values().collect();
+
+    let outlines_id = doc.new_object_id();
+    let item_ids: Vec<_> = bookmarks.iter().map(|_| doc.new_object_id()).collect();
+
+    for (i, ((title, page), id)) in bookmarks.iter().zip(&item_ids).enumerate() {
+        // Outline titles are text strings: UTF-16BE with a byte order mark
+        let title_bytes = [0xFE, 0xFF]
+            .into_iter()
+            .chain(title.encode_utf16().flat_map(u16::to_be_bytes))
+            .collect();
+
+        let mut item = dictionary! {
+            "Title" => Object::String(title_bytes, StringFormat::Hexadecimal),
+            "Parent" => outlines_id,
+            "Dest" => vec![page_ids[*page].into(), "Fit".into()],
+        };
+        if i > 0 {
+            item.set("Prev", item_ids[i - 1]);
+        }
+        if let Some(next) = item_ids.get(i + 1) {
+            item.set("Next", *next);
+        }
+        doc.objects.insert(*id, Object::Dictionary(item));
+    }
+
+    doc.objects.insert(
+        outlines_id,
+        Object::Dictionary(dictionary! {
+            "Type" => "Outlines",
+            "First" => item_ids[0],
+            "Last" => item_ids[item_ids.len() - 1],
+            "Count" => item_ids.len() as i64,
+        }),
+    );
+    doc.catalog_mut().map_err(pdf_error)?.set("Outlines", outlines_id);
+
+    let mut output = Vec::new();
+    doc.save_to(&mut output)
+        .map_err(|e| PapermakeError::Rendering(format!("Failed to write merged PDF: {}", e)))?;
+    Ok(output)
+}
@@ -0,0 +1,121 @@
+//! Document structure extracted from rendered documents
+//!
+//! Every successful render reports, in `RenderResult::metadata`, the page
+//! count, the outline (the headings that become PDF bookmarks) and the
+//! labeled elements, which typst exports as named destinations. Downstream
+//! systems can deep-link to `document.pdf#nameddest=<name>` or display page
+//! counts without parsing the PDF.
+
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+use typst::foundations::{Content, NativeElement, StyleChain};
+use typst::layout::PagedDocument;
+use typst::model::HeadingElem;
+
+use crate::pdf_ops::PageSelection;
+
+/// Structure of a rendered document
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DocumentMetadata {
+    pub page_count: usize,
+    /// Headings in document order, as they appear in the PDF outline
+    pub outline: Vec<OutlineEntry>,
+    /// Labeled elements, e.g. `<totals>`, in document order
+    pub destinations: Vec<Destination>,
+}
+
+/// A heading of the outline
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OutlineEntry {
+    pub title: String,
+    /// Nesting level, starting at 1
+    pub level: usize,
+    /// 1-based page number in the PDF
+    pub page: usize,
+    /// Distance from the top of the page, in points
+    pub y: f64,
+    /// Label of the heading, if it has one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+}
+
+/// A named destination, reachable as `#nameddest=<name>`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Destination {
+    /// The label without angle brackets
+    pub name: String,
+    /// 1-based page number in the PDF
+    pub page: usize,
+    /// Distance from the top of the page, in points
+    pub y: f64,
+}
+
+impl DocumentMetadata {
+    /// Extract the structure of a compiled document
+    pub(crate) fn from_document(document: &PagedDocument) -> Self {
+        let introspector = &document.introspector;
+        let locate = |content: &Content| {
+            let position = introspector.position(content.location()?);
+            Some((position.page.get(), position.point.y.to_pt()))
+        };
+
+        let outline = introspector
+            .query(&HeadingElem::elem().select())
+            .iter()
+            .filter_map(|content| {
+                let heading = content.to_packed::<HeadingElem>()?;
+                // Same rule as typst's PDF export for bookmarks
+                let bookmarked = heading
+                    .bookmarked(StyleChain::default())
+                    .unwrap_or_else(|| heading.outlined(StyleChain::default()));
+                if !bookmarked {
+                    return None;
+                }
+                let (page, y) = locate(content)?;
+                Some(OutlineEntry {
+                    title: heading.body.plain_text().trim().to_string(),
+                    level: heading.resolve_level(StyleChain::default()).get(),
+                    page,
+                    y,
+                    label: content.label().map(|label| label.resolve().as_str().to_string()),
+                })
+            })
+            .collect();
+
+        // Typst only exports destinations for labels that are unique
+        let mut seen = HashSet::new();
+        let mut duplicates = HashSet::new();
+        for label in introspector.all().filter_map(Content::label) {
+            if !seen.insert(label) {
+                duplicates.insert(label);
+            }
+        }
+        let destinations = introspector
+            .all()
+            .filter_map(|content| {
+                let label = content.label().filter(|label| !duplicates.contains(label))?;
+                let (page, y) = locate(content)?;
+                Some(Destination { name: label.resolve().as_str().to_string(), page, y })
+            })
+            .collect();
+
+        Self { page_count: document.pages.len(), outline, destinations }
+    }
+
+    /// Restrict to the pages exported with `RenderOptions::pages`,
+    /// renumbering them as they appear in the PDF
+    pub(crate) fn select(mut self, selection: &PageSelection) -> Self {
+        let renumber = |page: usize| (1..=page).filter(|p| selection.contains(*p)).count();
+        self.outline.retain(|entry| selection.contains(entry.page));
+        for entry in &mut self.outline {
+            entry.page = renumber(entry.page);
+        }
+        self.destinations.retain(|destination| selection.contains(destination.page));
+        for destination in &mut self.destinations {
+            destination.page = renumber(destination.page);
+        }
+        self.page_count = selection.count(self.page_count);
+        self
+    }
+}
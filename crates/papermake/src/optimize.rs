@@ -0,0 +1,283 @@
+//! Size optimization of rendered PDFs
+//!
+//! Typst already embeds fonts subsetted to the glyphs a document uses, but
+//! writes the PDF for speed rather than size: images are kept at their full
+//! resolution and every object is written individually and uncompressed.
+//! Setting `RenderOptions::optimize` rewrites the PDF after export:
+//!
+//! - identical streams and font dictionaries, e.g. from attachments or
+//!   post-processing, are stored once,
+//! - unreferenced objects are dropped and uncompressed streams compressed,
+//! - objects are packed into compressed object streams (PDF 1.5),
+//! - at [`OptimizeLevel::Balanced`] and above, images larger than a pixel
+//!   threshold are downsampled.
+//!
+//! Sizes before and after are reported in `RenderResult::optimization`.
+
+use std::collections::{BTreeMap, HashMap};
+
+use image::{DynamicImage, GrayImage, ImageFormat, RgbImage};
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use lopdf::{Dictionary, Document, Object, ObjectId, Stream};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::error::{PapermakeError, Result};
+
+/// How much effort goes into shrinking a PDF
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OptimizeLevel {
+    /// Keep the PDF as exported
+    #[default]
+    None,
+    /// Deduplicate, prune and compress without changing any content
+    Lossless,
+    /// Lossless, and downsample images above 2400 pixels on their long edge
+    Balanced,
+    /// Lossless, and downsample images above 1200 pixels on their long edge
+    /// with stronger JPEG compression
+    Aggressive,
+}
+
+impl OptimizeLevel {
+    /// Longest image edge in pixels kept as is, and the JPEG quality of
+    /// downsampled photos
+    fn image_threshold(self) -> Option<(u32, u8)> {
+        match self {
+            OptimizeLevel::None | OptimizeLevel::Lossless => None,
+            OptimizeLevel::Balanced => Some((2400, 85)),
+            OptimizeLevel::Aggressive => Some((1200, 70)),
+        }
+    }
+}
+
+/// Sizes of a PDF before and after optimization
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OptimizationReport {
+    pub original_bytes: usize,
+    pub optimized_bytes: usize,
+    /// Streams and font dictionaries removed as duplicates
+    pub deduplicated_objects: usize,
+    pub downsampled_images: usize,
+}
+
+/// Optimize a PDF, keeping the original if optimizing doesn't shrink it
+pub fn optimize_pdf(pdf: Vec<u8>, level: OptimizeLevel) -> Result<(Vec<u8>, OptimizationReport)> {
+    let pdf_error = |e: lopdf::Error| PapermakeError::Rendering(format!("Failed to optimize PDF: {}", e));
+    let original_bytes = pdf.len();
+    let mut report = OptimizationReport {
+        original_bytes,
+        optimized_bytes: original_bytes,
+        deduplicated_objects: 0,
+        downsampled_images: 0,
+    };
+    if level == OptimizeLevel::None {
+        return Ok((pdf, report));
+    }
+
+    let mut doc = Document::load_mem(&pdf).map_err(pdf_error)?;
+    if let Some((max_pixels, quality)) = level.image_threshold() {
+        report.downsampled_images = downsample_images(&mut doc, max_pixels, quality);
+    }
+    report.deduplicated_objects = deduplicate(&mut doc);
+    doc.prune_objects();
+    doc.delete_zero_length_streams();
+    doc.renumber_objects();
+    doc.compress();
+
+    let mut optimized = Vec::new();
+    doc.save_modern(&mut optimized).map_err(pdf_error)?;
+    if optimized.len() >= original_bytes {
+        return Ok((pdf, report));
+    }
+    report.optimized_bytes = optimized.len();
+    Ok((optimized, report))
+}
+
+/// Store identical streams and font dictionaries once, returning how many
+/// objects were dropped
+///
+/// Font dictionaries only become identical once their font programs are
+/// merged, so passes repeat until nothing changes.
+fn deduplicate(doc: &mut Document) -> usize {
+    let mut removed = 0;
+    loop {
+        let mut canonical: HashMap<Vec<u8>, ObjectId> = HashMap::new();
+        let mut duplicates = HashMap::new();
+        for (id, object) in &doc.objects {
+            let Some(digest) = dedup_digest(object) else {
+                continue;
+            };
+            match canonical.get(&digest) {
+                Some(first) => {
+                    duplicates.insert(*id, *first);
+                }
+                None => {
+                    canonical.insert(digest, *id);
+                }
+            }
+        }
+        if duplicates.is_empty() {
+            return removed;
+        }
+
+        removed += duplicates.len();
+        for id in duplicates.keys() {
+            doc.objects.remove(id);
+        }
+        for object in doc.objects.values_mut() {
+            replace_references(object, &duplicates);
+        }
+        replace_references_in_dict(&mut doc.trailer, &duplicates);
+    }
+}
+
+/// Digest of an object that may be shared, or `None` for objects whose
+/// identity matters (pages, the catalog, ...)
+fn dedup_digest(object: &Object) -> Option<Vec<u8>> {
+    let (dict, content) = match object {
+        Object::Stream(stream) => (&stream.dict, stream.content.as_slice()),
+        Object::Dictionary(dict) => {
+            let shareable = matches!(dict.get(b"Type").and_then(Object::as_name), Ok(b"Font" | b"FontDescriptor"));
+            if !shareable {
+                return None;
+            }
+            (dict, &[][..])
+        }
+        _ => return None,
+    };
+    let mut hasher = Sha256::new();
+    // Dictionaries keep their insertion order, which identical objects share
+    hasher.update(format!("{:?}", dict).as_bytes());
+    hasher.update(content);
+    Some(hasher.finalize().to_vec())
+}
+
+fn replace_references(object: &mut Object, replacements: &HashMap<ObjectId, ObjectId>) {
+    match object {
+        Object::Reference(id) => {
+            if let Some(replacement) = replacements.get(id) {
+                *id = *replacement;
+            }
+        }
+        Object::Array(items) => {
+            for item in items {
+                replace_references(item, replacements);
+            }
+        }
+        Object::Dictionary(dict) => replace_references_in_dict(dict, replacements),
+        Object::Stream(stream) => replace_references_in_dict(&mut stream.dict, replacements),
+        _ => {}
+    }
+}
+
+fn replace_references_in_dict(dict: &mut Dictionary, replacements: &HashMap<ObjectId, ObjectId>) {
+    for (_, value) in dict.iter_mut() {
+        replace_references(value, replacements);
+    }
+}
+
+/// Downsample images whose long edge exceeds `max_pixels`, returning how
+/// many were changed
+///
+/// Only 8-bit gray and RGB images stored as JPEG or deflated samples are
+/// touched; anything else is left as exported.
+fn downsample_images(doc: &mut Document, max_pixels: u32, quality: u8) -> usize {
+    let components: BTreeMap<ObjectId, u8> = doc
+        .objects
+        .iter()
+        .filter_map(|(id, object)| {
+            let Object::Stream(stream) = object else {
+                return None;
+            };
+            let dict = &stream.dict;
+            if dict.get(b"Subtype").and_then(Object::as_name).ok()? != b"Image" {
+                return None;
+            }
+            let (width, height) = image_size(dict)?;
+            if width.max(height) <= max_pixels || dict.get(b"BitsPerComponent").and_then(Object::as_i64).ok()? != 8 {
+                return None;
+            }
+            color_components(doc, dict).map(|components| (*id, components))
+        })
+        .collect();
+
+    let mut downsampled = 0;
+    for (id, components) in components {
+        let Some(Object::Stream(stream)) = doc.objects.get_mut(&id) else {
+            continue;
+        };
+        if downsample(stream, components, max_pixels, quality).is_some() {
+            downsampled += 1;
+        }
+    }
+    downsampled
+}
+
+fn image_size(dict: &Dictionary) -> Option<(u32, u32)> {
+    let width = dict.get(b"Width").and_then(Object::as_i64).ok()?;
+    let height = dict.get(b"Height").and_then(Object::as_i64).ok()?;
+    Some((u32::try_from(width).ok()?, u32::try_from(height).ok()?))
+}
+
+/// Number of color components of a gray or RGB image
+fn color_components(doc: &Document, dict: &Dictionary) -> Option<u8> {
+    let color_space = match dict.get(b"ColorSpace").ok()? {
+        Object::Reference(id) => doc.get_object(*id).ok()?,
+        color_space => color_space,
+    };
+    let components = match color_space {
+        Object::Name(name) if name == b"DeviceGray" => 1,
+        Object::Name(name) if name == b"DeviceRGB" => 3,
+        // [/ICCBased <profile>], whose stream gives the component count
+        Object::Array(items) if items.first().and_then(|name| name.as_name().ok()) == Some(&b"ICCBased"[..]) => {
+            let profile = doc.get_object(items.get(1)?.as_reference().ok()?).ok()?;
+            profile.as_stream().ok()?.dict.get(b"N").and_then(Object::as_i64).ok()?
+        }
+        _ => return None,
+    };
+    matches!(components, 1 | 3).then_some(components as u8)
+}
+
+fn downsample(stream: &mut Stream, components: u8, max_pixels: u32, quality: u8) -> Option<()> {
+    let (width, height) = image_size(&stream.dict)?;
+    let scale = f64::from(max_pixels) / f64::from(width.max(height));
+    let new_width = ((f64::from(width) * scale).round() as u32).max(1);
+    let new_height = ((f64::from(height) * scale).round() as u32).max(1);
+
+    let filters: Vec<Vec<u8>> = stream.filters().ok()?.into_iter().map(<[u8]>::to_vec).collect();
+    if stream.dict.has(b"DecodeParms") {
+        return None;
+    }
+    match filters.as_slice() {
+        [filter] if filter == b"DCTDecode" => {
+            let decoded = image::load_from_memory_with_format(&stream.content, ImageFormat::Jpeg).ok()?;
+            let resized = decoded.resize_exact(new_width, new_height, FilterType::Triangle);
+            let resized = match components {
+                1 => DynamicImage::ImageLuma8(resized.to_luma8()),
+                _ => DynamicImage::ImageRgb8(resized.to_rgb8()),
+            };
+            let mut jpeg = Vec::new();
+            JpegEncoder::new_with_quality(&mut jpeg, quality).encode_image(&resized).ok()?;
+            stream.set_content(jpeg);
+        }
+        [filter] if filter == b"FlateDecode" => {
+            let samples = stream.decompressed_content().ok()?;
+            let decoded = match components {
+                1 => DynamicImage::ImageLuma8(GrayImage::from_raw(width, height, samples)?),
+                _ => DynamicImage::ImageRgb8(RgbImage::from_raw(width, height, samples)?),
+            };
+            let resized = decoded.resize_exact(new_width, new_height, FilterType::Triangle);
+            stream.dict.remove(b"Filter");
+            stream.set_content(resized.into_bytes());
+            // Uncompressed samples are still valid, just larger
+            let _ = stream.compress();
+        }
+        _ => return None,
+    }
+    stream.dict.set("Width", i64::from(new_width));
+    stream.dict.set("Height", i64::from(new_height));
+    Some(())
+}
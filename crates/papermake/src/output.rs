@@ -0,0 +1,258 @@
+//! Rendering to formats other than PDF
+//!
+//! [`render`] renders a template to any [`OutputFormat`]. PNG and SVG
+//! produce one file per page; PDF and HTML produce a single file. HTML uses
+//! Typst's experimental HTML export and needs the `html` feature.
+//!
+//! [`render_all`] compiles once and exports several formats from the same
+//! document, e.g. a PDF with page thumbnails, into a [`RenderBundle`].
+//!
+//! PNG pages are rasterized at [`PNG_PIXEL_PER_PT`]; see
+//! [`crate::preview`] for other resolutions and image formats.
+
+use serde::{Deserialize, Serialize};
+use typst::layout::PagedDocument;
+
+use crate::error::{PapermakeError, Result};
+use crate::metadata::DocumentMetadata;
+use crate::optimize::OptimizationReport;
+use crate::preview::{rasterize, PreviewOptions};
+use crate::render::{compile_prepared_template, compile_template, export_pdf, prepared_data, render_pdf, RenderError, RenderOptions};
+use crate::template::Template;
+use crate::text::{extract_text, PageText};
+use crate::trace::render_phase;
+
+/// Resolution of PNG output in pixels per point (144 DPI)
+pub const PNG_PIXEL_PER_PT: f32 = 2.0;
+
+/// Format a template is rendered to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    #[default]
+    Pdf,
+    Png,
+    Svg,
+    Html,
+}
+
+impl OutputFormat {
+    /// MIME type of files in this format
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            OutputFormat::Pdf => "application/pdf",
+            OutputFormat::Png => "image/png",
+            OutputFormat::Svg => "image/svg+xml",
+            OutputFormat::Html => "text/html; charset=utf-8",
+        }
+    }
+
+    /// File extension, without the dot
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Pdf => "pdf",
+            OutputFormat::Png => "png",
+            OutputFormat::Svg => "svg",
+            OutputFormat::Html => "html",
+        }
+    }
+}
+
+impl std::str::FromStr for OutputFormat {
+    type Err = PapermakeError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "pdf" => Ok(OutputFormat::Pdf),
+            "png" => Ok(OutputFormat::Png),
+            "svg" => Ok(OutputFormat::Svg),
+            "html" => Ok(OutputFormat::Html),
+            other => Err(PapermakeError::InvalidInput(format!(
+                "Unknown output format '{}', expected 'pdf', 'png', 'svg' or 'html'",
+                other
+            ))),
+        }
+    }
+}
+
+/// Result of rendering to an [`OutputFormat`]
+#[derive(Debug, Serialize)]
+pub struct RenderOutput {
+    pub format: OutputFormat,
+    /// The rendered files: one per selected page for PNG and SVG, a single
+    /// one otherwise; empty if compilation failed
+    pub files: Vec<Vec<u8>>,
+    pub errors: Vec<RenderError>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<RenderError>,
+}
+
+impl RenderOutput {
+    fn failed(format: OutputFormat, errors: Vec<RenderError>, warnings: Vec<RenderError>) -> Self {
+        Self { format, files: Vec::new(), errors, warnings }
+    }
+}
+
+/// Render a template with data to the given format
+pub fn render(
+    template: &Template,
+    data: &serde_json::Value,
+    format: OutputFormat,
+    options: Option<RenderOptions>,
+) -> Result<RenderOutput> {
+    match format {
+        OutputFormat::Pdf => {
+            let result = render_pdf(template, data, options)?;
+            Ok(RenderOutput {
+                format,
+                files: result.pdf.into_iter().collect(),
+                errors: result.errors,
+                warnings: result.warnings,
+            })
+        }
+        OutputFormat::Png | OutputFormat::Svg => {
+            let options = options.unwrap_or_default();
+            let compiled = compile_template(template, data, None, &options)?;
+            let Some(document) = compiled.document else {
+                return Ok(RenderOutput::failed(format, compiled.errors, compiled.warnings));
+            };
+
+            if let Some(pages) = &options.pages {
+                pages.check(document.pages.len())?;
+            }
+            let files = export_pages(&document, format, &options)?;
+            Ok(RenderOutput { format, files, errors: Vec::new(), warnings: compiled.warnings })
+        }
+        #[cfg(feature = "html")]
+        OutputFormat::Html => render_html(template, data, options),
+        #[cfg(not(feature = "html"))]
+        OutputFormat::Html => {
+            let _ = options;
+            Err(PapermakeError::InvalidInput(
+                "HTML output requires papermake's `html` feature".to_string(),
+            ))
+        }
+    }
+}
+
+/// Export the selected pages of a document to PNG or SVG, one file each
+fn export_pages(document: &PagedDocument, format: OutputFormat, options: &RenderOptions) -> Result<Vec<Vec<u8>>> {
+    document
+        .pages
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| options.pages.as_ref().is_none_or(|pages| pages.contains(i + 1)))
+        .map(|(_, page)| match format {
+            OutputFormat::Png => rasterize(page, &PreviewOptions::default()).map(|(png, _, _)| png),
+            _ => Ok(typst_svg::svg(page).into_bytes()),
+        })
+        .collect()
+}
+
+/// Everything exported from a single compilation by [`render_all`]
+#[derive(Debug, Default, Serialize)]
+pub struct RenderBundle {
+    /// The PDF, if requested; post-processed like [`render_pdf`] output
+    pub pdf: Option<Vec<u8>>,
+    /// One PNG per selected page, if requested
+    pub png: Vec<Vec<u8>>,
+    /// One SVG per selected page, if requested
+    pub svg: Vec<Vec<u8>>,
+    /// Positioned text of each selected page, always extracted
+    pub text: Vec<PageText>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<DocumentMetadata>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub optimization: Option<OptimizationReport>,
+    pub errors: Vec<RenderError>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<RenderError>,
+}
+
+impl RenderBundle {
+    /// Whether the document compiled; nothing is exported otherwise
+    pub fn is_success(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// Text of all selected pages, separated by newlines
+    pub fn full_text(&self) -> String {
+        self.text.iter().map(|page| page.text.as_str()).collect::<Vec<_>>().join("\n")
+    }
+}
+
+/// Render a template once and export it to each of the given formats
+///
+/// PDF, PNG and SVG are exported from the same compiled document, and the
+/// text of each page is extracted alongside, so producing a PDF with page
+/// thumbnails costs one compilation instead of one per format. HTML needs
+/// a differently compiled document and can't be part of a bundle.
+pub fn render_all(
+    template: &Template,
+    data: &serde_json::Value,
+    formats: &[OutputFormat],
+    options: Option<RenderOptions>,
+) -> Result<RenderBundle> {
+    if formats.contains(&OutputFormat::Html) {
+        return Err(PapermakeError::InvalidInput(
+            "HTML output can't be rendered together with other formats".to_string(),
+        ));
+    }
+
+    let options = options.unwrap_or_default();
+    let render = render_phase(template, &options);
+    let data = prepared_data(template, data, &options)?;
+    let compiled = compile_prepared_template(template, &data, None, &options)?;
+    render.record("success", compiled.document.is_some());
+    let Some(document) = compiled.document else {
+        return Ok(RenderBundle { errors: compiled.errors, warnings: compiled.warnings, ..RenderBundle::default() });
+    };
+
+    let mut bundle = RenderBundle { warnings: compiled.warnings, ..RenderBundle::default() };
+    let mut metadata = DocumentMetadata::from_document(&document);
+    if let Some(pages) = &options.pages {
+        pages.check(document.pages.len())?;
+        metadata = metadata.select(pages);
+    }
+    bundle.metadata = Some(metadata);
+    bundle.text = extract_text(&document, options.pages.as_ref());
+
+    if formats.contains(&OutputFormat::Pdf) {
+        let (pdf, optimization) = export_pdf(&document, template, &data, &options)?;
+        bundle.pdf = Some(pdf);
+        bundle.optimization = optimization;
+    }
+    if formats.contains(&OutputFormat::Png) {
+        bundle.png = export_pages(&document, OutputFormat::Png, &options)?;
+    }
+    if formats.contains(&OutputFormat::Svg) {
+        bundle.svg = export_pages(&document, OutputFormat::Svg, &options)?;
+    }
+    Ok(bundle)
+}
+
+/// Render a template with data to an HTML page
+#[cfg(feature = "html")]
+pub fn render_html(
+    template: &Template,
+    data: &serde_json::Value,
+    options: Option<RenderOptions>,
+) -> Result<RenderOutput> {
+    use typst::html::HtmlDocument;
+
+    let compiled = crate::render::compile::<HtmlDocument>(template, data, None, &options.unwrap_or_default())?;
+    let Some(document) = compiled.document else {
+        return Ok(RenderOutput::failed(OutputFormat::Html, compiled.errors, compiled.warnings));
+    };
+
+    let html = typst_html::html(&document).map_err(|diagnostics| {
+        let messages: Vec<_> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        PapermakeError::Rendering(format!("HTML export failed: {}", messages.join("; ")))
+    })?;
+    Ok(RenderOutput {
+        format: OutputFormat::Html,
+        files: vec![html.into_bytes()],
+        errors: Vec::new(),
+        warnings: compiled.warnings,
+    })
+}
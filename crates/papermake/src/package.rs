@@ -0,0 +1,145 @@
+//! Portable template packages (gzipped tar archives)
+//!
+//! Archive layout:
+//! ```text
+//! package.json    # package format version
+//! template.json   # template metadata (id, name, examples, timestamps, ...)
+//! main.typ        # Typst content
+//! schema.json     # data schema
+//! files/...       # asset files (images, fonts, includes)
+//! ```
+
+use std::collections::BTreeMap;
+use std::io::Read;
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{PapermakeError, Result};
+use crate::schema::Schema;
+use crate::storage::validate_file_path;
+use crate::template::Template;
+
+/// Current version of the package format
+pub const PACKAGE_FORMAT_VERSION: u32 = 1;
+
+const FILES_PREFIX: &str = "files/";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PackageInfo {
+    format_version: u32,
+    papermake_version: String,
+}
+
+/// A template together with its asset files
+#[derive(Debug, Clone)]
+pub struct TemplatePackage {
+    pub template: Template,
+    pub files: BTreeMap<String, Vec<u8>>,
+}
+
+impl Template {
+    /// Export the template and its asset files as a gzipped tar archive
+    pub fn export_package(&self, files: &BTreeMap<String, Vec<u8>>) -> Result<Vec<u8>> {
+        let mut metadata = self.clone();
+        metadata.content = String::new();
+        metadata.schema = Schema::default();
+
+        let info = PackageInfo {
+            format_version: PACKAGE_FORMAT_VERSION,
+            papermake_version: crate::version().to_string(),
+        };
+
+        let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+        let mtime = self.updated_at.unix_timestamp().max(0) as u64;
+
+        append(&mut builder, "package.json", &to_json(&info)?, mtime)?;
+        append(&mut builder, "template.json", &to_json(&metadata)?, mtime)?;
+        append(&mut builder, "main.typ", self.content.as_bytes(), mtime)?;
+        append(&mut builder, "schema.json", &to_json(&self.schema)?, mtime)?;
+        for (path, content) in files {
+            validate_file_path(path)?;
+            append(&mut builder, &format!("{}{}", FILES_PREFIX, path), content, mtime)?;
+        }
+
+        let encoder = builder.into_inner()?;
+        Ok(encoder.finish()?)
+    }
+
+    /// Import a template and its asset files from a package archive
+    pub fn import_package(bytes: &[u8]) -> Result<TemplatePackage> {
+        let mut archive = tar::Archive::new(GzDecoder::new(bytes));
+
+        let mut info = None;
+        let mut template: Option<Template> = None;
+        let mut content = None;
+        let mut schema = None;
+        let mut files = BTreeMap::new();
+
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+
+            let path = entry.path()?.to_string_lossy().replace('\\', "/");
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data)?;
+
+            match path.as_str() {
+                "package.json" => info = Some(from_json::<PackageInfo>(&data, &path)?),
+                "template.json" => template = Some(from_json(&data, &path)?),
+                "main.typ" => {
+                    content = Some(String::from_utf8(data).map_err(|_| {
+                        PapermakeError::InvalidInput("main.typ is not valid UTF-8".to_string())
+                    })?)
+                }
+                "schema.json" => schema = Some(from_json(&data, &path)?),
+                other => {
+                    if let Some(file_path) = other.strip_prefix(FILES_PREFIX) {
+                        validate_file_path(file_path)?;
+                        files.insert(file_path.to_string(), data);
+                    }
+                }
+            }
+        }
+
+        let info = info.ok_or_else(|| missing("package.json"))?;
+        if info.format_version > PACKAGE_FORMAT_VERSION {
+            return Err(PapermakeError::InvalidInput(format!(
+                "Unsupported package format version {} (supported up to {})",
+                info.format_version, PACKAGE_FORMAT_VERSION
+            )));
+        }
+
+        let mut template = template.ok_or_else(|| missing("template.json"))?;
+        template.content = content.ok_or_else(|| missing("main.typ"))?;
+        template.schema = schema.unwrap_or_default();
+
+        Ok(TemplatePackage { template, files })
+    }
+}
+
+fn append<W: std::io::Write>(builder: &mut tar::Builder<W>, path: &str, data: &[u8], mtime: u64) -> Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(mtime);
+    builder.append_data(&mut header, path, data)?;
+    Ok(())
+}
+
+fn to_json<T: Serialize>(value: &T) -> Result<Vec<u8>> {
+    serde_json::to_vec_pretty(value).map_err(|e| PapermakeError::Template(e.to_string()))
+}
+
+fn from_json<T: for<'de> Deserialize<'de>>(data: &[u8], path: &str) -> Result<T> {
+    serde_json::from_slice(data)
+        .map_err(|e| PapermakeError::InvalidInput(format!("Invalid {} in package: {}", path, e)))
+}
+
+fn missing(path: &str) -> PapermakeError {
+    PapermakeError::InvalidInput(format!("Package is missing {}", path))
+}
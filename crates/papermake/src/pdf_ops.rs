@@ -0,0 +1,164 @@
+//! Page selection and post-processing of rendered PDFs
+//!
+//! [`PageSelection`] picks the pages a render exports, via
+//! `RenderOptions::pages`. The functions below work on finished PDFs, e.g.
+//! to split a rendered document into one PDF per page.
+
+use std::fmt;
+use std::num::NonZeroUsize;
+
+use lopdf::Document;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{PapermakeError, Result};
+
+/// A set of 1-based page ranges such as `1-3,5,8-`
+///
+/// An open range like `8-` runs to the last page.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct PageSelection {
+    ranges: Vec<(usize, Option<usize>)>,
+}
+
+impl PageSelection {
+    /// A single range of pages, inclusive; `None` runs to the last page
+    pub fn range(first: usize, last: Option<usize>) -> Result<Self> {
+        if first == 0 || last.is_some_and(|last| last < first) {
+            return Err(invalid(&format!("{}-{}", first, last.map(|l| l.to_string()).unwrap_or_default())));
+        }
+        Ok(Self { ranges: vec![(first, last)] })
+    }
+
+    /// Whether the 1-based page number is selected
+    pub fn contains(&self, page: usize) -> bool {
+        self.ranges
+            .iter()
+            .any(|(first, last)| page >= *first && last.is_none_or(|last| page <= last))
+    }
+
+    /// Number of selected pages in a document of `total` pages
+    pub fn count(&self, total: usize) -> usize {
+        (1..=total).filter(|page| self.contains(*page)).count()
+    }
+
+    /// Fail if no page of a document of `total` pages is selected
+    pub(crate) fn check(&self, total: usize) -> Result<()> {
+        if self.count(total) == 0 {
+            return Err(PapermakeError::InvalidInput(format!(
+                "Page selection '{}' matches none of the {} pages",
+                self, total
+            )));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn to_page_ranges(&self) -> typst::layout::PageRanges {
+        typst::layout::PageRanges::new(
+            self.ranges
+                .iter()
+                .map(|(first, last)| NonZeroUsize::new(*first)..=last.and_then(NonZeroUsize::new))
+                .collect(),
+        )
+    }
+}
+
+impl std::str::FromStr for PageSelection {
+    type Err = PapermakeError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let page = |part: &str| part.trim().parse::<usize>().ok().filter(|page| *page > 0);
+        let ranges = s
+            .split(',')
+            .map(|part| {
+                let range = match part.split_once('-') {
+                    Some((first, last)) if last.trim().is_empty() => page(first).map(|first| (first, None)),
+                    Some((first, last)) => page(first).zip(page(last)).map(|(first, last)| (first, Some(last))),
+                    None => page(part).map(|page| (page, Some(page))),
+                };
+                range
+                    .filter(|(first, last)| last.is_none_or(|last| last >= *first))
+                    .ok_or_else(|| invalid(part))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { ranges })
+    }
+}
+
+impl fmt::Display for PageSelection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (first, last)) in self.ranges.iter().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            match last {
+                Some(last) if last == first => write!(f, "{}", first)?,
+                Some(last) => write!(f, "{}-{}", first, last)?,
+                None => write!(f, "{}-", first)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl TryFrom<String> for PageSelection {
+    type Error = PapermakeError;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl From<PageSelection> for String {
+    fn from(selection: PageSelection) -> Self {
+        selection.to_string()
+    }
+}
+
+fn invalid(range: &str) -> PapermakeError {
+    PapermakeError::InvalidInput(format!(
+        "Invalid page range '{}', expected e.g. '1-3,5,8-'",
+        range.trim()
+    ))
+}
+
+/// Number of pages of a PDF
+pub fn page_count(pdf: &[u8]) -> Result<usize> {
+    Ok(load(pdf)?.get_pages().len())
+}
+
+/// Split a PDF into one single-page PDF per page
+pub fn split_pages(pdf: &[u8]) -> Result<Vec<Vec<u8>>> {
+    let doc = load(pdf)?;
+    let pages: Vec<u32> = doc.get_pages().into_keys().collect();
+    pages
+        .iter()
+        .map(|page| {
+            let others: Vec<u32> = pages.iter().copied().filter(|other| other != page).collect();
+            keep_pages(doc.clone(), &others)
+        })
+        .collect()
+}
+
+/// Copy of a PDF with only the selected pages
+pub fn select_pages(pdf: &[u8], selection: &PageSelection) -> Result<Vec<u8>> {
+    let doc = load(pdf)?;
+    let pages: Vec<u32> = doc.get_pages().into_keys().collect();
+    selection.check(pages.len())?;
+    let removed: Vec<u32> = pages.into_iter().filter(|page| !selection.contains(*page as usize)).collect();
+    keep_pages(doc, &removed)
+}
+
+/// Delete pages and drop the objects only they used
+fn keep_pages(mut doc: Document, removed: &[u32]) -> Result<Vec<u8>> {
+    doc.delete_pages(removed);
+    doc.prune_objects();
+    let mut pdf = Vec::new();
+    doc.save_to(&mut pdf)
+        .map_err(|e| PapermakeError::Rendering(format!("Failed to write PDF: {}", e)))?;
+    Ok(pdf)
+}
+
+fn load(pdf: &[u8]) -> Result<Document> {
+    Document::load_mem(pdf).map_err(|e| PapermakeError::InvalidInput(format!("Failed to read PDF: {}", e)))
+}
@@ -0,0 +1,175 @@
+//! Thread-safe pool of pre-warmed Typst worlds for concurrent rendering
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use sha2::{Digest, Sha256};
+
+use crate::render::{render_pdf_with_cache, RenderOptions, RenderResult};
+use crate::storage::Namespace;
+use crate::typst::TypstWorld;
+use crate::{PapermakeError, Result, Template, TemplateId};
+
+/// Default number of idle worlds kept per template version
+const DEFAULT_MAX_IDLE: usize = 8;
+
+/// Key identifying a specific revision of a tenant's template.
+///
+/// The update timestamp acts as the version, so a revision never reuses
+/// worlds of another. The key also includes a hash of the source, in case
+/// a caller doesn't separate tenants sharing id and timestamp.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct PoolKey {
+    namespace: Option<Namespace>,
+    id: TemplateId,
+    version: time::OffsetDateTime,
+    content_hash: [u8; 32],
+}
+
+impl PoolKey {
+    fn of(namespace: Option<&Namespace>, template: &Template) -> Self {
+        Self {
+            namespace: namespace.cloned(),
+            id: template.id.clone(),
+            version: template.updated_at,
+            content_hash: Sha256::digest(template.content.as_bytes()).into(),
+        }
+    }
+}
+
+/// A pool of Typst worlds shared across threads.
+///
+/// Worlds are checked out for the duration of a single render and checked
+/// back in afterwards, so concurrent renders of the same template reuse
+/// parsed sources and fonts without contending on a single world. Pools
+/// returned by [`WorldPool::for_namespace`] share the worlds but only see
+/// their tenant's.
+#[derive(Debug)]
+pub struct WorldPool {
+    worlds: Arc<Mutex<HashMap<PoolKey, Vec<TypstWorld>>>>,
+    namespace: Option<Namespace>,
+    max_idle: usize,
+}
+
+impl Default for WorldPool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WorldPool {
+    /// Create an empty pool
+    pub fn new() -> Self {
+        Self::with_max_idle(DEFAULT_MAX_IDLE)
+    }
+
+    /// Create an empty pool keeping at most `max_idle` worlds per template version
+    pub fn with_max_idle(max_idle: usize) -> Self {
+        Self {
+            worlds: Arc::new(Mutex::new(HashMap::new())),
+            namespace: None,
+            max_idle,
+        }
+    }
+
+    /// The pool of a tenant namespace, sharing this pool's worlds
+    pub fn for_namespace(&self, namespace: &Namespace) -> Arc<WorldPool> {
+        Arc::new(Self {
+            worlds: self.worlds.clone(),
+            namespace: Some(namespace.clone()),
+            max_idle: self.max_idle,
+        })
+    }
+
+    fn key(&self, template: &Template) -> PoolKey {
+        PoolKey::of(self.namespace.as_ref(), template)
+    }
+
+    /// Take a world for the template out of the pool, creating one if none is idle
+    pub fn checkout(&self, template: &Template) -> Result<TypstWorld> {
+        let key = self.key(template);
+        match self.lock()?.get_mut(&key).and_then(|idle| idle.pop()) {
+            Some(world) => Ok(world),
+            None => Ok(TypstWorld::new(template.content.clone(), "{}".to_string())),
+        }
+    }
+
+    /// Return a world to the pool after rendering
+    pub fn checkin(&self, template: &Template, world: TypstWorld) -> Result<()> {
+        let key = self.key(template);
+        let mut worlds = self.lock()?;
+
+        let idle = worlds.entry(key).or_default();
+        if idle.len() < self.max_idle {
+            idle.push(world);
+        }
+        Ok(())
+    }
+
+    /// Pre-create `count` worlds for a template so first renders start warm
+    pub fn warm(&self, template: &Template, count: usize) -> Result<()> {
+        for _ in 0..count.min(self.max_idle) {
+            let world = TypstWorld::new(template.content.clone(), "{}".to_string());
+            self.checkin(template, world)?;
+        }
+        Ok(())
+    }
+
+    /// Render a template using a pooled world
+    pub fn render(
+        &self,
+        template: &Template,
+        data: &serde_json::Value,
+        options: Option<RenderOptions>,
+    ) -> Result<RenderResult> {
+        let mut world = self.checkout(template)?;
+        let result = render_pdf_with_cache(template, data, Some(&mut world), options);
+        self.checkin(template, world)?;
+        result
+    }
+
+    /// Render a template using a pooled world on tokio's blocking thread pool
+    #[cfg(feature = "tokio")]
+    pub async fn render_async(
+        self: &std::sync::Arc<Self>,
+        template: &Template,
+        data: &serde_json::Value,
+        options: Option<RenderOptions>,
+    ) -> Result<RenderResult> {
+        let pool = self.clone();
+        let template = template.clone();
+        let data = data.clone();
+
+        tokio::task::spawn_blocking(move || pool.render(&template, &data, options))
+            .await
+            .map_err(|e| PapermakeError::Rendering(format!("Render task failed: {}", e)))?
+    }
+
+    /// Number of idle worlds currently pooled for a template
+    pub fn idle_count(&self, template: &Template) -> usize {
+        self.worlds
+            .lock()
+            .map(|worlds| worlds.get(&self.key(template)).map_or(0, Vec::len))
+            .unwrap_or(0)
+    }
+
+    /// Drop worlds of older revisions of a template, e.g. after saving it
+    pub fn prune(&self, template: &Template) -> Result<()> {
+        self.lock()?.retain(|k, _| {
+            k.namespace != self.namespace || k.id != template.id || k.version >= template.updated_at
+        });
+        Ok(())
+    }
+
+    /// Remove all pooled worlds for a template, e.g. after it was deleted
+    pub fn evict(&self, id: &TemplateId) -> Result<()> {
+        self.lock()?.retain(|k, _| k.namespace != self.namespace || &k.id != id);
+        Ok(())
+    }
+
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, HashMap<PoolKey, Vec<TypstWorld>>>> {
+        self.worlds
+            .lock()
+            .map_err(|_| PapermakeError::Rendering("Failed to acquire world pool lock".to_string()))
+    }
+}
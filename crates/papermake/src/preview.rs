@@ -0,0 +1,244 @@
+//! Raster previews of rendered pages
+//!
+//! [`render_preview`] rasterizes pages to PNG, WebP or JPEG with
+//! [`PreviewOptions`] controlling the resolution, which pages to include, a
+//! maximum width and the compression quality. A small JPEG of the first page
+//! makes a thumbnail, a 300 DPI PNG of every page a print proof:
+//!
+//! ```rust,no_run
+//! # fn example(template: &papermake::Template, data: &serde_json::Value) -> papermake::Result<()> {
+//! use papermake::preview::{render_preview, PreviewOptions};
+//!
+//! let preview = render_preview(template, data, &PreviewOptions::thumbnail(200), None)?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! PNG output of [`crate::render`] uses the default options.
+
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::{ExtendedColorType, ImageEncoder};
+use serde::{Deserialize, Serialize};
+use typst::layout::Page;
+
+use crate::error::{PapermakeError, Result};
+use crate::output::PNG_PIXEL_PER_PT;
+use crate::pdf_ops::PageSelection;
+use crate::render::{compile_template, RenderError, RenderOptions};
+use crate::template::Template;
+use crate::trace::render_phase;
+
+/// Highest resolution accepted, to keep page images within memory
+pub const MAX_DPI: f32 = 1200.0;
+
+/// Image format of a preview
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PreviewFormat {
+    #[default]
+    Png,
+    /// Lossless WebP
+    Webp,
+    Jpeg,
+}
+
+impl PreviewFormat {
+    /// MIME type of images in this format
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            PreviewFormat::Png => "image/png",
+            PreviewFormat::Webp => "image/webp",
+            PreviewFormat::Jpeg => "image/jpeg",
+        }
+    }
+
+    /// File extension, without the dot
+    pub fn extension(&self) -> &'static str {
+        match self {
+            PreviewFormat::Png => "png",
+            PreviewFormat::Webp => "webp",
+            PreviewFormat::Jpeg => "jpg",
+        }
+    }
+}
+
+/// How pages are rasterized
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PreviewOptions {
+    /// Resolution in dots per inch
+    pub dpi: f32,
+    /// Pages to rasterize; those selected by `RenderOptions::pages` if `None`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub page_range: Option<PageSelection>,
+    /// Widest image in pixels; wider pages are rasterized at a lower
+    /// resolution instead
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_width: Option<u32>,
+    pub format: PreviewFormat,
+    /// JPEG quality from 1 to 100; PNG and WebP are lossless
+    pub quality: u8,
+}
+
+impl Default for PreviewOptions {
+    fn default() -> Self {
+        Self {
+            dpi: PNG_PIXEL_PER_PT * 72.0,
+            page_range: None,
+            max_width: None,
+            format: PreviewFormat::Png,
+            quality: 85,
+        }
+    }
+}
+
+impl PreviewOptions {
+    /// A JPEG of the first page at most `max_width` pixels wide
+    pub fn thumbnail(max_width: u32) -> Self {
+        Self {
+            dpi: 72.0,
+            page_range: PageSelection::range(1, Some(1)).ok(),
+            max_width: Some(max_width),
+            format: PreviewFormat::Jpeg,
+            quality: 75,
+        }
+    }
+
+    /// Check the settings are usable
+    pub fn validate(&self) -> Result<()> {
+        if !(self.dpi > 0.0 && self.dpi <= MAX_DPI) {
+            return Err(PapermakeError::InvalidInput(format!(
+                "Preview resolution must be between 0 and {} DPI, got {}",
+                MAX_DPI, self.dpi
+            )));
+        }
+        if self.max_width == Some(0) {
+            return Err(PapermakeError::InvalidInput("Preview max_width must be at least 1".to_string()));
+        }
+        if !(1..=100).contains(&self.quality) {
+            return Err(PapermakeError::InvalidInput(format!(
+                "Preview quality must be between 1 and 100, got {}",
+                self.quality
+            )));
+        }
+        Ok(())
+    }
+
+    /// Pixels per point for a page `width` points wide
+    fn pixel_per_pt(&self, width: f64) -> f32 {
+        let pixel_per_pt = self.dpi / 72.0;
+        match self.max_width {
+            Some(max_width) if width > 0.0 && width as f32 * pixel_per_pt > max_width as f32 => {
+                max_width as f32 / width as f32
+            }
+            _ => pixel_per_pt,
+        }
+    }
+}
+
+/// A rasterized page
+#[derive(Debug, Clone, Serialize)]
+pub struct PreviewPage {
+    /// 1-based page number
+    pub page: usize,
+    /// Size in pixels
+    pub width: u32,
+    pub height: u32,
+    pub image: Vec<u8>,
+}
+
+/// Result of [`render_preview`]
+#[derive(Debug, Serialize)]
+pub struct Preview {
+    pub format: PreviewFormat,
+    /// The selected pages; empty if compilation failed
+    pub pages: Vec<PreviewPage>,
+    pub errors: Vec<RenderError>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<RenderError>,
+}
+
+/// Render a template with data and rasterize the selected pages
+pub fn render_preview(
+    template: &Template,
+    data: &serde_json::Value,
+    preview: &PreviewOptions,
+    options: Option<RenderOptions>,
+) -> Result<Preview> {
+    preview.validate()?;
+    let options = options.unwrap_or_default();
+    let render = render_phase(template, &options);
+    let compiled = compile_template(template, data, None, &options)?;
+    render.record("success", compiled.document.is_some());
+    let Some(document) = compiled.document else {
+        return Ok(Preview {
+            format: preview.format,
+            pages: Vec::new(),
+            errors: compiled.errors,
+            warnings: compiled.warnings,
+        });
+    };
+
+    let selection = preview.page_range.as_ref().or(options.pages.as_ref());
+    if let Some(selection) = selection {
+        selection.check(document.pages.len())?;
+    }
+    let pages = document
+        .pages
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| selection.is_none_or(|selection| selection.contains(i + 1)))
+        .map(|(i, page)| {
+            let (image, width, height) = rasterize(page, preview)?;
+            Ok(PreviewPage { page: i + 1, width, height, image })
+        })
+        .collect::<Result<_>>()?;
+    Ok(Preview { format: preview.format, pages, errors: Vec::new(), warnings: compiled.warnings })
+}
+
+/// Rasterize and encode a page, returning the image and its size in pixels
+pub(crate) fn rasterize(page: &Page, options: &PreviewOptions) -> Result<(Vec<u8>, u32, u32)> {
+    let pixmap = typst_render::render(page, options.pixel_per_pt(page.frame.width().to_pt()));
+    let (width, height) = (pixmap.width(), pixmap.height());
+    let failed = |e: &dyn std::fmt::Display| {
+        PapermakeError::Rendering(format!("{} export failed: {}", options.format.extension().to_uppercase(), e))
+    };
+
+    let image = match options.format {
+        PreviewFormat::Png => pixmap.encode_png().map_err(|e| failed(&e))?,
+        PreviewFormat::Webp => {
+            let rgba: Vec<u8> = pixmap
+                .pixels()
+                .iter()
+                .flat_map(|pixel| {
+                    let color = pixel.demultiply();
+                    [color.red(), color.green(), color.blue(), color.alpha()]
+                })
+                .collect();
+            let mut image = Vec::new();
+            WebPEncoder::new_lossless(&mut image)
+                .write_image(&rgba, width, height, ExtendedColorType::Rgba8)
+                .map_err(|e| failed(&e))?;
+            image
+        }
+        PreviewFormat::Jpeg => {
+            // JPEG has no alpha; premultiplied pixels over white gain what
+            // the alpha lacks in each channel
+            let rgb: Vec<u8> = pixmap
+                .data()
+                .chunks_exact(4)
+                .flat_map(|pixel| {
+                    let white = 255 - pixel[3];
+                    [pixel[0].saturating_add(white), pixel[1].saturating_add(white), pixel[2].saturating_add(white)]
+                })
+                .collect();
+            let mut image = Vec::new();
+            JpegEncoder::new_with_quality(&mut image, options.quality)
+                .write_image(&rgb, width, height, ExtendedColorType::Rgb8)
+                .map_err(|e| failed(&e))?;
+            image
+        }
+    };
+    Ok((image, width, height))
+}
@@ -0,0 +1,132 @@
+//! Remote images and other resources fetched over HTTP(S)
+//!
+//! With `RenderOptions::remote_resources` set, templates can load files by
+//! URL, e.g. a customer logo whose URL is in the data:
+//!
+//! ```typst
+//! #image(data.logo_url, width: 3cm)
+//! ```
+//!
+//! Only hosts on the allowlist are contacted, without following redirects.
+//! Responses above the size limit or slower than the timeout fail the
+//! render, and fetched files are cached for a while so batches of renders
+//! don't fetch the same logo over and over. PDFs served from the render
+//! cache keep the resources they were rendered with.
+
+use std::collections::HashMap;
+use std::io::Read;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use typst::foundations::Bytes;
+
+/// Files kept in the cache at most; the oldest are dropped first
+const MAX_CACHED: usize = 256;
+
+/// Settings and cache of remote resource fetching; clones share the cache
+#[derive(Debug, Clone)]
+pub struct RemoteResources {
+    allowed_hosts: Vec<String>,
+    max_bytes: usize,
+    timeout: Duration,
+    cache_ttl: Duration,
+    cache: Arc<Mutex<HashMap<String, (Instant, Bytes)>>>,
+}
+
+impl RemoteResources {
+    /// Fetching from the given hosts (e.g. `cdn.example.com`), matched
+    /// exactly; files up to 5 MB, 10 second timeout, cached for 5 minutes
+    pub fn new<I, S>(allowed_hosts: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        Self {
+            allowed_hosts: allowed_hosts.into_iter().map(|host| host.as_ref().to_ascii_lowercase()).collect(),
+            max_bytes: 5 * 1024 * 1024,
+            timeout: Duration::from_secs(10),
+            cache_ttl: Duration::from_secs(300),
+            cache: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub fn max_bytes(mut self, bytes: usize) -> Self {
+        self.max_bytes = bytes;
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// How long fetched files are reused; zero disables the cache
+    pub fn cache_ttl(mut self, ttl: Duration) -> Self {
+        self.cache_ttl = ttl;
+        self
+    }
+
+    /// Whether `url` may be fetched
+    pub fn allows(&self, url: &str) -> bool {
+        url::Url::parse(url).is_ok_and(|url| {
+            matches!(url.scheme(), "http" | "https")
+                && url.host_str().is_some_and(|host| self.allowed_hosts.iter().any(|allowed| *allowed == host))
+        })
+    }
+
+    /// The contents of `url`, from the cache if fetched recently
+    pub(crate) fn fetch(&self, url: &str) -> Result<Bytes, String> {
+        if !self.allows(url) {
+            return Err(format!("Fetching {} is not allowed", url));
+        }
+        if let Some(bytes) = self.cached(url) {
+            return Ok(bytes);
+        }
+
+        let agent = ureq::AgentBuilder::new().timeout(self.timeout).redirects(0).build();
+        let response = agent.get(url).call().map_err(|e| format!("Failed to fetch {}: {}", url, e))?;
+        if response.status() != 200 {
+            return Err(format!("Failed to fetch {}: status {}", url, response.status()));
+        }
+        let too_large = || format!("{} exceeds the maximum size of {} bytes", url, self.max_bytes);
+        let length = response.header("Content-Length").and_then(|length| length.parse::<usize>().ok());
+        if length.is_some_and(|length| length > self.max_bytes) {
+            return Err(too_large());
+        }
+        let mut body = Vec::new();
+        response
+            .into_reader()
+            .take(self.max_bytes as u64 + 1)
+            .read_to_end(&mut body)
+            .map_err(|e| format!("Failed to fetch {}: {}", url, e))?;
+        if body.len() > self.max_bytes {
+            return Err(too_large());
+        }
+
+        let bytes = Bytes::new(body);
+        self.store(url, bytes.clone());
+        Ok(bytes)
+    }
+
+    fn cached(&self, url: &str) -> Option<Bytes> {
+        let cache = self.cache.lock().ok()?;
+        let (fetched, bytes) = cache.get(url)?;
+        (fetched.elapsed() < self.cache_ttl).then(|| bytes.clone())
+    }
+
+    fn store(&self, url: &str, bytes: Bytes) {
+        if self.cache_ttl.is_zero() {
+            return;
+        }
+        let Ok(mut cache) = self.cache.lock() else {
+            return;
+        };
+        cache.retain(|_, (fetched, _)| fetched.elapsed() < self.cache_ttl);
+        if cache.len() >= MAX_CACHED {
+            if let Some(oldest) = cache.iter().min_by_key(|(_, (fetched, _))| *fetched).map(|(url, _)| url.clone()) {
+                cache.remove(&oldest);
+            }
+        }
+        cache.insert(url.to_string(), (Instant::now(), bytes));
+    }
+}
@@ -1,15 +1,49 @@
 //! PDF rendering functionality
 
-use serde::Serialize;
+use std::borrow::Cow;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use typst::diag::SourceDiagnostic;
+use typst::layout::PagedDocument;
 use typst::WorldExt;
 use typst::World;
-use typst_pdf::PdfOptions;
+use typst::foundations::{Datetime, Smart};
+use typst_pdf::{PdfOptions, Timestamp};
 
+use crate::attachment::{attach_files, PdfAttachment};
+#[cfg(feature = "barcodes")]
+use crate::barcode::render_barcodes;
+#[cfg(feature = "charts")]
+use crate::charts::ChartSpec;
+#[cfg(feature = "scripting")]
+use crate::script::{run_script, ScriptLimits};
+#[cfg(feature = "remote")]
+use crate::remote::RemoteResources;
+use crate::encryption::{encrypt_pdf, PdfEncryption};
 use crate::error::Result;
+use crate::limits::SizeLimits;
+use crate::metadata::DocumentMetadata;
+use crate::optimize::{optimize_pdf, OptimizationReport, OptimizeLevel};
+use crate::pdf_ops::PageSelection;
+use crate::sandbox::SandboxPolicy;
+use crate::sections::{check_sections, section_states};
+use crate::render_cache::{CachePolicy, RenderCache, RenderCacheKey};
+use crate::shared::SharedSources;
 use crate::template::Template;
+use crate::trace::{phase, render_phase};
+use crate::text::{extract_text, PageText};
+use crate::transform::TransformPipeline;
 use crate::typst::TypstWorld;
+use crate::watermark::Watermark;
 use crate::PapermakeError;
 
+/// Number of compilations a memoized result survives without being reused
+///
+/// Typst memoizes parsing, evaluation and layout across compilations; old
+/// entries are evicted so long-running processes don't grow unboundedly.
+const CACHE_MAX_AGE: usize = 30;
+
 /// Options for PDF rendering
 #[derive(Debug, Clone)]
 pub struct RenderOptions {
@@ -18,6 +52,99 @@ pub struct RenderOptions {
     
     /// Whether to compress the output PDF
     pub compress: bool,
+    
+    /// Size optimization applied to the output PDF, see [`crate::optimize`]
+    pub optimize: OptimizeLevel,
+    
+    /// Whether to coerce loosely typed data (e.g. `"42"` for a number field)
+    /// into the types declared by the schema before validation
+    pub coerce_data: bool,
+    
+    /// For merged renders: the data field (or JSON pointer) titling each
+    /// record's bookmark in the PDF outline; no bookmarks when `None`
+    pub bookmark_field: Option<String>,
+    
+    /// Password protection and permission flags applied to the output PDF
+    pub encryption: Option<PdfEncryption>,
+    
+    /// Sources of shared templates imported via `papermake:` paths,
+    /// usually obtained from `shared::resolve_shared`
+    pub shared_sources: SharedSources,
+    
+    /// Cache returning previously rendered PDFs for identical renders
+    pub render_cache: Option<Arc<dyn RenderCache>>,
+    
+    /// How this render uses `render_cache`
+    pub cache_policy: CachePolicy,
+    
+    /// Transforms applied to the data before validation
+    pub transforms: TransformPipeline,
+    
+    /// The data was already prepared with [`prepare_data`] using these
+    /// options (e.g. to reject invalid data before queueing a render), so
+    /// it is rendered as is instead of being prepared again
+    pub data_prepared: bool,
+    
+    /// Locale (e.g. `de-DE`) selecting the template variant and exposed as
+    /// `sys.inputs.locale`
+    pub locale: Option<String>,
+
+    /// Deployment environment (e.g. `staging`) selecting the template's
+    /// settings, exposed as `sys.inputs.environment`
+    pub environment: Option<String>,
+    
+    /// Named inputs besides the data, e.g. the requesting user or a
+    /// correlation id, exposed as `sys.inputs.extra`; not validated
+    /// against the schema
+    pub extra_inputs: std::collections::BTreeMap<String, serde_json::Value>,
+    
+    /// Produce byte-identical PDFs for identical inputs: the creation date
+    /// and `datetime.today()` are fixed (see [`deterministic_time`]) and the
+    /// document identifier is derived from the template id
+    pub deterministic: bool,
+    
+    /// Time of the render, seen by `datetime.today()` and stamped as the
+    /// PDF's creation date; the current time (or [`deterministic_time`] for
+    /// deterministic renders) if `None`
+    pub now: Option<time::OffsetDateTime>,
+    
+    /// UTC offset of the time zone `datetime.today()` gives the date in,
+    /// e.g. a tenant's `+02:00`; UTC if `None`. `datetime.today(offset: ..)`
+    /// still selects an offset explicitly.
+    pub timezone: Option<time::UtcOffset>,
+    
+    /// Restrictions applied to every rendered template, combined with the
+    /// template's own policy
+    pub sandbox: Option<SandboxPolicy>,
+    
+    /// Pages to export, e.g. `1-3,5`; all pages when `None`
+    pub pages: Option<PageSelection>,
+    
+    /// Files embedded in the output PDF, e.g. the input data for
+    /// e-invoicing formats
+    pub attachments: Vec<PdfAttachment>,
+    
+    /// Maximum sizes of the data and template, checked before rendering
+    pub size_limits: SizeLimits,
+    
+    /// Stamp drawn over the rendered pages; the environment's watermark
+    /// text if `None`
+    pub watermark: Option<Watermark>,
+    
+    /// Extract the positioned text of each page into `RenderResult::text`
+    pub extract_text: bool,
+    
+    /// Charts drawn before compiling, loaded by templates as `chart:<name>.svg`
+    #[cfg(feature = "charts")]
+    pub charts: std::collections::BTreeMap<String, ChartSpec>,
+    
+    /// Resource limits of the template's script
+    #[cfg(feature = "scripting")]
+    pub script_limits: ScriptLimits,
+    
+    /// Hosts templates may load files from by URL; no remote files if `None`
+    #[cfg(feature = "remote")]
+    pub remote_resources: Option<RemoteResources>,
 }
 
 impl Default for RenderOptions {
@@ -25,11 +152,38 @@ impl Default for RenderOptions {
         RenderOptions {
             paper_size: "a4".to_string(),
             compress: true,
+            optimize: OptimizeLevel::default(),
+            coerce_data: false,
+            bookmark_field: None,
+            encryption: None,
+            shared_sources: SharedSources::default(),
+            render_cache: None,
+            cache_policy: CachePolicy::default(),
+            transforms: TransformPipeline::default(),
+            data_prepared: false,
+            locale: None,
+            environment: None,
+            extra_inputs: std::collections::BTreeMap::new(),
+            deterministic: false,
+            now: None,
+            timezone: None,
+            sandbox: None,
+            pages: None,
+            attachments: Vec::new(),
+            size_limits: SizeLimits::default(),
+            watermark: None,
+            extract_text: false,
+            #[cfg(feature = "charts")]
+            charts: std::collections::BTreeMap::new(),
+            #[cfg(feature = "scripting")]
+            script_limits: ScriptLimits::default(),
+            #[cfg(feature = "remote")]
+            remote_resources: None,
         }
     }
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RenderError {
     pub message: String,
     pub start: usize,
@@ -40,108 +194,425 @@ pub struct RenderError {
 pub struct RenderResult {
     pub pdf: Option<Vec<u8>>,
     pub errors: Vec<RenderError>,
+    /// Non-fatal compile diagnostics, e.g. unknown fonts; not kept for
+    /// PDFs served from the render cache
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<RenderError>,
+    /// Whether the PDF was served from the render cache
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub cached: bool,
+    /// Sizes before and after optimizing, if `RenderOptions::optimize` is set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub optimization: Option<OptimizationReport>,
+    /// Page count, outline and named destinations of the PDF; not kept for
+    /// PDFs served from the render cache
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<DocumentMetadata>,
+    /// Text of each exported page, if `RenderOptions::extract_text` is set;
+    /// not kept for PDFs served from the render cache
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<Vec<PageText>>,
+}
+
+/// Prepare data for rendering: check input sizes, apply schema defaults,
+/// optionally coerce values, run the configured transforms and the
+/// template's script, and validate the result against the template's schema
+pub fn prepare_data(
+    template: &Template,
+    data: &serde_json::Value,
+    options: &RenderOptions,
+) -> Result<serde_json::Value> {
+    options.size_limits.check_template(template)?;
+    options.size_limits.check_data(data)?;
+    let mut data = data.clone();
+    template.schema.apply_defaults(&mut data);
+    if options.coerce_data {
+        template.schema.coerce(&mut data);
+    }
+    options.transforms.apply(&mut data)?;
+    if let Some(script) = &template.script {
+        #[cfg(feature = "scripting")]
+        run_script(script, &mut data, &options.script_limits)?;
+        #[cfg(not(feature = "scripting"))]
+        return Err(PapermakeError::InvalidInput(format!(
+            "Template '{}' has a script ({} bytes), which requires the `scripting` feature",
+            template.id.0,
+            script.len()
+        )));
+    }
+    template.validate_data(&data)?;
+    Ok(data)
+}
+
+/// Data a render compiles with: prepared here unless the caller already did
+/// (see `RenderOptions::data_prepared`)
+pub(crate) fn prepared_data<'a>(
+    template: &Template,
+    data: &'a serde_json::Value,
+    options: &RenderOptions,
+) -> Result<Cow<'a, serde_json::Value>> {
+    if options.data_prepared {
+        return Ok(Cow::Borrowed(data));
+    }
+    let _phase = phase!("validate");
+    prepare_data(template, data, options).map(Cow::Owned)
 }
 
 /// Render a template with data to a PDF
 pub fn render_pdf(
     template: &Template,
     data: &serde_json::Value,
-    _options: Option<RenderOptions>,
+    options: Option<RenderOptions>,
 ) -> Result<RenderResult> {
-    // Validate data against schema
-    template.validate_data(data)?;
-    
-    let world = TypstWorld::new(
-        template.content.clone(),
-        serde_json::to_string(&data).map_err(|e| PapermakeError::Rendering(e.to_string()))?,
-    );
+    render_pdf_with_cache(template, data, None, options)
+}
 
-    let compile_result = typst::compile(&world);
-    
-    let mut errors = Vec::new();
-    let mut pdf = None;
+/// Render a template with data to a PDF without blocking the async runtime
+///
+/// Compilation runs on tokio's blocking thread pool, so slow renders don't
+/// starve other tasks scheduled on the executor.
+#[cfg(feature = "tokio")]
+pub async fn render_pdf_async(
+    template: &Template,
+    data: &serde_json::Value,
+    options: Option<RenderOptions>,
+) -> Result<RenderResult> {
+    let template = template.clone();
+    let data = data.clone();
 
-    match compile_result.output {
-        Ok(document) => {
-            pdf = Some(typst_pdf::pdf(&document, &PdfOptions::default()).unwrap());
+    tokio::task::spawn_blocking(move || render_pdf(&template, &data, options))
+        .await
+        .map_err(|e| PapermakeError::Rendering(format!("Render task failed: {}", e)))?
+}
+
+pub fn render_pdf_with_cache(
+    template: &Template,
+    data: &serde_json::Value,
+    world_cache: Option<&mut TypstWorld>, // Add a cache parameter
+    options: Option<RenderOptions>,
+) -> Result<RenderResult> {
+    let options = options.unwrap_or_default();
+    let render = render_phase(template, &options);
+    let prepared = prepared_data(template, data, &options)?;
+
+    // Encrypted output is never cached, so passwords don't end up in cache keys
+    // and protected documents aren't kept around in plain storage. Keys cover
+    // the prepared data, so differently configured transforms don't collide.
+    let cache = match &options.render_cache {
+        Some(cache) if options.encryption.is_none() && options.cache_policy != CachePolicy::Bypass => {
+            Some((cache, RenderCacheKey::new(template, &prepared, &options)))
+        }
+        _ => None,
+    };
+
+    if let Some((cache, key)) = &cache {
+        if options.cache_policy.reads() {
+            if let Some(pdf) = cache.get(key) {
+                render.record("cached", true);
+                render.record("success", true);
+                return Ok(RenderResult {
+                    pdf: Some(pdf),
+                    errors: Vec::new(),
+                    warnings: Vec::new(),
+                    cached: true,
+                    optimization: None,
+                    metadata: None,
+                    text: None,
+                });
+            }
         }
-        Err(diagnostics) => {
-            for diagnostic in diagnostics {
-                let span = diagnostic.span;
-                if let Some(id) = span.id() {
-                    if let Ok(_file) = world.source(id) {
-                        if let Some(range) = world.range(span) {
-                            errors.push(RenderError {
-                                message: diagnostic.message.to_string(),
-                                start: range.start,
-                                end: range.end,});
-                        }
-                    }
-                }
+    }
+
+    let compiled = compile_prepared_template(template, &prepared, world_cache, &options)?;
+
+    let mut optimization = None;
+    let mut metadata = None;
+    let mut text = None;
+    let pdf = match &compiled.document {
+        Some(document) => {
+            if options.extract_text {
+                text = Some(extract_text(document, options.pages.as_ref()));
+            }
+            let mut extracted = DocumentMetadata::from_document(document);
+            if let Some(pages) = &options.pages {
+                pages.check(document.pages.len())?;
+                extracted = extracted.select(pages);
             }
+            metadata = Some(extracted);
+            let (pdf, report) = export_pdf(document, template, &prepared, &options)?;
+            optimization = report;
+            Some(pdf)
+        }
+        None => None,
+    };
+
+    if let (Some((cache, key)), Some(pdf)) = (&cache, &pdf) {
+        if options.cache_policy.writes() {
+            cache.put(key, pdf);
         }
     }
 
+    render.record("cached", false);
+    render.record("success", pdf.is_some());
     Ok(RenderResult {
         pdf,
-        errors,
+        errors: compiled.errors,
+        warnings: compiled.warnings,
+        cached: false,
+        optimization,
+        metadata,
+        text,
     })
 }
 
-pub fn render_pdf_with_cache(
+/// Export a compiled document to PDF with attachments built from the
+/// prepared `data`, optimization and encryption applied
+pub(crate) fn export_pdf(
+    document: &PagedDocument,
     template: &Template,
     data: &serde_json::Value,
-    world_cache: Option<&mut TypstWorld>, // Add a cache parameter
-    _options: Option<RenderOptions>,
-) -> Result<RenderResult> {
-    // Validate data against schema
-    template.validate_data(data)?;
-    
+    options: &RenderOptions,
+) -> Result<(Vec<u8>, Option<OptimizationReport>)> {
+    let mut pdf = {
+        let _phase = phase!("pdf_export");
+        typst_pdf::pdf(document, &pdf_options(template, options))
+            .map_err(|e| PapermakeError::Rendering(format!("PDF export failed: {:?}", e)))?
+    };
+
+    let _phase = phase!("post_process");
+    if !options.attachments.is_empty() {
+        pdf = attach_files(&pdf, &options.attachments, data)?;
+    }
+    let (pdf, optimization) = optimize_output(pdf, options.optimize)?;
+    let pdf = match &options.encryption {
+        Some(encryption) => encrypt_pdf(&pdf, encryption)?,
+        None => pdf,
+    };
+    Ok((pdf, optimization))
+}
+
+/// Optimize an exported PDF before it is encrypted, reporting the sizes
+pub(crate) fn optimize_output(pdf: Vec<u8>, level: OptimizeLevel) -> Result<(Vec<u8>, Option<OptimizationReport>)> {
+    if level == OptimizeLevel::None {
+        return Ok((pdf, None));
+    }
+    let (pdf, report) = optimize_pdf(pdf, level)?;
+    Ok((pdf, Some(report)))
+}
+
+/// Time seen by deterministic renders: `SOURCE_DATE_EPOCH` if set, the Unix
+/// epoch otherwise
+pub fn deterministic_time() -> time::OffsetDateTime {
+    std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.trim().parse::<i64>().ok())
+        .and_then(|epoch| time::OffsetDateTime::from_unix_timestamp(epoch).ok())
+        .unwrap_or(time::OffsetDateTime::UNIX_EPOCH)
+}
+
+/// Time a render sees as the current time
+pub(crate) fn render_time(options: &RenderOptions) -> time::OffsetDateTime {
+    match options.now {
+        Some(now) => now,
+        None if options.deterministic => deterministic_time(),
+        None => time::OffsetDateTime::now_utc(),
+    }
+}
+
+/// PDF export settings for a render
+pub(crate) fn pdf_options<'a>(template: &'a Template, options: &RenderOptions) -> PdfOptions<'a> {
+    let page_ranges = options.pages.as_ref().map(PageSelection::to_page_ranges);
+    if !options.deterministic && options.now.is_none() {
+        return PdfOptions { page_ranges, ..PdfOptions::default() };
+    }
+
+    let time = render_time(options).to_offset(time::UtcOffset::UTC);
+    let timestamp = Datetime::from_ymd_hms(
+        time.year(),
+        time.month() as u8,
+        time.day(),
+        time.hour(),
+        time.minute(),
+        time.second(),
+    )
+    .map(Timestamp::new_utc);
+    PdfOptions {
+        ident: if options.deterministic { Smart::Custom(template.id.as_ref()) } else { Smart::Auto },
+        timestamp,
+        page_ranges,
+        ..PdfOptions::default()
+    }
+}
+
+/// A compiled document together with any compile errors
+pub(crate) struct Compiled<D = PagedDocument> {
+    pub document: Option<D>,
+    pub errors: Vec<RenderError>,
+    pub warnings: Vec<RenderError>,
+}
+
+/// Document types a template compiles to
+pub(crate) trait CompileTarget: typst::Document {
+    /// Whether compiling needs Typst's HTML export enabled
+    const HTML: bool;
+}
+
+impl CompileTarget for PagedDocument {
+    const HTML: bool = false;
+}
+
+#[cfg(feature = "html")]
+impl CompileTarget for typst::html::HtmlDocument {
+    const HTML: bool = true;
+}
+
+/// Prepare the data and compile a template into a paged document
+pub(crate) fn compile_template(
+    template: &Template,
+    data: &serde_json::Value,
+    world_cache: Option<&mut TypstWorld>,
+    options: &RenderOptions,
+) -> Result<Compiled> {
+    let data = prepared_data(template, data, options)?;
+    compile_prepared_template(template, &data, world_cache, options)
+}
+
+/// Compile a template with prepared data into a paged document
+pub(crate) fn compile_prepared_template(
+    template: &Template,
+    data: &serde_json::Value,
+    world_cache: Option<&mut TypstWorld>,
+    options: &RenderOptions,
+) -> Result<Compiled> {
+    let mut compiled = compile_prepared(template, data, world_cache, options)?;
+    let watermark = options.watermark.clone().or_else(|| {
+        template.environment(options.environment.as_deref()).watermark.map(Watermark::text)
+    });
+    if let (Some(watermark), Some(document)) = (watermark, &mut compiled.document) {
+        watermark.apply(document)?;
+    }
+    Ok(compiled)
+}
+
+/// Prepare the data and compile a template into any document type
+pub(crate) fn compile<D: CompileTarget>(
+    template: &Template,
+    data: &serde_json::Value,
+    world_cache: Option<&mut TypstWorld>,
+    options: &RenderOptions,
+) -> Result<Compiled<D>> {
+    let data = prepared_data(template, data, options)?;
+    compile_prepared(template, &data, world_cache, options)
+}
+
+/// Compile a template with prepared data into any document type
+fn compile_prepared<D: CompileTarget>(
+    template: &Template,
+    data: &serde_json::Value,
+    world_cache: Option<&mut TypstWorld>,
+    options: &RenderOptions,
+) -> Result<Compiled<D>> {
+    let setup = phase!("world_setup");
+    #[cfg(feature = "barcodes")]
+    let barcodes = render_barcodes(&template.schema, data)?;
+    let sections = section_states(&template.schema, data);
+    #[cfg(feature = "charts")]
+    let charts = crate::charts::render_charts(&options.charts, data)?;
+    let data = serde_json::to_string(data).map_err(|e| PapermakeError::Rendering(e.to_string()))?;
+
+    let content = template.content_for(options.locale.as_deref());
+
+    // Reject denied functions before evaluating anything
+    let policy = SandboxPolicy::effective(options.sandbox.as_ref(), template.sandbox.as_ref());
+    let mut errors = policy.check_source(content);
+    if policy.shared_imports {
+        for (path, source) in options.shared_sources.iter() {
+            errors.extend(policy.check_source(source).into_iter().map(|error| RenderError {
+                message: format!("{} (in papermake:{})", error.message, path),
+                start: 0,
+                end: 0,
+            }));
+        }
+    }
+    // Sections named in the template must be declared in the schema
+    errors.extend(check_sections(content, &template.schema));
+    if !errors.is_empty() {
+        return Ok(Compiled { document: None, errors, warnings: Vec::new() });
+    }
+
     // Either use the cached world or create a new one
+    let mut new_world;
     let world = match world_cache {
         Some(cached_world) => {
             // Update the inputs in the existing world
-            cached_world.update_data(
-                serde_json::to_string(&data).map_err(|e| PapermakeError::Rendering(e.to_string()))?,
-            ).map_err(|e| PapermakeError::Rendering(e.to_string()))?;
+            cached_world.set_locale(options.locale.as_deref());
+            cached_world.update_source(content);
+            cached_world.update_data(data)
+                .map_err(PapermakeError::Rendering)?;
             // Make sure to reset tracking state
             // cached_world.reset(); TODO: Implement this
             cached_world
         }
-        None => &mut TypstWorld::new(
-            template.content.clone(),
-            serde_json::to_string(&data).map_err(|e| PapermakeError::Rendering(e.to_string()))?,
-        ),
+        None => {
+            new_world = TypstWorld::new(content.to_string(), data);
+            new_world.set_locale(options.locale.as_deref());
+            &mut new_world
+        }
     };
+    world.set_shared_sources(&options.shared_sources);
+    #[cfg(feature = "barcodes")]
+    world.set_barcodes(barcodes);
+    world.set_sections(sections);
+    world.set_environment(options.environment.as_deref(), template.environment(options.environment.as_deref()));
+    world.set_extra_inputs(&options.extra_inputs);
+    #[cfg(feature = "charts")]
+    world.set_charts(charts);
+    #[cfg(feature = "remote")]
+    world.set_remote_resources(options.remote_resources.clone());
+    world.set_sandbox(policy);
+    world.set_html(D::HTML);
+    world.set_time(render_time(options));
+    world.set_timezone(options.timezone.unwrap_or(time::UtcOffset::UTC));
+    drop(setup);
 
-    let compile_result = typst::compile(world as &dyn World);
-
-    let mut errors = Vec::new();
-    let mut pdf = None;
+    let _phase = phase!("compile");
+    let compile_result = typst::compile::<D>(world as &dyn World);
+    comemo::evict(CACHE_MAX_AGE);
 
+    let warnings = collect_errors(world, &compile_result.warnings);
     match compile_result.output {
-        Ok(document) => {
-            pdf = Some(typst_pdf::pdf(&document, &PdfOptions::default()).unwrap());
-        }
-        Err(diagnostics) => {
-            for diagnostic in diagnostics {
-                let span = diagnostic.span;
-                if let Some(id) = span.id() {
-                    if let Ok(_file) = world.source(id) {
-                        if let Some(range) = world.range(span) {
-                            errors.push(RenderError {
-                                message: diagnostic.message.to_string(),
-                                start: range.start,
-                                end: range.end,});
-                        }
-                    }
-                }
-            }
-        }
+        Ok(document) => Ok(Compiled {
+            document: Some(document),
+            errors: Vec::new(),
+            warnings,
+        }),
+        Err(diagnostics) => Ok(Compiled {
+            document: None,
+            errors: collect_errors(world, &diagnostics),
+            warnings,
+        }),
     }
+}
 
-    Ok(RenderResult {
-        pdf,
-        errors,
-    })
-}
\ No newline at end of file
+/// Convert compile diagnostics into render errors with source ranges
+///
+/// Diagnostics without a location in a known source (e.g. font warnings)
+/// get an empty range at the start of the template.
+fn collect_errors(world: &TypstWorld, diagnostics: &[SourceDiagnostic]) -> Vec<RenderError> {
+    diagnostics
+        .iter()
+        .map(|diagnostic| {
+            let span = diagnostic.span;
+            let range = span
+                .id()
+                .filter(|id| world.source(*id).is_ok())
+                .and_then(|_| world.range(span))
+                .unwrap_or(0..0);
+            RenderError {
+                message: diagnostic.message.to_string(),
+                start: range.start,
+                end: range.end,
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,244 @@
+//! Caching of rendered PDFs
+//!
+//! Renders are deterministic for a given template version, data and set of
+//! options, so identical requests can be answered from a cache. Set
+//! `RenderOptions::render_cache` to enable caching and
+//! `RenderOptions::cache_policy` to bypass it per request.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use sha2::{Digest, Sha256};
+
+use crate::attachment::AttachmentContent;
+use crate::render::RenderOptions;
+use crate::sandbox::SandboxPolicy;
+use crate::template::Template;
+use crate::watermark::WatermarkContent;
+
+/// Cache key identifying a render: a SHA-256 over template version, data and options
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RenderCacheKey(String);
+
+impl RenderCacheKey {
+    /// Compute the key for rendering `template` with `data` and `options`
+    pub fn new(template: &Template, data: &serde_json::Value, options: &RenderOptions) -> Self {
+        let mut hasher = Sha256::new();
+        let mut field = |value: &[u8]| {
+            // Length-prefix every field so adjacent fields can't run together
+            hasher.update((value.len() as u64).to_le_bytes());
+            hasher.update(value);
+        };
+
+        field(template.id.0.as_bytes());
+        field(&template.updated_at.unix_timestamp_nanos().to_le_bytes());
+        field(template.content_for(options.locale.as_deref()).as_bytes());
+        field(data.to_string().as_bytes());
+        field(options.paper_size.as_bytes());
+        field(&[options.compress as u8, options.coerce_data as u8, options.deterministic as u8, options.optimize as u8]);
+        field(options.bookmark_field.as_deref().unwrap_or_default().as_bytes());
+        field(options.locale.as_deref().unwrap_or_default().as_bytes());
+        field(options.environment.as_deref().unwrap_or_default().as_bytes());
+        field(serde_json::to_string(&options.extra_inputs).unwrap_or_default().as_bytes());
+        field(options.pages.as_ref().map(ToString::to_string).unwrap_or_default().as_bytes());
+        field(format!("{:?} {:?}", options.now.map(|now| now.unix_timestamp_nanos()), options.timezone).as_bytes());
+        let sandbox = SandboxPolicy::effective(options.sandbox.as_ref(), template.sandbox.as_ref());
+        field(serde_json::to_string(&sandbox).unwrap_or_default().as_bytes());
+        if let Some(watermark) = &options.watermark {
+            match &watermark.content {
+                WatermarkContent::Text(text) => field(text.as_bytes()),
+                WatermarkContent::Image(data) => field(data),
+            }
+            field(&watermark.opacity.to_le_bytes());
+            field(&watermark.rotation.to_le_bytes());
+            field(&[watermark.pages as u8]);
+        }
+        for attachment in &options.attachments {
+            field(attachment.filename.as_bytes());
+            field(attachment.mime_type.as_bytes());
+            field(attachment.description.as_deref().unwrap_or_default().as_bytes());
+            field(format!("{:?}", attachment.relationship).as_bytes());
+            match &attachment.content {
+                AttachmentContent::InputData => field(b"input"),
+                AttachmentContent::Bytes(bytes) => field(bytes),
+            }
+        }
+        #[cfg(feature = "charts")]
+        for (name, chart) in &options.charts {
+            field(name.as_bytes());
+            field(serde_json::to_string(chart).unwrap_or_default().as_bytes());
+        }
+        for (path, content) in options.shared_sources.iter() {
+            field(path.as_bytes());
+            field(content.as_bytes());
+        }
+
+        Self(hex::encode(hasher.finalize()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for RenderCacheKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// How a render uses the configured cache, mirroring `Cache-Control`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CachePolicy {
+    /// Return cached PDFs and store new ones
+    #[default]
+    Use,
+    /// Always render, but store the result (`no-cache`)
+    Refresh,
+    /// Neither read from nor write to the cache (`no-store`)
+    Bypass,
+}
+
+impl CachePolicy {
+    /// Policy for a `Cache-Control` header value; `no-store` wins over `no-cache`
+    pub fn from_cache_control(value: &str) -> Self {
+        let directives: Vec<String> = value
+            .split(',')
+            .map(|d| d.trim().to_ascii_lowercase())
+            .collect();
+        if directives.iter().any(|d| d == "no-store") {
+            CachePolicy::Bypass
+        } else if directives.iter().any(|d| d == "no-cache") {
+            CachePolicy::Refresh
+        } else {
+            CachePolicy::Use
+        }
+    }
+
+    pub(crate) fn reads(self) -> bool {
+        self == CachePolicy::Use
+    }
+
+    pub(crate) fn writes(self) -> bool {
+        self != CachePolicy::Bypass
+    }
+}
+
+/// Storage for rendered PDFs
+///
+/// Caches are best-effort: failures to read or write must not fail the render.
+pub trait RenderCache: Send + Sync + fmt::Debug {
+    fn get(&self, key: &RenderCacheKey) -> Option<Vec<u8>>;
+
+    fn put(&self, key: &RenderCacheKey, pdf: &[u8]);
+}
+
+/// In-memory cache evicting the least recently used entries
+#[derive(Debug)]
+pub struct MemoryRenderCache {
+    inner: Mutex<LruState>,
+    max_entries: usize,
+}
+
+#[derive(Debug, Default)]
+struct LruState {
+    entries: HashMap<RenderCacheKey, (Vec<u8>, u64)>,
+    /// Entries by last use
+    recency: BTreeMap<u64, RenderCacheKey>,
+    tick: u64,
+}
+
+impl LruState {
+    fn touch(&mut self, key: &RenderCacheKey) {
+        self.tick += 1;
+        let tick = self.tick;
+        if let Some((_, last_used)) = self.entries.get_mut(key) {
+            self.recency.remove(last_used);
+            *last_used = tick;
+            self.recency.insert(tick, key.clone());
+        }
+    }
+}
+
+impl MemoryRenderCache {
+    /// Create a cache holding at most `max_entries` PDFs
+    pub fn new(max_entries: usize) -> Self {
+        Self {
+            inner: Mutex::new(LruState::default()),
+            max_entries,
+        }
+    }
+
+    /// Number of cached PDFs
+    pub fn len(&self) -> usize {
+        self.inner.lock().map(|state| state.entries.len()).unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl RenderCache for MemoryRenderCache {
+    fn get(&self, key: &RenderCacheKey) -> Option<Vec<u8>> {
+        let mut state = self.inner.lock().ok()?;
+        state.touch(key);
+        state.entries.get(key).map(|(pdf, _)| pdf.clone())
+    }
+
+    fn put(&self, key: &RenderCacheKey, pdf: &[u8]) {
+        if self.max_entries == 0 {
+            return;
+        }
+        let Ok(mut state) = self.inner.lock() else {
+            return;
+        };
+
+        if !state.entries.contains_key(key) {
+            while state.entries.len() >= self.max_entries {
+                let Some((_, oldest)) = state.recency.pop_first() else {
+                    break;
+                };
+                state.entries.remove(&oldest);
+            }
+            state.entries.insert(key.clone(), (pdf.to_vec(), 0));
+        }
+        state.touch(key);
+    }
+}
+
+/// Cache storing PDFs as files in a directory
+#[derive(Debug, Clone)]
+pub struct DiskRenderCache {
+    dir: PathBuf,
+}
+
+impl DiskRenderCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    fn path(&self, key: &RenderCacheKey) -> PathBuf {
+        self.dir.join(format!("{}.pdf", key.as_str()))
+    }
+}
+
+impl RenderCache for DiskRenderCache {
+    fn get(&self, key: &RenderCacheKey) -> Option<Vec<u8>> {
+        std::fs::read(self.path(key)).ok()
+    }
+
+    fn put(&self, key: &RenderCacheKey, pdf: &[u8]) {
+        let path = self.path(key);
+        // Write to a temporary file first so readers never see partial PDFs
+        let tmp = path.with_extension(format!("pdf.{}.tmp", std::process::id()));
+        let result = std::fs::create_dir_all(&self.dir)
+            .and_then(|_| std::fs::write(&tmp, pdf))
+            .and_then(|_| std::fs::rename(&tmp, &path));
+        if result.is_err() {
+            let _ = std::fs::remove_file(&tmp);
+        }
+    }
+}
@@ -0,0 +1,219 @@
+//! Sample data generated from schemas
+//!
+//! Lets template authors preview layouts before real data exists. Values
+//! are picked by field type and, for strings and numbers, by hints in the
+//! field key (`email`, `amount`, `city`, ...). Output is deterministic for a
+//! given seed.
+
+use serde_json::{Map, Value};
+
+use crate::schema::{ean13_check_digit, BarcodeKind, FieldType, Schema};
+
+const FIRST_NAMES: &[&str] = &[
+    "Alice", "Ben", "Clara", "David", "Emma", "Felix", "Grace", "Henry", "Isabel", "Jonas",
+    "Lena", "Marco", "Nora", "Oscar", "Paula", "Sofia",
+];
+const LAST_NAMES: &[&str] = &[
+    "Becker", "Costa", "Fischer", "Garcia", "Jensen", "Kowalski", "Lambert", "Meyer", "Nguyen",
+    "Novak", "Rossi", "Schmidt", "Tanaka", "Walsh",
+];
+const COMPANIES: &[&str] = &[
+    "Acme Corp", "Globex", "Initech", "Northwind Traders", "Umbrella GmbH", "Vandelay Industries",
+];
+const STREETS: &[&str] = &[
+    "Main Street", "Oak Avenue", "Harbor Road", "Station Square", "Mill Lane", "Park Boulevard",
+];
+const CITIES: &[&str] = &[
+    "Berlin", "Lisbon", "Vienna", "Copenhagen", "Amsterdam", "Zurich", "Dublin", "Prague",
+];
+const COUNTRIES: &[&str] = &["Germany", "Portugal", "Austria", "Denmark", "Netherlands", "Ireland"];
+const CURRENCIES: &[&str] = &["EUR", "USD", "GBP", "CHF"];
+const WORDS: &[&str] = &[
+    "quarterly", "service", "consulting", "hardware", "license", "support", "annual", "premium",
+    "delivery", "maintenance", "report", "standard",
+];
+
+/// Options for [`Schema::generate_sample_data_with`]
+#[derive(Debug, Clone)]
+pub struct SampleOptions {
+    /// Number of items generated for each array field
+    pub array_len: usize,
+    /// Whether optional fields without a default get a value
+    pub include_optional: bool,
+}
+
+impl Default for SampleOptions {
+    fn default() -> Self {
+        SampleOptions {
+            array_len: 3,
+            include_optional: true,
+        }
+    }
+}
+
+impl Schema {
+    /// Generate fake data matching this schema
+    ///
+    /// The same seed always yields the same data.
+    pub fn generate_sample_data(&self, seed: u64) -> Value {
+        self.generate_sample_data_with(seed, &SampleOptions::default())
+    }
+
+    /// Generate fake data matching this schema with custom options
+    pub fn generate_sample_data_with(&self, seed: u64, options: &SampleOptions) -> Value {
+        let mut rng = SampleRng::new(seed);
+        sample_object(self, &mut rng, options)
+    }
+}
+
+fn sample_object(schema: &Schema, rng: &mut SampleRng, options: &SampleOptions) -> Value {
+    let mut object = Map::new();
+    for field in &schema.fields {
+        // Defaults show what the template looks like without the field set
+        let value = if let Some(default) = &field.default {
+            default.clone()
+        } else if field.required || options.include_optional {
+            sample_value(&field.key, &field.field_type, rng, options)
+        } else {
+            continue;
+        };
+        object.insert(field.key.clone(), value);
+    }
+    Value::Object(object)
+}
+
+fn sample_value(key: &str, field_type: &FieldType, rng: &mut SampleRng, options: &SampleOptions) -> Value {
+    match field_type {
+        FieldType::String => Value::String(sample_string(&key.to_lowercase(), rng)),
+        FieldType::Number => sample_number(&key.to_lowercase(), rng),
+        FieldType::Boolean => Value::Bool(rng.below(2) == 1),
+        FieldType::Date => Value::String(sample_date(rng)),
+        FieldType::Barcode(kind) => Value::String(sample_barcode(*kind, rng)),
+        // Previews show every optional section
+        FieldType::Section => Value::Bool(true),
+        FieldType::Object(schema) => sample_object(schema, rng, options),
+        FieldType::Array(item_type) => Value::Array(
+            (0..options.array_len)
+                .map(|_| sample_value(key, item_type, rng, options))
+                .collect(),
+        ),
+    }
+}
+
+fn sample_barcode(kind: BarcodeKind, rng: &mut SampleRng) -> String {
+    match kind {
+        BarcodeKind::Qr => format!("https://example.com/t/{:06}", rng.below(1_000_000)),
+        BarcodeKind::Code128 => format!("PM-{:08}", rng.below(100_000_000)),
+        BarcodeKind::Ean13 => {
+            let digits = format!("400{:09}", rng.below(1_000_000_000));
+            format!("{}{}", digits, ean13_check_digit(&digits))
+        }
+    }
+}
+
+fn sample_string(key: &str, rng: &mut SampleRng) -> String {
+    let has = |hints: &[&str]| hints.iter().any(|hint| key.contains(hint));
+
+    if has(&["email", "mail"]) {
+        format!(
+            "{}.{}@example.com",
+            rng.pick(FIRST_NAMES).to_lowercase(),
+            rng.pick(LAST_NAMES).to_lowercase()
+        )
+    } else if has(&["phone", "tel", "mobile"]) {
+        format!("+49 30 {:04} {:04}", rng.below(10_000), rng.below(10_000))
+    } else if has(&["company", "organization", "organisation", "vendor", "customer"]) {
+        rng.pick(COMPANIES).to_string()
+    } else if has(&["first"]) {
+        rng.pick(FIRST_NAMES).to_string()
+    } else if has(&["last", "surname"]) {
+        rng.pick(LAST_NAMES).to_string()
+    } else if has(&["name"]) {
+        format!("{} {}", rng.pick(FIRST_NAMES), rng.pick(LAST_NAMES))
+    } else if has(&["street", "address"]) {
+        format!("{} {}", rng.pick(STREETS), 1 + rng.below(200))
+    } else if has(&["city", "town"]) {
+        rng.pick(CITIES).to_string()
+    } else if has(&["country"]) {
+        rng.pick(COUNTRIES).to_string()
+    } else if has(&["zip", "postal", "postcode"]) {
+        format!("{:05}", 10_000 + rng.below(90_000))
+    } else if has(&["currency"]) {
+        rng.pick(CURRENCIES).to_string()
+    } else if has(&["date", "_at"]) {
+        sample_date(rng)
+    } else if has(&["number", "code", "reference"]) || key == "id" || key.ends_with("_id") {
+        format!("{}-{:05}", (b'A' + rng.below(26) as u8) as char, rng.below(100_000))
+    } else if has(&["url", "website", "link"]) {
+        format!("https://example.com/{}", rng.pick(WORDS))
+    } else if has(&["description", "note", "comment", "text", "summary"]) {
+        let words: Vec<&str> = (0..8).map(|_| rng.pick(WORDS)).collect();
+        let mut sentence = words.join(" ");
+        sentence[..1].make_ascii_uppercase();
+        sentence + "."
+    } else {
+        let word = rng.pick(WORDS);
+        let mut title = word.to_string();
+        title[..1].make_ascii_uppercase();
+        format!("{} {}", title, rng.pick(WORDS))
+    }
+}
+
+fn sample_number(key: &str, rng: &mut SampleRng) -> Value {
+    let has = |hints: &[&str]| hints.iter().any(|hint| key.contains(hint));
+
+    if has(&["amount", "price", "total", "cost", "subtotal", "tax", "fee", "balance", "sum"]) {
+        // Currency amounts with two decimals
+        let cents = 100 + rng.below(500_000);
+        serde_json::Number::from_f64(cents as f64 / 100.0)
+            .map(Value::Number)
+            .unwrap_or(Value::from(cents / 100))
+    } else if has(&["percent", "rate", "discount"]) {
+        Value::from(rng.below(50))
+    } else if has(&["quantity", "qty", "count"]) {
+        Value::from(1 + rng.below(20))
+    } else if has(&["year"]) {
+        Value::from(2015 + rng.below(12))
+    } else if key == "age" || key.ends_with("_age") {
+        Value::from(18 + rng.below(60))
+    } else {
+        Value::from(rng.below(1_000))
+    }
+}
+
+/// A date between 2020 and 2027 in `YYYY-MM-DD` form
+fn sample_date(rng: &mut SampleRng) -> String {
+    format!(
+        "{}-{:02}-{:02}",
+        2020 + rng.below(8),
+        1 + rng.below(12),
+        1 + rng.below(28)
+    )
+}
+
+/// Small deterministic generator (SplitMix64); sample data doesn't need
+/// statistical quality, only stability across releases
+struct SampleRng(u64);
+
+impl SampleRng {
+    fn new(seed: u64) -> Self {
+        SampleRng(seed)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform-ish value in `0..bound`
+    fn below(&mut self, bound: u64) -> u64 {
+        self.next() % bound
+    }
+
+    fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+        items[self.below(items.len() as u64) as usize]
+    }
+}
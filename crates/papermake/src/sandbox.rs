@@ -0,0 +1,139 @@
+//! Sandbox policies restricting what a template may access
+//!
+//! Templates only ever see files papermake hands to the Typst world: shared
+//! template sources and built-in `papermake:` modules. A [`SandboxPolicy`]
+//! narrows that down further and can forbid Typst functions, so untrusted,
+//! user-submitted templates can be rendered safely.
+//!
+//! Policies are set server-wide through `RenderOptions::sandbox` and per
+//! template through `Template::sandbox`; when both are set, a render gets
+//! the more restrictive of the two.
+
+use serde::{Deserialize, Serialize};
+use typst::syntax::{LinkedNode, SyntaxKind};
+
+use crate::render::RenderError;
+
+/// What a template may access while rendering
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SandboxPolicy {
+    /// Import other templates via `papermake:shared/<id>.typ`
+    pub shared_imports: bool,
+    /// Import built-in modules like `papermake:locale.typ` and
+    /// `papermake:sections.typ`
+    pub builtin_modules: bool,
+    /// Import Typst packages; papermake doesn't resolve packages yet, so
+    /// allowed imports fail as unavailable rather than denied
+    pub packages: bool,
+    /// Typst functions the template may not reference, e.g. `read`
+    ///
+    /// The check is syntactic: any use of the name as a value is rejected,
+    /// whether it refers to the standard library function or not.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub denied_functions: Vec<String>,
+}
+
+impl Default for SandboxPolicy {
+    /// A permissive policy, matching renders without a sandbox
+    fn default() -> Self {
+        Self {
+            shared_imports: true,
+            builtin_modules: true,
+            packages: true,
+            denied_functions: Vec::new(),
+        }
+    }
+}
+
+impl SandboxPolicy {
+    /// Policy for untrusted templates: no imports besides the built-in
+    /// modules, and no reading files, loading plugins or evaluating
+    /// strings as code
+    pub fn restrictive() -> Self {
+        Self {
+            shared_imports: false,
+            builtin_modules: true,
+            packages: false,
+            denied_functions: ["eval", "plugin", "read"].map(String::from).to_vec(),
+        }
+    }
+
+    /// Deny a Typst function
+    pub fn deny_function(mut self, name: impl Into<String>) -> Self {
+        let name = name.into();
+        if !self.denied_functions.contains(&name) {
+            self.denied_functions.push(name);
+        }
+        self
+    }
+
+    /// The policy allowing only what both `self` and `other` allow
+    pub fn intersect(&self, other: &SandboxPolicy) -> SandboxPolicy {
+        let mut denied = self.denied_functions.clone();
+        for name in &other.denied_functions {
+            if !denied.contains(name) {
+                denied.push(name.clone());
+            }
+        }
+        SandboxPolicy {
+            shared_imports: self.shared_imports && other.shared_imports,
+            builtin_modules: self.builtin_modules && other.builtin_modules,
+            packages: self.packages && other.packages,
+            denied_functions: denied,
+        }
+    }
+
+    /// The policy in effect when a render and a template both may set one
+    pub fn effective(render: Option<&SandboxPolicy>, template: Option<&SandboxPolicy>) -> SandboxPolicy {
+        match (render, template) {
+            (Some(a), Some(b)) => a.intersect(b),
+            (Some(policy), None) | (None, Some(policy)) => policy.clone(),
+            (None, None) => SandboxPolicy::default(),
+        }
+    }
+
+    /// Uses of denied functions in Typst source
+    pub fn check_source(&self, source: &str) -> Vec<RenderError> {
+        if self.denied_functions.is_empty() {
+            return Vec::new();
+        }
+
+        let root = typst::syntax::parse(source);
+        let mut errors = Vec::new();
+        self.visit(&LinkedNode::new(&root), &mut errors);
+        errors
+    }
+
+    fn visit(&self, node: &LinkedNode, errors: &mut Vec<RenderError>) {
+        let is_ident = matches!(node.kind(), SyntaxKind::Ident | SyntaxKind::MathIdent);
+        if is_ident && self.denied_functions.iter().any(|name| name == node.text().as_str()) && !is_key(node) {
+            let range = node.range();
+            errors.push(RenderError {
+                message: format!("`{}` is not allowed by the sandbox policy", node.text()),
+                start: range.start,
+                end: range.end,
+            });
+        }
+        for child in node.children() {
+            self.visit(&child, errors);
+        }
+    }
+}
+
+/// Whether an identifier names a field or argument rather than referring
+/// to a value: `data.read`, `(read: 1)`. Fields of `std` still count.
+fn is_key(node: &LinkedNode) -> bool {
+    let Some(parent) = node.parent() else {
+        return false;
+    };
+    let first = parent.children().next();
+    let is_first = first.as_ref().is_some_and(|first| first.range() == node.range());
+    match parent.kind() {
+        SyntaxKind::Named => is_first,
+        SyntaxKind::FieldAccess => {
+            !is_first && !first.is_some_and(|target| target.kind() == SyntaxKind::Ident && target.text() == "std")
+        }
+        _ => false,
+    }
+}
@@ -0,0 +1,292 @@
+//! Starter Typst content generated from a schema
+//!
+//! [`Template::scaffold`] lays out every field of a schema so new templates
+//! start from a working document instead of a blank file: a title block,
+//! labeled values, a section per nested object and a table per array of
+//! objects. Barcode fields are shown as images. Missing optional values are
+//! shown as a dash, so the scaffold renders with any data matching the
+//! schema.
+
+use serde::{Deserialize, Serialize};
+
+use crate::schema::{BarcodeKind, FieldType, Schema, SchemaField, BARCODE_DIR};
+use crate::template::Template;
+
+/// Layout of scaffolded content
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScaffoldStyle {
+    /// Labeled values one per line, with a heading per nested object
+    #[default]
+    Document,
+    /// Labeled values in two-column tables, like a filled-in form
+    Form,
+    /// Numbered section headings, a running header and page numbers
+    Report,
+}
+
+/// Top-level string field used for the title block, if present
+const TITLE_KEY: &str = "title";
+
+const PRELUDE: &str = r#"#let data = json.decode(sys.inputs.data)
+
+// Shows a value, or a dash if it is missing
+#let show-value(value) = if value == none [—] else if type(value) == bool {
+  if value [Yes] else [No]
+} else [#value]
+"#;
+
+impl Template {
+    /// Generate starter Typst content displaying every field of `schema`
+    pub fn scaffold(schema: &Schema, style: ScaffoldStyle) -> String {
+        let title_field = schema
+            .fields
+            .iter()
+            .find(|field| field.key == TITLE_KEY && field.field_type == FieldType::String);
+        let title = match title_field {
+            Some(field) => format!("#show-value({})", at("data", &field.key, "none")),
+            None => "Document title".to_string(),
+        };
+
+        let mut out = String::from(PRELUDE);
+        out.push('\n');
+        match style {
+            ScaffoldStyle::Report => {
+                out.push_str(&format!(
+                    "#set page(paper: \"a4\", margin: 2cm, numbering: \"1 / 1\", header: align(right, text(size: 9pt, fill: luma(120))[{}]))\n",
+                    title
+                ));
+                out.push_str("#set heading(numbering: \"1.\")\n");
+            }
+            _ => out.push_str("#set page(paper: \"a4\", margin: 2cm)\n"),
+        }
+        out.push_str("#set text(size: 11pt)\n");
+        out.push_str("#set table(stroke: 0.5pt + luma(180), inset: 6pt)\n\n");
+
+        match style {
+            ScaffoldStyle::Report => {
+                out.push_str(&format!("#text(size: 20pt, weight: \"bold\")[{}]\n", title));
+                out.push_str("#line(length: 100%)\n\n");
+            }
+            _ => out.push_str(&format!("#align(center, text(size: 20pt, weight: \"bold\")[{}])\n\n", title)),
+        }
+
+        let fields: Vec<&SchemaField> = schema
+            .fields
+            .iter()
+            .filter(|field| !title_field.is_some_and(|title| std::ptr::eq(*field, title)))
+            .collect();
+        let mut scaffold = Scaffold { out, style };
+        scaffold.fields(&fields, "data", "", 1);
+        scaffold.out
+    }
+}
+
+struct Scaffold {
+    out: String,
+    style: ScaffoldStyle,
+}
+
+impl Scaffold {
+    /// Lay out fields of the object at `target`, whose barcodes live under
+    /// `path` (e.g. `customer.`)
+    fn fields(&mut self, fields: &[&SchemaField], target: &str, path: &str, depth: usize) {
+        let mut rows = Vec::new();
+        for field in fields {
+            let label = escape_markup(&label(field));
+            let field_path = format!("{}{}", path, field.key);
+            match &field.field_type {
+                FieldType::Object(schema) => {
+                    self.rows(&mut rows);
+                    self.heading(field, depth);
+                    let nested: Vec<&SchemaField> = schema.fields.iter().collect();
+                    let target = at(target, &field.key, "(:)");
+                    self.fields(&nested, &target, &format!("{}.", field_path), depth + 1);
+                }
+                FieldType::Array(item) if matches!(item.as_ref(), FieldType::Object(_)) => {
+                    self.rows(&mut rows);
+                    self.heading(field, depth);
+                    let FieldType::Object(schema) = item.as_ref() else { unreachable!() };
+                    self.table(schema, &at(target, &field.key, "()"), &field_path);
+                }
+                field_type => {
+                    let value = value_markup(field_type, &at(target, &field.key, default(field_type)), &quoted(&field_path));
+                    rows.push((label, value, field.description.clone()));
+                }
+            }
+        }
+        self.rows(&mut rows);
+    }
+
+    fn heading(&mut self, field: &SchemaField, depth: usize) {
+        self.out.push_str(&format!("{} {}\n", "=".repeat(depth), escape_markup(&label(field))));
+        if let Some(description) = &field.description {
+            self.out.push_str(&format!("// {}\n", description.replace('\n', " ")));
+        }
+        self.out.push('\n');
+    }
+
+    /// Write and clear a run of labeled values
+    fn rows(&mut self, rows: &mut Vec<(String, String, Option<String>)>) {
+        if rows.is_empty() {
+            return;
+        }
+        if self.style == ScaffoldStyle::Form {
+            self.out.push_str("#table(\n  columns: (auto, 1fr),\n");
+            for (label, value, description) in rows.iter() {
+                if let Some(description) = description {
+                    self.out.push_str(&format!("  // {}\n", description.replace('\n', " ")));
+                }
+                self.out.push_str(&format!("  [*{}*], [{}],\n", label, value));
+            }
+            self.out.push_str(")\n\n");
+        } else {
+            let last = rows.len() - 1;
+            for (i, (label, value, description)) in rows.iter().enumerate() {
+                if let Some(description) = description {
+                    self.out.push_str(&format!("// {}\n", description.replace('\n', " ")));
+                }
+                let line_break = if i < last { " \\" } else { "" };
+                self.out.push_str(&format!("*{}:* {}{}\n", label, value, line_break));
+            }
+            self.out.push('\n');
+        }
+        rows.clear();
+    }
+
+    /// A table with a row per array item and a column per item field
+    fn table(&mut self, schema: &Schema, target: &str, path: &str) {
+        let mut columns = Vec::new();
+        item_columns(schema, "item", "", &mut columns);
+        if columns.is_empty() {
+            return;
+        }
+        let header: Vec<String> = columns.iter().map(|(label, _)| format!("[*{}*]", label)).collect();
+        let cells: Vec<String> = columns.iter().map(|(_, value)| format!("[{}]", value)).collect();
+
+        // The index is only needed for the barcode paths of the items
+        let uses_index = columns.iter().any(|(_, value)| value.contains(BARCODE_DIR));
+        let (items, binding) = if uses_index {
+            (format!("{}.enumerate()", target), "((i, item))")
+        } else {
+            (target.to_string(), "item")
+        };
+        let cells = cells.join(", ").replace(ITEM_PATH, &format!("{}.\" + str(i) + \".", path));
+
+        self.out.push_str("#table(\n");
+        self.out.push_str(&format!("  columns: {},\n", columns.len()));
+        self.out.push_str(&format!("  table.header({}),\n", header.join(", ")));
+        self.out.push_str(&format!("  ..{}.map({} => ({},)).flatten(),\n", items, binding, cells));
+        self.out.push_str(")\n\n");
+    }
+}
+
+/// Placeholder for the array path of an item's barcodes, replaced once the
+/// table knows its path
+const ITEM_PATH: &str = "{item}.";
+
+/// Columns for the fields of an array item, flattening nested objects
+fn item_columns(schema: &Schema, target: &str, path: &str, columns: &mut Vec<(String, String)>) {
+    for field in &schema.fields {
+        let label = escape_markup(&label(field));
+        match &field.field_type {
+            FieldType::Object(nested) => {
+                let mut nested_columns = Vec::new();
+                item_columns(
+                    nested,
+                    &at(target, &field.key, "(:)"),
+                    &format!("{}{}.", path, field.key),
+                    &mut nested_columns,
+                );
+                columns.extend(
+                    nested_columns
+                        .into_iter()
+                        .map(|(nested_label, value)| (format!("{}: {}", label, nested_label), value)),
+                );
+            }
+            // Arrays of objects in a cell only show how many there are
+            FieldType::Array(item) if matches!(item.as_ref(), FieldType::Object(_)) => {
+                columns.push((label, format!("#{}.len()", at(target, &field.key, "()"))));
+            }
+            field_type => {
+                let barcode_path = quoted(&format!("{}{}{}", ITEM_PATH, path, field.key));
+                columns.push((label, value_markup(field_type, &at(target, &field.key, default(field_type)), &barcode_path)));
+            }
+        }
+    }
+}
+
+/// Markup showing a value that is not an object or an array of objects
+fn value_markup(field_type: &FieldType, value: &str, barcode_path: &str) -> String {
+    match field_type {
+        FieldType::Barcode(kind) => format!("#if {} != none {{ {} }} else [—]", value, barcode_image(*kind, barcode_path)),
+        FieldType::Array(item) => match item.as_ref() {
+            FieldType::Barcode(kind) => {
+                let path = format!("{} + \".\" + str(j)", barcode_path);
+                format!(
+                    "#stack(dir: ltr, spacing: 1em, ..{}.enumerate().map(((j, _)) => {}))",
+                    value,
+                    barcode_image(*kind, &path)
+                )
+            }
+            _ => format!("#show-value({}.map(show-value).join([, ]))", value),
+        },
+        _ => format!("#show-value({})", value),
+    }
+}
+
+fn barcode_image(kind: BarcodeKind, path: &str) -> String {
+    let size = match kind {
+        BarcodeKind::Qr => "width: 3cm",
+        BarcodeKind::Code128 | BarcodeKind::Ean13 => "height: 1.5cm",
+    };
+    let path = format!("\"papermake:{}\" + {} + \".svg\"", BARCODE_DIR, path);
+    format!("image({}, {})", merge_literals(&path), size)
+}
+
+/// Join adjacent string literals, e.g. `"a" + "b"` into `"ab"`
+fn merge_literals(expr: &str) -> String {
+    expr.replace("\" + \"", "")
+}
+
+/// Value used for a missing field, matching what the layout expects
+fn default(field_type: &FieldType) -> &'static str {
+    match field_type {
+        FieldType::Array(_) => "()",
+        _ => "none",
+    }
+}
+
+/// `target.at("key", default: ...)`
+fn at(target: &str, key: &str, default: &str) -> String {
+    format!("{}.at({}, default: {})", target, quoted(key), default)
+}
+
+fn quoted(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// The field's label, or its key in sentence case
+fn label(field: &SchemaField) -> String {
+    if let Some(label) = &field.label {
+        return label.clone();
+    }
+    let words = field.key.replace(['_', '-'], " ");
+    let mut chars = words.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => words,
+    }
+}
+
+/// Escape characters with a meaning in Typst markup
+fn escape_markup(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '*' | '_' | '#' | '[' | ']' | '$' | '@' | '<' | '>' | '`' | '~' | '/' | '=' | '-' | '+') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
@@ -12,11 +12,107 @@ pub enum FieldType {
     Number,
     Boolean,
     Date,
+    /// Text drawn as a barcode with the `barcodes` feature, see `crate::barcode`
+    Barcode(BarcodeKind),
+    /// Boolean switching a named block of the document on or off, see
+    /// [`crate::sections`]
+    Section,
     Object(Box<Schema>),
     Array(Box<FieldType>),
 }
 
+/// Path prefix of generated barcodes, after the `papermake:` scheme
+pub(crate) const BARCODE_DIR: &str = "barcodes/";
+
+/// Symbology of a barcode field
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BarcodeKind {
+    /// QR code of arbitrary text, e.g. a URL or payment data
+    Qr,
+    /// Code 128 of printable ASCII text
+    Code128,
+    /// EAN-13 product code: 12 digits, or 13 including the check digit
+    Ean13,
+}
+
+impl BarcodeKind {
+    /// Check that `text` can be encoded, describing the problem if not.
+    /// Without the `barcodes` feature QR codes aren't drawn, so any text passes
+    pub fn check(&self, text: &str) -> std::result::Result<(), String> {
+        match self {
+            #[cfg(feature = "barcodes")]
+            BarcodeKind::Qr => qrcode::QrCode::new(text.as_bytes())
+                .map(|_| ())
+                .map_err(|e| format!("can't be encoded as a QR code: {}", e)),
+            #[cfg(not(feature = "barcodes"))]
+            BarcodeKind::Qr => Ok(()),
+            BarcodeKind::Code128 => {
+                if text.is_empty() || !text.chars().all(|c| (' '..='~').contains(&c)) {
+                    return Err("must be non-empty printable ASCII for Code 128".to_string());
+                }
+                Ok(())
+            }
+            BarcodeKind::Ean13 => {
+                if !matches!(text.len(), 12 | 13) || !text.bytes().all(|b| b.is_ascii_digit()) {
+                    return Err("must be 12 or 13 digits for EAN-13".to_string());
+                }
+                if text.len() == 13 && ean13_check_digit(&text[..12]) != text.as_bytes()[12] - b'0' {
+                    return Err("has an invalid EAN-13 check digit".to_string());
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Check digit of the first 12 digits of an EAN-13 code
+pub(crate) fn ean13_check_digit(digits: &str) -> u8 {
+    let sum: u32 = digits
+        .bytes()
+        .enumerate()
+        .map(|(i, b)| u32::from(b - b'0') * if i % 2 == 0 { 1 } else { 3 })
+        .sum();
+    ((10 - sum % 10) % 10) as u8
+}
+
+/// Normalize a date string within the format it arrived in: RFC 3339
+/// timestamps stay timestamps with their offset, calendar dates like
+/// `2024-01-05` stay calendar dates
+fn normalize_date(value: &str) -> Option<serde_json::Value> {
+    use time::format_description::well_known::Rfc3339;
+
+    let value = value.trim();
+    let normalized = match time::OffsetDateTime::parse(value, &Rfc3339) {
+        Ok(timestamp) => timestamp.format(&Rfc3339).ok()?,
+        Err(_) => {
+            let format = time::macros::format_description!("[year]-[month]-[day]");
+            time::Date::parse(value, format).ok()?.format(format).ok()?
+        }
+    };
+    Some(serde_json::Value::String(normalized))
+}
+
+/// Input control a generated form uses for a field
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Widget {
+    Text,
+    Textarea,
+    /// A choice between fixed values
+    Select { options: Vec<String> },
+    DatePicker,
+    Checkbox,
+    Number,
+    /// Not shown; the value comes from the default or the calling system
+    Hidden,
+}
+
 /// A field in a schema with metadata
+///
+/// Besides the data's shape, fields carry presentation hints (`label`,
+/// `description`, `placeholder`, `widget`, `order`, `group`) so frontends
+/// can generate data entry forms from the schema alone.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct SchemaField {
     pub key: String,
@@ -26,6 +122,66 @@ pub struct SchemaField {
     pub description: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub default: Option<serde_json::Value>,
+    /// Hint shown in the empty input
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub placeholder: Option<String>,
+    /// Input control; forms choose one by field type if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub widget: Option<Widget>,
+    /// Position in the form, lowest first; fields without one follow in schema order
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub order: Option<i32>,
+    /// Heading the field is grouped under in the form
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+}
+
+impl SchemaField {
+    /// A required field without presentation hints
+    pub fn new(key: impl Into<String>, field_type: FieldType) -> Self {
+        Self {
+            key: key.into(),
+            label: None,
+            field_type,
+            required: true,
+            description: None,
+            default: None,
+            placeholder: None,
+            widget: None,
+            order: None,
+            group: None,
+        }
+    }
+
+    pub fn with_label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    pub fn with_placeholder(mut self, placeholder: impl Into<String>) -> Self {
+        self.placeholder = Some(placeholder.into());
+        self
+    }
+
+    pub fn with_widget(mut self, widget: Widget) -> Self {
+        self.widget = Some(widget);
+        self
+    }
+
+    pub fn with_order(mut self, order: i32) -> Self {
+        self.order = Some(order);
+        self
+    }
+
+    pub fn with_group(mut self, group: impl Into<String>) -> Self {
+        self.group = Some(group.into());
+        self
+    }
 }
 
 /// A schema defining the structure of data for a template
@@ -76,6 +232,107 @@ impl Schema {
         Ok(())
     }
     
+    /// Fill in default values for optional fields missing from the data
+    ///
+    /// Defaults are applied recursively to nested objects and to objects
+    /// inside arrays.
+    pub fn apply_defaults(&self, data: &mut serde_json::Value) {
+        let Some(data_obj) = data.as_object_mut() else {
+            return;
+        };
+        
+        for field in &self.fields {
+            if !data_obj.contains_key(&field.key) {
+                if let Some(default) = &field.default {
+                    data_obj.insert(field.key.clone(), default.clone());
+                }
+            }
+            
+            if let Some(value) = data_obj.get_mut(&field.key) {
+                Self::apply_field_defaults(&field.field_type, value);
+            }
+        }
+    }
+    
+    fn apply_field_defaults(field_type: &FieldType, value: &mut serde_json::Value) {
+        match field_type {
+            FieldType::Object(sub_schema) => sub_schema.apply_defaults(value),
+            FieldType::Array(item_type) => {
+                if let Some(items) = value.as_array_mut() {
+                    for item in items {
+                        Self::apply_field_defaults(item_type, item);
+                    }
+                }
+            },
+            _ => {}
+        }
+    }
+    
+    /// Coerce loosely typed values into the types declared by the schema
+    ///
+    /// Strings holding numbers or booleans are converted for `Number`,
+    /// `Boolean` and `Section` fields, numbers and booleans are stringified for `String`
+    /// fields, numbers for `Barcode` fields, and unix timestamps are converted to RFC 3339 strings for
+    /// `Date` fields, while date strings are only normalized within their own format. Values that
+    /// cannot be coerced are left untouched so validation can report them.
+    pub fn coerce(&self, data: &mut serde_json::Value) {
+        let Some(data_obj) = data.as_object_mut() else {
+            return;
+        };
+        
+        for field in &self.fields {
+            if let Some(value) = data_obj.get_mut(&field.key) {
+                Self::coerce_value(&field.field_type, value);
+            }
+        }
+    }
+    
+    fn coerce_value(field_type: &FieldType, value: &mut serde_json::Value) {
+        use serde_json::Value;
+        
+        match field_type {
+            FieldType::Object(sub_schema) => return sub_schema.coerce(value),
+            FieldType::Array(item_type) => {
+                if let Some(items) = value.as_array_mut() {
+                    for item in items {
+                        Self::coerce_value(item_type, item);
+                    }
+                }
+                return;
+            },
+            _ => {}
+        }
+        
+        let coerced = match (field_type, &*value) {
+            (FieldType::Number, Value::String(s)) => {
+                let s = s.trim();
+                s.parse::<i64>().map(Value::from).ok()
+                    .or_else(|| s.parse::<f64>().ok()
+                        .and_then(serde_json::Number::from_f64)
+                        .map(Value::Number))
+            },
+            (FieldType::Boolean | FieldType::Section, Value::String(s)) => match s.trim().to_lowercase().as_str() {
+                "true" | "yes" | "1" => Some(Value::Bool(true)),
+                "false" | "no" | "0" => Some(Value::Bool(false)),
+                _ => None,
+            },
+            (FieldType::String, Value::Number(n)) => Some(Value::String(n.to_string())),
+            (FieldType::String, Value::Bool(b)) => Some(Value::String(b.to_string())),
+            // EAN codes are often sent as numbers
+            (FieldType::Barcode(_), Value::Number(n)) => Some(Value::String(n.to_string())),
+            (FieldType::Date, Value::Number(n)) => n.as_i64()
+                .and_then(|ts| time::OffsetDateTime::from_unix_timestamp(ts).ok())
+                .and_then(|dt| dt.format(&time::format_description::well_known::Rfc3339).ok())
+                .map(Value::String),
+            (FieldType::Date, Value::String(s)) => normalize_date(s),
+            _ => None,
+        };
+        
+        if let Some(coerced) = coerced {
+            *value = coerced;
+        }
+    }
+    
     // Validate that a value matches the expected type
     fn validate_field_type(&self, field_type: &FieldType, value: &serde_json::Value, path: &str) -> Result<()> {
         match field_type {
@@ -93,7 +350,7 @@ impl Schema {
                     ));
                 }
             },
-            FieldType::Boolean => {
+            FieldType::Boolean | FieldType::Section => {
                 if !value.is_boolean() {
                     return Err(PapermakeError::SchemaValidation(
                         format!("Field '{}' must be a boolean", path)
@@ -109,6 +366,18 @@ impl Schema {
                     ));
                 }
             },
+            FieldType::Barcode(kind) => {
+                let Some(text) = value.as_str() else {
+                    return Err(PapermakeError::SchemaValidation(
+                        format!("Field '{}' must be a string", path)
+                    ));
+                };
+                if let Err(message) = kind.check(text) {
+                    return Err(PapermakeError::SchemaValidation(
+                        format!("Field '{}' {}", path, message)
+                    ));
+                }
+            },
             FieldType::Object(sub_schema) => {
                 if !value.is_object() {
                     return Err(PapermakeError::SchemaValidation(
@@ -150,27 +419,13 @@ impl SchemaBuilder {
     
     /// Add a required field
     pub fn field(mut self, key: impl Into<String>, field_type: FieldType) -> Self {
-        self.fields.push(SchemaField {
-            key: key.into(),
-            label: None,
-            field_type,
-            required: true,
-            description: None,
-            default: None,
-        });
+        self.fields.push(SchemaField::new(key, field_type));
         self
     }
     
     /// Add a required field with label
     pub fn field_with_label(mut self, key: impl Into<String>, label: impl Into<String>, field_type: FieldType) -> Self {
-        self.fields.push(SchemaField {
-            key: key.into(),
-            label: Some(label.into()),
-            field_type,
-            required: true,
-            description: None,
-            default: None,
-        });
+        self.fields.push(SchemaField::new(key, field_type).with_label(label));
         self
     }
     
@@ -181,40 +436,29 @@ impl SchemaBuilder {
     
     /// Add an optional field
     pub fn optional(mut self, key: impl Into<String>, field_type: FieldType) -> Self {
-        self.fields.push(SchemaField {
-            key: key.into(),
-            label: None,
-            field_type,
-            required: false,
-            description: None,
-            default: None,
-        });
+        self.fields.push(SchemaField { required: false, ..SchemaField::new(key, field_type) });
         self
     }
     
     /// Add an optional field with default value
     pub fn optional_with_default(mut self, key: impl Into<String>, field_type: FieldType, default: serde_json::Value) -> Self {
         self.fields.push(SchemaField {
-            key: key.into(),
-            label: None,
-            field_type,
             required: false,
-            description: None,
             default: Some(default),
+            ..SchemaField::new(key, field_type)
         });
         self
     }
     
     /// Add a field with description
     pub fn field_with_description(mut self, key: impl Into<String>, field_type: FieldType, description: impl Into<String>) -> Self {
-        self.fields.push(SchemaField {
-            key: key.into(),
-            label: None,
-            field_type,
-            required: true,
-            description: Some(description.into()),
-            default: None,
-        });
+        self.fields.push(SchemaField::new(key, field_type).with_description(description));
+        self
+    }
+    
+    /// Add a fully configured field, e.g. with presentation hints
+    pub fn add(mut self, field: SchemaField) -> Self {
+        self.fields.push(field);
         self
     }
     
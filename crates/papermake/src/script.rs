@@ -0,0 +1,88 @@
+//! Rhai scripts computing fields from the render data
+//!
+//! A template can carry a script in [`Template::script`](crate::Template::script)
+//! that runs before validation, so business logic like totals or formatted
+//! addresses lives with the template instead of in every calling service.
+//! The script sees the data as a mutable `data` map:
+//!
+//! ```rhai
+//! let total = 0.0;
+//! for item in data.items {
+//!     total += item.price * item.quantity;
+//! }
+//! data.total = total;
+//! data.address = `${data.street}\n${data.zip} ${data.city}`;
+//! ```
+//!
+//! Scripts are sandboxed: they can't import modules, evaluate strings or
+//! print, and [`ScriptLimits`] caps the operations they run and the size
+//! of the values they build.
+
+use rhai::module_resolvers::DummyModuleResolver;
+use rhai::{Dynamic, Engine, Scope};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{PapermakeError, Result};
+
+/// Resource limits of a template script
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScriptLimits {
+    /// Operations (roughly, evaluated expressions) before the script is stopped
+    pub max_operations: u64,
+    /// Depth of nested function calls
+    pub max_call_levels: usize,
+    /// Length of a string, in bytes
+    pub max_string_size: usize,
+    /// Items of an array
+    pub max_array_size: usize,
+    /// Entries of a map
+    pub max_map_size: usize,
+}
+
+impl Default for ScriptLimits {
+    fn default() -> Self {
+        Self {
+            max_operations: 100_000,
+            max_call_levels: 32,
+            max_string_size: 1 << 20,
+            max_array_size: 100_000,
+            max_map_size: 10_000,
+        }
+    }
+}
+
+impl ScriptLimits {
+    /// A sandboxed engine enforcing these limits
+    fn engine(&self) -> Engine {
+        let mut engine = Engine::new();
+        engine.set_module_resolver(DummyModuleResolver::new());
+        engine.disable_symbol("eval");
+        engine.on_print(|_| {});
+        engine.on_debug(|_, _, _| {});
+        engine.set_max_operations(self.max_operations);
+        engine.set_max_call_levels(self.max_call_levels);
+        engine.set_max_string_size(self.max_string_size);
+        engine.set_max_array_size(self.max_array_size);
+        engine.set_max_map_size(self.max_map_size);
+        engine
+    }
+}
+
+/// Run a template script on the data, replacing it with the script's `data`
+pub fn run_script(script: &str, data: &mut serde_json::Value, limits: &ScriptLimits) -> Result<()> {
+    let engine = limits.engine();
+    let input = rhai::serde::to_dynamic(&*data).map_err(|e| script_error(&e))?;
+
+    let mut scope = Scope::new();
+    scope.push_dynamic("data", input);
+    engine.run_with_scope(&mut scope, script).map_err(|e| script_error(&e))?;
+
+    let output = scope.get_value::<Dynamic>("data").unwrap_or_default();
+    *data = rhai::serde::from_dynamic(&output).map_err(|e| script_error(&e))?;
+    Ok(())
+}
+
+fn script_error(err: &rhai::EvalAltResult) -> PapermakeError {
+    PapermakeError::InvalidInput(format!("Template script failed: {}", err))
+}
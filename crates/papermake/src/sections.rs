@@ -0,0 +1,130 @@
+//! Optional document sections toggled by schema fields
+//!
+//! Fields of type [`FieldType::Section`] hold a boolean switching a named
+//! block of the document on or off. Before each render their states are
+//! exposed as `sys.inputs.sections`, keyed by field path (nested keys joined
+//! with dots), and templates use them through the built-in helpers:
+//!
+//! ```typst
+//! #import "papermake:sections.typ": section, enabled
+//! #section("discount")[You saved #data.discount!]
+//! #if enabled("terms") { include-terms() }
+//! ```
+//!
+//! Missing section fields count as disabled. Naming a section the schema
+//! doesn't declare is an error, reported before the template is compiled.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Range;
+
+use typst::foundations::{Dict, IntoValue};
+use typst::syntax::ast::{self, Expr};
+use typst::syntax::LinkedNode;
+
+use crate::render::RenderError;
+use crate::schema::{FieldType, Schema};
+
+/// Import path (without scheme) of the section helpers
+pub const SECTIONS_MODULE_PATH: &str = "sections.typ";
+
+/// Typst source of the section helpers; reads the states injected by
+/// [`section_inputs`]
+pub const SECTIONS_MODULE: &str = r#"
+#let sections = sys.inputs.at("sections", default: (:))
+
+/// Whether the section is switched on.
+#let enabled(name) = {
+  assert(name in sections, message: "Section '" + name + "' is not declared in the schema")
+  sections.at(name)
+}
+
+/// Show `body` only if the section is switched on.
+#let section(name, body) = if enabled(name) { body }
+"#;
+
+/// Helpers taking a section name as their first argument
+const HELPERS: [&str; 2] = ["section", "enabled"];
+
+/// Paths of all section fields, descending into nested objects
+pub fn declared_sections(schema: &Schema) -> BTreeSet<String> {
+    let mut states = BTreeMap::new();
+    collect_states(schema, None, "", &mut states);
+    states.into_keys().collect()
+}
+
+/// Whether each declared section is switched on in `data`
+pub fn section_states(schema: &Schema, data: &serde_json::Value) -> BTreeMap<String, bool> {
+    let mut states = BTreeMap::new();
+    collect_states(schema, Some(data), "", &mut states);
+    states
+}
+
+fn collect_states(
+    schema: &Schema,
+    data: Option<&serde_json::Value>,
+    prefix: &str,
+    states: &mut BTreeMap<String, bool>,
+) {
+    for field in &schema.fields {
+        let path = format!("{}{}", prefix, field.key);
+        let value = data.and_then(|data| data.get(&field.key));
+        match &field.field_type {
+            FieldType::Section => {
+                states.insert(path, value.and_then(serde_json::Value::as_bool).unwrap_or(false));
+            }
+            FieldType::Object(nested) => collect_states(nested, value, &format!("{}.", path), states),
+            _ => {}
+        }
+    }
+}
+
+/// Entry added to `sys.inputs` for the section states
+pub(crate) fn section_inputs(states: &BTreeMap<String, bool>) -> Dict {
+    let sections: Dict = states
+        .iter()
+        .map(|(path, on)| (path.as_str().into(), on.into_value()))
+        .collect();
+    let mut inputs = Dict::new();
+    inputs.insert("sections".into(), sections.into_value());
+    inputs
+}
+
+/// Sections named in `source` that `schema` doesn't declare
+pub fn check_sections(source: &str, schema: &Schema) -> Vec<RenderError> {
+    let declared = declared_sections(schema);
+    referenced_sections(source)
+        .into_iter()
+        .filter(|(name, _)| !declared.contains(name))
+        .map(|(name, range)| RenderError {
+            message: format!("Section '{}' is not declared in the schema", name),
+            start: range.start,
+            end: range.end,
+        })
+        .collect()
+}
+
+/// Section names passed to the helpers in `source`, with the calls' ranges
+///
+/// Only sources importing the helpers are searched, so templates defining
+/// their own `section` function are left alone.
+pub(crate) fn referenced_sections(source: &str) -> Vec<(String, Range<usize>)> {
+    if !source.contains(&format!("papermake:{}", SECTIONS_MODULE_PATH)) {
+        return Vec::new();
+    }
+    let root = typst::syntax::parse(source);
+    let mut found = Vec::new();
+    visit(&LinkedNode::new(&root), &mut found);
+    found
+}
+
+fn visit(node: &LinkedNode, found: &mut Vec<(String, Range<usize>)>) {
+    if let Some(call) = node.cast::<ast::FuncCall>() {
+        let helper = matches!(call.callee(), Expr::Ident(ident) if HELPERS.contains(&ident.get().as_str()));
+        if let (true, Some(ast::Arg::Pos(Expr::Str(name)))) = (helper, call.args().items().next()) {
+            found.push((name.get().to_string(), node.range()));
+        }
+    }
+    for child in node.children() {
+        visit(&child, found);
+    }
+}
@@ -0,0 +1,209 @@
+//! Shared partials imported across templates
+//!
+//! Templates marked as shared act as a library of snippets (headers,
+//! footers, style definitions). Other templates import them with
+//! `#import "papermake:shared/<id>.typ"`. Since rendering is synchronous and
+//! storage is not, imports are resolved up front into [`SharedSources`],
+//! which are passed to the compiler through `RenderOptions`.
+
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+use serde::Serialize;
+use typst::syntax::ast::{self, Expr};
+use typst::syntax::LinkedNode;
+
+use crate::error::{PapermakeError, Result};
+use crate::locale::LOCALE_MODULE_PATH;
+use crate::storage::Storage;
+use crate::template::{Template, TemplateId};
+
+/// Prefix of import paths resolved from storage
+pub const IMPORT_SCHEME: &str = "papermake:";
+
+/// Namespace of shared templates within `papermake:` paths
+const SHARED_PREFIX: &str = "shared/";
+
+/// Resolved sources of shared templates, keyed by import path without the
+/// scheme (e.g. `shared/header.typ`)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SharedSources {
+    sources: BTreeMap<String, String>,
+}
+
+impl SharedSources {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the source for an import path
+    pub fn insert(&mut self, path: impl Into<String>, content: impl Into<String>) {
+        self.sources.insert(path.into(), content.into());
+    }
+
+    /// Source for an import path
+    pub fn get(&self, path: &str) -> Option<&str> {
+        self.sources.get(path).map(String::as_str)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sources.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.sources.iter().map(|(path, content)| (path.as_str(), content.as_str()))
+    }
+}
+
+/// Import paths (without the scheme) of all `papermake:` imports and includes
+pub fn shared_imports(content: &str) -> Vec<String> {
+    let root = typst::syntax::parse(content);
+    let mut imports = Vec::new();
+    collect_imports(&LinkedNode::new(&root), &mut imports);
+    imports.dedup();
+    imports
+}
+
+fn collect_imports(node: &LinkedNode, imports: &mut Vec<String>) {
+    let source = if let Some(import) = node.cast::<ast::ModuleImport>() {
+        Some(import.source())
+    } else {
+        node.cast::<ast::ModuleInclude>().map(|include| include.source())
+    };
+
+    if let Some(Expr::Str(path)) = source {
+        if let Some(path) = path.get().strip_prefix(IMPORT_SCHEME) {
+            imports.push(path.to_string());
+        }
+    }
+
+    for child in node.children() {
+        collect_imports(&child, imports);
+    }
+}
+
+/// The shared template id referenced by an import path like `shared/header.typ`
+pub fn shared_template_id(path: &str) -> Result<TemplateId> {
+    path.strip_prefix(SHARED_PREFIX)
+        .map(|name| name.strip_suffix(".typ").unwrap_or(name))
+        .filter(|name| !name.is_empty() && !name.contains('/'))
+        .map(TemplateId::from)
+        .ok_or_else(|| {
+            PapermakeError::Template(format!(
+                "Invalid import '{}{}', expected '{}{}<id>.typ'",
+                IMPORT_SCHEME, path, IMPORT_SCHEME, SHARED_PREFIX
+            ))
+        })
+}
+
+/// Ids of the shared templates a template imports directly, from its main
+/// content and all locale variants; invalid import paths are skipped
+pub fn imported_templates(template: &Template) -> BTreeSet<TemplateId> {
+    std::iter::once(&template.content)
+        .chain(template.variants.values())
+        .flat_map(|content| shared_imports(content))
+        .filter(|path| path != LOCALE_MODULE_PATH)
+        .filter_map(|path| shared_template_id(&path).ok())
+        .collect()
+}
+
+/// A template importing a shared template, directly or through other
+/// shared templates
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Dependent {
+    pub id: TemplateId,
+    pub name: String,
+    pub shared: bool,
+    /// Shared templates between the dependent and the imported template,
+    /// starting with the one the dependent imports; empty for direct imports
+    pub via: Vec<TemplateId>,
+}
+
+/// All templates among `templates` that depend on the template `id`,
+/// direct importers first, then by id
+pub fn dependents(templates: &[Template], id: &TemplateId) -> Vec<Dependent> {
+    let mut importers: BTreeMap<TemplateId, Vec<&Template>> = BTreeMap::new();
+    for template in templates {
+        for imported in imported_templates(template) {
+            importers.entry(imported).or_default().push(template);
+        }
+    }
+
+    // Breadth-first, so each dependent is reported with its shortest chain
+    let mut dependents = Vec::new();
+    let mut seen = BTreeSet::from([id.clone()]);
+    let mut queue = VecDeque::from([(id.clone(), Vec::new())]);
+    while let Some((imported, via)) = queue.pop_front() {
+        for template in importers.get(&imported).into_iter().flatten() {
+            if !seen.insert(template.id.clone()) {
+                continue;
+            }
+            dependents.push(Dependent {
+                id: template.id.clone(),
+                name: template.name.clone(),
+                shared: template.shared,
+                via: via.clone(),
+            });
+            if template.shared {
+                let mut chain = vec![template.id.clone()];
+                chain.extend(via.iter().cloned());
+                queue.push_back((template.id.clone(), chain));
+            }
+        }
+    }
+    dependents.sort_by(|a, b| a.via.len().cmp(&b.via.len()).then_with(|| a.id.cmp(&b.id)));
+    dependents
+}
+
+/// Load the sources of all shared templates a template imports, directly or
+/// through other shared templates
+///
+/// Fails if an import refers to a missing or non-shared template, or if the
+/// imports form a cycle.
+pub async fn resolve_shared(storage: &dyn Storage, template: &Template) -> Result<SharedSources> {
+    let mut resolved = SharedSources::new();
+    // Depth-first walk; the stack holds the import chain from the template
+    let mut stack: Vec<(String, Vec<String>)> = vec![(
+        template.id.0.clone(),
+        shared_imports(&template.content),
+    )];
+
+    while let Some((_, pending)) = stack.last_mut() {
+        let Some(path) = pending.pop() else {
+            stack.pop();
+            continue;
+        };
+        // Built-in modules are served by the compiler, not storage
+        if path == LOCALE_MODULE_PATH {
+            continue;
+        }
+
+        let id = shared_template_id(&path)?;
+        if stack.iter().any(|(name, _)| name == &id.0) {
+            let chain: Vec<&str> = stack.iter().map(|(name, _)| name.as_str()).collect();
+            return Err(PapermakeError::Template(format!(
+                "Cyclic shared import: {} -> {}",
+                chain.join(" -> "),
+                id.0
+            )));
+        }
+        if resolved.get(&path).is_some() {
+            continue;
+        }
+
+        let shared = storage.get_template(&id).await.map_err(|_| {
+            PapermakeError::Template(format!("Shared template '{}' not found", id.0))
+        })?;
+        if !shared.shared {
+            return Err(PapermakeError::Template(format!(
+                "Template '{}' is not a shared template",
+                id.0
+            )));
+        }
+
+        let imports = shared_imports(&shared.content);
+        resolved.insert(path, shared.content);
+        stack.push((id.0, imports));
+    }
+
+    Ok(resolved)
+}
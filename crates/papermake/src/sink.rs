@@ -0,0 +1,200 @@
+//! Output sinks receiving rendered documents
+//!
+//! Sinks let batch renders and background jobs write each finished PDF
+//! straight to its destination and hand out a location instead of keeping
+//! all output bytes in memory.
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+
+use crate::error::{PapermakeError, Result};
+use crate::storage::validate_file_path;
+
+/// Destination for rendered documents
+#[async_trait]
+pub trait RenderSink: Send + Sync {
+    /// Store a document under `key`, returning a URL locating it
+    async fn write(&self, key: &str, pdf: Vec<u8>) -> Result<String>;
+
+    /// Read back a document previously written under `key`
+    async fn read(&self, key: &str) -> Result<Vec<u8>>;
+}
+
+/// Sink keeping documents in memory, mainly for tests and small batches
+#[derive(Debug, Default)]
+pub struct MemorySink {
+    documents: Mutex<BTreeMap<String, Vec<u8>>>,
+}
+
+impl MemorySink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keys of all stored documents
+    pub fn keys(&self) -> Vec<String> {
+        self.documents
+            .lock()
+            .map(|documents| documents.keys().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, BTreeMap<String, Vec<u8>>>> {
+        self.documents
+            .lock()
+            .map_err(|_| PapermakeError::Storage("Failed to acquire sink lock".to_string()))
+    }
+}
+
+#[async_trait]
+impl RenderSink for MemorySink {
+    async fn write(&self, key: &str, pdf: Vec<u8>) -> Result<String> {
+        self.lock()?.insert(key.to_string(), pdf);
+        Ok(format!("memory://{}", key))
+    }
+
+    async fn read(&self, key: &str) -> Result<Vec<u8>> {
+        self.lock()?
+            .get(key)
+            .cloned()
+            .ok_or_else(|| PapermakeError::not_found("Output", key))
+    }
+}
+
+#[cfg(feature = "fs")]
+pub use file_sink::FileSink;
+
+#[cfg(feature = "fs")]
+mod file_sink {
+    use std::path::PathBuf;
+
+    use async_trait::async_trait;
+    use tokio::fs;
+
+    use super::{validate_file_path, RenderSink};
+    use crate::error::{PapermakeError, Result};
+
+    /// Sink writing documents below a local directory
+    #[derive(Debug, Clone)]
+    pub struct FileSink {
+        base_path: PathBuf,
+    }
+
+    impl FileSink {
+        pub fn new(base_path: impl Into<PathBuf>) -> Self {
+            Self {
+                base_path: base_path.into(),
+            }
+        }
+
+        fn path(&self, key: &str) -> Result<PathBuf> {
+            validate_file_path(key)?;
+            Ok(self.base_path.join(key))
+        }
+    }
+
+    #[async_trait]
+    impl RenderSink for FileSink {
+        async fn write(&self, key: &str, pdf: Vec<u8>) -> Result<String> {
+            let path = self.path(key)?;
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).await?;
+            }
+            fs::write(&path, pdf).await?;
+
+            let path = fs::canonicalize(&path).await.unwrap_or(path);
+            Ok(format!("file://{}", path.display()))
+        }
+
+        async fn read(&self, key: &str) -> Result<Vec<u8>> {
+            let path = self.path(key)?;
+            fs::read(&path).await.map_err(|e| match e.kind() {
+                std::io::ErrorKind::NotFound => PapermakeError::not_found("Output", key),
+                _ => PapermakeError::storage_io(&path, e),
+            })
+        }
+    }
+}
+
+#[cfg(feature = "s3")]
+pub use s3_sink::S3Sink;
+
+#[cfg(feature = "s3")]
+mod s3_sink {
+    use async_trait::async_trait;
+    use aws_sdk_s3::primitives::ByteStream;
+
+    use super::{validate_file_path, RenderSink};
+    use crate::error::{PapermakeError, Result};
+
+    /// Sink uploading documents to an S3 bucket
+    #[derive(Debug, Clone)]
+    pub struct S3Sink {
+        client: aws_sdk_s3::Client,
+        bucket: String,
+        prefix: String,
+    }
+
+    impl S3Sink {
+        /// Create a sink from an existing client
+        pub fn new(client: aws_sdk_s3::Client, bucket: impl Into<String>, prefix: impl Into<String>) -> Self {
+            Self {
+                client,
+                bucket: bucket.into(),
+                prefix: prefix.into().trim_matches('/').to_string(),
+            }
+        }
+
+        /// Create a sink using credentials and region from the environment
+        pub async fn from_env(bucket: impl Into<String>, prefix: impl Into<String>) -> Self {
+            let config = aws_config::load_defaults(aws_config::BehaviorVersion::latest()).await;
+            Self::new(aws_sdk_s3::Client::new(&config), bucket, prefix)
+        }
+
+        fn object_key(&self, key: &str) -> Result<String> {
+            validate_file_path(key)?;
+            if self.prefix.is_empty() {
+                Ok(key.to_string())
+            } else {
+                Ok(format!("{}/{}", self.prefix, key))
+            }
+        }
+    }
+
+    #[async_trait]
+    impl RenderSink for S3Sink {
+        async fn write(&self, key: &str, pdf: Vec<u8>) -> Result<String> {
+            let object_key = self.object_key(key)?;
+            self.client
+                .put_object()
+                .bucket(&self.bucket)
+                .key(&object_key)
+                .content_type("application/pdf")
+                .body(ByteStream::from(pdf))
+                .send()
+                .await
+                .map_err(|e| PapermakeError::Storage(format!("Failed to upload {}: {}", object_key, e)))?;
+            Ok(format!("s3://{}/{}", self.bucket, object_key))
+        }
+
+        async fn read(&self, key: &str) -> Result<Vec<u8>> {
+            let object_key = self.object_key(key)?;
+            let object = self
+                .client
+                .get_object()
+                .bucket(&self.bucket)
+                .key(&object_key)
+                .send()
+                .await
+                .map_err(|e| PapermakeError::Storage(format!("Failed to download {}: {}", object_key, e)))?;
+            let bytes = object
+                .body
+                .collect()
+                .await
+                .map_err(|e| PapermakeError::Storage(format!("Failed to download {}: {}", object_key, e)))?;
+            Ok(bytes.into_bytes().to_vec())
+        }
+    }
+}
@@ -0,0 +1,294 @@
+//! Per-template render statistics
+//!
+//! A [`RenderStats`] store folds every [`RenderRecord`] into running
+//! aggregates per template: render count, failure rate, average output size
+//! and duration percentiles. Percentiles are taken over the most recent
+//! [`DURATION_WINDOW`] renders, so they follow changes to a template instead
+//! of averaging over its whole life.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+use crate::error::{PapermakeError, Result};
+use crate::history::RenderRecord;
+use crate::storage::Namespace;
+use crate::template::TemplateId;
+
+/// Number of recent durations percentiles are computed from
+pub const DURATION_WINDOW: usize = 1000;
+
+/// Running totals of one template's renders, as kept by a [`RenderStats`]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StatsAggregate {
+    pub renders: u64,
+    pub failures: u64,
+    /// Sum of the sizes of successful renders' output
+    pub output_bytes: u64,
+    /// Renders with a known output size
+    pub outputs: u64,
+    /// Durations in milliseconds of the most recent renders, oldest first
+    pub durations_ms: VecDeque<u64>,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub last_render_at: Option<OffsetDateTime>,
+}
+
+impl StatsAggregate {
+    /// Add a render to the totals
+    pub fn add(&mut self, record: &RenderRecord) {
+        self.renders += 1;
+        if !record.success {
+            self.failures += 1;
+        }
+        if let Some(bytes) = record.output_bytes {
+            self.output_bytes += bytes;
+            self.outputs += 1;
+        }
+        self.durations_ms.push_back(record.duration_ms);
+        while self.durations_ms.len() > DURATION_WINDOW {
+            self.durations_ms.pop_front();
+        }
+        self.last_render_at = Some(record.created_at);
+    }
+
+    /// The summary reported for `template_id`
+    pub fn summarize(&self, template_id: &TemplateId) -> TemplateStats {
+        let mut durations: Vec<u64> = self.durations_ms.iter().copied().collect();
+        durations.sort_unstable();
+        TemplateStats {
+            template_id: template_id.clone(),
+            renders: self.renders,
+            failures: self.failures,
+            failure_rate: if self.renders == 0 { 0.0 } else { self.failures as f64 / self.renders as f64 },
+            p50_ms: percentile(&durations, 50),
+            p95_ms: percentile(&durations, 95),
+            avg_output_bytes: self.output_bytes.checked_div(self.outputs).unwrap_or(0),
+            last_render_at: self.last_render_at,
+        }
+    }
+}
+
+/// Nearest-rank percentile of sorted values, 0 when there are none
+fn percentile(sorted: &[u64], percent: usize) -> u64 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let rank = (sorted.len() * percent).div_ceil(100).max(1);
+    sorted[rank - 1]
+}
+
+/// Render statistics of a template
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TemplateStats {
+    pub template_id: TemplateId,
+    pub renders: u64,
+    pub failures: u64,
+    /// Share of renders that failed, from 0 to 1
+    pub failure_rate: f64,
+    /// Median duration of recent renders
+    pub p50_ms: u64,
+    pub p95_ms: u64,
+    /// Average size of the output of successful renders
+    pub avg_output_bytes: u64,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub last_render_at: Option<OffsetDateTime>,
+}
+
+/// Slowest first by p95 duration, then by median
+fn slowest_first(mut stats: Vec<TemplateStats>, limit: usize) -> Vec<TemplateStats> {
+    stats.sort_by(|a, b| {
+        b.p95_ms
+            .cmp(&a.p95_ms)
+            .then_with(|| b.p50_ms.cmp(&a.p50_ms))
+            .then_with(|| a.template_id.0.cmp(&b.template_id.0))
+    });
+    stats.truncate(limit);
+    stats
+}
+
+/// Store of per-template render aggregates
+#[async_trait]
+pub trait RenderStats: Send + Sync {
+    /// Add a finished render to its template's aggregates
+    async fn record(&self, record: &RenderRecord) -> Result<()>;
+
+    /// Statistics of a template; templates never rendered have all zeros
+    async fn get_stats(&self, template_id: &TemplateId) -> Result<TemplateStats>;
+
+    /// The `limit` templates with the slowest renders
+    async fn slowest(&self, limit: usize) -> Result<Vec<TemplateStats>>;
+
+    /// Statistics of a tenant namespace, isolated from these
+    fn for_namespace(&self, namespace: &Namespace) -> Arc<dyn RenderStats>;
+}
+
+type MemoryAggregates = BTreeMap<(String, String), StatsAggregate>;
+
+/// Statistics kept in memory, lost on restart
+#[derive(Debug, Clone, Default)]
+pub struct MemoryRenderStats {
+    namespace: String,
+    aggregates: Arc<Mutex<MemoryAggregates>>,
+}
+
+impl MemoryRenderStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, MemoryAggregates>> {
+        self.aggregates
+            .lock()
+            .map_err(|_| PapermakeError::Storage("Failed to acquire stats lock".to_string()))
+    }
+}
+
+#[async_trait]
+impl RenderStats for MemoryRenderStats {
+    async fn record(&self, record: &RenderRecord) -> Result<()> {
+        self.lock()?
+            .entry((self.namespace.clone(), record.template_id.0.clone()))
+            .or_default()
+            .add(record);
+        Ok(())
+    }
+
+    async fn get_stats(&self, template_id: &TemplateId) -> Result<TemplateStats> {
+        let aggregates = self.lock()?;
+        let aggregate = aggregates.get(&(self.namespace.clone(), template_id.0.clone()));
+        Ok(aggregate.cloned().unwrap_or_default().summarize(template_id))
+    }
+
+    async fn slowest(&self, limit: usize) -> Result<Vec<TemplateStats>> {
+        let stats = self.lock()?
+            .iter()
+            .filter(|((namespace, _), _)| *namespace == self.namespace)
+            .map(|((_, id), aggregate)| aggregate.summarize(&TemplateId(id.clone())))
+            .collect();
+        Ok(slowest_first(stats, limit))
+    }
+
+    fn for_namespace(&self, namespace: &Namespace) -> Arc<dyn RenderStats> {
+        Arc::new(Self {
+            namespace: namespace.to_string(),
+            aggregates: self.aggregates.clone(),
+        })
+    }
+}
+
+#[cfg(feature = "fs")]
+pub use file_stats::FileRenderStats;
+
+#[cfg(feature = "fs")]
+mod file_stats {
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    use async_trait::async_trait;
+    use tokio::fs;
+
+    use super::{slowest_first, RenderStats, StatsAggregate, TemplateStats};
+    use crate::error::{PapermakeError, Result};
+    use crate::history::RenderRecord;
+    use crate::storage::{validate_file_path, Namespace};
+    use crate::template::TemplateId;
+
+    /// Statistics stored as one JSON file per template
+    ///
+    /// Directory structure, mirroring `FileRenderHistory`:
+    /// ```text
+    /// base_path/
+    /// ├── stats/
+    /// │   └── template_id.json
+    /// └── tenants/
+    ///     └── namespace/
+    ///         └── stats/
+    ///             └── ...
+    /// ```
+    ///
+    /// Updates are serialized within the process; replicas sharing the
+    /// directory can lose each other's updates.
+    #[derive(Debug, Clone)]
+    pub struct FileRenderStats {
+        base_path: PathBuf,
+        /// Serializes read-modify-write updates, shared with namespaced stats
+        lock: Arc<tokio::sync::Mutex<()>>,
+    }
+
+    impl FileRenderStats {
+        pub fn new(base_path: impl Into<PathBuf>) -> Self {
+            Self {
+                base_path: base_path.into(),
+                lock: Arc::new(tokio::sync::Mutex::new(())),
+            }
+        }
+
+        fn stats_file(&self, template_id: &TemplateId) -> Result<PathBuf> {
+            validate_file_path(&template_id.0)?;
+            Ok(self.base_path.join("stats").join(format!("{}.json", template_id.0)))
+        }
+
+        async fn load(&self, template_id: &TemplateId) -> Result<StatsAggregate> {
+            match fs::read(self.stats_file(template_id)?).await {
+                Ok(json) => serde_json::from_slice(&json).map_err(|e| {
+                    PapermakeError::Storage(format!("Failed to parse stats of {}: {}", template_id.0, e))
+                }),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(StatsAggregate::default()),
+                Err(err) => Err(err.into()),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl RenderStats for FileRenderStats {
+        async fn record(&self, record: &RenderRecord) -> Result<()> {
+            let path = self.stats_file(&record.template_id)?;
+            let _guard = self.lock.lock().await;
+            let mut aggregate = self.load(&record.template_id).await?;
+            aggregate.add(record);
+
+            let json = serde_json::to_vec(&aggregate)
+                .map_err(|e| PapermakeError::Storage(format!("Failed to serialize stats: {}", e)))?;
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).await?;
+            }
+            fs::write(path, json).await?;
+            Ok(())
+        }
+
+        async fn get_stats(&self, template_id: &TemplateId) -> Result<TemplateStats> {
+            Ok(self.load(template_id).await?.summarize(template_id))
+        }
+
+        async fn slowest(&self, limit: usize) -> Result<Vec<TemplateStats>> {
+            let stats_dir = self.base_path.join("stats");
+            if !stats_dir.exists() {
+                return Ok(Vec::new());
+            }
+
+            let mut stats = Vec::new();
+            let mut entries = fs::read_dir(&stats_dir).await?;
+            while let Some(entry) = entries.next_entry().await? {
+                let name = entry.file_name().to_string_lossy().to_string();
+                let Some(id) = name.strip_suffix(".json") else {
+                    continue;
+                };
+                let template_id = TemplateId(id.to_string());
+                if let Ok(aggregate) = self.load(&template_id).await {
+                    stats.push(aggregate.summarize(&template_id));
+                }
+            }
+            Ok(slowest_first(stats, limit))
+        }
+
+        fn for_namespace(&self, namespace: &Namespace) -> Arc<dyn RenderStats> {
+            Arc::new(Self {
+                base_path: self.base_path.join("tenants").join(namespace.as_str()),
+                lock: self.lock.clone(),
+            })
+        }
+    }
+}
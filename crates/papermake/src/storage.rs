@@ -0,0 +1,898 @@
+//! Storage abstraction for templates and their files
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+use crate::error::{PapermakeError, Result};
+use crate::shared::{dependents, Dependent};
+use crate::template::{Template, TemplateId};
+
+/// A tenant namespace isolating one team's templates from another's
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Namespace(String);
+
+impl Namespace {
+    /// Create a namespace; names may contain ASCII letters, digits, `-` and `_`
+    pub fn new(name: impl Into<String>) -> Result<Self> {
+        let name = name.into();
+        let valid = !name.is_empty()
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if valid {
+            Ok(Self(name))
+        } else {
+            Err(PapermakeError::InvalidInput(format!("Invalid namespace: {}", name)))
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for Namespace {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for Namespace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Order of listed templates
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TemplateSort {
+    /// By id, ascending
+    #[default]
+    Id,
+    /// Least recently updated first
+    UpdatedAsc,
+    /// Most recently updated first
+    UpdatedDesc,
+}
+
+impl std::str::FromStr for TemplateSort {
+    type Err = PapermakeError;
+
+    /// Parse `id`, `updated_at` or `-updated_at` (descending)
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "id" => Ok(TemplateSort::Id),
+            "updated_at" => Ok(TemplateSort::UpdatedAsc),
+            "-updated_at" => Ok(TemplateSort::UpdatedDesc),
+            other => Err(PapermakeError::InvalidInput(format!(
+                "Unknown sort '{}', expected 'id', 'updated_at' or '-updated_at'",
+                other
+            ))),
+        }
+    }
+}
+
+/// Filtering, sorting and pagination for [`Storage::list_templates`]
+#[derive(Debug, Clone, Default)]
+pub struct ListOptions {
+    /// Maximum number of templates per page; all remaining when `None`
+    pub limit: Option<usize>,
+    /// Continue after the page that returned this cursor
+    pub cursor: Option<String>,
+    /// Only templates whose name contains this (case-insensitive)
+    pub name_contains: Option<String>,
+    /// Only shared (`Some(true)`) or only regular (`Some(false)`) templates
+    pub shared: Option<bool>,
+    /// Only templates carrying all of these tags
+    pub tags: Vec<String>,
+    /// Only templates with all of these metadata entries
+    pub metadata: BTreeMap<String, String>,
+    pub sort: TemplateSort,
+}
+
+impl ListOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn cursor(mut self, cursor: impl Into<String>) -> Self {
+        self.cursor = Some(cursor.into());
+        self
+    }
+
+    pub fn name_contains(mut self, name: impl Into<String>) -> Self {
+        self.name_contains = Some(name.into());
+        self
+    }
+
+    pub fn shared(mut self, shared: bool) -> Self {
+        self.shared = Some(shared);
+        self
+    }
+
+    pub fn tag(mut self, tag: impl Into<String>) -> Self {
+        self.tags.push(tag.into());
+        self
+    }
+
+    pub fn metadata(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.metadata.insert(key.into(), value.into());
+        self
+    }
+
+    pub fn sort(mut self, sort: TemplateSort) -> Self {
+        self.sort = sort;
+        self
+    }
+
+    /// Whether a template passes the filters
+    pub fn matches(&self, template: &Template) -> bool {
+        if let Some(shared) = self.shared {
+            if template.shared != shared {
+                return false;
+            }
+        }
+        if let Some(name) = &self.name_contains {
+            if !template.name.to_lowercase().contains(&name.to_lowercase()) {
+                return false;
+            }
+        }
+        if !self.tags.iter().all(|tag| template.has_tag(tag)) {
+            return false;
+        }
+        self.metadata
+            .iter()
+            .all(|(key, value)| template.metadata.get(key) == Some(value))
+    }
+
+    /// Filter, sort and paginate a full listing
+    ///
+    /// Backends without native querying can load every template and hand
+    /// them to this. Cursors are positions in the sort order rather than
+    /// offsets, so pages stay consistent when templates are added.
+    pub fn apply(&self, templates: Vec<Template>) -> Result<TemplatePage> {
+        let mut templates: Vec<Template> = templates.into_iter().filter(|t| self.matches(t)).collect();
+        templates.sort_by(|a, b| self.sort_key(a).cmp(&self.sort_key(b)));
+
+        if let Some(cursor) = &self.cursor {
+            let after = decode_cursor(cursor)?;
+            templates.retain(|t| self.sort_key(t) > after);
+        }
+
+        let next_cursor = match self.limit {
+            Some(limit) if templates.len() > limit => {
+                templates.truncate(limit);
+                templates.last().map(|t| encode_cursor(&self.sort_key(t)))
+            }
+            _ => None,
+        };
+
+        Ok(TemplatePage { templates, next_cursor })
+    }
+
+    /// Key ordering templates under this sort; ids break ties
+    fn sort_key(&self, template: &Template) -> (i128, String) {
+        let updated = template.updated_at.unix_timestamp_nanos();
+        let rank = match self.sort {
+            TemplateSort::Id => 0,
+            TemplateSort::UpdatedAsc => updated,
+            TemplateSort::UpdatedDesc => -updated,
+        };
+        (rank, template.id.0.clone())
+    }
+}
+
+fn encode_cursor((rank, id): &(i128, String)) -> String {
+    hex::encode(format!("{}:{}", rank, id))
+}
+
+fn decode_cursor(cursor: &str) -> Result<(i128, String)> {
+    let invalid = || PapermakeError::InvalidInput(format!("Invalid cursor: {}", cursor));
+    let decoded = hex::decode(cursor).map_err(|_| invalid())?;
+    let decoded = String::from_utf8(decoded).map_err(|_| invalid())?;
+    let (rank, id) = decoded.split_once(':').ok_or_else(invalid)?;
+    Ok((rank.parse().map_err(|_| invalid())?, id.to_string()))
+}
+
+/// One page of a template listing
+#[derive(Debug, Clone)]
+pub struct TemplatePage {
+    pub templates: Vec<Template>,
+    /// Cursor for the next page; `None` on the last page
+    pub next_cursor: Option<String>,
+}
+
+/// A template found by [`Storage::search_templates`]
+#[derive(Debug, Clone)]
+pub struct SearchHit {
+    pub template: Template,
+    /// Relevance; higher is better
+    pub score: u32,
+}
+
+/// Relevance of a template for a search query, or `None` if it doesn't match
+///
+/// Every whitespace-separated term of the query must occur (case-insensitive)
+/// in the name, description, tags or Typst content. Matches in the name
+/// weigh most, matches in the content least.
+pub fn search_score(template: &Template, query: &str) -> Option<u32> {
+    let name = template.name.to_lowercase();
+    let description = template.description.as_deref().unwrap_or_default().to_lowercase();
+    let content = template.content.to_lowercase();
+
+    let mut score = 0;
+    let mut terms = query.split_whitespace().map(str::to_lowercase).peekable();
+    terms.peek()?;
+    for term in terms {
+        let term_score = if name.contains(&term) { 10 } else { 0 }
+            + if description.contains(&term) { 5 } else { 0 }
+            + if template.tags.iter().any(|tag| tag.to_lowercase() == term) { 5 } else { 0 }
+            + content.matches(&term).count().min(5) as u32;
+        if term_score == 0 {
+            return None;
+        }
+        score += term_score;
+    }
+    Some(score)
+}
+
+/// Kinds of stored artifacts that expire, see [`Storage::purge`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArtifactKind {
+    /// Render records and their archived inputs
+    RenderHistory,
+    /// Documents written by asynchronous jobs
+    JobOutputs,
+    /// Published revisions of archived templates
+    TemplateVersions,
+}
+
+/// Number of artifacts deleted by [`Storage::purge`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct PurgeReport {
+    pub render_records: usize,
+    pub job_outputs: usize,
+    pub template_versions: usize,
+}
+
+impl PurgeReport {
+    pub fn total(&self) -> usize {
+        self.render_records + self.job_outputs + self.template_versions
+    }
+
+    fn add(&mut self, other: PurgeReport) {
+        self.render_records += other.render_records;
+        self.job_outputs += other.job_outputs;
+        self.template_versions += other.template_versions;
+    }
+}
+
+/// Storage backend for templates and their associated files (images, fonts, includes)
+#[async_trait]
+pub trait Storage: Send + Sync {
+    /// Save a template, replacing any existing template with the same id
+    ///
+    /// If a template with the id is stored, `template.revision` must equal
+    /// its revision, otherwise the save fails with
+    /// [`PapermakeError::Conflict`]. The stored revision becomes
+    /// `template.revision + 1`.
+    async fn save_template(&self, template: &Template) -> Result<()>;
+
+    /// Get a template by id
+    async fn get_template(&self, id: &TemplateId) -> Result<Template>;
+
+    /// List templates matching `options`, one page at a time
+    async fn list_templates(&self, options: &ListOptions) -> Result<TemplatePage>;
+
+    /// Find templates by name, description, tags and content, best match first
+    ///
+    /// The default implementation scans every template with [`search_score`];
+    /// backends with a search index should override it.
+    async fn search_templates(&self, query: &str) -> Result<Vec<SearchHit>> {
+        let templates = self.list_templates(&ListOptions::new()).await?.templates;
+        let mut hits: Vec<SearchHit> = templates
+            .into_iter()
+            .filter_map(|template| {
+                let score = search_score(&template, query)?;
+                Some(SearchHit { template, score })
+            })
+            .collect();
+        hits.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| a.template.id.0.cmp(&b.template.id.0)));
+        Ok(hits)
+    }
+
+    /// Save the published revision of a template, served to renders by default
+    async fn save_published_template(&self, template: &Template) -> Result<()>;
+
+    /// Get the published revision of a template
+    async fn get_published_template(&self, id: &TemplateId) -> Result<Template>;
+
+    /// Templates importing the shared template `id`, directly or through
+    /// other shared templates
+    ///
+    /// The default implementation scans every template with
+    /// [`crate::shared::dependents`].
+    async fn get_dependents(&self, id: &TemplateId) -> Result<Vec<Dependent>> {
+        let templates = self.list_templates(&ListOptions::new()).await?.templates;
+        Ok(dependents(&templates, id))
+    }
+
+    /// List shared templates, which other templates can import
+    async fn list_shared_templates(&self) -> Result<Vec<Template>> {
+        let page = self.list_templates(&ListOptions::new().shared(true)).await?;
+        Ok(page.templates)
+    }
+
+    /// Delete a template and all of its files
+    async fn delete_template(&self, id: &TemplateId) -> Result<()>;
+
+    /// Copy a template and all of its files to `new_id`, returning the
+    /// stored copy (see [`Template::fork`])
+    ///
+    /// Fails with [`PapermakeError::Conflict`] if `new_id` is taken. The
+    /// default implementation copies file by file, so an interrupted copy
+    /// can leave a partial template behind; backends should override it
+    /// with an atomic copy.
+    async fn copy_template(&self, id: &TemplateId, new_id: &TemplateId) -> Result<Template> {
+        let template = self.get_template(id).await?;
+        if self.get_template(new_id).await.is_ok() {
+            return Err(PapermakeError::Conflict(format!("Template '{}' already exists", new_id.as_ref())));
+        }
+        let mut fork = template.fork(new_id.clone());
+        self.save_template(&fork).await?;
+        fork.revision += 1;
+        for path in self.list_template_files(id).await? {
+            let content = self.get_template_file(id, &path).await?;
+            self.save_template_file(new_id, &path, &content).await?;
+        }
+        Ok(fork)
+    }
+
+    /// Save a file belonging to a template
+    async fn save_template_file(&self, id: &TemplateId, path: &str, content: &[u8]) -> Result<()>;
+
+    /// Get a file belonging to a template
+    async fn get_template_file(&self, id: &TemplateId, path: &str) -> Result<Vec<u8>>;
+
+    /// List the paths of all files belonging to a template
+    async fn list_template_files(&self, id: &TemplateId) -> Result<Vec<String>>;
+
+    /// Delete a file belonging to a template
+    async fn delete_template_file(&self, id: &TemplateId, path: &str) -> Result<()>;
+
+    /// Rename (move) a file belonging to a template
+    async fn rename_template_file(&self, id: &TemplateId, from: &str, to: &str) -> Result<()>;
+
+    /// Delete artifacts of the given kinds last written before `before`
+    ///
+    /// Long-running deployments call this periodically so history, job
+    /// output and superseded revisions don't grow without bound. Templates
+    /// and published revisions of live templates are never purged. The
+    /// default implementation deletes nothing; backends keeping such
+    /// artifacts should override it.
+    async fn purge(&self, before: OffsetDateTime, kinds: &[ArtifactKind]) -> Result<PurgeReport> {
+        let _ = (before, kinds);
+        Ok(PurgeReport::default())
+    }
+
+    /// Check that the backend is reachable, for readiness probes
+    ///
+    /// The default implementation lists a single template; backends should
+    /// override it with a check that also covers writes.
+    async fn check_health(&self) -> Result<()> {
+        self.list_templates(&ListOptions::new().limit(1)).await.map(|_| ())
+    }
+
+    /// Storage holding the templates of a tenant namespace, isolated from
+    /// this storage and from every other namespace
+    fn for_namespace(&self, namespace: &Namespace) -> Arc<dyn Storage>;
+}
+
+/// Check that a template id names a single directory; ids may contain ASCII
+/// letters, digits, `-` and `_`, like namespaces
+pub fn validate_template_id(id: &TemplateId) -> Result<()> {
+    let valid = !id.0.is_empty()
+        && id.0.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(PapermakeError::InvalidInput(format!("Invalid template id: {}", id.0)))
+    }
+}
+
+/// Check that a template file path stays inside the template directory
+pub fn validate_file_path(path: &str) -> Result<()> {
+    use std::path::Component;
+
+    let valid = !path.is_empty()
+        && std::path::Path::new(path)
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)));
+
+    if valid {
+        Ok(())
+    } else {
+        Err(PapermakeError::InvalidInput(format!("Invalid file path: {}", path)))
+    }
+}
+
+#[cfg(feature = "fs")]
+pub use file_storage::FileStorage;
+
+#[cfg(feature = "fs")]
+mod file_storage {
+    use std::collections::HashMap;
+    use std::path::{Path, PathBuf};
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::SystemTime;
+
+    use async_trait::async_trait;
+    use time::OffsetDateTime;
+    use tokio::fs;
+    use tokio::sync::OwnedMutexGuard;
+
+    use super::{validate_file_path, validate_template_id, ArtifactKind, ListOptions, Namespace, PurgeReport, Storage, TemplatePage};
+    use crate::error::{PapermakeError, Result};
+    use crate::template::{Template, TemplateId, TemplateStatus};
+
+    /// Suffix of temporary files written before being renamed into place
+    const TMP_SUFFIX: &str = ".tmp";
+
+    /// File-based storage
+    ///
+    /// Directory structure:
+    /// ```text
+    /// base_path/
+    /// ├── templates/
+    /// │   └── template_id/
+    /// │       ├── template.json
+    /// │       ├── published.json
+    /// │       └── files/
+    /// │           ├── logo.png
+    /// │           └── ...
+    /// ├── renders/     (`FileRenderHistory`, purged with the storage)
+    /// ├── outputs/     (`FileSink` of job documents, purged with the storage)
+    /// └── tenants/
+    ///     └── namespace/
+    ///         └── templates/
+    ///             └── ...
+    /// ```
+    ///
+    /// Files are written to a temporary file and renamed into place, so
+    /// readers never see partial writes. Writes to a template are serialized
+    /// by a per-template lock, which only coordinates clones of the same
+    /// storage within one process; optimistic revision checks catch the rest.
+    #[derive(Debug, Clone)]
+    pub struct FileStorage {
+        base_path: PathBuf,
+        /// Write locks by template directory, shared with namespaced storages
+        locks: Arc<Mutex<HashMap<PathBuf, Arc<tokio::sync::Mutex<()>>>>>,
+    }
+
+    impl FileStorage {
+        /// Create a new file storage rooted at `base_path`
+        pub fn new(base_path: impl Into<PathBuf>) -> Self {
+            Self {
+                base_path: base_path.into(),
+                locks: Arc::new(Mutex::new(HashMap::new())),
+            }
+        }
+
+        /// Hold the write lock of a template
+        async fn lock(&self, id: &TemplateId) -> Result<OwnedMutexGuard<()>> {
+            let lock = self
+                .locks
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .entry(self.template_dir(id)?)
+                .or_default()
+                .clone();
+            Ok(lock.lock_owned().await)
+        }
+
+        /// Get path to a template's base directory, rejecting ids that
+        /// would escape the templates directory
+        fn template_dir(&self, id: &TemplateId) -> Result<PathBuf> {
+            validate_template_id(id)?;
+            Ok(self.base_path.join("templates").join(&id.0))
+        }
+
+        /// Get path to a template's metadata file
+        fn template_file(&self, id: &TemplateId) -> Result<PathBuf> {
+            Ok(self.template_dir(id)?.join("template.json"))
+        }
+
+        /// Get path to a template's published revision
+        fn published_file(&self, id: &TemplateId) -> Result<PathBuf> {
+            Ok(self.template_dir(id)?.join("published.json"))
+        }
+
+        /// Get path to a template's files directory
+        fn files_dir(&self, id: &TemplateId) -> Result<PathBuf> {
+            Ok(self.template_dir(id)?.join("files"))
+        }
+
+        /// Get path to a single template file, rejecting paths that escape the files directory
+        fn file_path(&self, id: &TemplateId, path: &str) -> Result<PathBuf> {
+            validate_file_path(path)?;
+            Ok(self.files_dir(id)?.join(path))
+        }
+
+        fn not_found(id: &TemplateId) -> PapermakeError {
+            PapermakeError::template_not_found(id)
+        }
+
+        /// Write a file by writing a temporary sibling and renaming it into place
+        pub(crate) async fn write_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+            static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+            let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+            let tmp = path.with_file_name(format!(
+                ".{}.{}.{}{}",
+                file_name,
+                std::process::id(),
+                COUNTER.fetch_add(1, Ordering::Relaxed),
+                TMP_SUFFIX
+            ));
+
+            let result = match fs::write(&tmp, contents).await {
+                Ok(()) => fs::rename(&tmp, path).await,
+                Err(err) => Err(err),
+            };
+            if result.is_err() {
+                let _ = fs::remove_file(&tmp).await;
+            }
+            Ok(result?)
+        }
+
+        /// Recursively copy a directory, skipping leftovers of interrupted writes
+        async fn copy_dir(from: &Path, to: &Path) -> Result<()> {
+            fs::create_dir_all(to).await?;
+            let mut entries = fs::read_dir(from).await?;
+            while let Some(entry) = entries.next_entry().await? {
+                let target = to.join(entry.file_name());
+                if entry.file_type().await?.is_dir() {
+                    Box::pin(Self::copy_dir(&entry.path(), &target)).await?;
+                } else if !entry.file_name().to_string_lossy().ends_with(TMP_SUFFIX) {
+                    fs::copy(entry.path(), target).await?;
+                }
+            }
+            Ok(())
+        }
+
+        /// Recursively list files in a directory relative to `base`
+        async fn list_files_recursive(dir: &Path, base: &Path, files: &mut Vec<String>) -> Result<()> {
+            let mut entries = fs::read_dir(dir).await?;
+
+            while let Some(entry) = entries.next_entry().await? {
+                let path = entry.path();
+
+                if entry.file_type().await?.is_dir() {
+                    Box::pin(Self::list_files_recursive(&path, base, files)).await?;
+                } else if entry.file_name().to_string_lossy().ends_with(TMP_SUFFIX) {
+                    // Leftover of an interrupted write
+                    continue;
+                } else if let Ok(rel_path) = path.strip_prefix(base) {
+                    if let Some(path_str) = rel_path.to_str() {
+                        files.push(path_str.replace('\\', "/"));
+                    }
+                }
+            }
+
+            Ok(())
+        }
+
+        /// Purge this storage's directory, leaving namespaces below it alone
+        async fn purge_own(&self, before: SystemTime, kinds: &[ArtifactKind]) -> Result<PurgeReport> {
+            let mut report = PurgeReport::default();
+            if kinds.contains(&ArtifactKind::RenderHistory) {
+                let renders = self.base_path.join("renders");
+                for dir in Self::subdirs(&renders).await? {
+                    let record = dir.join("record.json");
+                    let written = if record.exists() { record } else { dir.clone() };
+                    if Self::written_before(&written, before).await {
+                        fs::remove_dir_all(&dir).await?;
+                        report.render_records += 1;
+                    }
+                }
+            }
+            if kinds.contains(&ArtifactKind::JobOutputs) {
+                let outputs = self.base_path.join("outputs");
+                if outputs.exists() {
+                    report.job_outputs = Self::purge_files(&outputs, before).await?;
+                }
+            }
+            if kinds.contains(&ArtifactKind::TemplateVersions) {
+                for dir in Self::subdirs(&self.base_path.join("templates")).await? {
+                    let Some(id) = dir.file_name().map(|name| TemplateId(name.to_string_lossy().to_string())) else {
+                        continue;
+                    };
+                    let Ok(published) = self.published_file(&id) else {
+                        continue;
+                    };
+                    let _guard = self.lock(&id).await?;
+                    let archived = self.get_template(&id).await.is_ok_and(|t| t.status == TemplateStatus::Archived);
+                    if archived && published.exists() && Self::written_before(&published, before).await {
+                        fs::remove_file(&published).await?;
+                        report.template_versions += 1;
+                    }
+                }
+            }
+            Ok(report)
+        }
+
+        /// Directories inside `dir`, skipping leftovers of interrupted writes
+        async fn subdirs(dir: &Path) -> Result<Vec<PathBuf>> {
+            let mut dirs = Vec::new();
+            if !dir.exists() {
+                return Ok(dirs);
+            }
+            let mut entries = fs::read_dir(dir).await?;
+            while let Some(entry) = entries.next_entry().await? {
+                if entry.file_type().await?.is_dir() && !entry.file_name().to_string_lossy().ends_with(TMP_SUFFIX) {
+                    dirs.push(entry.path());
+                }
+            }
+            Ok(dirs)
+        }
+
+        /// Recursively delete files written before `before`, and the
+        /// directories left empty; returns the number of deleted files
+        async fn purge_files(dir: &Path, before: SystemTime) -> Result<usize> {
+            let mut deleted = 0;
+            let mut entries = fs::read_dir(dir).await?;
+            while let Some(entry) = entries.next_entry().await? {
+                let path = entry.path();
+                if entry.file_type().await?.is_dir() {
+                    deleted += Box::pin(Self::purge_files(&path, before)).await?;
+                    // Fails while the directory still has files
+                    let _ = fs::remove_dir(&path).await;
+                } else if Self::written_before(&path, before).await {
+                    fs::remove_file(&path).await?;
+                    deleted += 1;
+                }
+            }
+            Ok(deleted)
+        }
+
+        async fn written_before(path: &Path, before: SystemTime) -> bool {
+            let modified = fs::metadata(path).await.and_then(|metadata| metadata.modified());
+            modified.is_ok_and(|modified| modified < before)
+        }
+    }
+
+    #[async_trait]
+    impl Storage for FileStorage {
+        async fn save_template(&self, template: &Template) -> Result<()> {
+            let _guard = self.lock(&template.id).await?;
+            fs::create_dir_all(self.template_dir(&template.id)?).await?;
+
+            if let Ok(stored) = self.get_template(&template.id).await {
+                if stored.revision != template.revision {
+                    return Err(PapermakeError::Conflict(format!(
+                        "Template '{}' was modified concurrently (revision {}, expected {})",
+                        template.id.as_ref(),
+                        stored.revision,
+                        template.revision
+                    )));
+                }
+            }
+
+            let mut next = template.clone();
+            next.revision += 1;
+            let json = serde_json::to_string_pretty(&next)
+                .map_err(|e| PapermakeError::Storage(e.to_string()))?;
+            Self::write_atomic(&self.template_file(&template.id)?, json.as_bytes()).await
+        }
+
+        async fn get_template(&self, id: &TemplateId) -> Result<Template> {
+            let path = self.template_file(id)?;
+            if !path.exists() {
+                return Err(Self::not_found(id));
+            }
+
+            let content = fs::read_to_string(&path).await.map_err(|e| PapermakeError::storage_io(&path, e))?;
+            serde_json::from_str(&content).map_err(|e| PapermakeError::Storage(e.to_string()))
+        }
+
+        async fn save_published_template(&self, template: &Template) -> Result<()> {
+            let _guard = self.lock(&template.id).await?;
+            fs::create_dir_all(self.template_dir(&template.id)?).await?;
+
+            let json = serde_json::to_string_pretty(template)
+                .map_err(|e| PapermakeError::Storage(e.to_string()))?;
+            Self::write_atomic(&self.published_file(&template.id)?, json.as_bytes()).await
+        }
+
+        async fn get_published_template(&self, id: &TemplateId) -> Result<Template> {
+            let path = self.published_file(id)?;
+            if !path.exists() {
+                return Err(PapermakeError::not_found("Published version of template", id.as_ref()));
+            }
+
+            let content = fs::read_to_string(&path).await.map_err(|e| PapermakeError::storage_io(&path, e))?;
+            serde_json::from_str(&content).map_err(|e| PapermakeError::Storage(e.to_string()))
+        }
+
+        async fn list_templates(&self, options: &ListOptions) -> Result<TemplatePage> {
+            let templates_dir = self.base_path.join("templates");
+            if !templates_dir.exists() {
+                return options.apply(Vec::new());
+            }
+
+            let mut templates = Vec::new();
+            let mut entries = fs::read_dir(&templates_dir).await?;
+
+            while let Some(entry) = entries.next_entry().await? {
+                let name = entry.file_name().to_string_lossy().to_string();
+                // Copies in progress are staged next to the templates
+                if entry.file_type().await?.is_dir() && !name.ends_with(TMP_SUFFIX) {
+                    let id = TemplateId(name);
+                    if let Ok(template) = self.get_template(&id).await {
+                        templates.push(template);
+                    }
+                }
+            }
+
+            options.apply(templates)
+        }
+
+        async fn delete_template(&self, id: &TemplateId) -> Result<()> {
+            let _guard = self.lock(id).await?;
+            let dir = self.template_dir(id)?;
+            if !dir.exists() {
+                return Err(Self::not_found(id));
+            }
+            fs::remove_dir_all(dir).await?;
+            Ok(())
+        }
+
+        /// Copies into a staging directory that is renamed into place, so
+        /// the copy appears completely or not at all
+        async fn copy_template(&self, id: &TemplateId, new_id: &TemplateId) -> Result<Template> {
+            static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+            // Lock in a fixed order so opposite copies can't deadlock
+            let (first, second) = if id.0 <= new_id.0 { (id, new_id) } else { (new_id, id) };
+            let _first = self.lock(first).await?;
+            let _second = if first != second { Some(self.lock(second).await?) } else { None };
+
+            let template = self.get_template(id).await?;
+            let target = self.template_dir(new_id)?;
+            if target.exists() {
+                return Err(PapermakeError::Conflict(format!("Template '{}' already exists", new_id.as_ref())));
+            }
+
+            let mut fork = template.fork(new_id.clone());
+            fork.revision = 1;
+            let staging = target.with_file_name(format!(
+                ".{}.{}.{}{}",
+                new_id.as_ref(),
+                std::process::id(),
+                COUNTER.fetch_add(1, Ordering::Relaxed),
+                TMP_SUFFIX
+            ));
+            let result: Result<()> = async {
+                fs::create_dir_all(&staging).await?;
+                let json = serde_json::to_string_pretty(&fork)
+                    .map_err(|e| PapermakeError::Storage(e.to_string()))?;
+                fs::write(staging.join("template.json"), json).await?;
+                let files = self.files_dir(id)?;
+                if files.exists() {
+                    Self::copy_dir(&files, &staging.join("files")).await?;
+                }
+                fs::rename(&staging, &target).await?;
+                Ok(())
+            }
+            .await;
+            if result.is_err() {
+                let _ = fs::remove_dir_all(&staging).await;
+            }
+            result.map(|()| fork)
+        }
+
+        async fn save_template_file(&self, id: &TemplateId, path: &str, content: &[u8]) -> Result<()> {
+            let file_path = self.file_path(id, path)?;
+            let _guard = self.lock(id).await?;
+            if let Some(parent) = file_path.parent() {
+                fs::create_dir_all(parent).await?;
+            }
+            Self::write_atomic(&file_path, content).await
+        }
+
+        async fn get_template_file(&self, id: &TemplateId, path: &str) -> Result<Vec<u8>> {
+            let file_path = self.file_path(id, path)?;
+            fs::read(&file_path).await.map_err(|e| match e.kind() {
+                std::io::ErrorKind::NotFound => PapermakeError::not_found("File", path),
+                _ => PapermakeError::storage_io(&file_path, e),
+            })
+        }
+
+        async fn list_template_files(&self, id: &TemplateId) -> Result<Vec<String>> {
+            if !self.template_dir(id)?.exists() {
+                return Err(Self::not_found(id));
+            }
+
+            let files_dir = self.files_dir(id)?;
+            let mut files = Vec::new();
+            if files_dir.exists() {
+                Self::list_files_recursive(&files_dir, &files_dir, &mut files).await?;
+            }
+            files.sort();
+            Ok(files)
+        }
+
+        async fn delete_template_file(&self, id: &TemplateId, path: &str) -> Result<()> {
+            let file_path = self.file_path(id, path)?;
+            let _guard = self.lock(id).await?;
+            if !file_path.is_file() {
+                return Err(PapermakeError::not_found("File", path));
+            }
+            fs::remove_file(&file_path).await?;
+            Ok(())
+        }
+
+        async fn rename_template_file(&self, id: &TemplateId, from: &str, to: &str) -> Result<()> {
+            let from_path = self.file_path(id, from)?;
+            let to_path = self.file_path(id, to)?;
+            let _guard = self.lock(id).await?;
+            if !from_path.is_file() {
+                return Err(PapermakeError::not_found("File", from));
+            }
+            if to_path.exists() {
+                return Err(PapermakeError::Conflict(format!("File already exists: {}", to)));
+            }
+            if let Some(parent) = to_path.parent() {
+                fs::create_dir_all(parent).await?;
+            }
+            fs::rename(&from_path, &to_path).await?;
+            Ok(())
+        }
+
+        /// Purges this storage and every namespace below it
+        async fn purge(&self, before: OffsetDateTime, kinds: &[ArtifactKind]) -> Result<PurgeReport> {
+            let before = SystemTime::from(before);
+            let mut report = self.purge_own(before, kinds).await?;
+            for dir in Self::subdirs(&self.base_path.join("tenants")).await? {
+                let name = dir.file_name().unwrap_or_default().to_string_lossy().to_string();
+                let Ok(namespace) = Namespace::new(name) else {
+                    continue;
+                };
+                let tenant = FileStorage {
+                    base_path: self.base_path.join("tenants").join(namespace.as_str()),
+                    locks: self.locks.clone(),
+                };
+                report.add(tenant.purge_own(before, kinds).await?);
+            }
+            Ok(report)
+        }
+
+        async fn check_health(&self) -> Result<()> {
+            // A full disk or read-only mount only shows on writes
+            fs::create_dir_all(&self.base_path).await?;
+            let probe = self.base_path.join(".health");
+            Self::write_atomic(&probe, b"ok").await?;
+            // Concurrent probes share the file; whichever removes it first wins
+            match fs::remove_file(&probe).await {
+                Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+                _ => Ok(()),
+            }
+        }
+
+        fn for_namespace(&self, namespace: &Namespace) -> Arc<dyn Storage> {
+            Arc::new(FileStorage {
+                base_path: self.base_path.join("tenants").join(namespace.as_str()),
+                locks: self.locks.clone(),
+            })
+        }
+    }
+}
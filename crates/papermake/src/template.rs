@@ -1,12 +1,15 @@
 //! Template handling for Typst documents
 
+use std::collections::BTreeMap;
 use std::path::Path;
 use serde::{Serialize, Deserialize};
+use crate::environment::{EnvironmentConfig, DEFAULT_ENVIRONMENT};
 use crate::error::{PapermakeError, Result};
+use crate::sandbox::SandboxPolicy;
 use crate::schema::Schema;
 
 /// Unique identifier for a template
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct TemplateId(pub String);
 
 impl From<String> for TemplateId {
@@ -27,6 +30,25 @@ impl AsRef<str> for TemplateId {
     }
 }
 
+/// Lifecycle state of a template
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TemplateStatus {
+    /// Being edited; only renderable when explicitly requested
+    Draft,
+    /// Live; renders use the published revision by default
+    Published,
+    /// Retired; no longer renderable
+    Archived,
+}
+
+impl TemplateStatus {
+    /// Templates stored before the lifecycle existed were all live
+    fn legacy() -> Self {
+        TemplateStatus::Published
+    }
+}
+
 /// A template for PDF generation
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Template {
@@ -45,6 +67,61 @@ pub struct Template {
     /// Optional description
     pub description: Option<String>,
     
+    /// Named sample datasets used to test the template
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub examples: BTreeMap<String, serde_json::Value>,
+    
+    /// Whether this is a shared template other templates can import via
+    /// `papermake:shared/<id>.typ`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub shared: bool,
+    
+    /// Labels for organizing templates (e.g. department, locale, document type)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    
+    /// Free-form key-value metadata
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, String>,
+    
+    /// Localized sources by locale tag (e.g. `de-DE`), used instead of
+    /// `content` when rendering for a matching locale
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub variants: BTreeMap<String, String>,
+    
+    /// Restrictions applied when rendering this template, combined with
+    /// any set in the render options
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sandbox: Option<SandboxPolicy>,
+    
+    /// Settings per deployment environment (e.g. `staging`), selected by
+    /// `RenderOptions::environment`; see [`crate::environment`]
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub environments: BTreeMap<String, EnvironmentConfig>,
+    
+    /// Rhai script computing fields from the data before validation; see
+    /// [`crate::script`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub script: Option<String>,
+    
+    /// Revision of the stored template, incremented by every save and
+    /// checked by storage to detect concurrent modifications
+    #[serde(default)]
+    pub revision: u64,
+    
+    /// Lifecycle state
+    #[serde(default = "TemplateStatus::legacy")]
+    pub status: TemplateStatus,
+    
+    /// When the template was last published
+    #[serde(default, with = "time::serde::rfc3339::option", skip_serializing_if = "Option::is_none")]
+    pub published_at: Option<time::OffsetDateTime>,
+    
+    /// SHA-256 of each template file at the time of publishing, by path;
+    /// only set on published revisions, see [`crate::lifecycle::asset_manifest`]
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub assets: BTreeMap<String, String>,
+    
     /// Creation timestamp
     #[serde(with = "time::serde::rfc3339")]
     pub created_at: time::OffsetDateTime,
@@ -64,6 +141,18 @@ impl Template {
             content: content.into(),
             schema,
             description: None,
+            examples: BTreeMap::new(),
+            shared: false,
+            tags: Vec::new(),
+            metadata: BTreeMap::new(),
+            variants: BTreeMap::new(),
+            sandbox: None,
+            environments: BTreeMap::new(),
+            script: None,
+            revision: 0,
+            status: TemplateStatus::Draft,
+            published_at: None,
+            assets: BTreeMap::new(),
             created_at: now,
             updated_at: now,
         }
@@ -80,6 +169,113 @@ impl Template {
         self
     }
     
+    /// Attach a named example dataset
+    pub fn with_example(mut self, name: impl Into<String>, data: serde_json::Value) -> Self {
+        self.examples.insert(name.into(), data);
+        self
+    }
+    
+    /// Mark the template as shared, importable by other templates
+    pub fn as_shared(mut self) -> Self {
+        self.shared = true;
+        self
+    }
+    
+    /// Add a tag, ignoring duplicates
+    pub fn with_tag(mut self, tag: impl Into<String>) -> Self {
+        let tag = tag.into();
+        if !self.tags.contains(&tag) {
+            self.tags.push(tag);
+        }
+        self
+    }
+    
+    /// Set a metadata entry
+    pub fn with_metadata(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.metadata.insert(key.into(), value.into());
+        self
+    }
+    
+    /// Add a localized source for a locale
+    pub fn with_variant(mut self, locale: impl Into<String>, content: impl Into<String>) -> Self {
+        self.variants.insert(locale.into(), content.into());
+        self
+    }
+    
+    /// Restrict what the template may access when rendered
+    pub fn with_sandbox(mut self, policy: SandboxPolicy) -> Self {
+        self.sandbox = Some(policy);
+        self
+    }
+    
+    /// Set the settings for an environment, or for all of them with
+    /// [`DEFAULT_ENVIRONMENT`]
+    pub fn with_environment(mut self, name: impl Into<String>, config: EnvironmentConfig) -> Self {
+        self.environments.insert(name.into(), config);
+        self
+    }
+    
+    /// Set the script computing fields before rendering
+    pub fn with_script(mut self, script: impl Into<String>) -> Self {
+        self.script = Some(script.into());
+        self
+    }
+    
+    /// Settings for rendering in an environment: its entry merged over the
+    /// default entry
+    pub fn environment(&self, name: Option<&str>) -> EnvironmentConfig {
+        let default = self.environments.get(DEFAULT_ENVIRONMENT).cloned().unwrap_or_default();
+        match name.and_then(|name| self.environments.get(name)) {
+            Some(config) => default.merged(config),
+            None => default,
+        }
+    }
+    
+    /// A new template starting from this one's content, schema and settings
+    ///
+    /// The fork is an unpublished draft with its own history: revision 0,
+    /// fresh timestamps, and the source id in its `forked_from` metadata.
+    /// Asset files are not part of a `Template`; use
+    /// [`Storage::copy_template`](crate::storage::Storage::copy_template)
+    /// to copy them along.
+    pub fn fork(&self, new_id: impl Into<TemplateId>) -> Template {
+        let now = time::OffsetDateTime::now_utc();
+        let mut fork = self.clone();
+        fork.id = new_id.into();
+        fork.metadata.insert("forked_from".to_string(), self.id.0.clone());
+        fork.revision = 0;
+        fork.status = TemplateStatus::Draft;
+        fork.published_at = None;
+        fork.assets.clear();
+        fork.created_at = now;
+        fork.updated_at = now;
+        fork
+    }
+    
+    /// The template as rendered for a locale
+    ///
+    /// The content is taken from the best matching variant (exact locale,
+    /// then bare language, then another region of the language), falling
+    /// back to the template's own content.
+    pub fn variant(&self, locale: &str) -> Template {
+        let mut template = self.clone();
+        template.content = self.content_for(Some(locale)).to_string();
+        template
+    }
+    
+    /// The source rendered for `locale`, without cloning the template
+    pub fn content_for(&self, locale: Option<&str>) -> &str {
+        locale
+            .and_then(|locale| crate::locale::resolve_locale(self.variants.keys().map(String::as_str), locale))
+            .and_then(|matched| self.variants.get(matched))
+            .unwrap_or(&self.content)
+    }
+    
+    /// Whether the template has a tag
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+    
     /// Validate data against the template's schema
     pub fn validate_data(&self, data: &serde_json::Value) -> Result<()> {
         self.schema.validate(data)
@@ -95,6 +291,17 @@ impl Template {
         crate::render::render_pdf(self, data, Some(options))
     }
     
+    /// Render the template with data to a PDF on tokio's blocking thread pool
+    #[cfg(feature = "tokio")]
+    pub async fn render_async(&self, data: &serde_json::Value) -> Result<crate::render::RenderResult> {
+        crate::render::render_pdf_async(self, data, None).await
+    }
+    
+    /// Render the template with typed data
+    pub fn render_typed<T: crate::data::PapermakeData>(&self, data: &T) -> Result<crate::render::RenderResult> {
+        crate::data::render_pdf_typed(self, data, None)
+    }
+    
     /// Render the template with data using a cached world
     pub fn render_with_cache(&self, data: &serde_json::Value, world_cache: Option<&mut crate::typst::TypstWorld>) -> Result<crate::render::RenderResult> {
         crate::render::render_pdf_with_cache(self, data, world_cache, None)
@@ -130,6 +337,18 @@ impl Template {
             content: template_content,
             schema,
             description: None,
+            examples: BTreeMap::new(),
+            shared: false,
+            tags: Vec::new(),
+            metadata: BTreeMap::new(),
+            variants: BTreeMap::new(),
+            sandbox: None,
+            environments: BTreeMap::new(),
+            script: None,
+            revision: 0,
+            status: TemplateStatus::Draft,
+            published_at: None,
+            assets: BTreeMap::new(),
             created_at: time::OffsetDateTime::now_utc(),
             updated_at: time::OffsetDateTime::now_utc(),
         })
@@ -154,6 +373,14 @@ pub struct TemplateBuilder {
     content: Option<String>,
     schema: Option<Schema>,
     description: Option<String>,
+    examples: BTreeMap<String, serde_json::Value>,
+    shared: bool,
+    tags: Vec<String>,
+    metadata: BTreeMap<String, String>,
+    variants: BTreeMap<String, String>,
+    sandbox: Option<SandboxPolicy>,
+    environments: BTreeMap<String, EnvironmentConfig>,
+    script: Option<String>,
 }
 
 impl TemplateBuilder {
@@ -165,6 +392,14 @@ impl TemplateBuilder {
             content: None,
             schema: None,
             description: None,
+            examples: BTreeMap::new(),
+            shared: false,
+            tags: Vec::new(),
+            metadata: BTreeMap::new(),
+            variants: BTreeMap::new(),
+            sandbox: None,
+            environments: BTreeMap::new(),
+            script: None,
         }
     }
     
@@ -194,12 +429,69 @@ impl TemplateBuilder {
         self
     }
     
+    /// Set the schema from a type implementing `PapermakeData`
+    pub fn schema_for<T: crate::data::PapermakeData>(mut self) -> Self {
+        self.schema = Some(T::schema());
+        self
+    }
+    
     /// Set the description
     pub fn description(mut self, description: impl Into<String>) -> Self {
         self.description = Some(description.into());
         self
     }
     
+    /// Attach a named example dataset
+    pub fn example(mut self, name: impl Into<String>, data: serde_json::Value) -> Self {
+        self.examples.insert(name.into(), data);
+        self
+    }
+    
+    /// Mark the template as shared, importable by other templates
+    pub fn shared(mut self, shared: bool) -> Self {
+        self.shared = shared;
+        self
+    }
+    
+    /// Add a tag, ignoring duplicates
+    pub fn tag(mut self, tag: impl Into<String>) -> Self {
+        let tag = tag.into();
+        if !self.tags.contains(&tag) {
+            self.tags.push(tag);
+        }
+        self
+    }
+    
+    /// Set a metadata entry
+    pub fn metadata(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.metadata.insert(key.into(), value.into());
+        self
+    }
+    
+    /// Add a localized source for a locale
+    pub fn variant(mut self, locale: impl Into<String>, content: impl Into<String>) -> Self {
+        self.variants.insert(locale.into(), content.into());
+        self
+    }
+    
+    /// Restrict what the template may access when rendered
+    pub fn sandbox(mut self, policy: SandboxPolicy) -> Self {
+        self.sandbox = Some(policy);
+        self
+    }
+    
+    /// Set the settings for an environment
+    pub fn environment(mut self, name: impl Into<String>, config: EnvironmentConfig) -> Self {
+        self.environments.insert(name.into(), config);
+        self
+    }
+    
+    /// Set the script computing fields before rendering
+    pub fn script(mut self, script: impl Into<String>) -> Self {
+        self.script = Some(script.into());
+        self
+    }
+    
     /// Build the template
     pub fn build(self) -> Result<Template> {
         let name = self.name.ok_or_else(|| PapermakeError::Template("Template name is required".to_string()))?;
@@ -213,6 +505,18 @@ impl TemplateBuilder {
             content,
             schema,
             description: self.description,
+            examples: self.examples,
+            shared: self.shared,
+            tags: self.tags,
+            metadata: self.metadata,
+            variants: self.variants,
+            sandbox: self.sandbox,
+            environments: self.environments,
+            script: self.script,
+            revision: 0,
+            status: TemplateStatus::Draft,
+            published_at: None,
+            assets: BTreeMap::new(),
             created_at: now,
             updated_at: now,
         })
@@ -234,4 +538,4 @@ impl TemplateBuilder {
             .build()?;
         Ok(template.with_cache())
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,237 @@
+//! Test harness for rendering templates against their attached examples
+//!
+//! Examples are sample datasets attached to a template with
+//! [`Template::with_example`]. The helpers in this module render them and
+//! provide assertions on the output, so templates can be snapshot-tested in CI.
+//! Snapshots are golden files holding the text of each page; running the
+//! tests with `PAPERMAKE_UPDATE_GOLDEN=1` writes them from the current output.
+//!
+//! ```rust,no_run
+//! use papermake::{testing::assert_renders, Template};
+//! use serde_json::json;
+//!
+//! let template = Template::builder("greeting")
+//!     .name("Greeting")
+//!     .content("#let data = json.decode(sys.inputs.data)\nHello #data.name!")
+//!     .example("basic", json!({ "name": "World" }))
+//!     .build()
+//!     .unwrap();
+//!
+//! assert_renders(&template, "basic")
+//!     .assert_page_count(1)
+//!     .assert_contains_text("World")
+//!     .assert_matches_golden("tests/golden/greeting-basic.txt");
+//! ```
+
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::error::{PapermakeError, Result};
+use crate::line_diff::unified_diff;
+use crate::render::{compile_template, pdf_options, RenderError, RenderOptions};
+use crate::template::Template;
+use crate::text::extract_text;
+
+/// Environment variable that makes [`RenderedExample::assert_matches_golden`]
+/// write golden files instead of comparing against them
+pub const UPDATE_GOLDEN_ENV: &str = "PAPERMAKE_UPDATE_GOLDEN";
+
+/// Output of rendering a single example
+#[derive(Debug)]
+pub struct RenderedExample {
+    /// Name of the example
+    pub name: String,
+
+    /// Rendered PDF bytes
+    pub pdf: Vec<u8>,
+
+    /// Number of pages in the document
+    pub page_count: usize,
+
+    /// Plain text content of each page
+    pub pages_text: Vec<String>,
+}
+
+impl RenderedExample {
+    /// Text content of the whole document, pages separated by newlines
+    pub fn text(&self) -> String {
+        self.pages_text.join("\n")
+    }
+
+    /// Assert that the document has exactly `expected` pages
+    #[track_caller]
+    pub fn assert_page_count(&self, expected: usize) -> &Self {
+        assert_eq!(
+            self.page_count, expected,
+            "example '{}' rendered {} pages, expected {}",
+            self.name, self.page_count, expected
+        );
+        self
+    }
+
+    /// Assert that the document contains the given text
+    #[track_caller]
+    pub fn assert_contains_text(&self, needle: &str) -> &Self {
+        assert!(
+            self.text().contains(needle),
+            "example '{}' does not contain text '{}'",
+            self.name, needle
+        );
+        self
+    }
+
+    /// Assert that the given page (1-based) contains the given text
+    #[track_caller]
+    pub fn assert_page_contains_text(&self, page: usize, needle: &str) -> &Self {
+        let text = page
+            .checked_sub(1)
+            .and_then(|index| self.pages_text.get(index))
+            .unwrap_or_else(|| panic!("example '{}' has no page {}", self.name, page));
+        assert!(
+            text.contains(needle),
+            "page {} of example '{}' does not contain text '{}'",
+            page, self.name, needle
+        );
+        self
+    }
+
+    /// Text of every page under a `--- page N ---` header, as stored in
+    /// golden files
+    pub fn golden_text(&self) -> String {
+        self.pages_text
+            .iter()
+            .enumerate()
+            .map(|(i, text)| format!("--- page {} ---\n{}\n", i + 1, text.trim_end()))
+            .collect()
+    }
+
+    /// Compare the text with the golden file at `path`, returning a diff
+    /// from the golden file to the current text if they differ. With
+    /// `update` set the file is written instead.
+    pub fn compare_golden(&self, path: impl AsRef<Path>, update: bool) -> Result<Option<String>> {
+        let path = path.as_ref();
+        let actual = self.golden_text();
+        if update {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(path, actual)?;
+            return Ok(None);
+        }
+
+        let expected = std::fs::read_to_string(path).map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => PapermakeError::not_found("Golden file", path.display().to_string()),
+            _ => PapermakeError::storage_io(path, e),
+        })?;
+        Ok((expected != actual).then(|| unified_diff(&expected, &actual, 2)))
+    }
+
+    /// Assert that the text matches the golden file at `path`, or write the
+    /// file when `PAPERMAKE_UPDATE_GOLDEN` is set
+    #[track_caller]
+    pub fn assert_matches_golden(&self, path: impl AsRef<Path>) -> &Self {
+        let path = path.as_ref();
+        let update = std::env::var_os(UPDATE_GOLDEN_ENV).is_some_and(|value| value != "0");
+        match self.compare_golden(path, update) {
+            Ok(None) => self,
+            Ok(Some(diff)) => panic!(
+                "example '{}' doesn't match {}; run with {}=1 to update it\n{}",
+                self.name,
+                path.display(),
+                UPDATE_GOLDEN_ENV,
+                diff
+            ),
+            Err(e) => panic!(
+                "example '{}' can't be compared with {}: {}; run with {}=1 to create it",
+                self.name,
+                path.display(),
+                e,
+                UPDATE_GOLDEN_ENV
+            ),
+        }
+    }
+}
+
+/// Outcome of running a single example, suitable for reporting
+#[derive(Debug, Serialize)]
+pub struct ExampleReport {
+    pub name: String,
+    pub passed: bool,
+    pub page_count: Option<usize>,
+    pub errors: Vec<RenderError>,
+}
+
+/// Render a named example attached to the template
+pub fn render_example(template: &Template, example: &str) -> Result<RenderedExample> {
+    let data = template.examples.get(example).ok_or_else(|| {
+        PapermakeError::InvalidInput(format!(
+            "Template '{}' has no example named '{}'",
+            template.id.as_ref(),
+            example
+        ))
+    })?;
+
+    // Deterministic, so rendered examples can be compared byte for byte
+    let options = RenderOptions {
+        deterministic: true,
+        ..RenderOptions::default()
+    };
+    let compiled = compile_template(template, data, None, &options)?;
+    let document = compiled.document.ok_or_else(|| {
+        let messages: Vec<_> = compiled.errors.iter().map(|e| e.message.as_str()).collect();
+        PapermakeError::Rendering(messages.join("; "))
+    })?;
+
+    let pdf = typst_pdf::pdf(&document, &pdf_options(template, &options))
+        .map_err(|e| PapermakeError::Rendering(format!("PDF export failed: {:?}", e)))?;
+
+    Ok(RenderedExample {
+        name: example.to_string(),
+        pdf,
+        page_count: document.pages.len(),
+        pages_text: extract_text(&document, None).into_iter().map(|page| page.text).collect(),
+    })
+}
+
+/// Render a named example, panicking with the compile errors if it fails
+#[track_caller]
+pub fn assert_renders(template: &Template, example: &str) -> RenderedExample {
+    match render_example(template, example) {
+        Ok(rendered) => rendered,
+        Err(e) => panic!(
+            "example '{}' of template '{}' failed to render: {}",
+            example,
+            template.id.as_ref(),
+            e
+        ),
+    }
+}
+
+/// Render every example attached to the template and report the outcome
+pub fn run_examples(template: &Template) -> Vec<ExampleReport> {
+    template
+        .examples
+        .iter()
+        .map(|(name, data)| {
+            match compile_template(template, data, None, &RenderOptions::default()) {
+                Ok(compiled) => ExampleReport {
+                    name: name.clone(),
+                    passed: compiled.document.is_some(),
+                    page_count: compiled.document.map(|document| document.pages.len()),
+                    errors: compiled.errors,
+                },
+                Err(e) => ExampleReport {
+                    name: name.clone(),
+                    passed: false,
+                    page_count: None,
+                    errors: vec![RenderError {
+                        message: e.to_string(),
+                        start: 0,
+                        end: 0,
+                    }],
+                },
+            }
+        })
+        .collect()
+}
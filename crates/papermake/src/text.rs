@@ -0,0 +1,87 @@
+//! Positioned text content of rendered pages
+//!
+//! With `RenderOptions::extract_text` set, [`RenderResult::text`](crate::RenderResult::text)
+//! holds the text of each exported page, read from the compiled document
+//! rather than parsed back out of the PDF. Every run of text laid out in one
+//! font and size is a [`TextSpan`] with its position on the page, so callers
+//! can index documents for search or assert that a total appears on the
+//! first page:
+//!
+//! ```rust,no_run
+//! # fn check(result: papermake::RenderResult) {
+//! let pages = result.text.unwrap();
+//! assert!(pages[0].contains("Total: 42.00"));
+//! # }
+//! ```
+
+use serde::{Deserialize, Serialize};
+use typst::layout::{Frame, FrameItem, PagedDocument, Point, Transform};
+
+use crate::pdf_ops::PageSelection;
+
+/// Text of a single page
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PageText {
+    /// 1-based number of the page in the compiled document
+    pub page: usize,
+    /// Text of all spans in layout order, separated by spaces
+    pub text: String,
+    pub spans: Vec<TextSpan>,
+}
+
+impl PageText {
+    /// Whether the page contains `needle`, within or across spans
+    pub fn contains(&self, needle: &str) -> bool {
+        self.text.contains(needle)
+    }
+}
+
+/// A run of text in one font and size; positions are in points from the
+/// top-left corner of the page, with `y` at the baseline
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TextSpan {
+    pub text: String,
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub font_size: f64,
+}
+
+/// Text of the selected pages of a document, all pages if `pages` is `None`
+pub fn extract_text(document: &PagedDocument, pages: Option<&PageSelection>) -> Vec<PageText> {
+    document
+        .pages
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| pages.is_none_or(|pages| pages.contains(i + 1)))
+        .map(|(i, page)| {
+            let mut spans = Vec::new();
+            collect_spans(&page.frame, Transform::identity(), &mut spans);
+            let text = spans.iter().map(|span| span.text.as_str()).collect::<Vec<_>>().join(" ");
+            PageText { page: i + 1, text, spans }
+        })
+        .collect()
+}
+
+/// Collect the text items of a frame and its groups, in layout order
+fn collect_spans(frame: &Frame, ts: Transform, spans: &mut Vec<TextSpan>) {
+    for (pos, item) in frame.items() {
+        match item {
+            FrameItem::Group(group) => {
+                let ts = ts.pre_concat(Transform::translate(pos.x, pos.y)).pre_concat(group.transform);
+                collect_spans(&group.frame, ts, spans);
+            }
+            FrameItem::Text(item) if !item.text.is_empty() => {
+                let Point { x, y } = pos.transform(ts);
+                spans.push(TextSpan {
+                    text: item.text.to_string(),
+                    x: x.to_pt(),
+                    y: y.to_pt(),
+                    width: item.width().to_pt(),
+                    font_size: item.size.to_pt(),
+                });
+            }
+            _ => {}
+        }
+    }
+}
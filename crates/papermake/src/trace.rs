@@ -0,0 +1,67 @@
+//! Tracing spans around the phases of a render
+//!
+//! Every render runs in a `render` span carrying the template id, revision
+//! and locale, with child spans for the phases: `validate` (preparing the
+//! data), `world_setup`, `compile`, `pdf_export` and `post_process`
+//! (attachments, optimization and encryption). Each span records its
+//! duration in an `elapsed_ms` field when it closes, so a subscriber shows
+//! where render time goes instead of a single total.
+
+use tracing::field::Empty;
+use tracing::span::EnteredSpan;
+use tracing::Span;
+
+use crate::render::RenderOptions;
+use crate::template::Template;
+
+/// An entered span recording its duration when dropped
+pub(crate) struct Phase {
+    span: EnteredSpan,
+    #[cfg(not(target_arch = "wasm32"))]
+    started: std::time::Instant,
+}
+
+impl Phase {
+    /// Enter `span`, which must declare an `elapsed_ms` field
+    pub(crate) fn start(span: Span) -> Self {
+        Self {
+            span: span.entered(),
+            #[cfg(not(target_arch = "wasm32"))]
+            started: std::time::Instant::now(),
+        }
+    }
+
+    /// Record a field declared by the span
+    pub(crate) fn record(&self, field: &str, value: impl tracing::Value) {
+        self.span.record(field, value);
+    }
+}
+
+impl Drop for Phase {
+    fn drop(&mut self) {
+        #[cfg(not(target_arch = "wasm32"))]
+        self.span.record("elapsed_ms", self.started.elapsed().as_secs_f64() * 1000.0);
+    }
+}
+
+/// Start a phase span named `$name` inside the current render
+macro_rules! phase {
+    ($name:literal) => {
+        $crate::trace::Phase::start(tracing::debug_span!($name, elapsed_ms = tracing::field::Empty))
+    };
+}
+pub(crate) use phase;
+
+/// Start the span of a whole render of `template`
+pub(crate) fn render_phase(template: &Template, options: &RenderOptions) -> Phase {
+    Phase::start(tracing::info_span!(
+        "render",
+        template_id = %template.id.0,
+        revision = template.revision,
+        status = ?template.status,
+        locale = options.locale.as_deref(),
+        cached = Empty,
+        success = Empty,
+        elapsed_ms = Empty,
+    ))
+}
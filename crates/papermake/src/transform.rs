@@ -0,0 +1,280 @@
+//! Data transformations applied before validation and rendering
+//!
+//! Transforms compute or reshape values (totals, formatted amounts, looked
+//! up labels) so templates can stay presentation-only. They run in order as
+//! a [`TransformPipeline`] set on `RenderOptions::transforms`, once per
+//! render.
+
+use std::fmt;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::{PapermakeError, Result};
+use crate::format::{parse_date, LocaleFormat};
+
+/// A step rewriting render data
+pub trait DataTransform: Send + Sync {
+    /// Modify the data in place
+    fn apply(&self, data: &mut Value) -> Result<()>;
+
+    /// Name shown in debug output and error messages
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+}
+
+impl<F> DataTransform for F
+where
+    F: Fn(&mut Value) -> Result<()> + Send + Sync,
+{
+    fn apply(&self, data: &mut Value) -> Result<()> {
+        self(data)
+    }
+
+    fn name(&self) -> &str {
+        "closure"
+    }
+}
+
+/// Ordered chain of transforms
+#[derive(Clone, Default)]
+pub struct TransformPipeline {
+    steps: Vec<Arc<dyn DataTransform>>,
+}
+
+impl TransformPipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append a transform
+    pub fn then(mut self, transform: impl DataTransform + 'static) -> Self {
+        self.steps.push(Arc::new(transform));
+        self
+    }
+
+    /// Append a shared transform
+    pub fn push(&mut self, transform: Arc<dyn DataTransform>) {
+        self.steps.push(transform);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /// Run all transforms in order
+    pub fn apply(&self, data: &mut Value) -> Result<()> {
+        for step in &self.steps {
+            step.apply(data).map_err(|err| {
+                PapermakeError::InvalidInput(format!("Transform '{}' failed: {}", step.name(), err))
+            })?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for TransformPipeline {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.steps.iter().map(|step| step.name()))
+            .finish()
+    }
+}
+
+/// Formats a number field for a locale into a string field
+///
+/// `path` is a dotted path where `*` matches every array element, e.g.
+/// `items.*.price`. The result is stored next to the source as
+/// `<field>_formatted` unless another target name is set.
+#[derive(Debug, Clone)]
+pub struct FormatNumber {
+    path: String,
+    target: Option<String>,
+    format: LocaleFormat,
+    decimals: usize,
+    currency: Option<String>,
+}
+
+impl FormatNumber {
+    pub fn new(path: impl Into<String>, locale: &str) -> Self {
+        Self {
+            path: path.into(),
+            target: None,
+            format: LocaleFormat::for_locale(locale),
+            decimals: 2,
+            currency: None,
+        }
+    }
+
+    /// Number of fraction digits (2 by default)
+    pub fn decimals(mut self, decimals: usize) -> Self {
+        self.decimals = decimals;
+        self
+    }
+
+    /// Format as an amount in this ISO 4217 currency
+    pub fn currency(mut self, currency: impl Into<String>) -> Self {
+        self.currency = Some(currency.into());
+        self
+    }
+
+    /// Name of the field receiving the formatted value
+    pub fn target(mut self, target: impl Into<String>) -> Self {
+        self.target = Some(target.into());
+        self
+    }
+}
+
+impl DataTransform for FormatNumber {
+    fn apply(&self, data: &mut Value) -> Result<()> {
+        format_fields(data, &self.path, self.target.as_deref(), |value| {
+            let number = value.as_f64()?;
+            Some(match &self.currency {
+                Some(currency) => self.format.format_currency(number, currency),
+                None => self.format.format_number(number, self.decimals),
+            })
+        });
+        Ok(())
+    }
+
+    fn name(&self) -> &str {
+        "format_number"
+    }
+}
+
+/// Formats an ISO 8601 date field for a locale into a string field
+///
+/// Paths and targets work as for [`FormatNumber`].
+#[derive(Debug, Clone)]
+pub struct FormatDate {
+    path: String,
+    target: Option<String>,
+    format: LocaleFormat,
+}
+
+impl FormatDate {
+    pub fn new(path: impl Into<String>, locale: &str) -> Self {
+        Self {
+            path: path.into(),
+            target: None,
+            format: LocaleFormat::for_locale(locale),
+        }
+    }
+
+    /// Name of the field receiving the formatted value
+    pub fn target(mut self, target: impl Into<String>) -> Self {
+        self.target = Some(target.into());
+        self
+    }
+}
+
+impl DataTransform for FormatDate {
+    fn apply(&self, data: &mut Value) -> Result<()> {
+        format_fields(data, &self.path, self.target.as_deref(), |value| {
+            parse_date(value.as_str()?).map(|date| self.format.format_date(date))
+        });
+        Ok(())
+    }
+
+    fn name(&self) -> &str {
+        "format_date"
+    }
+}
+
+/// Serializable description of a built-in transform, e.g. from a request body
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TransformSpec {
+    FormatNumber {
+        field: String,
+        locale: String,
+        decimals: Option<usize>,
+        currency: Option<String>,
+        target: Option<String>,
+    },
+    FormatDate {
+        field: String,
+        locale: String,
+        target: Option<String>,
+    },
+}
+
+impl TransformSpec {
+    pub fn into_transform(self) -> Arc<dyn DataTransform> {
+        match self {
+            TransformSpec::FormatNumber { field, locale, decimals, currency, target } => {
+                let mut transform = FormatNumber::new(field, &locale);
+                if let Some(decimals) = decimals {
+                    transform = transform.decimals(decimals);
+                }
+                if let Some(currency) = currency {
+                    transform = transform.currency(currency);
+                }
+                if let Some(target) = target {
+                    transform = transform.target(target);
+                }
+                Arc::new(transform)
+            }
+            TransformSpec::FormatDate { field, locale, target } => {
+                let mut transform = FormatDate::new(field, &locale);
+                if let Some(target) = target {
+                    transform = transform.target(target);
+                }
+                Arc::new(transform)
+            }
+        }
+    }
+}
+
+impl FromIterator<TransformSpec> for TransformPipeline {
+    fn from_iter<I: IntoIterator<Item = TransformSpec>>(specs: I) -> Self {
+        let mut pipeline = TransformPipeline::new();
+        for spec in specs {
+            pipeline.push(spec.into_transform());
+        }
+        pipeline
+    }
+}
+
+/// Write `format(value)` next to every value matched by `path`; values
+/// that can't be formatted (missing, wrong type) are skipped
+fn format_fields(
+    data: &mut Value,
+    path: &str,
+    target: Option<&str>,
+    format: impl Fn(&Value) -> Option<String>,
+) {
+    let segments: Vec<&str> = path.split('.').collect();
+    let Some((field, parents)) = segments.split_last() else {
+        return;
+    };
+    let target = target
+        .map(str::to_string)
+        .unwrap_or_else(|| format!("{}_formatted", field));
+
+    for parent in select(data, parents) {
+        let Some(object) = parent.as_object_mut() else {
+            continue;
+        };
+        if let Some(formatted) = object.get(*field).and_then(&format) {
+            object.insert(target.clone(), Value::String(formatted));
+        }
+    }
+}
+
+/// All values at a dotted path, with `*` expanding array elements
+fn select<'a>(value: &'a mut Value, path: &[&str]) -> Vec<&'a mut Value> {
+    let Some((first, rest)) = path.split_first() else {
+        return vec![value];
+    };
+    match (*first, value) {
+        ("*", Value::Array(items)) => items.iter_mut().flat_map(|item| select(item, rest)).collect(),
+        (key, Value::Object(object)) => match object.get_mut(key) {
+            Some(child) => select(child, rest),
+            None => Vec::new(),
+        },
+        _ => Vec::new(),
+    }
+}
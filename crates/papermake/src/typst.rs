@@ -1,40 +1,62 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 
 use once_cell::sync::Lazy;
 use typst::diag::{FileError, FileResult};
 use typst::foundations::{Bytes, Datetime, Dict, IntoValue};
-use typst::syntax::{FileId, Source};
+use typst::syntax::{FileId, Source, VirtualPath};
 use typst::text::{Font, FontBook};
 use typst::utils::LazyHash;
-use typst::Library;
+use typst::{Feature, Features, Library};
+#[cfg(feature = "system-fonts")]
 use typst_kit::fonts::{FontSearcher, FontSlot};
 
-// Define a static lazy variable to hold the cached fonts
-static CACHED_FONTS: Lazy<(FontBook, Vec<Font>)> = Lazy::new(|| {
-    let mut font_searcher = FontSearcher::new();
-    let font_searcher = font_searcher.include_system_fonts(true);
+use crate::environment::{environment_inputs, json_value, EnvironmentConfig};
+use crate::locale::{locale_inputs, LOCALE_MODULE, LOCALE_MODULE_PATH};
+use crate::sandbox::SandboxPolicy;
+use crate::sections::{section_inputs, SECTIONS_MODULE, SECTIONS_MODULE_PATH};
+use crate::shared::{SharedSources, IMPORT_SCHEME};
 
-    let fonts = match std::env::var_os("FONTS_DIR") {
-        Some(fonts_dir) => {
-            let fonts_dir = PathBuf::from(fonts_dir);
-            font_searcher.search_with([&fonts_dir])
-        }
-        None => font_searcher.search(),
-    };
+/// Path prefix templates load charts from, see `crate::charts`
+const CHART_SCHEME: &str = "chart:";
+
+// Define a static lazy variable to hold the cached fonts. The font book is
+// hashed once and shared by all worlds, so comemo sees the same book everywhere.
+static CACHED_FONTS: Lazy<(LazyHash<FontBook>, Vec<Font>)> = Lazy::new(|| {
+    let mut fonts = Vec::new();
+
+    #[cfg(feature = "system-fonts")]
+    {
+        let mut font_searcher = FontSearcher::new();
+        let font_searcher = font_searcher.include_system_fonts(true);
+
+        let found = match std::env::var_os("FONTS_DIR") {
+            Some(fonts_dir) => {
+                let fonts_dir = PathBuf::from(fonts_dir);
+                font_searcher.search_with([&fonts_dir])
+            }
+            None => font_searcher.search(),
+        };
+        fonts.extend(found.fonts.iter().filter_map(FontSlot::get));
+    }
 
-    let book = fonts.book;
-    let fonts = fonts
-        .fonts
-        .iter()
-        .map(FontSlot::get)
-        .filter_map(|f| f)
-        .collect::<Vec<_>>();
+    // Embedded fonts are the only ones available where there is no file
+    // system to search, e.g. in the browser
+    #[cfg(feature = "embed-fonts")]
+    fonts.extend(
+        typst_assets::fonts().flat_map(|data| Font::iter(Bytes::new(data))),
+    );
 
-    (book, fonts)
+    let book = FontBook::from_fonts(&fonts);
+    (LazyHash::new(book), fonts)
 });
 
+// Stable id of the main source. Every world uses the same id, so memoized
+// parsing, evaluation and layout results carry over between worlds and
+// renders of the same template.
+static MAIN_ID: Lazy<FileId> = Lazy::new(|| FileId::new(None, VirtualPath::new("main.typ")));
+
 /// Main interface that determines the environment for Typst.
 #[derive(Debug)]
 pub struct TypstWorld {
@@ -44,58 +66,239 @@ pub struct TypstWorld {
     /// The standard library.
     library: LazyHash<Library>,
 
-    /// Metadata about all known fonts.
-    book: LazyHash<FontBook>,
+    /// The data currently exposed as `sys.inputs.data`.
+    data: String,
+
+    /// The locale currently exposed as `sys.inputs.locale`.
+    locale: Option<String>,
+
+    /// Section states currently exposed as `sys.inputs.sections`.
+    sections: BTreeMap<String, bool>,
+
+    /// Environment name and settings exposed as `sys.inputs.environment`.
+    environment: (Option<String>, EnvironmentConfig),
+
+    /// Caller-supplied inputs exposed as `sys.inputs.extra`.
+    extra_inputs: BTreeMap<String, serde_json::Value>,
 
-    /// Metadata about all known fonts.
-    fonts: Vec<Font>,
+    /// Whether the library enables Typst's HTML export.
+    html: bool,
 
     /// Map of all known files.
     files: Arc<Mutex<HashMap<FileId, FileEntry>>>,
 
+    /// Shared template sources importable via `papermake:` paths.
+    shared: HashMap<String, Bytes>,
+
+    /// Barcodes drawn from the data, under `papermake:barcodes/`.
+    barcodes: HashMap<String, Bytes>,
+
+    /// Charts drawn before compiling, under `chart:`.
+    charts: HashMap<String, Bytes>,
+
+    /// Fetches files by URL, if enabled.
+    #[cfg(feature = "remote")]
+    remote: Option<crate::remote::RemoteResources>,
+
+    /// What the template may access.
+    sandbox: SandboxPolicy,
+
     /// Cache directory (e.g. where packages are downloaded to).
     #[allow(dead_code)]
     cache_directory: PathBuf,
 
     /// Datetime.
     time: time::OffsetDateTime,
+
+    /// Offset `datetime.today()` gives the date in when none is passed.
+    timezone: time::UtcOffset,
 }
 
 impl TypstWorld {
     pub fn new(template_content: String, data: String) -> Self {
-        // Use the cached fonts directly
-        let (book, fonts) = CACHED_FONTS.clone();
-
-        let mut inputs_dict = Dict::new();
-        inputs_dict.insert("data".into(), data.as_str().into_value());
-
-        let library = Library::builder().with_inputs(inputs_dict).build();
-
         Self {
-            library: LazyHash::new(library),
-            book: LazyHash::new(book),
-            fonts, // Use the cached fonts
-            source: Source::detached(template_content),
+            library: LazyHash::new(build_library(&data, None, &BTreeMap::new(), &Default::default(), &BTreeMap::new(), false)),
+            data,
+            locale: None,
+            sections: BTreeMap::new(),
+            environment: Default::default(),
+            extra_inputs: BTreeMap::new(),
+            html: false,
+            source: Source::new(*MAIN_ID, template_content),
             time: time::OffsetDateTime::now_utc(),
-            cache_directory: std::env::var_os("CACHE_DIRECTORY")
-                .map(|os_path| os_path.into())
-                .unwrap_or(std::env::temp_dir()),
+            timezone: time::UtcOffset::UTC,
+            cache_directory: cache_directory(),
             files: Arc::new(Mutex::new(HashMap::new())),
+            shared: HashMap::new(),
+            barcodes: HashMap::new(),
+            charts: HashMap::new(),
+            #[cfg(feature = "remote")]
+            remote: None,
+            sandbox: SandboxPolicy::default(),
         }
     }
 
     pub fn update_data(&mut self, data: String) -> Result<(), String> {
-        // Update the data in the inputs dictionary
-        let mut inputs_dict = Dict::new();
-        inputs_dict.insert("data".into(), data.as_str().into_value());
-        
+        // Keep the library (and its hash) when the data is unchanged, so
+        // memoized evaluation results stay valid
+        if data == self.data {
+            return Ok(());
+        }
+
         // Create a new library with updated inputs
         // Note: This is not optimal - ideally we'd modify the existing library
-        let library = Library::builder().with_inputs(inputs_dict).build();
-        self.library = LazyHash::new(library);
-        
+        self.data = data;
+        self.rebuild_library();
+
         Ok(())
     }
+
+    /// Set the locale exposed to the template and its formatting helpers
+    pub fn set_locale(&mut self, locale: Option<&str>) {
+        if self.locale.as_deref() != locale {
+            self.locale = locale.map(str::to_string);
+            self.rebuild_library();
+        }
+    }
+
+    /// Set the section states exposed to the template and its helpers
+    pub fn set_sections(&mut self, sections: BTreeMap<String, bool>) {
+        if self.sections != sections {
+            self.sections = sections;
+            self.rebuild_library();
+        }
+    }
+
+    /// Set the environment name and settings exposed to the template
+    pub fn set_environment(&mut self, name: Option<&str>, config: EnvironmentConfig) {
+        let environment = (name.map(str::to_string), config);
+        if self.environment != environment {
+            self.environment = environment;
+            self.rebuild_library();
+        }
+    }
+
+    /// Set the caller-supplied inputs exposed as `sys.inputs.extra`
+    pub fn set_extra_inputs(&mut self, inputs: &BTreeMap<String, serde_json::Value>) {
+        if &self.extra_inputs != inputs {
+            self.extra_inputs = inputs.clone();
+            self.rebuild_library();
+        }
+    }
+
+    /// Enable or disable Typst's HTML export in the library
+    pub fn set_html(&mut self, html: bool) {
+        if self.html != html {
+            self.html = html;
+            self.rebuild_library();
+        }
+    }
+
+    fn rebuild_library(&mut self) {
+        self.library = LazyHash::new(build_library(
+            &self.data,
+            self.locale.as_deref(),
+            &self.sections,
+            &self.environment,
+            &self.extra_inputs,
+            self.html,
+        ));
+    }
+
+    /// Replace the template source, reparsing only the changed parts
+    pub fn update_source(&mut self, template_content: &str) {
+        if self.source.text() != template_content {
+            self.source.replace(template_content);
+        }
+    }
+
+    /// Set the time `datetime.today()` is based on
+    pub fn set_time(&mut self, time: time::OffsetDateTime) {
+        self.time = time;
+    }
+
+    /// Set the UTC offset `datetime.today()` uses when not given one
+    pub fn set_timezone(&mut self, offset: time::UtcOffset) {
+        self.timezone = offset;
+    }
+
+    /// Set what the template may access
+    pub fn set_sandbox(&mut self, policy: SandboxPolicy) {
+        self.sandbox = policy;
+    }
+
+    /// Replace the shared template sources available to imports
+    pub fn set_shared_sources(&mut self, sources: &SharedSources) {
+        self.shared = sources
+            .iter()
+            .map(|(path, content)| (path.to_string(), Bytes::new(content.as_bytes().to_vec())))
+            .collect();
+    }
+}
+
+impl TypstWorld {
+    /// Replace the barcode images available to the template
+    pub fn set_barcodes(&mut self, barcodes: HashMap<String, Vec<u8>>) {
+        self.barcodes = barcodes
+            .into_iter()
+            .map(|(path, svg)| (path, Bytes::new(svg)))
+            .collect();
+    }
+
+    /// Make a file available at `path`, relative to the main source
+    pub(crate) fn add_file(&mut self, path: &str, bytes: Vec<u8>) {
+        let id = FileId::new(None, VirtualPath::new(path));
+        if let Ok(mut files) = self.files.lock() {
+            files.insert(id, FileEntry::new(bytes, None));
+        }
+    }
+
+    /// Replace the chart images available to the template
+    pub fn set_charts(&mut self, charts: HashMap<String, Vec<u8>>) {
+        self.charts = charts
+            .into_iter()
+            .map(|(name, svg)| (name, Bytes::new(svg)))
+            .collect();
+    }
+
+    /// Allow loading files by URL from the given hosts, or not at all
+    #[cfg(feature = "remote")]
+    pub fn set_remote_resources(&mut self, remote: Option<crate::remote::RemoteResources>) {
+        self.remote = remote;
+    }
+}
+
+/// Directory for downloaded packages and other cached files
+fn cache_directory() -> PathBuf {
+    match std::env::var_os("CACHE_DIRECTORY") {
+        Some(os_path) => os_path.into(),
+        // There is no temporary directory on wasm32-unknown-unknown; asking
+        // for it panics
+        None if cfg!(target_arch = "wasm32") => PathBuf::new(),
+        None => std::env::temp_dir(),
+    }
+}
+
+/// Build the standard library with `data` exposed as `sys.inputs.data`,
+/// along with the locale's inputs when one is set, the section states, the
+/// environment and the caller's extra inputs
+fn build_library(
+    data: &str,
+    locale: Option<&str>,
+    sections: &BTreeMap<String, bool>,
+    (environment, config): &(Option<String>, EnvironmentConfig),
+    extra_inputs: &BTreeMap<String, serde_json::Value>,
+    html: bool,
+) -> Library {
+    let mut inputs_dict = locale.map(locale_inputs).unwrap_or_default();
+    inputs_dict.extend(section_inputs(sections));
+    inputs_dict.extend(environment_inputs(environment.as_deref(), config));
+    // Always present, so templates can look up optional inputs with `at`
+    let extra: Dict = extra_inputs.iter().map(|(name, value)| (name.as_str().into(), json_value(value))).collect();
+    inputs_dict.insert("extra".into(), extra.into_value());
+    inputs_dict.insert("data".into(), data.into_value());
+    let features: Features = if html { [Feature::Html].into_iter().collect() } else { Features::default() };
+    Library::builder().with_inputs(inputs_dict).with_features(features).build()
 }
 
 /// A File that will be stored in the HashMap.
@@ -106,7 +309,6 @@ struct FileEntry {
 }
 
 impl FileEntry {
-    #[allow(dead_code)]
     fn new(bytes: Vec<u8>, source: Option<Source>) -> Self {
         Self {
             bytes: Bytes::new(bytes),
@@ -132,11 +334,75 @@ impl TypstWorld {
     ///
     /// Requests will be either in packages or a local file.
     fn file(&self, id: FileId) -> FileResult<FileEntry> {
+        // Remote files are never kept in `files`, so pooled worlds check each
+        // render's allowlist; `RemoteResources` caches responses for a while
+        #[cfg(feature = "remote")]
+        if let Some(url) = remote_url(id) {
+            let Some(remote) = &self.remote else {
+                return Err(FileError::AccessDenied);
+            };
+            let bytes = remote.fetch(&url).map_err(|message| FileError::Other(Some(message.into())))?;
+            return Ok(FileEntry { bytes, source: None });
+        }
+
         let files = self.files.lock().map_err(|_| FileError::AccessDenied)?;
         if let Some(entry) = files.get(&id) {
             return Ok(entry.clone());
         }
 
+        if let Some(package) = id.package() {
+            return Err(if self.sandbox.packages {
+                FileError::Other(Some(format!("Package {} is not available", package).into()))
+            } else {
+                FileError::AccessDenied
+            });
+        }
+
+        if let Some(name) = scheme_path(id, CHART_SCHEME) {
+            if let Some(bytes) = self.charts.get(&name) {
+                return Ok(FileEntry {
+                    bytes: bytes.clone(),
+                    source: None,
+                });
+            }
+        }
+
+        if let Some(path) = scheme_path(id, IMPORT_SCHEME) {
+            if path == LOCALE_MODULE_PATH {
+                if !self.sandbox.builtin_modules {
+                    return Err(FileError::AccessDenied);
+                }
+                return Ok(FileEntry {
+                    bytes: Bytes::new(LOCALE_MODULE.as_bytes()),
+                    source: None,
+                });
+            }
+            if path == SECTIONS_MODULE_PATH {
+                if !self.sandbox.builtin_modules {
+                    return Err(FileError::AccessDenied);
+                }
+                return Ok(FileEntry {
+                    bytes: Bytes::new(SECTIONS_MODULE.as_bytes()),
+                    source: None,
+                });
+            }
+            if let Some(bytes) = self.barcodes.get(&path) {
+                return Ok(FileEntry {
+                    bytes: bytes.clone(),
+                    source: None,
+                });
+            }
+            if let Some(bytes) = self.shared.get(&path) {
+                if !self.sandbox.shared_imports {
+                    return Err(FileError::AccessDenied);
+                }
+                return Ok(FileEntry {
+                    bytes: bytes.clone(),
+                    source: None,
+                });
+            }
+        }
+
         // TODO: handle packages and other sources
         eprintln!("accessing file id: {id:?}");
         Err(FileError::AccessDenied)
@@ -144,6 +410,33 @@ impl TypstWorld {
 
 }
 
+/// The path of a `papermake:` (or other `scheme`) file, without the scheme.
+///
+/// Import paths are resolved relative to the importing file, so a shared
+/// template importing another one yields a path like
+/// `/papermake:shared/papermake:shared/style.typ`; the last scheme wins.
+fn scheme_path(id: FileId, scheme: &str) -> Option<String> {
+    if id.package().is_some() {
+        return None;
+    }
+    let path = id.vpath().as_rootless_path().to_string_lossy();
+    let start = path.rfind(scheme)? + scheme.len();
+    Some(path[start..].to_string())
+}
+
+/// The URL of an `http(s)://` path; Typst collapses the double slash
+/// (`https:/host/logo.png`)
+#[cfg(feature = "remote")]
+fn remote_url(id: FileId) -> Option<String> {
+    if id.package().is_some() {
+        return None;
+    }
+    let path = id.vpath().as_rootless_path().to_string_lossy().replace('\\', "/");
+    let start = path.find("https:/").or_else(|| path.find("http:/"))?;
+    let (scheme, rest) = path[start..].split_once(":/")?;
+    Some(format!("{}://{}", scheme, rest.trim_start_matches('/')))
+}
+
 /// This is the interface we have to implement such that `typst` can compile it.
 ///
 /// I have tried to keep it as minimal as possible
@@ -155,7 +448,7 @@ impl typst::World for TypstWorld {
 
     /// Metadata about all known Books.
     fn book(&self) -> &LazyHash<FontBook> {
-        &self.book
+        &CACHED_FONTS.0
     }
 
     /// Accessing the main source file.
@@ -179,15 +472,18 @@ impl typst::World for TypstWorld {
 
     /// Accessing a specified font per index of font book.
     fn font(&self, id: usize) -> Option<Font> {
-        self.fonts.get(id).cloned()
+        CACHED_FONTS.1.get(id).cloned()
     }
 
     /// Get the current date.
     ///
-    /// Optionally, an offset in hours is given.
+    /// Optionally, an offset in hours is given; otherwise the world's
+    /// time zone is used.
     fn today(&self, offset: Option<i64>) -> Option<Datetime> {
-        let offset = offset.unwrap_or(0);
-        let offset = time::UtcOffset::from_hms(offset.try_into().ok()?, 0, 0).ok()?;
+        let offset = match offset {
+            Some(hours) => time::UtcOffset::from_hms(hours.try_into().ok()?, 0, 0).ok()?,
+            None => self.timezone,
+        };
         let time = self.time.checked_to_offset(offset)?;
         Some(Datetime::Date(time.date()))
     }
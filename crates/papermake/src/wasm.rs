@@ -0,0 +1,77 @@
+//! Browser bindings for client-side template previews
+//!
+//! Build with `cargo rustc -p papermake --lib --crate-type cdylib --target wasm32-unknown-unknown
+//! --no-default-features --features wasm` and generate the bindings with `wasm-bindgen`.
+//! Only Typst's embedded fonts are available, and templates importing
+//! `papermake:` shared templates need those sources passed in explicitly.
+
+use wasm_bindgen::prelude::*;
+
+use crate::render::{RenderError, RenderOptions};
+use crate::schema::Schema;
+use crate::shared::SharedSources;
+use crate::template::Template;
+
+/// Outcome of a browser render: either a PDF or the compile errors
+#[wasm_bindgen]
+pub struct RenderOutput {
+    pdf: Option<Vec<u8>>,
+    errors: Vec<RenderError>,
+}
+
+#[wasm_bindgen]
+impl RenderOutput {
+    /// The rendered PDF, or `undefined` if compilation failed
+    #[wasm_bindgen(getter)]
+    pub fn pdf(&self) -> Option<Vec<u8>> {
+        self.pdf.clone()
+    }
+
+    /// Compile errors as a JSON array of `{ message, start, end }`, with
+    /// byte offsets into the template source
+    #[wasm_bindgen(getter)]
+    pub fn errors(&self) -> String {
+        serde_json::to_string(&self.errors).unwrap_or_else(|_| "[]".to_string())
+    }
+}
+
+/// Render template source with JSON data to a PDF
+///
+/// `schema_json` validates the data when given. `shared_json` maps shared
+/// import paths (e.g. `shared/header.typ`) to their sources. Throws on
+/// invalid input; compile errors are reported through [`RenderOutput`].
+#[wasm_bindgen(js_name = renderPdf)]
+pub fn render_pdf(
+    content: &str,
+    data_json: &str,
+    schema_json: Option<String>,
+    shared_json: Option<String>,
+) -> Result<RenderOutput, JsError> {
+    let data: serde_json::Value = serde_json::from_str(data_json)
+        .map_err(|e| JsError::new(&format!("Invalid data JSON: {}", e)))?;
+    let schema = match schema_json {
+        Some(json) => serde_json::from_str::<Schema>(&json)
+            .map_err(|e| JsError::new(&format!("Invalid schema JSON: {}", e)))?,
+        None => Schema::default(),
+    };
+
+    let mut options = RenderOptions::default();
+    if let Some(json) = shared_json {
+        let sources: std::collections::BTreeMap<String, String> = serde_json::from_str(&json)
+            .map_err(|e| JsError::new(&format!("Invalid shared sources JSON: {}", e)))?;
+        let mut shared = SharedSources::new();
+        for (path, source) in sources {
+            shared.insert(path, source);
+        }
+        options.shared_sources = shared;
+    }
+
+    let template = Template::new("preview", "Preview", content, schema);
+    let result = crate::render::render_pdf(&template, &data, Some(options))
+        .map_err(|e| JsError::new(&e.to_string()))?;
+
+    Ok(RenderOutput {
+        pdf: result.pdf,
+        errors: result.errors,
+    })
+}
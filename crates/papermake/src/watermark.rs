@@ -0,0 +1,187 @@
+//! Watermarks and stamps drawn over rendered pages
+//!
+//! `RenderOptions::watermark` stamps text like `CONFIDENTIAL` or an image
+//! over every page (or only the first) of a rendered document, so templates
+//! don't have to lay out stamps themselves. The stamp is laid out by Typst
+//! as a transparent page of the same size and added as the topmost layer of
+//! each page before export, so it appears in PDF, PNG and SVG output alike.
+//!
+//! Without a watermark in the options, the text of the template's
+//! environment settings (see [`crate::environment`]) is used.
+
+use std::collections::HashMap;
+use std::io::Cursor;
+
+use image::{DynamicImage, ImageFormat};
+use serde::{Deserialize, Serialize};
+use typst::layout::{Frame, PagedDocument, Point, Size};
+use typst::World;
+
+use crate::error::{PapermakeError, Result};
+use crate::typst::TypstWorld;
+
+/// Path the overlay loads an image watermark from
+const IMAGE_PATH: &str = "watermark.png";
+
+/// Typst source of the overlay; reads the stamp's settings from the data
+const OVERLAY: &str = r#"
+#let w = json.decode(sys.inputs.data)
+#set page(width: w.width * 1pt, height: w.height * 1pt, margin: 0pt, fill: none)
+#let stamp = if w.text != none {
+  text(size: w.size * 1pt, weight: "bold", fill: gray.transparentize((1 - w.opacity) * 100%), w.text)
+} else {
+  image("watermark.png", width: w.width * 0.5pt)
+}
+#place(center + horizon, rotate(-w.rotation * 1deg, reflow: true, stamp))
+"#;
+
+/// Which pages get the watermark
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WatermarkPages {
+    #[default]
+    All,
+    First,
+}
+
+/// What the watermark shows
+#[derive(Debug, Clone, PartialEq)]
+pub enum WatermarkContent {
+    /// Bold gray text, sized to the page
+    Text(String),
+    /// A PNG or JPEG image, half as wide as the page
+    Image(Vec<u8>),
+}
+
+/// A stamp drawn over rendered pages
+#[derive(Debug, Clone, PartialEq)]
+pub struct Watermark {
+    pub content: WatermarkContent,
+    /// From 0 (invisible) to 1 (opaque)
+    pub opacity: f32,
+    /// Counter-clockwise rotation in degrees
+    pub rotation: f32,
+    pub pages: WatermarkPages,
+}
+
+impl Watermark {
+    /// A diagonal text stamp on every page
+    pub fn text(text: impl Into<String>) -> Self {
+        Self {
+            content: WatermarkContent::Text(text.into()),
+            opacity: 0.25,
+            rotation: 45.0,
+            pages: WatermarkPages::All,
+        }
+    }
+
+    /// An upright image stamp on every page
+    pub fn image(data: Vec<u8>) -> Self {
+        Self {
+            content: WatermarkContent::Image(data),
+            opacity: 0.25,
+            rotation: 0.0,
+            pages: WatermarkPages::All,
+        }
+    }
+
+    pub fn with_opacity(mut self, opacity: f32) -> Self {
+        self.opacity = opacity;
+        self
+    }
+
+    pub fn with_rotation(mut self, degrees: f32) -> Self {
+        self.rotation = degrees;
+        self
+    }
+
+    /// Stamp only the first page
+    pub fn first_page_only(mut self) -> Self {
+        self.pages = WatermarkPages::First;
+        self
+    }
+
+    /// Draw the watermark over the selected pages of a document
+    pub(crate) fn apply(&self, document: &mut PagedDocument) -> Result<()> {
+        if !(0.0..=1.0).contains(&self.opacity) {
+            return Err(PapermakeError::InvalidInput(format!(
+                "Watermark opacity must be between 0 and 1, got {}",
+                self.opacity
+            )));
+        }
+        let image = match &self.content {
+            WatermarkContent::Image(data) => Some(self.transparent_image(data)?),
+            WatermarkContent::Text(_) => None,
+        };
+
+        // Pages of the same size share an overlay
+        let mut overlays: HashMap<(u64, u64), Frame> = HashMap::new();
+        let count = match self.pages {
+            WatermarkPages::All => document.pages.len(),
+            WatermarkPages::First => 1,
+        };
+        for page in document.pages.iter_mut().take(count) {
+            let size = page.frame.size();
+            let key = (size.x.to_pt().to_bits(), size.y.to_pt().to_bits());
+            let overlay = match overlays.get(&key) {
+                Some(overlay) => overlay.clone(),
+                None => {
+                    let overlay = self.overlay(size, image.as_deref())?;
+                    overlays.insert(key, overlay.clone());
+                    overlay
+                }
+            };
+            page.frame.push_frame(Point::zero(), overlay);
+        }
+        Ok(())
+    }
+
+    /// Lay out the stamp on a transparent page of the given size
+    fn overlay(&self, size: Size, image: Option<&[u8]>) -> Result<Frame> {
+        let (width, height) = (size.x.to_pt(), size.y.to_pt());
+        let text = match &self.content {
+            WatermarkContent::Text(text) => Some(text.as_str()),
+            WatermarkContent::Image(_) => None,
+        };
+        let settings = serde_json::json!({
+            "text": text,
+            "size": width.min(height) / 8.0,
+            "opacity": self.opacity,
+            "rotation": self.rotation,
+            "width": width,
+            "height": height,
+        });
+
+        let mut world = TypstWorld::new(OVERLAY.to_string(), settings.to_string());
+        if let Some(image) = image {
+            world.add_file(IMAGE_PATH, image.to_vec());
+        }
+        let compiled = typst::compile::<PagedDocument>(&world as &dyn World);
+        let document = compiled.output.map_err(|errors| {
+            let messages: Vec<_> = errors.iter().map(|error| error.message.as_str()).collect();
+            PapermakeError::Rendering(format!("Failed to draw watermark: {}", messages.join("; ")))
+        })?;
+        document
+            .pages
+            .into_iter()
+            .next()
+            .map(|page| page.frame)
+            .ok_or_else(|| PapermakeError::Rendering("Failed to draw watermark: no page".to_string()))
+    }
+
+    /// The image as a PNG with its alpha scaled by the opacity; Typst has no
+    /// opacity setting for images
+    fn transparent_image(&self, data: &[u8]) -> Result<Vec<u8>> {
+        let image = image::load_from_memory(data)
+            .map_err(|e| PapermakeError::InvalidInput(format!("Invalid watermark image: {}", e)))?;
+        let mut rgba = image.to_rgba8();
+        for pixel in rgba.pixels_mut() {
+            pixel.0[3] = (f32::from(pixel.0[3]) * self.opacity).round() as u8;
+        }
+        let mut png = Vec::new();
+        DynamicImage::ImageRgba8(rgba)
+            .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+            .map_err(|e| PapermakeError::Rendering(format!("Failed to encode watermark image: {}", e)))?;
+        Ok(png)
+    }
+}
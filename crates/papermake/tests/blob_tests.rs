@@ -0,0 +1,103 @@
+use std::sync::Arc;
+
+use papermake::blobs::blob_hash;
+use papermake::storage::{FileStorage, Namespace, Storage};
+use papermake::{BlobStore, DedupStorage, FileBlobStore, MemoryBlobStore, Schema, Template, TemplateId};
+use tempfile::tempdir;
+use time::{Duration, OffsetDateTime};
+
+fn template(id: &str) -> Template {
+    Template::new(id, "Letter", "Dear #sys.inputs.data", Schema::new())
+}
+
+#[tokio::test]
+async fn test_dedup_storage_shares_and_collects_blobs() {
+    let temp_dir = tempdir().unwrap();
+    let inner = Arc::new(FileStorage::new(temp_dir.path().join("storage")));
+    let blobs: Arc<dyn BlobStore> = Arc::new(FileBlobStore::new(temp_dir.path().join("blobs")));
+    let storage = DedupStorage::new(inner.clone(), blobs.clone());
+    let (letter, invoice) = (TemplateId::from("letter"), TemplateId::from("invoice"));
+    let logo = blob_hash(b"logo");
+
+    storage.save_template(&template("letter")).await.unwrap();
+    storage.save_template(&template("invoice")).await.unwrap();
+    storage.save_template_file(&letter, "logo.png", b"logo").await.unwrap();
+    storage.save_template_file(&invoice, "images/logo.png", b"logo").await.unwrap();
+    assert_eq!(blobs.ref_count(&logo).await.unwrap(), 2);
+    assert_eq!(storage.get_template_file(&invoice, "images/logo.png").await.unwrap(), b"logo");
+    // The template itself only holds a pointer
+    assert!(inner.get_template_file(&letter, "logo.png").await.unwrap().starts_with(b"papermake-blob:sha256:"));
+
+    // Saving again doesn't count twice; copies and tenants share the blob
+    storage.save_template_file(&letter, "logo.png", b"logo").await.unwrap();
+    storage.copy_template(&letter, &TemplateId::from("letter-copy")).await.unwrap();
+    let tenant = storage.for_namespace(&Namespace::new("acme").unwrap());
+    tenant.save_template(&template("letter")).await.unwrap();
+    tenant.save_template_file(&letter, "logo.png", b"logo").await.unwrap();
+    assert_eq!(blobs.ref_count(&logo).await.unwrap(), 4);
+
+    storage.save_template_file(&letter, "logo.png", b"new logo").await.unwrap();
+    storage.rename_template_file(&invoice, "images/logo.png", "logo.png").await.unwrap();
+    storage.delete_template(&TemplateId::from("letter-copy")).await.unwrap();
+    tenant.delete_template_file(&letter, "logo.png").await.unwrap();
+    assert_eq!(blobs.ref_count(&logo).await.unwrap(), 1);
+    assert_eq!(blobs.ref_count(&blob_hash(b"new logo")).await.unwrap(), 1);
+
+    // Recently stored blobs survive collection even without references
+    storage.delete_template_file(&invoice, "logo.png").await.unwrap();
+    let report = blobs.collect_garbage(OffsetDateTime::now_utc() - Duration::hours(1)).await.unwrap();
+    assert_eq!((report.kept, report.deleted), (2, 0));
+    let report = blobs.collect_garbage(OffsetDateTime::now_utc() + Duration::minutes(1)).await.unwrap();
+    assert_eq!((report.kept, report.deleted, report.freed_bytes), (1, 1, 4));
+    assert!(blobs.get(&logo).await.is_err());
+    assert_eq!(storage.get_template_file(&letter, "logo.png").await.unwrap(), b"new logo");
+}
+
+#[tokio::test]
+async fn test_dedup_storage_reads_files_stored_before() {
+    let temp_dir = tempdir().unwrap();
+    let inner = Arc::new(FileStorage::new(temp_dir.path()));
+    let id = TemplateId::from("letter");
+    inner.save_template(&template("letter")).await.unwrap();
+    inner.save_template_file(&id, "footer.typ", b"Footer").await.unwrap();
+
+    let blobs = Arc::new(MemoryBlobStore::new());
+    let storage = DedupStorage::new(inner, blobs.clone());
+    assert_eq!(storage.get_template_file(&id, "footer.typ").await.unwrap(), b"Footer");
+    storage.delete_template(&id).await.unwrap();
+
+    assert!(blobs.get("not-a-hash").await.is_err());
+    let report = blobs.collect_garbage(OffsetDateTime::now_utc()).await.unwrap();
+    assert_eq!((report.kept, report.deleted), (0, 0));
+}
+
+#[tokio::test]
+async fn test_dedup_storage_ignores_forged_pointers() {
+    let temp_dir = tempdir().unwrap();
+    let inner = Arc::new(FileStorage::new(temp_dir.path()));
+    let blobs = Arc::new(MemoryBlobStore::new());
+    let storage = DedupStorage::new(inner.clone(), blobs.clone());
+    let (acme, globex) = (Namespace::new("acme").unwrap(), Namespace::new("globex").unwrap());
+    let id = TemplateId::from("letter");
+
+    let victim = storage.for_namespace(&globex);
+    victim.save_template(&template("letter")).await.unwrap();
+    victim.save_template_file(&id, "contract.pdf", b"secret terms").await.unwrap();
+
+    // A file stored before deduplication, pointing at the other tenant's blob
+    let forged = format!("papermake-blob:sha256:{}", blob_hash(b"secret terms"));
+    let attacker = inner.for_namespace(&acme);
+    attacker.save_template(&template("letter")).await.unwrap();
+    attacker.save_template_file(&id, "steal.pdf", forged.as_bytes()).await.unwrap();
+    let attacker = storage.for_namespace(&acme);
+    assert_eq!(attacker.get_template_file(&id, "steal.pdf").await.unwrap(), forged.as_bytes());
+
+    // Uploading pointer-like content stores it like any other content
+    attacker.save_template_file(&id, "upload.pdf", forged.as_bytes()).await.unwrap();
+    assert_eq!(attacker.get_template_file(&id, "upload.pdf").await.unwrap(), forged.as_bytes());
+
+    // Deleting the forged file doesn't drop the victim's reference
+    attacker.delete_template_file(&id, "steal.pdf").await.unwrap();
+    assert_eq!(blobs.ref_count(&blob_hash(b"secret terms")).await.unwrap(), 1);
+    assert_eq!(victim.get_template_file(&id, "contract.pdf").await.unwrap(), b"secret terms");
+}
@@ -1,4 +1,4 @@
-use papermake::{schema, Template, TemplateCache};
+use papermake::{render_pdf, schema, Schema, Template, TemplateCache, WorldPool};
 use serde_json::json;
 
 #[test]
@@ -122,4 +122,117 @@ fn test_cached_template_clone() {
     // Render with cloned template
     let _result2 = cached_template2.render(&data).unwrap();
     assert!(cached_template2.is_cached());
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_world_pool_reuses_worlds() {
+    let template = Template::builder("pooled")
+        .name("Pooled Template")
+        .content("#let data = json.decode(sys.inputs.data)\nHello #data.name!")
+        .schema(schema! { name: String })
+        .build()
+        .unwrap();
+
+    let pool = WorldPool::new();
+    assert_eq!(pool.idle_count(&template), 0);
+
+    let result = pool.render(&template, &json!({ "name": "John Doe" }), None).unwrap();
+    assert!(result.pdf.is_some());
+    assert_eq!(pool.idle_count(&template), 1);
+
+    // A second render checks the same world out again
+    let result = pool.render(&template, &json!({ "name": "Jane Doe" }), None).unwrap();
+    assert!(result.pdf.is_some());
+    assert_eq!(pool.idle_count(&template), 1);
+
+    pool.evict(&template.id).unwrap();
+    assert_eq!(pool.idle_count(&template), 0);
+}
+
+#[test]
+fn test_world_pool_separates_templates_sharing_id_and_version() {
+    let acme = Template::new("invoice", "Invoice", "Acme invoice", Schema::new());
+    let mut globex = acme.clone();
+    globex.content = "Globex invoice".to_string();
+
+    let pool = WorldPool::new();
+    pool.render(&acme, &json!({}), None).unwrap();
+    assert_eq!(pool.idle_count(&acme), 1);
+    assert_eq!(pool.idle_count(&globex), 0);
+
+    // Neither tenant gets the other's world, nor evicts it
+    let result = pool.render(&globex, &json!({}), None).unwrap();
+    assert!(result.pdf.is_some());
+    assert_eq!((pool.idle_count(&acme), pool.idle_count(&globex)), (1, 1));
+}
+
+#[test]
+fn test_world_pool_namespaces_prune_and_evict_their_own_worlds() {
+    use papermake::storage::Namespace;
+
+    let template = Template::new("invoice", "Invoice", "Invoice", Schema::new());
+    let mut edited = template.clone();
+    edited.updated_at += time::Duration::seconds(1);
+
+    let pool = WorldPool::new();
+    let acme = pool.for_namespace(&Namespace::new("acme").unwrap());
+    let globex = pool.for_namespace(&Namespace::new("globex").unwrap());
+    acme.render(&template, &json!({}), None).unwrap();
+    globex.render(&template, &json!({}), None).unwrap();
+    assert_eq!((acme.idle_count(&template), globex.idle_count(&template), pool.idle_count(&template)), (1, 1, 0));
+
+    // Rendering a newer revision keeps the old worlds until the template is saved
+    globex.render(&edited, &json!({}), None).unwrap();
+    assert_eq!(globex.idle_count(&template), 1);
+    globex.prune(&edited).unwrap();
+    assert_eq!((acme.idle_count(&template), globex.idle_count(&template)), (1, 0));
+    assert_eq!(globex.idle_count(&edited), 1);
+
+    globex.evict(&edited.id).unwrap();
+    assert_eq!((acme.idle_count(&template), globex.idle_count(&edited)), (1, 0));
+}
+
+#[test]
+fn test_render_cache_hits_for_identical_renders() {
+    use papermake::{CachePolicy, MemoryRenderCache, RenderCache, RenderOptions};
+    use std::sync::Arc;
+
+    let template = Template::new(
+        "cached",
+        "Cached",
+        "#let data = json.decode(sys.inputs.data)\nHello #data.name!",
+        Schema::new(),
+    );
+    let cache = Arc::new(MemoryRenderCache::new(2));
+    let options = |policy| RenderOptions {
+        render_cache: Some(cache.clone() as Arc<dyn RenderCache>),
+        cache_policy: policy,
+        ..Default::default()
+    };
+    let data = json!({ "name": "World" });
+
+    let first = render_pdf(&template, &data, Some(options(CachePolicy::Use))).unwrap();
+    assert!(!first.cached);
+    let second = render_pdf(&template, &data, Some(options(CachePolicy::Use))).unwrap();
+    assert!(second.cached);
+    assert_eq!(first.pdf, second.pdf);
+
+    // no-cache renders again, no-store leaves the cache untouched
+    assert!(!render_pdf(&template, &data, Some(options(CachePolicy::Refresh))).unwrap().cached);
+    let other = json!({ "name": "Other" });
+    assert!(!render_pdf(&template, &other, Some(options(CachePolicy::Bypass))).unwrap().cached);
+    assert_eq!(cache.len(), 1);
+
+    // Different data is a different cache entry
+    assert!(!render_pdf(&template, &other, Some(options(CachePolicy::Use))).unwrap().cached);
+    assert_eq!(cache.len(), 2);
+}
+
+#[test]
+fn test_cache_policy_from_cache_control() {
+    use papermake::CachePolicy;
+
+    assert_eq!(CachePolicy::from_cache_control("max-age=0"), CachePolicy::Use);
+    assert_eq!(CachePolicy::from_cache_control("No-Cache"), CachePolicy::Refresh);
+    assert_eq!(CachePolicy::from_cache_control("no-cache, no-store"), CachePolicy::Bypass);
+}
@@ -0,0 +1,64 @@
+#![cfg(feature = "charts")]
+
+use papermake::{render_pdf, ChartData, ChartKind, ChartSpec, PapermakeError, RenderOptions, Schema, Template};
+use serde_json::json;
+
+fn report() -> Template {
+    Template::new("report", "Report", "#image(\"chart:revenue.svg\", width: 100%)", Schema::new())
+}
+
+#[test]
+fn test_charts_from_data() {
+    let data = json!({
+        "months": [
+            { "name": "Jan", "revenue": 120.0, "costs": 80.0 },
+            { "name": "Feb", "revenue": 150.5, "costs": 95.0 },
+            { "name": "Mar", "revenue": 90.0, "costs": 70.0 },
+        ]
+    });
+
+    for kind in [ChartKind::Bar, ChartKind::Line, ChartKind::Pie] {
+        let chart = ChartSpec::new(kind, ChartData::Path("/months/*/name".to_string()))
+            .title("Revenue")
+            .series("Revenue", ChartData::Path("/months/*/revenue".to_string()))
+            .series("Costs", ChartData::Path("/months/*/costs".to_string()));
+        let svg = chart.render_svg(&data).unwrap();
+        assert!(svg.starts_with("<svg"), "not an SVG: {}", &svg[..svg.len().min(100)]);
+
+        let options = RenderOptions {
+            charts: [("revenue".to_string(), chart)].into_iter().collect(),
+            ..Default::default()
+        };
+        let result = render_pdf(&report(), &data, Some(options)).unwrap();
+        assert!(result.pdf.is_some(), "{:?} chart failed: {:?}", kind, result.errors);
+    }
+}
+
+#[test]
+fn test_chart_errors() {
+    // Without a chart of that name, the image can't be loaded
+    let result = render_pdf(&report(), &json!({}), None).unwrap();
+    assert!(result.pdf.is_none());
+
+    let mismatched = ChartSpec::new(ChartKind::Bar, ChartData::Values(vec!["a".into(), "b".into()]))
+        .series("values", ChartData::Values(vec![1.0]));
+    let options = RenderOptions {
+        charts: [("revenue".to_string(), mismatched)].into_iter().collect(),
+        ..Default::default()
+    };
+    let err = render_pdf(&report(), &json!({}), Some(options)).unwrap_err();
+    assert!(matches!(err, PapermakeError::InvalidInput(ref msg) if msg.contains("Chart 'revenue'")), "unexpected error: {}", err);
+}
+
+#[test]
+fn test_chart_spec_json() {
+    let spec: ChartSpec = serde_json::from_value(json!({
+        "kind": "bar",
+        "labels": ["Q1", "Q2"],
+        "series": [{ "name": "Sales", "values": "/sales" }]
+    }))
+    .unwrap();
+    assert_eq!(spec.labels, ChartData::Values(vec!["Q1".to_string(), "Q2".to_string()]));
+    assert_eq!(spec.series[0].values, ChartData::Path("/sales".to_string()));
+    assert_eq!((spec.width, spec.height), (640, 400));
+}
@@ -0,0 +1,91 @@
+#![cfg(feature = "derive")]
+
+use papermake::{FieldType, PapermakeData, Template};
+use serde::Serialize;
+
+#[derive(Serialize, PapermakeData)]
+struct LineItem {
+    description: String,
+    quantity: u32,
+    price: f64,
+}
+
+#[derive(Serialize, PapermakeData)]
+#[serde(rename_all = "camelCase")]
+struct Invoice {
+    /// Name of the billed customer
+    customer_name: String,
+    #[papermake(label = "Paid")]
+    is_paid: bool,
+    #[papermake(date)]
+    due_date: String,
+    notes: Option<String>,
+    items: Vec<LineItem>,
+    #[serde(skip)]
+    #[allow(dead_code)]
+    internal_id: u64,
+}
+
+#[test]
+fn test_derived_schema() {
+    let schema = Invoice::schema();
+    let keys: Vec<_> = schema.fields.iter().map(|f| f.key.as_str()).collect();
+    assert_eq!(keys, vec!["customerName", "isPaid", "dueDate", "notes", "items"]);
+
+    assert_eq!(schema.fields[0].description.as_deref(), Some("Name of the billed customer"));
+    assert_eq!(schema.fields[1].label.as_deref(), Some("Paid"));
+    assert_eq!(schema.fields[1].field_type, FieldType::Boolean);
+    assert_eq!(schema.fields[2].field_type, FieldType::Date);
+    assert!(!schema.fields[3].required);
+    assert_eq!(
+        schema.fields[4].field_type,
+        FieldType::Array(Box::new(FieldType::Object(Box::new(LineItem::schema()))))
+    );
+}
+
+#[test]
+fn test_render_typed() {
+    let template = Template::builder("invoice")
+        .name("Invoice")
+        .content("#let data = json.decode(sys.inputs.data)\nInvoice for #data.customerName")
+        .schema_for::<Invoice>()
+        .build()
+        .unwrap();
+
+    let invoice = Invoice {
+        customer_name: "ACME".to_string(),
+        is_paid: false,
+        due_date: "2025-01-31".to_string(),
+        notes: None,
+        items: vec![LineItem { description: "Widget".to_string(), quantity: 2, price: 9.5 }],
+        internal_id: 7,
+    };
+
+    let result = template.render_typed(&invoice).unwrap();
+    assert!(result.pdf.is_some());
+}
+
+#[derive(Serialize, PapermakeData)]
+struct Order {
+    #[papermake(options = ["EUR", "USD"], order = 1)]
+    currency: String,
+    #[papermake(widget = "textarea", placeholder = "Delivery notes", group = "Shipping")]
+    notes: String,
+    #[papermake(widget = "date_picker", order = -1)]
+    delivery: String,
+}
+
+#[test]
+fn test_derived_form_hints() {
+    use papermake::Widget;
+
+    let schema = Order::schema();
+    let select = Widget::Select { options: vec!["EUR".to_string(), "USD".to_string()] };
+    assert_eq!(schema.fields[0].widget, Some(select));
+    assert_eq!(schema.fields[0].order, Some(1));
+    assert_eq!(schema.fields[1].widget, Some(Widget::Textarea));
+    assert_eq!(schema.fields[1].placeholder.as_deref(), Some("Delivery notes"));
+    assert_eq!(schema.fields[1].group.as_deref(), Some("Shipping"));
+    assert_eq!(schema.fields[2].widget, Some(Widget::DatePicker));
+    assert_eq!(schema.fields[2].order, Some(-1));
+}
@@ -0,0 +1,35 @@
+use papermake::diff::{diff_pdfs, diff_templates, PageStatus};
+use papermake::{render_pdf, DiffOptions, RenderOptions, Schema, Template};
+use serde_json::json;
+
+#[test]
+fn test_diff_templates_reports_changed_and_added_pages() {
+    let before = Template::new("report", "Report", "= Summary\nFirst page", Schema::new());
+    let after = Template::new("report", "Report", "= Summary\nFirst page, revised\n#pagebreak()\nAppendix", Schema::new());
+
+    let report = diff_templates(&before, &before, &json!({}), &RenderOptions::default(), &DiffOptions::default()).unwrap();
+    assert!(report.is_identical());
+
+    let report = diff_templates(&before, &after, &json!({}), &RenderOptions::default(), &DiffOptions::default()).unwrap();
+    assert_eq!(report.pages.len(), 2);
+    assert_eq!(report.pages[0].status, PageStatus::Changed);
+    assert!(report.pages[0].changed_pixels > 0);
+    assert!(report.pages[0].bounds.is_some());
+    assert_eq!(report.pages[1].status, PageStatus::Added);
+}
+
+#[test]
+fn test_diff_pdfs_against_baseline() {
+    let options = || Some(RenderOptions { deterministic: true, ..Default::default() });
+    let template = Template::new("letter", "Letter", "Dear customer", Schema::new());
+    let baseline = render_pdf(&template, &json!({}), options()).unwrap().pdf.unwrap();
+    let same = render_pdf(&template, &json!({}), options()).unwrap().pdf.unwrap();
+    assert!(diff_pdfs(&baseline, &same).unwrap().is_identical());
+
+    let changed = Template::new("letter", "Letter", "Dear valued customer", Schema::new());
+    let changed = render_pdf(&changed, &json!({}), options()).unwrap().pdf.unwrap();
+    let report = diff_pdfs(&baseline, &changed).unwrap();
+    assert_eq!(report.changed_pages().count(), 1);
+
+    assert!(diff_pdfs(b"not a pdf", &baseline).is_err());
+}
@@ -0,0 +1,73 @@
+#![cfg(feature = "encrypted-storage")]
+
+use std::sync::Arc;
+
+use papermake::render_cache::RenderCacheKey;
+use papermake::storage::{FileStorage, Namespace, Storage};
+use papermake::{
+    EncryptedRenderCache, EncryptedStorage, EncryptionKey, MemoryRenderCache, RenderCache, RenderOptions, Schema,
+    Template, TemplateId,
+};
+use serde_json::json;
+use tempfile::tempdir;
+
+#[tokio::test]
+async fn test_encrypted_storage_roundtrip() {
+    let temp_dir = tempdir().unwrap();
+    let key = EncryptionKey::from_bytes([7; 32]);
+    let storage = EncryptedStorage::new(Arc::new(FileStorage::new(temp_dir.path())), key.clone());
+    let id = TemplateId::from("letter");
+
+    let template = Template::new("letter", "Letter", "Dear #data.name", Schema::new())
+        .with_example("default", json!({"name": "Ada Lovelace"}));
+    storage.save_template(&template).await.unwrap();
+    storage.save_template_file(&id, "signature.png", b"secret signature").await.unwrap();
+
+    // Nothing confidential reaches the disk in the clear
+    let raw = std::fs::read_to_string(temp_dir.path().join("templates/letter/template.json")).unwrap();
+    assert!(raw.contains("Letter"));
+    assert!(!raw.contains("Dear") && !raw.contains("Ada Lovelace"));
+    let raw_file = std::fs::read(temp_dir.path().join("templates/letter/files/signature.png")).unwrap();
+    assert_ne!(raw_file, b"secret signature");
+
+    let loaded = storage.get_template(&id).await.unwrap();
+    assert_eq!(loaded.content, "Dear #data.name");
+    assert_eq!(loaded.examples["default"], json!({"name": "Ada Lovelace"}));
+    assert_eq!(storage.get_template_file(&id, "signature.png").await.unwrap(), b"secret signature");
+    assert_eq!(storage.search_templates("dear").await.unwrap().len(), 1);
+
+    // Another key can't read the data
+    let other = EncryptedStorage::new(Arc::new(FileStorage::new(temp_dir.path())), EncryptionKey::from_bytes([8; 32]));
+    assert!(other.get_template(&id).await.is_err());
+
+    // Data stored before encryption was enabled stays readable
+    let plain = FileStorage::new(temp_dir.path());
+    plain.save_template(&Template::new("old", "Old", "Plain", Schema::new())).await.unwrap();
+    assert_eq!(storage.get_template(&"old".into()).await.unwrap().content, "Plain");
+
+    // Namespaces are encrypted too
+    let tenant = storage.for_namespace(&Namespace::new("acme").unwrap());
+    tenant.save_template(&Template::new("memo", "Memo", "Confidential", Schema::new())).await.unwrap();
+    let raw = std::fs::read_to_string(temp_dir.path().join("tenants/acme/templates/memo/template.json")).unwrap();
+    assert!(!raw.contains("Confidential"));
+    assert_eq!(tenant.get_template(&"memo".into()).await.unwrap().content, "Confidential");
+}
+
+#[test]
+fn test_encrypted_render_cache() {
+    let inner = Arc::new(MemoryRenderCache::new(8));
+    let cache = EncryptedRenderCache::new(inner.clone(), EncryptionKey::from_bytes([7; 32]));
+    let template = Template::new("letter", "Letter", "Hello", Schema::new());
+    let key = RenderCacheKey::new(&template, &json!({}), &RenderOptions::default());
+
+    cache.put(&key, b"%PDF-1.7");
+    assert_eq!(cache.get(&key).unwrap(), b"%PDF-1.7");
+    assert_ne!(inner.get(&key).unwrap(), b"%PDF-1.7");
+}
+
+#[test]
+fn test_encryption_key_from_base64() {
+    assert!(EncryptionKey::from_base64("AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=").is_ok());
+    assert!(EncryptionKey::from_base64("dG9vIHNob3J0").is_err());
+    assert!(EncryptionKey::from_base64("not base64!").is_err());
+}
@@ -0,0 +1,89 @@
+#![cfg(feature = "test-util")]
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use papermake::faulty::Operation;
+use papermake::storage::{FileStorage, ListOptions, Namespace, Storage};
+use papermake::{FaultyStorage, Schema, Template, TemplateId};
+use tempfile::tempdir;
+
+fn template(id: &str) -> Template {
+    Template::new(id, "Letter", "Dear #sys.inputs.data", Schema::new())
+}
+
+#[tokio::test]
+async fn test_transient_failures_recover() {
+    let temp_dir = tempdir().unwrap();
+    let storage = FaultyStorage::new(Arc::new(FileStorage::new(temp_dir.path()))).only(&[Operation::SaveTemplate]);
+
+    storage.fail_next(2);
+    assert!(storage.save_template(&template("letter")).await.is_err());
+    let err = storage.save_template(&template("letter")).await.unwrap_err();
+    assert!(err.to_string().contains("Injected fault"), "{}", err);
+    storage.save_template(&template("letter")).await.unwrap();
+
+    // Reads aren't targeted
+    storage.fail_next(1);
+    storage.get_template(&TemplateId::from("letter")).await.unwrap();
+    assert!(storage.save_template(&storage.get_template(&TemplateId::from("letter")).await.unwrap()).await.is_err());
+
+    assert_eq!(storage.injected(), 3);
+    assert_eq!(storage.calls(), 6);
+}
+
+#[tokio::test]
+async fn test_fault_patterns() {
+    let temp_dir = tempdir().unwrap();
+    let inner = Arc::new(FileStorage::new(temp_dir.path()));
+    inner.save_template(&template("letter")).await.unwrap();
+    let id = TemplateId::from("letter");
+
+    let every_third = FaultyStorage::new(inner.clone()).fail_every(3);
+    let mut outcomes = Vec::new();
+    for _ in 0..6 {
+        outcomes.push(every_third.get_template(&id).await.is_ok());
+    }
+    assert_eq!(outcomes, [true, true, false, true, true, false]);
+
+    // Seeded failures repeat exactly
+    let run = |seed| {
+        let storage = FaultyStorage::new(inner.clone()).failure_rate(0.5, seed);
+        let id = id.clone();
+        async move {
+            let mut outcomes = Vec::new();
+            for _ in 0..32 {
+                outcomes.push(storage.get_template(&id).await.is_ok());
+            }
+            outcomes
+        }
+    };
+    let first = run(42).await;
+    assert_eq!(first, run(42).await);
+    assert!(first.contains(&true) && first.contains(&false));
+
+    let healed = FaultyStorage::new(inner.clone()).fail_every(1);
+    healed.heal();
+    healed.get_template(&id).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_partial_writes_and_latency() {
+    let temp_dir = tempdir().unwrap();
+    let inner = Arc::new(FileStorage::new(temp_dir.path()));
+    let storage = FaultyStorage::new(inner.clone()).partial_writes().latency(Duration::from_millis(20));
+
+    // The write lands even though it's reported as failed
+    storage.fail_next(1);
+    let started = std::time::Instant::now();
+    assert!(storage.save_template(&template("letter")).await.is_err());
+    assert!(started.elapsed() >= Duration::from_millis(20));
+    inner.get_template(&TemplateId::from("letter")).await.unwrap();
+
+    // Namespaces share the faults
+    let tenant = storage.for_namespace(&Namespace::new("acme").unwrap());
+    storage.fail_next(1);
+    let err = tenant.list_templates(&ListOptions::new()).await.unwrap_err();
+    assert!(err.to_string().contains("Injected fault"), "{}", err);
+    assert_eq!(storage.injected(), 2);
+}
@@ -0,0 +1,83 @@
+use std::time::Duration;
+
+use papermake::history::data_hash;
+use papermake::storage::Namespace;
+use papermake::{FileRenderHistory, FileRenderStats, RenderHistory, RenderRecord, RenderResult, RenderStats, Schema, Template};
+use serde_json::json;
+use tempfile::tempdir;
+
+fn success() -> papermake::Result<RenderResult> {
+    Ok(RenderResult { pdf: Some(b"%PDF".to_vec()), errors: Vec::new(), warnings: Vec::new(), cached: false, optimization: None, metadata: None, text: None })
+}
+
+#[tokio::test]
+async fn test_file_render_history_roundtrip() {
+    let temp_dir = tempdir().unwrap();
+    let history = FileRenderHistory::new(temp_dir.path());
+    let template = Template::new("invoice", "Invoice", "Hello", Schema::new());
+    let data = json!({ "total": 42 });
+
+    let first = RenderRecord::new("r1", &template, &data)
+        .with_api_key_id(Some("key-1".to_string()))
+        .finish(Duration::from_millis(12), &success());
+    history.record(&first, Some(&data)).await.unwrap();
+
+    let mut second = RenderRecord::new("r2", &template, &json!({}))
+        .finish(Duration::from_millis(3), &Err(papermake::PapermakeError::Rendering("boom".to_string())));
+    second.created_at += time::Duration::seconds(1);
+    history.record(&second, None).await.unwrap();
+
+    let loaded = history.get_record("r1").await.unwrap();
+    assert!(loaded.success);
+    assert!(loaded.has_inputs);
+    assert_eq!(loaded.duration_ms, 12);
+    assert_eq!(loaded.data_hash, data_hash(&data));
+    assert_eq!(loaded.api_key_id.as_deref(), Some("key-1"));
+    assert_eq!(history.get_inputs("r1").await.unwrap(), data);
+    assert!(history.get_inputs("r2").await.is_err());
+
+    // Most recent first
+    let records = history.list_records(&template.id, 10).await.unwrap();
+    let ids: Vec<&str> = records.iter().map(|r| r.id.as_str()).collect();
+    assert_eq!(ids, ["r2", "r1"]);
+    assert_eq!(records[0].errors, ["Rendering error: boom"]);
+
+    let tenant = history.for_namespace(&Namespace::new("acme").unwrap());
+    assert!(tenant.get_record("r1").await.is_err());
+    assert!(history.get_record("../r1").await.is_err());
+}
+
+#[tokio::test]
+async fn test_file_render_stats() {
+    let temp_dir = tempdir().unwrap();
+    let stats = FileRenderStats::new(temp_dir.path());
+    let invoice = Template::new("invoice", "Invoice", "Hello", Schema::new());
+    let letter = Template::new("letter", "Letter", "Hello", Schema::new());
+
+    for ms in [10, 20, 30, 40] {
+        let record = RenderRecord::new("r", &invoice, &json!({})).finish(Duration::from_millis(ms), &success());
+        stats.record(&record).await.unwrap();
+    }
+    let failed = RenderRecord::new("r", &letter, &json!({}))
+        .finish(Duration::from_millis(500), &Err(papermake::PapermakeError::Rendering("boom".to_string())));
+    stats.record(&failed).await.unwrap();
+
+    let invoice_stats = stats.get_stats(&invoice.id).await.unwrap();
+    assert_eq!(invoice_stats.renders, 4);
+    assert_eq!(invoice_stats.failure_rate, 0.0);
+    assert_eq!(invoice_stats.p50_ms, 20);
+    assert_eq!(invoice_stats.p95_ms, 40);
+    assert_eq!(invoice_stats.avg_output_bytes, 4);
+
+    let letter_stats = stats.get_stats(&letter.id).await.unwrap();
+    assert_eq!(letter_stats.failure_rate, 1.0);
+    assert_eq!(letter_stats.avg_output_bytes, 0);
+
+    let slowest = stats.slowest(10).await.unwrap();
+    let ids: Vec<&str> = slowest.iter().map(|s| s.template_id.as_ref()).collect();
+    assert_eq!(ids, ["letter", "invoice"]);
+
+    let tenant = stats.for_namespace(&Namespace::new("acme").unwrap());
+    assert_eq!(tenant.get_stats(&invoice.id).await.unwrap().renders, 0);
+    assert!(tenant.slowest(10).await.unwrap().is_empty());
+}
@@ -72,3 +72,650 @@ fn test_render_pdf() {
     
     assert!(found_arial, "PDF should contain Arial font");
 }
+
+#[tokio::test]
+async fn test_render_pdf_async() {
+    let template = Template::new(
+        "test",
+        "Test Template",
+        "#let data = json.decode(sys.inputs.data)\nHello #data.name!",
+        Schema::new()
+    );
+
+    let data = json!({
+        "name": "World"
+    });
+
+    let result = papermake::render_pdf_async(&template, &data, None).await.unwrap();
+    assert!(result.pdf.is_some());
+    assert!(result.errors.is_empty());
+}
+
+#[test]
+fn test_render_merged() {
+    let template = Template::new(
+        "statement",
+        "Statement",
+        "#let data = json.decode(sys.inputs.data)\nStatement for #data.name",
+        Schema::new()
+    );
+
+    let records = vec![
+        json!({ "name": "Alice" }),
+        json!({ "name": "Bob" }),
+        json!({ "name": "Carol" }),
+    ];
+
+    let options = papermake::RenderOptions {
+        bookmark_field: Some("name".to_string()),
+        ..Default::default()
+    };
+    let result = papermake::render_merged(&template, &records, options).unwrap();
+    assert!(result.errors.is_empty());
+
+    let pdf = result.pdf.unwrap();
+    let file = pdf::file::FileOptions::cached().load(pdf).unwrap();
+    assert_eq!(file.num_pages(), 3);
+}
+
+#[test]
+fn test_render_encrypted_pdf() {
+    let template = Template::new(
+        "payslip",
+        "Payslip",
+        "#let data = json.decode(sys.inputs.data)\nSalary for #data.name",
+        Schema::new()
+    );
+
+    let options = papermake::RenderOptions {
+        encryption: Some(papermake::PdfEncryption::new("owner").user_password("secret").no_print().no_copy()),
+        ..Default::default()
+    };
+    let result = render_pdf(&template, &json!({ "name": "Alice" }), Some(options)).unwrap();
+    let pdf = result.pdf.unwrap();
+
+    assert!(pdf.starts_with(b"%PDF"));
+    let doc = lopdf::Document::load_mem(&pdf).unwrap();
+    let encrypt = doc.get_encrypted().expect("PDF should have an encryption dictionary");
+    assert_eq!(encrypt.get(b"Filter").unwrap().as_name().unwrap(), b"Standard");
+    assert_eq!(encrypt.get(b"V").unwrap().as_i64().unwrap(), 5);
+    assert!(doc.authenticate_password("secret").is_ok());
+    assert!(doc.authenticate_password("wrong").is_err());
+}
+
+#[test]
+fn test_data_transforms() {
+    use papermake::{FormatDate, FormatNumber, RenderOptions, TransformPipeline};
+
+    let schema = Schema::builder()
+        .field("date", papermake::FieldType::Date)
+        .field("total", papermake::FieldType::Number)
+        .build();
+    let template = Template::new("invoice", "Invoice", "#let data = json.decode(sys.inputs.data)\n#data.total_formatted", schema);
+
+    let options = RenderOptions {
+        transforms: TransformPipeline::new()
+            .then(|data: &mut serde_json::Value| {
+                let total: f64 = data["items"].as_array().into_iter().flatten()
+                    .filter_map(|item| item["price"].as_f64())
+                    .sum();
+                data["total"] = json!(total);
+                Ok(())
+            })
+            .then(FormatNumber::new("items.*.price", "de-DE"))
+            .then(FormatNumber::new("total", "de-DE").currency("EUR"))
+            .then(FormatDate::new("date", "de-DE")),
+        ..Default::default()
+    };
+
+    let data = json!({
+        "date": "2024-12-31",
+        "items": [{ "price": 1200.5 }, { "price": 34 }]
+    });
+    let prepared = papermake::prepare_data(&template, &data, &options).unwrap();
+
+    assert_eq!(prepared["total"], json!(1234.5));
+    assert_eq!(prepared["total_formatted"], json!("1.234,50\u{a0}€"));
+    assert_eq!(prepared["items"][0]["price_formatted"], json!("1.200,50"));
+    assert_eq!(prepared["date_formatted"], json!("31.12.2024"));
+
+    // Running the pipeline again leaves the data unchanged
+    assert_eq!(papermake::prepare_data(&template, &prepared, &options).unwrap(), prepared);
+
+    let result = render_pdf(&template, &data, Some(options)).unwrap();
+    assert!(result.pdf.is_some(), "render failed: {:?}", result.errors);
+}
+
+#[test]
+fn test_data_is_prepared_once_per_render() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use papermake::{MemoryRenderCache, RenderCache, RenderOptions, TransformPipeline};
+
+    let template = Template::new("counter", "Counter", "#let data = json.decode(sys.inputs.data)\nRun #data.runs", Schema::new());
+    let runs = Arc::new(AtomicUsize::new(0));
+    let counter = runs.clone();
+    let options = RenderOptions {
+        transforms: TransformPipeline::new().then(move |data: &mut serde_json::Value| {
+            data["runs"] = json!(counter.fetch_add(1, Ordering::SeqCst) + 1);
+            Ok(())
+        }),
+        render_cache: Some(Arc::new(MemoryRenderCache::new(2)) as Arc<dyn RenderCache>),
+        ..Default::default()
+    };
+
+    // The cache key and the compile share one preparation
+    let result = render_pdf(&template, &json!({}), Some(options.clone())).unwrap();
+    assert!(result.pdf.is_some(), "render failed: {:?}", result.errors);
+    assert_eq!(runs.load(Ordering::SeqCst), 1);
+
+    // Data prepared up front is rendered as is
+    let prepared = papermake::prepare_data(&template, &json!({}), &options).unwrap();
+    let options = RenderOptions { data_prepared: true, render_cache: None, ..options };
+    let result = render_pdf(&template, &prepared, Some(options)).unwrap();
+    assert!(result.pdf.is_some(), "render failed: {:?}", result.errors);
+    assert_eq!(runs.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn test_deterministic_render_is_byte_identical() {
+    let template = Template::new("report", "Report", "#set document(title: \"Report\")\nIssued #datetime.today().display()", Schema::new());
+    let options = || papermake::RenderOptions { deterministic: true, ..Default::default() };
+
+    let first = render_pdf(&template, &json!({}), Some(options())).unwrap().pdf.unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(10));
+    let second = render_pdf(&template, &json!({}), Some(options())).unwrap().pdf.unwrap();
+    assert_eq!(first, second);
+}
+
+#[test]
+fn test_render_clock_and_timezone() {
+    use time::macros::{datetime, offset};
+
+    let expect_today = |date: &str| {
+        let content = format!(
+            "#assert.eq(datetime.today().display(), \"{}\")\n#assert.eq(datetime.today(offset: 0).display(), \"2024-02-29\")",
+            date
+        );
+        Template::new("dated", "Dated", content, Schema::new())
+    };
+    let now = datetime!(2024-02-29 23:30 UTC);
+
+    let utc = papermake::RenderOptions { now: Some(now), ..Default::default() };
+    let result = render_pdf(&expect_today("2024-02-29"), &json!({}), Some(utc)).unwrap();
+    assert!(result.pdf.is_some(), "render failed: {:?}", result.errors);
+
+    // Already the next day two hours east of UTC
+    let berlin = || papermake::RenderOptions { now: Some(now), timezone: Some(offset!(+2)), ..Default::default() };
+    let result = render_pdf(&expect_today("2024-03-01"), &json!({}), Some(berlin())).unwrap();
+    assert!(result.pdf.is_some(), "render failed: {:?}", result.errors);
+    let result = render_pdf(&expect_today("2024-02-29"), &json!({}), Some(berlin())).unwrap();
+    assert!(result.pdf.is_none());
+}
+
+#[test]
+fn test_extra_inputs() {
+    use papermake::render_cache::RenderCacheKey;
+
+    let template = Template::new(
+        "audit",
+        "Audit",
+        "#let extra = sys.inputs.extra\n#assert.eq(extra.at(\"requesting_user\", default: none), \"alice\")\n#assert.eq(extra.attempt, 2)",
+        Schema::new(),
+    );
+    let options = || papermake::RenderOptions {
+        extra_inputs: [
+            ("requesting_user".to_string(), json!("alice")),
+            ("attempt".to_string(), json!(2)),
+        ]
+        .into(),
+        ..Default::default()
+    };
+    let result = render_pdf(&template, &json!({}), Some(options())).unwrap();
+    assert!(result.pdf.is_some(), "render failed: {:?}", result.errors);
+
+    // Present but empty without extras
+    let empty = Template::new("plain", "Plain", "#assert.eq(sys.inputs.extra, (:))", Schema::new());
+    let result = render_pdf(&empty, &json!({}), None).unwrap();
+    assert!(result.pdf.is_some(), "render failed: {:?}", result.errors);
+
+    let mut other = options();
+    other.extra_inputs.insert("requesting_user".to_string(), json!("bob"));
+    assert_ne!(
+        RenderCacheKey::new(&template, &json!({}), &options()),
+        RenderCacheKey::new(&template, &json!({}), &other)
+    );
+}
+
+#[cfg(feature = "remote")]
+#[test]
+fn test_remote_resources_allowlist() {
+    use papermake::RemoteResources;
+
+    let remote = RemoteResources::new(["cdn.example.com"]);
+    assert!(remote.allows("https://cdn.example.com/logo.png"));
+    assert!(remote.allows("http://CDN.example.com/logo.png?v=2"));
+    assert!(!remote.allows("https://cdn.example.com.evil.test/logo.png"));
+    assert!(!remote.allows("ftp://cdn.example.com/logo.png"));
+
+    let template = Template::new("logo", "Logo", "#image(\"https://evil.test/logo.png\")", Schema::new());
+    let options = papermake::RenderOptions { remote_resources: Some(remote), ..Default::default() };
+    let result = render_pdf(&template, &json!({}), Some(options)).unwrap();
+    assert!(result.pdf.is_none());
+    assert!(result.errors.iter().any(|e| e.message.contains("not allowed")), "{:?}", result.errors);
+
+    // Without remote resources URLs are plain (missing) files
+    let result = render_pdf(&template, &json!({}), None).unwrap();
+    assert!(result.pdf.is_none());
+}
+
+#[cfg(feature = "remote")]
+#[test]
+fn test_pooled_worlds_recheck_remote_resources() {
+    use std::io::{Read, Write};
+    use papermake::{RemoteResources, WorldPool};
+
+    // Serve a small SVG for every request
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    std::thread::spawn(move || {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="10" height="10"><rect width="10" height="10"/></svg>"#;
+        for mut stream in listener.incoming().flatten() {
+            let mut request = [0; 1024];
+            let _ = stream.read(&mut request);
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: image/svg+xml\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                svg.len(),
+                svg
+            );
+            let _ = stream.write_all(response.as_bytes());
+        }
+    });
+
+    let content = format!("#image(\"http://127.0.0.1:{}/logo.svg\")", port);
+    let template = Template::new("logo", "Logo", content, Schema::new());
+    let pool = WorldPool::with_max_idle(1);
+    let allowed = papermake::RenderOptions {
+        remote_resources: Some(RemoteResources::new(["127.0.0.1"])),
+        ..Default::default()
+    };
+    let result = pool.render(&template, &json!({}), Some(allowed)).unwrap();
+    assert!(result.pdf.is_some(), "render failed: {:?}", result.errors);
+
+    // The same pooled world doesn't serve the file to renders that may not fetch it
+    let result = pool.render(&template, &json!({}), None).unwrap();
+    assert!(result.pdf.is_none());
+    let elsewhere = papermake::RenderOptions {
+        remote_resources: Some(RemoteResources::new(["cdn.example.com"])),
+        ..Default::default()
+    };
+    let result = pool.render(&template, &json!({}), Some(elsewhere)).unwrap();
+    assert!(result.pdf.is_none());
+}
+
+#[test]
+fn test_render_output_formats() {
+    use papermake::{render, OutputFormat};
+
+    let template = Template::new("report", "Report", "First\n#pagebreak()\nSecond", Schema::new());
+
+    let png = render(&template, &json!({}), OutputFormat::Png, None).unwrap();
+    assert_eq!(png.files.len(), 2);
+    assert!(png.files[0].starts_with(b"\x89PNG"));
+
+    let svg = render(&template, &json!({}), OutputFormat::Svg, None).unwrap();
+    assert_eq!(svg.files.len(), 2);
+    assert!(String::from_utf8_lossy(&svg.files[1]).contains("<svg"));
+
+    let pdf = render(&template, &json!({}), "PDF".parse().unwrap(), None).unwrap();
+    assert_eq!(pdf.files.len(), 1);
+    assert!("docx".parse::<OutputFormat>().is_err());
+
+    let broken = Template::new("broken", "Broken", "#unknown-function()", Schema::new());
+    let output = render(&broken, &json!({}), OutputFormat::Png, None).unwrap();
+    assert!(output.files.is_empty());
+    assert!(!output.errors.is_empty());
+}
+
+#[test]
+fn test_render_all_formats() {
+    use papermake::{render_all, OutputFormat};
+
+    let template = Template::new("report", "Report", "First\n#pagebreak()\nSecond", Schema::new());
+
+    let bundle = render_all(&template, &json!({}), &[OutputFormat::Pdf, OutputFormat::Png], None).unwrap();
+    assert!(bundle.is_success());
+    assert!(bundle.pdf.as_ref().unwrap().starts_with(b"%PDF"));
+    assert_eq!(bundle.png.len(), 2);
+    assert!(bundle.png[1].starts_with(b"\x89PNG"));
+    assert!(bundle.svg.is_empty());
+    assert_eq!(bundle.full_text(), "First\nSecond");
+    assert_eq!(bundle.metadata.unwrap().page_count, 2);
+
+    // Page selection applies to every format
+    let options = papermake::RenderOptions { pages: Some("2".parse().unwrap()), ..Default::default() };
+    let bundle = render_all(&template, &json!({}), &[OutputFormat::Svg], Some(options)).unwrap();
+    assert!(bundle.pdf.is_none());
+    assert_eq!(bundle.svg.len(), 1);
+    assert_eq!(bundle.full_text(), "Second");
+
+    assert!(render_all(&template, &json!({}), &[OutputFormat::Pdf, OutputFormat::Html], None).is_err());
+
+    let broken = Template::new("broken", "Broken", "#unknown-function()", Schema::new());
+    let bundle = render_all(&broken, &json!({}), &[OutputFormat::Pdf], None).unwrap();
+    assert!(!bundle.is_success());
+    assert!(bundle.pdf.is_none());
+}
+
+#[test]
+fn test_render_preview() {
+    use papermake::preview::{render_preview, PreviewFormat, PreviewOptions};
+
+    // A4 is 595.28pt wide
+    let template = Template::new("report", "Report", "#set page(paper: \"a4\")\nFirst\n#pagebreak()\nSecond", Schema::new());
+
+    let preview = render_preview(&template, &json!({}), &PreviewOptions::default(), None).unwrap();
+    assert_eq!(preview.pages.len(), 2);
+    assert_eq!(preview.pages[0].width, 1191);
+    assert!(preview.pages[1].image.starts_with(b"\x89PNG"));
+
+    let thumbnail = render_preview(&template, &json!({}), &PreviewOptions::thumbnail(200), None).unwrap();
+    assert_eq!(thumbnail.pages.len(), 1);
+    assert_eq!(thumbnail.pages[0].width, 200);
+    assert!(thumbnail.pages[0].image.starts_with(&[0xFF, 0xD8]));
+
+    let proof = PreviewOptions {
+        dpi: 300.0,
+        page_range: Some("2".parse().unwrap()),
+        format: PreviewFormat::Webp,
+        ..PreviewOptions::default()
+    };
+    let preview = render_preview(&template, &json!({}), &proof, None).unwrap();
+    assert_eq!(preview.pages[0].page, 2);
+    assert_eq!(preview.pages[0].width, 2480);
+    assert_eq!(&preview.pages[0].image[8..12], b"WEBP");
+
+    let invalid = PreviewOptions { quality: 0, ..PreviewOptions::default() };
+    assert!(render_preview(&template, &json!({}), &invalid, None).is_err());
+    let out_of_range = PreviewOptions { page_range: Some("3".parse().unwrap()), ..PreviewOptions::default() };
+    assert!(render_preview(&template, &json!({}), &out_of_range, None).is_err());
+}
+
+#[test]
+fn test_extract_text() {
+    let template = Template::new(
+        "invoice",
+        "Invoice",
+        "#set page(margin: 2cm)\n#set text(size: 12pt)\nInvoice\n#pagebreak()\n#text(size: 20pt)[Total: 42.00]",
+        Schema::new(),
+    );
+
+    // Off by default
+    let result = render_pdf(&template, &json!({}), None).unwrap();
+    assert!(result.text.is_none());
+
+    let options = papermake::RenderOptions { extract_text: true, ..Default::default() };
+    let pages = render_pdf(&template, &json!({}), Some(options)).unwrap().text.unwrap();
+    assert_eq!(pages.len(), 2);
+    assert_eq!(pages[0].page, 1);
+    assert_eq!(pages[0].text, "Invoice");
+    assert!(pages[1].contains("Total: 42.00"));
+
+    // Spans are placed inside the page margins, at their font size
+    let span = &pages[1].spans[0];
+    assert!((span.x - 56.69).abs() < 0.1, "{:?}", span);
+    assert!(span.y > 56.69);
+    assert!(span.width > 0.0);
+    assert_eq!(span.font_size, 20.0);
+
+    let options = papermake::RenderOptions { extract_text: true, pages: Some("2".parse().unwrap()), ..Default::default() };
+    let pages = render_pdf(&template, &json!({}), Some(options)).unwrap().text.unwrap();
+    assert_eq!(pages.len(), 1);
+    assert_eq!(pages[0].page, 2);
+}
+
+#[test]
+fn test_sandbox_policy_restricts_templates() {
+    use papermake::{SandboxPolicy, SharedSources};
+
+    let restrictive = || papermake::RenderOptions { sandbox: Some(SandboxPolicy::restrictive()), ..Default::default() };
+
+    let reads = Template::new("reads", "Reads", "#read(\"/etc/passwd\")", Schema::new());
+    let result = render_pdf(&reads, &json!({}), Some(restrictive())).unwrap();
+    assert!(result.pdf.is_none());
+    assert!(result.errors[0].message.contains("`read` is not allowed"));
+    assert_eq!(result.errors[0].start, 1);
+
+    // Fields and argument names that happen to be denied are fine
+    let fields = Template::new("fields", "Fields", "#let data = (read: true)\n#data.read", Schema::new());
+    let result = render_pdf(&fields, &json!({}), Some(restrictive())).unwrap();
+    assert!(result.pdf.is_some(), "render failed: {:?}", result.errors);
+
+    // Shared imports resolve normally but are denied by the policy
+    let mut shared_sources = SharedSources::default();
+    shared_sources.insert("shared/header.typ", "#let header = [Header]");
+    let imports = Template::new("imports", "Imports", "#import \"papermake:shared/header.typ\": header\n#header", Schema::new());
+    let options = papermake::RenderOptions { shared_sources: shared_sources.clone(), ..restrictive() };
+    assert!(render_pdf(&imports, &json!({}), Some(options)).unwrap().pdf.is_none());
+    let options = papermake::RenderOptions { shared_sources, ..Default::default() };
+    assert!(render_pdf(&imports, &json!({}), Some(options)).unwrap().pdf.is_some());
+
+    // A template's own policy applies on top of the render's
+    let evals = Template::new("evals", "Evals", "#eval(\"1 + 1\")", Schema::new())
+        .with_sandbox(SandboxPolicy::default().deny_function("eval"));
+    assert!(render_pdf(&evals, &json!({}), None).unwrap().pdf.is_none());
+}
+
+#[test]
+fn test_render_warnings() {
+    let template = Template::new("fonts", "Fonts", "#set text(font: \"No Such Font\")\nHello", Schema::new());
+    let result = render_pdf(&template, &json!({}), None).unwrap();
+    assert!(result.pdf.is_some(), "render failed: {:?}", result.errors);
+    assert!(result.warnings.iter().any(|w| w.message.contains("unknown font family")));
+
+    let clean = Template::new("clean", "Clean", "Hello", Schema::new());
+    assert!(render_pdf(&clean, &json!({}), None).unwrap().warnings.is_empty());
+}
+
+#[test]
+fn test_page_selection() {
+    use papermake::pdf_ops::{page_count, select_pages, split_pages};
+    use papermake::PageSelection;
+
+    let selection: PageSelection = "1-2, 4-".parse().unwrap();
+    assert_eq!(selection.to_string(), "1-2,4-");
+    assert!(selection.contains(5) && !selection.contains(3));
+    assert!("3-1".parse::<PageSelection>().is_err());
+    assert!("0".parse::<PageSelection>().is_err());
+
+    let template = Template::new("pages", "Pages", "One\n#pagebreak()\nTwo\n#pagebreak()\nThree", Schema::new());
+    let full = render_pdf(&template, &json!({}), None).unwrap().pdf.unwrap();
+    assert_eq!(page_count(&full).unwrap(), 3);
+
+    let options = papermake::RenderOptions { pages: Some("2-".parse().unwrap()), ..Default::default() };
+    let partial = render_pdf(&template, &json!({}), Some(options)).unwrap().pdf.unwrap();
+    assert_eq!(page_count(&partial).unwrap(), 2);
+
+    let options = papermake::RenderOptions { pages: Some("7".parse().unwrap()), ..Default::default() };
+    assert!(render_pdf(&template, &json!({}), Some(options)).is_err());
+
+    let pages = split_pages(&full).unwrap();
+    assert_eq!(pages.len(), 3);
+    assert!(pages.iter().all(|page| page_count(page).unwrap() == 1));
+    assert_eq!(page_count(&select_pages(&full, &"1,3".parse().unwrap()).unwrap()).unwrap(), 2);
+}
+
+#[test]
+fn test_render_with_attachments() {
+    use papermake::{AttachmentRelationship, PdfAttachment};
+
+    let template = Template::new("invoice", "Invoice", "Invoice", Schema::new());
+    let options = papermake::RenderOptions {
+        attachments: vec![
+            PdfAttachment::input_data("invoice.json"),
+            PdfAttachment::bytes("factur-x.xml", "text/xml", b"<Invoice/>".to_vec())
+                .relationship(AttachmentRelationship::Alternative),
+        ],
+        // Attached data is the data the template rendered
+        transforms: papermake::TransformPipeline::new().then(|data: &mut serde_json::Value| {
+            data["currency"] = json!("EUR");
+            Ok(())
+        }),
+        ..Default::default()
+    };
+    let pdf = render_pdf(&template, &json!({ "total": 42 }), Some(options)).unwrap().pdf.unwrap();
+
+    let contains = |needle: &[u8]| pdf.windows(needle.len()).any(|w| w == needle);
+    assert!(contains(b"/EmbeddedFiles"));
+    assert!(contains(b"(factur-x.xml)"));
+    assert!(contains(b"/Alternative"));
+    assert!(contains(b"\"total\": 42"));
+    assert!(contains(b"\"currency\": \"EUR\""));
+    assert!(contains(b"<Invoice/>"));
+}
+
+#[test]
+fn test_size_limits() {
+    use papermake::{PapermakeError, RenderOptions, SizeLimits};
+
+    let template = Template::new("sized", "Sized", "#data.at(\"text\", default: \"\")", Schema::new());
+    let limited = |limits: SizeLimits| RenderOptions { size_limits: limits, ..Default::default() };
+
+    let small = json!({ "text": "hello" });
+    let large = json!({ "text": "x".repeat(1000) });
+    let limits = SizeLimits::default().max_data_bytes(100);
+    assert!(render_pdf(&template, &small, Some(limited(limits))).unwrap().pdf.is_some());
+    let err = render_pdf(&template, &large, Some(limited(limits))).unwrap_err();
+    assert!(matches!(err, PapermakeError::TooLarge(_)), "unexpected error: {}", err);
+
+    let limits = SizeLimits::default().max_template_bytes(10);
+    let err = render_pdf(&template, &small, Some(limited(limits))).unwrap_err();
+    assert!(matches!(err, PapermakeError::TooLarge(_)), "unexpected error: {}", err);
+
+    let limits = SizeLimits::default().max_asset_bytes(1024);
+    assert!(limits.check_asset("logo.png", 1024).is_ok());
+    assert!(matches!(limits.check_asset("logo.png", 1025), Err(PapermakeError::TooLarge(_))));
+}
+
+#[cfg(feature = "barcodes")]
+#[test]
+fn test_barcode_fields() {
+    use papermake::{BarcodeKind, FieldType, PapermakeError, SchemaBuilder};
+
+    let schema = SchemaBuilder::new()
+        .field("link", FieldType::Barcode(BarcodeKind::Qr))
+        .field("ean", FieldType::Barcode(BarcodeKind::Ean13))
+        .build();
+    let template = Template::new(
+        "ticket",
+        "Ticket",
+        "#image(\"papermake:barcodes/link.svg\", width: 3cm)\n#image(\"papermake:barcodes/ean.svg\", width: 4cm)",
+        schema,
+    );
+
+    let data = json!({ "link": "https://example.com/t/42", "ean": "4006381333931" });
+    let result = render_pdf(&template, &data, None).unwrap();
+    assert!(result.pdf.is_some(), "render failed: {:?}", result.errors);
+
+    // Invalid check digits fail validation before rendering
+    let data = json!({ "link": "https://example.com/t/42", "ean": "4006381333932" });
+    let err = render_pdf(&template, &data, None).unwrap_err();
+    assert!(matches!(err, PapermakeError::SchemaValidation(_)), "unexpected error: {}", err);
+
+    // Sample data produces valid codes
+    let sample = template.schema.generate_sample_data(7);
+    assert!(render_pdf(&template, &sample, None).unwrap().pdf.is_some());
+}
+
+#[test]
+fn test_optimize_pdf() {
+    use papermake::pdf_ops::page_count;
+    use papermake::{OptimizeLevel, PdfAttachment};
+
+    let template = Template::new("report", "Report", "Page one\n#pagebreak()\nPage two", Schema::new());
+    let plain = render_pdf(&template, &json!({}), None).unwrap();
+    assert!(plain.optimization.is_none());
+
+    // The same file attached twice is stored once
+    let xml = b"<Report>".repeat(512);
+    let options = papermake::RenderOptions {
+        optimize: OptimizeLevel::Lossless,
+        attachments: vec![
+            PdfAttachment::bytes("a.xml", "text/xml", xml.clone()),
+            PdfAttachment::bytes("b.xml", "text/xml", xml),
+        ],
+        ..Default::default()
+    };
+    let result = render_pdf(&template, &json!({}), Some(options)).unwrap();
+    let report = result.optimization.expect("optimized renders report their sizes");
+    let pdf = result.pdf.unwrap();
+
+    assert_eq!(report.optimized_bytes, pdf.len());
+    assert!(report.optimized_bytes < report.original_bytes, "{:?}", report);
+    assert!(report.deduplicated_objects >= 1, "{:?}", report);
+    assert_eq!(page_count(&pdf).unwrap(), 2);
+}
+
+#[test]
+fn test_document_metadata() {
+    let template = Template::new(
+        "handbook",
+        "Handbook",
+        "= Intro <intro>\nWelcome\n#pagebreak()\n== Details\nMore\n#pagebreak()\n= Totals <totals>\n#heading(outlined: false)[Hidden]",
+        Schema::new(),
+    );
+    let metadata = render_pdf(&template, &json!({}), None).unwrap().metadata.unwrap();
+    assert_eq!(metadata.page_count, 3);
+
+    let outline: Vec<_> = metadata.outline.iter().map(|e| (e.title.as_str(), e.level, e.page)).collect();
+    assert_eq!(outline, [("Intro", 1, 1), ("Details", 2, 2), ("Totals", 1, 3)]);
+    assert_eq!(metadata.outline[0].label.as_deref(), Some("intro"));
+
+    let destinations: Vec<_> = metadata.destinations.iter().map(|d| (d.name.as_str(), d.page)).collect();
+    assert_eq!(destinations, [("intro", 1), ("totals", 3)]);
+
+    // Pages are numbered as exported
+    let options = papermake::RenderOptions { pages: Some("2-".parse().unwrap()), ..Default::default() };
+    let metadata = render_pdf(&template, &json!({}), Some(options)).unwrap().metadata.unwrap();
+    assert_eq!(metadata.page_count, 2);
+    assert_eq!(metadata.outline.len(), 2);
+    assert_eq!(metadata.destinations[0].name, "totals");
+    assert_eq!(metadata.destinations[0].page, 2);
+}
+
+#[test]
+fn test_watermark() {
+    use papermake::environment::DEFAULT_ENVIRONMENT;
+    use papermake::{EnvironmentConfig, OutputFormat, Watermark};
+
+    let template = Template::new("memo", "Memo", "Page one\n#pagebreak()\nPage two", Schema::new());
+    let png = |template: &Template, options: papermake::RenderOptions| {
+        papermake::render(template, &json!({}), OutputFormat::Png, Some(options)).unwrap().files
+    };
+    let plain = png(&template, Default::default());
+
+    let options = papermake::RenderOptions {
+        watermark: Some(Watermark::text("CONFIDENTIAL").first_page_only()),
+        ..Default::default()
+    };
+    let stamped = png(&template, options.clone());
+    assert_ne!(stamped[0], plain[0]);
+    assert_eq!(stamped[1], plain[1]);
+    assert!(render_pdf(&template, &json!({}), Some(options)).unwrap().pdf.is_some());
+
+    let options = papermake::RenderOptions {
+        watermark: Some(Watermark::text("COPY").with_opacity(1.5)),
+        ..Default::default()
+    };
+    assert!(render_pdf(&template, &json!({}), Some(options)).is_err());
+
+    // Environments with watermark text stamp every page
+    let template = template.with_environment("staging", EnvironmentConfig {
+        watermark: Some("DRAFT".to_string()),
+        ..Default::default()
+    });
+    let staging = png(&template, papermake::RenderOptions { environment: Some("staging".to_string()), ..Default::default() });
+    assert!(staging.iter().zip(&plain).all(|(staging, plain)| staging != plain));
+    let options = papermake::RenderOptions { environment: Some(DEFAULT_ENVIRONMENT.to_string()), ..Default::default() };
+    assert_eq!(png(&template, options), plain);
+}
@@ -0,0 +1,61 @@
+#![cfg(feature = "scripting")]
+
+use papermake::schema::FieldType;
+use papermake::{prepare_data, render_pdf, PapermakeError, RenderOptions, Schema, ScriptLimits, Template};
+use serde_json::json;
+
+const TOTALS: &str = r#"
+let total = 0.0;
+for item in data.items {
+    total += item.price * item.quantity;
+}
+data.total = total;
+data.address = `${data.street}, ${data.city}`;
+"#;
+
+#[test]
+fn test_script_computes_fields() {
+    let schema = Schema::builder()
+        .field("items", FieldType::Array(Box::new(FieldType::Object(Box::new(Schema::new())))))
+        .field("total", FieldType::Number)
+        .build();
+    let content = "#let data = json.decode(sys.inputs.data)\nTotal: #data.total, #data.address";
+    let template = Template::new("invoice", "Invoice", content, schema).with_script(TOTALS);
+    let data = json!({
+        "items": [{ "price": 2.5, "quantity": 2 }, { "price": 10, "quantity": 1 }],
+        "street": "Main St 1",
+        "city": "Springfield",
+    });
+
+    let prepared = prepare_data(&template, &data, &RenderOptions::default()).unwrap();
+    assert_eq!(prepared["total"], json!(15.0));
+    assert_eq!(prepared["address"], json!("Main St 1, Springfield"));
+
+    // Running the script again gives the same data
+    let again = prepare_data(&template, &prepared, &RenderOptions::default()).unwrap();
+    assert_eq!(again, prepared);
+
+    let result = render_pdf(&template, &data, None).unwrap();
+    assert!(result.pdf.is_some(), "render failed: {:?}", result.errors);
+}
+
+#[test]
+fn test_script_is_sandboxed() {
+    let run = |script: &str, options: &RenderOptions| {
+        let template = Template::new("t", "T", "Hello", Schema::new()).with_script(script);
+        prepare_data(&template, &json!({}), options)
+    };
+    let defaults = RenderOptions::default();
+
+    // Runaway loops are stopped by the operation limit
+    assert!(matches!(run("loop { }", &defaults), Err(PapermakeError::InvalidInput(_))));
+    assert!(run(r#"import "secrets" as s;"#, &defaults).is_err());
+    assert!(run(r#"eval("data.x = 1")"#, &defaults).is_err());
+
+    let tight = RenderOptions {
+        script_limits: ScriptLimits { max_array_size: 10, ..Default::default() },
+        ..Default::default()
+    };
+    assert!(run("data.items = []; for i in 0..100 { data.items.push(i); }", &tight).is_err());
+    assert!(run("data.items = []; for i in 0..100 { data.items.push(i); }", &defaults).is_ok());
+}
@@ -0,0 +1,63 @@
+use papermake::{render_batch, FileSink, MemorySink, RenderOptions, RenderSink, Schema, Template};
+use serde_json::json;
+
+fn greeting_template() -> Template {
+    Template::new(
+        "greeting",
+        "Greeting",
+        "#let data = json.decode(sys.inputs.data)\nHello #data.name!",
+        Schema::new(),
+    )
+}
+
+#[tokio::test]
+async fn test_render_batch_streams_to_memory_sink() {
+    let template = greeting_template();
+    let records = vec![json!({ "name": "Alice" }), json!({ "name": "Bob" })];
+    let sink = MemorySink::new();
+
+    let items = render_batch(&template, &records, RenderOptions::default(), &sink, "batch")
+        .await
+        .unwrap();
+
+    assert_eq!(items.len(), 2);
+    assert!(items.iter().all(|item| item.is_success()));
+    assert_eq!(items[1].url.as_deref(), Some("memory://batch/000001.pdf"));
+    assert_eq!(sink.keys(), vec!["batch/000000.pdf", "batch/000001.pdf"]);
+
+    let pdf = sink.read("batch/000000.pdf").await.unwrap();
+    assert!(pdf.starts_with(b"%PDF"));
+    assert_eq!(items[0].size_bytes, Some(pdf.len()));
+}
+
+#[tokio::test]
+async fn test_render_batch_reports_failing_records() {
+    let template = Template::new(
+        "broken",
+        "Broken",
+        "#let data = json.decode(sys.inputs.data)\n#data.missing.field",
+        Schema::new(),
+    );
+    let sink = MemorySink::new();
+
+    let items = render_batch(&template, &[json!({})], RenderOptions::default(), &sink, "batch")
+        .await
+        .unwrap();
+
+    assert!(!items[0].is_success());
+    assert!(!items[0].errors.is_empty());
+    assert!(sink.keys().is_empty());
+}
+
+#[tokio::test]
+async fn test_file_sink_round_trip() {
+    let dir = tempfile::tempdir().unwrap();
+    let sink = FileSink::new(dir.path());
+
+    let url = sink.write("jobs/1/000000.pdf", b"%PDF-1.7".to_vec()).await.unwrap();
+    assert!(url.starts_with("file://"));
+    assert!(dir.path().join("jobs/1/000000.pdf").exists());
+    assert_eq!(sink.read("jobs/1/000000.pdf").await.unwrap(), b"%PDF-1.7");
+
+    assert!(sink.write("../escape.pdf", Vec::new()).await.is_err());
+}
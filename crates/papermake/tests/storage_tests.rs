@@ -0,0 +1,480 @@
+use papermake::storage::{FileStorage, ListOptions, Storage, TemplateSort};
+use papermake::{ErrorCode, Schema, Template, TemplateId};
+use tempfile::tempdir;
+
+#[tokio::test]
+async fn test_file_storage_template_roundtrip() {
+    let temp_dir = tempdir().unwrap();
+    let storage = FileStorage::new(temp_dir.path());
+
+    let template = Template::new("invoice", "Invoice", "Hello", Schema::new());
+    storage.save_template(&template).await.unwrap();
+
+    let loaded = storage.get_template(&"invoice".into()).await.unwrap();
+    assert_eq!(loaded.name, "Invoice");
+    assert_eq!(storage.list_templates(&ListOptions::new()).await.unwrap().templates.len(), 1);
+
+    storage.delete_template(&"invoice".into()).await.unwrap();
+    assert!(storage.get_template(&"invoice".into()).await.is_err());
+}
+
+#[tokio::test]
+async fn test_file_storage_delete_and_rename_files() {
+    let temp_dir = tempdir().unwrap();
+    let storage = FileStorage::new(temp_dir.path());
+    let id = TemplateId::from("invoice");
+
+    storage.save_template(&Template::new("invoice", "Invoice", "Hello", Schema::new())).await.unwrap();
+    storage.save_template_file(&id, "images/logo.png", b"png").await.unwrap();
+    storage.save_template_file(&id, "footer.typ", b"footer").await.unwrap();
+
+    storage.rename_template_file(&id, "images/logo.png", "logo.png").await.unwrap();
+    assert_eq!(storage.get_template_file(&id, "logo.png").await.unwrap(), b"png");
+    assert!(storage.get_template_file(&id, "images/logo.png").await.is_err());
+
+    storage.delete_template_file(&id, "footer.typ").await.unwrap();
+    assert_eq!(storage.list_template_files(&id).await.unwrap(), vec!["logo.png".to_string()]);
+
+    // Deleting a missing file and escaping the template directory are errors
+    assert!(storage.delete_template_file(&id, "footer.typ").await.is_err());
+    assert!(storage.save_template_file(&id, "../escape.typ", b"x").await.is_err());
+}
+
+#[tokio::test]
+async fn test_file_storage_error_codes() {
+    let temp_dir = tempdir().unwrap();
+    let storage = FileStorage::new(temp_dir.path());
+    let id = TemplateId::from("invoice");
+
+    let err = storage.get_template(&id).await.unwrap_err();
+    assert_eq!(err.code(), ErrorCode::TemplateNotFound);
+    assert_eq!(err.code().as_str(), "TEMPLATE_NOT_FOUND");
+
+    storage.save_template(&Template::new("invoice", "Invoice", "Hello", Schema::new())).await.unwrap();
+    let err = storage.get_template_file(&id, "missing.png").await.unwrap_err();
+    assert_eq!(err.code(), ErrorCode::NotFound);
+    assert_eq!(err.to_string(), "File not found: missing.png");
+}
+
+#[tokio::test]
+async fn test_file_storage_rejects_invalid_template_ids() {
+    let temp_dir = tempdir().unwrap();
+    let storage = FileStorage::new(temp_dir.path().join("storage"));
+    std::fs::create_dir_all(temp_dir.path().join("storage/templates")).unwrap();
+    std::fs::write(temp_dir.path().join("secret.json"), "{}").unwrap();
+
+    for id in ["..", "a/b", "", ".", "..\\a"] {
+        let id = TemplateId::from(id);
+        let err = storage.get_template(&id).await.unwrap_err();
+        assert_eq!(err.code(), ErrorCode::InvalidInput, "{:?}", id);
+        assert!(storage.save_template(&Template::new(id.clone(), "X", "Hello", Schema::new())).await.is_err());
+        assert!(storage.save_template_file(&id, "logo.png", b"png").await.is_err());
+        assert!(storage.list_template_files(&id).await.is_err());
+        assert!(storage.delete_template(&id).await.is_err());
+    }
+    assert!(papermake::storage::validate_template_id(&"invoice_2024-v2".into()).is_ok());
+    assert!(temp_dir.path().join("secret.json").exists());
+}
+
+#[tokio::test]
+async fn test_shared_template_imports() {
+    let temp_dir = tempdir().unwrap();
+    let storage = FileStorage::new(temp_dir.path());
+
+    let header = Template::new("header", "Header", "#let header(title) = [= #title]", Schema::new()).as_shared();
+    storage.save_template(&header).await.unwrap();
+    let invoice = Template::new(
+        "invoice",
+        "Invoice",
+        "#import \"papermake:shared/header.typ\": header\n#header[Invoice]",
+        Schema::new(),
+    );
+    storage.save_template(&invoice).await.unwrap();
+
+    assert_eq!(storage.list_shared_templates().await.unwrap().len(), 1);
+
+    let shared_sources = papermake::resolve_shared(&storage, &invoice).await.unwrap();
+    assert_eq!(shared_sources.get("shared/header.typ"), Some(header.content.as_str()));
+
+    let options = papermake::RenderOptions {
+        shared_sources,
+        ..Default::default()
+    };
+    let result = invoice.render_with_options(&serde_json::json!({}), options).unwrap();
+    assert!(result.pdf.is_some(), "render failed: {:?}", result.errors);
+}
+
+#[tokio::test]
+async fn test_shared_template_import_errors() {
+    let temp_dir = tempdir().unwrap();
+    let storage = FileStorage::new(temp_dir.path());
+
+    let a = Template::new("a", "A", "#import \"papermake:shared/b.typ\"", Schema::new()).as_shared();
+    let b = Template::new("b", "B", "#import \"papermake:shared/a.typ\"", Schema::new()).as_shared();
+    let plain = Template::new("plain", "Plain", "Hello", Schema::new());
+    for template in [&a, &b, &plain] {
+        storage.save_template(template).await.unwrap();
+    }
+
+    let err = papermake::resolve_shared(&storage, &a).await.unwrap_err();
+    assert!(err.to_string().contains("Cyclic shared import"));
+
+    let imports_plain = Template::new("doc", "Doc", "#include \"papermake:shared/plain.typ\"", Schema::new());
+    let err = papermake::resolve_shared(&storage, &imports_plain).await.unwrap_err();
+    assert!(err.to_string().contains("not a shared template"));
+}
+
+#[tokio::test]
+async fn test_file_storage_namespaces_are_isolated() {
+    let temp_dir = tempdir().unwrap();
+    let storage = FileStorage::new(temp_dir.path());
+    let acme = storage.for_namespace(&papermake::storage::Namespace::new("acme").unwrap());
+    let globex = storage.for_namespace(&papermake::storage::Namespace::new("globex").unwrap());
+
+    acme.save_template(&Template::new("invoice", "Acme Invoice", "Hello", Schema::new())).await.unwrap();
+
+    assert_eq!(acme.get_template(&"invoice".into()).await.unwrap().name, "Acme Invoice");
+    assert!(globex.get_template(&"invoice".into()).await.is_err());
+    assert!(storage.list_templates(&ListOptions::new()).await.unwrap().templates.is_empty());
+    assert!(temp_dir.path().join("tenants/acme/templates/invoice/template.json").exists());
+
+    assert!(papermake::storage::Namespace::new("../escape").is_err());
+}
+
+#[tokio::test]
+async fn test_file_storage_namespaces_reject_traversing_ids() {
+    let temp_dir = tempdir().unwrap();
+    let storage = FileStorage::new(temp_dir.path());
+    let acme = storage.for_namespace(&papermake::storage::Namespace::new("acme").unwrap());
+    let globex = storage.for_namespace(&papermake::storage::Namespace::new("globex").unwrap());
+    globex.save_template(&Template::new("invoice", "Globex Invoice", "Hello", Schema::new())).await.unwrap();
+    globex.save_template_file(&"invoice".into(), "logo.png", b"png").await.unwrap();
+
+    // From acme's templates directory this id names globex's template
+    let id = TemplateId::from("../../globex/templates/invoice");
+    let err = acme.get_template(&id).await.unwrap_err();
+    assert_eq!(err.code(), ErrorCode::InvalidInput);
+    assert!(acme.get_template_file(&id, "logo.png").await.is_err());
+    assert!(acme.copy_template(&id, &"stolen".into()).await.is_err());
+    assert!(acme.delete_template(&id).await.is_err());
+    assert!(acme.get_template(&"stolen".into()).await.is_err());
+    assert_eq!(globex.get_template(&"invoice".into()).await.unwrap().name, "Globex Invoice");
+}
+
+#[tokio::test]
+async fn test_template_publish_lifecycle() {
+    use papermake::lifecycle::{archive_template, publish_template, save_draft, template_for_render};
+    use papermake::{TemplateStatus, TemplateVersion};
+
+    let temp_dir = tempdir().unwrap();
+    let storage = FileStorage::new(temp_dir.path());
+    let id = TemplateId::from("invoice");
+
+    let mut template = Template::new("invoice", "Invoice", "Version 1", Schema::new());
+    save_draft(&storage, &mut template).await.unwrap();
+
+    // Drafts only render when explicitly requested
+    assert!(template_for_render(&storage, &id, TemplateVersion::Published).await.is_err());
+    assert_eq!(template_for_render(&storage, &id, TemplateVersion::Draft).await.unwrap().content, "Version 1");
+
+    let published = publish_template(&storage, &id).await.unwrap();
+    assert_eq!(published.status, TemplateStatus::Published);
+    assert!(published.published_at.is_some());
+
+    // Editing the working copy leaves the published revision untouched
+    let mut template = published;
+    template.content = "Version 2".to_string();
+    save_draft(&storage, &mut template).await.unwrap();
+    assert_eq!(template_for_render(&storage, &id, TemplateVersion::Published).await.unwrap().content, "Version 1");
+    assert_eq!(template_for_render(&storage, &id, TemplateVersion::Draft).await.unwrap().content, "Version 2");
+
+    archive_template(&storage, &id).await.unwrap();
+    assert!(template_for_render(&storage, &id, TemplateVersion::Published).await.is_err());
+    assert!(template_for_render(&storage, &id, TemplateVersion::Draft).await.is_err());
+}
+
+#[tokio::test]
+async fn test_changes_since_publishing() {
+    use papermake::lifecycle::{asset_manifest, publish_template, save_draft, template_for_render};
+    use papermake::{FieldType, FileChangeKind, SchemaChangeKind, TemplateVersion};
+
+    let temp_dir = tempdir().unwrap();
+    let storage = FileStorage::new(temp_dir.path());
+    let id = TemplateId::from("invoice");
+
+    let schema = Schema::builder().field("name", FieldType::String).build();
+    let mut template = Template::new("invoice", "Invoice", "= Invoice\nTo: #data.name\n", schema);
+    save_draft(&storage, &mut template).await.unwrap();
+    storage.save_template_file(&id, "logo.png", b"old logo").await.unwrap();
+    storage.save_template_file(&id, "footer.typ", b"Footer").await.unwrap();
+    let mut template = publish_template(&storage, &id).await.unwrap();
+
+    template.content = "= Invoice\nTo: #data.customer\n".to_string();
+    template.schema = Schema::builder().field("customer", FieldType::String).build();
+    save_draft(&storage, &mut template).await.unwrap();
+    storage.save_template_file(&id, "logo.png", b"new logo").await.unwrap();
+    storage.delete_template_file(&id, "footer.typ").await.unwrap();
+    storage.save_template_file(&id, "stamp.svg", b"<svg/>").await.unwrap();
+
+    let published = template_for_render(&storage, &id, TemplateVersion::Published).await.unwrap();
+    assert_eq!(published.assets.len(), 2);
+    let mut draft = storage.get_template(&id).await.unwrap();
+    assert!(draft.assets.is_empty());
+    draft.assets = asset_manifest(&storage, &id).await.unwrap();
+
+    let changes = published.changes(&draft);
+    assert_eq!(changes.content, "@@ -1,2 +1,2 @@\n = Invoice\n-To: #data.name\n+To: #data.customer\n");
+    let kinds: Vec<_> = changes.schema.changes.iter().map(|c| (c.kind, c.path.as_str())).collect();
+    assert!(kinds.contains(&(SchemaChangeKind::FieldRemoved, "name")), "{:?}", kinds);
+    assert!(kinds.contains(&(SchemaChangeKind::FieldAdded, "customer")), "{:?}", kinds);
+    let files: Vec<_> = changes.files.iter().map(|f| (f.path.as_str(), f.kind)).collect();
+    assert_eq!(
+        files,
+        [("footer.typ", FileChangeKind::Removed), ("logo.png", FileChangeKind::Modified), ("stamp.svg", FileChangeKind::Added)]
+    );
+
+    assert!(published.changes(&published).is_empty());
+}
+
+#[tokio::test]
+async fn test_list_templates_filter_sort_and_paginate() {
+    let temp_dir = tempdir().unwrap();
+    let storage = FileStorage::new(temp_dir.path());
+
+    for (id, name) in [("a", "Invoice DE"), ("b", "Letter"), ("c", "Invoice US"), ("d", "Invoice UK")] {
+        storage.save_template(&Template::new(id, name, "Hello", Schema::new())).await.unwrap();
+    }
+
+    let invoices = storage.list_templates(&ListOptions::new().name_contains("invoice")).await.unwrap();
+    let ids: Vec<&str> = invoices.templates.iter().map(|t| t.id.as_ref()).collect();
+    assert_eq!(ids, ["a", "c", "d"]);
+    assert!(invoices.next_cursor.is_none());
+
+    // Page through most recently updated first
+    let mut touched = storage.get_template(&"a".into()).await.unwrap();
+    touched.updated_at += time::Duration::hours(1);
+    storage.save_template(&touched).await.unwrap();
+
+    let options = ListOptions::new().sort(TemplateSort::UpdatedDesc).limit(3);
+    let first = storage.list_templates(&options).await.unwrap();
+    assert_eq!(first.templates.len(), 3);
+    assert_eq!(first.templates[0].id.as_ref(), "a");
+
+    let cursor = first.next_cursor.expect("more templates to list");
+    let second = storage.list_templates(&options.clone().cursor(cursor)).await.unwrap();
+    assert_eq!(second.templates.len(), 1);
+    assert!(second.next_cursor.is_none());
+
+    let invalid = ListOptions::new().cursor("not a cursor");
+    assert!(storage.list_templates(&invalid).await.is_err());
+}
+
+#[tokio::test]
+async fn test_list_templates_by_tag_and_metadata() {
+    let temp_dir = tempdir().unwrap();
+    let storage = FileStorage::new(temp_dir.path());
+
+    let invoice = Template::new("invoice", "Invoice", "Hello", Schema::new())
+        .with_tag("finance")
+        .with_tag("de")
+        .with_metadata("department", "billing");
+    let letter = Template::new("letter", "Letter", "Hello", Schema::new())
+        .with_tag("de")
+        .with_metadata("department", "hr");
+    storage.save_template(&invoice).await.unwrap();
+    storage.save_template(&letter).await.unwrap();
+
+    let loaded = storage.get_template(&"invoice".into()).await.unwrap();
+    assert_eq!(loaded.tags, ["finance", "de"]);
+    assert_eq!(loaded.metadata.get("department").map(String::as_str), Some("billing"));
+
+    let german = storage.list_templates(&ListOptions::new().tag("de")).await.unwrap();
+    assert_eq!(german.templates.len(), 2);
+
+    let finance = storage.list_templates(&ListOptions::new().tag("de").tag("finance")).await.unwrap();
+    assert_eq!(finance.templates.len(), 1);
+    assert_eq!(finance.templates[0].id.as_ref(), "invoice");
+
+    let hr = storage.list_templates(&ListOptions::new().metadata("department", "hr")).await.unwrap();
+    assert_eq!(hr.templates.len(), 1);
+    assert_eq!(hr.templates[0].id.as_ref(), "letter");
+}
+
+#[tokio::test]
+async fn test_save_template_detects_concurrent_modification() {
+    let temp_dir = tempdir().unwrap();
+    let storage = FileStorage::new(temp_dir.path());
+    let id = TemplateId::from("invoice");
+
+    storage.save_template(&Template::new("invoice", "Invoice", "Hello", Schema::new())).await.unwrap();
+    let mut first = storage.get_template(&id).await.unwrap();
+    let mut second = storage.get_template(&id).await.unwrap();
+    assert_eq!(first.revision, 1);
+
+    first.content = "First".to_string();
+    storage.save_template(&first).await.unwrap();
+
+    // The second writer saw an older revision
+    second.content = "Second".to_string();
+    let err = storage.save_template(&second).await.unwrap_err();
+    assert!(matches!(err, papermake::PapermakeError::Conflict(_)));
+
+    let stored = storage.get_template(&id).await.unwrap();
+    assert_eq!(stored.content, "First");
+    assert_eq!(stored.revision, 2);
+
+    // Concurrent writers of the same revision: exactly one wins
+    let writes: Vec<_> = (0..8).map(|i| {
+        let storage = storage.clone();
+        let mut template = stored.clone();
+        template.content = format!("Writer {}", i);
+        tokio::spawn(async move { storage.save_template(&template).await })
+    }).collect();
+    let mut succeeded = 0;
+    for write in writes {
+        if write.await.unwrap().is_ok() {
+            succeeded += 1;
+        }
+    }
+    assert_eq!(succeeded, 1);
+    assert_eq!(storage.get_template(&id).await.unwrap().revision, 3);
+
+    // No temporary files are left behind
+    let dir = temp_dir.path().join("templates/invoice");
+    let leftovers = std::fs::read_dir(dir).unwrap()
+        .filter(|entry| entry.as_ref().unwrap().file_name().to_string_lossy().ends_with(".tmp"))
+        .count();
+    assert_eq!(leftovers, 0);
+}
+
+#[tokio::test]
+async fn test_search_templates_ranks_name_matches_first() {
+    let temp_dir = tempdir().unwrap();
+    let storage = FileStorage::new(temp_dir.path());
+
+    let invoice = Template::new("invoice", "Invoice", "= Rechnung", Schema::new());
+    let letter = Template::new("letter", "Letter", "Attached is your invoice.", Schema::new())
+        .with_description("Cover letter for invoices");
+    let report = Template::new("report", "Report", "= Quarterly report", Schema::new());
+    for template in [&invoice, &letter, &report] {
+        storage.save_template(template).await.unwrap();
+    }
+
+    let hits = storage.search_templates("INVOICE").await.unwrap();
+    let ids: Vec<&str> = hits.iter().map(|hit| hit.template.id.as_ref()).collect();
+    assert_eq!(ids, ["invoice", "letter"]);
+
+    // Every term must match somewhere
+    let hits = storage.search_templates("cover invoice").await.unwrap();
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].template.id.as_ref(), "letter");
+
+    assert!(storage.search_templates("payroll").await.unwrap().is_empty());
+    assert!(storage.search_templates("   ").await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_template_dependents() {
+    let temp_dir = tempdir().unwrap();
+    let storage = FileStorage::new(temp_dir.path());
+
+    let header = Template::new("header", "Header", "#let header = [Header]", Schema::new()).as_shared();
+    let letterhead = Template::new("letterhead", "Letterhead", "#import \"papermake:shared/header.typ\": header", Schema::new()).as_shared();
+    let invoice = Template::new("invoice", "Invoice", "#import \"papermake:shared/letterhead.typ\"", Schema::new());
+    let receipt = Template::new("receipt", "Receipt", "Hello", Schema::new())
+        .with_variant("de", "#import \"papermake:shared/header.typ\": header");
+    let plain = Template::new("plain", "Plain", "Hello", Schema::new());
+    for template in [&header, &letterhead, &invoice, &receipt, &plain] {
+        storage.save_template(template).await.unwrap();
+    }
+
+    let dependents = storage.get_dependents(&"header".into()).await.unwrap();
+    let ids: Vec<&str> = dependents.iter().map(|d| d.id.0.as_str()).collect();
+    assert_eq!(ids, ["letterhead", "receipt", "invoice"]);
+    assert_eq!(dependents[2].via, [TemplateId::from("letterhead")]);
+    assert!(storage.get_dependents(&"plain".into()).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_copy_template() {
+    let temp_dir = tempdir().unwrap();
+    let storage = FileStorage::new(temp_dir.path());
+    let id = TemplateId::from("invoice");
+    let copy_id = TemplateId::from("invoice-2025");
+
+    let template = Template::new("invoice", "Invoice", "Hello", Schema::new()).with_tag("finance");
+    storage.save_template(&template).await.unwrap();
+    papermake::lifecycle::publish_template(&storage, &id).await.unwrap();
+    storage.save_template_file(&id, "images/logo.png", b"png").await.unwrap();
+
+    let copy = storage.copy_template(&id, &copy_id).await.unwrap();
+    assert_eq!(copy.id, copy_id);
+    assert_eq!(copy.status, papermake::TemplateStatus::Draft);
+    assert_eq!(copy.metadata.get("forked_from").map(String::as_str), Some("invoice"));
+
+    let stored = storage.get_template(&copy_id).await.unwrap();
+    assert_eq!(stored.content, "Hello");
+    assert_eq!(stored.tags, vec!["finance".to_string()]);
+    assert_eq!(stored.revision, copy.revision);
+    assert_eq!(storage.get_template_file(&copy_id, "images/logo.png").await.unwrap(), b"png");
+    assert!(storage.get_published_template(&copy_id).await.is_err());
+    assert_eq!(storage.list_templates(&ListOptions::new()).await.unwrap().templates.len(), 2);
+
+    // The copy is independent of its source
+    storage.save_template_file(&copy_id, "images/logo.png", b"new").await.unwrap();
+    assert_eq!(storage.get_template_file(&id, "images/logo.png").await.unwrap(), b"png");
+
+    // Existing ids aren't overwritten
+    let err = storage.copy_template(&id, &copy_id).await.unwrap_err();
+    assert!(matches!(err, papermake::PapermakeError::Conflict(_)));
+    assert!(storage.copy_template(&"missing".into(), &"other".into()).await.is_err());
+}
+
+#[tokio::test]
+async fn test_purge_expired_artifacts() {
+    use papermake::lifecycle::{archive_template, publish_template};
+    use papermake::storage::{ArtifactKind, Namespace};
+    use papermake::{FileRenderHistory, RenderHistory, RenderRecord};
+
+    let temp_dir = tempdir().unwrap();
+    let storage = FileStorage::new(temp_dir.path());
+    let all = [ArtifactKind::RenderHistory, ArtifactKind::JobOutputs, ArtifactKind::TemplateVersions];
+
+    for id in ["live", "old"] {
+        let template = Template::new(id, id, "Hello", Schema::new());
+        storage.save_template(&template).await.unwrap();
+        publish_template(&storage, &id.into()).await.unwrap();
+    }
+    archive_template(&storage, &"old".into()).await.unwrap();
+
+    let template = storage.get_template(&"live".into()).await.unwrap();
+    let history = FileRenderHistory::new(temp_dir.path());
+    let record = RenderRecord::new("render-1", &template, &serde_json::json!({}));
+    history.record(&record, None).await.unwrap();
+    let tenant = FileRenderHistory::new(temp_dir.path()).for_namespace(&Namespace::new("acme").unwrap());
+    tenant.record(&RenderRecord::new("render-2", &template, &serde_json::json!({})), None).await.unwrap();
+    std::fs::create_dir_all(temp_dir.path().join("outputs/job-1")).unwrap();
+    std::fs::write(temp_dir.path().join("outputs/job-1/0.pdf"), b"%PDF").unwrap();
+
+    // Nothing is older than an hour ago
+    let hour_ago = time::OffsetDateTime::now_utc() - time::Duration::hours(1);
+    assert_eq!(storage.purge(hour_ago, &all).await.unwrap().total(), 0);
+
+    // Only the requested kinds are purged
+    let later = time::OffsetDateTime::now_utc() + time::Duration::minutes(1);
+    let report = storage.purge(later, &[ArtifactKind::JobOutputs]).await.unwrap();
+    assert_eq!(report.job_outputs, 1);
+    assert_eq!(report.render_records, 0);
+    assert!(!temp_dir.path().join("outputs/job-1").exists());
+
+    let report = storage.purge(later, &all).await.unwrap();
+    assert_eq!(report.render_records, 2);
+    assert_eq!(report.template_versions, 1);
+    assert!(history.get_record("render-1").await.is_err());
+    assert!(tenant.get_record("render-2").await.is_err());
+
+    // Live templates keep their published revision; archived ones keep their working copy
+    assert!(storage.get_published_template(&"live".into()).await.is_ok());
+    assert!(storage.get_published_template(&"old".into()).await.is_err());
+    assert!(storage.get_template(&"old".into()).await.is_ok());
+}
@@ -12,6 +12,10 @@ fn test_template_schema_validation() {
         required: true,
         description: Some("Customer name".to_string()),
         default: None,
+        placeholder: None,
+        widget: None,
+        order: None,
+        group: None,
     }).add_field(SchemaField {
         key: "age".to_string(),
         label: Some("Age".to_string()),
@@ -19,6 +23,10 @@ fn test_template_schema_validation() {
         required: false,
         description: Some("Customer age".to_string()),
         default: None,
+        placeholder: None,
+        widget: None,
+        order: None,
+        group: None,
     });
     
     // Create a template with the schema
@@ -51,4 +59,541 @@ fn test_template_schema_validation() {
     });
     
     assert!(template.validate_data(&invalid_type_data).is_err());
-}
\ No newline at end of file
+}
+#[test]
+fn test_schema_defaults_and_coercion() {
+    let schema = Schema::builder()
+        .field("name", FieldType::String)
+        .optional_with_default("currency", FieldType::String, json!("EUR"))
+        .optional("amount", FieldType::Number)
+        .optional("paid", FieldType::Boolean)
+        .build();
+
+    // Defaults fill in missing optional fields
+    let mut data = json!({ "name": "ACME" });
+    schema.apply_defaults(&mut data);
+    assert_eq!(data["currency"], "EUR");
+
+    // Explicit values are never overwritten by defaults
+    let mut data = json!({ "name": "ACME", "currency": "USD" });
+    schema.apply_defaults(&mut data);
+    assert_eq!(data["currency"], "USD");
+
+    // Coercion converts string payloads into declared types
+    let mut data = json!({ "name": 42, "amount": "19.5", "paid": "true" });
+    assert!(schema.validate(&data).is_err());
+    schema.coerce(&mut data);
+    assert_eq!(data, json!({ "name": "42", "amount": 19.5, "paid": true }));
+    assert!(schema.validate(&data).is_ok());
+}
+
+#[test]
+fn test_date_coercion_and_validation() {
+    let schema = Schema::builder()
+        .field("issued", FieldType::Date)
+        .optional("due", FieldType::Date)
+        .optional("paid_at", FieldType::Date)
+        .build();
+
+    // Date strings keep their format, unix timestamps become RFC 3339 strings
+    let mut data = json!({ "issued": " 2024-01-05 ", "due": "2024-02-01T09:30:00.000+02:00", "paid_at": 1704412800 });
+    schema.coerce(&mut data);
+    assert_eq!(
+        data,
+        json!({ "issued": "2024-01-05", "due": "2024-02-01T09:30:00+02:00", "paid_at": "2024-01-05T00:00:00Z" })
+    );
+    assert!(schema.validate(&data).is_ok());
+
+    // Strings that aren't dates are left alone by coercion and still validate
+    for unparsed in ["05/01/2024", "2024-13-01", "tomorrow"] {
+        let mut data = json!({ "issued": unparsed });
+        schema.coerce(&mut data);
+        assert_eq!(data["issued"], unparsed);
+        assert!(schema.validate(&data).is_ok());
+    }
+
+    let mut data = json!({ "issued": 20240105.5 });
+    schema.coerce(&mut data);
+    let err = schema.validate(&data).unwrap_err();
+    assert!(err.to_string().contains("must be a date string"), "{}", err);
+}
+
+#[test]
+fn test_form_data() {
+    let item = Schema::builder()
+        .field("name", FieldType::String)
+        .field("price", FieldType::Number)
+        .build();
+    let schema = Schema::builder()
+        .field("customer", FieldType::Object(Box::new(Schema::builder().field("name", FieldType::String).build())))
+        .field("items", FieldType::Array(Box::new(FieldType::Object(Box::new(item)))))
+        .optional("tags", FieldType::Array(Box::new(FieldType::String)))
+        .optional("express", FieldType::Boolean)
+        .optional("gift", FieldType::Boolean)
+        .optional("discount", FieldType::Number)
+        .build();
+
+    let fields = [
+        ("customer.name", "Ada"),
+        ("items[1].name", "Ink"),
+        ("items[1].price", "4"),
+        ("items[0].name", "Pen"),
+        ("items[0].price", "1.5"),
+        ("tags[]", "urgent"),
+        ("express", "on"),
+        ("discount", ""),
+    ];
+    let data = papermake::form_data(&schema, fields).unwrap();
+    assert_eq!(data, json!({
+        "customer": { "name": "Ada" },
+        "items": [{ "name": "Pen", "price": 1.5 }, { "name": "Ink", "price": 4 }],
+        "tags": ["urgent"],
+        "express": true,
+        "gift": false,
+    }));
+    assert!(schema.validate(&data).is_ok());
+
+    // Repeated fields collect into arrays
+    let data = papermake::form_data(&schema, [("tags", "a"), ("tags", "b")]).unwrap();
+    assert_eq!(data["tags"], json!(["a", "b"]));
+
+    assert!(papermake::form_data(&schema, [("items[x].name", "Pen")]).is_err());
+    assert!(papermake::form_data(&schema, [("customer", "Ada"), ("customer.name", "Ada")]).is_err());
+    assert!(papermake::form_data(&schema, [("items[99999999].name", "Pen")]).is_err());
+}
+
+#[test]
+fn test_template_package_roundtrip() {
+    use std::collections::BTreeMap;
+
+    let template = Template::builder("invoice")
+        .name("Invoice Template")
+        .description("Monthly invoice")
+        .content("#image(\"logo.png\")\nInvoice")
+        .schema(Schema::builder().field("customer", FieldType::String).build())
+        .example("basic", json!({ "customer": "ACME" }))
+        .build()
+        .unwrap();
+
+    let mut files = BTreeMap::new();
+    files.insert("logo.png".to_string(), vec![0x89, b'P', b'N', b'G']);
+    files.insert("partials/footer.typ".to_string(), b"Footer".to_vec());
+
+    let archive = template.export_package(&files).unwrap();
+    let package = Template::import_package(&archive).unwrap();
+
+    assert_eq!(package.template.id, template.id);
+    assert_eq!(package.template.name, template.name);
+    assert_eq!(package.template.description, template.description);
+    assert_eq!(package.template.content, template.content);
+    assert_eq!(package.template.schema, template.schema);
+    assert_eq!(package.template.examples, template.examples);
+    assert_eq!(package.files, files);
+
+    assert!(Template::import_package(b"not an archive").is_err());
+}
+
+#[test]
+fn test_template_lint() {
+    use papermake::lint::LintKind;
+
+    let schema = Schema::builder()
+        .field("customer", FieldType::Object(Box::new(
+            Schema::builder()
+                .field("name", FieldType::String)
+                .optional("vat_id", FieldType::String)
+                .build(),
+        )))
+        .field("items", FieldType::Array(Box::new(FieldType::String)))
+        .optional("notes", FieldType::String)
+        .build();
+
+    let template = Template::new(
+        "invoice",
+        "Invoice",
+        "#let data = json.decode(sys.inputs.data)\n\
+         Customer: #data.customer.name\n\
+         Items: #data.items.len()\n\
+         Total: #data.at(\"total\")",
+        schema,
+    );
+
+    let report = template.lint();
+
+    let undeclared: Vec<_> = report.of_kind(LintKind::UndeclaredField).map(|i| i.path.as_str()).collect();
+    assert_eq!(undeclared, vec!["total"]);
+
+    let unused: Vec<_> = report.of_kind(LintKind::UnusedField).map(|i| i.path.as_str()).collect();
+    assert_eq!(unused, vec!["customer.vat_id", "notes"]);
+}
+
+#[test]
+fn test_generate_sample_data() {
+    let schema = Schema::builder()
+        .field("customer_name", FieldType::String)
+        .field("email", FieldType::String)
+        .field("invoice_date", FieldType::Date)
+        .field("paid", FieldType::Boolean)
+        .optional_with_default("currency", FieldType::String, json!("EUR"))
+        .field("items", FieldType::Array(Box::new(FieldType::Object(Box::new(
+            Schema::builder()
+                .field("description", FieldType::String)
+                .field("amount", FieldType::Number)
+                .build(),
+        )))))
+        .build();
+
+    let data = schema.generate_sample_data(42);
+    assert!(schema.validate(&data).is_ok(), "sample data should match its schema: {}", data);
+    assert_eq!(data, schema.generate_sample_data(42), "same seed should give same data");
+    assert_ne!(data, schema.generate_sample_data(7));
+
+    assert_eq!(data["currency"], json!("EUR"));
+    assert!(data["email"].as_str().unwrap().ends_with("@example.com"));
+    assert_eq!(data["items"].as_array().unwrap().len(), 3);
+
+    let options = papermake::SampleOptions { array_len: 5, ..Default::default() };
+    let data = schema.generate_sample_data_with(42, &options);
+    assert_eq!(data["items"].as_array().unwrap().len(), 5);
+}
+
+#[test]
+fn test_template_locale_variants() {
+    let template = Template::new("letter", "Letter", "Dear", Schema::new())
+        .with_variant("de", "Sehr geehrte")
+        .with_variant("fr-CA", "Cher");
+
+    assert_eq!(template.variant("de-DE").content, "Sehr geehrte");
+    assert_eq!(template.variant("de_AT").content, "Sehr geehrte");
+    assert_eq!(template.variant("fr-FR").content, "Cher");
+    assert_eq!(template.variant("en-US").content, "Dear");
+    assert_eq!(template.content_for(None), "Dear");
+}
+
+#[test]
+fn test_render_with_locale_helpers() {
+    let content = r#"#import "papermake:locale.typ": format-currency, format-date, format-number
+#let data = json.decode(sys.inputs.data)
+#assert.eq(sys.inputs.locale, "de-DE")
+#assert.eq(format-number(data.total), "1.234,50")
+#assert.eq(format-currency(data.total, "EUR"), "1.234,50" + "\u{a0}" + "€")
+#assert.eq(format-date(data.date), "31.12.2024")
+Gesamt: #format-currency(data.total, "EUR")"#;
+    let template = Template::new("invoice", "Invoice", "Total", Schema::new())
+        .with_variant("de", content);
+
+    let options = papermake::RenderOptions {
+        locale: Some("de-DE".to_string()),
+        ..Default::default()
+    };
+    let result = template
+        .render_with_options(&json!({ "total": 1234.5, "date": "2024-12-31" }), options)
+        .unwrap();
+    assert!(result.pdf.is_some(), "render failed: {:?}", result.errors);
+}
+
+#[test]
+fn test_conditional_sections() {
+    use papermake::lint::LintKind;
+
+    let content = r#"#import "papermake:sections.typ": section, enabled
+#let data = json.decode(sys.inputs.data)
+#assert.eq(enabled("discount"), data.expect_discount)
+#assert.eq(enabled("notes.internal"), false)
+#section("discount")[You saved 10%]"#;
+    let schema = Schema::builder()
+        .field("expect_discount", FieldType::Boolean)
+        .optional("discount", FieldType::Section)
+        .optional("notes", FieldType::Object(Box::new(
+            Schema::builder().optional("internal", FieldType::Section).build(),
+        )))
+        .build();
+    let template = Template::new("offer", "Offer", content, schema);
+
+    for on in [true, false] {
+        let result = template.render(&json!({ "expect_discount": on, "discount": on })).unwrap();
+        assert!(result.pdf.is_some(), "render failed: {:?}", result.errors);
+    }
+    // Sections coerce like booleans and default to off
+    let result = template
+        .render_with_options(
+            &json!({ "expect_discount": true, "discount": "yes" }),
+            papermake::RenderOptions { coerce_data: true, ..Default::default() },
+        )
+        .unwrap();
+    assert!(result.pdf.is_some(), "render failed: {:?}", result.errors);
+    assert!(template.render(&json!({ "expect_discount": false })).unwrap().pdf.is_some());
+    assert!(template.render(&json!({ "expect_discount": false, "discount": "no" })).is_err());
+
+    // Naming a section in the helpers uses it
+    assert_eq!(template.lint().of_kind(LintKind::UnusedField).count(), 0);
+
+    // Sections the schema doesn't declare fail before compiling
+    let undeclared = Template::new(
+        "offer",
+        "Offer",
+        format!("{}\n#section(\"terms\")[Terms]", content),
+        template.schema.clone(),
+    );
+    let result = undeclared.render(&json!({ "expect_discount": false })).unwrap();
+    assert!(result.pdf.is_none());
+    assert!(result.errors.iter().any(|e| e.message.contains("Section 'terms'")), "{:?}", result.errors);
+    assert!(undeclared.lint().of_kind(LintKind::UndeclaredField).any(|i| i.path == "terms"));
+}
+
+#[test]
+fn test_environment_settings() {
+    use papermake::environment::DEFAULT_ENVIRONMENT;
+    use papermake::EnvironmentConfig;
+
+    let content = r#"#let env = sys.inputs.environment
+#let data = json.decode(sys.inputs.data)
+#assert.eq(env.name, data.name)
+#assert.eq(env.watermark, data.watermark)
+#assert.eq(env.asset_base_url, "https://cdn.example.com")
+#assert.eq(env.at("sign", default: none), data.sign)
+Offer"#;
+    let staging = EnvironmentConfig {
+        watermark: Some("DRAFT".to_string()),
+        settings: serde_json::from_value(json!({ "sign": false })).unwrap(),
+        ..Default::default()
+    };
+    let template = Template::new("offer", "Offer", content, Schema::new())
+        .with_environment(DEFAULT_ENVIRONMENT, EnvironmentConfig {
+            asset_base_url: Some("https://cdn.example.com".to_string()),
+            settings: serde_json::from_value(json!({ "sign": true })).unwrap(),
+            ..Default::default()
+        })
+        .with_environment("staging", staging);
+
+    assert_eq!(template.environment(Some("staging")).watermark.as_deref(), Some("DRAFT"));
+    assert_eq!(template.environment(Some("staging")).asset_base_url.as_deref(), Some("https://cdn.example.com"));
+
+    let cases = [
+        (Some("staging"), json!({ "name": "staging", "watermark": "DRAFT", "sign": false })),
+        // Environments without an entry get the defaults
+        (Some("prod"), json!({ "name": "prod", "watermark": null, "sign": true })),
+        (None, json!({ "name": null, "watermark": null, "sign": true })),
+    ];
+    for (environment, expected) in cases {
+        let options = papermake::RenderOptions { environment: environment.map(str::to_string), ..Default::default() };
+        let result = template.render_with_options(&expected, options).unwrap();
+        assert!(result.pdf.is_some(), "{:?}: {:?}", environment, result.errors);
+    }
+}
+
+#[test]
+fn test_schema_compatibility() {
+    use papermake::SchemaChangeKind;
+
+    let old = Schema::builder()
+        .required("name", FieldType::String)
+        .optional("note", FieldType::String)
+        .optional("total", FieldType::Number)
+        .build();
+
+    let compatible = Schema::builder()
+        .optional("name", FieldType::String)
+        .optional("note", FieldType::String)
+        .optional("total", FieldType::Number)
+        .optional("currency", FieldType::String)
+        .optional_with_default("paid", FieldType::Boolean, json!(false))
+        .build();
+    let report = Schema::compatibility(&old, &compatible);
+    assert!(report.is_compatible(), "{:?}", report);
+    assert_eq!(report.changes.len(), 3);
+
+    let breaking = Schema::builder()
+        .required("name", FieldType::String)
+        .required("note", FieldType::String)
+        .optional("total", FieldType::String)
+        .required("currency", FieldType::String)
+        .build();
+    let report = Schema::compatibility(&old, &breaking);
+    let kinds: Vec<SchemaChangeKind> = report.breaking_changes().map(|change| change.kind).collect();
+    assert_eq!(
+        kinds,
+        [SchemaChangeKind::BecameRequired, SchemaChangeKind::TypeChanged, SchemaChangeKind::FieldAdded]
+    );
+    assert!(!Schema::compatibility(&old, &Schema::new()).is_compatible());
+}
+
+#[cfg(feature = "barcodes")]
+#[test]
+fn test_scaffold_from_schema() {
+    use papermake::{render_pdf, BarcodeKind, ScaffoldStyle, SchemaBuilder};
+
+    let schema = SchemaBuilder::new()
+        .field("title", FieldType::String)
+        .field("invoice_date", FieldType::Date)
+        .field("paid", FieldType::Boolean)
+        .optional("notes", FieldType::String)
+        .field("payment", FieldType::Barcode(BarcodeKind::Qr))
+        .field("customer", FieldType::Object(Box::new(
+            SchemaBuilder::new()
+                .field("name", FieldType::String)
+                .field("email", FieldType::String)
+                .build(),
+        )))
+        .field("items", FieldType::Array(Box::new(FieldType::Object(Box::new(
+            SchemaBuilder::new()
+                .field("description", FieldType::String)
+                .field("amount", FieldType::Number)
+                .field("sku", FieldType::Barcode(BarcodeKind::Code128))
+                .build(),
+        )))))
+        .build();
+
+    for style in [ScaffoldStyle::Document, ScaffoldStyle::Form, ScaffoldStyle::Report] {
+        let content = Template::scaffold(&schema, style);
+        assert!(content.contains("Invoice date"), "labels come from keys:\n{}", content);
+        assert!(content.contains("\"papermake:barcodes/items.\" + str(i) + \".sku.svg\""));
+
+        let template = Template::new("invoice", "Invoice", content, schema.clone());
+        let report = template.lint();
+        assert!(report.is_clean(), "{:?} scaffold should use every field: {:?}", style, report.issues);
+
+        let data = schema.generate_sample_data(3);
+        let result = render_pdf(&template, &data, None).unwrap();
+        assert!(result.pdf.is_some(), "{:?} scaffold failed to render: {:?}", style, result.errors);
+
+        // Optional fields may be missing
+        let mut data = data;
+        data.as_object_mut().unwrap().remove("notes");
+        assert!(render_pdf(&template, &data, None).unwrap().pdf.is_some());
+    }
+}
+
+#[test]
+fn test_analyze_template() {
+    use papermake::analyze::Severity;
+    use papermake::render::RenderOptions;
+    use papermake::{analyze_template, SchemaBuilder};
+
+    let schema = SchemaBuilder::new()
+        .field("customer", FieldType::Object(Box::new(
+            SchemaBuilder::new()
+                .field("name", FieldType::String)
+                .optional("email", FieldType::String)
+                .build(),
+        )))
+        .build();
+    let content = "#let data = json.decode(sys.inputs.data)\n#data.customer.name\n#data.customer.phone\n#data.customer.";
+    let template = Template::new("letter", "Letter", content, schema);
+
+    let analysis = analyze_template(&template, Some(content.len()), None, &RenderOptions::default());
+    let labels: Vec<_> = analysis.completions.iter().map(|c| c.label.as_str()).collect();
+    assert_eq!(labels, ["name", "email"]);
+    assert_eq!(analysis.completions[1].detail, "string");
+    assert!(!analysis.completions[1].required);
+
+    // The undeclared field is a lint warning on the third line
+    let warning = analysis
+        .diagnostics
+        .iter()
+        .find(|d| d.severity == Severity::Warning && d.message.contains("customer.phone"))
+        .expect("undeclared field is reported");
+    assert_eq!(warning.start_position.line, 2);
+    assert_eq!(&content[warning.start..warning.end], "data.customer.phone");
+
+    // Hovering over `name` describes the field
+    let cursor = content.find("name").unwrap() + 1;
+    let hover = analyze_template(&template, Some(cursor), None, &RenderOptions::default()).hover.unwrap();
+    assert_eq!(hover.path, "customer.name");
+    assert!(hover.contents.contains("string"));
+
+    // Keys are completed inside `.at("`
+    let content = "#let data = json.decode(sys.inputs.data)\n#data.at(\"customer\").at(\"em";
+    let template = Template::new("letter", "Letter", content, template.schema.clone());
+    let analysis = analyze_template(&template, Some(content.len()), None, &RenderOptions::default());
+    let labels: Vec<_> = analysis.completions.iter().map(|c| c.label.as_str()).collect();
+    assert_eq!(labels, ["email"]);
+    assert_eq!(&content[analysis.completions[0].start..analysis.completions[0].end], "em");
+}
+
+#[test]
+fn test_three_way_merge() {
+    use papermake::line_diff::merge;
+
+    let base = "= Invoice\nTo: #data.name\n\nTotal: #data.total\n";
+
+    // Edits of separate lines combine
+    let ours = "= Invoice\nTo: #data.customer\n\nTotal: #data.total\n";
+    let theirs = "= Invoice\nTo: #data.name\n\nTotal: #data.total EUR\nThank you!\n";
+    let merged = merge(base, ours, theirs);
+    assert!(merged.is_clean());
+    assert_eq!(merged.content, "= Invoice\nTo: #data.customer\n\nTotal: #data.total EUR\nThank you!\n");
+
+    // Identical edits aren't conflicts
+    assert!(merge(base, ours, ours).is_clean());
+    assert_eq!(merge(base, ours, ours).content, ours);
+
+    // Different edits of the same line are
+    let theirs = "= Invoice\nTo: #data.recipient\n\nTotal: #data.total\n";
+    let merged = merge(base, ours, theirs);
+    assert_eq!(merged.conflicts.len(), 1);
+    let conflict = &merged.conflicts[0];
+    assert_eq!(conflict.line, 2);
+    assert_eq!(conflict.base, "To: #data.name\n");
+    assert_eq!(conflict.ours, "To: #data.customer\n");
+    assert_eq!(conflict.theirs, "To: #data.recipient\n");
+    assert_eq!(
+        merged.content,
+        "= Invoice\n<<<<<<< ours\nTo: #data.customer\n=======\nTo: #data.recipient\n>>>>>>> theirs\n\nTotal: #data.total\n"
+    );
+}
+
+#[test]
+fn test_schema_field_form_hints() {
+    use papermake::Widget;
+
+    let schema = Schema::builder()
+        .add(
+            SchemaField::new("notes", FieldType::String)
+                .with_label("Notes")
+                .with_placeholder("Anything else?")
+                .with_widget(Widget::Textarea)
+                .with_order(2)
+                .with_group("Extras"),
+        )
+        .add(SchemaField::new("currency", FieldType::String).with_widget(Widget::Select {
+            options: vec!["EUR".to_string(), "USD".to_string()],
+        }))
+        .field("total", FieldType::Number)
+        .build();
+
+    let json = serde_json::to_value(&schema).unwrap();
+    assert_eq!(json["fields"][0]["widget"], json!({"type": "textarea"}));
+    assert_eq!(json["fields"][1]["widget"], json!({"type": "select", "options": ["EUR", "USD"]}));
+    assert_eq!(json["fields"][0]["group"], "Extras");
+    // Fields without hints serialize as before
+    assert!(json["fields"][2].get("widget").is_none());
+    assert_eq!(serde_json::from_value::<Schema>(json).unwrap(), schema);
+
+    // Schemas stored before hints existed still load
+    let old = json!({"fields": [{"key": "name", "label": null, "field_type": "string", "required": true, "description": null}]});
+    let schema: Schema = serde_json::from_value(old).unwrap();
+    assert_eq!(schema.fields[0].widget, None);
+}
+
+#[test]
+fn test_check_template() {
+    use papermake::SchemaBuilder;
+
+    let schema = SchemaBuilder::new()
+        .field("customer", FieldType::String)
+        .optional("notes", FieldType::String)
+        .field("items", FieldType::Array(Box::new(FieldType::Number)))
+        .build();
+    let content = "#let data = json.decode(sys.inputs.data)\nFor #data.customer: #data.notes\n#for item in data.items [#item]";
+    let report = Template::new("invoice", "Invoice", content, schema.clone()).check().unwrap();
+    assert!(report.ok);
+    assert!(report.errors.is_empty());
+    assert_eq!(report.pages, 1);
+
+    let broken = Template::new("invoice", "Invoice", "#let data = json.decode(sys.inputs.data)\n#data.missing", schema);
+    let report = broken.check().unwrap();
+    assert!(!report.ok);
+    assert_eq!(report.pages, 0);
+    assert!(report.errors[0].message.contains("missing"));
+}
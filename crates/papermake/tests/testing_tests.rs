@@ -0,0 +1,68 @@
+use papermake::testing::{assert_renders, render_example, run_examples};
+use papermake::{schema, Template};
+use serde_json::json;
+
+fn greeting_template() -> Template {
+    Template::builder("greeting")
+        .name("Greeting")
+        .content("#let data = json.decode(sys.inputs.data)\nHello #data.name!")
+        .schema(schema! { name: String })
+        .example("basic", json!({ "name": "World" }))
+        .build()
+        .unwrap()
+}
+
+#[test]
+fn test_assert_renders_example() {
+    let template = greeting_template();
+
+    assert_renders(&template, "basic")
+        .assert_page_count(1)
+        .assert_contains_text("World")
+        .assert_page_contains_text(1, "Hello");
+}
+
+#[test]
+fn test_golden_files() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let golden = temp_dir.path().join("golden/greeting-basic.txt");
+    let rendered = render_example(&greeting_template(), "basic").unwrap();
+
+    // Missing golden files fail until written
+    assert!(rendered.compare_golden(&golden, false).is_err());
+    assert_eq!(rendered.compare_golden(&golden, true).unwrap(), None);
+    assert_eq!(std::fs::read_to_string(&golden).unwrap(), rendered.golden_text());
+    assert!(rendered.golden_text().starts_with("--- page 1 ---\nHello"));
+    rendered.assert_matches_golden(&golden);
+
+    std::fs::write(&golden, "--- page 1 ---\nHello Moon!\n").unwrap();
+    let diff = rendered.compare_golden(&golden, false).unwrap().unwrap();
+    assert!(diff.contains("-Hello Moon!") && diff.contains("+Hello"), "{}", diff);
+    let mismatch = std::panic::catch_unwind(|| {
+        rendered.assert_matches_golden(&golden);
+    });
+    assert!(mismatch.is_err());
+}
+
+#[test]
+fn test_unknown_example_is_an_error() {
+    let template = greeting_template();
+    assert!(render_example(&template, "missing").is_err());
+}
+
+#[test]
+fn test_run_examples_reports_failures() {
+    let template = greeting_template()
+        .with_example("invalid", json!({ "age": 30 }));
+
+    let reports = run_examples(&template);
+    assert_eq!(reports.len(), 2);
+
+    let basic = reports.iter().find(|r| r.name == "basic").unwrap();
+    assert!(basic.passed);
+    assert_eq!(basic.page_count, Some(1));
+
+    let invalid = reports.iter().find(|r| r.name == "invalid").unwrap();
+    assert!(!invalid.passed);
+    assert!(!invalid.errors.is_empty());
+}